@@ -0,0 +1,164 @@
+// the interpreter's speed on a few workloads, timed by criterion
+//
+//   cargo bench                                  every workload
+//   cargo bench -- copy                          workloads named with copy
+//   cargo bench --features match-dispatch        the other dispatch
+//
+// each workload is a short program assembled by hand. instructions run
+// through run::run_for, a block of cycles at a time, with the devices
+// ticked and interrupts taken after each but none of the trace or
+// monitor around them.
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+
+use e6502r::cpu::{reset_cpu, Cpu, RESET_VECTOR_HIBYTE, RESET_VECTOR_LOBYTE};
+use e6502r::memory::{Device, Memory};
+use e6502r::run;
+use e6502r::run::StopConditions;
+
+const CHUNK: i64 = 16384;           // cycles timed at a time
+
+const LOAD_ADDR: u16 = 0x0400;
+const STORM_ADDR: u16 = 0xd000;
+const STORM_PERIOD: u32 = 100;      // cycles between interrupts
+
+struct Workload {
+    name: &'static str,
+    setup: fn(&mut Memory),
+}
+
+const WORKLOADS: [Workload; 3] = [
+    Workload { name: "alu", setup: alu_loop },
+    Workload { name: "copy", setup: memory_copy },
+    Workload { name: "irq-storm", setup: interrupt_storm },
+];
+
+// arithmetic and logic on the accumulator, counting round with x
+fn alu_loop(mem: &mut Memory) {
+    load(mem, &[
+        0xa2, 0x00,             // 0400  LDX #$00
+        0x8a,                   // 0402  TXA
+        0x18,                   // 0403  CLC
+        0x69, 0x37,             // 0404  ADC #$37
+        0x49, 0xa5,             // 0406  EOR #$A5
+        0x0a,                   // 0408  ASL A
+        0x29, 0x7f,             // 0409  AND #$7F
+        0x09, 0x01,             // 040b  ORA #$01
+        0x6a,                   // 040d  ROR A
+        0xc9, 0x40,             // 040e  CMP #$40
+        0xe8,                   // 0410  INX
+        0xd0, 0xef,             // 0411  BNE $0402
+        0x4c, 0x00, 0x04,       // 0413  JMP $0400
+    ]);
+}
+
+// copy $2000-$2fff to $4000-$4fff through pointers, over and over
+fn memory_copy(mem: &mut Memory) {
+    load(mem, &[
+        0xa0, 0x00,             // 0400  LDY #$00
+        0xb1, 0x10,             // 0402  LDA ($10),Y
+        0x91, 0x12,             // 0404  STA ($12),Y
+        0xc8,                   // 0406  INY
+        0xd0, 0xf9,             // 0407  BNE $0402
+        0xe6, 0x11,             // 0409  INC $11
+        0xe6, 0x13,             // 040b  INC $13
+        0xa5, 0x11,             // 040d  LDA $11
+        0xc9, 0x30,             // 040f  CMP #$30
+        0xd0, 0xef,             // 0411  BNE $0402
+        0xa9, 0x20,             // 0413  LDA #$20
+        0x85, 0x11,             // 0415  STA $11
+        0xa9, 0x40,             // 0417  LDA #$40
+        0x85, 0x13,             // 0419  STA $13
+        0x4c, 0x00, 0x04,       // 041b  JMP $0400
+    ]);
+    mem.mem[0x10..0x14].copy_from_slice(&[0x00, 0x20, 0x00, 0x40]);
+}
+
+// a device interrupting every STORM_PERIOD cycles, each interrupt
+// acknowledged by a handler that reads it
+fn interrupt_storm(mem: &mut Memory) {
+    load(mem, &[
+        0x58,                   // 0400  CLI
+        0xe8,                   // 0401  INX
+        0xc8,                   // 0402  INY
+        0x4c, 0x01, 0x04,       // 0403  JMP $0401
+    ]);
+    mem.mem[0x0500..0x0506].copy_from_slice(&[
+        0x48,                   // 0500  PHA
+        0xad, 0x00, 0xd0,       // 0501  LDA $D000
+        0x68,                   // 0504  PLA
+        0x40,                   // 0505  RTI
+    ]);
+    mem.mem[0xfffe] = 0x00;
+    mem.mem[0xffff] = 0x05;
+    let storm = Storm { elapsed: 0, pending: false };
+    if let Err(message) = mem.attach(STORM_ADDR, 1, Box::new(storm)) {
+        panic!("{}", message);
+    }
+}
+
+// raises an irq every STORM_PERIOD cycles, held until it is read
+struct Storm {
+    elapsed: u32,
+    pending: bool,
+}
+
+impl Device for Storm {
+    fn read(&mut self, _offset: u16) -> u8 {
+        self.pending = false;
+        0
+    }
+
+    fn write(&mut self, _offset: u16, _value: u8) {}
+
+    fn tick(&mut self, cycles: u32) {
+        self.elapsed += cycles;
+        if self.elapsed >= STORM_PERIOD {
+            self.elapsed -= STORM_PERIOD;
+            self.pending = true;
+        }
+    }
+
+    fn irq(&self) -> bool {
+        self.pending
+    }
+
+    fn raises_irqs(&self) -> bool {
+        true
+    }
+}
+
+// put a program at LOAD_ADDR and point the reset vector at it
+fn load(mem: &mut Memory, program: &[u8]) {
+    let start = LOAD_ADDR as usize;
+    mem.mem[start..start + program.len()].copy_from_slice(program);
+    mem.mem[RESET_VECTOR_LOBYTE] = (LOAD_ADDR & 0xff) as u8;
+    mem.mem[RESET_VECTOR_HIBYTE] = (LOAD_ADDR >> 8) as u8;
+}
+
+// each workload timed a block of CHUNK cycles at a time, so the
+// throughput is in emulated cycles a second
+fn interpreter(c: &mut Criterion) {
+    let mut group = c.benchmark_group("interpreter");
+    group.throughput(Throughput::Elements(CHUNK as u64));
+    for workload in &WORKLOADS {
+        let mut cpu = Cpu::new();
+        let mut mem = Memory::new();
+        (workload.setup)(&mut mem);
+        reset_cpu(&mut cpu, &mem);
+        let stops = StopConditions::default();
+        let mut leftover = 0;
+        group.bench_function(workload.name, |b| b.iter(|| {
+            let block = run::run_for(&mut cpu, &mut mem, &stops, CHUNK + leftover);
+            if let Some(stop) = block.stop {
+                panic!("{}", stop);
+            }
+            leftover = block.leftover;
+            block.executed
+        }));
+    }
+    group.finish();
+}
+
+criterion_group!(benches, interpreter);
+criterion_main!(benches);
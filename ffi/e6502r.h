@@ -0,0 +1,214 @@
+/* e6502r: an embeddable 6502 core. generated by cbindgen from src/lib.rs */
+
+#ifndef E6502R_H
+#define E6502R_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+#define E6502R_OK 0
+
+#define E6502R_ERROR_ARGUMENT -1
+
+#define E6502R_ERROR_RANGE -2
+
+#define E6502R_ERROR_UNIMPLEMENTED -3
+
+#define E6502R_CPU_6502 0
+
+#define E6502R_CPU_65C02 1
+
+#define E6502R_CPU_2A03 2
+
+typedef struct e6502r_machine e6502r_machine;
+
+typedef struct e6502r_registers {
+  uint16_t pc;
+  uint8_t a;
+  uint8_t x;
+  uint8_t y;
+  uint8_t sp;
+  uint8_t p;
+  uint64_t cycles;
+} e6502r_registers;
+
+/**
+ * called for a read or a write at offset from the base it was mapped at
+ */
+typedef uint8_t (*e6502r_read_callback)(void *user, uint16_t offset);
+
+typedef void (*e6502r_write_callback)(void *user, uint16_t offset, uint8_t value);
+
+/**
+ * called after each instruction with the cycles it took
+ */
+typedef void (*e6502r_tick_callback)(void *user, uint32_t cycles);
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * a new machine with zeroed memory, or null for an unknown cpu; the cpu
+ * starts once e6502r_reset has read the reset vector
+ */
+e6502r_machine *e6502r_create(int32_t cpu);
+
+/**
+ * free a machine
+ *
+ * # Safety
+ * machine is null or came from e6502r_create and hasn't been destroyed
+ */
+void e6502r_destroy(e6502r_machine *machine);
+
+/**
+ * start from the reset vector; frames are counted from here
+ *
+ * # Safety
+ * machine is null or came from e6502r_create and hasn't been destroyed
+ */
+int32_t e6502r_reset(e6502r_machine *machine);
+
+/**
+ * run one instruction, then let mapped memory catch up and take any
+ * interrupt; returns the cycles it took
+ *
+ * # Safety
+ * machine is null or came from e6502r_create and hasn't been destroyed
+ */
+int32_t e6502r_step(e6502r_machine *machine);
+
+/**
+ * run instructions for a budget of cycles, each followed by mapped
+ * memory catching up and any interrupt, as e6502r_step does; a loop to
+ * itself runs on until the budget is spent. the last instruction can
+ * run past the budget: *leftover is set to the cycles left over,
+ * negative when it did, and adding it to the next budget keeps the
+ * blocks to their length. leftover may be null
+ *
+ * # Safety
+ * machine is null or came from e6502r_create, and leftover is null or writable
+ */
+int32_t e6502r_run_for(e6502r_machine *machine, int64_t cycles, int64_t *leftover);
+
+/**
+ * the cpu's clock and the frames a second e6502r_run_frame runs, e.g.
+ * 1789773 and 60.0988 for an ntsc nes, 1 MHz at 60 until set; frames
+ * are counted from here
+ *
+ * # Safety
+ * machine is null or came from e6502r_create and hasn't been destroyed
+ */
+int32_t e6502r_set_clock(e6502r_machine *machine, double clock_hz, double frame_rate);
+
+/**
+ * run one frame's cycles at the clock, as e6502r_run_for does; an
+ * instruction that runs past the end of a frame comes off the next, so
+ * frames take the clock's cycles exactly on average
+ *
+ * # Safety
+ * machine is null or came from e6502r_create and hasn't been destroyed
+ */
+int32_t e6502r_run_frame(e6502r_machine *machine);
+
+/**
+ * a data read, as the cpu makes it; callbacks see it
+ *
+ * # Safety
+ * machine is null or came from e6502r_create and hasn't been destroyed
+ */
+uint8_t e6502r_read(e6502r_machine *machine, uint16_t addr);
+
+/**
+ * a data write, as the cpu makes it; callbacks see it
+ *
+ * # Safety
+ * machine is null or came from e6502r_create and hasn't been destroyed
+ */
+void e6502r_write(e6502r_machine *machine, uint16_t addr, uint8_t value);
+
+/**
+ * copy len bytes into ram at addr, under any mappings
+ *
+ * # Safety
+ * machine is null or came from e6502r_create, and data points to len bytes
+ */
+int32_t e6502r_load(e6502r_machine *machine, uint16_t addr, const uint8_t *data, size_t len);
+
+/**
+ * copy the registers to out
+ *
+ * # Safety
+ * machine is null or came from e6502r_create, and out is null or writable
+ */
+int32_t e6502r_get_registers(const e6502r_machine *machine, e6502r_registers *out);
+
+/**
+ * set the registers from registers
+ *
+ * # Safety
+ * machine is null or came from e6502r_create, and registers is null or readable
+ */
+int32_t e6502r_set_registers(e6502r_machine *machine, const e6502r_registers *registers);
+
+/**
+ * hand the size addresses from base to the host: data reads and writes
+ * there call read and write with user and the offset from base. either
+ * callback may be null; reads then give $ff and writes are dropped.
+ *
+ * # Safety
+ * machine is null or came from e6502r_create; the callbacks and user
+ * stay valid until the machine is destroyed
+ */
+int32_t e6502r_register_callback(e6502r_machine *machine,
+                                 uint16_t base,
+                                 uint16_t size,
+                                 e6502r_read_callback read,
+                                 e6502r_write_callback write,
+                                 void *user);
+
+/**
+ * as e6502r_register_callback, and tick is called with user and the
+ * cycles each instruction took once it has run, before interrupts are
+ * taken, so the host's timers and sound keep time with the cpu. any of
+ * the callbacks may be null.
+ *
+ * # Safety
+ * machine is null or came from e6502r_create; the callbacks and user
+ * stay valid until the machine is destroyed
+ */
+int32_t e6502r_register_device(e6502r_machine *machine,
+                               uint16_t base,
+                               uint16_t size,
+                               e6502r_read_callback read,
+                               e6502r_write_callback write,
+                               e6502r_tick_callback tick,
+                               void *user);
+
+/**
+ * map an apple ii style keyboard at base, as --device keyboard@ADDR:
+ * the last key at base with bit 7 set until a read or write at
+ * base+$10 clears it; with uppercase letters come in upper case
+ *
+ * # Safety
+ * machine is null or came from e6502r_create
+ */
+int32_t e6502r_attach_keyboard(e6502r_machine *machine, uint16_t base, bool uppercase);
+
+/**
+ * queue a key, as an ascii code, for the machine's keyboard
+ *
+ * # Safety
+ * machine is null or came from e6502r_create
+ */
+int32_t e6502r_key(e6502r_machine *machine, uint8_t key);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* E6502R_H */
@@ -0,0 +1,55 @@
+/* prints a message through an output port mapped with a callback
+ *
+ *   cargo build --release
+ *   cc -I. examples/hello.c -Ltarget/release -le6502r -o hello
+ *   LD_LIBRARY_PATH=target/release ./hello
+ */
+
+#include <stdio.h>
+
+#include "e6502r.h"
+
+#define OUTPUT_PORT 0xf001
+
+static void output(void *user, uint16_t offset, uint8_t value) {
+    (void)offset;
+    fputc(value, (FILE *)user);
+}
+
+int main(void) {
+    static const uint8_t program[] = {
+        0xa2, 0x00,             /* 0400  LDX #$00     */
+        0xbd, 0x0f, 0x04,       /* 0402  LDA $040F,X  */
+        0xf0, 0x06,             /* 0405  BEQ $040D    */
+        0x8d, 0x01, 0xf0,       /* 0407  STA $F001    */
+        0xe8,                   /* 040a  INX          */
+        0xd0, 0xf5,             /* 040b  BNE $0402    */
+        0xdb,                   /* 040d  STP          */
+        0x00,
+        'h', 'e', 'l', 'l', 'o', '\n', 0,
+    };
+    static const uint8_t reset_vector[] = { 0x00, 0x04 };
+
+    e6502r_machine *machine = e6502r_create(E6502R_CPU_65C02);
+    if (machine == NULL) {
+        return 1;
+    }
+    e6502r_load(machine, 0x0400, program, sizeof program);
+    e6502r_load(machine, 0xfffc, reset_vector, sizeof reset_vector);
+    if (e6502r_register_callback(machine, OUTPUT_PORT, 1, NULL, output, stdout) != E6502R_OK) {
+        return 1;
+    }
+    e6502r_reset(machine);
+
+    e6502r_registers registers;
+    e6502r_get_registers(machine, &registers);
+    while (e6502r_read(machine, registers.pc) != 0xdb) {
+        if (e6502r_step(machine) < 0) {
+            return 1;
+        }
+        e6502r_get_registers(machine, &registers);
+    }
+    printf("stopped at $%04x after %llu cycles\n", registers.pc, (unsigned long long)registers.cycles);
+    e6502r_destroy(machine);
+    return 0;
+}
@@ -0,0 +1,337 @@
+// a c interface to the cpu and memory, for embedding the core
+//
+// build with: cargo build --release --manifest-path ffi/Cargo.toml
+//
+// that gives libe6502r.so (or .dylib or .dll) and libe6502r.a, declared
+// in e6502r.h, which cbindgen generates from this file. a machine is
+// made with e6502r_create and freed with e6502r_destroy; every other
+// function takes the pointer create returned. the host maps its own
+// hardware into memory with e6502r_register_callback, and reads and
+// writes there go to its callbacks; e6502r_register_device adds a tick
+// callback told the cycles each instruction takes. e6502r_attach_keyboard
+// maps an apple ii style keyboard, and e6502r_key queues a key for that
+// machine's keyboard alone.
+//
+// nothing here panics on bad input from c; errors are returned as
+// negative numbers.
+
+use std::ffi::c_void;
+use std::slice;
+
+use emulator::cpu::{reset_cpu, Cpu, CpuVariant};
+use emulator::devices::keyboard::{Keyboard, KEYBOARD_SIZE};
+use emulator::memory::{Device, Memory, MEMSIZE};
+use emulator::run::{run_for, run_frame, step, BlockRun, FrameClock, StopConditions, StopReason};
+
+// returned by the functions that can fail, as is a cycle count by e6502r_step
+pub const E6502R_OK: i32 = 0;
+pub const E6502R_ERROR_ARGUMENT: i32 = -1;       // a null pointer or a bad cpu
+pub const E6502R_ERROR_RANGE: i32 = -2;          // past the end of memory, or over another mapping
+pub const E6502R_ERROR_UNIMPLEMENTED: i32 = -3;  // the opcode at the pc isn't implemented
+
+// the cpus e6502r_create makes
+pub const E6502R_CPU_6502: i32 = 0;
+pub const E6502R_CPU_65C02: i32 = 1;
+pub const E6502R_CPU_2A03: i32 = 2;              // the NES cpu: a 6502 without decimal mode
+
+// unimplemented opcodes are reported rather than left to spin at the pc;
+// a loop to itself is the host's business
+const STOPS: StopConditions = StopConditions { trap_on_brk: false, strict_opcodes: true, stop_at: Vec::new(), smc: None };
+
+#[repr(C)]
+pub struct Registers {
+    pub pc: u16,
+    pub a: u8,
+    pub x: u8,
+    pub y: u8,
+    pub sp: u8,
+    pub p: u8,
+    pub cycles: u64,
+}
+
+/// called for a read or a write at offset from the base it was mapped at
+pub type ReadCallback = Option<extern "C" fn(user: *mut c_void, offset: u16) -> u8>;
+pub type WriteCallback = Option<extern "C" fn(user: *mut c_void, offset: u16, value: u8)>;
+/// called after each instruction with the cycles it took
+pub type TickCallback = Option<extern "C" fn(user: *mut c_void, cycles: u32)>;
+
+pub struct Machine {
+    cpu: Cpu,
+    mem: Memory,
+    clock: FrameClock,      // for e6502r_run_frame
+}
+
+// memory the host answers for
+struct Callbacks {
+    read: ReadCallback,
+    write: WriteCallback,
+    tick: TickCallback,
+    user: *mut c_void,
+}
+
+impl Device for Callbacks {
+    fn read(&mut self, offset: u16) -> u8 {
+        self.read.map_or(0xff, |read| read(self.user, offset))
+    }
+
+    fn write(&mut self, offset: u16, value: u8) {
+        if let Some(write) = self.write {
+            write(self.user, offset, value);
+        }
+    }
+
+    fn tick(&mut self, cycles: u32) {
+        if let Some(tick) = self.tick {
+            tick(self.user, cycles);
+        }
+    }
+}
+
+/// a new machine with zeroed memory, or null for an unknown cpu; the cpu
+/// starts once e6502r_reset has read the reset vector
+#[no_mangle]
+pub extern "C" fn e6502r_create(cpu: i32) -> *mut Machine {
+    let variant = match cpu {
+        E6502R_CPU_6502 => CpuVariant::Nmos6502,
+        E6502R_CPU_65C02 => CpuVariant::Cmos65c02,
+        E6502R_CPU_2A03 => CpuVariant::Ricoh2a03,
+        _ => return std::ptr::null_mut(),
+    };
+    let mut machine = Box::new(Machine { cpu: Cpu::new(), mem: Memory::new(), clock: FrameClock::default() });
+    machine.cpu.variant = variant;
+    Box::into_raw(machine)
+}
+
+/// free a machine
+///
+/// # Safety
+/// machine is null or came from e6502r_create and hasn't been destroyed
+#[no_mangle]
+pub unsafe extern "C" fn e6502r_destroy(machine: *mut Machine) {
+    if !machine.is_null() {
+        drop(Box::from_raw(machine));
+    }
+}
+
+/// start from the reset vector; frames are counted from here
+///
+/// # Safety
+/// machine is null or came from e6502r_create and hasn't been destroyed
+#[no_mangle]
+pub unsafe extern "C" fn e6502r_reset(machine: *mut Machine) -> i32 {
+    let Some(machine) = machine.as_mut() else { return E6502R_ERROR_ARGUMENT };
+    reset_cpu(&mut machine.cpu, &machine.mem);
+    machine.clock.restart(machine.cpu.cycles);
+    E6502R_OK
+}
+
+/// run one instruction, then let mapped memory catch up and take any
+/// interrupt; returns the cycles it took
+///
+/// # Safety
+/// machine is null or came from e6502r_create and hasn't been destroyed
+#[no_mangle]
+pub unsafe extern "C" fn e6502r_step(machine: *mut Machine) -> i32 {
+    let Some(Machine { cpu, mem, .. }) = machine.as_mut() else { return E6502R_ERROR_ARGUMENT };
+    let start = cpu.cycles;
+    match step(cpu, mem, &STOPS) {
+        Some(StopReason::Unimplemented(..)) => E6502R_ERROR_UNIMPLEMENTED,
+        _ => (cpu.cycles - start) as i32,
+    }
+}
+
+/// run instructions for a budget of cycles, each followed by mapped
+/// memory catching up and any interrupt, as e6502r_step does; a loop to
+/// itself runs on until the budget is spent. the last instruction can
+/// run past the budget: *leftover is set to the cycles left over,
+/// negative when it did, and adding it to the next budget keeps the
+/// blocks to their length. leftover may be null
+///
+/// # Safety
+/// machine is null or came from e6502r_create, and leftover is null or writable
+#[no_mangle]
+pub unsafe extern "C" fn e6502r_run_for(machine: *mut Machine, cycles: i64, leftover: *mut i64) -> i32 {
+    let Some(Machine { cpu, mem, .. }) = machine.as_mut() else { return E6502R_ERROR_ARGUMENT };
+    let block = run_for(cpu, mem, &STOPS, cycles);
+    let (result, left) = finish_block(cpu, mem, block);
+    if let Some(leftover) = leftover.as_mut() {
+        *leftover = left;
+    }
+    result
+}
+
+/// the cpu's clock and the frames a second e6502r_run_frame runs, e.g.
+/// 1789773 and 60.0988 for an ntsc nes, 1 MHz at 60 until set; frames
+/// are counted from here
+///
+/// # Safety
+/// machine is null or came from e6502r_create and hasn't been destroyed
+#[no_mangle]
+pub unsafe extern "C" fn e6502r_set_clock(machine: *mut Machine, clock_hz: f64, frame_rate: f64) -> i32 {
+    let Some(machine) = machine.as_mut() else { return E6502R_ERROR_ARGUMENT };
+    match FrameClock::new(clock_hz, frame_rate, machine.cpu.cycles) {
+        Ok(clock) => {
+            machine.clock = clock;
+            E6502R_OK
+        }
+        Err(_) => E6502R_ERROR_ARGUMENT,
+    }
+}
+
+/// run one frame's cycles at the clock, as e6502r_run_for does; an
+/// instruction that runs past the end of a frame comes off the next, so
+/// frames take the clock's cycles exactly on average
+///
+/// # Safety
+/// machine is null or came from e6502r_create and hasn't been destroyed
+#[no_mangle]
+pub unsafe extern "C" fn e6502r_run_frame(machine: *mut Machine) -> i32 {
+    let Some(Machine { cpu, mem, clock }) = machine.as_mut() else { return E6502R_ERROR_ARGUMENT };
+    let block = run_frame(cpu, mem, &STOPS, clock);
+    finish_block(cpu, mem, block).0
+}
+
+// carry on past loops to themselves until the block's budget is spent;
+// returns E6502R_OK or the error, and the cycles left over
+fn finish_block(cpu: &mut Cpu, mem: &mut Memory, mut block: BlockRun) -> (i32, i64) {
+    loop {
+        match block.stop {
+            Some(StopReason::Unimplemented(..)) => return (E6502R_ERROR_UNIMPLEMENTED, block.leftover),
+            Some(_) if block.leftover > 0 => block = run_for(cpu, mem, &STOPS, block.leftover),
+            _ => return (E6502R_OK, block.leftover),
+        }
+    }
+}
+
+/// a data read, as the cpu makes it; callbacks see it
+///
+/// # Safety
+/// machine is null or came from e6502r_create and hasn't been destroyed
+#[no_mangle]
+pub unsafe extern "C" fn e6502r_read(machine: *mut Machine, addr: u16) -> u8 {
+    match machine.as_mut() {
+        Some(machine) => machine.mem.read(addr),
+        None => 0xff,
+    }
+}
+
+/// a data write, as the cpu makes it; callbacks see it
+///
+/// # Safety
+/// machine is null or came from e6502r_create and hasn't been destroyed
+#[no_mangle]
+pub unsafe extern "C" fn e6502r_write(machine: *mut Machine, addr: u16, value: u8) {
+    if let Some(machine) = machine.as_mut() {
+        machine.mem.write(addr, value);
+    }
+}
+
+/// copy len bytes into ram at addr, under any mappings
+///
+/// # Safety
+/// machine is null or came from e6502r_create, and data points to len bytes
+#[no_mangle]
+pub unsafe extern "C" fn e6502r_load(machine: *mut Machine, addr: u16, data: *const u8, len: usize) -> i32 {
+    let Some(machine) = machine.as_mut() else { return E6502R_ERROR_ARGUMENT };
+    if data.is_null() && len > 0 {
+        return E6502R_ERROR_ARGUMENT;
+    }
+    let start = addr as usize;
+    if len > MEMSIZE - start {
+        return E6502R_ERROR_RANGE;
+    }
+    if len > 0 {
+        machine.mem.mem[start..start + len].copy_from_slice(slice::from_raw_parts(data, len));
+    }
+    E6502R_OK
+}
+
+/// copy the registers to out
+///
+/// # Safety
+/// machine is null or came from e6502r_create, and out is null or writable
+#[no_mangle]
+pub unsafe extern "C" fn e6502r_get_registers(machine: *const Machine, out: *mut Registers) -> i32 {
+    let (Some(machine), Some(out)) = (machine.as_ref(), out.as_mut()) else { return E6502R_ERROR_ARGUMENT };
+    let cpu = &machine.cpu;
+    *out = Registers { pc: cpu.pc, a: cpu.ac, x: cpu.xr, y: cpu.yr, sp: cpu.sp, p: cpu.status(), cycles: cpu.cycles };
+    E6502R_OK
+}
+
+/// set the registers from registers
+///
+/// # Safety
+/// machine is null or came from e6502r_create, and registers is null or readable
+#[no_mangle]
+pub unsafe extern "C" fn e6502r_set_registers(machine: *mut Machine, registers: *const Registers) -> i32 {
+    let (Some(machine), Some(registers)) = (machine.as_mut(), registers.as_ref()) else {
+        return E6502R_ERROR_ARGUMENT;
+    };
+    let cpu = &mut machine.cpu;
+    cpu.pc = registers.pc;
+    cpu.ac = registers.a;
+    cpu.xr = registers.x;
+    cpu.yr = registers.y;
+    cpu.sp = registers.sp;
+    cpu.set_status(registers.p);
+    cpu.cycles = registers.cycles;
+    E6502R_OK
+}
+
+/// hand the size addresses from base to the host: data reads and writes
+/// there call read and write with user and the offset from base. either
+/// callback may be null; reads then give $ff and writes are dropped.
+///
+/// # Safety
+/// machine is null or came from e6502r_create; the callbacks and user
+/// stay valid until the machine is destroyed
+#[no_mangle]
+pub unsafe extern "C" fn e6502r_register_callback(machine: *mut Machine, base: u16, size: u16,
+    read: ReadCallback, write: WriteCallback, user: *mut c_void) -> i32 {
+    e6502r_register_device(machine, base, size, read, write, None, user)
+}
+
+/// as e6502r_register_callback, and tick is called with user and the
+/// cycles each instruction took once it has run, before interrupts are
+/// taken, so the host's timers and sound keep time with the cpu. any of
+/// the callbacks may be null.
+///
+/// # Safety
+/// machine is null or came from e6502r_create; the callbacks and user
+/// stay valid until the machine is destroyed
+#[no_mangle]
+pub unsafe extern "C" fn e6502r_register_device(machine: *mut Machine, base: u16, size: u16,
+    read: ReadCallback, write: WriteCallback, tick: TickCallback, user: *mut c_void) -> i32 {
+    let Some(machine) = machine.as_mut() else { return E6502R_ERROR_ARGUMENT };
+    match machine.mem.attach(base, size, Box::new(Callbacks { read, write, tick, user })) {
+        Ok(()) => E6502R_OK,
+        Err(_) => E6502R_ERROR_RANGE,
+    }
+}
+
+/// map an apple ii style keyboard at base, as --device keyboard@ADDR:
+/// the last key at base with bit 7 set until a read or write at
+/// base+$10 clears it; with uppercase letters come in upper case
+///
+/// # Safety
+/// machine is null or came from e6502r_create
+#[no_mangle]
+pub unsafe extern "C" fn e6502r_attach_keyboard(machine: *mut Machine, base: u16, uppercase: bool) -> i32 {
+    let Some(machine) = machine.as_mut() else { return E6502R_ERROR_ARGUMENT };
+    let keyboard = Keyboard::new(machine.mem.inputs.clone(), uppercase);
+    match machine.mem.attach(base, KEYBOARD_SIZE, Box::new(keyboard)) {
+        Ok(()) => E6502R_OK,
+        Err(_) => E6502R_ERROR_RANGE,
+    }
+}
+
+/// queue a key, as an ascii code, for the machine's keyboard
+///
+/// # Safety
+/// machine is null or came from e6502r_create
+#[no_mangle]
+pub unsafe extern "C" fn e6502r_key(machine: *mut Machine, key: u8) -> i32 {
+    let Some(machine) = machine.as_mut() else { return E6502R_ERROR_ARGUMENT };
+    machine.mem.inputs.push_key(key);
+    E6502R_OK
+}
@@ -0,0 +1,45 @@
+// feeds arbitrary memory images to the cpu
+//
+// the input is copied into memory from $0000 (repeating to fill all
+// 64k), the first byte picks the cpu variant and the reset vector in the
+// image gives the start address. a bounded number of instructions are
+// run through the opcode handlers, checking after each one that:
+//
+//   - no handler panics (including arithmetic overflow on SP and PC)
+//   - the stack pointer and program counter wrap instead of escaping
+//     their ranges
+//   - the unused status bit stays set
+//
+// run with: cargo +nightly fuzz run cpu
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+use e6502r::cpu::{opcode_table, reset_cpu, Cpu, CpuVariant, STATUS_FLAGS_UNUSED};
+use e6502r::memory::{Memory, MEMSIZE};
+
+const MAX_INSTRUCTIONS: usize = 10_000;
+
+fuzz_target!(|data: &[u8]| {
+    if data.is_empty() {
+        return;
+    }
+
+    let mut mem = Memory::new();
+    for i in 0..MEMSIZE {
+        mem.mem[i] = data[i % data.len()];
+    }
+
+    let mut cpu = Cpu::new();
+    cpu.variant = if data[0] & 1 == 0 { CpuVariant::Nmos6502 } else { CpuVariant::Cmos65c02 };
+    reset_cpu(&mut cpu, &mem);
+
+    let ops = opcode_table(cpu.variant);
+    for _ in 0..MAX_INSTRUCTIONS {
+        let opcode = mem.mem[cpu.pc as usize];
+        ops[opcode as usize](&mut cpu, &mut mem);
+
+        assert!(cpu.status() & STATUS_FLAGS_UNUSED != 0, "opcode ${:02x} cleared the unused status bit", opcode);
+    }
+});
@@ -0,0 +1,25 @@
+-- a console device at $f001 written in lua, and a hook that counts
+-- the times a routine is called
+--
+--   cargo run --release -- examples/console.lua program.bin
+
+emu.load(arg[1] or error("usage: console.lua PROGRAM.bin"), 0x0400)
+emu.poke(0xfffc, 0x00)
+emu.poke(0xfffd, 0x04)
+emu.reset()
+
+emu.device(0xf000, 2, {
+    write = function(offset, value)
+        if offset == 1 then io.write(string.char(value)) end
+    end,
+})
+
+local calls = 0
+emu.hook(0x0500, function()
+    calls = calls + 1
+    return calls == 100         -- stop on the hundredth
+end)
+
+local why = emu.run(10000000)
+print(string.format("\nstopped (%s) at $%04x after %d cycles, $0500 called %d times",
+    why, emu.reg("pc"), emu.reg("cycles"), calls))
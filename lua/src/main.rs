@@ -0,0 +1,309 @@
+// e6502r-lua: the cpu and memory driven by a lua script
+//
+//   cargo run --release -- script.lua [ARG...]
+//
+// the script gets a table, emu, with the machine in it:
+//
+//   emu.cpu(VARIANT)               "6502" (the default), "65c02" or "2a03"
+//   emu.load(PATH [, ADDR])        a binary image at ADDR (default $0400)
+//   emu.reset()                    start from the reset vector
+//   emu.peek(ADDR)  emu.poke(ADDR, VALUE)
+//   emu.reg(NAME [, VALUE])        a, x, y, sp, pc or p; cycles to read
+//   emu.step()                     one instruction; returns its cycles
+//   emu.run([MAX])                 until a hook stops it, the cpu loops
+//                                  to itself or MAX instructions have
+//                                  run; returns "hook", "loop" or "steps"
+//   emu.stop()                     end the run once this hook returns
+//   emu.hook(ADDR, FUNCTION)       call FUNCTION(ADDR) when execution
+//                                  reaches ADDR; returning true stops
+//                                  the run
+//   emu.unhook(ADDR)
+//   emu.device(BASE, SIZE, HANDLERS)
+//                                  map a device run by lua functions:
+//                                  read(OFFSET) returns a byte,
+//                                  write(OFFSET, VALUE), tick(CYCLES)
+//                                  and irq() returns whether it holds
+//                                  the irq line; any may be left out
+//
+// arguments after the script are in arg, as for the lua interpreter.
+// devices are ticked and interrupts taken after each instruction, as the
+// emulator's main loop does. a device's handlers run in the middle of
+// an instruction, so they can't use emu themselves.
+
+use std::cell::{RefCell, RefMut};
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::process;
+use std::rc::Rc;
+
+use mlua::{Error, Function, Lua, Table};
+
+use e6502r::cpu;
+use e6502r::cpu::Cpu;
+use e6502r::memory::{Device, Memory, MEMSIZE};
+use e6502r::run::{step, StopConditions, StopReason};
+
+struct Machine {
+    cpu: Cpu,
+    mem: Memory,
+    hooks: BTreeMap<u16, Function>,
+    stopping: bool,
+    device_error: Rc<RefCell<Option<Error>>>,   // from a handler, for after the instruction
+}
+
+type Shared = Rc<RefCell<Machine>>;
+
+// a device whose registers are lua functions
+struct ScriptDevice {
+    read: Option<Function>,
+    write: Option<Function>,
+    tick: Option<Function>,
+    irq: Option<Function>,
+    error: Rc<RefCell<Option<Error>>>,
+}
+
+impl ScriptDevice {
+    // keep the first error for the instruction to report
+    fn check<T: Default>(&self, result: mlua::Result<T>) -> T {
+        result.unwrap_or_else(|e| {
+            self.error.borrow_mut().get_or_insert(e);
+            T::default()
+        })
+    }
+}
+
+impl Device for ScriptDevice {
+    fn read(&mut self, offset: u16) -> u8 {
+        match &self.read {
+            Some(read) => self.check(read.call::<u8>(offset)),
+            None => 0,
+        }
+    }
+
+    fn write(&mut self, offset: u16, value: u8) {
+        if let Some(write) = &self.write {
+            self.check(write.call::<()>((offset, value)));
+        }
+    }
+
+    fn tick(&mut self, cycles: u32) {
+        if let Some(tick) = &self.tick {
+            self.check(tick.call::<()>(cycles));
+        }
+    }
+
+    fn irq(&self) -> bool {
+        match &self.irq {
+            Some(irq) => self.check(irq.call::<bool>(())),
+            None => false,
+        }
+    }
+
+    fn raises_irqs(&self) -> bool {
+        self.irq.is_some()
+    }
+}
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let Some(path) = args.next() else {
+        eprintln!("usage: e6502r-lua SCRIPT [ARG...]");
+        process::exit(2);
+    };
+    if let Err(e) = run_script(&path, args.collect()) {
+        eprintln!("e6502r-lua: {}", e);
+        process::exit(1);
+    }
+}
+
+fn run_script(path: &str, args: Vec<String>) -> mlua::Result<()> {
+    let source = fs::read_to_string(path).map_err(|e| Error::runtime(format!("{}: {}", path, e)))?;
+    let lua = Lua::new();
+    let machine = Rc::new(RefCell::new(Machine {
+        cpu: Cpu::new(),
+        mem: Memory::new(),
+        hooks: BTreeMap::new(),
+        stopping: false,
+        device_error: Rc::new(RefCell::new(None)),
+    }));
+    lua.globals().set("emu", api(&lua, &machine)?)?;
+
+    let arg = lua.create_table()?;
+    arg.set(0, path)?;
+    for (index, value) in args.into_iter().enumerate() {
+        arg.set(index + 1, value)?;
+    }
+    lua.globals().set("arg", arg)?;
+
+    lua.load(&source).set_name(path).exec()
+}
+
+// the emu table
+fn api(lua: &Lua, machine: &Shared) -> mlua::Result<Table> {
+    let emu = lua.create_table()?;
+
+    let m = machine.clone();
+    emu.set("cpu", lua.create_function(move |_, variant: String| {
+        busy(&m)?.cpu.variant = variant.parse().map_err(Error::runtime)?;
+        Ok(())
+    })?)?;
+
+    let m = machine.clone();
+    emu.set("load", lua.create_function(move |_, (path, addr): (String, Option<u16>)| {
+        let data = fs::read(&path).map_err(|e| Error::runtime(format!("{}: {}", path, e)))?;
+        let start = addr.unwrap_or(0x0400) as usize;
+        if start + data.len() > MEMSIZE {
+            return Err(Error::runtime(format!("{} doesn't fit in memory at ${:04x}", path, start)));
+        }
+        busy(&m)?.mem.mem[start..start + data.len()].copy_from_slice(&data);
+        Ok(())
+    })?)?;
+
+    let m = machine.clone();
+    emu.set("reset", lua.create_function(move |_, ()| {
+        let machine = &mut *busy(&m)?;
+        cpu::reset_cpu(&mut machine.cpu, &machine.mem);
+        Ok(())
+    })?)?;
+
+    let m = machine.clone();
+    emu.set("peek", lua.create_function(move |_, addr: u16| Ok(busy(&m)?.mem.mem[addr as usize]))?)?;
+
+    let m = machine.clone();
+    emu.set("poke", lua.create_function(move |_, (addr, value): (u16, u8)| {
+        busy(&m)?.mem.mem[addr as usize] = value;
+        Ok(())
+    })?)?;
+
+    let m = machine.clone();
+    emu.set("reg", lua.create_function(move |_, (name, value): (String, Option<u16>)| {
+        let cpu = &mut busy(&m)?.cpu;
+        if let Some(value) = value {
+            set_register(cpu, &name, value)?;
+        }
+        register(cpu, &name)
+    })?)?;
+
+    let m = machine.clone();
+    emu.set("step", lua.create_function(move |_, ()| {
+        let (cycles, _) = execute(&m)?;
+        call_hook(&m)?;
+        Ok(cycles)
+    })?)?;
+
+    let m = machine.clone();
+    emu.set("run", lua.create_function(move |_, max_steps: Option<u64>| {
+        busy(&m)?.stopping = false;
+        let mut steps = 0;
+        loop {
+            if max_steps.is_some_and(|max| steps >= max) {
+                return Ok("steps");
+            }
+            let (_, stop) = execute(&m)?;
+            steps += 1;
+            if call_hook(&m)? {
+                return Ok("hook");
+            }
+            if let Some(StopReason::SelfLoop(_)) = stop {
+                return Ok("loop");
+            }
+        }
+    })?)?;
+
+    let m = machine.clone();
+    emu.set("stop", lua.create_function(move |_, ()| {
+        busy(&m)?.stopping = true;
+        Ok(())
+    })?)?;
+
+    let m = machine.clone();
+    emu.set("hook", lua.create_function(move |_, (addr, hook): (u16, Function)| {
+        busy(&m)?.hooks.insert(addr, hook);
+        Ok(())
+    })?)?;
+
+    let m = machine.clone();
+    emu.set("unhook", lua.create_function(move |_, addr: u16| {
+        busy(&m)?.hooks.remove(&addr);
+        Ok(())
+    })?)?;
+
+    let m = machine.clone();
+    emu.set("device", lua.create_function(move |_, (base, size, handlers): (u16, u16, Table)| {
+        let machine = &mut *busy(&m)?;
+        let device = ScriptDevice {
+            read: handlers.get("read")?,
+            write: handlers.get("write")?,
+            tick: handlers.get("tick")?,
+            irq: handlers.get("irq")?,
+            error: machine.device_error.clone(),
+        };
+        machine.mem.attach(base, size, Box::new(device)).map_err(Error::runtime)
+    })?)?;
+
+    Ok(emu)
+}
+
+// the machine, unless a device handler is asking for it mid-instruction
+fn busy(machine: &Shared) -> mlua::Result<RefMut<'_, Machine>> {
+    machine.try_borrow_mut().map_err(|_| Error::runtime("a device handler can't use emu"))
+}
+
+// run one instruction as the main loop does; returns the cycles it took
+// and whether it was a loop to itself
+fn execute(machine: &Shared) -> mlua::Result<(u64, Option<StopReason>)> {
+    let machine = &mut *busy(machine)?;
+    let (cpu, mem) = (&mut machine.cpu, &mut machine.mem);
+    let start = cpu.cycles;
+    let stop = step(cpu, mem, &StopConditions { strict_opcodes: true, ..Default::default() });
+    if let Some(stop @ StopReason::Unimplemented(..)) = stop {
+        return Err(Error::runtime(stop.to_string()));
+    }
+    if let Some(e) = machine.device_error.borrow_mut().take() {
+        return Err(e);
+    }
+    Ok((cpu.cycles - start, stop))
+}
+
+// call the hook where execution has got to, if there is one; returns
+// whether it asked for the run to stop
+fn call_hook(machine: &Shared) -> mlua::Result<bool> {
+    let (pc, hook) = {
+        let machine = busy(machine)?;
+        (machine.cpu.pc, machine.hooks.get(&machine.cpu.pc).cloned())
+    };
+    let Some(hook) = hook else { return Ok(false) };
+    let stop = hook.call::<bool>(pc)?;
+    Ok(stop || busy(machine)?.stopping)
+}
+
+fn register(cpu: &Cpu, name: &str) -> mlua::Result<u64> {
+    Ok(match name {
+        "a" => cpu.ac as u64,
+        "x" => cpu.xr as u64,
+        "y" => cpu.yr as u64,
+        "sp" => cpu.sp as u64,
+        "pc" => cpu.pc as u64,
+        "p" => cpu.status() as u64,
+        "cycles" => cpu.cycles,
+        _ => return Err(Error::runtime(format!("no register '{}'", name))),
+    })
+}
+
+fn set_register(cpu: &mut Cpu, name: &str, value: u16) -> mlua::Result<()> {
+    if name == "pc" {
+        cpu.pc = value;
+        return Ok(());
+    }
+    let byte = u8::try_from(value).map_err(|_| Error::runtime(format!("{} takes values up to $ff", name)))?;
+    match name {
+        "a" => cpu.ac = byte,
+        "x" => cpu.xr = byte,
+        "y" => cpu.yr = byte,
+        "sp" => cpu.sp = byte,
+        "p" => cpu.set_status(byte),
+        _ => return Err(Error::runtime(format!("can't set '{}'", name))),
+    }
+    Ok(())
+}
@@ -0,0 +1,38 @@
+/* e6502r device plugins in C; the same table as plugin/src/lib.rs
+ *
+ * a plugin is a shared library that defines e6502r_device_v1, returning
+ * a table that lives as long as the library. create gets the device's
+ * options as text and returns its state, which the other functions are
+ * given, or NULL with a message written to error. tick, irq and nmi may
+ * be NULL. while no device raises irqs, a cpu looping to itself has
+ * stopped, so raises_irqs should say false unless irq or nmi can be true.
+ *
+ *   cc -shared -fPIC -o mydevice.so mydevice.c
+ */
+
+#ifndef E6502R_PLUGIN_H
+#define E6502R_PLUGIN_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#define E6502R_PLUGIN_ABI_VERSION 1
+
+typedef struct e6502r_device_v1 {
+  uint32_t abi_version;                  /* E6502R_PLUGIN_ABI_VERSION */
+  void *(*create)(const char *const *keys, const char *const *values, size_t count,
+                  double clock_hz, char *error, size_t error_len);
+  uint16_t (*size)(void *state);         /* addresses answered from the base */
+  uint8_t (*read)(void *state, uint16_t offset);
+  void (*write)(void *state, uint16_t offset, uint8_t value);
+  void (*tick)(void *state, uint32_t cycles);
+  bool (*irq)(void *state);              /* whether the irq line is held */
+  bool (*nmi)(void *state);
+  bool (*raises_irqs)(void *state);      /* whether irq or nmi can be true */
+  void (*destroy)(void *state);
+} e6502r_device_v1_t;
+
+const e6502r_device_v1_t *e6502r_device_v1(void);
+
+#endif
@@ -0,0 +1,116 @@
+// a real time clock as a plugin, from the host's clock in UTC
+//
+//   cargo build --release --example rtc
+//   e6502r --device plugin@d000,file=target/release/examples/librtc.so ...
+//
+// registers, from the base:
+//
+//   0 seconds  1 minutes  2 hours  3 day  4 month  5 year - 2000
+//   6 status: bit 7 set when a second has passed since it was last read
+//
+// reading the seconds latches the time, so the rest read the same
+// moment. with format = "bcd" the time is in binary coded decimal. with
+// irq = "true" the device holds the irq line while bit 7 of the status
+// is set.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use e6502r_plugin::{export_device, Device, Options};
+
+struct Rtc {
+    bcd: bool,
+    raise_irq: bool,
+    latched: [u8; 6],
+    second: u64,
+    ticked: bool,
+}
+
+const STATUS: u16 = 6;
+
+impl Device for Rtc {
+    fn new(options: &Options, _clock_hz: f64) -> Result<Rtc, String> {
+        let bcd = match options.get("format").map(|format| format.as_str()) {
+            None | Some("binary") => false,
+            Some("bcd") => true,
+            Some(other) => return Err(format!("format should be binary or bcd, not '{}'", other)),
+        };
+        let raise_irq = match options.get("irq").map(|irq| irq.as_str()) {
+            None | Some("false") => false,
+            Some("true") => true,
+            Some(other) => return Err(format!("irq should be true or false, not '{}'", other)),
+        };
+        let mut rtc = Rtc { bcd, raise_irq, latched: [0; 6], second: now(), ticked: false };
+        rtc.latch();
+        Ok(rtc)
+    }
+
+    fn size(&self) -> u16 {
+        7
+    }
+
+    fn read(&mut self, offset: u16) -> u8 {
+        match offset {
+            0 => {
+                self.latch();
+                self.latched[0]
+            }
+            1..=5 => self.latched[offset as usize],
+            STATUS => {
+                let status = if self.ticked { 0x80 } else { 0 };
+                self.ticked = false;
+                status
+            }
+            _ => 0,
+        }
+    }
+
+    fn write(&mut self, _offset: u16, _value: u8) {}
+
+    fn tick(&mut self, _cycles: u32) {
+        let second = now();
+        if second != self.second {
+            self.second = second;
+            self.ticked = true;
+        }
+    }
+
+    fn irq(&self) -> bool {
+        self.raise_irq && self.ticked
+    }
+
+    fn raises_irqs(&self) -> bool {
+        self.raise_irq
+    }
+}
+
+impl Rtc {
+    fn latch(&mut self) {
+        let seconds = now();
+        let (year, month, day) = civil_from_days((seconds / 86400) as i64);
+        let fields = [seconds % 60, seconds / 60 % 60, seconds / 3600 % 24, day, month, year.saturating_sub(2000)];
+        for (latched, field) in self.latched.iter_mut().zip(fields) {
+            let field = field.min(99) as u8;
+            *latched = if self.bcd { ((field / 10) << 4) | (field % 10) } else { field };
+        }
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_secs())
+}
+
+// year, month and day of a day counted from 1970-01-01
+fn civil_from_days(days: i64) -> (u64, u64, u64) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year as u64, month as u64, day as u64)
+}
+
+export_device!(Rtc);
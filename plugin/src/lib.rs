@@ -0,0 +1,167 @@
+// writing e6502r devices as plugins, shared libraries the emulator
+// loads when the machine names one:
+//
+//   [[device]]
+//   type = "plugin"
+//   base = 0xd000
+//   file = "target/release/librtc.so"
+//   format = "bcd"            # other keys come to new() as options
+//
+// a plugin is a cdylib that implements Device for its state and says
+// export_device!(ItsType). that exports e6502r_device_v1, which hands
+// the emulator a table of functions, DeviceV1, whose layout is fixed
+// for abi version 1; e6502r_plugin.h declares the same table for
+// plugins written in C. a plugin built with a version of the table the
+// emulator doesn't take is refused when the machine is loaded.
+//
+// a panic in the device is caught at the edge and aborts the emulator,
+// since it can't unwind into it.
+
+use std::collections::BTreeMap;
+use std::ffi::{c_char, c_void, CStr};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::process;
+
+pub const ABI_VERSION: u32 = 1;
+
+// the device's options from the machine file or --device, as text
+pub type Options = BTreeMap<String, String>;
+
+pub trait Device: Sized + 'static {
+    // make the device; an error is shown as the reason the machine
+    // couldn't be loaded
+    fn new(options: &Options, clock_hz: f64) -> Result<Self, String>;
+
+    // how many addresses from its base the device answers
+    fn size(&self) -> u16;
+
+    fn read(&mut self, offset: u16) -> u8;
+
+    fn write(&mut self, offset: u16, value: u8);
+
+    // the cpu has run this many more cycles
+    fn tick(&mut self, _cycles: u32) {}
+
+    // whether the device is holding the irq or nmi line
+    fn irq(&self) -> bool {
+        false
+    }
+
+    fn nmi(&self) -> bool {
+        false
+    }
+
+    // whether irq or nmi can ever return true; while none of a
+    // machine's devices can, a cpu looping to itself has stopped
+    fn raises_irqs(&self) -> bool {
+        false
+    }
+}
+
+// the table e6502r_device_v1 returns; every function but create takes
+// the state create returned
+#[repr(C)]
+pub struct DeviceV1 {
+    pub abi_version: u32,
+    pub create: unsafe extern "C" fn(keys: *const *const c_char, values: *const *const c_char, count: usize,
+        clock_hz: f64, error: *mut c_char, error_len: usize) -> *mut c_void,
+    pub size: unsafe extern "C" fn(state: *mut c_void) -> u16,
+    pub read: unsafe extern "C" fn(state: *mut c_void, offset: u16) -> u8,
+    pub write: unsafe extern "C" fn(state: *mut c_void, offset: u16, value: u8),
+    pub tick: Option<unsafe extern "C" fn(state: *mut c_void, cycles: u32)>,
+    pub irq: Option<unsafe extern "C" fn(state: *mut c_void) -> bool>,
+    pub nmi: Option<unsafe extern "C" fn(state: *mut c_void) -> bool>,
+    pub raises_irqs: unsafe extern "C" fn(state: *mut c_void) -> bool,
+    pub destroy: unsafe extern "C" fn(state: *mut c_void),
+}
+
+// export a Device as this library's plugin
+#[macro_export]
+macro_rules! export_device {
+    ($device:ty) => {
+        #[no_mangle]
+        pub extern "C" fn e6502r_device_v1() -> *const $crate::DeviceV1 {
+            static TABLE: $crate::DeviceV1 = $crate::table::<$device>();
+            &TABLE
+        }
+    };
+}
+
+// the table for a Device, for export_device!
+pub const fn table<D: Device>() -> DeviceV1 {
+    DeviceV1 {
+        abi_version: ABI_VERSION,
+        create: create::<D>,
+        size: size::<D>,
+        read: read::<D>,
+        write: write::<D>,
+        tick: Some(tick::<D>),
+        irq: Some(irq::<D>),
+        nmi: Some(nmi::<D>),
+        raises_irqs: raises_irqs::<D>,
+        destroy: destroy::<D>,
+    }
+}
+
+// run f, aborting rather than unwinding into the emulator
+fn guard<T>(f: impl FnOnce() -> T) -> T {
+    catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|_| process::abort())
+}
+
+// keys and values each point to count nul terminated strings, and
+// error to error_len writable bytes
+unsafe extern "C" fn create<D: Device>(keys: *const *const c_char, values: *const *const c_char, count: usize,
+    clock_hz: f64, error: *mut c_char, error_len: usize) -> *mut c_void {
+    guard(|| {
+        let mut options = Options::new();
+        for index in 0..count {
+            let text = |strings: *const *const c_char| CStr::from_ptr(*strings.add(index)).to_string_lossy().into_owned();
+            options.insert(text(keys), text(values));
+        }
+        match D::new(&options, clock_hz) {
+            Ok(device) => Box::into_raw(Box::new(device)) as *mut c_void,
+            Err(message) => {
+                if error_len > 0 {
+                    let length = message.len().min(error_len - 1);
+                    std::ptr::copy_nonoverlapping(message.as_ptr() as *const c_char, error, length);
+                    *error.add(length) = 0;
+                }
+                std::ptr::null_mut()
+            }
+        }
+    })
+}
+
+// the rest take the state create made, until destroy frees it
+
+unsafe extern "C" fn size<D: Device>(state: *mut c_void) -> u16 {
+    guard(|| (*(state as *mut D)).size())
+}
+
+unsafe extern "C" fn read<D: Device>(state: *mut c_void, offset: u16) -> u8 {
+    guard(|| (*(state as *mut D)).read(offset))
+}
+
+unsafe extern "C" fn write<D: Device>(state: *mut c_void, offset: u16, value: u8) {
+    guard(|| (*(state as *mut D)).write(offset, value))
+}
+
+unsafe extern "C" fn tick<D: Device>(state: *mut c_void, cycles: u32) {
+    guard(|| (*(state as *mut D)).tick(cycles))
+}
+
+unsafe extern "C" fn irq<D: Device>(state: *mut c_void) -> bool {
+    guard(|| (*(state as *const D)).irq())
+}
+
+unsafe extern "C" fn nmi<D: Device>(state: *mut c_void) -> bool {
+    guard(|| (*(state as *const D)).nmi())
+}
+
+unsafe extern "C" fn raises_irqs<D: Device>(state: *mut c_void) -> bool {
+    guard(|| (*(state as *const D)).raises_irqs())
+}
+
+unsafe extern "C" fn destroy<D: Device>(state: *mut c_void) {
+    guard(|| drop(Box::from_raw(state as *mut D)))
+}
@@ -0,0 +1,363 @@
+// the py6502r python module: the cpu and memory driven from python
+//
+// build and install into the current virtualenv with: maturin develop
+//
+//   import py6502r
+//   machine = py6502r.Machine("65c02")
+//   machine.bus.load(0x0400, program)
+//   machine.bus[0xfffc:0xfffe] = b"\x00\x04"
+//   machine.reset()
+//   machine.add_breakpoint(0x0420)
+//   machine.run()                 # "breakpoint"
+//   print(hex(machine.cpu.pc), machine.bus[0x0200])
+//
+// a Machine has a Cpu, its registers, and a Bus, its 64k of memory.
+// both are live views: changing a register or a byte changes the
+// machine. attach_keyboard maps an apple ii style keyboard, and key
+// queues a key for it; each machine has a queue of its own.
+
+// pyo3 0.22's #[pymethods] expansion converts PyResult returns into themselves
+#![allow(clippy::useless_conversion)]
+
+use std::collections::BTreeSet;
+
+use pyo3::exceptions::{PyIndexError, PyRuntimeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PySlice};
+
+use e6502r::cpu;
+use e6502r::devices::keyboard::{Keyboard, KEYBOARD_SIZE};
+use e6502r::memory::{Memory, MEMSIZE};
+use e6502r::run::{run_for, run_frame, step, BlockRun, FrameClock, StopConditions, StopReason};
+
+// the registers, and the cycles run so far
+#[pyclass]
+pub struct Cpu {
+    cpu: cpu::Cpu,
+}
+
+#[pymethods]
+impl Cpu {
+    #[getter]
+    fn pc(&self) -> u16 {
+        self.cpu.pc
+    }
+
+    #[setter]
+    fn set_pc(&mut self, value: u16) {
+        self.cpu.pc = value;
+    }
+
+    #[getter]
+    fn a(&self) -> u8 {
+        self.cpu.ac
+    }
+
+    #[setter]
+    fn set_a(&mut self, value: u8) {
+        self.cpu.ac = value;
+    }
+
+    #[getter]
+    fn x(&self) -> u8 {
+        self.cpu.xr
+    }
+
+    #[setter]
+    fn set_x(&mut self, value: u8) {
+        self.cpu.xr = value;
+    }
+
+    #[getter]
+    fn y(&self) -> u8 {
+        self.cpu.yr
+    }
+
+    #[setter]
+    fn set_y(&mut self, value: u8) {
+        self.cpu.yr = value;
+    }
+
+    #[getter]
+    fn sp(&self) -> u8 {
+        self.cpu.sp
+    }
+
+    #[setter]
+    fn set_sp(&mut self, value: u8) {
+        self.cpu.sp = value;
+    }
+
+    #[getter]
+    fn p(&self) -> u8 {
+        self.cpu.status()
+    }
+
+    #[setter]
+    fn set_p(&mut self, value: u8) {
+        self.cpu.set_status(value);
+    }
+
+    #[getter]
+    fn cycles(&self) -> u64 {
+        self.cpu.cycles
+    }
+
+    // "6502", "65c02" or "2a03"
+    #[getter]
+    fn variant(&self) -> String {
+        self.cpu.variant.to_string()
+    }
+
+    fn __repr__(&self) -> String {
+        let cpu = &self.cpu;
+        format!("<Cpu pc=${:04x} a=${:02x} x=${:02x} y=${:02x} sp=${:02x} p=${:02x} cycles={}>",
+            cpu.pc, cpu.ac, cpu.xr, cpu.yr, cpu.sp, cpu.status(), cpu.cycles)
+    }
+}
+
+// the 64k address space; bus[addr] and bus[start:end] read and write it
+// memory can hold devices, which aren't Send, so a Bus stays on the
+// thread that made it
+#[pyclass(unsendable)]
+pub struct Bus {
+    mem: Memory,
+}
+
+#[pymethods]
+impl Bus {
+    fn peek(&self, addr: u16) -> u8 {
+        self.mem.mem[addr as usize]
+    }
+
+    fn poke(&mut self, addr: u16, value: u8) {
+        self.mem.mem[addr as usize] = value;
+    }
+
+    // copy bytes into memory from addr
+    fn load(&mut self, addr: u16, data: &[u8]) -> PyResult<()> {
+        let start = addr as usize;
+        if start + data.len() > MEMSIZE {
+            return Err(PyIndexError::new_err(format!("{} bytes at ${:04x} don't fit in memory", data.len(), addr)));
+        }
+        self.mem.mem[start..start + data.len()].copy_from_slice(data);
+        Ok(())
+    }
+
+    fn __len__(&self) -> usize {
+        MEMSIZE
+    }
+
+    fn __getitem__(&self, py: Python<'_>, key: &Bound<'_, PyAny>) -> PyResult<PyObject> {
+        if let Ok(slice) = key.downcast::<PySlice>() {
+            let range = slice_range(slice)?;
+            return Ok(PyBytes::new_bound(py, &self.mem.mem[range]).into_py(py));
+        }
+        let addr = address(key.extract()?)?;
+        Ok(self.mem.mem[addr].into_py(py))
+    }
+
+    fn __setitem__(&mut self, key: &Bound<'_, PyAny>, value: &Bound<'_, PyAny>) -> PyResult<()> {
+        if let Ok(slice) = key.downcast::<PySlice>() {
+            let range = slice_range(slice)?;
+            let data: Vec<u8> = value.extract()?;
+            if data.len() != range.len() {
+                return Err(PyValueError::new_err(format!("{} bytes for a slice of {}", data.len(), range.len())));
+            }
+            self.mem.mem[range].copy_from_slice(&data);
+            return Ok(());
+        }
+        let addr = address(key.extract()?)?;
+        self.mem.mem[addr] = value.extract()?;
+        Ok(())
+    }
+}
+
+fn address(index: isize) -> PyResult<usize> {
+    usize::try_from(index)
+        .ok()
+        .filter(|&addr| addr < MEMSIZE)
+        .ok_or_else(|| PyIndexError::new_err(format!("address {} is outside memory", index)))
+}
+
+fn slice_range(slice: &Bound<'_, PySlice>) -> PyResult<std::ops::Range<usize>> {
+    let indices = slice.indices(MEMSIZE as _)?;
+    if indices.step != 1 {
+        return Err(PyValueError::new_err("memory slices can't have a step"));
+    }
+    let start = indices.start as usize;
+    Ok(start..start.max(indices.stop as usize))
+}
+
+#[pyclass]
+pub struct Machine {
+    cpu: Py<Cpu>,
+    bus: Py<Bus>,
+    breakpoints: BTreeSet<u16>,
+    clock: FrameClock,      // for run_frame
+}
+
+#[pymethods]
+impl Machine {
+    // cpu is "6502", "65c02" or "2a03"
+    #[new]
+    #[pyo3(signature = (cpu = "6502"))]
+    fn new(py: Python<'_>, cpu: &str) -> PyResult<Machine> {
+        let mut state = cpu::Cpu::new();
+        state.variant = cpu.parse().map_err(PyValueError::new_err)?;
+        Ok(Machine {
+            cpu: Py::new(py, Cpu { cpu: state })?,
+            bus: Py::new(py, Bus { mem: Memory::new() })?,
+            breakpoints: BTreeSet::new(),
+            clock: FrameClock::default(),
+        })
+    }
+
+    #[getter]
+    fn cpu(&self, py: Python<'_>) -> Py<Cpu> {
+        self.cpu.clone_ref(py)
+    }
+
+    #[getter]
+    fn bus(&self, py: Python<'_>) -> Py<Bus> {
+        self.bus.clone_ref(py)
+    }
+
+    // start from the reset vector; frames are counted from here
+    fn reset(&mut self, py: Python<'_>) {
+        let bus = self.bus.borrow(py);
+        let mut cpu = self.cpu.borrow_mut(py);
+        cpu::reset_cpu(&mut cpu.cpu, &bus.mem);
+        self.clock.restart(cpu.cpu.cycles);
+    }
+
+    // the cpu's clock and the frames a second run_frame runs, e.g.
+    // 1789773 and 60.0988 for an ntsc nes, 1 MHz at 60 until set;
+    // frames are counted from here
+    fn set_clock(&mut self, py: Python<'_>, clock_hz: f64, frame_rate: f64) -> PyResult<()> {
+        let from = self.cpu.borrow(py).cpu.cycles;
+        self.clock = FrameClock::new(clock_hz, frame_rate, from).map_err(PyValueError::new_err)?;
+        Ok(())
+    }
+
+    // run one instruction; returns the cycles it took
+    fn step(&self, py: Python<'_>) -> PyResult<u64> {
+        let mut cpu = self.cpu.borrow_mut(py);
+        let mut bus = self.bus.borrow_mut(py);
+        let start = cpu.cpu.cycles;
+        execute(&mut cpu.cpu, &mut bus.mem, &StopConditions { strict_opcodes: true, ..Default::default() })?;
+        Ok(cpu.cpu.cycles - start)
+    }
+
+    // run until a breakpoint, a loop to itself or, if given, max_steps
+    // instructions; returns "breakpoint", "loop" or "steps"
+    #[pyo3(signature = (max_steps = None))]
+    fn run(&self, py: Python<'_>, max_steps: Option<u64>) -> PyResult<&'static str> {
+        let mut cpu = self.cpu.borrow_mut(py);
+        let mut bus = self.bus.borrow_mut(py);
+        let stops = self.stops();
+        let mut steps = 0;
+        loop {
+            if max_steps.is_some_and(|max| steps >= max) {
+                return Ok("steps");
+            }
+            let stop = execute(&mut cpu.cpu, &mut bus.mem, &stops)?;
+            steps += 1;
+            match stop {
+                Some(StopReason::StopAddress(_)) => return Ok("breakpoint"),
+                Some(StopReason::SelfLoop(_)) => return Ok("loop"),
+                _ => {}
+            }
+            // let ctrl-c through on long runs
+            if steps % 0x10000 == 0 {
+                py.check_signals()?;
+            }
+        }
+    }
+
+    // run for a budget of cycles, or until a breakpoint or a loop to
+    // itself; returns the cycles left over, negative when the last
+    // instruction ran past the budget, with None, "breakpoint" or "loop"
+    fn run_for(&self, py: Python<'_>, cycles: i64) -> PyResult<(i64, Option<&'static str>)> {
+        let mut cpu = self.cpu.borrow_mut(py);
+        let mut bus = self.bus.borrow_mut(py);
+        let block = run_for(&mut cpu.cpu, &mut bus.mem, &self.stops(), cycles);
+        Ok((block.leftover, stopped(block)?))
+    }
+
+    // run one frame's cycles at the clock, or until a breakpoint or a
+    // loop to itself; returns None, "breakpoint" or "loop". an
+    // instruction that runs past the end of a frame comes off the next
+    fn run_frame(&mut self, py: Python<'_>) -> PyResult<Option<&'static str>> {
+        let stops = self.stops();
+        let mut cpu = self.cpu.borrow_mut(py);
+        let mut bus = self.bus.borrow_mut(py);
+        stopped(run_frame(&mut cpu.cpu, &mut bus.mem, &stops, &mut self.clock))
+    }
+
+    // an apple ii style keyboard at addr, as --device keyboard@ADDR
+    #[pyo3(signature = (addr, uppercase = false))]
+    fn attach_keyboard(&self, py: Python<'_>, addr: u16, uppercase: bool) -> PyResult<()> {
+        let mut bus = self.bus.borrow_mut(py);
+        let keyboard = Keyboard::new(bus.mem.inputs.clone(), uppercase);
+        bus.mem.attach(addr, KEYBOARD_SIZE, Box::new(keyboard)).map_err(PyValueError::new_err)
+    }
+
+    // a key for this machine's keyboard device, as an ascii code
+    fn key(&self, py: Python<'_>, key: u8) {
+        self.bus.borrow(py).mem.inputs.push_key(key);
+    }
+
+    fn add_breakpoint(&mut self, addr: u16) {
+        self.breakpoints.insert(addr);
+    }
+
+    fn remove_breakpoint(&mut self, addr: u16) -> PyResult<()> {
+        if !self.breakpoints.remove(&addr) {
+            return Err(PyValueError::new_err(format!("no breakpoint at ${:04x}", addr)));
+        }
+        Ok(())
+    }
+
+    #[getter]
+    fn breakpoints(&self) -> Vec<u16> {
+        self.breakpoints.iter().copied().collect()
+    }
+}
+
+impl Machine {
+    // unimplemented opcodes, breakpoints and loops to themselves stop a run
+    fn stops(&self) -> StopConditions {
+        StopConditions {
+            strict_opcodes: true,
+            stop_at: self.breakpoints.iter().copied().collect(),
+            ..Default::default()
+        }
+    }
+}
+
+// why a block stopped early, raising an error for an unimplemented opcode
+fn stopped(block: BlockRun) -> PyResult<Option<&'static str>> {
+    match block.stop {
+        Some(stop @ StopReason::Unimplemented(..)) => Err(PyRuntimeError::new_err(stop.to_string())),
+        Some(StopReason::StopAddress(_)) => Ok(Some("breakpoint")),
+        Some(_) => Ok(Some("loop")),
+        None => Ok(None),
+    }
+}
+
+// run one instruction, raising an error for an unimplemented opcode
+fn execute(cpu: &mut cpu::Cpu, mem: &mut Memory, stops: &StopConditions) -> PyResult<Option<StopReason>> {
+    match step(cpu, mem, stops) {
+        Some(stop @ StopReason::Unimplemented(..)) => Err(PyRuntimeError::new_err(stop.to_string())),
+        stop => Ok(stop),
+    }
+}
+
+#[pymodule]
+fn py6502r(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<Machine>()?;
+    module.add_class::<Cpu>()?;
+    module.add_class::<Bus>()?;
+    Ok(())
+}
@@ -0,0 +1,550 @@
+// command line argument parsing, with clap's derive. the options and
+// sub-commands are described on the structs below, which clap turns
+// into the help; parse_args hands main the options it runs with
+
+use clap::error::ErrorKind;
+use clap::{Args, CommandFactory, Parser, Subcommand, ValueEnum};
+
+use crate::ca65::Ca65Options;
+use crate::cpu::CpuVariant;
+use crate::harte::HarteOptions;
+use crate::klaus::{default_success_addr, KlausOptions, KLAUS_START_ADDR, KLAUS_TEST_CASE_ADDR};
+use crate::lorenz::LorenzOptions;
+use crate::devices::DeviceConfig;
+use crate::nes::NesOptions;
+use crate::poweron;
+use crate::poweron::PowerOn;
+use crate::randomtest::RandomOptions;
+use crate::run::SmcMode;
+use crate::toml::{Table, Value};
+
+const AFTER_HELP: &str = "\
+while running, type t and enter to toggle turbo, or just enter to pause;
+with a keyboard device, keys go to the program, ctrl-] pauses and ctrl-^
+pastes the host clipboard";
+
+// a binary image to load into memory
+#[derive(Clone)]
+pub struct LoadImage {
+    pub path: String,
+    pub addr: u16,
+}
+
+// what ctrl-c does while the program runs, when it's caught
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+pub enum CtrlCMode {
+    /// pull the NMI line, as an SBC's break button does
+    Nmi,
+    /// pause into the monitor
+    Monitor,
+}
+
+// options collected from the command line
+pub struct Options {
+    pub images: Vec<LoadImage>,
+    pub reset_vector: Option<u16>,
+    pub machine: Option<String>,
+    pub rom_dir: String,
+    pub devices: Vec<DeviceConfig>,
+    pub serial: Option<String>,     // connect for the first acia
+    pub variant: Option<CpuVariant>,
+    pub power_on: Option<PowerOn>,
+    pub trace_file: Option<String>,
+    pub trace_json: Option<String>,
+    pub print_output: bool,
+    pub color: bool,
+    pub speed: Option<u32>,
+    pub clock_hz: Option<f64>,
+    pub pause_on_exec_instr: bool,
+    pub interactive: bool,
+    pub stop_at: Vec<u16>,
+    pub exit_code_at: Option<u16>,
+    pub bench: Option<f64>,
+    pub use_tui: bool,
+    pub cosim: Option<String>,
+    pub http: Option<String>,
+    pub dap: Option<String>,
+    pub debug_info: Option<String>,
+    pub load_state: Option<String>,
+    pub save_state: Option<String>,
+    pub xxd_loads: Vec<LoadImage>,
+    pub save_xxd: Option<(String, u16, u16)>,
+    pub record: Option<String>,
+    pub replay: Option<String>,
+    pub trap_on_brk: bool,
+    pub strict_opcodes: bool,
+    pub smc: Option<SmcMode>,
+    pub stats: Option<String>,
+    pub screenshot: Option<String>,
+    pub ctrl_c: Option<CtrlCMode>,
+}
+
+// result of parsing the command line
+pub enum ParsedArgs {
+    Run(Box<Options>),
+    Test(String),
+    Klaus(KlausOptions),
+    Lorenz(LorenzOptions),
+    Harte(HarteOptions),
+    Nes(NesOptions),
+    Random(RandomOptions),
+    DiffState(String, String),
+    Disasm(Ca65Options),
+}
+
+/// 6502 emulator
+#[derive(Parser)]
+#[command(name = "e6502r", after_help = AFTER_HELP, args_conflicts_with_subcommands = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    #[command(flatten)]
+    run: RunArgs,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// run each DIR/*.bin as a test, checking the results described in
+    /// its .toml sidecar
+    Test {
+        dir: String,
+    },
+    /// run Klaus Dormann's 6502 functional test binary
+    ///
+    /// by default it starts at 0400, succeeds by looping at 3469 and keeps
+    /// the current test number at 0200; with --cpu 65c02 it runs the 65C02
+    /// extended opcodes test, which succeeds by looping at 24f1
+    Klaus {
+        file: String,
+        /// cpu variant: 6502, 65c02 or 2a03
+        #[arg(long, value_name = "VARIANT", default_value = "6502")]
+        cpu: CpuVariant,
+        /// where execution starts (default 0400)
+        #[arg(long, value_name = "ADDR", value_parser = parse_addr)]
+        start: Option<u16>,
+        /// the address of the success self-loop
+        #[arg(long, value_name = "ADDR", value_parser = parse_addr)]
+        success: Option<u16>,
+        /// where the current test number is kept (default 0200)
+        #[arg(long, value_name = "ADDR", value_parser = parse_addr)]
+        test_case: Option<u16>,
+    },
+    /// run Wolfgang Lorenz's C64 cpu tests from DIR, starting with NAME
+    /// and following each test on to the next
+    Lorenz {
+        dir: String,
+        #[arg(default_value = "start")]
+        name: String,
+    },
+    /// run Tom Harte's single-instruction tests from a .json file or a
+    /// directory of them
+    Harte {
+        path: String,
+        /// cpu variant: 6502, 65c02 or 2a03
+        #[arg(long, value_name = "VARIANT", default_value = "6502")]
+        cpu: CpuVariant,
+    },
+    /// run an NES cpu test rom (iNES, mapper 0) headless, reading the
+    /// result blargg's roms leave at 6000
+    Nes {
+        file: String,
+        /// run nestest in automation mode
+        #[arg(long)]
+        nestest: bool,
+    },
+    /// run random programs through the cpu, checking that no opcode
+    /// handler panics and the unused status bit stays set
+    ///
+    /// the runs are spread over threads, and run N takes seed SEED+N
+    Random {
+        /// the number of runs
+        #[arg(long, default_value_t = 1000)]
+        count: usize,
+        /// instructions in each run
+        #[arg(long, default_value_t = 10_000)]
+        steps: usize,
+        /// cpu variant: 6502, 65c02 or 2a03
+        #[arg(long, value_name = "VARIANT", default_value = "6502")]
+        cpu: CpuVariant,
+        /// the seed of the first run (default from the clock)
+        #[arg(long)]
+        seed: Option<u64>,
+        /// threads to run on (default one per core)
+        #[arg(long)]
+        threads: Option<usize>,
+    },
+    /// compare two saved states, or a state and a memory dump, showing
+    /// the registers and memory that differ; exits with 1 when they differ
+    DiffState {
+        a: String,
+        b: String,
+    },
+    /// write a binary image as ca65 source that assembles back to it
+    ///
+    /// code is found from the vectors (or --org) and each --entry, with
+    /// labels for its targets, and .byte is written for the rest and any
+    /// --data range
+    Disasm {
+        file: String,
+        /// where the image loads
+        #[arg(long, value_name = "ADDR", value_parser = parse_addr, default_value = "0400")]
+        org: u16,
+        /// cpu variant: 6502, 65c02 or 2a03
+        #[arg(long, value_name = "VARIANT", default_value = "6502")]
+        cpu: CpuVariant,
+        /// another place code starts (repeatable)
+        #[arg(long, value_name = "ADDR", value_parser = parse_addr)]
+        entry: Vec<u16>,
+        /// a range that is never code (repeatable)
+        #[arg(long, value_name = "ADDR-ADDR", value_parser = parse_range)]
+        data: Vec<(u16, u16)>,
+        /// the file to write (default stdout)
+        #[arg(long, value_name = "FILE")]
+        out: Option<String>,
+    },
+}
+
+#[derive(Args)]
+struct RunArgs {
+    /// binary images to load, at ADDR (hex, default 0400)
+    #[arg(value_name = "FILE[@ADDR]", value_parser = parse_image)]
+    images: Vec<LoadImage>,
+    /// load a machine configuration (.toml), or use a built-in profile:
+    /// apple1, apple2, pet, c64, eater, nes
+    #[arg(long, value_name = "FILE")]
+    machine: Option<String>,
+    /// where built-in profiles find their rom images
+    #[arg(long, value_name = "DIR", default_value = ".")]
+    rom_dir: String,
+    /// override the reset vector; without it, and with no image covering
+    /// fffc, it is the first image's address
+    #[arg(long, value_name = "ADDR", value_parser = parse_addr)]
+    reset: Option<u16>,
+    /// attach a device at ADDR with options (repeatable)
+    ///
+    /// types: console (write base+1 to print a character), keyboard (key
+    /// at base, clear strobe at base+10), via (6522; lcd=16x2 wires an
+    /// HD44780 to its pins, sd=FILE an sd card), framebuffer (width=,
+    /// height=, depth=1/2/4/8, palette=#000000/..., window=true and
+    /// scale=N with the window feature), text-screen
+    /// (columns=40, rows=25, charset=ascii or pet), speaker (played with
+    /// the audio feature, file=OUT.wav records it), joystick (stick, fire and paddle from the keyboard),
+    /// random (seed=N), disk (file=IMAGE, 512 byte sectors), cassette
+    /// (load=, save= a .wav, .tim or data file), printer (file=OUT.txt,
+    /// translate=ascii or petscii), pia (6520/6821), apple1-io,
+    /// apple2-text, pet-screen, pet-io, cia (6526), c64-port, c64-io,
+    /// c64-screen, nes-ram, nes-ppu, nes-apu, nes-cart (file=GAME.nes),
+    /// eater-io, timer, acia (6551; connect=stdio joins it to the
+    /// terminal, connect=tcp:PORT to a tcp client), script
+    /// (file=DEVICE.rhai, size=N; with the rhai feature), plugin
+    /// (file=LIBRARY.so; other options go to the plugin)
+    #[arg(long, value_name = "TYPE@ADDR[,KEY=VALUE...]", value_parser = parse_device)]
+    device: Vec<DeviceConfig>,
+    /// connect the first acia, the machine's or one from --device, to a
+    /// tcp client on PORT (tcp:HOST:PORT to choose the interface) or to
+    /// the terminal with stdio, as its connect option would
+    #[arg(long, value_name = "tcp:PORT")]
+    serial: Option<String>,
+    /// cpu variant: 6502 (default), 65c02 or 2a03 (NES, no decimal mode)
+    #[arg(long, value_name = "VARIANT")]
+    cpu: Option<CpuVariant>,
+    /// registers at power on (a, x, y, sp, p; hex); random or random=SEED
+    /// gives the rest random values
+    #[arg(long, value_name = "REG=VALUE[,...]", value_parser = poweron::parse)]
+    power_on: Option<PowerOn>,
+    /// write the instruction trace to FILE
+    #[arg(long, value_name = "FILE")]
+    trace: Option<String>,
+    /// write the trace to FILE as a json object per instruction, with its
+    /// registers, cycles and the memory it read and wrote
+    #[arg(long, value_name = "FILE")]
+    trace_json: Option<String>,
+    /// do not print the instruction trace
+    #[arg(long)]
+    quiet: bool,
+    /// do not colour the trace, monitor and disassembly (as when NO_COLOR
+    /// is set or stdout isn't a terminal)
+    #[arg(long)]
+    no_color: bool,
+    /// limit execution to N instructions per second
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    speed: Option<u32>,
+    /// pace execution to a clock rate like 1MHz or 1.79MHz
+    #[arg(long, value_name = "FREQ", value_parser = parse_frequency)]
+    clock: Option<f64>,
+    /// start running without pausing for the monitor
+    #[arg(long)]
+    free_run: bool,
+    /// run non-interactively: no trace on stdout, never read stdin, stop
+    /// where the monitor would be entered
+    #[arg(long)]
+    run: bool,
+    /// stop when execution reaches ADDR (repeatable)
+    #[arg(long, value_name = "ADDR", value_parser = parse_addr)]
+    stop_at: Vec<u16>,
+    /// exit with the byte at ADDR as the process status
+    #[arg(long, value_name = "ADDR", value_parser = parse_addr)]
+    exit_code_at: Option<u16>,
+    /// run flat out for SECONDS and report instructions and cycles per
+    /// second (ignores traps and stop conditions)
+    #[arg(long, value_name = "SECONDS", value_parser = parse_seconds)]
+    bench: Option<f64>,
+    /// use the full-screen debugger
+    #[arg(long)]
+    tui: bool,
+    /// run COMMAND as a reference emulator in lock-step and stop at the
+    /// first difference
+    #[arg(long, value_name = "COMMAND")]
+    cosim: Option<String>,
+    /// run headless under the control of http requests on PORT (localhost
+    /// unless ADDR is given); starts paused unless --free-run
+    #[arg(long, value_name = "[ADDR:]PORT", value_parser = parse_listen, conflicts_with = "dap")]
+    http: Option<String>,
+    /// run headless under a debugger that speaks the Debug Adapter
+    /// Protocol, connecting on PORT; starts paused until the debugger is
+    /// set up, unless --free-run
+    #[arg(long, value_name = "[ADDR:]PORT", value_parser = parse_listen)]
+    dap: Option<String>,
+    /// read source lines and labels for --dap and the monitor from the
+    /// debug info ld65 wrote with --dbgfile
+    #[arg(long, value_name = "FILE")]
+    dbg: Option<String>,
+    /// start from the machine saved in FILE, which must be set up with the
+    /// same options
+    #[arg(long, value_name = "FILE")]
+    load_state: Option<String>,
+    /// save the whole machine to FILE when the run ends
+    #[arg(long, value_name = "FILE")]
+    save_state: Option<String>,
+    /// load a hex dump in xxd's format, its offsets taken from ADDR (hex,
+    /// default 0000)
+    #[arg(long, value_name = "FILE[@ADDR]", value_parser = parse_xxd_load)]
+    load_xxd: Vec<LoadImage>,
+    /// write memory from ADDR to ADDR (default all of it) to FILE as an
+    /// xxd hex dump when the run ends
+    #[arg(long, value_name = "FILE[@ADDR-ADDR]", value_parser = parse_xxd_save)]
+    save_xxd: Option<(String, u16, u16)>,
+    /// record keys, serial input, random seeds, and irq and nmi from the
+    /// monitor or ctrl-c, to FILE
+    #[arg(long, value_name = "FILE", conflicts_with = "replay")]
+    record: Option<String>,
+    /// feed the machine the inputs recorded in FILE, at the cycles they
+    /// came in, instead of live input
+    #[arg(long, value_name = "FILE")]
+    replay: Option<String>,
+    /// BRK drops into the monitor instead of vectoring
+    #[arg(long)]
+    brk_trap: bool,
+    /// stop on opcodes that are not implemented
+    #[arg(long)]
+    strict: bool,
+    /// report, or stop at, writes to memory that has run as code
+    /// (self-modifying code): warn or break
+    #[arg(long, value_name = "warn|break", value_parser = parse_smc)]
+    smc: Option<SmcMode>,
+    /// write how many times each opcode ran to FILE as csv when the run
+    /// ends
+    #[arg(long, value_name = "FILE")]
+    stats: Option<String>,
+    /// save what the framebuffer or text screen shows to FILE as a png
+    /// when the run ends
+    #[arg(long, value_name = "FILE")]
+    screenshot: Option<String>,
+    /// what ctrl-c does while running, instead of ending the run
+    #[arg(long, value_name = "nmi|monitor")]
+    ctrl_c: Option<CtrlCMode>,
+}
+
+// parse a hex address with an optional $ or 0x prefix
+pub fn parse_addr(text: &str) -> Result<u16, String> {
+    let digits = text.trim();
+    let digits = digits.strip_prefix('$')
+        .or_else(|| digits.strip_prefix("0x"))
+        .unwrap_or(digits);
+    u16::from_str_radix(digits, 16).map_err(|_| format!("bad address '{}'", text))
+}
+
+// parse an inclusive address range like c000-cfff
+pub fn parse_range(text: &str) -> Result<(u16, u16), String> {
+    let (first, last) = text.split_once('-').ok_or(format!("bad range '{}', should be ADDR-ADDR", text))?;
+    let (first, last) = (parse_addr(first)?, parse_addr(last)?);
+    if first > last {
+        return Err(format!("bad range '{}', it ends before it starts", text));
+    }
+    Ok((first, last))
+}
+
+// parse a frequency like 1MHz, 1.79MHz, 500kHz or 1000000
+pub fn parse_frequency(text: &str) -> Result<f64, String> {
+    let lower = text.trim().to_lowercase();
+    let (number, scale) = if let Some(n) = lower.strip_suffix("mhz") {
+        (n, 1e6)
+    } else if let Some(n) = lower.strip_suffix("khz") {
+        (n, 1e3)
+    } else if let Some(n) = lower.strip_suffix("hz") {
+        (n, 1.0)
+    } else {
+        (lower.as_str(), 1.0)
+    };
+    number.trim().parse::<f64>()
+        .ok()
+        .map(|n| n * scale)
+        .filter(|hz| *hz > 0.0)
+        .ok_or(format!("bad frequency '{}'", text))
+}
+
+// parse a device like acia@8000,connect=stdio
+pub fn parse_device(text: &str) -> Result<DeviceConfig, String> {
+    let mut parts = text.split(',');
+    let (kind, base) = parts.next()
+        .and_then(|device| device.rsplit_once('@'))
+        .ok_or(format!("--device needs TYPE@ADDR, not '{}'", text))?;
+    let mut options = Table::new();
+    for part in parts {
+        let (key, value) = part.split_once('=').ok_or(format!("device option '{}' needs a value", part))?;
+        options.insert(key.to_string(), Value::String(value.to_string()));
+    }
+    Ok(DeviceConfig { kind: kind.to_string(), base: parse_addr(base)?, options })
+}
+
+// FILE or FILE@ADDR, loaded at default without an address
+fn parse_load(text: &str, default: u16) -> Result<LoadImage, String> {
+    Ok(match text.rsplit_once('@') {
+        Some((path, addr)) => LoadImage { path: path.to_string(), addr: parse_addr(addr)? },
+        None => LoadImage { path: text.to_string(), addr: default },
+    })
+}
+
+fn parse_image(text: &str) -> Result<LoadImage, String> {
+    parse_load(text, 0x0400)
+}
+
+fn parse_xxd_load(text: &str) -> Result<LoadImage, String> {
+    parse_load(text, 0x0000)
+}
+
+// FILE or FILE@ADDR-ADDR, all of memory without a range
+fn parse_xxd_save(text: &str) -> Result<(String, u16, u16), String> {
+    Ok(match text.rsplit_once('@') {
+        Some((path, range)) => {
+            let (first, last) = parse_range(range)?;
+            (path.to_string(), first, last)
+        }
+        None => (text.to_string(), 0x0000, 0xffff),
+    })
+}
+
+fn parse_seconds(text: &str) -> Result<f64, String> {
+    text.parse::<f64>()
+        .ok()
+        .filter(|n| *n > 0.0)
+        .ok_or(format!("bad benchmark time '{}'", text))
+}
+
+// a bare port number listens on localhost only
+fn parse_listen(text: &str) -> Result<String, String> {
+    Ok(if text.contains(':') { text.to_string() } else { format!("127.0.0.1:{}", text) })
+}
+
+fn parse_smc(text: &str) -> Result<SmcMode, String> {
+    match text {
+        "warn" => Ok(SmcMode::Warn),
+        "break" => Ok(SmcMode::Break),
+        other => Err(format!("bad --smc '{}', should be warn or break", other)),
+    }
+}
+
+// parse the command line, the program name first; help, and errors
+// with the usage, come back as clap errors for main to exit with
+pub fn parse_args<I: IntoIterator<Item = String>>(args: I) -> Result<ParsedArgs, clap::Error> {
+    let cli = Cli::try_parse_from(args)?;
+    let parsed = match cli.command {
+        None => ParsedArgs::Run(Box::new(run_options(cli.run)?)),
+        Some(Command::Test { dir }) => ParsedArgs::Test(dir),
+        Some(Command::Klaus { file, cpu, start, success, test_case }) => ParsedArgs::Klaus(KlausOptions {
+            path: file,
+            variant: cpu,
+            start: start.unwrap_or(KLAUS_START_ADDR),
+            // the default success address depends on the cpu
+            success: success.unwrap_or(default_success_addr(cpu)),
+            test_case: test_case.unwrap_or(KLAUS_TEST_CASE_ADDR),
+        }),
+        Some(Command::Lorenz { dir, name }) => ParsedArgs::Lorenz(LorenzOptions { dir, first: name }),
+        Some(Command::Harte { path, cpu }) => ParsedArgs::Harte(HarteOptions { path, variant: cpu }),
+        Some(Command::Nes { file, nestest }) => ParsedArgs::Nes(NesOptions { path: file, nestest }),
+        Some(Command::Random { count, steps, cpu, seed, threads }) => {
+            ParsedArgs::Random(RandomOptions { count, steps, variant: cpu, seed, threads })
+        }
+        Some(Command::DiffState { a, b }) => ParsedArgs::DiffState(a, b),
+        Some(Command::Disasm { file, org, cpu, entry, data, out }) => ParsedArgs::Disasm(Ca65Options {
+            path: file,
+            org,
+            variant: cpu,
+            entries: entry,
+            data,
+            out,
+        }),
+    };
+    Ok(parsed)
+}
+
+// the options a run goes on with, from the flags given for it
+fn run_options(args: RunArgs) -> Result<Options, clap::Error> {
+    let mut options = Options {
+        images: args.images,
+        reset_vector: args.reset,
+        machine: args.machine,
+        rom_dir: args.rom_dir,
+        devices: args.device,
+        serial: args.serial,
+        variant: args.cpu,
+        power_on: args.power_on,
+        trace_file: args.trace,
+        trace_json: args.trace_json,
+        print_output: !args.quiet,
+        color: !args.no_color,
+        speed: args.speed,
+        clock_hz: args.clock,
+        pause_on_exec_instr: !args.free_run,
+        // under http or a debugger there's no monitor to type at
+        interactive: !args.run && args.http.is_none() && args.dap.is_none(),
+        stop_at: args.stop_at,
+        exit_code_at: args.exit_code_at,
+        bench: args.bench,
+        use_tui: args.tui,
+        cosim: args.cosim,
+        http: args.http,
+        dap: args.dap,
+        debug_info: args.dbg,
+        load_state: args.load_state,
+        save_state: args.save_state,
+        xxd_loads: args.load_xxd,
+        save_xxd: args.save_xxd,
+        record: args.record,
+        replay: args.replay,
+        trap_on_brk: args.brk_trap,
+        strict_opcodes: args.strict,
+        smc: args.smc,
+        stats: args.stats,
+        screenshot: args.screenshot,
+        ctrl_c: args.ctrl_c,
+    };
+
+    let conflict = |message: &str| Cli::command().error(ErrorKind::ArgumentConflict, message);
+    if options.debug_info.is_some() && options.dap.is_none() && !options.interactive {
+        return Err(conflict("--dbg is only used with --dap or the monitor"));
+    }
+    if options.ctrl_c == Some(CtrlCMode::Monitor) && !options.interactive {
+        return Err(conflict("--ctrl-c monitor needs the monitor, which --run, --http and --dap leave out"));
+    }
+
+    // a non-interactive run only traces when asked to write a file
+    // under http or a debugger it waits for a request unless --free-run
+    if !options.interactive {
+        let remote = options.http.is_some() || options.dap.is_some();
+        options.pause_on_exec_instr = remote && options.pause_on_exec_instr;
+        options.print_output = options.print_output && options.trace_file.is_some();
+    }
+
+    Ok(options)
+}
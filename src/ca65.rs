@@ -0,0 +1,293 @@
+// disassembly of a binary image as ca65 source that assembles back to
+// the same bytes
+//
+//   e6502r disasm rom.bin --org e000 > rom.s
+//   cl65 -t none -o rom2.bin rom.s         # rom2.bin is rom.bin
+//
+// code is found by following execution from the entry points: the
+// reset, nmi and irq vectors when the image holds them and they point
+// into it, or else its first byte, plus any --entry given. branches,
+// jumps and subroutine calls are followed; RTS, RTI, BRK, indirect
+// jumps and anything that isn't an instruction end a path. bytes never
+// reached, and --data ranges, are written as .byte, and the vectors as
+// .word. targets of branches, jumps and calls, and absolute operands
+// that point into the image, get labels named Lxxxx for their address.
+//
+// ca65 picks zero page addressing when it can, so an absolute operand
+// below $100 is written with a: to keep its encoding. opcodes ca65
+// won't take under the cpu set with .setcpu, such as the 65c02's
+// multi-byte NOPs, are written as .byte with the instruction in a
+// comment.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write as _;
+use std::fs;
+
+use crate::cpu::{instruction_text, CpuVariant};
+use crate::disasm::{addr_modes, disassemble, mode_len, AddrMode};
+use crate::memory::{Memory, MEMSIZE};
+
+pub struct Ca65Options {
+    pub path: String,
+    pub org: u16,                   // where the image loads
+    pub variant: CpuVariant,
+    pub entries: Vec<u16>,          // more places code starts
+    pub data: Vec<(u16, u16)>,      // inclusive ranges that are never code
+    pub out: Option<String>,        // the file to write, or stdout
+}
+
+const VECTORS: [(u16, &str); 3] = [(0xfffc, "reset"), (0xfffa, "nmi"), (0xfffe, "irq")];
+
+// what each byte of the image was found to be
+#[derive(Clone, Copy, PartialEq)]
+enum Byte {
+    Unknown,
+    Data,                   // a --data range
+    Code(u16),              // part of the instruction starting there
+}
+
+struct Image {
+    mem: Memory,
+    start: u16,
+    end: usize,             // one past the last byte
+    variant: CpuVariant,
+    bytes: Vec<Byte>,       // indexed from start
+}
+
+impl Image {
+    fn contains(&self, addr: u16, len: u16) -> bool {
+        addr >= self.start && addr as usize + len as usize <= self.end
+    }
+
+    fn byte(&self, addr: u16) -> Byte {
+        self.bytes[(addr - self.start) as usize]
+    }
+
+    fn mnemonic(&self, addr: u16) -> &'static str {
+        instruction_text(self.variant)[self.mem.mem[addr as usize] as usize]
+    }
+
+    fn mode(&self, addr: u16) -> AddrMode {
+        addr_modes(self.variant)[self.mem.mem[addr as usize] as usize]
+    }
+
+    fn word(&self, addr: u16) -> u16 {
+        u16::from_le_bytes([self.mem.mem[addr as usize], self.mem.mem[addr.wrapping_add(1) as usize]])
+    }
+
+    // the address an instruction branches, jumps or calls to, and the
+    // address its operand refers to
+    fn target(&self, addr: u16) -> Option<u16> {
+        let lo = self.mem.mem[addr.wrapping_add(1) as usize];
+        let hi = self.mem.mem[addr.wrapping_add(2) as usize];
+        match self.mode(addr) {
+            AddrMode::Rel => Some(addr.wrapping_add(2).wrapping_add(lo as i8 as u16)),
+            AddrMode::Zpr => Some(addr.wrapping_add(3).wrapping_add(hi as i8 as u16)),
+            AddrMode::Abs | AddrMode::Abx | AddrMode::Aby | AddrMode::Ind | AddrMode::Iax => Some(self.word(addr.wrapping_add(1))),
+            _ => None,
+        }
+    }
+
+    // whether ca65 assembles the instruction as it is written here
+    fn assembles(&self, addr: u16) -> bool {
+        let opcode = self.mem.mem[addr as usize];
+        match self.variant {
+            CpuVariant::Cmos65c02 => !matches!(self.mnemonic(addr), "NOP" | "WAI" | "STP") || opcode == 0xea,
+            _ => true,
+        }
+    }
+}
+
+pub fn run_export(options: &Ca65Options) -> Result<(), String> {
+    let data = fs::read(&options.path).map_err(|e| format!("{}: {}", options.path, e))?;
+    let start = options.org as usize;
+    if data.is_empty() || start + data.len() > MEMSIZE {
+        return Err(format!("{} doesn't fit in memory at ${:04x}", options.path, start));
+    }
+    let mut mem = Memory::new();
+    mem.mem[start..start + data.len()].copy_from_slice(&data);
+    let mut image = Image {
+        mem,
+        start: options.org,
+        end: start + data.len(),
+        variant: options.variant,
+        bytes: vec![Byte::Unknown; data.len()],
+    };
+    for &(first, last) in &options.data {
+        for addr in first..=last {
+            if image.contains(addr, 1) {
+                image.bytes[(addr - image.start) as usize] = Byte::Data;
+            }
+        }
+    }
+
+    let mut entries = options.entries.clone();
+    for (vector, _) in VECTORS {
+        if image.contains(vector, 2) && image.contains(image.word(vector), 1) {
+            entries.push(image.word(vector));
+        }
+    }
+    if entries.is_empty() {
+        entries.push(image.start);
+    }
+    let targets = trace(&mut image, entries);
+    let source = write_source(&image, &targets, &options.path);
+    match &options.out {
+        Some(path) => fs::write(path, source).map_err(|e| format!("{}: {}", path, e)),
+        None => {
+            print!("{}", source);
+            Ok(())
+        }
+    }
+}
+
+// follow execution from the entries, marking the code it passes
+// through; returns the addresses in the image that code refers to
+fn trace(image: &mut Image, mut pending: Vec<u16>) -> BTreeSet<u16> {
+    let mut targets: BTreeSet<u16> = pending.iter().copied().filter(|&addr| image.contains(addr, 1)).collect();
+    while let Some(mut addr) = pending.pop() {
+        loop {
+            if !image.contains(addr, 1) || image.byte(addr) != Byte::Unknown {
+                break;
+            }
+            let mnemonic = image.mnemonic(addr);
+            if mnemonic.is_empty() {
+                break;
+            }
+            let mode = image.mode(addr);
+            let len = mode_len(mode);
+            if !image.contains(addr, len) || (1..len).any(|i| image.byte(addr + i) != Byte::Unknown) {
+                break;
+            }
+            for i in 0..len {
+                image.bytes[(addr + i - image.start) as usize] = Byte::Code(addr);
+            }
+            if let Some(target) = image.target(addr).filter(|&target| image.contains(target, 1)) {
+                targets.insert(target);
+                let jumps = matches!(mnemonic, "JMP" | "JSR") && mode == AddrMode::Abs;
+                if jumps || matches!(mode, AddrMode::Rel | AddrMode::Zpr) {
+                    pending.push(target);
+                }
+            }
+            if matches!(mnemonic, "JMP" | "RTS" | "RTI" | "BRK" | "BRA" | "STP") {
+                break;
+            }
+            addr += len;
+        }
+    }
+    targets
+}
+
+// the source for the image once its code is known
+fn write_source(image: &Image, targets: &BTreeSet<u16>, path: &str) -> String {
+    // a label is put where an instruction or data byte starts; one in
+    // the middle of an instruction is named from the instruction's
+    let mut labels = BTreeMap::new();
+    for &target in targets {
+        let at = match image.byte(target) {
+            Byte::Code(start) => start,
+            _ => target,
+        };
+        labels.insert(at, format!("L{:04X}", at));
+    }
+    let name = |addr: u16| -> Option<String> {
+        if !image.contains(addr, 1) {
+            return None;
+        }
+        let at = match image.byte(addr) {
+            Byte::Code(start) => start,
+            _ => addr,
+        };
+        let label = labels.get(&at)?;
+        Some(if at == addr { label.clone() } else { format!("{}+{}", label, addr - at) })
+    };
+
+    let mut out = String::new();
+    let cpu = if image.variant == CpuVariant::Cmos65c02 { "65C02" } else { "6502" };
+    let _ = writeln!(out, "; {} disassembled by e6502r\n", path);
+    let _ = writeln!(out, "        .setcpu \"{}\"", cpu);
+    let _ = writeln!(out, "        .org    ${:04X}\n", image.start);
+
+    let mut addr = image.start as usize;
+    while addr < image.end {
+        let at = addr as u16;
+        if let Some(label) = labels.get(&at) {
+            let _ = writeln!(out, "{}:", label);
+        }
+        match image.byte(at) {
+            Byte::Code(_) => {
+                let len = mode_len(image.mode(at));
+                if image.assembles(at) {
+                    let _ = writeln!(out, "        {}", instruction(image, at, &name));
+                } else {
+                    let bytes: Vec<String> = (0..len).map(|i| format!("${:02X}", image.mem.mem[(at + i) as usize])).collect();
+                    let (text, _) = disassemble(image.variant, &image.mem, at);
+                    let _ = writeln!(out, "        .byte   {:<24}; {}", bytes.join(","), text.to_lowercase());
+                }
+                addr += len as usize;
+            }
+            _ => {
+                // a vector pointing into the image
+                if let Some((_, vector)) = VECTORS.iter().find(|(vector, _)| *vector == at).filter(|_| is_data(image, at, 2, &labels)) {
+                    let target = image.word(at);
+                    let operand = name(target).unwrap_or(format!("${:04X}", target));
+                    let _ = writeln!(out, "        .word   {:<24}; {}", operand, vector);
+                    addr += 2;
+                    continue;
+                }
+                let mut len = 1;
+                while len < 8 && is_data(image, at, len + 1, &labels) && !VECTORS.iter().any(|(vector, _)| *vector == at + len) {
+                    len += 1;
+                }
+                let bytes: Vec<String> = (0..len).map(|i| format!("${:02X}", image.mem.mem[(at + i) as usize])).collect();
+                let _ = writeln!(out, "        .byte   {}", bytes.join(","));
+                addr += len as usize;
+            }
+        }
+    }
+    out
+}
+
+// whether the len bytes from addr are all data with no label after the first
+fn is_data(image: &Image, addr: u16, len: u16, labels: &BTreeMap<u16, String>) -> bool {
+    image.contains(addr, len)
+        && (0..len).all(|i| !matches!(image.byte(addr + i), Byte::Code(_)))
+        && (1..len).all(|i| !labels.contains_key(&(addr + i)))
+}
+
+// an instruction in ca65's syntax
+fn instruction(image: &Image, addr: u16, name: &dyn Fn(u16) -> Option<String>) -> String {
+    let mnemonic = image.mnemonic(addr).to_lowercase();
+    let lo = image.mem.mem[addr.wrapping_add(1) as usize];
+    let word = image.word(addr.wrapping_add(1));
+    let target = image.target(addr).unwrap_or(0);
+    let address = |value: u16| name(value).unwrap_or(format!("${:04X}", value));
+    // an absolute operand that would fit in zero page
+    let absolute = |value: u16| {
+        let prefix = if word < 0x100 && !matches!(image.mnemonic(addr), "JMP" | "JSR") { "a:" } else { "" };
+        format!("{}{}", prefix, address(value))
+    };
+    let operand = match image.mode(addr) {
+        AddrMode::Imp => String::new(),
+        AddrMode::Acc => String::from("a"),
+        AddrMode::Imm => format!("#${:02X}", lo),
+        AddrMode::Zpg => format!("${:02X}", lo),
+        AddrMode::Zpx => format!("${:02X},x", lo),
+        AddrMode::Zpy => format!("${:02X},y", lo),
+        AddrMode::Abs => absolute(word),
+        AddrMode::Abx => format!("{},x", absolute(word)),
+        AddrMode::Aby => format!("{},y", absolute(word)),
+        AddrMode::Ind => format!("({})", address(word)),
+        AddrMode::Izx => format!("(${:02X},x)", lo),
+        AddrMode::Izy => format!("(${:02X}),y", lo),
+        AddrMode::Rel => address(target),
+        AddrMode::Izp => format!("(${:02X})", lo),
+        AddrMode::Iax => format!("({},x)", address(word)),
+        AddrMode::Zpr => format!("${:02X},{}", lo, address(target)),
+    };
+    if operand.is_empty() {
+        mnemonic
+    } else {
+        format!("{:<8}{}", mnemonic, operand)
+    }
+}
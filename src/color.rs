@@ -0,0 +1,94 @@
+// ansi colour for the trace, the monitor and disassembly
+//
+// mnemonics are cyan, addresses yellow and registers that changed since
+// the last line of the trace bold red. colour is on when stdout is a
+// terminal, unless NO_COLOR is set or --no-color is given; a trace
+// written to a file with --trace is never coloured.
+
+use std::env;
+use std::io;
+use std::io::IsTerminal;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::cpu::Cpu;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+const RESET: &str = "\x1b[0m";
+const MNEMONIC: &str = "\x1b[36m";
+const ADDRESS: &str = "\x1b[33m";
+const CHANGED: &str = "\x1b[1;31m";
+
+// the registers compared from line to line: a, x, y, sp and p
+pub type Registers = [u8; 5];
+
+// turn colour on if it is allowed and stdout will show it
+pub fn init(allowed: bool) {
+    let on = allowed && io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none();
+    ENABLED.store(on, Ordering::Relaxed);
+}
+
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+fn paint(code: &str, text: &str) -> String {
+    if enabled() {
+        format!("{}{}{}", code, text, RESET)
+    } else {
+        text.to_string()
+    }
+}
+
+pub fn registers_of(cpu: &Cpu) -> Registers {
+    cpu.registers().map(|(_, value)| value)
+}
+
+// an address like $0400
+pub fn address(addr: u16) -> String {
+    paint(ADDRESS, &format!("${:04x}", addr))
+}
+
+// a value that changed since it was last shown
+pub fn changed(text: &str) -> String {
+    paint(CHANGED, text)
+}
+
+// a disassembled instruction, its mnemonic and the addresses in its
+// operand coloured
+pub fn instruction(text: &str) -> String {
+    if !enabled() {
+        return text.to_string();
+    }
+    let (mnemonic, operand) = text.split_once(' ').unwrap_or((text, ""));
+    let mut out = paint(MNEMONIC, mnemonic);
+    if !operand.is_empty() {
+        out.push(' ');
+    }
+    // a $ and the hex digits after it, unless it's an immediate
+    let mut rest = operand;
+    while let Some(start) = rest.find('$') {
+        let digits = rest[start + 1..].find(|c: char| !c.is_ascii_hexdigit()).unwrap_or(rest.len() - start - 1);
+        let end = start + 1 + digits;
+        if rest[..start].ends_with('#') {
+            out.push_str(&rest[..end]);
+        } else {
+            out.push_str(&rest[..start]);
+            out.push_str(&paint(ADDRESS, &rest[start..end]));
+        }
+        rest = &rest[end..];
+    }
+    out.push_str(rest);
+    out
+}
+
+// the registers as the cpu displays them, with any that differ from
+// before highlighted
+pub fn registers(cpu: &Cpu, before: Option<&Registers>) -> String {
+    let now = registers_of(cpu);
+    cpu.registers_text(|index, text| match index {
+        None => paint(ADDRESS, &text),
+        Some(index) if before.is_some_and(|before| before[index] != now[index]) => paint(CHANGED, &text),
+        Some(_) => text,
+    })
+}
@@ -0,0 +1,99 @@
+// dumps of the machine for looking into what went wrong
+//
+// when the cpu jams on an opcode it can't execute, the monitor's input
+// ends under it, or the emulator panics, a dump is written to
+// e6502r-core-MILLIS.txt in the current directory, MILLIS being the
+// time in milliseconds since the unix epoch. it holds the reason, the
+// registers, the last HISTORY_SIZE instructions executed with the
+// registers before each, and all of memory as a hex dump, with runs of
+// identical lines shown once.
+
+use std::fs;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::cpu::Cpu;
+use crate::disasm;
+use crate::memory::{Memory, MEMSIZE};
+
+const HISTORY_SIZE: usize = 64;
+
+// the registers before an instruction
+#[derive(Clone, Copy, Default)]
+struct Entry {
+    pc: u16,
+    ac: u8,
+    xr: u8,
+    yr: u8,
+    sp: u8,
+    st: u8,
+}
+
+// the last instructions executed, oldest overwritten first
+pub struct History {
+    entries: [Entry; HISTORY_SIZE],
+    next: usize,
+    count: usize,
+}
+
+impl History {
+    pub fn new() -> History {
+        History { entries: [Entry::default(); HISTORY_SIZE], next: 0, count: 0 }
+    }
+
+    // call before executing each instruction
+    pub fn record(&mut self, cpu: &Cpu) {
+        self.entries[self.next] = Entry { pc: cpu.pc, ac: cpu.ac, xr: cpu.xr, yr: cpu.yr, sp: cpu.sp, st: cpu.status() };
+        self.next = (self.next + 1) % HISTORY_SIZE;
+        self.count = (self.count + 1).min(HISTORY_SIZE);
+    }
+
+    fn oldest_first(&self) -> impl Iterator<Item = &Entry> {
+        let start = (self.next + HISTORY_SIZE - self.count) % HISTORY_SIZE;
+        (0..self.count).map(move |n| &self.entries[(start + n) % HISTORY_SIZE])
+    }
+}
+
+// write a dump and say where it went
+pub fn write_dump(reason: &str, cpu: &Cpu, mem: &Memory, history: &History) {
+    let millis = SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_millis()).unwrap_or(0);
+    let path = format!("e6502r-core-{}.txt", millis);
+    match fs::write(&path, dump(reason, cpu, mem, history)) {
+        Ok(()) => eprintln!("e6502r: {}; core dump written to {}", reason, path),
+        Err(e) => eprintln!("e6502r: {}; can't write core dump {}: {}", reason, path, e),
+    }
+}
+
+fn dump(reason: &str, cpu: &Cpu, mem: &Memory, history: &History) -> String {
+    let mut text = String::from("e6502r core dump\n");
+    text.push_str(&format!("reason: {}\n", reason));
+    text.push_str(&format!("cpu: {}\n", cpu.variant));
+    text.push_str(&format!("cycles: {}\n", cpu.cycles));
+    text.push_str(&format!("{}\n", cpu));
+
+    // disassembled from memory as it is now
+    text.push_str("\nrecent instructions, oldest first:\n");
+    for entry in history.oldest_first() {
+        let (instruction, _) = disasm::disassemble(cpu.variant, mem, entry.pc);
+        text.push_str(&format!("  ${:04x}  {:<16} A=${:02x} X=${:02x} Y=${:02x} SP=${:02x} P=${:02x}\n",
+            entry.pc, instruction, entry.ac, entry.xr, entry.yr, entry.sp, entry.st));
+    }
+    let (instruction, _) = disasm::disassemble(cpu.variant, mem, cpu.pc);
+    text.push_str(&format!("> ${:04x}  {}\n", cpu.pc, instruction));
+
+    text.push_str("\nmemory:\n");
+    let mut repeating = false;
+    for start in (0..MEMSIZE).step_by(16) {
+        let line = &mem.mem[start..start + 16];
+        if start > 0 && start + 16 < MEMSIZE && line == &mem.mem[start - 16..start] {
+            if !repeating {
+                text.push_str("*\n");
+                repeating = true;
+            }
+            continue;
+        }
+        repeating = false;
+        let bytes: Vec<String> = line.iter().map(|byte| format!("{:02x}", byte)).collect();
+        text.push_str(&format!("{:04x}: {}\n", start, bytes.join(" ")));
+    }
+    text
+}
@@ -0,0 +1,183 @@
+
+// lock-step co-simulation against a reference emulator
+//
+// the reference runs as a separate process and talks a line protocol
+// over its stdin/stdout, all numbers in hex:
+//
+//   mem AAAA BBBB...         set memory from AAAA to the bytes given
+//   regs PC SP A X Y P       set the registers
+//   step                     execute one instruction
+//
+// `mem` and `regs` are answered with "ok"; `step` is answered with the
+// registers afterwards and any memory writes the instruction made:
+//
+//   PC SP A X Y P [AAAA=BB ...]
+//
+// after each instruction the reply is compared with this core and the
+// first difference stops execution.
+
+use std::collections::BTreeMap;
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+
+use crate::cpu::Cpu;
+use crate::memory::Memory;
+
+const PAGE_SIZE: usize = 256;
+
+// registers in the order the protocol sends them
+struct Registers {
+    pc: u16,
+    sp: u8,
+    ac: u8,
+    xr: u8,
+    yr: u8,
+    st: u8,
+}
+
+impl Registers {
+    fn of(cpu: &Cpu) -> Registers {
+        Registers { pc: cpu.pc, sp: cpu.sp, ac: cpu.ac, xr: cpu.xr, yr: cpu.yr, st: cpu.status() }
+    }
+
+    fn to_line(&self) -> String {
+        format!("{:04x} {:02x} {:02x} {:02x} {:02x} {:02x}", self.pc, self.sp, self.ac, self.xr, self.yr, self.st)
+    }
+}
+
+pub struct CoSim {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+    shadow: Vec<u8>,            // memory as the reference last saw it
+}
+
+impl CoSim {
+    // start the reference and give it the current machine state
+    pub fn start(command: &str, cpu: &Cpu, mem: &Memory) -> Result<CoSim, String> {
+        let mut words = command.split_whitespace();
+        let program = words.next().ok_or("--cosim needs a command")?;
+        let mut child = Command::new(program)
+            .args(words)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("{}: {}", program, e))?;
+        let stdin = child.stdin.take().ok_or("cannot write to the reference")?;
+        let stdout = BufReader::new(child.stdout.take().ok_or("cannot read from the reference")?);
+
+        let mut cosim = CoSim { child, stdin, stdout, shadow: vec![0; mem.mem.len()] };
+        // everything starts out different so the first sync sends it all
+        for (i, byte) in mem.mem.iter().enumerate() {
+            cosim.shadow[i] = !byte;
+        }
+        cosim.sync(cpu, mem)?;
+        Ok(cosim)
+    }
+
+    fn send(&mut self, line: &str) -> Result<String, String> {
+        writeln!(self.stdin, "{}", line)
+            .and_then(|_| self.stdin.flush())
+            .map_err(|e| format!("reference: {}", e))?;
+        let mut reply = String::new();
+        match self.stdout.read_line(&mut reply) {
+            Ok(0) => Err(String::from("reference exited")),
+            Ok(_) => Ok(reply.trim().to_string()),
+            Err(e) => Err(format!("reference: {}", e)),
+        }
+    }
+
+    fn send_ok(&mut self, line: &str) -> Result<(), String> {
+        match self.send(line)?.as_str() {
+            "ok" => Ok(()),
+            reply => Err(format!("reference answered '{}' to '{}'", reply, line.split(' ').next().unwrap_or(""))),
+        }
+    }
+
+    // bring the reference up to date after the state was changed
+    // outside of execution, e.g. from the monitor
+    pub fn sync(&mut self, cpu: &Cpu, mem: &Memory) -> Result<(), String> {
+        for page in 0..mem.mem.len() / PAGE_SIZE {
+            let range = page * PAGE_SIZE..(page + 1) * PAGE_SIZE;
+            if mem.mem[range.clone()] != self.shadow[range.clone()] {
+                let bytes: String = mem.mem[range.clone()].iter().map(|b| format!("{:02x}", b)).collect();
+                self.send_ok(&format!("mem {:04x} {}", range.start, bytes))?;
+                self.shadow[range.clone()].copy_from_slice(&mem.mem[range]);
+            }
+        }
+        self.send_ok(&format!("regs {}", Registers::of(cpu).to_line()))
+    }
+
+    // step the reference and compare it with the instruction this core
+    // just executed; Err describes the divergence
+    pub fn check(&mut self, cpu: &Cpu, mem: &Memory) -> Result<(), String> {
+        let reply = self.send("step")?;
+        let (registers, writes) = parse_step(&reply).ok_or(format!("bad reply from reference '{}'", reply))?;
+
+        // writes made by this core, found against the shadow copy
+        let mut ours: Vec<(u16, u8)> = Vec::new();
+        for (addr, byte) in mem.mem.iter().enumerate() {
+            if self.shadow[addr] != *byte {
+                ours.push((addr as u16, *byte));
+            }
+        }
+        // keep the last value written to each address, dropping writes
+        // that left a byte unchanged since this core cannot see them
+        let mut last: BTreeMap<u16, u8> = BTreeMap::new();
+        for (addr, byte) in writes {
+            last.insert(addr, byte);
+        }
+        let theirs: Vec<(u16, u8)> = last.into_iter()
+            .filter(|(addr, byte)| self.shadow[*addr as usize] != *byte)
+            .collect();
+        for (addr, byte) in &ours {
+            self.shadow[*addr as usize] = *byte;
+        }
+
+        let mine = Registers::of(cpu);
+        let mut problems = Vec::new();
+        if mine.to_line() != registers.to_line() {
+            problems.push(format!("registers differ\n  this core: PC SP A X Y P {}\n  reference: PC SP A X Y P {}",
+                mine.to_line(), registers.to_line()));
+        }
+        if ours != theirs {
+            let show = |writes: &[(u16, u8)]| writes.iter()
+                .map(|(addr, byte)| format!("${:04x}=${:02x}", addr, byte))
+                .collect::<Vec<String>>()
+                .join(" ");
+            problems.push(format!("memory writes differ\n  this core: {}\n  reference: {}",
+                show(&ours), show(&theirs)));
+        }
+        if problems.is_empty() { Ok(()) } else { Err(problems.join("\n")) }
+    }
+}
+
+impl Drop for CoSim {
+    fn drop(&mut self) {
+        let _result = self.child.kill();
+        let _result = self.child.wait();
+    }
+}
+
+// parse "PC SP A X Y P [AAAA=BB ...]"
+fn parse_step(reply: &str) -> Option<(Registers, Vec<(u16, u8)>)> {
+    let words: Vec<&str> = reply.split_whitespace().collect();
+    if words.len() < 6 {
+        return None;
+    }
+    let byte = |i: usize| u8::from_str_radix(words[i], 16).ok();
+    let registers = Registers {
+        pc: u16::from_str_radix(words[0], 16).ok()?,
+        sp: byte(1)?,
+        ac: byte(2)?,
+        xr: byte(3)?,
+        yr: byte(4)?,
+        st: byte(5)?,
+    };
+    let mut writes = Vec::new();
+    for word in &words[6..] {
+        let (addr, value) = word.split_once('=')?;
+        writes.push((u16::from_str_radix(addr, 16).ok()?, u8::from_str_radix(value, 16).ok()?));
+    }
+    Some((registers, writes))
+}
@@ -0,0 +1,2968 @@
+
+// 6502 cpu: registers, stack and one handler per opcode
+//
+// handlers named iXX implement the NMOS 6502 opcodes; the 65C02 uses
+// the same table with its own cXX handlers filling the gaps
+//
+// data accesses go through Memory::read/write so mapped devices see
+// them; opcodes, operands, pointers, vectors and the stack come
+// straight from ram
+//
+// most instructions set N and Z from their result, and most of those
+// flags are overwritten before anything looks at them, so the result is
+// kept instead and the flags worked out when the status register is
+// read, pushed or branched on
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::dynarec::Dynarec;
+use crate::memory::Memory;
+
+pub const RESET_VECTOR_LOBYTE: usize = 0xfffc;  // reset vector memory location
+pub const RESET_VECTOR_HIBYTE: usize = 0xfffd;
+pub const BREAK_VECTOR_LOBYTE: usize = 0xfffe;  // break vector memory location
+pub const BREAK_VECTOR_HIBYTE: usize = 0xffff;
+pub const NMI_VECTOR_LOBYTE: usize = 0xfffa;    // nmi vector memory location
+pub const NMI_VECTOR_HIBYTE: usize = 0xfffb;
+pub const STATUS_FLAGS_CARRY: u8 = 0x01;        // carry status bit
+pub const STATUS_FLAGS_ZERO: u8 = 0x02;         // zero status bit
+pub const STATUS_BIT_INT_DIS: u8 = 0x04;        // interrup disable status bit
+pub const STATUS_FLAGS_DECIMAL: u8 = 0x08;      // decimal mode status bit
+pub const STATUS_FLAGS_BREAK: u8 = 0x10;        // break status bit
+pub const STATUS_FLAGS_UNUSED: u8 = 0x20;       // unused status bit
+pub const STATUS_FLAGS_OVERFLOW: u8 = 0x40;     // overflow status bit
+pub const STATUS_FLAGS_NEGATIVE: u8 = 0x80;     // negative status bit
+
+// cpu variant
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum CpuVariant {
+    Nmos6502,
+    Cmos65c02,
+    Ricoh2a03,              // the NES cpu: a 6502 without decimal mode
+}
+
+impl fmt::Display for CpuVariant {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CpuVariant::Nmos6502 => write!(f, "6502"),
+            CpuVariant::Cmos65c02 => write!(f, "65c02"),
+            CpuVariant::Ricoh2a03 => write!(f, "2a03"),
+        }
+    }
+}
+
+impl FromStr for CpuVariant {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<CpuVariant, String> {
+        match text.to_lowercase().as_str() {
+            "6502" => Ok(CpuVariant::Nmos6502),
+            "65c02" => Ok(CpuVariant::Cmos65c02),
+            "2a03" => Ok(CpuVariant::Ricoh2a03),
+            other => Err(format!("unknown cpu variant '{}'", other)),
+        }
+    }
+}
+
+// instruction text by opcode
+pub const INSTRUCTION_TEXT: [&str; 256] = [
+	"BRK",  "ORA","",     "","",        "ORA","ASL",     "","PHP","ORA", "ASL", "","",       "ORA", "ASL", "", // 00
+	"BPL",  "ORA","",     "","",        "ORA","ASL",     "","CLC","ORA", "",    "","",       "ORA", "ASL", "", // 10
+	"JSR",  "AND","",     "","BIT",     "AND","ROL",     "","PLP","AND", "ROL", "","BIT",    "AND", "ROL", "", // 20
+	"BMI",  "AND","",     "","",        "AND","ROL",     "","SEC","AND", "",    "","",       "AND", "ROL", "", // 30
+	"RTI",  "EOR","",     "","",        "EOR","LSR",     "","PHA","EOR", "LSR", "","JMP",    "EOR", "LSR", "", // 40
+	"BVC",  "EOR","",     "","",        "EOR","LSR",     "","CLI","EOR", "",    "","",       "EOR", "LSR", "", // 50
+	"RTS",  "ADC","",     "","",        "ADC","ROR",     "","PLA","ADC", "ROR", "","JMP",    "ADC", "ROR", "", // 60
+	"BVS",  "ADC","",     "","",        "ADC","ROR",     "","SEI","ADC", "",    "","",       "ADC", "ROR", "", // 70
+	"",     "STA","",     "","STY",     "STA","STX",     "","DEY","",    "TXA", "","STY",    "STA", "STX", "", // 80
+	"BCC",  "STA","",     "","STY",     "STA","STX",     "","TYA","STA", "TXS", "","",       "STA", "",    "", // 90
+	"LDY",  "LDA","LDX",  "","LDY",     "LDA","LDX",     "","TAY","LDA", "TAX", "","LDY",    "LDA", "LDX", "", // a0
+	"BCS",  "LDA","",     "","LDY",     "LDA","LDX",     "","CLV","LDA", "TSX", "","LDY",    "LDA", "LDX", "", // b0
+	"CPY",  "CMP","",     "","CPY",     "CMP","DEC",     "","INY","CMP", "DEX", "","CPY",    "CMP", "DEC", "", // c0
+	"BNE",  "CMP","",     "","",        "CMP","DEC",     "","CLD","CMP", "",    "","",       "CMP", "DEC", "", // d0
+	"CPX",  "SBC","",     "","CPX",     "SBC","INC",     "","INX","SBC", "NOP", "","CPX",    "SBC", "INC", "", // e0
+	"BEQ",  "SBC","",     "","",        "SBC","INC",     "","SED","SBC", "",    "","",       "SBC", "INC", ""  // f0
+];
+
+// instruction text by opcode for the 65c02
+pub const INSTRUCTION_TEXT_65C02: [&str; 256] = [
+    "BRK",  "ORA",  "NOP",  "NOP",  "TSB",  "ORA",  "ASL",  "RMB0", "PHP",  "ORA",  "ASL",  "NOP",  "TSB",  "ORA",  "ASL",  "BBR0",   // 00
+    "BPL",  "ORA",  "ORA",  "NOP",  "TRB",  "ORA",  "ASL",  "RMB1", "CLC",  "ORA",  "INC",  "NOP",  "TRB",  "ORA",  "ASL",  "BBR1",   // 10
+    "JSR",  "AND",  "NOP",  "NOP",  "BIT",  "AND",  "ROL",  "RMB2", "PLP",  "AND",  "ROL",  "NOP",  "BIT",  "AND",  "ROL",  "BBR2",   // 20
+    "BMI",  "AND",  "AND",  "NOP",  "BIT",  "AND",  "ROL",  "RMB3", "SEC",  "AND",  "DEC",  "NOP",  "BIT",  "AND",  "ROL",  "BBR3",   // 30
+    "RTI",  "EOR",  "NOP",  "NOP",  "NOP",  "EOR",  "LSR",  "RMB4", "PHA",  "EOR",  "LSR",  "NOP",  "JMP",  "EOR",  "LSR",  "BBR4",   // 40
+    "BVC",  "EOR",  "EOR",  "NOP",  "NOP",  "EOR",  "LSR",  "RMB5", "CLI",  "EOR",  "PHY",  "NOP",  "NOP",  "EOR",  "LSR",  "BBR5",   // 50
+    "RTS",  "ADC",  "NOP",  "NOP",  "STZ",  "ADC",  "ROR",  "RMB6", "PLA",  "ADC",  "ROR",  "NOP",  "JMP",  "ADC",  "ROR",  "BBR6",   // 60
+    "BVS",  "ADC",  "ADC",  "NOP",  "STZ",  "ADC",  "ROR",  "RMB7", "SEI",  "ADC",  "PLY",  "NOP",  "JMP",  "ADC",  "ROR",  "BBR7",   // 70
+    "BRA",  "STA",  "NOP",  "NOP",  "STY",  "STA",  "STX",  "SMB0", "DEY",  "BIT",  "TXA",  "NOP",  "STY",  "STA",  "STX",  "BBS0",   // 80
+    "BCC",  "STA",  "STA",  "NOP",  "STY",  "STA",  "STX",  "SMB1", "TYA",  "STA",  "TXS",  "NOP",  "STZ",  "STA",  "STZ",  "BBS1",   // 90
+    "LDY",  "LDA",  "LDX",  "NOP",  "LDY",  "LDA",  "LDX",  "SMB2", "TAY",  "LDA",  "TAX",  "NOP",  "LDY",  "LDA",  "LDX",  "BBS2",   // a0
+    "BCS",  "LDA",  "LDA",  "NOP",  "LDY",  "LDA",  "LDX",  "SMB3", "CLV",  "LDA",  "TSX",  "NOP",  "LDY",  "LDA",  "LDX",  "BBS3",   // b0
+    "CPY",  "CMP",  "NOP",  "NOP",  "CPY",  "CMP",  "DEC",  "SMB4", "INY",  "CMP",  "DEX",  "WAI",  "CPY",  "CMP",  "DEC",  "BBS4",   // c0
+    "BNE",  "CMP",  "CMP",  "NOP",  "NOP",  "CMP",  "DEC",  "SMB5", "CLD",  "CMP",  "PHX",  "STP",  "NOP",  "CMP",  "DEC",  "BBS5",   // d0
+    "CPX",  "SBC",  "NOP",  "NOP",  "CPX",  "SBC",  "INC",  "SMB6", "INX",  "SBC",  "NOP",  "NOP",  "CPX",  "SBC",  "INC",  "BBS6",   // e0
+    "BEQ",  "SBC",  "SBC",  "NOP",  "NOP",  "SBC",  "INC",  "SMB7", "SED",  "SBC",  "PLX",  "NOP",  "NOP",  "SBC",  "INC",  "BBS7",   // f0
+];
+
+// CPU
+#[derive(Clone)]
+pub struct Cpu {
+    pub pc: u16,
+    pub sp: u8,
+    pub ac: u8,
+    pub xr: u8,
+    pub yr: u8,
+    st: u8,             // read with status(); N and Z are stale while nz holds a result
+    nz: Option<u8>,     // the last result, for N and Z to be worked out from when looked at
+    pub cycles: u64,    // total cycles executed
+    pub variant: CpuVariant,
+    pub waiting: bool,  // a WAI has run and no interrupt has ended it
+    pub decoded: DecodeCache,   // only used with the decode-cache feature
+    pub blocks: Dynarec,        // only used with the dynarec feature
+}
+
+impl Default for Cpu {
+    fn default() -> Cpu {
+        Cpu::new()
+    }
+}
+
+impl Cpu {
+    pub fn new() -> Cpu {
+        Cpu {
+            pc: 0,
+            sp: 0,
+            ac: 0,
+            xr: 0,
+            yr: 0,
+            st: STATUS_FLAGS_UNUSED,
+            nz: None,
+            cycles: 0,
+            variant: CpuVariant::Nmos6502,
+            waiting: false,
+            decoded: DecodeCache::new(),
+            blocks: Dynarec::new(),
+        }
+    }
+
+    // the status register, with N and Z brought up to date
+    pub fn status(&self) -> u8 {
+        match self.nz {
+            Some(value) => {
+                let zero = if value == 0 { STATUS_FLAGS_ZERO } else { 0 };
+                (self.st & !(STATUS_FLAGS_NEGATIVE | STATUS_FLAGS_ZERO)) | (value & STATUS_FLAGS_NEGATIVE) | zero
+            }
+            None => self.st,
+        }
+    }
+
+    // B isn't a flag in the register, only in the copies pushed by BRK
+    // and PHP, and bit 5 always reads as set
+    pub fn set_status(&mut self, st: u8) {
+        self.st = (st & !STATUS_FLAGS_BREAK) | STATUS_FLAGS_UNUSED;
+        self.nz = None;
+    }
+}
+
+// the flags of a status value as nv-bdizc, upper case when set
+pub fn flags_text(status: u8) -> String {
+    "NV-BDIZC".chars().enumerate()
+        .map(|(bit, set)| match (status & (0x80 >> bit) != 0, bit) {
+            (_, 2) => '-',
+            (true, _) => set,
+            (false, _) => set.to_ascii_lowercase(),
+        })
+        .collect()
+}
+
+impl Cpu {
+    // the registers shown after the PC, by name
+    pub fn registers(&self) -> [(&'static str, u8); 5] {
+        [("A", self.ac), ("X", self.xr), ("Y", self.yr), ("SP", self.sp), ("P", self.status())]
+    }
+
+    //   PC=$0400 A=$00 X=$00 Y=$00 SP=$ff P=$24 [nv-bdIzc]
+    // with the PC's address, and each of the registers by its index,
+    // passed through mark, which the trace colours them with
+    pub fn registers_text(&self, mark: impl Fn(Option<usize>, String) -> String) -> String {
+        let mut out = format!("PC={}", mark(None, format!("${:04x}", self.pc)));
+        for (index, (name, value)) in self.registers().into_iter().enumerate() {
+            out.push(' ');
+            out.push_str(&mark(Some(index), format!("{}=${:02x}", name, value)));
+        }
+        out.push_str(&format!(" [{}]", flags_text(self.status())));
+        out
+    }
+}
+
+impl fmt::Display for Cpu {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.registers_text(|_, text| text))
+    }
+}
+
+impl fmt::Debug for Cpu {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Cpu")
+            .field("variant", &self.variant)
+            .field("pc", &format_args!("${:04x}", self.pc))
+            .field("a", &format_args!("${:02x}", self.ac))
+            .field("x", &format_args!("${:02x}", self.xr))
+            .field("y", &format_args!("${:02x}", self.yr))
+            .field("sp", &format_args!("${:02x}", self.sp))
+            .field("p", &format_args!("${:02x} [{}]", self.status(), flags_text(self.status())))
+            .field("cycles", &self.cycles)
+            .finish()
+    }
+}
+
+// convert two bytes (hi and lo) to a word
+pub fn byte_to_word(lobyte: u8, hibyte: u8) -> u16 {
+    ((hibyte as u16) << 8) | lobyte as u16
+}
+
+// reset cpu
+// set stack pointer to 0xff
+// set program counter to reset vector
+pub fn reset_cpu(cpu: &mut Cpu, mem: &Memory) {
+    cpu.sp = 0xff;
+    cpu.pc = byte_to_word(mem.mem[RESET_VECTOR_LOBYTE], mem.mem[RESET_VECTOR_HIBYTE]);
+    cpu.waiting = false;
+}
+
+// push the pc and status and jump through a vector, as for an IRQ
+// the status is pushed with the break bit clear
+fn interrupt(cpu: &mut Cpu, mem: &mut Memory, lobyte: usize, hibyte: usize) {
+    end_wait(cpu);
+    mem.dummy_read(cpu.pc);
+    mem.dummy_read(cpu.pc);
+    push_to_stack((cpu.pc >> 8) as u8, cpu, mem);
+    push_to_stack((cpu.pc & 0xff) as u8, cpu, mem);
+    push_to_stack(cpu.status(), cpu, mem);
+    enter_handler(cpu);
+    cpu.pc = byte_to_word(mem.fetch(lobyte as u16), mem.fetch(hibyte as u16));
+    cpu.cycles += 7;
+}
+
+// the flags on entering an interrupt or BRK handler: interrupts are
+// disabled, and the 65c02 clears decimal mode where the 6502 leaves it
+fn enter_handler(cpu: &mut Cpu) {
+    cpu.st |= STATUS_BIT_INT_DIS;
+    if cpu.variant == CpuVariant::Cmos65c02 {
+        cpu.st &= !STATUS_FLAGS_DECIMAL;
+    }
+}
+
+// a WAI that was waiting for an interrupt is finished, and the pc
+// moves on past it
+fn end_wait(cpu: &mut Cpu) {
+    if cpu.waiting {
+        cpu.waiting = false;
+        cpu.pc = cpu.pc.wrapping_add(1);
+    }
+}
+
+// take a maskable interrupt unless interrupts are disabled
+// returns true if it was taken; when they are disabled an irq still
+// ends a WAI, and the cpu carries on after it without the vector
+pub fn irq(cpu: &mut Cpu, mem: &mut Memory) -> bool {
+    if cpu.st & STATUS_BIT_INT_DIS != 0 {
+        end_wait(cpu);
+        return false;
+    }
+    interrupt(cpu, mem, BREAK_VECTOR_LOBYTE, BREAK_VECTOR_HIBYTE);
+    true
+}
+
+// take a non-maskable interrupt
+pub fn nmi(cpu: &mut Cpu, mem: &mut Memory) {
+    interrupt(cpu, mem, NMI_VECTOR_LOBYTE, NMI_VECTOR_HIBYTE);
+}
+
+// pushes a u8 to the stack
+fn push_to_stack(b:u8, cpu: &mut Cpu, mem: &mut Memory)
+{
+    let stack_base:usize = 0x0100;
+    let memloc:usize = stack_base + cpu.sp as usize;
+	mem.mem[memloc] = b;
+    mem.log_access(memloc as u16, b, true);
+    cpu.sp = cpu.sp.wrapping_sub(1);
+}
+
+// pulls a u8 from the stack
+fn pull_from_stack(cpu: &mut Cpu, mem: &mut Memory) -> u8
+{
+    cpu.sp = cpu.sp.wrapping_add(1);
+    let stack_base:usize = 0x0100;
+    let memloc:usize = stack_base + cpu.sp as usize;
+    let value = mem.mem[memloc];
+    mem.log_access(memloc as u16, value, false);
+    value
+}
+
+// read the byte following the opcode
+fn operand_byte(cpu: &Cpu, mem: &mut Memory) -> u8 {
+    mem.fetch(cpu.pc.wrapping_add(1))
+}
+
+// read the word following the opcode
+fn operand_word(cpu: &Cpu, mem: &mut Memory) -> u16 {
+    let lo = mem.fetch(cpu.pc.wrapping_add(1));
+    byte_to_word(lo, mem.fetch(cpu.pc.wrapping_add(2)))
+}
+
+// the cycles the bus is driven without the cpu using what it reads
+// (or writes) are only noted on the bus log, never made, so a device
+// doesn't see them
+
+// a one byte instruction reads the byte after it, and ignores it
+fn implied(cpu: &Cpu, mem: &mut Memory) {
+    mem.dummy_read(cpu.pc.wrapping_add(1));
+}
+
+// the cycle spent moving the stack pointer before a pull, or before
+// JSR pushes, reads the stack where it points
+fn stack_cycle(cpu: &Cpu, mem: &mut Memory) {
+    mem.dummy_read(0x0100 | cpu.sp as u16);
+}
+
+// the cycle an indexed mode spends adding the index: the 6502 reads
+// the partly worked out address, the 65c02 the instruction's last byte
+// again, length bytes from the opcode
+fn index_cycle(cpu: &Cpu, mem: &mut Memory, partial: u16, length: u16) {
+    if cpu.variant == CpuVariant::Cmos65c02 {
+        mem.dummy_read(cpu.pc.wrapping_add(length - 1));
+    } else {
+        mem.dummy_read(partial);
+    }
+}
+
+// the cycle a read-modify-write spends on the change: the 6502 writes
+// the old value back, the 65c02 reads it again
+fn modify_cycle(cpu: &Cpu, mem: &mut Memory, addr: u16, value: u8) {
+    if cpu.variant == CpuVariant::Cmos65c02 {
+        mem.dummy_read(addr);
+    } else {
+        mem.dummy_write(addr, value);
+    }
+}
+
+// effective addresses for each addressing mode
+// the indexed modes also return whether a page boundary was crossed,
+// which costs reads an extra cycle; writes and read-modify-writes
+// always take it, and use the _write versions
+
+fn addr_zpg(cpu: &Cpu, mem: &mut Memory) -> u16 {
+    operand_byte(cpu, mem) as u16
+}
+
+fn addr_zpx(cpu: &Cpu, mem: &mut Memory) -> u16 {
+    let zp = operand_byte(cpu, mem);
+    index_cycle(cpu, mem, zp as u16, 2);
+    zp.wrapping_add(cpu.xr) as u16
+}
+
+fn addr_zpy(cpu: &Cpu, mem: &mut Memory) -> u16 {
+    let zp = operand_byte(cpu, mem);
+    index_cycle(cpu, mem, zp as u16, 2);
+    zp.wrapping_add(cpu.yr) as u16
+}
+
+fn addr_abs(cpu: &Cpu, mem: &mut Memory) -> u16 {
+    operand_word(cpu, mem)
+}
+
+// a base address plus an index, and whether a page was crossed; the
+// fix-up cycle is taken if it was, or always if fixed
+fn indexed(cpu: &Cpu, mem: &mut Memory, base: u16, index: u8, length: u16, fixed: bool) -> (u16, bool) {
+    let addr = base.wrapping_add(index as u16);
+    let crossed = (base ^ addr) & 0xff00 != 0;
+    if crossed || fixed {
+        index_cycle(cpu, mem, (base & 0xff00) | (addr & 0x00ff), length);
+    }
+    (addr, crossed)
+}
+
+fn addr_abx(cpu: &Cpu, mem: &mut Memory) -> (u16, bool) {
+    let base = operand_word(cpu, mem);
+    indexed(cpu, mem, base, cpu.xr, 3, false)
+}
+
+fn addr_abx_write(cpu: &Cpu, mem: &mut Memory) -> u16 {
+    let base = operand_word(cpu, mem);
+    indexed(cpu, mem, base, cpu.xr, 3, true).0
+}
+
+// the 65c02's shifts and rotates only take the fix-up cycle when a
+// page is crossed, the 6502's always do; true if it was taken
+fn addr_abx_shift(cpu: &Cpu, mem: &mut Memory) -> (u16, bool) {
+    let base = operand_word(cpu, mem);
+    let fixed = cpu.variant != CpuVariant::Cmos65c02;
+    let (addr, crossed) = indexed(cpu, mem, base, cpu.xr, 3, fixed);
+    (addr, crossed || fixed)
+}
+
+fn addr_aby(cpu: &Cpu, mem: &mut Memory) -> (u16, bool) {
+    let base = operand_word(cpu, mem);
+    indexed(cpu, mem, base, cpu.yr, 3, false)
+}
+
+fn addr_aby_write(cpu: &Cpu, mem: &mut Memory) -> u16 {
+    let base = operand_word(cpu, mem);
+    indexed(cpu, mem, base, cpu.yr, 3, true).0
+}
+
+// a pointer read from zero page, wrapping within it
+fn zero_page_pointer(mem: &mut Memory, zp: u8) -> u16 {
+    let lo = mem.fetch(zp as u16);
+    byte_to_word(lo, mem.fetch(zp.wrapping_add(1) as u16))
+}
+
+fn addr_izx(cpu: &Cpu, mem: &mut Memory) -> u16 {
+    let zp = operand_byte(cpu, mem);
+    index_cycle(cpu, mem, zp as u16, 2);
+    zero_page_pointer(mem, zp.wrapping_add(cpu.xr))
+}
+
+fn addr_izy(cpu: &Cpu, mem: &mut Memory) -> (u16, bool) {
+    let zp = operand_byte(cpu, mem);
+    let base = zero_page_pointer(mem, zp);
+    indexed(cpu, mem, base, cpu.yr, 2, false)
+}
+
+fn addr_izy_write(cpu: &Cpu, mem: &mut Memory) -> u16 {
+    let zp = operand_byte(cpu, mem);
+    let base = zero_page_pointer(mem, zp);
+    indexed(cpu, mem, base, cpu.yr, 2, true).0
+}
+
+// 65c02 zero page indirect
+fn addr_izp(cpu: &Cpu, mem: &mut Memory) -> u16 {
+    let zp = operand_byte(cpu, mem);
+    zero_page_pointer(mem, zp)
+}
+
+// set or clear status bits
+fn set_flag(cpu: &mut Cpu, flag: u8, on: bool) {
+    if flag & (STATUS_FLAGS_NEGATIVE | STATUS_FLAGS_ZERO) != 0 {
+        cpu.set_status(cpu.status());
+    }
+    if on {
+        cpu.st |= flag;
+    } else {
+        cpu.st &= !flag;
+    }
+}
+
+// set negative and zero from a result; they are only worked out when
+// something looks at them, which most results never have happen
+fn set_nz(cpu: &mut Cpu, value: u8) {
+    cpu.nz = Some(value);
+}
+
+fn lda(cpu: &mut Cpu, value: u8) {
+    cpu.ac = value;
+    set_nz(cpu, value);
+}
+
+fn ldx(cpu: &mut Cpu, value: u8) {
+    cpu.xr = value;
+    set_nz(cpu, value);
+}
+
+fn ldy(cpu: &mut Cpu, value: u8) {
+    cpu.yr = value;
+    set_nz(cpu, value);
+}
+
+fn and(cpu: &mut Cpu, value: u8) {
+    cpu.ac &= value;
+    set_nz(cpu, cpu.ac);
+}
+
+fn ora(cpu: &mut Cpu, value: u8) {
+    cpu.ac |= value;
+    set_nz(cpu, cpu.ac);
+}
+
+fn eor(cpu: &mut Cpu, value: u8) {
+    cpu.ac ^= value;
+    set_nz(cpu, cpu.ac);
+}
+
+// true if ADC and SBC work in BCD; the 2a03 ignores the D flag
+fn decimal_mode(cpu: &Cpu) -> bool {
+    cpu.st & STATUS_FLAGS_DECIMAL != 0 && cpu.variant != CpuVariant::Ricoh2a03
+}
+
+// add with carry
+// in decimal mode the accumulator and carry are BCD adjusted, and V
+// comes from the sum of the sign extended high nibbles before the high
+// digit is adjusted. the 6502 takes N from that sum too and leaves Z as
+// the binary addition set it; the 65c02 sets N and Z from the result
+// and takes an extra cycle
+fn adc(cpu: &mut Cpu, mem: &mut Memory, value: u8) {
+    let carry = (cpu.st & STATUS_FLAGS_CARRY) as u16;
+    let sum = cpu.ac as u16 + value as u16 + carry;
+    let result = sum as u8;
+    set_flag(cpu, STATUS_FLAGS_OVERFLOW, (!(cpu.ac ^ value) & (cpu.ac ^ result) & 0x80) != 0);
+    set_nz(cpu, result);
+
+    if decimal_mode(cpu) {
+        let mut lo = (cpu.ac & 0x0f) as u16 + (value & 0x0f) as u16 + carry;
+        if lo >= 0x0a {
+            lo = ((lo + 0x06) & 0x0f) + 0x10;
+        }
+        let mut total = (cpu.ac & 0xf0) as u16 + (value & 0xf0) as u16 + lo;
+        let signed = (cpu.ac & 0xf0) as i8 as i16 + (value & 0xf0) as i8 as i16 + lo as i16;
+        set_flag(cpu, STATUS_FLAGS_OVERFLOW, !(-128..=127).contains(&signed));
+        if cpu.variant != CpuVariant::Cmos65c02 {
+            set_flag(cpu, STATUS_FLAGS_NEGATIVE, total & 0x80 != 0);
+        }
+        if total >= 0xa0 {
+            total += 0x60;
+        }
+        set_flag(cpu, STATUS_FLAGS_CARRY, total >= 0x100);
+        if cpu.variant == CpuVariant::Cmos65c02 {
+            set_nz(cpu, total as u8);
+            mem.repeat_cycle();
+            cpu.cycles += 1;
+        }
+        cpu.ac = total as u8;
+    } else {
+        set_flag(cpu, STATUS_FLAGS_CARRY, sum > 0xff);
+        cpu.ac = result;
+    }
+}
+
+// subtract with borrow (carry clear means borrow)
+// the flags come from the binary subtraction, except that the 65c02
+// sets N and Z from the decimal result and takes an extra cycle
+fn sbc(cpu: &mut Cpu, mem: &mut Memory, value: u8) {
+    let borrow = 1 - (cpu.st & STATUS_FLAGS_CARRY) as i16;
+    let diff = cpu.ac as i16 - value as i16 - borrow;
+    let result = diff as u8;
+    set_flag(cpu, STATUS_FLAGS_OVERFLOW, ((cpu.ac ^ value) & (cpu.ac ^ result) & 0x80) != 0);
+    set_flag(cpu, STATUS_FLAGS_CARRY, diff >= 0);
+    set_nz(cpu, result);
+
+    if decimal_mode(cpu) && cpu.variant == CpuVariant::Cmos65c02 {
+        let lo = (cpu.ac & 0x0f) as i16 - (value & 0x0f) as i16 - borrow;
+        let mut total = diff;
+        if total < 0 {
+            total -= 0x60;
+        }
+        if lo < 0 {
+            total -= 0x06;
+        }
+        cpu.ac = total as u8;
+        set_nz(cpu, cpu.ac);
+        mem.repeat_cycle();
+        cpu.cycles += 1;
+    } else if decimal_mode(cpu) {
+        let mut lo = (cpu.ac & 0x0f) as i16 - (value & 0x0f) as i16 - borrow;
+        if lo < 0 {
+            lo = ((lo - 0x06) & 0x0f) - 0x10;
+        }
+        let mut total = (cpu.ac & 0xf0) as i16 - (value & 0xf0) as i16 + lo;
+        if total < 0 {
+            total -= 0x60;
+        }
+        cpu.ac = total as u8;
+    } else {
+        cpu.ac = result;
+    }
+}
+
+// CMP, CPX and CPY
+fn compare(cpu: &mut Cpu, reg: u8, value: u8) {
+    set_flag(cpu, STATUS_FLAGS_CARRY, reg >= value);
+    set_nz(cpu, reg.wrapping_sub(value));
+}
+
+fn bit(cpu: &mut Cpu, value: u8) {
+    set_flag(cpu, STATUS_FLAGS_ZERO, cpu.ac & value == 0);
+    set_flag(cpu, STATUS_FLAGS_NEGATIVE, value & 0x80 != 0);
+    set_flag(cpu, STATUS_FLAGS_OVERFLOW, value & 0x40 != 0);
+}
+
+fn asl(cpu: &mut Cpu, value: u8) -> u8 {
+    let result = value << 1;
+    set_flag(cpu, STATUS_FLAGS_CARRY, value & 0x80 != 0);
+    set_nz(cpu, result);
+    result
+}
+
+fn lsr(cpu: &mut Cpu, value: u8) -> u8 {
+    let result = value >> 1;
+    set_flag(cpu, STATUS_FLAGS_CARRY, value & 0x01 != 0);
+    set_nz(cpu, result);
+    result
+}
+
+fn rol(cpu: &mut Cpu, value: u8) -> u8 {
+    let result = (value << 1) | (cpu.st & STATUS_FLAGS_CARRY);
+    set_flag(cpu, STATUS_FLAGS_CARRY, value & 0x80 != 0);
+    set_nz(cpu, result);
+    result
+}
+
+fn ror(cpu: &mut Cpu, value: u8) -> u8 {
+    let result = (value >> 1) | ((cpu.st & STATUS_FLAGS_CARRY) << 7);
+    set_flag(cpu, STATUS_FLAGS_CARRY, value & 0x01 != 0);
+    set_nz(cpu, result);
+    result
+}
+
+fn inc(cpu: &mut Cpu, value: u8) -> u8 {
+    let result = value.wrapping_add(1);
+    set_nz(cpu, result);
+    result
+}
+
+fn dec(cpu: &mut Cpu, value: u8) -> u8 {
+    let result = value.wrapping_sub(1);
+    set_nz(cpu, result);
+    result
+}
+
+// relative branch; taken branches cost a cycle, or two if they
+// land on another page
+fn branch(cpu: &mut Cpu, mem: &mut Memory, taken: bool) {
+    let offset = operand_byte(cpu, mem) as i8;
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 2;
+    if taken {
+        branch_to(cpu, mem, offset);
+    }
+}
+
+// 65c02 BBR/BBS: branch if a bit of a zero page byte is clear/set
+fn branch_on_bit(cpu: &mut Cpu, mem: &mut Memory, mask: u8, set: bool) {
+    let addr = addr_zpg(cpu, mem);
+    let value = mem.read(addr);
+    mem.dummy_read(addr);
+    let offset = mem.fetch(cpu.pc.wrapping_add(2)) as i8;
+    cpu.pc = cpu.pc.wrapping_add(3);
+    cpu.cycles += 5;
+    if (value & mask != 0) == set {
+        branch_to(cpu, mem, offset);
+    }
+}
+
+// take a branch from the instruction after it: the next opcode is read
+// and thrown away, and so is the target on the old page if it's moved
+fn branch_to(cpu: &mut Cpu, mem: &mut Memory, offset: i8) {
+    let target = cpu.pc.wrapping_add(offset as u16);
+    mem.dummy_read(cpu.pc);
+    if (target ^ cpu.pc) & 0xff00 != 0 {
+        mem.dummy_read((cpu.pc & 0xff00) | (target & 0x00ff));
+        cpu.cycles += 2;
+    } else {
+        cpu.cycles += 1;
+    }
+    cpu.pc = target;
+}
+
+// prototype for cpu operation (opcode)
+pub type CpuOp = fn(cpu: &mut Cpu, mem: &mut Memory);
+
+// for unused op codes just do nothing
+fn ixx(_cpu: &mut Cpu, _mem: &mut Memory) {
+    // place holder for op codes not implemented
+}
+
+// the 65c02 treats its unused opcodes as NOPs of various lengths
+// named by their size in bytes and cycles
+
+fn nop_b1_c1(cpu: &mut Cpu, _mem: &mut Memory) {
+    cpu.pc = cpu.pc.wrapping_add(1);
+    cpu.cycles += 1;
+}
+
+fn nop_b2_c2(cpu: &mut Cpu, mem: &mut Memory) {
+    operand_byte(cpu, mem);
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 2;
+}
+
+// reads a zero page byte, and ignores it
+fn nop_b2_c3(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_zpg(cpu, mem);
+    mem.dummy_read(addr);
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 3;
+}
+
+// and as $nn,X
+fn nop_b2_c4(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_zpx(cpu, mem);
+    mem.dummy_read(addr);
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 4;
+}
+
+// and as $nnnn
+fn nop_b3_c4(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_abs(cpu, mem);
+    mem.dummy_read(addr);
+    cpu.pc = cpu.pc.wrapping_add(3);
+    cpu.cycles += 4;
+}
+
+// reads $ffnn over and over, where nn is the low byte of its operand
+fn nop_b3_c8(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = 0xff00 | (operand_word(cpu, mem) & 0x00ff);
+    for _ in 0..5 {
+        mem.dummy_read(addr);
+    }
+    cpu.pc = cpu.pc.wrapping_add(3);
+    cpu.cycles += 8;
+}
+
+// op code array for a cpu variant
+pub fn opcode_table(variant: CpuVariant) -> &'static [CpuOp; 256] {
+    match variant {
+        CpuVariant::Nmos6502 | CpuVariant::Ricoh2a03 => &CPU_OPS,
+        CpuVariant::Cmos65c02 => &CPU_OPS_65C02,
+    }
+}
+
+// the instruction an opcode decodes to for a cpu variant
+pub fn decode(variant: CpuVariant, opcode: u8) -> Instruction {
+    match variant {
+        CpuVariant::Nmos6502 | CpuVariant::Ricoh2a03 => DECODE[opcode as usize],
+        CpuVariant::Cmos65c02 => DECODE_65C02[opcode as usize],
+    }
+}
+
+// execute a decoded instruction with a single match, an alternative to
+// calling through CPU_OPS
+pub fn execute(cpu: &mut Cpu, mem: &mut Memory, instruction: Instruction) {
+    match instruction {
+        Instruction::Unimplemented => ixx(cpu, mem),
+        Instruction::Brk => i00(cpu, mem),
+        Instruction::OraIzx => i01(cpu, mem),
+        Instruction::OraZpg => i05(cpu, mem),
+        Instruction::AslZpg => i06(cpu, mem),
+        Instruction::Php => i08(cpu, mem),
+        Instruction::OraImm => i09(cpu, mem),
+        Instruction::AslAcc => i0a(cpu, mem),
+        Instruction::OraAbs => i0d(cpu, mem),
+        Instruction::AslAbs => i0e(cpu, mem),
+        Instruction::Bpl => i10(cpu, mem),
+        Instruction::OraIzy => i11(cpu, mem),
+        Instruction::OraZpx => i15(cpu, mem),
+        Instruction::AslZpx => i16(cpu, mem),
+        Instruction::Clc => i18(cpu, mem),
+        Instruction::OraAby => i19(cpu, mem),
+        Instruction::OraAbx => i1d(cpu, mem),
+        Instruction::AslAbx => i1e(cpu, mem),
+        Instruction::JsrAbs => i20(cpu, mem),
+        Instruction::AndIzx => i21(cpu, mem),
+        Instruction::BitZpg => i24(cpu, mem),
+        Instruction::AndZpg => i25(cpu, mem),
+        Instruction::RolZpg => i26(cpu, mem),
+        Instruction::Plp => i28(cpu, mem),
+        Instruction::AndImm => i29(cpu, mem),
+        Instruction::RolAcc => i2a(cpu, mem),
+        Instruction::BitAbs => i2c(cpu, mem),
+        Instruction::AndAbs => i2d(cpu, mem),
+        Instruction::RolAbs => i2e(cpu, mem),
+        Instruction::Bmi => i30(cpu, mem),
+        Instruction::AndIzy => i31(cpu, mem),
+        Instruction::AndZpx => i35(cpu, mem),
+        Instruction::RolZpx => i36(cpu, mem),
+        Instruction::Sec => i38(cpu, mem),
+        Instruction::AndAby => i39(cpu, mem),
+        Instruction::AndAbx => i3d(cpu, mem),
+        Instruction::RolAbx => i3e(cpu, mem),
+        Instruction::Rti => i40(cpu, mem),
+        Instruction::EorIzx => i41(cpu, mem),
+        Instruction::EorZpg => i45(cpu, mem),
+        Instruction::LsrZpg => i46(cpu, mem),
+        Instruction::Pha => i48(cpu, mem),
+        Instruction::EorImm => i49(cpu, mem),
+        Instruction::LsrAcc => i4a(cpu, mem),
+        Instruction::JmpAbs => i4c(cpu, mem),
+        Instruction::EorAbs => i4d(cpu, mem),
+        Instruction::LsrAbs => i4e(cpu, mem),
+        Instruction::Bvc => i50(cpu, mem),
+        Instruction::EorIzy => i51(cpu, mem),
+        Instruction::EorZpx => i55(cpu, mem),
+        Instruction::LsrZpx => i56(cpu, mem),
+        Instruction::Cli => i58(cpu, mem),
+        Instruction::EorAby => i59(cpu, mem),
+        Instruction::EorAbx => i5d(cpu, mem),
+        Instruction::LsrAbx => i5e(cpu, mem),
+        Instruction::Rts => i60(cpu, mem),
+        Instruction::AdcIzx => i61(cpu, mem),
+        Instruction::AdcZpg => i65(cpu, mem),
+        Instruction::RorZpg => i66(cpu, mem),
+        Instruction::Pla => i68(cpu, mem),
+        Instruction::AdcImm => i69(cpu, mem),
+        Instruction::RorAcc => i6a(cpu, mem),
+        Instruction::JmpInd => i6c(cpu, mem),
+        Instruction::AdcAbs => i6d(cpu, mem),
+        Instruction::RorAbs => i6e(cpu, mem),
+        Instruction::Bvs => i70(cpu, mem),
+        Instruction::AdcIzy => i71(cpu, mem),
+        Instruction::AdcZpx => i75(cpu, mem),
+        Instruction::RorZpx => i76(cpu, mem),
+        Instruction::Sei => i78(cpu, mem),
+        Instruction::AdcAby => i79(cpu, mem),
+        Instruction::AdcAbx => i7d(cpu, mem),
+        Instruction::RorAbx => i7e(cpu, mem),
+        Instruction::StaIzx => i81(cpu, mem),
+        Instruction::StyZpg => i84(cpu, mem),
+        Instruction::StaZpg => i85(cpu, mem),
+        Instruction::StxZpg => i86(cpu, mem),
+        Instruction::Dey => i88(cpu, mem),
+        Instruction::Txa => i8a(cpu, mem),
+        Instruction::StyAbs => i8c(cpu, mem),
+        Instruction::StaAbs => i8d(cpu, mem),
+        Instruction::StxAbs => i8e(cpu, mem),
+        Instruction::Bcc => i90(cpu, mem),
+        Instruction::StaIzy => i91(cpu, mem),
+        Instruction::StyZpx => i94(cpu, mem),
+        Instruction::StaZpx => i95(cpu, mem),
+        Instruction::StxZpy => i96(cpu, mem),
+        Instruction::Tya => i98(cpu, mem),
+        Instruction::StaAby => i99(cpu, mem),
+        Instruction::Txs => i9a(cpu, mem),
+        Instruction::StaAbx => i9d(cpu, mem),
+        Instruction::LdyImm => ia0(cpu, mem),
+        Instruction::LdaIzx => ia1(cpu, mem),
+        Instruction::LdxImm => ia2(cpu, mem),
+        Instruction::LdyZpg => ia4(cpu, mem),
+        Instruction::LdaZpg => ia5(cpu, mem),
+        Instruction::LdxZpg => ia6(cpu, mem),
+        Instruction::Tay => ia8(cpu, mem),
+        Instruction::LdaImm => ia9(cpu, mem),
+        Instruction::Tax => iaa(cpu, mem),
+        Instruction::LdyAbs => iac(cpu, mem),
+        Instruction::LdaAbs => iad(cpu, mem),
+        Instruction::LdxAbs => iae(cpu, mem),
+        Instruction::Bcs => ib0(cpu, mem),
+        Instruction::LdaIzy => ib1(cpu, mem),
+        Instruction::LdyZpx => ib4(cpu, mem),
+        Instruction::LdaZpx => ib5(cpu, mem),
+        Instruction::LdxZpy => ib6(cpu, mem),
+        Instruction::Clv => ib8(cpu, mem),
+        Instruction::LdaAby => ib9(cpu, mem),
+        Instruction::Tsx => iba(cpu, mem),
+        Instruction::LdyAbx => ibc(cpu, mem),
+        Instruction::LdaAbx => ibd(cpu, mem),
+        Instruction::LdxAby => ibe(cpu, mem),
+        Instruction::CpyImm => ic0(cpu, mem),
+        Instruction::CmpIzx => ic1(cpu, mem),
+        Instruction::CpyZpg => ic4(cpu, mem),
+        Instruction::CmpZpg => ic5(cpu, mem),
+        Instruction::DecZpg => ic6(cpu, mem),
+        Instruction::Iny => ic8(cpu, mem),
+        Instruction::CmpImm => ic9(cpu, mem),
+        Instruction::Dex => ica(cpu, mem),
+        Instruction::CpyAbs => icc(cpu, mem),
+        Instruction::CmpAbs => icd(cpu, mem),
+        Instruction::DecAbs => ice(cpu, mem),
+        Instruction::Bne => id0(cpu, mem),
+        Instruction::CmpIzy => id1(cpu, mem),
+        Instruction::CmpZpx => id5(cpu, mem),
+        Instruction::DecZpx => id6(cpu, mem),
+        Instruction::Cld => id8(cpu, mem),
+        Instruction::CmpAby => id9(cpu, mem),
+        Instruction::CmpAbx => idd(cpu, mem),
+        Instruction::DecAbx => ide(cpu, mem),
+        Instruction::CpxImm => ie0(cpu, mem),
+        Instruction::SbcIzx => ie1(cpu, mem),
+        Instruction::CpxZpg => ie4(cpu, mem),
+        Instruction::SbcZpg => ie5(cpu, mem),
+        Instruction::IncZpg => ie6(cpu, mem),
+        Instruction::Inx => ie8(cpu, mem),
+        Instruction::SbcImm => ie9(cpu, mem),
+        Instruction::Nop => iea(cpu, mem),
+        Instruction::CpxAbs => iec(cpu, mem),
+        Instruction::SbcAbs => ied(cpu, mem),
+        Instruction::IncAbs => iee(cpu, mem),
+        Instruction::Beq => if0(cpu, mem),
+        Instruction::SbcIzy => if1(cpu, mem),
+        Instruction::SbcZpx => if5(cpu, mem),
+        Instruction::IncZpx => if6(cpu, mem),
+        Instruction::Sed => if8(cpu, mem),
+        Instruction::SbcAby => if9(cpu, mem),
+        Instruction::SbcAbx => ifd(cpu, mem),
+        Instruction::IncAbx => ife(cpu, mem),
+        Instruction::NopB2C2 => nop_b2_c2(cpu, mem),
+        Instruction::NopB1C1 => nop_b1_c1(cpu, mem),
+        Instruction::TsbZpg => c04(cpu, mem),
+        Instruction::Rmb0Zpg => c07(cpu, mem),
+        Instruction::TsbAbs => c0c(cpu, mem),
+        Instruction::Bbr0 => c0f(cpu, mem),
+        Instruction::OraIzp => c12(cpu, mem),
+        Instruction::TrbZpg => c14(cpu, mem),
+        Instruction::Rmb1Zpg => c17(cpu, mem),
+        Instruction::IncAcc => c1a(cpu, mem),
+        Instruction::TrbAbs => c1c(cpu, mem),
+        Instruction::Bbr1 => c1f(cpu, mem),
+        Instruction::Rmb2Zpg => c27(cpu, mem),
+        Instruction::Bbr2 => c2f(cpu, mem),
+        Instruction::AndIzp => c32(cpu, mem),
+        Instruction::BitZpx => c34(cpu, mem),
+        Instruction::Rmb3Zpg => c37(cpu, mem),
+        Instruction::DecAcc => c3a(cpu, mem),
+        Instruction::BitAbx => c3c(cpu, mem),
+        Instruction::Bbr3 => c3f(cpu, mem),
+        Instruction::NopB2C3 => nop_b2_c3(cpu, mem),
+        Instruction::Rmb4Zpg => c47(cpu, mem),
+        Instruction::Bbr4 => c4f(cpu, mem),
+        Instruction::EorIzp => c52(cpu, mem),
+        Instruction::NopB2C4 => nop_b2_c4(cpu, mem),
+        Instruction::Rmb5Zpg => c57(cpu, mem),
+        Instruction::Phy => c5a(cpu, mem),
+        Instruction::NopB3C8 => nop_b3_c8(cpu, mem),
+        Instruction::Bbr5 => c5f(cpu, mem),
+        Instruction::StzZpg => c64(cpu, mem),
+        Instruction::Rmb6Zpg => c67(cpu, mem),
+        Instruction::JmpIndCmos => c6c(cpu, mem),
+        Instruction::Bbr6 => c6f(cpu, mem),
+        Instruction::AdcIzp => c72(cpu, mem),
+        Instruction::StzZpx => c74(cpu, mem),
+        Instruction::Rmb7Zpg => c77(cpu, mem),
+        Instruction::Ply => c7a(cpu, mem),
+        Instruction::JmpIax => c7c(cpu, mem),
+        Instruction::Bbr7 => c7f(cpu, mem),
+        Instruction::Bra => c80(cpu, mem),
+        Instruction::Smb0Zpg => c87(cpu, mem),
+        Instruction::BitImm => c89(cpu, mem),
+        Instruction::Bbs0 => c8f(cpu, mem),
+        Instruction::StaIzp => c92(cpu, mem),
+        Instruction::Smb1Zpg => c97(cpu, mem),
+        Instruction::StzAbs => c9c(cpu, mem),
+        Instruction::StzAbx => c9e(cpu, mem),
+        Instruction::Bbs1 => c9f(cpu, mem),
+        Instruction::Smb2Zpg => ca7(cpu, mem),
+        Instruction::Bbs2 => caf(cpu, mem),
+        Instruction::LdaIzp => cb2(cpu, mem),
+        Instruction::Smb3Zpg => cb7(cpu, mem),
+        Instruction::Bbs3 => cbf(cpu, mem),
+        Instruction::Smb4Zpg => cc7(cpu, mem),
+        Instruction::Wai => ccb(cpu, mem),
+        Instruction::Bbs4 => ccf(cpu, mem),
+        Instruction::CmpIzp => cd2(cpu, mem),
+        Instruction::Smb5Zpg => cd7(cpu, mem),
+        Instruction::Phx => cda(cpu, mem),
+        Instruction::Stp => cdb(cpu, mem),
+        Instruction::NopB3C4 => nop_b3_c4(cpu, mem),
+        Instruction::Bbs5 => cdf(cpu, mem),
+        Instruction::Smb6Zpg => ce7(cpu, mem),
+        Instruction::Bbs6 => cef(cpu, mem),
+        Instruction::SbcIzp => cf2(cpu, mem),
+        Instruction::Smb7Zpg => cf7(cpu, mem),
+        Instruction::Plx => cfa(cpu, mem),
+        Instruction::Bbs7 => cff(cpu, mem),
+    }
+}
+
+// instructions decoded by address, so a loop isn't decoded again each
+// time around. each entry is keyed by the opcode it was decoded from: a
+// write that changes the opcode at an address, whether the program
+// modifying itself, a bank switch or the monitor, makes the entry miss
+// and the new opcode is decoded in its place. operands are read as the
+// instruction executes, so writes to them need nothing done.
+#[derive(Clone)]
+pub struct DecodeCache {
+    variant: CpuVariant,
+    entries: Vec<(u8, Instruction)>,   // empty until first used
+}
+
+impl Default for DecodeCache {
+    fn default() -> DecodeCache {
+        DecodeCache::new()
+    }
+}
+
+impl DecodeCache {
+    pub fn new() -> DecodeCache {
+        DecodeCache { variant: CpuVariant::Nmos6502, entries: Vec::new() }
+    }
+
+    // the instruction for the opcode at addr
+    #[inline(always)]
+    pub fn fetch(&mut self, variant: CpuVariant, addr: u16, opcode: u8) -> Instruction {
+        if self.variant != variant || self.entries.is_empty() {
+            // every entry starts as opcode 00 decoded, which is right for it
+            self.variant = variant;
+            self.entries = vec![(0x00, decode(variant, 0x00)); 0x10000];
+        }
+        let entry = &mut self.entries[addr as usize];
+        if entry.0 != opcode {
+            *entry = (opcode, decode(variant, opcode));
+        }
+        entry.1
+    }
+}
+
+// true if the opcode has a handler other than the ixx place holder
+pub fn is_implemented(variant: CpuVariant, opcode: u8) -> bool {
+    decode(variant, opcode) != Instruction::Unimplemented
+}
+
+// instruction text by opcode for a cpu variant
+pub fn instruction_text(variant: CpuVariant) -> &'static [&'static str; 256] {
+    match variant {
+        CpuVariant::Nmos6502 | CpuVariant::Ricoh2a03 => &INSTRUCTION_TEXT,
+        CpuVariant::Cmos65c02 => &INSTRUCTION_TEXT_65C02,
+    }
+}
+
+// BRK (00)
+fn i00(cpu: &mut Cpu, mem: &mut Memory) {
+    operand_byte(cpu, mem);
+    cpu.pc = cpu.pc.wrapping_add(2);
+    push_to_stack((cpu.pc >> 8) as u8, cpu, mem);
+    push_to_stack((cpu.pc & 0xff) as u8, cpu, mem);
+    push_to_stack(cpu.status() | STATUS_FLAGS_BREAK, cpu, mem);
+    enter_handler(cpu);
+    cpu.pc = byte_to_word(mem.fetch(BREAK_VECTOR_LOBYTE as u16), mem.fetch(BREAK_VECTOR_HIBYTE as u16));
+    cpu.cycles += 7;
+}
+
+// ORA ($nn,X) (01)
+fn i01(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_izx(cpu, mem);
+    let value = mem.read(addr);
+    ora(cpu, value);
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 6;
+}
+
+// ORA $nn (05)
+fn i05(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_zpg(cpu, mem);
+    let value = mem.read(addr);
+    ora(cpu, value);
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 3;
+}
+
+// ASL $nn (06)
+fn i06(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_zpg(cpu, mem);
+    let value = mem.read(addr);
+    modify_cycle(cpu, mem, addr, value);
+    mem.write(addr, asl(cpu, value));
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 5;
+}
+
+// PHP (08)
+fn i08(cpu: &mut Cpu, mem: &mut Memory) {
+    implied(cpu, mem);
+    push_to_stack(cpu.status() | STATUS_FLAGS_BREAK, cpu, mem);
+    cpu.pc = cpu.pc.wrapping_add(1);
+    cpu.cycles += 3;
+}
+
+// ORA #$nn (09)
+fn i09(cpu: &mut Cpu, mem: &mut Memory) {
+    let value = operand_byte(cpu, mem);
+    ora(cpu, value);
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 2;
+}
+
+// ASL A (0A)
+fn i0a(cpu: &mut Cpu, mem: &mut Memory) {
+    implied(cpu, mem);
+    cpu.ac = asl(cpu, cpu.ac);
+    cpu.pc = cpu.pc.wrapping_add(1);
+    cpu.cycles += 2;
+}
+
+// ORA $nnnn (0D)
+fn i0d(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_abs(cpu, mem);
+    let value = mem.read(addr);
+    ora(cpu, value);
+    cpu.pc = cpu.pc.wrapping_add(3);
+    cpu.cycles += 4;
+}
+
+// ASL $nnnn (0E)
+fn i0e(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_abs(cpu, mem);
+    let value = mem.read(addr);
+    modify_cycle(cpu, mem, addr, value);
+    mem.write(addr, asl(cpu, value));
+    cpu.pc = cpu.pc.wrapping_add(3);
+    cpu.cycles += 6;
+}
+
+// BPL $nnnn (10)
+fn i10(cpu: &mut Cpu, mem: &mut Memory) {
+    let taken = cpu.status() & STATUS_FLAGS_NEGATIVE == 0;
+    branch(cpu, mem, taken);
+}
+
+// ORA ($nn),Y (11)
+fn i11(cpu: &mut Cpu, mem: &mut Memory) {
+    let (addr, crossed) = addr_izy(cpu, mem);
+    let value = mem.read(addr);
+    ora(cpu, value);
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 5 + crossed as u64;
+}
+
+// ORA $nn,X (15)
+fn i15(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_zpx(cpu, mem);
+    let value = mem.read(addr);
+    ora(cpu, value);
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 4;
+}
+
+// ASL $nn,X (16)
+fn i16(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_zpx(cpu, mem);
+    let value = mem.read(addr);
+    modify_cycle(cpu, mem, addr, value);
+    mem.write(addr, asl(cpu, value));
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 6;
+}
+
+// CLC (18)
+fn i18(cpu: &mut Cpu, mem: &mut Memory) {
+    implied(cpu, mem);
+    cpu.st &= !STATUS_FLAGS_CARRY;
+    cpu.pc = cpu.pc.wrapping_add(1);
+    cpu.cycles += 2;
+}
+
+// ORA $nnnn,Y (19)
+fn i19(cpu: &mut Cpu, mem: &mut Memory) {
+    let (addr, crossed) = addr_aby(cpu, mem);
+    let value = mem.read(addr);
+    ora(cpu, value);
+    cpu.pc = cpu.pc.wrapping_add(3);
+    cpu.cycles += 4 + crossed as u64;
+}
+
+// ORA $nnnn,X (1D)
+fn i1d(cpu: &mut Cpu, mem: &mut Memory) {
+    let (addr, crossed) = addr_abx(cpu, mem);
+    let value = mem.read(addr);
+    ora(cpu, value);
+    cpu.pc = cpu.pc.wrapping_add(3);
+    cpu.cycles += 4 + crossed as u64;
+}
+
+// ASL $nnnn,X (1E)
+fn i1e(cpu: &mut Cpu, mem: &mut Memory) {
+    let (addr, fixed) = addr_abx_shift(cpu, mem);
+    let value = mem.read(addr);
+    modify_cycle(cpu, mem, addr, value);
+    mem.write(addr, asl(cpu, value));
+    cpu.pc = cpu.pc.wrapping_add(3);
+    cpu.cycles += 6 + fixed as u64;
+}
+
+// JSR $nnnn (20)
+fn i20(cpu: &mut Cpu, mem: &mut Memory) {
+    // pushes the address of the last byte of the instruction, which
+    // is only read after the pushes
+    let lo = operand_byte(cpu, mem);
+    stack_cycle(cpu, mem);
+    let ret = cpu.pc.wrapping_add(2);
+    push_to_stack((ret >> 8) as u8, cpu, mem);
+    push_to_stack((ret & 0xff) as u8, cpu, mem);
+    cpu.pc = byte_to_word(lo, mem.fetch(ret));
+    cpu.cycles += 6;
+}
+
+// AND ($nn,X) (21)
+fn i21(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_izx(cpu, mem);
+    let value = mem.read(addr);
+    and(cpu, value);
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 6;
+}
+
+// BIT $nn (24)
+fn i24(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_zpg(cpu, mem);
+    let value = mem.read(addr);
+    bit(cpu, value);
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 3;
+}
+
+// AND $nn (25)
+fn i25(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_zpg(cpu, mem);
+    let value = mem.read(addr);
+    and(cpu, value);
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 3;
+}
+
+// ROL $nn (26)
+fn i26(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_zpg(cpu, mem);
+    let value = mem.read(addr);
+    modify_cycle(cpu, mem, addr, value);
+    mem.write(addr, rol(cpu, value));
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 5;
+}
+
+// PLP (28)
+fn i28(cpu: &mut Cpu, mem: &mut Memory) {
+    implied(cpu, mem);
+    stack_cycle(cpu, mem);
+    let st = pull_from_stack(cpu, mem);
+    cpu.set_status(st);
+    cpu.pc = cpu.pc.wrapping_add(1);
+    cpu.cycles += 4;
+}
+
+// AND #$nn (29)
+fn i29(cpu: &mut Cpu, mem: &mut Memory) {
+    let value = operand_byte(cpu, mem);
+    and(cpu, value);
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 2;
+}
+
+// ROL A (2A)
+fn i2a(cpu: &mut Cpu, mem: &mut Memory) {
+    implied(cpu, mem);
+    cpu.ac = rol(cpu, cpu.ac);
+    cpu.pc = cpu.pc.wrapping_add(1);
+    cpu.cycles += 2;
+}
+
+// BIT $nnnn (2C)
+fn i2c(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_abs(cpu, mem);
+    let value = mem.read(addr);
+    bit(cpu, value);
+    cpu.pc = cpu.pc.wrapping_add(3);
+    cpu.cycles += 4;
+}
+
+// AND $nnnn (2D)
+fn i2d(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_abs(cpu, mem);
+    let value = mem.read(addr);
+    and(cpu, value);
+    cpu.pc = cpu.pc.wrapping_add(3);
+    cpu.cycles += 4;
+}
+
+// ROL $nnnn (2E)
+fn i2e(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_abs(cpu, mem);
+    let value = mem.read(addr);
+    modify_cycle(cpu, mem, addr, value);
+    mem.write(addr, rol(cpu, value));
+    cpu.pc = cpu.pc.wrapping_add(3);
+    cpu.cycles += 6;
+}
+
+// BMI $nnnn (30)
+fn i30(cpu: &mut Cpu, mem: &mut Memory) {
+    let taken = cpu.status() & STATUS_FLAGS_NEGATIVE != 0;
+    branch(cpu, mem, taken);
+}
+
+// AND ($nn),Y (31)
+fn i31(cpu: &mut Cpu, mem: &mut Memory) {
+    let (addr, crossed) = addr_izy(cpu, mem);
+    let value = mem.read(addr);
+    and(cpu, value);
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 5 + crossed as u64;
+}
+
+// AND $nn,X (35)
+fn i35(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_zpx(cpu, mem);
+    let value = mem.read(addr);
+    and(cpu, value);
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 4;
+}
+
+// ROL $nn,X (36)
+fn i36(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_zpx(cpu, mem);
+    let value = mem.read(addr);
+    modify_cycle(cpu, mem, addr, value);
+    mem.write(addr, rol(cpu, value));
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 6;
+}
+
+// SEC (38)
+fn i38(cpu: &mut Cpu, mem: &mut Memory) {
+    implied(cpu, mem);
+    cpu.st |= STATUS_FLAGS_CARRY;
+    cpu.pc = cpu.pc.wrapping_add(1);
+    cpu.cycles += 2;
+}
+
+// AND $nnnn,Y (39)
+fn i39(cpu: &mut Cpu, mem: &mut Memory) {
+    let (addr, crossed) = addr_aby(cpu, mem);
+    let value = mem.read(addr);
+    and(cpu, value);
+    cpu.pc = cpu.pc.wrapping_add(3);
+    cpu.cycles += 4 + crossed as u64;
+}
+
+// AND $nnnn,X (3D)
+fn i3d(cpu: &mut Cpu, mem: &mut Memory) {
+    let (addr, crossed) = addr_abx(cpu, mem);
+    let value = mem.read(addr);
+    and(cpu, value);
+    cpu.pc = cpu.pc.wrapping_add(3);
+    cpu.cycles += 4 + crossed as u64;
+}
+
+// ROL $nnnn,X (3E)
+fn i3e(cpu: &mut Cpu, mem: &mut Memory) {
+    let (addr, fixed) = addr_abx_shift(cpu, mem);
+    let value = mem.read(addr);
+    modify_cycle(cpu, mem, addr, value);
+    mem.write(addr, rol(cpu, value));
+    cpu.pc = cpu.pc.wrapping_add(3);
+    cpu.cycles += 6 + fixed as u64;
+}
+
+// RTI (40)
+fn i40(cpu: &mut Cpu, mem: &mut Memory) {
+    implied(cpu, mem);
+    stack_cycle(cpu, mem);
+    let st = pull_from_stack(cpu, mem);
+    cpu.set_status(st);
+    let lo = pull_from_stack(cpu, mem);
+    let hi = pull_from_stack(cpu, mem);
+    cpu.pc = byte_to_word(lo, hi);
+    cpu.cycles += 6;
+}
+
+// EOR ($nn,X) (41)
+fn i41(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_izx(cpu, mem);
+    let value = mem.read(addr);
+    eor(cpu, value);
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 6;
+}
+
+// EOR $nn (45)
+fn i45(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_zpg(cpu, mem);
+    let value = mem.read(addr);
+    eor(cpu, value);
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 3;
+}
+
+// LSR $nn (46)
+fn i46(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_zpg(cpu, mem);
+    let value = mem.read(addr);
+    modify_cycle(cpu, mem, addr, value);
+    mem.write(addr, lsr(cpu, value));
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 5;
+}
+
+// PHA (48)
+fn i48(cpu: &mut Cpu, mem: &mut Memory) {
+    implied(cpu, mem);
+    push_to_stack(cpu.ac, cpu, mem);
+    cpu.pc = cpu.pc.wrapping_add(1);
+    cpu.cycles += 3;
+}
+
+// EOR #$nn (49)
+fn i49(cpu: &mut Cpu, mem: &mut Memory) {
+    let value = operand_byte(cpu, mem);
+    eor(cpu, value);
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 2;
+}
+
+// LSR A (4A)
+fn i4a(cpu: &mut Cpu, mem: &mut Memory) {
+    implied(cpu, mem);
+    cpu.ac = lsr(cpu, cpu.ac);
+    cpu.pc = cpu.pc.wrapping_add(1);
+    cpu.cycles += 2;
+}
+
+// JMP $nnnn (4C)
+fn i4c(cpu: &mut Cpu, mem: &mut Memory) {
+    cpu.pc = operand_word(cpu, mem);
+    cpu.cycles += 3;
+}
+
+// EOR $nnnn (4D)
+fn i4d(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_abs(cpu, mem);
+    let value = mem.read(addr);
+    eor(cpu, value);
+    cpu.pc = cpu.pc.wrapping_add(3);
+    cpu.cycles += 4;
+}
+
+// LSR $nnnn (4E)
+fn i4e(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_abs(cpu, mem);
+    let value = mem.read(addr);
+    modify_cycle(cpu, mem, addr, value);
+    mem.write(addr, lsr(cpu, value));
+    cpu.pc = cpu.pc.wrapping_add(3);
+    cpu.cycles += 6;
+}
+
+// BVC $nnnn (50)
+fn i50(cpu: &mut Cpu, mem: &mut Memory) {
+    let taken = cpu.st & STATUS_FLAGS_OVERFLOW == 0;
+    branch(cpu, mem, taken);
+}
+
+// EOR ($nn),Y (51)
+fn i51(cpu: &mut Cpu, mem: &mut Memory) {
+    let (addr, crossed) = addr_izy(cpu, mem);
+    let value = mem.read(addr);
+    eor(cpu, value);
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 5 + crossed as u64;
+}
+
+// EOR $nn,X (55)
+fn i55(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_zpx(cpu, mem);
+    let value = mem.read(addr);
+    eor(cpu, value);
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 4;
+}
+
+// LSR $nn,X (56)
+fn i56(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_zpx(cpu, mem);
+    let value = mem.read(addr);
+    modify_cycle(cpu, mem, addr, value);
+    mem.write(addr, lsr(cpu, value));
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 6;
+}
+
+// CLI (58)
+fn i58(cpu: &mut Cpu, mem: &mut Memory) {
+    implied(cpu, mem);
+    cpu.st &= !STATUS_BIT_INT_DIS;
+    cpu.pc = cpu.pc.wrapping_add(1);
+    cpu.cycles += 2;
+}
+
+// EOR $nnnn,Y (59)
+fn i59(cpu: &mut Cpu, mem: &mut Memory) {
+    let (addr, crossed) = addr_aby(cpu, mem);
+    let value = mem.read(addr);
+    eor(cpu, value);
+    cpu.pc = cpu.pc.wrapping_add(3);
+    cpu.cycles += 4 + crossed as u64;
+}
+
+// EOR $nnnn,X (5D)
+fn i5d(cpu: &mut Cpu, mem: &mut Memory) {
+    let (addr, crossed) = addr_abx(cpu, mem);
+    let value = mem.read(addr);
+    eor(cpu, value);
+    cpu.pc = cpu.pc.wrapping_add(3);
+    cpu.cycles += 4 + crossed as u64;
+}
+
+// LSR $nnnn,X (5E)
+fn i5e(cpu: &mut Cpu, mem: &mut Memory) {
+    let (addr, fixed) = addr_abx_shift(cpu, mem);
+    let value = mem.read(addr);
+    modify_cycle(cpu, mem, addr, value);
+    mem.write(addr, lsr(cpu, value));
+    cpu.pc = cpu.pc.wrapping_add(3);
+    cpu.cycles += 6 + fixed as u64;
+}
+
+// RTS (60)
+fn i60(cpu: &mut Cpu, mem: &mut Memory) {
+    implied(cpu, mem);
+    stack_cycle(cpu, mem);
+    let lo = pull_from_stack(cpu, mem);
+    let hi = pull_from_stack(cpu, mem);
+    // the last byte of the JSR is read again on the way past it
+    let ret = byte_to_word(lo, hi);
+    mem.dummy_read(ret);
+    cpu.pc = ret.wrapping_add(1);
+    cpu.cycles += 6;
+}
+
+// ADC ($nn,X) (61)
+fn i61(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_izx(cpu, mem);
+    let value = mem.read(addr);
+    adc(cpu, mem, value);
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 6;
+}
+
+// ADC $nn (65)
+fn i65(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_zpg(cpu, mem);
+    let value = mem.read(addr);
+    adc(cpu, mem, value);
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 3;
+}
+
+// ROR $nn (66)
+fn i66(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_zpg(cpu, mem);
+    let value = mem.read(addr);
+    modify_cycle(cpu, mem, addr, value);
+    mem.write(addr, ror(cpu, value));
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 5;
+}
+
+// PLA (68)
+fn i68(cpu: &mut Cpu, mem: &mut Memory) {
+    implied(cpu, mem);
+    stack_cycle(cpu, mem);
+    cpu.ac = pull_from_stack(cpu, mem);
+    set_nz(cpu, cpu.ac);
+    cpu.pc = cpu.pc.wrapping_add(1);
+    cpu.cycles += 4;
+}
+
+// ADC #$nn (69)
+fn i69(cpu: &mut Cpu, mem: &mut Memory) {
+    let value = operand_byte(cpu, mem);
+    adc(cpu, mem, value);
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 2;
+}
+
+// ROR A (6A)
+fn i6a(cpu: &mut Cpu, mem: &mut Memory) {
+    implied(cpu, mem);
+    cpu.ac = ror(cpu, cpu.ac);
+    cpu.pc = cpu.pc.wrapping_add(1);
+    cpu.cycles += 2;
+}
+
+// JMP ($nnnn) (6C)
+fn i6c(cpu: &mut Cpu, mem: &mut Memory) {
+    // the pointer's high byte comes from the same page
+    let ptr = operand_word(cpu, mem);
+    let lo = mem.fetch(ptr);
+    cpu.pc = byte_to_word(lo, mem.fetch((ptr & 0xff00) | (ptr.wrapping_add(1) & 0x00ff)));
+    cpu.cycles += 5;
+}
+
+// ADC $nnnn (6D)
+fn i6d(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_abs(cpu, mem);
+    let value = mem.read(addr);
+    adc(cpu, mem, value);
+    cpu.pc = cpu.pc.wrapping_add(3);
+    cpu.cycles += 4;
+}
+
+// ROR $nnnn (6E)
+fn i6e(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_abs(cpu, mem);
+    let value = mem.read(addr);
+    modify_cycle(cpu, mem, addr, value);
+    mem.write(addr, ror(cpu, value));
+    cpu.pc = cpu.pc.wrapping_add(3);
+    cpu.cycles += 6;
+}
+
+// BVS $nnnn (70)
+fn i70(cpu: &mut Cpu, mem: &mut Memory) {
+    let taken = cpu.st & STATUS_FLAGS_OVERFLOW != 0;
+    branch(cpu, mem, taken);
+}
+
+// ADC ($nn),Y (71)
+fn i71(cpu: &mut Cpu, mem: &mut Memory) {
+    let (addr, crossed) = addr_izy(cpu, mem);
+    let value = mem.read(addr);
+    adc(cpu, mem, value);
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 5 + crossed as u64;
+}
+
+// ADC $nn,X (75)
+fn i75(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_zpx(cpu, mem);
+    let value = mem.read(addr);
+    adc(cpu, mem, value);
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 4;
+}
+
+// ROR $nn,X (76)
+fn i76(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_zpx(cpu, mem);
+    let value = mem.read(addr);
+    modify_cycle(cpu, mem, addr, value);
+    mem.write(addr, ror(cpu, value));
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 6;
+}
+
+// SEI (78)
+fn i78(cpu: &mut Cpu, mem: &mut Memory) {
+    implied(cpu, mem);
+    cpu.st |= STATUS_BIT_INT_DIS;
+    cpu.pc = cpu.pc.wrapping_add(1);
+    cpu.cycles += 2;
+}
+
+// ADC $nnnn,Y (79)
+fn i79(cpu: &mut Cpu, mem: &mut Memory) {
+    let (addr, crossed) = addr_aby(cpu, mem);
+    let value = mem.read(addr);
+    adc(cpu, mem, value);
+    cpu.pc = cpu.pc.wrapping_add(3);
+    cpu.cycles += 4 + crossed as u64;
+}
+
+// ADC $nnnn,X (7D)
+fn i7d(cpu: &mut Cpu, mem: &mut Memory) {
+    let (addr, crossed) = addr_abx(cpu, mem);
+    let value = mem.read(addr);
+    adc(cpu, mem, value);
+    cpu.pc = cpu.pc.wrapping_add(3);
+    cpu.cycles += 4 + crossed as u64;
+}
+
+// ROR $nnnn,X (7E)
+fn i7e(cpu: &mut Cpu, mem: &mut Memory) {
+    let (addr, fixed) = addr_abx_shift(cpu, mem);
+    let value = mem.read(addr);
+    modify_cycle(cpu, mem, addr, value);
+    mem.write(addr, ror(cpu, value));
+    cpu.pc = cpu.pc.wrapping_add(3);
+    cpu.cycles += 6 + fixed as u64;
+}
+
+// STA ($nn,X) (81)
+fn i81(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_izx(cpu, mem);
+    mem.write(addr, cpu.ac);
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 6;
+}
+
+// STY $nn (84)
+fn i84(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_zpg(cpu, mem);
+    mem.write(addr, cpu.yr);
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 3;
+}
+
+// STA $nn (85)
+fn i85(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_zpg(cpu, mem);
+    mem.write(addr, cpu.ac);
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 3;
+}
+
+// STX $nn (86)
+fn i86(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_zpg(cpu, mem);
+    mem.write(addr, cpu.xr);
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 3;
+}
+
+// DEY (88)
+fn i88(cpu: &mut Cpu, mem: &mut Memory) {
+    implied(cpu, mem);
+    cpu.yr = cpu.yr.wrapping_sub(1);
+    set_nz(cpu, cpu.yr);
+    cpu.pc = cpu.pc.wrapping_add(1);
+    cpu.cycles += 2;
+}
+
+// TXA (8A)
+fn i8a(cpu: &mut Cpu, mem: &mut Memory) {
+    implied(cpu, mem);
+    cpu.ac = cpu.xr;
+    set_nz(cpu, cpu.ac);
+    cpu.pc = cpu.pc.wrapping_add(1);
+    cpu.cycles += 2;
+}
+
+// STY $nnnn (8C)
+fn i8c(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_abs(cpu, mem);
+    mem.write(addr, cpu.yr);
+    cpu.pc = cpu.pc.wrapping_add(3);
+    cpu.cycles += 4;
+}
+
+// STA $nnnn (8D)
+fn i8d(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_abs(cpu, mem);
+    mem.write(addr, cpu.ac);
+    cpu.pc = cpu.pc.wrapping_add(3);
+    cpu.cycles += 4;
+}
+
+// STX $nnnn (8E)
+fn i8e(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_abs(cpu, mem);
+    mem.write(addr, cpu.xr);
+    cpu.pc = cpu.pc.wrapping_add(3);
+    cpu.cycles += 4;
+}
+
+// BCC $nnnn (90)
+fn i90(cpu: &mut Cpu, mem: &mut Memory) {
+    let taken = cpu.st & STATUS_FLAGS_CARRY == 0;
+    branch(cpu, mem, taken);
+}
+
+// STA ($nn),Y (91)
+fn i91(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_izy_write(cpu, mem);
+    mem.write(addr, cpu.ac);
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 6;
+}
+
+// STY $nn,X (94)
+fn i94(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_zpx(cpu, mem);
+    mem.write(addr, cpu.yr);
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 4;
+}
+
+// STA $nn,X (95)
+fn i95(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_zpx(cpu, mem);
+    mem.write(addr, cpu.ac);
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 4;
+}
+
+// STX $nn,Y (96)
+fn i96(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_zpy(cpu, mem);
+    mem.write(addr, cpu.xr);
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 4;
+}
+
+// TYA (98)
+fn i98(cpu: &mut Cpu, mem: &mut Memory) {
+    implied(cpu, mem);
+    cpu.ac = cpu.yr;
+    set_nz(cpu, cpu.ac);
+    cpu.pc = cpu.pc.wrapping_add(1);
+    cpu.cycles += 2;
+}
+
+// STA $nnnn,Y (99)
+fn i99(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_aby_write(cpu, mem);
+    mem.write(addr, cpu.ac);
+    cpu.pc = cpu.pc.wrapping_add(3);
+    cpu.cycles += 5;
+}
+
+// TXS (9A)
+fn i9a(cpu: &mut Cpu, mem: &mut Memory) {
+    implied(cpu, mem);
+    cpu.sp = cpu.xr;
+    cpu.pc = cpu.pc.wrapping_add(1);
+    cpu.cycles += 2;
+}
+
+// STA $nnnn,X (9D)
+fn i9d(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_abx_write(cpu, mem);
+    mem.write(addr, cpu.ac);
+    cpu.pc = cpu.pc.wrapping_add(3);
+    cpu.cycles += 5;
+}
+
+// LDY #$nn (A0)
+fn ia0(cpu: &mut Cpu, mem: &mut Memory) {
+    let value = operand_byte(cpu, mem);
+    ldy(cpu, value);
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 2;
+}
+
+// LDA ($nn,X) (A1)
+fn ia1(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_izx(cpu, mem);
+    let value = mem.read(addr);
+    lda(cpu, value);
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 6;
+}
+
+// LDX #$nn (A2)
+fn ia2(cpu: &mut Cpu, mem: &mut Memory) {
+    let value = operand_byte(cpu, mem);
+    ldx(cpu, value);
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 2;
+}
+
+// LDY $nn (A4)
+fn ia4(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_zpg(cpu, mem);
+    let value = mem.read(addr);
+    ldy(cpu, value);
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 3;
+}
+
+// LDA $nn (A5)
+fn ia5(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_zpg(cpu, mem);
+    let value = mem.read(addr);
+    lda(cpu, value);
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 3;
+}
+
+// LDX $nn (A6)
+fn ia6(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_zpg(cpu, mem);
+    let value = mem.read(addr);
+    ldx(cpu, value);
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 3;
+}
+
+// TAY (A8)
+fn ia8(cpu: &mut Cpu, mem: &mut Memory) {
+    implied(cpu, mem);
+    cpu.yr = cpu.ac;
+    set_nz(cpu, cpu.yr);
+    cpu.pc = cpu.pc.wrapping_add(1);
+    cpu.cycles += 2;
+}
+
+// LDA #$nn (A9)
+fn ia9(cpu: &mut Cpu, mem: &mut Memory) {
+    let value = operand_byte(cpu, mem);
+    lda(cpu, value);
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 2;
+}
+
+// TAX (AA)
+fn iaa(cpu: &mut Cpu, mem: &mut Memory) {
+    implied(cpu, mem);
+    cpu.xr = cpu.ac;
+    set_nz(cpu, cpu.xr);
+    cpu.pc = cpu.pc.wrapping_add(1);
+    cpu.cycles += 2;
+}
+
+// LDY $nnnn (AC)
+fn iac(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_abs(cpu, mem);
+    let value = mem.read(addr);
+    ldy(cpu, value);
+    cpu.pc = cpu.pc.wrapping_add(3);
+    cpu.cycles += 4;
+}
+
+// LDA $nnnn (AD)
+fn iad(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_abs(cpu, mem);
+    let value = mem.read(addr);
+    lda(cpu, value);
+    cpu.pc = cpu.pc.wrapping_add(3);
+    cpu.cycles += 4;
+}
+
+// LDX $nnnn (AE)
+fn iae(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_abs(cpu, mem);
+    let value = mem.read(addr);
+    ldx(cpu, value);
+    cpu.pc = cpu.pc.wrapping_add(3);
+    cpu.cycles += 4;
+}
+
+// BCS $nnnn (B0)
+fn ib0(cpu: &mut Cpu, mem: &mut Memory) {
+    let taken = cpu.st & STATUS_FLAGS_CARRY != 0;
+    branch(cpu, mem, taken);
+}
+
+// LDA ($nn),Y (B1)
+fn ib1(cpu: &mut Cpu, mem: &mut Memory) {
+    let (addr, crossed) = addr_izy(cpu, mem);
+    let value = mem.read(addr);
+    lda(cpu, value);
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 5 + crossed as u64;
+}
+
+// LDY $nn,X (B4)
+fn ib4(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_zpx(cpu, mem);
+    let value = mem.read(addr);
+    ldy(cpu, value);
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 4;
+}
+
+// LDA $nn,X (B5)
+fn ib5(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_zpx(cpu, mem);
+    let value = mem.read(addr);
+    lda(cpu, value);
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 4;
+}
+
+// LDX $nn,Y (B6)
+fn ib6(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_zpy(cpu, mem);
+    let value = mem.read(addr);
+    ldx(cpu, value);
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 4;
+}
+
+// CLV (B8)
+fn ib8(cpu: &mut Cpu, mem: &mut Memory) {
+    implied(cpu, mem);
+    cpu.st &= !STATUS_FLAGS_OVERFLOW;
+    cpu.pc = cpu.pc.wrapping_add(1);
+    cpu.cycles += 2;
+}
+
+// LDA $nnnn,Y (B9)
+fn ib9(cpu: &mut Cpu, mem: &mut Memory) {
+    let (addr, crossed) = addr_aby(cpu, mem);
+    let value = mem.read(addr);
+    lda(cpu, value);
+    cpu.pc = cpu.pc.wrapping_add(3);
+    cpu.cycles += 4 + crossed as u64;
+}
+
+// TSX (BA)
+fn iba(cpu: &mut Cpu, mem: &mut Memory) {
+    implied(cpu, mem);
+    cpu.xr = cpu.sp;
+    set_nz(cpu, cpu.xr);
+    cpu.pc = cpu.pc.wrapping_add(1);
+    cpu.cycles += 2;
+}
+
+// LDY $nnnn,X (BC)
+fn ibc(cpu: &mut Cpu, mem: &mut Memory) {
+    let (addr, crossed) = addr_abx(cpu, mem);
+    let value = mem.read(addr);
+    ldy(cpu, value);
+    cpu.pc = cpu.pc.wrapping_add(3);
+    cpu.cycles += 4 + crossed as u64;
+}
+
+// LDA $nnnn,X (BD)
+fn ibd(cpu: &mut Cpu, mem: &mut Memory) {
+    let (addr, crossed) = addr_abx(cpu, mem);
+    let value = mem.read(addr);
+    lda(cpu, value);
+    cpu.pc = cpu.pc.wrapping_add(3);
+    cpu.cycles += 4 + crossed as u64;
+}
+
+// LDX $nnnn,Y (BE)
+fn ibe(cpu: &mut Cpu, mem: &mut Memory) {
+    let (addr, crossed) = addr_aby(cpu, mem);
+    let value = mem.read(addr);
+    ldx(cpu, value);
+    cpu.pc = cpu.pc.wrapping_add(3);
+    cpu.cycles += 4 + crossed as u64;
+}
+
+// CPY #$nn (C0)
+fn ic0(cpu: &mut Cpu, mem: &mut Memory) {
+    let value = operand_byte(cpu, mem);
+    compare(cpu, cpu.yr, value);
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 2;
+}
+
+// CMP ($nn,X) (C1)
+fn ic1(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_izx(cpu, mem);
+    let value = mem.read(addr);
+    compare(cpu, cpu.ac, value);
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 6;
+}
+
+// CPY $nn (C4)
+fn ic4(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_zpg(cpu, mem);
+    let value = mem.read(addr);
+    compare(cpu, cpu.yr, value);
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 3;
+}
+
+// CMP $nn (C5)
+fn ic5(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_zpg(cpu, mem);
+    let value = mem.read(addr);
+    compare(cpu, cpu.ac, value);
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 3;
+}
+
+// DEC $nn (C6)
+fn ic6(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_zpg(cpu, mem);
+    let value = mem.read(addr);
+    modify_cycle(cpu, mem, addr, value);
+    mem.write(addr, dec(cpu, value));
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 5;
+}
+
+// INY (C8)
+fn ic8(cpu: &mut Cpu, mem: &mut Memory) {
+    implied(cpu, mem);
+    cpu.yr = cpu.yr.wrapping_add(1);
+    set_nz(cpu, cpu.yr);
+    cpu.pc = cpu.pc.wrapping_add(1);
+    cpu.cycles += 2;
+}
+
+// CMP #$nn (C9)
+fn ic9(cpu: &mut Cpu, mem: &mut Memory) {
+    let value = operand_byte(cpu, mem);
+    compare(cpu, cpu.ac, value);
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 2;
+}
+
+// DEX (CA)
+fn ica(cpu: &mut Cpu, mem: &mut Memory) {
+    implied(cpu, mem);
+    cpu.xr = cpu.xr.wrapping_sub(1);
+    set_nz(cpu, cpu.xr);
+    cpu.pc = cpu.pc.wrapping_add(1);
+    cpu.cycles += 2;
+}
+
+// CPY $nnnn (CC)
+fn icc(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_abs(cpu, mem);
+    let value = mem.read(addr);
+    compare(cpu, cpu.yr, value);
+    cpu.pc = cpu.pc.wrapping_add(3);
+    cpu.cycles += 4;
+}
+
+// CMP $nnnn (CD)
+fn icd(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_abs(cpu, mem);
+    let value = mem.read(addr);
+    compare(cpu, cpu.ac, value);
+    cpu.pc = cpu.pc.wrapping_add(3);
+    cpu.cycles += 4;
+}
+
+// DEC $nnnn (CE)
+fn ice(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_abs(cpu, mem);
+    let value = mem.read(addr);
+    modify_cycle(cpu, mem, addr, value);
+    mem.write(addr, dec(cpu, value));
+    cpu.pc = cpu.pc.wrapping_add(3);
+    cpu.cycles += 6;
+}
+
+// BNE $nnnn (D0)
+fn id0(cpu: &mut Cpu, mem: &mut Memory) {
+    let taken = cpu.status() & STATUS_FLAGS_ZERO == 0;
+    branch(cpu, mem, taken);
+}
+
+// CMP ($nn),Y (D1)
+fn id1(cpu: &mut Cpu, mem: &mut Memory) {
+    let (addr, crossed) = addr_izy(cpu, mem);
+    let value = mem.read(addr);
+    compare(cpu, cpu.ac, value);
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 5 + crossed as u64;
+}
+
+// CMP $nn,X (D5)
+fn id5(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_zpx(cpu, mem);
+    let value = mem.read(addr);
+    compare(cpu, cpu.ac, value);
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 4;
+}
+
+// DEC $nn,X (D6)
+fn id6(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_zpx(cpu, mem);
+    let value = mem.read(addr);
+    modify_cycle(cpu, mem, addr, value);
+    mem.write(addr, dec(cpu, value));
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 6;
+}
+
+// CLD (D8)
+fn id8(cpu: &mut Cpu, mem: &mut Memory) {
+    implied(cpu, mem);
+    cpu.st &= !STATUS_FLAGS_DECIMAL;
+    cpu.pc = cpu.pc.wrapping_add(1);
+    cpu.cycles += 2;
+}
+
+// CMP $nnnn,Y (D9)
+fn id9(cpu: &mut Cpu, mem: &mut Memory) {
+    let (addr, crossed) = addr_aby(cpu, mem);
+    let value = mem.read(addr);
+    compare(cpu, cpu.ac, value);
+    cpu.pc = cpu.pc.wrapping_add(3);
+    cpu.cycles += 4 + crossed as u64;
+}
+
+// CMP $nnnn,X (DD)
+fn idd(cpu: &mut Cpu, mem: &mut Memory) {
+    let (addr, crossed) = addr_abx(cpu, mem);
+    let value = mem.read(addr);
+    compare(cpu, cpu.ac, value);
+    cpu.pc = cpu.pc.wrapping_add(3);
+    cpu.cycles += 4 + crossed as u64;
+}
+
+// DEC $nnnn,X (DE)
+fn ide(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_abx_write(cpu, mem);
+    let value = mem.read(addr);
+    modify_cycle(cpu, mem, addr, value);
+    mem.write(addr, dec(cpu, value));
+    cpu.pc = cpu.pc.wrapping_add(3);
+    cpu.cycles += 7;
+}
+
+// CPX #$nn (E0)
+fn ie0(cpu: &mut Cpu, mem: &mut Memory) {
+    let value = operand_byte(cpu, mem);
+    compare(cpu, cpu.xr, value);
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 2;
+}
+
+// SBC ($nn,X) (E1)
+fn ie1(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_izx(cpu, mem);
+    let value = mem.read(addr);
+    sbc(cpu, mem, value);
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 6;
+}
+
+// CPX $nn (E4)
+fn ie4(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_zpg(cpu, mem);
+    let value = mem.read(addr);
+    compare(cpu, cpu.xr, value);
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 3;
+}
+
+// SBC $nn (E5)
+fn ie5(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_zpg(cpu, mem);
+    let value = mem.read(addr);
+    sbc(cpu, mem, value);
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 3;
+}
+
+// INC $nn (E6)
+fn ie6(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_zpg(cpu, mem);
+    let value = mem.read(addr);
+    modify_cycle(cpu, mem, addr, value);
+    mem.write(addr, inc(cpu, value));
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 5;
+}
+
+// INX (E8)
+fn ie8(cpu: &mut Cpu, mem: &mut Memory) {
+    implied(cpu, mem);
+    cpu.xr = cpu.xr.wrapping_add(1);
+    set_nz(cpu, cpu.xr);
+    cpu.pc = cpu.pc.wrapping_add(1);
+    cpu.cycles += 2;
+}
+
+// SBC #$nn (E9)
+fn ie9(cpu: &mut Cpu, mem: &mut Memory) {
+    let value = operand_byte(cpu, mem);
+    sbc(cpu, mem, value);
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 2;
+}
+
+// NOP (EA)
+fn iea(cpu: &mut Cpu, mem: &mut Memory) {
+    implied(cpu, mem);
+    cpu.pc = cpu.pc.wrapping_add(1);
+    cpu.cycles += 2;
+}
+
+// CPX $nnnn (EC)
+fn iec(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_abs(cpu, mem);
+    let value = mem.read(addr);
+    compare(cpu, cpu.xr, value);
+    cpu.pc = cpu.pc.wrapping_add(3);
+    cpu.cycles += 4;
+}
+
+// SBC $nnnn (ED)
+fn ied(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_abs(cpu, mem);
+    let value = mem.read(addr);
+    sbc(cpu, mem, value);
+    cpu.pc = cpu.pc.wrapping_add(3);
+    cpu.cycles += 4;
+}
+
+// INC $nnnn (EE)
+fn iee(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_abs(cpu, mem);
+    let value = mem.read(addr);
+    modify_cycle(cpu, mem, addr, value);
+    mem.write(addr, inc(cpu, value));
+    cpu.pc = cpu.pc.wrapping_add(3);
+    cpu.cycles += 6;
+}
+
+// BEQ $nnnn (F0)
+fn if0(cpu: &mut Cpu, mem: &mut Memory) {
+    let taken = cpu.status() & STATUS_FLAGS_ZERO != 0;
+    branch(cpu, mem, taken);
+}
+
+// SBC ($nn),Y (F1)
+fn if1(cpu: &mut Cpu, mem: &mut Memory) {
+    let (addr, crossed) = addr_izy(cpu, mem);
+    let value = mem.read(addr);
+    sbc(cpu, mem, value);
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 5 + crossed as u64;
+}
+
+// SBC $nn,X (F5)
+fn if5(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_zpx(cpu, mem);
+    let value = mem.read(addr);
+    sbc(cpu, mem, value);
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 4;
+}
+
+// INC $nn,X (F6)
+fn if6(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_zpx(cpu, mem);
+    let value = mem.read(addr);
+    modify_cycle(cpu, mem, addr, value);
+    mem.write(addr, inc(cpu, value));
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 6;
+}
+
+// SED (F8)
+fn if8(cpu: &mut Cpu, mem: &mut Memory) {
+    implied(cpu, mem);
+    cpu.st |= STATUS_FLAGS_DECIMAL;
+    cpu.pc = cpu.pc.wrapping_add(1);
+    cpu.cycles += 2;
+}
+
+// SBC $nnnn,Y (F9)
+fn if9(cpu: &mut Cpu, mem: &mut Memory) {
+    let (addr, crossed) = addr_aby(cpu, mem);
+    let value = mem.read(addr);
+    sbc(cpu, mem, value);
+    cpu.pc = cpu.pc.wrapping_add(3);
+    cpu.cycles += 4 + crossed as u64;
+}
+
+// SBC $nnnn,X (FD)
+fn ifd(cpu: &mut Cpu, mem: &mut Memory) {
+    let (addr, crossed) = addr_abx(cpu, mem);
+    let value = mem.read(addr);
+    sbc(cpu, mem, value);
+    cpu.pc = cpu.pc.wrapping_add(3);
+    cpu.cycles += 4 + crossed as u64;
+}
+
+// INC $nnnn,X (FE)
+fn ife(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_abx_write(cpu, mem);
+    let value = mem.read(addr);
+    modify_cycle(cpu, mem, addr, value);
+    mem.write(addr, inc(cpu, value));
+    cpu.pc = cpu.pc.wrapping_add(3);
+    cpu.cycles += 7;
+}
+
+// op code array
+pub const CPU_OPS: [CpuOp; 256] = [
+    //0    1    2    3    4    5    6    7    8    9    a    b    c    d    e    f
+    i00, i01, ixx, ixx, ixx, i05, i06, ixx, i08, i09, i0a, ixx, ixx, i0d, i0e, ixx,     // 00
+    i10, i11, ixx, ixx, ixx, i15, i16, ixx, i18, i19, ixx, ixx, ixx, i1d, i1e, ixx,     // 10
+    i20, i21, ixx, ixx, i24, i25, i26, ixx, i28, i29, i2a, ixx, i2c, i2d, i2e, ixx,     // 20
+    i30, i31, ixx, ixx, ixx, i35, i36, ixx, i38, i39, ixx, ixx, ixx, i3d, i3e, ixx,     // 30
+    i40, i41, ixx, ixx, ixx, i45, i46, ixx, i48, i49, i4a, ixx, i4c, i4d, i4e, ixx,     // 40
+    i50, i51, ixx, ixx, ixx, i55, i56, ixx, i58, i59, ixx, ixx, ixx, i5d, i5e, ixx,     // 50
+    i60, i61, ixx, ixx, ixx, i65, i66, ixx, i68, i69, i6a, ixx, i6c, i6d, i6e, ixx,     // 60
+    i70, i71, ixx, ixx, ixx, i75, i76, ixx, i78, i79, ixx, ixx, ixx, i7d, i7e, ixx,     // 70
+    ixx, i81, ixx, ixx, i84, i85, i86, ixx, i88, ixx, i8a, ixx, i8c, i8d, i8e, ixx,     // 80
+    i90, i91, ixx, ixx, i94, i95, i96, ixx, i98, i99, i9a, ixx, ixx, i9d, ixx, ixx,     // 90
+    ia0, ia1, ia2, ixx, ia4, ia5, ia6, ixx, ia8, ia9, iaa, ixx, iac, iad, iae, ixx,     // a0
+    ib0, ib1, ixx, ixx, ib4, ib5, ib6, ixx, ib8, ib9, iba, ixx, ibc, ibd, ibe, ixx,     // b0
+    ic0, ic1, ixx, ixx, ic4, ic5, ic6, ixx, ic8, ic9, ica, ixx, icc, icd, ice, ixx,     // c0
+    id0, id1, ixx, ixx, ixx, id5, id6, ixx, id8, id9, ixx, ixx, ixx, idd, ide, ixx,     // d0
+    ie0, ie1, ixx, ixx, ie4, ie5, ie6, ixx, ie8, ie9, iea, ixx, iec, ied, iee, ixx,     // e0
+    if0, if1, ixx, ixx, ixx, if5, if6, ixx, if8, if9, ixx, ixx, ixx, ifd, ife, ixx,     // f0
+];
+
+// TSB $nn (04)
+fn c04(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_zpg(cpu, mem);
+    let value = mem.read(addr);
+    set_flag(cpu, STATUS_FLAGS_ZERO, cpu.ac & value == 0);
+    modify_cycle(cpu, mem, addr, value);
+    mem.write(addr, value | cpu.ac);
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 5;
+}
+
+// RMB0 $nn (07)
+fn c07(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_zpg(cpu, mem);
+    let value = mem.read(addr);
+    modify_cycle(cpu, mem, addr, value);
+    mem.write(addr, value & !0x01);
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 5;
+}
+
+// TSB $nnnn (0C)
+fn c0c(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_abs(cpu, mem);
+    let value = mem.read(addr);
+    set_flag(cpu, STATUS_FLAGS_ZERO, cpu.ac & value == 0);
+    modify_cycle(cpu, mem, addr, value);
+    mem.write(addr, value | cpu.ac);
+    cpu.pc = cpu.pc.wrapping_add(3);
+    cpu.cycles += 6;
+}
+
+// BBR0 $nn,$nnnn (0F)
+fn c0f(cpu: &mut Cpu, mem: &mut Memory) {
+    branch_on_bit(cpu, mem, 0x01, false);
+}
+
+// ORA ($nn) (12)
+fn c12(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_izp(cpu, mem);
+    let value = mem.read(addr);
+    ora(cpu, value);
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 5;
+}
+
+// TRB $nn (14)
+fn c14(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_zpg(cpu, mem);
+    let value = mem.read(addr);
+    set_flag(cpu, STATUS_FLAGS_ZERO, cpu.ac & value == 0);
+    modify_cycle(cpu, mem, addr, value);
+    mem.write(addr, value & !cpu.ac);
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 5;
+}
+
+// RMB1 $nn (17)
+fn c17(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_zpg(cpu, mem);
+    let value = mem.read(addr);
+    modify_cycle(cpu, mem, addr, value);
+    mem.write(addr, value & !0x02);
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 5;
+}
+
+// INC A (1A)
+fn c1a(cpu: &mut Cpu, mem: &mut Memory) {
+    implied(cpu, mem);
+    cpu.ac = inc(cpu, cpu.ac);
+    cpu.pc = cpu.pc.wrapping_add(1);
+    cpu.cycles += 2;
+}
+
+// TRB $nnnn (1C)
+fn c1c(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_abs(cpu, mem);
+    let value = mem.read(addr);
+    set_flag(cpu, STATUS_FLAGS_ZERO, cpu.ac & value == 0);
+    modify_cycle(cpu, mem, addr, value);
+    mem.write(addr, value & !cpu.ac);
+    cpu.pc = cpu.pc.wrapping_add(3);
+    cpu.cycles += 6;
+}
+
+// BBR1 $nn,$nnnn (1F)
+fn c1f(cpu: &mut Cpu, mem: &mut Memory) {
+    branch_on_bit(cpu, mem, 0x02, false);
+}
+
+// RMB2 $nn (27)
+fn c27(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_zpg(cpu, mem);
+    let value = mem.read(addr);
+    modify_cycle(cpu, mem, addr, value);
+    mem.write(addr, value & !0x04);
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 5;
+}
+
+// BBR2 $nn,$nnnn (2F)
+fn c2f(cpu: &mut Cpu, mem: &mut Memory) {
+    branch_on_bit(cpu, mem, 0x04, false);
+}
+
+// AND ($nn) (32)
+fn c32(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_izp(cpu, mem);
+    let value = mem.read(addr);
+    and(cpu, value);
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 5;
+}
+
+// BIT $nn,X (34)
+fn c34(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_zpx(cpu, mem);
+    let value = mem.read(addr);
+    bit(cpu, value);
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 4;
+}
+
+// RMB3 $nn (37)
+fn c37(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_zpg(cpu, mem);
+    let value = mem.read(addr);
+    modify_cycle(cpu, mem, addr, value);
+    mem.write(addr, value & !0x08);
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 5;
+}
+
+// DEC A (3A)
+fn c3a(cpu: &mut Cpu, mem: &mut Memory) {
+    implied(cpu, mem);
+    cpu.ac = dec(cpu, cpu.ac);
+    cpu.pc = cpu.pc.wrapping_add(1);
+    cpu.cycles += 2;
+}
+
+// BIT $nnnn,X (3C)
+fn c3c(cpu: &mut Cpu, mem: &mut Memory) {
+    let (addr, crossed) = addr_abx(cpu, mem);
+    let value = mem.read(addr);
+    bit(cpu, value);
+    cpu.pc = cpu.pc.wrapping_add(3);
+    cpu.cycles += 4 + crossed as u64;
+}
+
+// BBR3 $nn,$nnnn (3F)
+fn c3f(cpu: &mut Cpu, mem: &mut Memory) {
+    branch_on_bit(cpu, mem, 0x08, false);
+}
+
+// RMB4 $nn (47)
+fn c47(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_zpg(cpu, mem);
+    let value = mem.read(addr);
+    modify_cycle(cpu, mem, addr, value);
+    mem.write(addr, value & !0x10);
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 5;
+}
+
+// BBR4 $nn,$nnnn (4F)
+fn c4f(cpu: &mut Cpu, mem: &mut Memory) {
+    branch_on_bit(cpu, mem, 0x10, false);
+}
+
+// EOR ($nn) (52)
+fn c52(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_izp(cpu, mem);
+    let value = mem.read(addr);
+    eor(cpu, value);
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 5;
+}
+
+// RMB5 $nn (57)
+fn c57(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_zpg(cpu, mem);
+    let value = mem.read(addr);
+    modify_cycle(cpu, mem, addr, value);
+    mem.write(addr, value & !0x20);
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 5;
+}
+
+// PHY (5A)
+fn c5a(cpu: &mut Cpu, mem: &mut Memory) {
+    implied(cpu, mem);
+    push_to_stack(cpu.yr, cpu, mem);
+    cpu.pc = cpu.pc.wrapping_add(1);
+    cpu.cycles += 3;
+}
+
+// BBR5 $nn,$nnnn (5F)
+fn c5f(cpu: &mut Cpu, mem: &mut Memory) {
+    branch_on_bit(cpu, mem, 0x20, false);
+}
+
+// STZ $nn (64)
+fn c64(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_zpg(cpu, mem);
+    mem.write(addr, 0);
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 3;
+}
+
+// RMB6 $nn (67)
+fn c67(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_zpg(cpu, mem);
+    let value = mem.read(addr);
+    modify_cycle(cpu, mem, addr, value);
+    mem.write(addr, value & !0x40);
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 5;
+}
+
+// JMP ($nnnn) (6C)
+fn c6c(cpu: &mut Cpu, mem: &mut Memory) {
+    // unlike the 6502, a pointer at the end of a page is read correctly
+    let ptr = operand_word(cpu, mem);
+    mem.dummy_read(cpu.pc.wrapping_add(2));
+    let lo = mem.fetch(ptr);
+    cpu.pc = byte_to_word(lo, mem.fetch(ptr.wrapping_add(1)));
+    cpu.cycles += 6;
+}
+
+// BBR6 $nn,$nnnn (6F)
+fn c6f(cpu: &mut Cpu, mem: &mut Memory) {
+    branch_on_bit(cpu, mem, 0x40, false);
+}
+
+// ADC ($nn) (72)
+fn c72(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_izp(cpu, mem);
+    let value = mem.read(addr);
+    adc(cpu, mem, value);
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 5;
+}
+
+// STZ $nn,X (74)
+fn c74(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_zpx(cpu, mem);
+    mem.write(addr, 0);
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 4;
+}
+
+// RMB7 $nn (77)
+fn c77(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_zpg(cpu, mem);
+    let value = mem.read(addr);
+    modify_cycle(cpu, mem, addr, value);
+    mem.write(addr, value & !0x80);
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 5;
+}
+
+// PLY (7A)
+fn c7a(cpu: &mut Cpu, mem: &mut Memory) {
+    implied(cpu, mem);
+    stack_cycle(cpu, mem);
+    cpu.yr = pull_from_stack(cpu, mem);
+    set_nz(cpu, cpu.yr);
+    cpu.pc = cpu.pc.wrapping_add(1);
+    cpu.cycles += 4;
+}
+
+// JMP ($nnnn,X) (7C)
+fn c7c(cpu: &mut Cpu, mem: &mut Memory) {
+    let ptr = operand_word(cpu, mem).wrapping_add(cpu.xr as u16);
+    mem.dummy_read(cpu.pc.wrapping_add(2));
+    let lo = mem.fetch(ptr);
+    cpu.pc = byte_to_word(lo, mem.fetch(ptr.wrapping_add(1)));
+    cpu.cycles += 6;
+}
+
+// BBR7 $nn,$nnnn (7F)
+fn c7f(cpu: &mut Cpu, mem: &mut Memory) {
+    branch_on_bit(cpu, mem, 0x80, false);
+}
+
+// BRA $nnnn (80)
+fn c80(cpu: &mut Cpu, mem: &mut Memory) {
+    branch(cpu, mem, true);
+}
+
+// SMB0 $nn (87)
+fn c87(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_zpg(cpu, mem);
+    let value = mem.read(addr);
+    modify_cycle(cpu, mem, addr, value);
+    mem.write(addr, value | 0x01);
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 5;
+}
+
+// BIT #$nn (89)
+fn c89(cpu: &mut Cpu, mem: &mut Memory) {
+    // only the zero flag is affected in immediate mode
+    let value = operand_byte(cpu, mem);
+    set_flag(cpu, STATUS_FLAGS_ZERO, cpu.ac & value == 0);
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 2;
+}
+
+// BBS0 $nn,$nnnn (8F)
+fn c8f(cpu: &mut Cpu, mem: &mut Memory) {
+    branch_on_bit(cpu, mem, 0x01, true);
+}
+
+// STA ($nn) (92)
+fn c92(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_izp(cpu, mem);
+    mem.write(addr, cpu.ac);
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 5;
+}
+
+// SMB1 $nn (97)
+fn c97(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_zpg(cpu, mem);
+    let value = mem.read(addr);
+    modify_cycle(cpu, mem, addr, value);
+    mem.write(addr, value | 0x02);
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 5;
+}
+
+// STZ $nnnn (9C)
+fn c9c(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_abs(cpu, mem);
+    mem.write(addr, 0);
+    cpu.pc = cpu.pc.wrapping_add(3);
+    cpu.cycles += 4;
+}
+
+// STZ $nnnn,X (9E)
+fn c9e(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_abx_write(cpu, mem);
+    mem.write(addr, 0);
+    cpu.pc = cpu.pc.wrapping_add(3);
+    cpu.cycles += 5;
+}
+
+// BBS1 $nn,$nnnn (9F)
+fn c9f(cpu: &mut Cpu, mem: &mut Memory) {
+    branch_on_bit(cpu, mem, 0x02, true);
+}
+
+// SMB2 $nn (A7)
+fn ca7(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_zpg(cpu, mem);
+    let value = mem.read(addr);
+    modify_cycle(cpu, mem, addr, value);
+    mem.write(addr, value | 0x04);
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 5;
+}
+
+// BBS2 $nn,$nnnn (AF)
+fn caf(cpu: &mut Cpu, mem: &mut Memory) {
+    branch_on_bit(cpu, mem, 0x04, true);
+}
+
+// LDA ($nn) (B2)
+fn cb2(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_izp(cpu, mem);
+    let value = mem.read(addr);
+    lda(cpu, value);
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 5;
+}
+
+// SMB3 $nn (B7)
+fn cb7(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_zpg(cpu, mem);
+    let value = mem.read(addr);
+    modify_cycle(cpu, mem, addr, value);
+    mem.write(addr, value | 0x08);
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 5;
+}
+
+// BBS3 $nn,$nnnn (BF)
+fn cbf(cpu: &mut Cpu, mem: &mut Memory) {
+    branch_on_bit(cpu, mem, 0x08, true);
+}
+
+// SMB4 $nn (C7)
+fn cc7(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_zpg(cpu, mem);
+    let value = mem.read(addr);
+    modify_cycle(cpu, mem, addr, value);
+    mem.write(addr, value | 0x10);
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 5;
+}
+
+// WAI (CB)
+fn ccb(cpu: &mut Cpu, mem: &mut Memory) {
+    // wait for an interrupt; the pc stays put until one arrives
+    implied(cpu, mem);
+    implied(cpu, mem);
+    cpu.waiting = true;
+    cpu.cycles += 3;
+}
+
+// BBS4 $nn,$nnnn (CF)
+fn ccf(cpu: &mut Cpu, mem: &mut Memory) {
+    branch_on_bit(cpu, mem, 0x10, true);
+}
+
+// CMP ($nn) (D2)
+fn cd2(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_izp(cpu, mem);
+    let value = mem.read(addr);
+    compare(cpu, cpu.ac, value);
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 5;
+}
+
+// SMB5 $nn (D7)
+fn cd7(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_zpg(cpu, mem);
+    let value = mem.read(addr);
+    modify_cycle(cpu, mem, addr, value);
+    mem.write(addr, value | 0x20);
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 5;
+}
+
+// PHX (DA)
+fn cda(cpu: &mut Cpu, mem: &mut Memory) {
+    implied(cpu, mem);
+    push_to_stack(cpu.xr, cpu, mem);
+    cpu.pc = cpu.pc.wrapping_add(1);
+    cpu.cycles += 3;
+}
+
+// STP (DB)
+fn cdb(cpu: &mut Cpu, mem: &mut Memory) {
+    // stop the clock until reset
+    implied(cpu, mem);
+    implied(cpu, mem);
+    cpu.cycles += 3;
+}
+
+// BBS5 $nn,$nnnn (DF)
+fn cdf(cpu: &mut Cpu, mem: &mut Memory) {
+    branch_on_bit(cpu, mem, 0x20, true);
+}
+
+// SMB6 $nn (E7)
+fn ce7(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_zpg(cpu, mem);
+    let value = mem.read(addr);
+    modify_cycle(cpu, mem, addr, value);
+    mem.write(addr, value | 0x40);
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 5;
+}
+
+// BBS6 $nn,$nnnn (EF)
+fn cef(cpu: &mut Cpu, mem: &mut Memory) {
+    branch_on_bit(cpu, mem, 0x40, true);
+}
+
+// SBC ($nn) (F2)
+fn cf2(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_izp(cpu, mem);
+    let value = mem.read(addr);
+    sbc(cpu, mem, value);
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 5;
+}
+
+// SMB7 $nn (F7)
+fn cf7(cpu: &mut Cpu, mem: &mut Memory) {
+    let addr = addr_zpg(cpu, mem);
+    let value = mem.read(addr);
+    modify_cycle(cpu, mem, addr, value);
+    mem.write(addr, value | 0x80);
+    cpu.pc = cpu.pc.wrapping_add(2);
+    cpu.cycles += 5;
+}
+
+// PLX (FA)
+fn cfa(cpu: &mut Cpu, mem: &mut Memory) {
+    implied(cpu, mem);
+    stack_cycle(cpu, mem);
+    cpu.xr = pull_from_stack(cpu, mem);
+    set_nz(cpu, cpu.xr);
+    cpu.pc = cpu.pc.wrapping_add(1);
+    cpu.cycles += 4;
+}
+
+// BBS7 $nn,$nnnn (FF)
+fn cff(cpu: &mut Cpu, mem: &mut Memory) {
+    branch_on_bit(cpu, mem, 0x80, true);
+}
+
+// op code array for the 65c02
+pub const CPU_OPS_65C02: [CpuOp; 256] = [
+    //0         1          2          3          4          5          6          7          8          9          a          b          c          d          e          f
+    i00,       i01,       nop_b2_c2, nop_b1_c1, c04,       i05,       i06,       c07,       i08,       i09,       i0a,       nop_b1_c1, c0c,       i0d,       i0e,       c0f,   // 00
+    i10,       i11,       c12,       nop_b1_c1, c14,       i15,       i16,       c17,       i18,       i19,       c1a,       nop_b1_c1, c1c,       i1d,       i1e,       c1f,   // 10
+    i20,       i21,       nop_b2_c2, nop_b1_c1, i24,       i25,       i26,       c27,       i28,       i29,       i2a,       nop_b1_c1, i2c,       i2d,       i2e,       c2f,   // 20
+    i30,       i31,       c32,       nop_b1_c1, c34,       i35,       i36,       c37,       i38,       i39,       c3a,       nop_b1_c1, c3c,       i3d,       i3e,       c3f,   // 30
+    i40,       i41,       nop_b2_c2, nop_b1_c1, nop_b2_c3, i45,       i46,       c47,       i48,       i49,       i4a,       nop_b1_c1, i4c,       i4d,       i4e,       c4f,   // 40
+    i50,       i51,       c52,       nop_b1_c1, nop_b2_c4, i55,       i56,       c57,       i58,       i59,       c5a,       nop_b1_c1, nop_b3_c8, i5d,       i5e,       c5f,   // 50
+    i60,       i61,       nop_b2_c2, nop_b1_c1, c64,       i65,       i66,       c67,       i68,       i69,       i6a,       nop_b1_c1, c6c,       i6d,       i6e,       c6f,   // 60
+    i70,       i71,       c72,       nop_b1_c1, c74,       i75,       i76,       c77,       i78,       i79,       c7a,       nop_b1_c1, c7c,       i7d,       i7e,       c7f,   // 70
+    c80,       i81,       nop_b2_c2, nop_b1_c1, i84,       i85,       i86,       c87,       i88,       c89,       i8a,       nop_b1_c1, i8c,       i8d,       i8e,       c8f,   // 80
+    i90,       i91,       c92,       nop_b1_c1, i94,       i95,       i96,       c97,       i98,       i99,       i9a,       nop_b1_c1, c9c,       i9d,       c9e,       c9f,   // 90
+    ia0,       ia1,       ia2,       nop_b1_c1, ia4,       ia5,       ia6,       ca7,       ia8,       ia9,       iaa,       nop_b1_c1, iac,       iad,       iae,       caf,   // a0
+    ib0,       ib1,       cb2,       nop_b1_c1, ib4,       ib5,       ib6,       cb7,       ib8,       ib9,       iba,       nop_b1_c1, ibc,       ibd,       ibe,       cbf,   // b0
+    ic0,       ic1,       nop_b2_c2, nop_b1_c1, ic4,       ic5,       ic6,       cc7,       ic8,       ic9,       ica,       ccb,       icc,       icd,       ice,       ccf,   // c0
+    id0,       id1,       cd2,       nop_b1_c1, nop_b2_c4, id5,       id6,       cd7,       id8,       id9,       cda,       cdb,       nop_b3_c4, idd,       ide,       cdf,   // d0
+    ie0,       ie1,       nop_b2_c2, nop_b1_c1, ie4,       ie5,       ie6,       ce7,       ie8,       ie9,       iea,       nop_b1_c1, iec,       ied,       iee,       cef,   // e0
+    if0,       if1,       cf2,       nop_b1_c1, nop_b2_c4, if5,       if6,       cf7,       if8,       if9,       cfa,       nop_b1_c1, nop_b3_c4, ifd,       ife,       cff,   // f0
+];
+
+// an instruction decoded from its opcode, named for its mnemonic and
+// addressing mode
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Instruction {
+    Unimplemented, Brk, OraIzx, OraZpg, AslZpg, Php, OraImm, AslAcc, OraAbs, AslAbs, Bpl, OraIzy,
+    OraZpx, AslZpx, Clc, OraAby, OraAbx, AslAbx, JsrAbs, AndIzx, BitZpg, AndZpg, RolZpg, Plp,
+    AndImm, RolAcc, BitAbs, AndAbs, RolAbs, Bmi, AndIzy, AndZpx, RolZpx, Sec, AndAby, AndAbx,
+    RolAbx, Rti, EorIzx, EorZpg, LsrZpg, Pha, EorImm, LsrAcc, JmpAbs, EorAbs, LsrAbs, Bvc, EorIzy,
+    EorZpx, LsrZpx, Cli, EorAby, EorAbx, LsrAbx, Rts, AdcIzx, AdcZpg, RorZpg, Pla, AdcImm, RorAcc,
+    JmpInd, AdcAbs, RorAbs, Bvs, AdcIzy, AdcZpx, RorZpx, Sei, AdcAby, AdcAbx, RorAbx, StaIzx,
+    StyZpg, StaZpg, StxZpg, Dey, Txa, StyAbs, StaAbs, StxAbs, Bcc, StaIzy, StyZpx, StaZpx, StxZpy,
+    Tya, StaAby, Txs, StaAbx, LdyImm, LdaIzx, LdxImm, LdyZpg, LdaZpg, LdxZpg, Tay, LdaImm, Tax,
+    LdyAbs, LdaAbs, LdxAbs, Bcs, LdaIzy, LdyZpx, LdaZpx, LdxZpy, Clv, LdaAby, Tsx, LdyAbx, LdaAbx,
+    LdxAby, CpyImm, CmpIzx, CpyZpg, CmpZpg, DecZpg, Iny, CmpImm, Dex, CpyAbs, CmpAbs, DecAbs, Bne,
+    CmpIzy, CmpZpx, DecZpx, Cld, CmpAby, CmpAbx, DecAbx, CpxImm, SbcIzx, CpxZpg, SbcZpg, IncZpg,
+    Inx, SbcImm, Nop, CpxAbs, SbcAbs, IncAbs, Beq, SbcIzy, SbcZpx, IncZpx, Sed, SbcAby, SbcAbx,
+    IncAbx, NopB2C2, NopB1C1, TsbZpg, Rmb0Zpg, TsbAbs, Bbr0, OraIzp, TrbZpg, Rmb1Zpg, IncAcc,
+    TrbAbs, Bbr1, Rmb2Zpg, Bbr2, AndIzp, BitZpx, Rmb3Zpg, DecAcc, BitAbx, Bbr3, NopB2C3, Rmb4Zpg,
+    Bbr4, EorIzp, NopB2C4, Rmb5Zpg, Phy, NopB3C8, Bbr5, StzZpg, Rmb6Zpg, JmpIndCmos, Bbr6, AdcIzp,
+    StzZpx, Rmb7Zpg, Ply, JmpIax, Bbr7, Bra, Smb0Zpg, BitImm, Bbs0, StaIzp, Smb1Zpg, StzAbs, StzAbx,
+    Bbs1, Smb2Zpg, Bbs2, LdaIzp, Smb3Zpg, Bbs3, Smb4Zpg, Wai, Bbs4, CmpIzp, Smb5Zpg, Phx, Stp,
+    NopB3C4, Bbs5, Smb6Zpg, Bbs6, SbcIzp, Smb7Zpg, Plx, Bbs7,
+}
+
+// instructions by opcode
+const DECODE: [Instruction; 256] = {
+    use Instruction::*;
+    [
+        Brk,             OraIzx,          Unimplemented,   Unimplemented,   Unimplemented,   OraZpg,          AslZpg,          Unimplemented,   Php,             OraImm,          AslAcc,          Unimplemented,   Unimplemented,   OraAbs,          AslAbs,          Unimplemented,   // 00
+        Bpl,             OraIzy,          Unimplemented,   Unimplemented,   Unimplemented,   OraZpx,          AslZpx,          Unimplemented,   Clc,             OraAby,          Unimplemented,   Unimplemented,   Unimplemented,   OraAbx,          AslAbx,          Unimplemented,   // 10
+        JsrAbs,          AndIzx,          Unimplemented,   Unimplemented,   BitZpg,          AndZpg,          RolZpg,          Unimplemented,   Plp,             AndImm,          RolAcc,          Unimplemented,   BitAbs,          AndAbs,          RolAbs,          Unimplemented,   // 20
+        Bmi,             AndIzy,          Unimplemented,   Unimplemented,   Unimplemented,   AndZpx,          RolZpx,          Unimplemented,   Sec,             AndAby,          Unimplemented,   Unimplemented,   Unimplemented,   AndAbx,          RolAbx,          Unimplemented,   // 30
+        Rti,             EorIzx,          Unimplemented,   Unimplemented,   Unimplemented,   EorZpg,          LsrZpg,          Unimplemented,   Pha,             EorImm,          LsrAcc,          Unimplemented,   JmpAbs,          EorAbs,          LsrAbs,          Unimplemented,   // 40
+        Bvc,             EorIzy,          Unimplemented,   Unimplemented,   Unimplemented,   EorZpx,          LsrZpx,          Unimplemented,   Cli,             EorAby,          Unimplemented,   Unimplemented,   Unimplemented,   EorAbx,          LsrAbx,          Unimplemented,   // 50
+        Rts,             AdcIzx,          Unimplemented,   Unimplemented,   Unimplemented,   AdcZpg,          RorZpg,          Unimplemented,   Pla,             AdcImm,          RorAcc,          Unimplemented,   JmpInd,          AdcAbs,          RorAbs,          Unimplemented,   // 60
+        Bvs,             AdcIzy,          Unimplemented,   Unimplemented,   Unimplemented,   AdcZpx,          RorZpx,          Unimplemented,   Sei,             AdcAby,          Unimplemented,   Unimplemented,   Unimplemented,   AdcAbx,          RorAbx,          Unimplemented,   // 70
+        Unimplemented,   StaIzx,          Unimplemented,   Unimplemented,   StyZpg,          StaZpg,          StxZpg,          Unimplemented,   Dey,             Unimplemented,   Txa,             Unimplemented,   StyAbs,          StaAbs,          StxAbs,          Unimplemented,   // 80
+        Bcc,             StaIzy,          Unimplemented,   Unimplemented,   StyZpx,          StaZpx,          StxZpy,          Unimplemented,   Tya,             StaAby,          Txs,             Unimplemented,   Unimplemented,   StaAbx,          Unimplemented,   Unimplemented,   // 90
+        LdyImm,          LdaIzx,          LdxImm,          Unimplemented,   LdyZpg,          LdaZpg,          LdxZpg,          Unimplemented,   Tay,             LdaImm,          Tax,             Unimplemented,   LdyAbs,          LdaAbs,          LdxAbs,          Unimplemented,   // a0
+        Bcs,             LdaIzy,          Unimplemented,   Unimplemented,   LdyZpx,          LdaZpx,          LdxZpy,          Unimplemented,   Clv,             LdaAby,          Tsx,             Unimplemented,   LdyAbx,          LdaAbx,          LdxAby,          Unimplemented,   // b0
+        CpyImm,          CmpIzx,          Unimplemented,   Unimplemented,   CpyZpg,          CmpZpg,          DecZpg,          Unimplemented,   Iny,             CmpImm,          Dex,             Unimplemented,   CpyAbs,          CmpAbs,          DecAbs,          Unimplemented,   // c0
+        Bne,             CmpIzy,          Unimplemented,   Unimplemented,   Unimplemented,   CmpZpx,          DecZpx,          Unimplemented,   Cld,             CmpAby,          Unimplemented,   Unimplemented,   Unimplemented,   CmpAbx,          DecAbx,          Unimplemented,   // d0
+        CpxImm,          SbcIzx,          Unimplemented,   Unimplemented,   CpxZpg,          SbcZpg,          IncZpg,          Unimplemented,   Inx,             SbcImm,          Nop,             Unimplemented,   CpxAbs,          SbcAbs,          IncAbs,          Unimplemented,   // e0
+        Beq,             SbcIzy,          Unimplemented,   Unimplemented,   Unimplemented,   SbcZpx,          IncZpx,          Unimplemented,   Sed,             SbcAby,          Unimplemented,   Unimplemented,   Unimplemented,   SbcAbx,          IncAbx,          Unimplemented,   // f0
+    ]
+};
+
+// instructions by opcode for the 65c02
+const DECODE_65C02: [Instruction; 256] = {
+    use Instruction::*;
+    [
+        Brk,          OraIzx,       NopB2C2,      NopB1C1,      TsbZpg,       OraZpg,       AslZpg,       Rmb0Zpg,      Php,          OraImm,       AslAcc,       NopB1C1,      TsbAbs,       OraAbs,       AslAbs,       Bbr0,         // 00
+        Bpl,          OraIzy,       OraIzp,       NopB1C1,      TrbZpg,       OraZpx,       AslZpx,       Rmb1Zpg,      Clc,          OraAby,       IncAcc,       NopB1C1,      TrbAbs,       OraAbx,       AslAbx,       Bbr1,         // 10
+        JsrAbs,       AndIzx,       NopB2C2,      NopB1C1,      BitZpg,       AndZpg,       RolZpg,       Rmb2Zpg,      Plp,          AndImm,       RolAcc,       NopB1C1,      BitAbs,       AndAbs,       RolAbs,       Bbr2,         // 20
+        Bmi,          AndIzy,       AndIzp,       NopB1C1,      BitZpx,       AndZpx,       RolZpx,       Rmb3Zpg,      Sec,          AndAby,       DecAcc,       NopB1C1,      BitAbx,       AndAbx,       RolAbx,       Bbr3,         // 30
+        Rti,          EorIzx,       NopB2C2,      NopB1C1,      NopB2C3,      EorZpg,       LsrZpg,       Rmb4Zpg,      Pha,          EorImm,       LsrAcc,       NopB1C1,      JmpAbs,       EorAbs,       LsrAbs,       Bbr4,         // 40
+        Bvc,          EorIzy,       EorIzp,       NopB1C1,      NopB2C4,      EorZpx,       LsrZpx,       Rmb5Zpg,      Cli,          EorAby,       Phy,          NopB1C1,      NopB3C8,      EorAbx,       LsrAbx,       Bbr5,         // 50
+        Rts,          AdcIzx,       NopB2C2,      NopB1C1,      StzZpg,       AdcZpg,       RorZpg,       Rmb6Zpg,      Pla,          AdcImm,       RorAcc,       NopB1C1,      JmpIndCmos,   AdcAbs,       RorAbs,       Bbr6,         // 60
+        Bvs,          AdcIzy,       AdcIzp,       NopB1C1,      StzZpx,       AdcZpx,       RorZpx,       Rmb7Zpg,      Sei,          AdcAby,       Ply,          NopB1C1,      JmpIax,       AdcAbx,       RorAbx,       Bbr7,         // 70
+        Bra,          StaIzx,       NopB2C2,      NopB1C1,      StyZpg,       StaZpg,       StxZpg,       Smb0Zpg,      Dey,          BitImm,       Txa,          NopB1C1,      StyAbs,       StaAbs,       StxAbs,       Bbs0,         // 80
+        Bcc,          StaIzy,       StaIzp,       NopB1C1,      StyZpx,       StaZpx,       StxZpy,       Smb1Zpg,      Tya,          StaAby,       Txs,          NopB1C1,      StzAbs,       StaAbx,       StzAbx,       Bbs1,         // 90
+        LdyImm,       LdaIzx,       LdxImm,       NopB1C1,      LdyZpg,       LdaZpg,       LdxZpg,       Smb2Zpg,      Tay,          LdaImm,       Tax,          NopB1C1,      LdyAbs,       LdaAbs,       LdxAbs,       Bbs2,         // a0
+        Bcs,          LdaIzy,       LdaIzp,       NopB1C1,      LdyZpx,       LdaZpx,       LdxZpy,       Smb3Zpg,      Clv,          LdaAby,       Tsx,          NopB1C1,      LdyAbx,       LdaAbx,       LdxAby,       Bbs3,         // b0
+        CpyImm,       CmpIzx,       NopB2C2,      NopB1C1,      CpyZpg,       CmpZpg,       DecZpg,       Smb4Zpg,      Iny,          CmpImm,       Dex,          Wai,          CpyAbs,       CmpAbs,       DecAbs,       Bbs4,         // c0
+        Bne,          CmpIzy,       CmpIzp,       NopB1C1,      NopB2C4,      CmpZpx,       DecZpx,       Smb5Zpg,      Cld,          CmpAby,       Phx,          Stp,          NopB3C4,      CmpAbx,       DecAbx,       Bbs5,         // d0
+        CpxImm,       SbcIzx,       NopB2C2,      NopB1C1,      CpxZpg,       SbcZpg,       IncZpg,       Smb6Zpg,      Inx,          SbcImm,       Nop,          NopB1C1,      CpxAbs,       SbcAbs,       IncAbs,       Bbs6,         // e0
+        Beq,          SbcIzy,       SbcIzp,       NopB1C1,      NopB2C4,      SbcZpx,       IncZpx,       Smb7Zpg,      Sed,          SbcAby,       Plx,          NopB1C1,      NopB3C4,      SbcAbx,       IncAbx,       Bbs7,         // f0
+    ]
+};
@@ -0,0 +1,661 @@
+// a Debug Adapter Protocol server for --dap, so an editor can debug the
+// emulated program
+//
+// the editor connects over tcp and each message either way is json after
+// a Content-Length header. one client is served at a time; a new
+// connection takes over from the last. the requests answered are
+//
+//   initialize, launch, attach, configurationDone, disconnect, terminate
+//   setBreakpoints, setInstructionBreakpoints, setExceptionBreakpoints
+//   threads, stackTrace, scopes, variables, setVariable, evaluate
+//   continue, pause, next, stepIn, stepOut
+//   readMemory, writeMemory, disassemble
+//
+// there is one thread, the cpu, with one frame, the instruction at the
+// pc. its scopes are the registers and the status flags, and evaluate
+// knows registers, labels and addresses. the machine waits until
+// configurationDone, stopping there if launch asked for stopOnEntry.
+//
+// with the debug info from --dbg, breakpoints can be set on source lines,
+// the frame shows where the pc is in the source and next and stepIn go
+// a line at a time; without it, or when the editor asks for instruction
+// granularity, they go an instruction at a time. next and stepOut run
+// subroutines called with JSR to their return. a breakpoint is a stop
+// address, so it is added to and taken from the --stop-at list.
+//
+// as with --http, connections are read on background threads and the
+// main loop looks for messages between instructions.
+
+use std::collections::BTreeMap;
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread;
+
+use crate::cpu::{Cpu, STATUS_BIT_INT_DIS, STATUS_FLAGS_BREAK, STATUS_FLAGS_CARRY, STATUS_FLAGS_DECIMAL,
+    STATUS_FLAGS_NEGATIVE, STATUS_FLAGS_OVERFLOW, STATUS_FLAGS_UNUSED, STATUS_FLAGS_ZERO};
+use crate::dbginfo::DebugInfo;
+use crate::disasm::{disassemble, disassemble_around};
+use crate::json;
+use crate::json::{Object, Value};
+use crate::memory::{Memory, MEMSIZE};
+use crate::remote;
+use crate::remote::{RemoteAction, RemoteControl};
+use crate::websocket::{base64, base64_decode};
+use crate::StopReason;
+
+// the largest message taken from a client
+const MAX_MESSAGE: usize = 1 << 20;
+
+const THREAD_ID: u32 = 1;
+const REGISTERS: u32 = 1;           // variable references for the scopes
+const FLAGS: u32 = 2;
+
+const FLAG_NAMES: [(&str, u8); 8] = [
+    ("N", STATUS_FLAGS_NEGATIVE),
+    ("V", STATUS_FLAGS_OVERFLOW),
+    ("-", STATUS_FLAGS_UNUSED),
+    ("B", STATUS_FLAGS_BREAK),
+    ("D", STATUS_FLAGS_DECIMAL),
+    ("I", STATUS_BIT_INT_DIS),
+    ("Z", STATUS_FLAGS_ZERO),
+    ("C", STATUS_FLAGS_CARRY),
+];
+
+const OP_JSR: u8 = 0x20;
+const OP_RTI: u8 = 0x40;
+const OP_RTS: u8 = 0x60;
+
+// what the connection threads pass to the main loop, each with the
+// number of its connection
+enum Incoming {
+    Opened(usize, TcpStream),
+    Message(usize, String),
+    Closed(usize),
+}
+
+struct Client {
+    id: usize,
+    out: BufWriter<TcpStream>,
+}
+
+// a next, stepIn or stepOut under way
+struct Step {
+    lines: bool,                    // by source line rather than instruction
+    over: bool,                     // run subroutines to their return
+    out: bool,                      // until the subroutine returns
+    sp: u8,                         // the stack pointer it started with
+    from: Option<(PathBuf, u32)>,   // the source line it started on
+    until: Option<(u16, u8)>,       // running a subroutine: its return and sp
+}
+
+impl Step {
+    // whether the step is over once the instruction at pc has run
+    fn done(&mut self, cpu: &Cpu, mem: &Memory, pc: u16, debug: Option<&DebugInfo>) -> bool {
+        let opcode = mem.mem[pc as usize];
+        if let Some((addr, sp)) = self.until {
+            if cpu.pc != addr || cpu.sp != sp {
+                return false;
+            }
+            self.until = None;
+        } else if self.over && opcode == OP_JSR {
+            self.until = Some((pc.wrapping_add(3), cpu.sp.wrapping_add(2)));
+            return false;
+        }
+        if self.out {
+            return (opcode == OP_RTS || opcode == OP_RTI) && cpu.sp > self.sp;
+        }
+        match debug.filter(|_| self.lines) {
+            Some(debug) => match debug.line_at(cpu.pc) {
+                Some(line) => debug.starts_line(cpu.pc)
+                    && self.from.as_ref().is_none_or(|(path, number)| path != line.path || *number != line.line),
+                // out of the code there's source for
+                None => true,
+            },
+            None => true,
+        }
+    }
+}
+
+pub struct DapControl {
+    incoming: Receiver<Incoming>,
+    client: Option<Client>,
+    seq: u64,
+    debug: Option<DebugInfo>,
+    source_breakpoints: BTreeMap<PathBuf, Vec<u16>>,
+    instruction_breakpoints: Vec<u16>,
+    added: Vec<u16>,                // the stop addresses put there for them
+    stop_on_entry: bool,
+    stepping: Option<Step>,
+}
+
+impl DapControl {
+    // listen on addr, like 127.0.0.1:4711
+    pub fn start(addr: &str, debug: Option<DebugInfo>) -> Result<DapControl, String> {
+        let listener = TcpListener::bind(addr).map_err(|e| format!("{}: {}", addr, e))?;
+        let (tx, rx) = channel();
+        thread::spawn(move || {
+            for (id, stream) in listener.incoming().flatten().enumerate() {
+                let Ok(reader) = stream.try_clone() else { continue };
+                if tx.send(Incoming::Opened(id, stream)).is_err() {
+                    return;
+                }
+                let tx = tx.clone();
+                thread::spawn(move || read_messages(id, BufReader::new(reader), &tx));
+            }
+        });
+        Ok(DapControl {
+            incoming: rx,
+            client: None,
+            seq: 0,
+            debug,
+            source_breakpoints: BTreeMap::new(),
+            instruction_breakpoints: Vec::new(),
+            added: Vec::new(),
+            stop_on_entry: false,
+            stepping: None,
+        })
+    }
+
+}
+
+impl RemoteControl for DapControl {
+    // answer the next message, waiting for one if wait is set
+    fn serve(&mut self, cpu: &mut Cpu, mem: &mut Memory, breakpoints: &mut Vec<u16>, running: bool, wait: bool)
+        -> RemoteAction {
+        if wait {
+            self.flush();
+        }
+        let incoming = match remote::receive(&self.incoming, wait) {
+            Ok(incoming) => incoming,
+            Err(action) => return action,
+        };
+        let action = match incoming {
+            Incoming::Opened(id, stream) => {
+                // the last client's breakpoints go with it
+                self.forget_client(breakpoints);
+                self.client = Some(Client { id, out: BufWriter::new(stream) });
+                RemoteAction::None
+            }
+            Incoming::Message(id, text) if self.client.as_ref().is_some_and(|client| client.id == id) => {
+                self.message(&text, cpu, mem, breakpoints, running)
+            }
+            Incoming::Closed(id) if self.client.as_ref().is_some_and(|client| client.id == id) => {
+                self.forget_client(breakpoints);
+                self.client = None;
+                RemoteAction::None
+            }
+            Incoming::Message(..) | Incoming::Closed(_) => RemoteAction::None,
+        };
+        self.flush();
+        action
+    }
+
+    // tell the editor about stops, and see to a step under way
+    fn after_instruction(&mut self, cpu: &Cpu, mem: &mut Memory, pc: u16, stop: Option<&StopReason>) {
+        if let Some(reason) = stop {
+            self.stepping = None;
+            let kind = match reason {
+                StopReason::StopAddress(_) => "breakpoint",
+                StopReason::Watched(..) | StopReason::WatchedEntry(..) => "data breakpoint",
+                _ => "exception",
+            };
+            self.stopped(kind, Some(&reason.to_string()));
+            self.flush();
+            return;
+        }
+        let debug = self.debug.as_ref();
+        if self.stepping.as_mut().is_some_and(|step| step.done(cpu, mem, pc, debug)) {
+            self.stepping = None;
+            self.stopped("step", None);
+            self.flush();
+        }
+    }
+
+    fn stepping(&self) -> bool {
+        self.stepping.is_some()
+    }
+}
+
+impl DapControl {
+    fn message(&mut self, text: &str, cpu: &mut Cpu, mem: &mut Memory, breakpoints: &mut Vec<u16>, running: bool)
+        -> RemoteAction {
+        let request = match json::parse(text) {
+            Ok(Value::Object(request)) => request,
+            _ => return RemoteAction::None,
+        };
+        if request.get("type") != Some(&Value::String(String::from("request"))) {
+            return RemoteAction::None;
+        }
+        let seq = match request.get("seq") {
+            Some(Value::Number(seq)) => *seq as u64,
+            _ => 0,
+        };
+        let command = match request.get("command") {
+            Some(Value::String(command)) => command.clone(),
+            _ => return RemoteAction::None,
+        };
+        let no_arguments = Object::new();
+        let arguments = match request.get("arguments") {
+            Some(Value::Object(arguments)) => arguments,
+            _ => &no_arguments,
+        };
+
+        let busy = running || self.stepping.is_some();
+        let (body, action) = match self.request(&command, arguments, cpu, mem, breakpoints, busy) {
+            Ok(answer) => answer,
+            Err(message) => {
+                self.send(&format!("\"type\":\"response\",\"request_seq\":{},\"success\":false,\"command\":{},\
+                    \"message\":{}", seq, json::quote(&command), json::quote(&message)));
+                return RemoteAction::None;
+            }
+        };
+        self.send(&format!("\"type\":\"response\",\"request_seq\":{},\"success\":true,\"command\":{},\"body\":{}",
+            seq, json::quote(&command), body));
+
+        // events that follow the response
+        match command.as_str() {
+            "initialize" => self.event("initialized", "{}"),
+            "pause" if busy => self.stopped("pause", None),
+            "configurationDone" if self.stop_on_entry => self.stopped("entry", None),
+            _ => {}
+        }
+        action
+    }
+
+    // the body of the response and what the main loop should do, or
+    // why the request failed
+    fn request(&mut self, command: &str, arguments: &Object, cpu: &mut Cpu, mem: &mut Memory,
+        breakpoints: &mut Vec<u16>, busy: bool) -> Result<(String, RemoteAction), String> {
+        let answer = |body: String| Ok((body, RemoteAction::None));
+        match command {
+            "initialize" => answer(String::from("{\"supportsConfigurationDoneRequest\":true,\
+                \"supportsSetVariable\":true,\"supportsReadMemoryRequest\":true,\"supportsWriteMemoryRequest\":true,\
+                \"supportsDisassembleRequest\":true,\"supportsInstructionBreakpoints\":true,\
+                \"supportsSteppingGranularity\":true,\"supportsTerminateRequest\":true,\
+                \"supportsEvaluateForHovers\":true}")),
+            "launch" | "attach" => {
+                self.stop_on_entry = arguments.get("stopOnEntry") == Some(&Value::Boolean(true));
+                answer(String::from("{}"))
+            }
+            "configurationDone" => {
+                let action = if self.stop_on_entry { RemoteAction::None } else { RemoteAction::Resume };
+                Ok((String::from("{}"), action))
+            }
+            "disconnect" => {
+                let action = match arguments.get("terminateDebuggee") {
+                    Some(Value::Boolean(false)) => {
+                        self.forget_client(breakpoints);
+                        RemoteAction::Resume
+                    }
+                    _ => RemoteAction::Quit,
+                };
+                Ok((String::from("{}"), action))
+            }
+            "terminate" => {
+                self.event("terminated", "{}");
+                Ok((String::from("{}"), RemoteAction::Quit))
+            }
+            "setBreakpoints" => {
+                let body = self.set_breakpoints(arguments)?;
+                self.update_breakpoints(breakpoints);
+                answer(body)
+            }
+            "setInstructionBreakpoints" => {
+                let mut verified = Vec::new();
+                self.instruction_breakpoints.clear();
+                for breakpoint in array(arguments, "breakpoints") {
+                    let Value::Object(breakpoint) = breakpoint else { continue };
+                    let reference = string(breakpoint, "instructionReference").unwrap_or_default();
+                    let offset = integer(breakpoint, "offset").unwrap_or(0);
+                    match self.memory_reference(reference) {
+                        Some(addr) => {
+                            let addr = (addr as i64 + offset) as u16;
+                            self.instruction_breakpoints.push(addr);
+                            verified.push(format!("{{\"verified\":true,\"instructionReference\":\"0x{:04x}\"}}", addr));
+                        }
+                        None => verified.push(String::from("{\"verified\":false,\"message\":\"not an address\"}")),
+                    }
+                }
+                self.update_breakpoints(breakpoints);
+                answer(format!("{{\"breakpoints\":[{}]}}", verified.join(",")))
+            }
+            "setExceptionBreakpoints" => answer(String::from("{}")),
+            "threads" => answer(format!("{{\"threads\":[{{\"id\":{},\"name\":\"6502\"}}]}}", THREAD_ID)),
+            "stackTrace" => answer(format!("{{\"stackFrames\":[{}],\"totalFrames\":1}}", self.frame(cpu))),
+            "scopes" => answer(format!("{{\"scopes\":[\
+                {{\"name\":\"Registers\",\"presentationHint\":\"registers\",\"variablesReference\":{},\"expensive\":false}},\
+                {{\"name\":\"Flags\",\"variablesReference\":{},\"expensive\":false}}]}}", REGISTERS, FLAGS)),
+            "variables" => {
+                let variables: Vec<String> = match integer(arguments, "variablesReference") {
+                    Some(reference) if reference == REGISTERS as i64 => registers(cpu).iter()
+                        .map(|(name, value)| variable(name, value))
+                        .collect(),
+                    Some(reference) if reference == FLAGS as i64 => FLAG_NAMES.iter()
+                        .map(|(name, flag)| variable(name, if cpu.status() & flag != 0 { "1" } else { "0" }))
+                        .collect(),
+                    _ => Vec::new(),
+                };
+                answer(format!("{{\"variables\":[{}]}}", variables.join(",")))
+            }
+            "setVariable" => {
+                let name = string(arguments, "name").ok_or("setVariable needs a name")?;
+                let text = string(arguments, "value").ok_or("setVariable needs a value")?;
+                let value = parse_value(text).ok_or(format!("'{}' isn't a number", text))?;
+                let shown = match integer(arguments, "variablesReference") {
+                    Some(reference) if reference == FLAGS as i64 => {
+                        let &(_, flag) = FLAG_NAMES.iter().find(|(flag, _)| *flag == name)
+                            .ok_or(format!("no flag {}", name))?;
+                        let status = if value != 0 { cpu.status() | flag } else { cpu.status() & !flag };
+                        cpu.set_status(status);
+                        String::from(if value != 0 { "1" } else { "0" })
+                    }
+                    _ => set_register(cpu, name, value)?,
+                };
+                answer(format!("{{\"value\":{}}}", json::quote(&shown)))
+            }
+            "evaluate" => {
+                let expression = string(arguments, "expression").unwrap_or_default().trim();
+                if let Some((_, value)) = registers(cpu).into_iter().find(|(name, _)| name.eq_ignore_ascii_case(expression)) {
+                    return answer(format!("{{\"result\":{},\"variablesReference\":0}}", json::quote(&value)));
+                }
+                let addr = self.memory_reference(expression).ok_or(format!("can't evaluate '{}'", expression))?;
+                answer(format!("{{\"result\":\"${:02x} at ${:04x}\",\"variablesReference\":0,\
+                    \"memoryReference\":\"0x{:04x}\"}}", mem.mem[addr as usize], addr, addr))
+            }
+            "continue" => {
+                self.stepping = None;
+                Ok((String::from("{\"allThreadsContinued\":true}"), RemoteAction::Resume))
+            }
+            "pause" => {
+                self.stepping = None;
+                Ok((String::from("{}"), RemoteAction::Pause))
+            }
+            "next" | "stepIn" | "stepOut" => {
+                if busy {
+                    return Err(String::from("pause before stepping"));
+                }
+                let lines = self.debug.is_some() && string(arguments, "granularity") != Some("instruction");
+                let from = self.debug.as_ref()
+                    .and_then(|debug| debug.line_at(cpu.pc))
+                    .map(|line| (line.path.to_path_buf(), line.line));
+                self.stepping = Some(Step {
+                    lines,
+                    over: command != "stepIn",
+                    out: command == "stepOut",
+                    sp: cpu.sp,
+                    from,
+                    until: None,
+                });
+                answer(String::from("{}"))
+            }
+            "readMemory" => {
+                let start = self.memory_at(arguments)?;
+                let count = integer(arguments, "count").unwrap_or(0).clamp(0, (MEMSIZE - start) as i64) as usize;
+                answer(format!("{{\"address\":\"0x{:04x}\",\"data\":\"{}\"}}", start,
+                    base64(&mem.mem[start..start + count])))
+            }
+            "writeMemory" => {
+                let start = self.memory_at(arguments)?;
+                let data = string(arguments, "data").and_then(base64_decode).ok_or("data should be base64")?;
+                if start + data.len() > MEMSIZE {
+                    return Err(format!("{} bytes at ${:04x} don't fit in memory", data.len(), start));
+                }
+                mem.mem[start..start + data.len()].copy_from_slice(&data);
+                answer(format!("{{\"bytesWritten\":{}}}", data.len()))
+            }
+            "disassemble" => {
+                let addr = self.memory_at(arguments)? as u16;
+                let count = integer(arguments, "instructionCount").unwrap_or(0).max(0);
+                let offset = integer(arguments, "instructionOffset").unwrap_or(0);
+                answer(format!("{{\"instructions\":[{}]}}", self.disassembly(cpu, mem, addr, offset, count).join(",")))
+            }
+            _ => Err(format!("{} isn't supported", command)),
+        }
+    }
+
+    // set the breakpoints for a source file; returns the response body
+    fn set_breakpoints(&mut self, arguments: &Object) -> Result<String, String> {
+        let source = match arguments.get("source") {
+            Some(Value::Object(source)) => source,
+            _ => return Err(String::from("setBreakpoints needs a source")),
+        };
+        let path = PathBuf::from(string(source, "path").ok_or("setBreakpoints needs a source path")?);
+        let mut addrs = Vec::new();
+        let mut verified = Vec::new();
+        for breakpoint in array(arguments, "breakpoints") {
+            let Value::Object(breakpoint) = breakpoint else { continue };
+            let line = integer(breakpoint, "line").unwrap_or(0).max(0) as u32;
+            let found = self.debug.as_ref().and_then(|debug| debug.addr_of(&path, line));
+            match found {
+                Some((addr, line)) => {
+                    addrs.push(addr);
+                    verified.push(format!("{{\"verified\":true,\"line\":{},\"instructionReference\":\"0x{:04x}\"}}",
+                        line, addr));
+                }
+                None => {
+                    let message = if self.debug.is_some() { "no code for this line" } else { "no --dbg file given" };
+                    verified.push(format!("{{\"verified\":false,\"line\":{},\"message\":\"{}\"}}", line, message));
+                }
+            }
+        }
+        self.source_breakpoints.insert(path, addrs);
+        Ok(format!("{{\"breakpoints\":[{}]}}", verified.join(",")))
+    }
+
+    // put the client's breakpoints in the stop addresses in place of the
+    // ones there for it before
+    fn update_breakpoints(&mut self, breakpoints: &mut Vec<u16>) {
+        for addr in self.added.drain(..) {
+            if let Some(index) = breakpoints.iter().position(|&breakpoint| breakpoint == addr) {
+                breakpoints.remove(index);
+            }
+        }
+        self.added = self.source_breakpoints.values().flatten().chain(&self.instruction_breakpoints).copied().collect();
+        breakpoints.extend(&self.added);
+    }
+
+    fn forget_client(&mut self, breakpoints: &mut Vec<u16>) {
+        self.source_breakpoints.clear();
+        self.instruction_breakpoints.clear();
+        self.update_breakpoints(breakpoints);
+        self.stop_on_entry = false;
+        self.stepping = None;
+    }
+
+    // the one stack frame, at the pc
+    fn frame(&self, cpu: &Cpu) -> String {
+        let name = match self.debug.as_ref().and_then(|debug| debug.label_before(cpu.pc)) {
+            Some((label, 0)) => label.to_string(),
+            Some((label, offset)) => format!("{}+{}", label, offset),
+            None => format!("${:04x}", cpu.pc),
+        };
+        let source = match self.debug.as_ref().and_then(|debug| debug.line_at(cpu.pc)) {
+            Some(line) => format!("\"source\":{},\"line\":{},\"column\":1,", source(line.path), line.line),
+            None => String::from("\"line\":0,\"column\":0,"),
+        };
+        format!("{{\"id\":0,\"name\":{},{}\"instructionPointerReference\":\"0x{:04x}\"}}",
+            json::quote(&name), source, cpu.pc)
+    }
+
+    // count instructions from offset instructions away from addr
+    fn disassembly(&self, cpu: &Cpu, mem: &Memory, addr: u16, offset: i64, count: i64) -> Vec<String> {
+        let before = (-offset).clamp(0, 0xffff) as u16;
+        let after = (offset + count - 1).clamp(0, 0xffff) as u16;
+        let around = disassemble_around(cpu.variant, mem, addr, before, after);
+        let at = around.iter().position(|&(pc, _)| pc == addr).unwrap_or(0) as i64;
+
+        // before the start of what could be decoded the editor is told
+        // there is nothing
+        let first = at + offset;
+        let mut instructions = Vec::new();
+        for index in first..first + count {
+            if index < 0 {
+                let pc = around[0].0.wrapping_sub((-index) as u16);
+                instructions.push(format!("{{\"address\":\"0x{:04x}\",\"instruction\":\"\",\
+                    \"presentationHint\":\"invalid\"}}", pc));
+                continue;
+            }
+            let Some((pc, text)) = around.get(index as usize) else { break };
+            let len = disassemble(cpu.variant, mem, *pc).1;
+            let bytes: Vec<String> = (0..len).map(|i| format!("{:02x}", mem.mem[pc.wrapping_add(i) as usize])).collect();
+            let mut fields = format!("\"address\":\"0x{:04x}\",\"instructionBytes\":\"{}\",\"instruction\":{}",
+                pc, bytes.join(" "), json::quote(text));
+            if let Some(debug) = &self.debug {
+                if let Some((label, 0)) = debug.label_before(*pc) {
+                    fields.push_str(&format!(",\"symbol\":{}", json::quote(label)));
+                }
+                if let Some(line) = debug.line_at(*pc).filter(|_| debug.starts_line(*pc)) {
+                    fields.push_str(&format!(",\"location\":{},\"line\":{}", source(line.path), line.line));
+                }
+            }
+            instructions.push(format!("{{{}}}", fields));
+        }
+        instructions
+    }
+
+    // an address as hex with $ or 0x, decimal or a label
+    fn memory_reference(&self, text: &str) -> Option<u16> {
+        if let Some(addr) = parse_value(text).filter(|&value| value <= 0xffff) {
+            return Some(addr as u16);
+        }
+        self.debug.as_ref().and_then(|debug| debug.label_addr(text))
+    }
+
+    // the memoryReference and offset of a memory request
+    fn memory_at(&self, arguments: &Object) -> Result<usize, String> {
+        let reference = string(arguments, "memoryReference").ok_or("a memoryReference is needed")?;
+        let addr = self.memory_reference(reference).ok_or(format!("bad memoryReference '{}'", reference))?;
+        let addr = addr as i64 + integer(arguments, "offset").unwrap_or(0);
+        if !(0..MEMSIZE as i64).contains(&addr) {
+            return Err(String::from("the address is outside memory"));
+        }
+        Ok(addr as usize)
+    }
+
+    fn stopped(&mut self, reason: &str, description: Option<&str>) {
+        let description = description.map(|text| format!(",\"text\":{}", json::quote(text))).unwrap_or_default();
+        self.event("stopped", &format!("{{\"reason\":\"{}\",\"threadId\":{},\"allThreadsStopped\":true{}}}",
+            reason, THREAD_ID, description));
+    }
+
+    fn event(&mut self, event: &str, body: &str) {
+        self.send(&format!("\"type\":\"event\",\"event\":\"{}\",\"body\":{}", event, body));
+    }
+
+    // send a message made of fields, numbering it
+    fn send(&mut self, fields: &str) {
+        self.seq += 1;
+        let Some(client) = &mut self.client else { return };
+        let message = format!("{{\"seq\":{},{}}}", self.seq, fields);
+        let _result = write!(client.out, "Content-Length: {}\r\n\r\n{}", message.len(), message);
+    }
+
+    fn flush(&mut self) {
+        if let Some(client) = &mut self.client {
+            let _result = client.out.flush();
+        }
+    }
+}
+
+// read a client's messages until it goes away, passing them on
+fn read_messages(id: usize, mut reader: BufReader<TcpStream>, incoming: &Sender<Incoming>) {
+    while let Some(text) = read_message(&mut reader) {
+        if incoming.send(Incoming::Message(id, text)).is_err() {
+            return;
+        }
+    }
+    let _result = incoming.send(Incoming::Closed(id));
+}
+
+fn read_message(reader: &mut BufReader<TcpStream>) -> Option<String> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header).ok()? == 0 {
+            return None;
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+    let length = length.filter(|&length| length <= MAX_MESSAGE)?;
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).ok()?;
+    Some(String::from_utf8_lossy(&body).into_owned())
+}
+
+fn registers(cpu: &Cpu) -> [(&'static str, String); 6] {
+    [
+        ("A", format!("${:02x}", cpu.ac)),
+        ("X", format!("${:02x}", cpu.xr)),
+        ("Y", format!("${:02x}", cpu.yr)),
+        ("SP", format!("${:02x}", cpu.sp)),
+        ("PC", format!("${:04x}", cpu.pc)),
+        ("P", format!("${:02x}", cpu.status())),
+    ]
+}
+
+// set a register by name; returns its value as shown
+fn set_register(cpu: &mut Cpu, name: &str, value: u64) -> Result<String, String> {
+    if name == "PC" {
+        cpu.pc = u16::try_from(value).map_err(|_| String::from("PC takes values up to $ffff"))?;
+        return Ok(format!("${:04x}", cpu.pc));
+    }
+    let byte = u8::try_from(value).map_err(|_| format!("{} takes values up to $ff", name))?;
+    match name {
+        "A" => cpu.ac = byte,
+        "X" => cpu.xr = byte,
+        "Y" => cpu.yr = byte,
+        "SP" => cpu.sp = byte,
+        "P" => cpu.set_status(byte),
+        _ => return Err(format!("no register {}", name)),
+    }
+    Ok(format!("${:02x}", byte))
+}
+
+fn variable(name: &str, value: &str) -> String {
+    format!("{{\"name\":{},\"value\":{},\"variablesReference\":0}}", json::quote(name), json::quote(value))
+}
+
+fn source(path: &Path) -> String {
+    let name = path.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+    format!("{{\"name\":{},\"path\":{}}}", json::quote(&name), json::quote(&path.to_string_lossy()))
+}
+
+// a number as hex with $ or 0x, or decimal
+fn parse_value(text: &str) -> Option<u64> {
+    let text = text.trim();
+    match text.strip_prefix('$').or_else(|| text.strip_prefix("0x")) {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => text.parse().ok(),
+    }
+}
+
+fn string<'a>(object: &'a Object, key: &str) -> Option<&'a str> {
+    match object.get(key) {
+        Some(Value::String(text)) => Some(text),
+        _ => None,
+    }
+}
+
+fn integer(object: &Object, key: &str) -> Option<i64> {
+    match object.get(key) {
+        Some(Value::Number(n)) if n.fract() == 0.0 => Some(*n as i64),
+        _ => None,
+    }
+}
+
+fn array<'a>(object: &'a Object, key: &str) -> &'a [Value] {
+    match object.get(key) {
+        Some(Value::Array(values)) => values,
+        _ => &[],
+    }
+}
@@ -0,0 +1,225 @@
+// reader for the debug info ld65 writes with --dbgfile
+//
+// the file is a line per record, a keyword then key=value pairs:
+//
+//   file id=0,name="hello.c",size=212,mtime=0x66f0a1b2,mod=0
+//   seg  id=0,name="CODE",start=0x000400,size=0x0123,addrsize=absolute,type=ro
+//   span id=5,seg=0,start=16,size=3
+//   line id=9,file=0,line=12,type=1,span=5+6
+//   sym  id=3,name="_main",addrsize=absolute,scope=0,def=1,val=0x410,type=lab
+//
+// a span is a range of bytes in a segment, and a line says which spans
+// its code went into. lines from C have type 1, those in a macro type 2,
+// and assembler lines no type; where they overlap C is preferred and
+// macros come last. file names are as the assembler was given them, so
+// relative ones are taken from the directory the debug file is in.
+// other records, and keys not used here, are skipped.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::args::parse_addr;
+
+pub struct DebugInfo {
+    files: Vec<PathBuf>,            // by the order they were listed
+    lines: Vec<Line>,
+    labels: BTreeMap<u16, String>,
+}
+
+// the code from one source line
+#[derive(Clone, Copy)]
+struct Line {
+    file: usize,                    // index into files
+    line: u32,
+    rank: u8,                       // 0 for C, 1 for assembler, 2 for a macro
+    start: u16,
+    end: u16,                       // inclusive
+}
+
+// a source line at an address
+pub struct SourceLine<'a> {
+    pub path: &'a Path,
+    pub line: u32,
+}
+
+impl DebugInfo {
+    pub fn load(path: &str) -> Result<DebugInfo, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        let dir = Path::new(path).parent().unwrap_or(Path::new(""));
+        let mut info = parse(&text, dir).map_err(|(line, message)| format!("{}:{}: {}", path, line, message))?;
+        // editors name sources by their full paths
+        for file in &mut info.files {
+            if let Ok(full) = fs::canonicalize(&*file) {
+                *file = full;
+            }
+        }
+        Ok(info)
+    }
+
+    // the source line whose code includes addr
+    pub fn line_at(&self, addr: u16) -> Option<SourceLine<'_>> {
+        let line = self.lines.iter()
+            .filter(|line| line.start <= addr && addr <= line.end)
+            .min_by_key(|line| (line.rank, line.end - line.start))?;
+        Some(SourceLine { path: &self.files[line.file], line: line.line })
+    }
+
+    // whether addr starts the code of a source line
+    pub fn starts_line(&self, addr: u16) -> bool {
+        self.lines.iter().any(|line| line.start == addr)
+    }
+
+    // the first address of the code for a line of the file at path, or
+    // of the next line after it that has code; returns the address and
+    // the line it was found on
+    pub fn addr_of(&self, path: &Path, line: u32) -> Option<(u16, u32)> {
+        let file = self.find_file(path)?;
+        self.lines.iter()
+            .filter(|entry| entry.file == file && entry.line >= line)
+            .min_by_key(|entry| (entry.line, entry.rank, entry.start))
+            .map(|entry| (entry.start, entry.line))
+    }
+
+    // the label at or closest below addr, and how far below it is
+    pub fn label_before(&self, addr: u16) -> Option<(&str, u16)> {
+        self.labels.range(..=addr).next_back().map(|(&at, name)| (name.as_str(), addr - at))
+    }
+
+    pub fn label_addr(&self, name: &str) -> Option<u16> {
+        self.labels.iter().find(|(_, label)| label.as_str() == name).map(|(&addr, _)| addr)
+    }
+
+    // a file is found by its full path or, failing that, by the end of
+    // the path if that is all the debug info knows of it
+    fn find_file(&self, path: &Path) -> Option<usize> {
+        let wanted = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        self.files.iter()
+            .position(|file| *file == wanted)
+            .or_else(|| self.files.iter().position(|file| path.ends_with(file) || file.ends_with(path)))
+    }
+}
+
+fn parse(text: &str, dir: &Path) -> Result<DebugInfo, (usize, String)> {
+    let mut files = BTreeMap::new();
+    let mut segs = BTreeMap::new();
+    let mut spans = BTreeMap::new();
+    let mut line_records = Vec::new();
+    let mut labels = BTreeMap::new();
+
+    for (index, record) in text.lines().enumerate() {
+        let number = index + 1;
+        let Some((keyword, rest)) = record.trim().split_once(char::is_whitespace) else { continue };
+        let fields = fields(rest).map_err(|message| (number, message))?;
+        let get = |key: &str| fields.get(key).ok_or((number, format!("{} record without {}", keyword, key)));
+        let int = |key: &str| get(key).and_then(|value| parse_number(value).ok_or((number, format!("bad {} '{}'", key, value))));
+        match keyword {
+            "file" => {
+                files.insert(int("id")?, dir.join(get("name")?));
+            }
+            "seg" => {
+                segs.insert(int("id")?, int("start")?);
+            }
+            "span" => {
+                spans.insert(int("id")?, (int("seg")?, int("start")?, int("size")?));
+            }
+            "line" => {
+                // a line without spans produced no code
+                let Some(list) = fields.get("span") else { continue };
+                let rank = match fields.get("type").map(|value| value.as_str()) {
+                    Some("1") => 0,
+                    Some("2") => 2,
+                    _ => 1,
+                };
+                let ids = list.split('+')
+                    .map(|id| parse_number(id).ok_or((number, format!("bad span '{}'", id))))
+                    .collect::<Result<Vec<u64>, _>>()?;
+                line_records.push((int("file")?, int("line")? as u32, rank, ids, number));
+            }
+            // labels, not equates
+            "sym" if fields.get("type").is_some_and(|kind| kind == "lab") => {
+                if let Some(value) = fields.get("val").and_then(|value| parse_number(value)) {
+                    labels.insert(value as u16, get("name")?.clone());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // files are numbered by their position from here on
+    let ids: Vec<u64> = files.keys().copied().collect();
+    let mut lines = Vec::new();
+    for (file, line, rank, span_ids, number) in line_records {
+        let file = ids.iter().position(|&id| id == file).ok_or((number, format!("no file {}", file)))?;
+        for span in span_ids {
+            let &(seg, start, size) = spans.get(&span).ok_or((number, format!("no span {}", span)))?;
+            let base = *segs.get(&seg).ok_or((number, format!("no segment {}", seg)))?;
+            if size == 0 {
+                continue;
+            }
+            let start = base + start;
+            let end = start + size - 1;
+            if end > 0xffff {
+                return Err((number, format!("span {} goes past $ffff", span)));
+            }
+            lines.push(Line { file, line, rank, start: start as u16, end: end as u16 });
+        }
+    }
+    Ok(DebugInfo { files: files.into_values().collect(), lines, labels })
+}
+
+// an address given to the monitor: a label, with or without the _ cc65
+// puts before C names, or hex. a label that is also hex wins, so $
+// gives the address
+pub fn parse_location(text: &str, debug: Option<&DebugInfo>) -> Result<u16, String> {
+    let text = text.trim();
+    let label = debug.and_then(|debug| debug.label_addr(text).or_else(|| debug.label_addr(&format!("_{}", text))));
+    match label {
+        Some(addr) => Ok(addr),
+        None => parse_addr(text).map_err(|_| format!("bad address or unknown label '{}'", text)),
+    }
+}
+
+// an inclusive range like c000-cfff, main-main_end or $0200..$02ff
+pub fn parse_location_range(text: &str, debug: Option<&DebugInfo>) -> Result<(u16, u16), String> {
+    let (first, last) = text.split_once("..").or_else(|| text.split_once('-'))
+        .ok_or(format!("bad range '{}', should be ADDR-ADDR", text))?;
+    let (first, last) = (parse_location(first, debug)?, parse_location(last, debug)?);
+    if first > last {
+        return Err(format!("bad range '{}', it ends before it starts", text));
+    }
+    Ok((first, last))
+}
+
+// the key=value pairs of a record; quoted values may hold commas
+fn fields(text: &str) -> Result<BTreeMap<String, String>, String> {
+    let mut fields = BTreeMap::new();
+    let mut chars = text.trim().chars().peekable();
+    while chars.peek().is_some() {
+        let key: String = chars.by_ref().take_while(|&c| c != '=').collect();
+        let mut value = String::new();
+        if chars.peek() == Some(&'"') {
+            chars.next();
+            loop {
+                match chars.next() {
+                    Some('"') => break,
+                    Some('\\') => value.extend(chars.next()),
+                    Some(c) => value.push(c),
+                    None => return Err(format!("unterminated string in {}", key.trim())),
+                }
+            }
+            chars.next_if_eq(&',');
+        } else {
+            value = chars.by_ref().take_while(|&c| c != ',').collect();
+        }
+        fields.insert(key.trim().to_string(), value);
+    }
+    Ok(fields)
+}
+
+fn parse_number(text: &str) -> Option<u64> {
+    match text.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => text.parse().ok(),
+    }
+}
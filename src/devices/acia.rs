@@ -0,0 +1,247 @@
+
+// 6551 asynchronous communications interface adapter
+//
+//   0   read: receive data       write: transmit data
+//   1   read: status             write: programmed reset
+//   2   command
+//   3   control
+//
+// characters move at the baud rate set in the control register, timed
+// against the cpu clock: one character takes its start, data, parity
+// and stop bits. with the external clock selected (rate 0) they move
+// as soon as possible. echo mode is not emulated.
+//
+// the acia is ticked only when a character is due to have gone or
+// arrived, or once a bit time while it waits for one to start.
+
+use crate::memory::{Device, StateReader, StateWriter};
+
+pub const ACIA_SIZE: u16 = 4;
+
+// status bits
+const STATUS_OVERRUN: u8 = 0x04;
+const STATUS_RDRF: u8 = 0x08;           // receive data register full
+const STATUS_TDRE: u8 = 0x10;           // transmit data register empty
+const STATUS_IRQ: u8 = 0x80;
+
+// command bits
+const COMMAND_DTR: u8 = 0x01;           // data terminal ready: enables the chip
+const COMMAND_RX_IRQ_OFF: u8 = 0x02;
+const COMMAND_TX_CONTROL: u8 = 0x0c;
+const COMMAND_TX_IRQ_ON: u8 = 0x04;
+const COMMAND_PARITY_ON: u8 = 0x20;
+
+// control bits
+const CONTROL_BAUD: u8 = 0x0f;
+const CONTROL_TWO_STOP_BITS: u8 = 0x80;
+
+const BAUD_RATES: [f64; 16] = [
+    0.0, 50.0, 75.0, 109.92, 134.58, 150.0, 300.0, 600.0,
+    1200.0, 1800.0, 2400.0, 3600.0, 4800.0, 7200.0, 9600.0, 19200.0,
+];
+
+// the other end of the serial line
+pub trait SerialPort {
+    // the next byte sent to the 6502, if one is waiting
+    fn receive(&mut self) -> Option<u8>;
+    // a byte sent by the 6502
+    fn send(&mut self, byte: u8);
+}
+
+// a serial line with nothing on the other end
+pub struct Unconnected;
+
+impl SerialPort for Unconnected {
+    fn receive(&mut self) -> Option<u8> {
+        None
+    }
+
+    fn send(&mut self, _byte: u8) {}
+}
+
+pub struct Acia {
+    port: Box<dyn SerialPort>,
+    clock_hz: f64,
+    rx_data: u8,
+    tx_data: u8,
+    status: u8,
+    command: u8,
+    control: u8,
+    rx_wait: u64,           // cycles until the receiver looks for the next byte
+    tx_wait: u64,           // cycles until the transmitted byte has gone
+}
+
+impl Acia {
+    pub fn new(port: Box<dyn SerialPort>, clock_hz: f64) -> Acia {
+        Acia {
+            port,
+            clock_hz,
+            rx_data: 0,
+            tx_data: 0,
+            status: STATUS_TDRE,
+            command: 0,
+            control: 0,
+            rx_wait: 0,
+            tx_wait: 0,
+        }
+    }
+
+    fn baud(&self) -> f64 {
+        BAUD_RATES[(self.control & CONTROL_BAUD) as usize]
+    }
+
+    // cycles taken by one bit, 0 with the external clock
+    fn bit_cycles(&self) -> u64 {
+        let baud = self.baud();
+        if baud == 0.0 {
+            return 0;
+        }
+        (self.clock_hz / baud) as u64
+    }
+
+    // cycles taken by one character at the current settings
+    fn char_cycles(&self) -> u64 {
+        let baud = self.baud();
+        if baud == 0.0 {
+            return 0;
+        }
+        let data_bits = 8 - ((self.control >> 5) & 0x03) as u32;
+        let parity_bits = if self.command & COMMAND_PARITY_ON != 0 { 1 } else { 0 };
+        let stop_bits = if self.control & CONTROL_TWO_STOP_BITS != 0 { 2 } else { 1 };
+        let bits = 1 + data_bits + parity_bits + stop_bits;
+        (self.clock_hz * bits as f64 / baud) as u64
+    }
+
+    fn enabled(&self) -> bool {
+        self.command & COMMAND_DTR != 0
+    }
+
+    fn rx_irq_enabled(&self) -> bool {
+        self.enabled() && self.command & COMMAND_RX_IRQ_OFF == 0
+    }
+
+    fn tx_irq_enabled(&self) -> bool {
+        self.enabled() && self.command & COMMAND_TX_CONTROL == COMMAND_TX_IRQ_ON
+    }
+
+    fn receive(&mut self) {
+        let Some(byte) = self.port.receive() else { return };
+        if self.status & STATUS_RDRF != 0 {
+            // the program didn't read the last byte in time
+            self.status |= STATUS_OVERRUN;
+        } else {
+            self.rx_data = byte;
+            self.status |= STATUS_RDRF;
+        }
+        if self.rx_irq_enabled() {
+            self.status |= STATUS_IRQ;
+        }
+        self.rx_wait = self.char_cycles();
+    }
+
+    fn transmit(&mut self) {
+        self.port.send(self.tx_data);
+        self.status |= STATUS_TDRE;
+        if self.tx_irq_enabled() {
+            self.status |= STATUS_IRQ;
+        }
+    }
+}
+
+impl Device for Acia {
+    fn read(&mut self, offset: u16) -> u8 {
+        match offset & 0x03 {
+            0 => {
+                self.status &= !(STATUS_RDRF | STATUS_OVERRUN);
+                self.rx_data
+            }
+            1 => {
+                // reading the status acknowledges the interrupt
+                let status = self.status;
+                self.status &= !STATUS_IRQ;
+                status
+            }
+            2 => self.command,
+            _ => self.control,
+        }
+    }
+
+    fn write(&mut self, offset: u16, value: u8) {
+        match offset & 0x03 {
+            0 => {
+                self.tx_data = value;
+                self.status &= !STATUS_TDRE;
+                self.tx_wait = self.char_cycles();
+                if self.tx_wait == 0 {
+                    self.transmit();
+                }
+            }
+            1 => {
+                // programmed reset
+                self.command &= 0xe0;
+                self.status &= !STATUS_OVERRUN;
+            }
+            2 => self.command = value,
+            _ => self.control = value,
+        }
+    }
+
+    fn tick(&mut self, cycles: u32) {
+        let cycles = cycles as u64;
+        if self.status & STATUS_TDRE == 0 {
+            if self.tx_wait <= cycles {
+                self.transmit();
+            } else {
+                self.tx_wait -= cycles;
+            }
+        }
+        if self.rx_wait <= cycles {
+            self.rx_wait = 0;
+            if self.enabled() {
+                self.receive();
+            }
+        } else {
+            self.rx_wait -= cycles;
+        }
+    }
+
+    fn next_event(&self) -> Option<u64> {
+        let tx = if self.status & STATUS_TDRE == 0 { self.tx_wait } else { u64::MAX };
+        let rx = if self.rx_wait > 0 {
+            self.rx_wait
+        } else if self.enabled() {
+            // a character could start arriving at any bit
+            self.bit_cycles()
+        } else {
+            u64::MAX
+        };
+        Some(tx.min(rx))
+    }
+
+    fn irq(&self) -> bool {
+        self.status & STATUS_IRQ != 0
+    }
+
+    fn raises_irqs(&self) -> bool {
+        true
+    }
+
+    // the connection is not saved; a restored acia carries on with
+    // whatever it is connected to now
+    fn save_state(&self, out: &mut StateWriter) {
+        for value in [self.rx_data, self.tx_data, self.status, self.command, self.control] {
+            out.u8(value);
+        }
+        out.u64(self.rx_wait);
+        out.u64(self.tx_wait);
+    }
+
+    fn load_state(&mut self, input: &mut StateReader) -> Result<(), String> {
+        for value in [&mut self.rx_data, &mut self.tx_data, &mut self.status, &mut self.command, &mut self.control] {
+            *value = input.u8()?;
+        }
+        self.rx_wait = input.u64()?;
+        self.tx_wait = input.u64()?;
+        Ok(())
+    }
+}
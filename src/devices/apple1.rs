@@ -0,0 +1,95 @@
+
+// Apple-1 keyboard and display, on a PIA at $d010
+//
+//   $d010  KBD     the last key, with bit 7 set
+//   $d011  KBDCR   bit 7 set when a key is waiting (CA1)
+//   $d012  DSP     characters written here are displayed; bit 7 reads
+//                  as the display's busy line, which is never busy
+//   $d013  DSPCR
+//
+// keys are sent in upper case with enter as a carriage return and
+// backspace as the underscore the Woz Monitor uses to rub out. the
+// PIA's irq lines are not connected on the Apple-1.
+
+use std::io;
+use std::io::Write;
+
+use crate::devices::pia::{Pia, PIA_SIZE};
+use crate::devices::write_terminal;
+use crate::replay::Inputs;
+use crate::memory::{Device, StateReader, StateWriter};
+
+pub const APPLE1_IO_SIZE: u16 = PIA_SIZE;
+
+const KBD: u16 = 0;
+const KBDCR: u16 = 1;
+const DSP: u16 = 2;
+const DSPCR: u16 = 3;
+
+const CR_FLAG: u8 = 0x80;
+const CR_OUTPUT_REGISTER: u8 = 0x04;
+
+pub struct Apple1Io {
+    pia: Pia,
+    out: io::Stdout,
+    inputs: Inputs,
+}
+
+impl Apple1Io {
+    pub fn new(inputs: Inputs) -> Apple1Io {
+        inputs.claim_keyboard();
+        let mut pia = Pia::new();
+        pia.pins_b = 0x7f;          // the display is always ready
+        Apple1Io { pia, out: io::stdout(), inputs }
+    }
+
+    // latch a typed key and strobe CA1 if the last one has been read
+    fn poll_keyboard(&mut self) {
+        if self.pia.read(KBDCR) & CR_FLAG != 0 {
+            return;
+        }
+        let Some(key) = self.inputs.poll_key() else { return };
+        let key = match key {
+            b'\n' => b'\r',
+            0x08 | 0x7f => b'_',
+            _ => key.to_ascii_uppercase(),
+        };
+        self.pia.pins_a = key | 0x80;
+        self.pia.set_ca1(false);
+        self.pia.set_ca1(true);
+    }
+
+    fn display(&mut self, value: u8) {
+        let c = value & 0x7f;
+        let _result = match c {
+            b'\r' => write_terminal(&mut self.out, b"\n"),
+            0x20..=0x7e => self.out.write_all(&[c]),
+            _ => Ok(()),
+        };
+        let _result = self.out.flush();
+    }
+}
+
+impl Device for Apple1Io {
+    fn read(&mut self, offset: u16) -> u8 {
+        if offset == KBD || offset == KBDCR {
+            self.poll_keyboard();
+        }
+        self.pia.read(offset)
+    }
+
+    fn write(&mut self, offset: u16, value: u8) {
+        self.pia.write(offset, value);
+        if offset == DSP && self.pia.read(DSPCR) & CR_OUTPUT_REGISTER != 0 {
+            self.display(value);
+        }
+    }
+
+    fn save_state(&self, out: &mut StateWriter) {
+        self.pia.save_state(out);
+    }
+
+    fn load_state(&mut self, input: &mut StateReader) -> Result<(), String> {
+        self.pia.load_state(input)
+    }
+}
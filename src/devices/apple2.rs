@@ -0,0 +1,26 @@
+
+// Apple II text page
+//
+// screen rows are interleaved in memory: row r starts at
+// (r % 8) * $80 + (r / 8) * $28. characters $00-$3f are inverse,
+// $40-$7f flashing (shown as inverse) and $80-$ff normal.
+
+use crate::devices::screen::{Layout, TextScreen};
+
+pub const TEXT_PAGE_SIZE: u16 = 0x400;
+
+fn row_offset(row: usize, _columns: usize) -> usize {
+    (row % 8) * 0x80 + (row / 8) * 0x28
+}
+
+fn decode(value: u8) -> (char, bool) {
+    let c = if value >= 0x80 { value & 0x7f } else { value & 0x3f };
+    let c = if c < 0x20 { c + 0x40 } else { c };
+    (c as char, value < 0x80)
+}
+
+// the 40x24 text page, cleared to spaces
+pub fn text_page(clock_hz: f64) -> TextScreen {
+    let layout = Layout { columns: 40, rows: 24, row_offset, decode };
+    TextScreen::new(layout, TEXT_PAGE_SIZE, 0xa0, clock_hz)
+}
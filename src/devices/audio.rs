@@ -0,0 +1,72 @@
+// sound played on the host's default output, with the audio feature
+//
+// the speaker hands over samples as the emulated clock makes them, and
+// the output takes them from a queue as it plays. a machine running
+// behind real time leaves gaps of silence; one running ahead, as in
+// turbo, has its oldest samples dropped so the sound keeps up with it.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{SampleFormat, SampleRate, SizedSample, StreamConfig};
+
+// the most that may wait to be played, a quarter of a second
+const MAX_QUEUED_SECONDS: f64 = 0.25;
+
+pub struct Playback {
+    _stream: cpal::Stream,
+    queue: Arc<Mutex<VecDeque<f32>>>,
+    max_queued: usize,
+}
+
+impl Playback {
+    // start playing silence at rate samples a second, until there's sound
+    pub fn open(rate: u32) -> Result<Playback, String> {
+        let device = cpal::default_host().default_output_device().ok_or("no audio output")?;
+        let supported = device.supported_output_configs()
+            .map_err(|e| format!("audio output: {}", e))?
+            .find(|range| range.min_sample_rate().0 <= rate && rate <= range.max_sample_rate().0)
+            .ok_or(format!("audio output can't play {} samples a second", rate))?
+            .with_sample_rate(SampleRate(rate));
+        let queue = Arc::new(Mutex::new(VecDeque::new()));
+        let config = supported.config();
+        let stream = match supported.sample_format() {
+            SampleFormat::F32 => output::<f32>(&device, &config, &queue),
+            SampleFormat::I16 => output::<i16>(&device, &config, &queue),
+            SampleFormat::U16 => output::<u16>(&device, &config, &queue),
+            other => return Err(format!("audio output takes {} samples, which aren't supported", other)),
+        }?;
+        stream.play().map_err(|e| format!("audio output: {}", e))?;
+        Ok(Playback { _stream: stream, queue, max_queued: (rate as f64 * MAX_QUEUED_SECONDS) as usize })
+    }
+
+    // queue samples from -1.0 to 1.0 to be played
+    pub fn play(&self, samples: impl Iterator<Item = f32>) {
+        let Ok(mut queue) = self.queue.lock() else { return };
+        queue.extend(samples);
+        let excess = queue.len().saturating_sub(self.max_queued);
+        queue.drain(..excess);
+    }
+}
+
+// a stream that plays the queue, the same sample on every channel
+fn output<T>(device: &cpal::Device, config: &StreamConfig, queue: &Arc<Mutex<VecDeque<f32>>>) -> Result<cpal::Stream, String>
+where
+    T: SizedSample + cpal::FromSample<f32>,
+{
+    let queue = Arc::clone(queue);
+    let channels = config.channels as usize;
+    device.build_output_stream(
+        config,
+        move |data: &mut [T], _info| {
+            let mut queue = queue.lock().ok();
+            for frame in data.chunks_mut(channels) {
+                let sample = queue.as_mut().and_then(|queue| queue.pop_front()).unwrap_or(0.0);
+                frame.fill(T::from_sample(sample));
+            }
+        },
+        |e| eprintln!("e6502r: audio output: {}", e),
+        None,
+    ).map_err(|e| format!("audio output: {}", e))
+}
@@ -0,0 +1,308 @@
+
+// Commodore 64 banking and i/o, enough to boot BASIC without video
+//
+// the processor port at $0000/$0001 switches BASIC at $a000, the
+// kernal at $e000 and, at $d000, the i/o chips or the character rom
+// in and out over the ram, going by LORAM, HIRAM and CHAREN (bits 0-2).
+// cartridges (EXROM/GAME) are not emulated.
+//
+// the i/o block at $d000-$dfff holds
+//   $d000   VIC-II registers: stored, with the raster line counting
+//           312 lines of 63 cycles; no video, sprites or irqs
+//   $d400   SID registers: writes are ignored and reads give 0
+//   $d800   colour ram, four bits per byte
+//   $dc00   CIA 1, with the keyboard on its ports, on the irq line
+//   $dd00   CIA 2, on the nmi line
+//
+// the screen at $0400 is drawn on the terminal like the PET's; moving
+// it with the VIC's memory pointers is not followed.
+
+use crate::devices::cia::Cia;
+use crate::devices::pet;
+use crate::devices::screen::{rows_in_order, Layout, TextScreen};
+use crate::replay::Inputs;
+use crate::memory::{Bank, Device, StateReader, StateWriter};
+
+pub const PORT_SIZE: u16 = 2;
+pub const IO_SIZE: u16 = 0x1000;
+pub const SCREEN_SIZE: u16 = 0x400;
+
+const BASIC: u16 = 0xa000;
+const CHARGEN: u16 = 0xd000;
+const KERNAL: u16 = 0xe000;
+
+const LORAM: u8 = 0x01;
+const HIRAM: u8 = 0x02;
+const CHAREN: u8 = 0x04;
+
+const RASTER_LINES: u64 = 312;
+const LINE_CYCLES: u64 = 63;
+
+const HOLD_HZ: f64 = 20.0;          // a key stays down for 1/20 s
+const GAP_HZ: f64 = 30.0;           // and is up 1/30 s before the next
+
+// rom images, as numbered by Memory::add_rom
+pub struct Roms {
+    pub basic: usize,
+    pub kernal: usize,
+    pub chargen: usize,
+}
+
+// the 6510's processor port and the PLA that decodes it
+pub struct ProcessorPort {
+    roms: Roms,
+    ddr: u8,
+    data: u8,
+    mapped: Option<u8>,     // the LORAM/HIRAM/CHAREN lines last mapped
+}
+
+impl ProcessorPort {
+    pub fn new(roms: Roms) -> ProcessorPort {
+        ProcessorPort { roms, ddr: 0x2f, data: 0x37, mapped: None }
+    }
+
+    // inputs are pulled up, so the banking lines read high when not driven
+    fn lines(&self) -> u8 {
+        (self.data | !self.ddr) & (LORAM | HIRAM | CHAREN)
+    }
+}
+
+impl Device for ProcessorPort {
+    fn read(&mut self, offset: u16) -> u8 {
+        if offset == 0 {
+            self.ddr
+        } else {
+            // the cassette sense (bit 4) reads high with no button down
+            (self.data & self.ddr) | (0x17 & !self.ddr)
+        }
+    }
+
+    fn write(&mut self, offset: u16, value: u8) {
+        if offset == 0 {
+            self.ddr = value;
+        } else {
+            self.data = value;
+        }
+    }
+
+    fn bank_switch(&mut self) -> Option<Vec<(u16, u16, Bank)>> {
+        let lines = self.lines();
+        if self.mapped == Some(lines) {
+            return None;
+        }
+        self.mapped = Some(lines);
+        let basic = if lines & (LORAM | HIRAM) == LORAM | HIRAM { Bank::Rom(self.roms.basic) } else { Bank::Ram };
+        let kernal = if lines & HIRAM != 0 { Bank::Rom(self.roms.kernal) } else { Bank::Ram };
+        let chargen = match lines {
+            _ if lines & (LORAM | HIRAM) == 0 => Bank::Ram,
+            _ if lines & CHAREN != 0 => Bank::Io,
+            _ => Bank::Rom(self.roms.chargen),
+        };
+        Some(vec![(BASIC, 0x2000, basic), (CHARGEN, 0x1000, chargen), (KERNAL, 0x2000, kernal)])
+    }
+
+    fn save_state(&self, out: &mut StateWriter) {
+        out.u8(self.ddr);
+        out.u8(self.data);
+    }
+
+    // memory restores the banks these lines had mapped
+    fn load_state(&mut self, input: &mut StateReader) -> Result<(), String> {
+        self.ddr = input.u8()?;
+        self.data = input.u8()?;
+        self.mapped = Some(self.lines());
+        Ok(())
+    }
+}
+
+const NO_KEY: u8 = 0xff;
+const RETURN: u8 = 0x0d;
+const DEL: u8 = 0x14;
+const STOP: u8 = 0x03;
+
+// the keyboard matrix, by port A bit (driven low to select) and port B
+// bit (read low while pressed), as the petscii each key types
+const KEY_MATRIX: [[u8; 8]; 8] = [
+    [DEL, RETURN, 0x1d, 0x88, 0x85, 0x86, 0x87, 0x11],
+    [b'3', b'W', b'A', b'4', b'Z', b'S', b'E', NO_KEY],
+    [b'5', b'R', b'D', b'6', b'C', b'F', b'T', b'X'],
+    [b'7', b'Y', b'G', b'8', b'B', b'H', b'U', b'V'],
+    [b'9', b'I', b'J', b'0', b'M', b'K', b'O', b'N'],
+    [b'+', b'P', b'L', b'-', b'.', b':', b'@', b','],
+    [0x5c, b'*', b';', 0x13, NO_KEY, b'=', 0x5e, b'/'],
+    [b'1', 0x5f, NO_KEY, b'2', b' ', NO_KEY, b'Q', STOP],
+];
+
+const LEFT_SHIFT: (usize, usize) = (1, 7);
+
+// symbols typed with shift, and the key they are on
+const SHIFTED: [(u8, u8); 14] = [
+    (b'!', b'1'), (b'"', b'2'), (b'#', b'3'), (b'$', b'4'), (b'%', b'5'),
+    (b'&', b'6'), (b'\'', b'7'), (b'(', b'8'), (b')', b'9'), (b'<', b','),
+    (b'>', b'.'), (b'?', b'/'), (b'[', b':'), (b']', b';'),
+];
+
+// where a host key is on the matrix, and whether it needs shift
+fn key_position(key: u8) -> Option<((usize, usize), bool)> {
+    let (key, shift) = match key {
+        b'\n' | b'\r' => (RETURN, false),
+        0x08 | 0x7f => (DEL, false),
+        b'^' => (0x5e, false),
+        b'_' => (0x5f, false),
+        NO_KEY => return None,
+        key => match SHIFTED.iter().find(|&&(shifted, _)| shifted == key) {
+            Some(&(_, base)) => (base, true),
+            None => (key.to_ascii_uppercase(), false),
+        },
+    };
+    (0..8)
+        .flat_map(|a| (0..8).map(move |b| (a, b)))
+        .find(|&(a, b)| KEY_MATRIX[a][b] == key)
+        .map(|position| (position, shift))
+}
+
+// the i/o block at $d000
+pub struct C64Io {
+    vic: [u8; 0x40],
+    color_ram: [u8; 0x400],
+    cia1: Cia,
+    cia2: Cia,
+    cycles: u64,
+    pressed: Option<((usize, usize), bool)>,
+    key_wait: u64,          // cycles the key stays down, or until the next
+    hold_cycles: u64,
+    gap_cycles: u64,
+    inputs: Inputs,
+}
+
+impl C64Io {
+    pub fn new(inputs: Inputs, clock_hz: f64) -> C64Io {
+        inputs.claim_keyboard();
+        C64Io {
+            vic: [0; 0x40],
+            color_ram: [0; 0x400],
+            cia1: Cia::new(clock_hz),
+            cia2: Cia::new(clock_hz),
+            cycles: 0,
+            pressed: None,
+            key_wait: 0,
+            hold_cycles: (clock_hz / HOLD_HZ) as u64,
+            gap_cycles: (clock_hz / GAP_HZ) as u64,
+            inputs,
+        }
+    }
+
+    fn raster(&self) -> u16 {
+        ((self.cycles / LINE_CYCLES) % RASTER_LINES) as u16
+    }
+
+    fn read_vic(&self, reg: usize) -> u8 {
+        match reg {
+            0x11 => (self.vic[reg] & 0x7f) | if self.raster() > 0xff { 0x80 } else { 0 },
+            0x12 => self.raster() as u8,
+            0x19 => self.vic[reg] | 0x70,
+            0x1a => self.vic[reg] | 0xf0,
+            0x20..=0x2e => self.vic[reg] | 0xf0,
+            0x2f..=0x3f => 0xff,
+            _ => self.vic[reg],
+        }
+    }
+
+    // the rows read on CIA 1 port B for the columns selected on port A
+    fn keyboard_rows(&self) -> u8 {
+        let Some(((a, b), shift)) = self.pressed else { return 0xff };
+        let columns = !self.cia1.port_a();
+        let mut rows = 0xff;
+        if columns & (1 << a) != 0 {
+            rows &= !(1 << b);
+        }
+        if shift && columns & (1 << LEFT_SHIFT.0) != 0 {
+            rows &= !(1 << LEFT_SHIFT.1);
+        }
+        rows
+    }
+
+    fn tick_keyboard(&mut self, cycles: u64) {
+        if self.key_wait > cycles {
+            self.key_wait -= cycles;
+            return;
+        }
+        if self.pressed.take().is_some() {
+            self.key_wait = self.gap_cycles;
+        } else if let Some(key) = self.inputs.poll_key() {
+            self.pressed = key_position(key);
+            self.key_wait = self.hold_cycles;
+        }
+    }
+}
+
+impl Device for C64Io {
+    fn read(&mut self, offset: u16) -> u8 {
+        match offset >> 8 {
+            0x0..=0x3 => self.read_vic((offset & 0x3f) as usize),
+            0x4..=0x7 => 0,
+            0x8..=0xb => self.color_ram[(offset & 0x3ff) as usize] & 0x0f,
+            0xc => {
+                self.cia1.pins_b = self.keyboard_rows();
+                self.cia1.read(offset & 0x0f)
+            }
+            0xd => self.cia2.read(offset & 0x0f),
+            _ => 0xff,
+        }
+    }
+
+    fn write(&mut self, offset: u16, value: u8) {
+        match offset >> 8 {
+            0x0..=0x3 => self.vic[(offset & 0x3f) as usize] = value,
+            0x8..=0xb => self.color_ram[(offset & 0x3ff) as usize] = value & 0x0f,
+            0xc => self.cia1.write(offset & 0x0f, value),
+            0xd => self.cia2.write(offset & 0x0f, value),
+            _ => {}
+        }
+    }
+
+    fn tick(&mut self, cycles: u32) {
+        self.cycles += cycles as u64;
+        self.cia1.tick(cycles);
+        self.cia2.tick(cycles);
+        self.tick_keyboard(cycles as u64);
+    }
+
+    fn irq(&self) -> bool {
+        self.cia1.irq()
+    }
+
+    fn nmi(&self) -> bool {
+        self.cia2.irq()
+    }
+
+    fn raises_irqs(&self) -> bool {
+        true
+    }
+
+    // a key being typed is not saved
+    fn save_state(&self, out: &mut StateWriter) {
+        out.bytes(&self.vic);
+        out.bytes(&self.color_ram);
+        self.cia1.save_state(out);
+        self.cia2.save_state(out);
+        out.u64(self.cycles);
+    }
+
+    fn load_state(&mut self, input: &mut StateReader) -> Result<(), String> {
+        input.bytes_into(&mut self.vic)?;
+        input.bytes_into(&mut self.color_ram)?;
+        self.cia1.load_state(input)?;
+        self.cia2.load_state(input)?;
+        self.cycles = input.u64()?;
+        self.pressed = None;
+        self.key_wait = 0;
+        Ok(())
+    }
+}
+
+// the 40x25 screen at $0400, in the same screen codes as the PET
+pub fn screen(clock_hz: f64) -> TextScreen {
+    let layout = Layout { columns: 40, rows: 25, row_offset: rows_in_order, decode: pet::decode };
+    TextScreen::new(layout, SCREEN_SIZE, 0x20, clock_hz)
+}
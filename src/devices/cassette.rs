@@ -0,0 +1,342 @@
+// cassette interface: a one-bit signal in from a tape and out to one
+//
+//   0   read: bit 7 is the level coming off the tape
+//       write: bit 0 sets the level going to the tape
+//   1   any read or write flips the level going to the tape, as the
+//       Apple II's $c020 does
+//
+// the tape is a list of times at which the level changes, counted in
+// cpu cycles, so tape routines can time it as they would the real one.
+// it is loaded from, and recorded to, one of:
+//
+//   .wav   audio: 8 or 16-bit pcm, the level is the sign of the sample
+//   .tim   text: one duration in microseconds per line, the time between
+//          level changes, starting low; # starts a comment
+//   other  binary data as Kansas City standard tones at 300 baud: a 0
+//          bit is four cycles of 1200 Hz, a 1 bit eight of 2400 Hz, each
+//          byte a 0 start bit, eight data bits low first and two 1 stop
+//          bits, after a second of 1s as a leader
+//
+// the tape starts playing the first time the program reads the input.
+// the recording is written when the emulator exits; decoding it as
+// Kansas City standard keeps the bytes that have good framing.
+
+use std::fs;
+use std::path::Path;
+
+use crate::devices::speaker::{wav_header, SAMPLE_RATE};
+use crate::memory::{Device, StateReader, StateWriter};
+
+pub const CASSETTE_SIZE: u16 = 2;
+
+const KCS_BAUD: f64 = 300.0;
+const KCS_ZERO_HZ: f64 = 1200.0;
+const KCS_ONE_HZ: f64 = 2400.0;
+const KCS_LEADER_SECONDS: f64 = 1.0;
+const WAV_AMPLITUDE: i16 = 8000;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Format {
+    Wav,
+    Timing,
+    Kcs,
+}
+
+fn format_of(path: &str) -> Format {
+    match Path::new(path).extension().and_then(|ext| ext.to_str()).map(|ext| ext.to_lowercase()).as_deref() {
+        Some("wav") => Format::Wav,
+        Some("tim") => Format::Timing,
+        _ => Format::Kcs,
+    }
+}
+
+pub struct Cassette {
+    clock_hz: f64,
+    now: u64,                   // cycles since power on
+    playback: Vec<u64>,         // times of level changes from the tape's start
+    started: Option<u64>,       // when the tape started playing
+    next_change: usize,
+    input: bool,
+    output: bool,
+    recording: Vec<u64>,        // times the output level changed
+    save: Option<String>,
+}
+
+impl Cassette {
+    pub fn new(load: Option<&str>, save: Option<&str>, clock_hz: f64) -> Result<Cassette, String> {
+        let playback = match load {
+            Some(path) => load_tape(path, clock_hz)?,
+            None => Vec::new(),
+        };
+        Ok(Cassette {
+            clock_hz,
+            now: 0,
+            playback,
+            started: None,
+            next_change: 0,
+            input: false,
+            output: false,
+            recording: Vec::new(),
+            save: save.map(str::to_string),
+        })
+    }
+
+    // bring the input level up to now
+    fn play(&mut self) {
+        let Some(started) = self.started else { return };
+        let position = self.now - started;
+        while self.next_change < self.playback.len() && self.playback[self.next_change] <= position {
+            self.input = !self.input;
+            self.next_change += 1;
+        }
+    }
+
+    fn set_output(&mut self, level: bool) {
+        if level != self.output {
+            self.output = level;
+            self.recording.push(self.now);
+        }
+    }
+}
+
+impl Device for Cassette {
+    fn read(&mut self, offset: u16) -> u8 {
+        if offset & 0x01 == 1 {
+            self.set_output(!self.output);
+            return 0;
+        }
+        if self.started.is_none() {
+            self.started = Some(self.now);
+        }
+        self.play();
+        if self.input { 0x80 } else { 0 }
+    }
+
+    fn write(&mut self, offset: u16, value: u8) {
+        if offset & 0x01 == 1 {
+            self.set_output(!self.output);
+        } else {
+            self.set_output(value & 0x01 != 0);
+        }
+    }
+
+    fn tick(&mut self, cycles: u32) {
+        self.now += cycles as u64;
+    }
+
+    // where the tape is, not the tape: the same one has to be loaded
+    fn save_state(&self, out: &mut StateWriter) {
+        out.u64(self.now);
+        out.bool(self.started.is_some());
+        out.u64(self.started.unwrap_or(0));
+        out.u64(self.next_change as u64);
+        out.bool(self.input);
+        out.bool(self.output);
+        out.u32(self.recording.len() as u32);
+        for &time in &self.recording {
+            out.u64(time);
+        }
+    }
+
+    fn load_state(&mut self, input: &mut StateReader) -> Result<(), String> {
+        self.now = input.u64()?;
+        let started = input.bool()?;
+        let at = input.u64()?;
+        self.started = started.then_some(at);
+        self.next_change = (input.u64()? as usize).min(self.playback.len());
+        self.input = input.bool()?;
+        self.output = input.bool()?;
+        let count = input.u32()?;
+        self.recording.clear();
+        for _ in 0..count {
+            self.recording.push(input.u64()?);
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Cassette {
+    fn drop(&mut self) {
+        let Some(path) = &self.save else { return };
+        let data = match format_of(path) {
+            Format::Wav => wav_from_changes(&self.recording, self.now, self.clock_hz),
+            Format::Timing => timing_from_changes(&self.recording, self.clock_hz).into_bytes(),
+            Format::Kcs => kcs_decode(&self.recording, self.clock_hz),
+        };
+        if let Err(e) = fs::write(path, data) {
+            eprintln!("e6502r: can't write {}: {}", path, e);
+        }
+    }
+}
+
+// the level changes on a tape file, in cycles from its start
+fn load_tape(path: &str, clock_hz: f64) -> Result<Vec<u64>, String> {
+    let data = fs::read(path).map_err(|e| format!("{}: {}", path, e))?;
+    match format_of(path) {
+        Format::Wav => changes_from_wav(&data, clock_hz).map_err(|message| format!("{}: {}", path, message)),
+        Format::Timing => {
+            let text = String::from_utf8_lossy(&data);
+            changes_from_timing(&text, clock_hz).map_err(|message| format!("{}: {}", path, message))
+        }
+        Format::Kcs => Ok(kcs_encode(&data, clock_hz)),
+    }
+}
+
+fn changes_from_timing(text: &str, clock_hz: f64) -> Result<Vec<u64>, String> {
+    let mut changes = Vec::new();
+    let mut micros = 0.0;
+    for (number, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or("").trim();
+        if line.is_empty() {
+            continue;
+        }
+        let duration: f64 = line.parse()
+            .map_err(|_| format!("line {}: '{}' should be a duration in microseconds", number + 1, line))?;
+        micros += duration;
+        changes.push((micros * clock_hz / 1e6) as u64);
+    }
+    Ok(changes)
+}
+
+fn timing_from_changes(changes: &[u64], clock_hz: f64) -> String {
+    let mut text = String::from("# microseconds between level changes, starting low\n");
+    let mut last = changes.first().copied().unwrap_or(0);
+    for &time in changes.iter().skip(1) {
+        text.push_str(&format!("{:.1}\n", (time - last) as f64 * 1e6 / clock_hz));
+        last = time;
+    }
+    text
+}
+
+fn changes_from_wav(data: &[u8], clock_hz: f64) -> Result<Vec<u64>, String> {
+    if data.len() < 12 || &data[..4] != b"RIFF" || &data[8..12] != b"WAVE" {
+        return Err(String::from("not a wav file"));
+    }
+    let word = |at: usize| u16::from_le_bytes([data[at], data[at + 1]]);
+    let long = |at: usize| u32::from_le_bytes([data[at], data[at + 1], data[at + 2], data[at + 3]]);
+
+    // walk the chunks for the format and the samples
+    let mut format = None;
+    let mut samples = None;
+    let mut at = 12;
+    while at + 8 <= data.len() {
+        let size = long(at + 4) as usize;
+        let body = at + 8..(at + 8 + size).min(data.len());
+        match &data[at..at + 4] {
+            b"fmt " if size >= 16 && at + 24 <= data.len() => format = Some((word(at + 8), word(at + 10), long(at + 12), word(at + 22))),
+            b"data" => samples = Some(body),
+            _ => {}
+        }
+        at += 8 + size + size % 2;
+    }
+    let (Some((kind, channels, rate, bits)), Some(samples)) = (format, samples) else {
+        return Err(String::from("wav file without format or data"));
+    };
+    if kind != 1 || !matches!(bits, 8 | 16) || channels == 0 {
+        return Err(String::from("only 8 and 16-bit pcm wav files can be loaded"));
+    }
+
+    // the first channel only
+    let frame = channels as usize * bits as usize / 8;
+    let mut changes = Vec::new();
+    let mut level = false;
+    for (index, start) in samples.step_by(frame).enumerate() {
+        if start + bits as usize / 8 > data.len() {
+            break;
+        }
+        let high = if bits == 8 { data[start] > 0x80 } else { word(start) as i16 > 0 };
+        if high != level {
+            level = high;
+            changes.push((index as f64 * clock_hz / rate as f64) as u64);
+        }
+    }
+    Ok(changes)
+}
+
+fn wav_from_changes(changes: &[u64], end: u64, clock_hz: f64) -> Vec<u8> {
+    let start = changes.first().copied().unwrap_or(end);
+    let count = ((end - start) as f64 * SAMPLE_RATE as f64 / clock_hz) as usize;
+    let mut data = Vec::with_capacity(count * 2);
+    let mut next = 0;
+    let mut level = false;
+    for index in 0..count {
+        let time = start + (index as f64 * clock_hz / SAMPLE_RATE as f64) as u64;
+        while next < changes.len() && changes[next] <= time {
+            level = !level;
+            next += 1;
+        }
+        let sample = if level { WAV_AMPLITUDE } else { -WAV_AMPLITUDE };
+        data.extend(sample.to_le_bytes());
+    }
+    let mut wav = wav_header(data.len() as u32);
+    wav.extend(data);
+    wav
+}
+
+// the tones for data bytes, as level changes
+fn kcs_encode(data: &[u8], clock_hz: f64) -> Vec<u64> {
+    let mut changes = Vec::new();
+    let mut time = 0.0;
+    let mut bit = |one: bool, changes: &mut Vec<u64>| {
+        let hz = if one { KCS_ONE_HZ } else { KCS_ZERO_HZ };
+        let half = clock_hz / hz / 2.0;
+        for _ in 0..(hz / KCS_BAUD * 2.0) as usize {
+            time += half;
+            changes.push(time as u64);
+        }
+    };
+    for _ in 0..(KCS_LEADER_SECONDS * KCS_BAUD) as usize {
+        bit(true, &mut changes);
+    }
+    for &byte in data {
+        bit(false, &mut changes);
+        for n in 0..8 {
+            bit(byte >> n & 1 != 0, &mut changes);
+        }
+        bit(true, &mut changes);
+        bit(true, &mut changes);
+    }
+    // a little trailer so the last stop bits end cleanly
+    for _ in 0..4 {
+        bit(true, &mut changes);
+    }
+    changes
+}
+
+// the bytes in recorded tones: each bit is read from the length of the
+// half cycle in its middle
+fn kcs_decode(changes: &[u64], clock_hz: f64) -> Vec<u8> {
+    let bit_cycles = clock_hz / KCS_BAUD;
+    // between the half cycles of the two tones
+    let long_half = clock_hz * (1.0 / KCS_ZERO_HZ + 1.0 / KCS_ONE_HZ) / 4.0;
+    // whether the half cycle at a time is one of the low tone's
+    let long_at = |time: f64| -> Option<bool> {
+        let next = changes.partition_point(|&change| (change as f64) <= time);
+        if next == 0 || next >= changes.len() {
+            return None;
+        }
+        Some((changes[next] - changes[next - 1]) as f64 > long_half)
+    };
+
+    let mut bytes = Vec::new();
+    let mut index = 1;
+    while index < changes.len() {
+        // a start bit begins with a long half cycle
+        if ((changes[index] - changes[index - 1]) as f64) <= long_half {
+            index += 1;
+            continue;
+        }
+        let start = changes[index - 1] as f64;
+        let long: Option<Vec<bool>> = (0..10).map(|n| long_at(start + (n as f64 + 0.5) * bit_cycles)).collect();
+        let Some(long) = long else { break };
+        // a 0 start bit and a 1 stop bit frame the byte
+        if long[0] && !long[9] {
+            let byte = (0..8).fold(0u8, |byte, n| byte | if long[n + 1] { 0 } else { 1 << n });
+            bytes.push(byte);
+        }
+        // carry on after the first stop bit
+        let resume = (start + 9.5 * bit_cycles) as u64;
+        index = changes.partition_point(|&change| change <= resume).max(index + 1);
+    }
+    bytes
+}
@@ -0,0 +1,348 @@
+
+// 6526 complex interface adapter
+//
+//   0 PRA    4 TA-L   8 TOD 10ths   c SDR
+//   1 PRB    5 TA-H   9 TOD sec     d ICR
+//   2 DDRA   6 TB-L   a TOD min     e CRA
+//   3 DDRB   7 TB-H   b TOD hr      f CRB
+//
+// both timers count down once per cycle, one-shot or continuous; timer
+// B can count timer A underflows instead (CRB bits 5-6 = %10). counting
+// CNT pulses and the serial port are not emulated. the time of day
+// clock counts tenths of a second of emulated time in bcd, latching on
+// a read of the hours until the tenths are read, and stopping on a
+// write of the hours until the tenths are written; CRB bit 7 selects
+// writing the alarm instead.
+//
+// port pins without an output driving them read from pins_a/pins_b,
+// which idle high.
+
+use crate::memory::{Device, StateReader, StateWriter};
+
+pub const CIA_SIZE: u16 = 16;
+
+const PRA: u16 = 0x0;
+const PRB: u16 = 0x1;
+const DDRA: u16 = 0x2;
+const DDRB: u16 = 0x3;
+const TA_L: u16 = 0x4;
+const TA_H: u16 = 0x5;
+const TB_L: u16 = 0x6;
+const TB_H: u16 = 0x7;
+const TOD_10THS: u16 = 0x8;
+const TOD_HR: u16 = 0xb;
+const SDR: u16 = 0xc;
+const ICR: u16 = 0xd;
+const CRA: u16 = 0xe;
+
+// interrupt bits
+const IRQ_TA: u8 = 0x01;
+const IRQ_TB: u8 = 0x02;
+const IRQ_ALARM: u8 = 0x04;
+const IRQ_ANY: u8 = 0x80;
+
+// control bits
+const CR_START: u8 = 0x01;
+const CR_ONE_SHOT: u8 = 0x08;
+const CR_LOAD: u8 = 0x10;               // strobe: load the counter from the latch
+const CRB_INMODE: u8 = 0x60;
+const CRB_COUNT_TA: u8 = 0x40;
+const CRB_ALARM: u8 = 0x80;
+
+struct Timer {
+    counter: u16,
+    latch: u16,
+    control: u8,
+}
+
+impl Timer {
+    fn new() -> Timer {
+        Timer { counter: 0xffff, latch: 0xffff, control: 0 }
+    }
+
+    // count once; true on underflow
+    fn count(&mut self) -> bool {
+        if self.control & CR_START == 0 {
+            return false;
+        }
+        if self.counter > 0 {
+            self.counter -= 1;
+            return false;
+        }
+        self.counter = self.latch;
+        if self.control & CR_ONE_SHOT != 0 {
+            self.control &= !CR_START;
+        }
+        true
+    }
+
+    fn write_latch_hi(&mut self, value: u8) {
+        self.latch = (self.latch & 0x00ff) | ((value as u16) << 8);
+        // a stopped timer loads at once
+        if self.control & CR_START == 0 {
+            self.counter = self.latch;
+        }
+    }
+
+    fn write_control(&mut self, value: u8) {
+        if value & CR_LOAD != 0 {
+            self.counter = self.latch;
+        }
+        self.control = value & !CR_LOAD;
+    }
+
+    fn save_state(&self, out: &mut StateWriter) {
+        out.u16(self.counter);
+        out.u16(self.latch);
+        out.u8(self.control);
+    }
+
+    fn load_state(&mut self, input: &mut StateReader) -> Result<(), String> {
+        self.counter = input.u16()?;
+        self.latch = input.u16()?;
+        self.control = input.u8()?;
+        Ok(())
+    }
+}
+
+pub struct Cia {
+    pub pins_a: u8,         // levels on port A pins not driven as outputs
+    pub pins_b: u8,         // levels on port B pins not driven as outputs
+    pra: u8,
+    prb: u8,
+    ddra: u8,
+    ddrb: u8,
+    ta: Timer,
+    tb: Timer,
+    tod: [u8; 4],           // tenths, seconds, minutes, hours (bit 7 pm)
+    alarm: [u8; 4],
+    tod_latch: Option<[u8; 4]>,
+    tod_stopped: bool,
+    tod_cycles: u64,        // cycles per tenth of a second
+    tod_wait: u64,
+    sdr: u8,
+    icr: u8,
+    mask: u8,
+}
+
+impl Cia {
+    pub fn new(clock_hz: f64) -> Cia {
+        let tod_cycles = (clock_hz / 10.0) as u64;
+        Cia {
+            pins_a: 0xff,
+            pins_b: 0xff,
+            pra: 0,
+            prb: 0,
+            ddra: 0,
+            ddrb: 0,
+            ta: Timer::new(),
+            tb: Timer::new(),
+            tod: [0, 0, 0, 0x01],
+            alarm: [0; 4],
+            tod_latch: None,
+            tod_stopped: false,
+            tod_cycles,
+            tod_wait: tod_cycles,
+            sdr: 0,
+            icr: 0,
+            mask: 0,
+        }
+    }
+
+    // levels on port A, outputs driven from PRA
+    pub fn port_a(&self) -> u8 {
+        (self.pra & self.ddra) | (self.pins_a & !self.ddra)
+    }
+
+    // levels on port B, outputs driven from PRB
+    pub fn port_b(&self) -> u8 {
+        (self.prb & self.ddrb) | (self.pins_b & !self.ddrb)
+    }
+
+    // one cycle of the timers
+    fn clock(&mut self) {
+        let ta_underflow = self.ta.count();
+        if ta_underflow {
+            self.icr |= IRQ_TA;
+        }
+        let tb_counts = match self.tb.control & CRB_INMODE {
+            0 => true,
+            CRB_COUNT_TA => ta_underflow,
+            _ => false,
+        };
+        if tb_counts && self.tb.count() {
+            self.icr |= IRQ_TB;
+        }
+    }
+
+    // advance the time of day by a tenth of a second
+    fn tick_tod(&mut self) {
+        fn bcd_inc(value: u8) -> u8 {
+            if value & 0x0f == 9 { (value & 0xf0) + 0x10 } else { value + 1 }
+        }
+        let [tenths, seconds, minutes, hours] = &mut self.tod;
+        *tenths = bcd_inc(*tenths);
+        if *tenths == 0x10 {
+            *tenths = 0;
+            *seconds = bcd_inc(*seconds);
+            if *seconds == 0x60 {
+                *seconds = 0;
+                *minutes = bcd_inc(*minutes);
+                if *minutes == 0x60 {
+                    *minutes = 0;
+                    // 12 hour clock with bit 7 for pm; 11 to 12 flips it
+                    let pm = *hours & 0x80;
+                    let hour = bcd_inc(*hours & 0x1f);
+                    *hours = match hour {
+                        0x12 => 0x12 | (pm ^ 0x80),
+                        0x13 => 0x01 | pm,
+                        _ => hour | pm,
+                    };
+                }
+            }
+        }
+        if self.tod == self.alarm {
+            self.icr |= IRQ_ALARM;
+        }
+    }
+
+    fn read_tod(&mut self, index: usize) -> u8 {
+        if index == 3 {
+            self.tod_latch = Some(self.tod);
+        }
+        let value = self.tod_latch.unwrap_or(self.tod)[index];
+        if index == 0 {
+            self.tod_latch = None;
+        }
+        value
+    }
+
+    fn write_tod(&mut self, index: usize, value: u8) {
+        let value = match index {
+            0 => value & 0x0f,
+            3 => value & 0x9f,
+            _ => value & 0x7f,
+        };
+        if self.tb.control & CRB_ALARM != 0 {
+            self.alarm[index] = value;
+            return;
+        }
+        self.tod[index] = value;
+        match index {
+            3 => self.tod_stopped = true,
+            0 => self.tod_stopped = false,
+            _ => {}
+        }
+    }
+}
+
+impl Device for Cia {
+    fn read(&mut self, offset: u16) -> u8 {
+        match offset & 0x0f {
+            PRA => self.port_a(),
+            PRB => self.port_b(),
+            DDRA => self.ddra,
+            DDRB => self.ddrb,
+            TA_L => self.ta.counter as u8,
+            TA_H => (self.ta.counter >> 8) as u8,
+            TB_L => self.tb.counter as u8,
+            TB_H => (self.tb.counter >> 8) as u8,
+            TOD_10THS..=TOD_HR => self.read_tod(((offset & 0x0f) - TOD_10THS) as usize),
+            SDR => self.sdr,
+            ICR => {
+                // reading acknowledges every interrupt
+                let any = if self.icr & self.mask != 0 { IRQ_ANY } else { 0 };
+                let value = self.icr | any;
+                self.icr = 0;
+                value
+            }
+            CRA => self.ta.control,
+            _ => self.tb.control,
+        }
+    }
+
+    fn write(&mut self, offset: u16, value: u8) {
+        match offset & 0x0f {
+            PRA => self.pra = value,
+            PRB => self.prb = value,
+            DDRA => self.ddra = value,
+            DDRB => self.ddrb = value,
+            TA_L => self.ta.latch = (self.ta.latch & 0xff00) | value as u16,
+            TA_H => self.ta.write_latch_hi(value),
+            TB_L => self.tb.latch = (self.tb.latch & 0xff00) | value as u16,
+            TB_H => self.tb.write_latch_hi(value),
+            TOD_10THS..=TOD_HR => self.write_tod(((offset & 0x0f) - TOD_10THS) as usize, value),
+            SDR => self.sdr = value,
+            ICR => {
+                if value & 0x80 != 0 {
+                    self.mask |= value & 0x1f;
+                } else {
+                    self.mask &= !(value & 0x1f);
+                }
+            }
+            CRA => self.ta.write_control(value),
+            _ => self.tb.write_control(value),
+        }
+    }
+
+    fn tick(&mut self, cycles: u32) {
+        for _ in 0..cycles {
+            self.clock();
+        }
+        if self.tod_stopped || self.tod_cycles == 0 {
+            return;
+        }
+        let mut cycles = cycles as u64;
+        while cycles >= self.tod_wait {
+            cycles -= self.tod_wait;
+            self.tod_wait = self.tod_cycles;
+            self.tick_tod();
+        }
+        self.tod_wait -= cycles;
+    }
+
+    fn irq(&self) -> bool {
+        self.icr & self.mask != 0
+    }
+
+    fn raises_irqs(&self) -> bool {
+        true
+    }
+
+    fn save_state(&self, out: &mut StateWriter) {
+        for value in [self.pins_a, self.pins_b, self.pra, self.prb, self.ddra, self.ddrb] {
+            out.u8(value);
+        }
+        self.ta.save_state(out);
+        self.tb.save_state(out);
+        out.bytes(&self.tod);
+        out.bytes(&self.alarm);
+        out.bool(self.tod_latch.is_some());
+        out.bytes(&self.tod_latch.unwrap_or_default());
+        out.bool(self.tod_stopped);
+        out.u64(self.tod_wait);
+        for value in [self.sdr, self.icr, self.mask] {
+            out.u8(value);
+        }
+    }
+
+    fn load_state(&mut self, input: &mut StateReader) -> Result<(), String> {
+        for value in [&mut self.pins_a, &mut self.pins_b, &mut self.pra, &mut self.prb, &mut self.ddra, &mut self.ddrb] {
+            *value = input.u8()?;
+        }
+        self.ta.load_state(input)?;
+        self.tb.load_state(input)?;
+        input.bytes_into(&mut self.tod)?;
+        input.bytes_into(&mut self.alarm)?;
+        let latched = input.bool()?;
+        let mut latch = [0; 4];
+        input.bytes_into(&mut latch)?;
+        self.tod_latch = latched.then_some(latch);
+        self.tod_stopped = input.bool()?;
+        self.tod_wait = input.u64()?;
+        for value in [&mut self.sdr, &mut self.icr, &mut self.mask] {
+            *value = input.u8()?;
+        }
+        Ok(())
+    }
+}
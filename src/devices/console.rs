@@ -0,0 +1,45 @@
+
+// character output device
+//
+//   base+1   write: print the byte on the host terminal
+//
+// the other registers read as zero and ignore writes. this matches the
+// putc address used by py65 and the EhBASIC ports built for it, so
+// with the console at $f000 they print without a full uart.
+
+use std::io;
+use std::io::Write;
+
+use crate::devices::write_terminal;
+use crate::memory::Device;
+
+pub const CONSOLE_SIZE: u16 = 16;
+
+const PUTC_OFFSET: u16 = 1;
+
+pub struct Console {
+    out: io::Stdout,
+}
+
+impl Console {
+    pub fn new() -> Console {
+        Console { out: io::stdout() }
+    }
+}
+
+impl Device for Console {
+    fn read(&mut self, _offset: u16) -> u8 {
+        0
+    }
+
+    fn write(&mut self, offset: u16, value: u8) {
+        if offset == PUTC_OFFSET {
+            // carriage returns from programs written for a terminal
+            // are dropped so lines don't end up doubled
+            if value != b'\r' {
+                let _result = write_terminal(&mut self.out, &[value]);
+            }
+            let _result = self.out.flush();
+        }
+    }
+}
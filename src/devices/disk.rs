@@ -0,0 +1,217 @@
+// block storage controller for 512 byte sectors in host image files
+//
+//   0   data: the next byte of the sector buffer
+//   1   status: bit 0 error, bit 3 the buffer wants reading or
+//       writing, bit 6 the selected drive is ready
+//   2   command: 1 read the sector into the buffer, 2 write the buffer
+//       to the sector once its 512 bytes are in, 3 put the drive's
+//       sector count in the first four bytes of the buffer
+//   3-6 sector number, low byte first
+//   7   drive: which of the images the commands use
+//
+// a read fills the buffer at once and the program then reads its bytes
+// from the data register; a write waits for the program to put 512
+// bytes there and then stores them. commands finish straight away, so
+// the controller is never busy. a sector past the end of the image, a
+// drive with no image or a write to a read only image sets the error
+// bit until the next command. writes go straight to the image file.
+
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+
+use crate::memory::{Device, StateReader, StateWriter};
+
+pub const DISK_SIZE: u16 = 8;
+
+const SECTOR_SIZE: usize = 512;
+
+const DATA: u16 = 0;
+const STATUS: u16 = 1;
+const COMMAND: u16 = 2;
+const SECTOR: u16 = 3;
+const DRIVE: u16 = 7;
+
+// status bits
+const STATUS_ERROR: u8 = 0x01;
+const STATUS_DATA_REQUEST: u8 = 0x08;
+const STATUS_READY: u8 = 0x40;
+
+// commands
+const COMMAND_READ: u8 = 1;
+const COMMAND_WRITE: u8 = 2;
+const COMMAND_SIZE: u8 = 3;
+
+struct Drive {
+    file: File,
+    sectors: u32,
+    writable: bool,
+}
+
+// what the data register is being used for
+#[derive(PartialEq)]
+enum Transfer {
+    Idle,
+    Reading,
+    Writing,
+}
+
+pub struct Disk {
+    drives: Vec<Drive>,
+    drive: u8,
+    sector: [u8; 4],
+    buffer: [u8; SECTOR_SIZE],
+    index: usize,
+    transfer: Transfer,
+    error: bool,
+}
+
+impl Disk {
+    // images that can't be written are still read
+    pub fn open(paths: &[String]) -> Result<Disk, String> {
+        let mut drives = Vec::new();
+        for path in paths {
+            let (file, writable) = match OpenOptions::new().read(true).write(true).open(path) {
+                Ok(file) => (file, true),
+                Err(_) => (File::open(path).map_err(|e| format!("can't open disk image {}: {}", path, e))?, false),
+            };
+            let len = file.metadata().map_err(|e| format!("can't read {}: {}", path, e))?.len();
+            let sectors = (len / SECTOR_SIZE as u64).min(u32::MAX as u64) as u32;
+            drives.push(Drive { file, sectors, writable });
+        }
+        Ok(Disk {
+            drives,
+            drive: 0,
+            sector: [0; 4],
+            buffer: [0; SECTOR_SIZE],
+            index: 0,
+            transfer: Transfer::Idle,
+            error: false,
+        })
+    }
+
+    fn sector(&self) -> u32 {
+        u32::from_le_bytes(self.sector)
+    }
+
+    fn status(&self) -> u8 {
+        let mut status = 0;
+        if self.error {
+            status |= STATUS_ERROR;
+        }
+        if self.transfer != Transfer::Idle {
+            status |= STATUS_DATA_REQUEST;
+        }
+        if (self.drive as usize) < self.drives.len() {
+            status |= STATUS_READY;
+        }
+        status
+    }
+
+    fn command(&mut self, command: u8) {
+        self.error = false;
+        self.transfer = Transfer::Idle;
+        self.index = 0;
+        let sector = self.sector();
+        let Some(drive) = self.drives.get_mut(self.drive as usize) else {
+            self.error = true;
+            return;
+        };
+        match command {
+            COMMAND_READ if sector < drive.sectors => {
+                let read = drive.file.seek(SeekFrom::Start(sector as u64 * SECTOR_SIZE as u64))
+                    .and_then(|_| drive.file.read_exact(&mut self.buffer));
+                match read {
+                    Ok(()) => self.transfer = Transfer::Reading,
+                    Err(_) => self.error = true,
+                }
+            }
+            COMMAND_WRITE if sector < drive.sectors && drive.writable => self.transfer = Transfer::Writing,
+            COMMAND_SIZE => {
+                self.buffer.fill(0);
+                self.buffer[..4].copy_from_slice(&drive.sectors.to_le_bytes());
+                self.transfer = Transfer::Reading;
+            }
+            _ => self.error = true,
+        }
+    }
+
+    // the last byte of a write is in: store the sector
+    fn store(&mut self) {
+        let sector = self.sector();
+        let drive = &mut self.drives[self.drive as usize];
+        let written = drive.file.seek(SeekFrom::Start(sector as u64 * SECTOR_SIZE as u64))
+            .and_then(|_| drive.file.write_all(&self.buffer))
+            .and_then(|_| drive.file.flush());
+        self.error = written.is_err();
+    }
+}
+
+impl Device for Disk {
+    fn read(&mut self, offset: u16) -> u8 {
+        match offset & 0x07 {
+            DATA => {
+                if self.transfer != Transfer::Reading {
+                    return 0xff;
+                }
+                let value = self.buffer[self.index];
+                self.index += 1;
+                if self.index == SECTOR_SIZE {
+                    self.transfer = Transfer::Idle;
+                }
+                value
+            }
+            STATUS => self.status(),
+            COMMAND => 0xff,
+            DRIVE => self.drive,
+            offset => self.sector[(offset - SECTOR) as usize],
+        }
+    }
+
+    fn write(&mut self, offset: u16, value: u8) {
+        match offset & 0x07 {
+            DATA => {
+                if self.transfer != Transfer::Writing {
+                    return;
+                }
+                self.buffer[self.index] = value;
+                self.index += 1;
+                if self.index == SECTOR_SIZE {
+                    self.transfer = Transfer::Idle;
+                    self.store();
+                }
+            }
+            STATUS => {}
+            COMMAND => self.command(value),
+            DRIVE => self.drive = value,
+            offset => self.sector[(offset - SECTOR) as usize] = value,
+        }
+    }
+
+    // the images are not saved, only the controller
+    fn save_state(&self, out: &mut StateWriter) {
+        out.u8(self.drive);
+        out.bytes(&self.sector);
+        out.bytes(&self.buffer);
+        out.u16(self.index as u16);
+        out.u8(match self.transfer {
+            Transfer::Idle => 0,
+            Transfer::Reading => 1,
+            Transfer::Writing => 2,
+        });
+        out.bool(self.error);
+    }
+
+    fn load_state(&mut self, input: &mut StateReader) -> Result<(), String> {
+        self.drive = input.u8()?;
+        input.bytes_into(&mut self.sector)?;
+        input.bytes_into(&mut self.buffer)?;
+        self.index = input.u16()? as usize % SECTOR_SIZE;
+        self.transfer = match input.u8()? {
+            1 => Transfer::Reading,
+            2 if (self.drive as usize) < self.drives.len() => Transfer::Writing,
+            _ => Transfer::Idle,
+        };
+        self.error = input.bool()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,90 @@
+
+// Ben Eater's breadboard 6502: a 6522 at $6000 with an HD44780 16x2
+// lcd and a button
+//
+//   port B   the lcd's eight data lines
+//   PA7      lcd E: a command or data byte is taken as E falls; reading
+//            with E high puts the lcd's byte on port B
+//   PA6      lcd RW
+//   PA5      lcd RS
+//   CA1      the button, pulled up: typing any key presses it briefly
+//
+// the rest of the VIA works as the via device does, with its irq wired
+// to the cpu.
+
+use crate::devices::lcd::{Lcd, LcdPort, Wiring};
+use crate::devices::via::{PortDevice, Via};
+use crate::replay::Inputs;
+use crate::memory::{Device, StateReader, StateWriter};
+
+pub const EATER_IO_SIZE: u16 = 16;
+
+const PRESS_SECONDS: f64 = 0.05;
+
+pub struct EaterIo {
+    via: Via,
+    lcd: LcdPort,
+    press_cycles: u64,
+    pressed_for: u64,       // cycles the button stays down
+    inputs: Inputs,
+}
+
+impl EaterIo {
+    pub fn new(inputs: Inputs, clock_hz: f64) -> EaterIo {
+        inputs.claim_keyboard();
+        EaterIo {
+            via: Via::new(),
+            lcd: LcdPort::new(Lcd::new(16, 2, clock_hz), Wiring::eater()),
+            press_cycles: (clock_hz * PRESS_SECONDS) as u64,
+            pressed_for: 0,
+            inputs,
+        }
+    }
+}
+
+impl Device for EaterIo {
+    fn read(&mut self, offset: u16) -> u8 {
+        self.via.read(offset)
+    }
+
+    fn write(&mut self, offset: u16, value: u8) {
+        self.via.write(offset, value);
+        self.lcd.update(&mut self.via);
+    }
+
+    fn tick(&mut self, cycles: u32) {
+        self.via.tick(cycles);
+        self.lcd.tick(cycles);
+        let cycles = cycles as u64;
+        if self.pressed_for > 0 {
+            self.pressed_for = self.pressed_for.saturating_sub(cycles);
+            if self.pressed_for == 0 {
+                self.via.set_ca1(true);
+            }
+        } else if self.inputs.poll_key().is_some() {
+            self.via.set_ca1(false);
+            self.pressed_for = self.press_cycles;
+        }
+    }
+
+    fn irq(&self) -> bool {
+        self.via.irq()
+    }
+
+    fn raises_irqs(&self) -> bool {
+        true
+    }
+
+    fn save_state(&self, out: &mut StateWriter) {
+        self.via.save_state(out);
+        self.lcd.save_state(out);
+        out.u64(self.pressed_for);
+    }
+
+    fn load_state(&mut self, input: &mut StateReader) -> Result<(), String> {
+        self.via.load_state(input)?;
+        self.lcd.load_state(input)?;
+        self.pressed_for = input.u64()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,267 @@
+// memory-mapped bitmap display, drawn on the host terminal or, with the
+// window feature, in a window of its own
+//
+// the framebuffer is ram the program writes pixels into: rows of width
+// pixels from the top left, each pixel depth bits (1, 2, 4 or 8) and
+// indexing the palette. pixels sharing a byte are packed from the high
+// bits, leftmost first. whenever it has changed it is redrawn at most
+// REFRESH_HZ times a second of emulated time, two pixel rows to a line
+// of half blocks in 24-bit colour, so it needs a terminal with truecolor
+// support and one column per pixel. a windowed framebuffer is shown
+// at the same rate, and its window's events are answered then whether
+// or not it changed.
+//
+// an offscreen framebuffer is never drawn; whoever made it takes the
+// pixels with take_frame instead, as the browser build does.
+//
+// the default palettes are black and white, four greys, the cga colours
+// and 3-3-2 rgb (rrrgggbb) for the four depths.
+
+use std::io;
+use std::io::Write;
+
+use crate::memory::{Device, Picture, StateReader, StateWriter};
+#[cfg(feature = "window")]
+use super::window::Window;
+
+const REFRESH_HZ: f64 = 30.0;
+
+const CGA: [u32; 16] = [
+    0x000000, 0x0000aa, 0x00aa00, 0x00aaaa, 0xaa0000, 0xaa00aa, 0xaa5500, 0xaaaaaa,
+    0x555555, 0x5555ff, 0x55ff55, 0x55ffff, 0xff5555, 0xff55ff, 0xffff55, 0xffffff,
+];
+
+// the colours a depth shows without a palette option
+pub fn default_palette(depth: u32) -> Vec<u32> {
+    match depth {
+        1 => vec![0x000000, 0xffffff],
+        2 => vec![0x000000, 0x555555, 0xaaaaaa, 0xffffff],
+        4 => CGA.to_vec(),
+        _ => (0..=255u32).map(|index| {
+            let red = (index >> 5) * 255 / 7;
+            let green = ((index >> 2) & 0x07) * 255 / 7;
+            let blue = (index & 0x03) * 255 / 3;
+            (red << 16) | (green << 8) | blue
+        }).collect(),
+    }
+}
+
+// parse a colour like ff8000 or #ff8000
+pub fn parse_colour(text: &str) -> Result<u32, String> {
+    let hex = text.trim().trim_start_matches('#');
+    match u32::from_str_radix(hex, 16) {
+        Ok(colour) if hex.len() == 6 => Ok(colour),
+        _ => Err(format!("bad colour '{}', expected one like #ff8000", text)),
+    }
+}
+
+// where the pixels are drawn
+enum Output {
+    Terminal(io::Stdout),
+    #[cfg(feature = "window")]
+    Window(Box<Window>),
+    Offscreen,
+}
+
+pub struct Framebuffer {
+    width: usize,
+    height: usize,
+    depth: u32,
+    palette: Vec<u32>,
+    ram: Vec<u8>,
+    dirty: bool,
+    drawn: bool,
+    refresh_cycles: u64,
+    wait: u64,
+    out: Output,
+}
+
+impl Framebuffer {
+    pub fn new(width: usize, height: usize, depth: u32, palette: Vec<u32>, clock_hz: f64) -> Framebuffer {
+        Framebuffer {
+            width,
+            height,
+            depth,
+            palette,
+            ram: vec![0; width * height * depth as usize / 8],
+            dirty: false,
+            drawn: false,
+            refresh_cycles: (clock_hz / REFRESH_HZ) as u64,
+            wait: 0,
+            out: Output::Terminal(io::stdout()),
+        }
+    }
+
+    // only the browser build draws offscreen
+    pub fn offscreen(width: usize, height: usize, depth: u32, palette: Vec<u32>) -> Framebuffer {
+        Framebuffer { out: Output::Offscreen, ..Framebuffer::new(width, height, depth, palette, 0.0) }
+    }
+
+    // shown in the window instead of on the terminal
+    #[cfg(feature = "window")]
+    pub fn windowed(width: usize, height: usize, depth: u32, palette: Vec<u32>, clock_hz: f64, window: Window) -> Framebuffer {
+        Framebuffer { out: Output::Window(Box::new(window)), ..Framebuffer::new(width, height, depth, palette, clock_hz) }
+    }
+
+    // bytes of ram the display takes
+    pub fn size(&self) -> usize {
+        self.ram.len()
+    }
+
+    fn pixel(&self, x: usize, y: usize) -> u32 {
+        let bit = (y * self.width + x) * self.depth as usize;
+        let byte = self.ram[bit / 8];
+        let shift = 8 - self.depth as usize - bit % 8;
+        let index = (byte as usize >> shift) & ((1 << self.depth) - 1);
+        // a short palette shows black for the colours it leaves out
+        self.palette.get(index).copied().unwrap_or(0)
+    }
+
+    // every pixel as 0xrrggbb, rows from the top left
+    fn pixels(&self) -> Vec<u32> {
+        (0..self.height).flat_map(|y| (0..self.width).map(move |x| (x, y)))
+            .map(|(x, y)| self.pixel(x, y))
+            .collect()
+    }
+
+    // the picture as rgba bytes if it changed since last taken
+    pub fn take_frame(&mut self) -> Option<Vec<u8>> {
+        if !self.dirty {
+            return None;
+        }
+        self.dirty = false;
+        let mut frame = Vec::with_capacity(self.width * self.height * 4);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let colour = self.pixel(x, y);
+                frame.extend_from_slice(&[(colour >> 16) as u8, (colour >> 8) as u8, colour as u8, 0xff]);
+            }
+        }
+        Some(frame)
+    }
+
+    fn draw(&mut self) {
+        #[cfg(feature = "window")]
+        if matches!(self.out, Output::Window(_)) {
+            let pixels = self.dirty.then(|| self.pixels());
+            let Output::Window(window) = &mut self.out else { return };
+            if let Some(pixels) = pixels {
+                window.show(&pixels);
+            } else {
+                window.update();
+            }
+            if !window.is_open() {
+                self.out = Output::Offscreen;
+            }
+            self.dirty = false;
+            return;
+        }
+        if let Output::Terminal(out) = &self.out {
+            let mut out = out.lock();
+            let _result = terminal::queue_frame(&mut out, self, !self.drawn).and_then(|_| out.flush());
+        }
+        self.drawn = true;
+        self.dirty = false;
+    }
+}
+
+impl Device for Framebuffer {
+    fn read(&mut self, offset: u16) -> u8 {
+        self.ram[offset as usize]
+    }
+
+    fn write(&mut self, offset: u16, value: u8) {
+        if self.ram[offset as usize] != value {
+            self.ram[offset as usize] = value;
+            self.dirty = true;
+        }
+    }
+
+    fn picture(&self) -> Option<Picture> {
+        Some(Picture { width: self.width, height: self.height, pixels: self.pixels() })
+    }
+
+    fn tick(&mut self, cycles: u32) {
+        self.wait = self.wait.saturating_sub(cycles as u64);
+        if self.wait != 0 {
+            return;
+        }
+        // a window is looked at even when nothing changed
+        let due = match self.out {
+            Output::Terminal(_) => self.dirty,
+            #[cfg(feature = "window")]
+            Output::Window(_) => true,
+            Output::Offscreen => false,
+        };
+        if due {
+            self.draw();
+            self.wait = self.refresh_cycles;
+        }
+    }
+
+    fn save_state(&self, out: &mut StateWriter) {
+        out.bytes(&self.ram);
+    }
+
+    fn load_state(&mut self, input: &mut StateReader) -> Result<(), String> {
+        input.bytes_into(&mut self.ram)?;
+        self.dirty = true;
+        Ok(())
+    }
+}
+
+// the host terminal a framebuffer is drawn on
+#[cfg(not(target_arch = "wasm32"))]
+mod terminal {
+    use std::io;
+    use std::io::Write;
+
+    use crossterm::cursor::MoveTo;
+    use crossterm::queue;
+    use crossterm::style::{Color, Colors, Print, ResetColor, SetColors};
+    use crossterm::terminal::{Clear, ClearType};
+
+    use super::Framebuffer;
+
+    fn rgb(colour: u32) -> Color {
+        Color::Rgb { r: (colour >> 16) as u8, g: (colour >> 8) as u8, b: colour as u8 }
+    }
+
+    // the pixels from the top left, the upper of each pair of rows as the
+    // foreground of a half block and the lower as its background
+    pub fn queue_frame(out: &mut impl Write, framebuffer: &Framebuffer, clear: bool) -> io::Result<()> {
+        if clear {
+            queue!(out, Clear(ClearType::All))?;
+        }
+        for y in (0..framebuffer.height).step_by(2) {
+            queue!(out, MoveTo(0, (y / 2) as u16))?;
+            // colours are only set when they change along the line
+            let mut colours = None;
+            let mut run = String::new();
+            for x in 0..framebuffer.width {
+                let top = framebuffer.pixel(x, y);
+                let bottom = if y + 1 < framebuffer.height { framebuffer.pixel(x, y + 1) } else { 0 };
+                if colours != Some((top, bottom)) {
+                    queue!(out, Print(&run), SetColors(Colors::new(rgb(top), rgb(bottom))))?;
+                    run.clear();
+                    colours = Some((top, bottom));
+                }
+                run.push('\u{2580}');
+            }
+            queue!(out, Print(&run), ResetColor)?;
+        }
+        queue!(out, MoveTo(0, framebuffer.height.div_ceil(2) as u16))
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+mod terminal {
+    use std::io;
+    use std::io::Write;
+
+    use super::Framebuffer;
+
+    pub fn queue_frame(_out: &mut impl Write, _framebuffer: &Framebuffer, _clear: bool) -> io::Result<()> {
+        Ok(())
+    }
+}
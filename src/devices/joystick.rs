@@ -0,0 +1,140 @@
+// joystick and paddle worked from the host keyboard
+//
+//   0   read: Atari style switches, low while pressed:
+//         bit 0 up, 1 down, 2 left, 3 right, 4 fire
+//   1   write: start the paddle timer, as the Apple II's $c070 does
+//       read: bit 7 set until the timer runs out, after a time in
+//       proportion to the paddle's position
+//   2   read: the paddle position, 0-255
+//
+// the arrow keys or w, a, s and d push the stick and space fires;
+// - and = turn the paddle down and up. the terminal only says when a
+// key is typed, so each one holds its switch down for HOLD_SECONDS,
+// long enough for the terminal's key repeat to keep a held key down.
+
+use crate::replay::Inputs;
+use crate::memory::{Device, StateReader, StateWriter};
+
+pub const JOYSTICK_SIZE: u16 = 4;
+
+const UP: u8 = 0x01;
+const DOWN: u8 = 0x02;
+const LEFT: u8 = 0x04;
+const RIGHT: u8 = 0x08;
+const FIRE: u8 = 0x10;
+const SWITCHES: [u8; 5] = [UP, DOWN, LEFT, RIGHT, FIRE];
+
+const HOLD_SECONDS: f64 = 0.15;
+const PADDLE_STEP: u8 = 8;
+// the paddle timer takes this long for each step of its position
+const PADDLE_UNIT_SECONDS: f64 = 11e-6;
+
+// where an escape sequence from an arrow key has got to
+enum Sequence {
+    None,
+    Started,
+    Bracket,
+}
+
+pub struct Joystick {
+    held: [u64; 5],         // cycles each switch stays down
+    hold_cycles: u64,
+    paddle: u8,
+    paddle_timer: u64,
+    unit_cycles: f64,
+    escape: Sequence,
+    inputs: Inputs,
+}
+
+impl Joystick {
+    pub fn new(inputs: Inputs, clock_hz: f64) -> Joystick {
+        inputs.claim_keyboard();
+        Joystick {
+            held: [0; 5],
+            hold_cycles: (clock_hz * HOLD_SECONDS) as u64,
+            paddle: 128,
+            paddle_timer: 0,
+            unit_cycles: clock_hz * PADDLE_UNIT_SECONDS,
+            escape: Sequence::None,
+            inputs,
+        }
+    }
+
+    fn press(&mut self, switch: u8) {
+        if let Some(index) = SWITCHES.iter().position(|&s| s == switch) {
+            self.held[index] = self.hold_cycles;
+        }
+    }
+
+    fn key(&mut self, key: u8) {
+        // arrows arrive as ESC [ A to D
+        match (&self.escape, key) {
+            (Sequence::None, 0x1b) => self.escape = Sequence::Started,
+            (Sequence::Started, b'[') => self.escape = Sequence::Bracket,
+            (Sequence::Bracket, b'A'..=b'D') => {
+                self.escape = Sequence::None;
+                self.press([UP, DOWN, RIGHT, LEFT][(key - b'A') as usize]);
+            }
+            _ => {
+                self.escape = Sequence::None;
+                match key.to_ascii_lowercase() {
+                    b'w' => self.press(UP),
+                    b's' => self.press(DOWN),
+                    b'a' => self.press(LEFT),
+                    b'd' => self.press(RIGHT),
+                    b' ' => self.press(FIRE),
+                    b'-' => self.paddle = self.paddle.saturating_sub(PADDLE_STEP),
+                    b'=' => self.paddle = self.paddle.saturating_add(PADDLE_STEP),
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    fn switches(&self) -> u8 {
+        let pressed = SWITCHES.iter().zip(&self.held)
+            .filter(|&(_, &held)| held > 0)
+            .fold(0, |bits, (&switch, _)| bits | switch);
+        !pressed
+    }
+}
+
+impl Device for Joystick {
+    fn read(&mut self, offset: u16) -> u8 {
+        match offset & 0x03 {
+            0 => self.switches(),
+            1 => if self.paddle_timer > 0 { 0x80 } else { 0 },
+            2 => self.paddle,
+            _ => 0xff,
+        }
+    }
+
+    fn write(&mut self, offset: u16, _value: u8) {
+        if offset & 0x03 == 1 {
+            self.paddle_timer = (self.paddle as f64 * self.unit_cycles) as u64;
+        }
+    }
+
+    fn tick(&mut self, cycles: u32) {
+        let cycles = cycles as u64;
+        for held in &mut self.held {
+            *held = held.saturating_sub(cycles);
+        }
+        self.paddle_timer = self.paddle_timer.saturating_sub(cycles);
+        while let Some(key) = self.inputs.poll_key() {
+            self.key(key);
+        }
+    }
+
+    // switches held by keys are not saved
+    fn save_state(&self, out: &mut StateWriter) {
+        out.u8(self.paddle);
+        out.u64(self.paddle_timer);
+    }
+
+    fn load_state(&mut self, input: &mut StateReader) -> Result<(), String> {
+        self.paddle = input.u8()?;
+        self.paddle_timer = input.u64()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,69 @@
+
+// apple ii style keyboard
+//
+//   base+$00..$0f   read: the last key, with bit 7 set until cleared
+//   base+$10..$1f   read or write: clear bit 7 (the strobe)
+//
+// keys come from the host terminal without blocking; a new key is
+// only latched once the program has cleared the strobe for the last
+// one. enter is sent as a carriage return, and with uppercase set
+// letters are sent in upper case as on the Apple II and II+.
+
+use crate::replay::Inputs;
+use crate::memory::{Device, StateReader, StateWriter};
+
+pub const KEYBOARD_SIZE: u16 = 0x20;
+
+const STROBE: u8 = 0x80;
+
+pub struct Keyboard {
+    latch: u8,
+    uppercase: bool,
+    inputs: Inputs,
+}
+
+impl Keyboard {
+    pub fn new(inputs: Inputs, uppercase: bool) -> Keyboard {
+        inputs.claim_keyboard();
+        Keyboard { latch: 0, uppercase, inputs }
+    }
+
+    fn clear_strobe(&mut self) {
+        self.latch &= !STROBE;
+    }
+}
+
+impl Device for Keyboard {
+    fn read(&mut self, offset: u16) -> u8 {
+        if offset >= 0x10 {
+            self.clear_strobe();
+            return self.latch;
+        }
+        if self.latch & STROBE == 0 {
+            if let Some(key) = self.inputs.poll_key() {
+                let key = match key {
+                    b'\n' => b'\r',
+                    key if self.uppercase => key.to_ascii_uppercase(),
+                    key => key,
+                };
+                self.latch = key | STROBE;
+            }
+        }
+        self.latch
+    }
+
+    fn write(&mut self, offset: u16, _value: u8) {
+        if offset >= 0x10 {
+            self.clear_strobe();
+        }
+    }
+
+    fn save_state(&self, out: &mut StateWriter) {
+        out.u8(self.latch);
+    }
+
+    fn load_state(&mut self, input: &mut StateReader) -> Result<(), String> {
+        self.latch = input.u8()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,447 @@
+
+// HD44780 character lcd, driven through its pins by the device it is
+// wired to, and drawn on the host terminal
+//
+// the controller takes a byte with RS low as a command and with RS
+// high as data for the display or character generator ram. commands
+// keep the busy flag set for as long as the real chip takes (37us, or
+// 1.52ms to clear or home), which programs see by reading with RS low.
+// it starts with an 8-bit interface; a function set with DL clear
+// switches to 4 bits, where each byte goes over D4-D7 as two nibbles,
+// high nibble first.
+//
+// LcdPort wires the lcd to a VIA's port pins: E, RW and RS on any pins
+// and the data lines on a whole port (8-bit) or four pins of one
+// (4-bit, to D4-D7). a byte is written as E falls with RW low; while E
+// is high with RW high the lcd drives the data pins.
+//
+// the display is the A00 character rom: ascii, with a yen sign for
+// backslash and arrows at $7e/$7f. the eight user characters show as
+// a block. a 4 row display shows the two lines of display ram as rows
+// 0 and 1, carrying on into rows 2 and 3 as the real 20x4 modules do.
+
+use std::io;
+use std::io::Write;
+
+use crate::devices::via::{Pin, Port, PortDevice, Via};
+use crate::memory::{StateReader, StateWriter};
+
+const DDRAM_SIZE: usize = 0x80;
+const LINE_LENGTH: usize = 0x28;           // each line holds 40 characters
+const REFRESH_HZ: f64 = 30.0;
+
+const COMMAND_SECONDS: f64 = 37e-6;
+const CLEAR_SECONDS: f64 = 1.52e-3;
+
+pub struct Lcd {
+    columns: usize,
+    rows: usize,
+    ddram: [u8; DDRAM_SIZE],
+    cgram: [u8; 64],
+    address: u8,
+    in_cgram: bool,         // data goes to the character generator
+    four_bit: bool,         // the interface is four bits wide
+    high_nibble: Option<u8>,    // the first half of a 4-bit write
+    low_nibble: Option<u8>,     // the second half of a 4-bit read
+    increment: bool,
+    shift_display: bool,
+    display_on: bool,
+    shift: usize,           // how far the display has shifted left
+    busy_cycles: u64,
+    command_cycles: u64,
+    clear_cycles: u64,
+    dirty: bool,
+    drawn: bool,
+    refresh_cycles: u64,
+    wait: u64,
+    out: io::Stdout,
+}
+
+impl Lcd {
+    pub fn new(columns: usize, rows: usize, clock_hz: f64) -> Lcd {
+        Lcd {
+            columns,
+            rows,
+            ddram: [0x20; DDRAM_SIZE],
+            cgram: [0; 64],
+            address: 0,
+            in_cgram: false,
+            four_bit: false,
+            high_nibble: None,
+            low_nibble: None,
+            increment: true,
+            shift_display: false,
+            display_on: false,
+            shift: 0,
+            busy_cycles: 0,
+            command_cycles: (clock_hz * COMMAND_SECONDS) as u64,
+            clear_cycles: (clock_hz * CLEAR_SECONDS) as u64,
+            dirty: false,
+            drawn: false,
+            refresh_cycles: (clock_hz / REFRESH_HZ) as u64,
+            wait: 0,
+            out: io::stdout(),
+        }
+    }
+
+    // the levels on D0-D7 as E falls for a write
+    pub fn bus_write(&mut self, rs: bool, bus: u8) {
+        if !self.four_bit {
+            self.write(rs, bus);
+        } else if let Some(high) = self.high_nibble.take() {
+            self.write(rs, high | (bus >> 4));
+        } else {
+            self.high_nibble = Some(bus & 0xf0);
+        }
+    }
+
+    // what the lcd drives onto D0-D7 as E rises for a read
+    pub fn bus_read(&mut self, rs: bool) -> u8 {
+        if !self.four_bit {
+            return self.read(rs);
+        }
+        if let Some(low) = self.low_nibble.take() {
+            return low << 4;
+        }
+        let value = self.read(rs);
+        self.low_nibble = Some(value & 0x0f);
+        value & 0xf0
+    }
+
+    // a byte written with RS low (command) or high (data)
+    fn write(&mut self, rs: bool, value: u8) {
+        if rs {
+            self.write_data(value);
+        } else {
+            self.command(value);
+        }
+    }
+
+    // a byte read with RS low (busy flag and address) or high (data)
+    fn read(&mut self, rs: bool) -> u8 {
+        if !rs {
+            let busy = if self.busy_cycles > 0 { 0x80 } else { 0 };
+            return busy | (self.address & 0x7f);
+        }
+        let value = if self.in_cgram {
+            self.cgram[(self.address & 0x3f) as usize]
+        } else {
+            self.ddram[self.address as usize % DDRAM_SIZE]
+        };
+        self.step_address();
+        value
+    }
+
+    fn command(&mut self, value: u8) {
+        self.busy_cycles = self.command_cycles;
+        match value {
+            0x80..=0xff => {
+                self.address = value & 0x7f;
+                self.in_cgram = false;
+            }
+            0x40..=0x7f => {
+                self.address = value & 0x3f;
+                self.in_cgram = true;
+            }
+            // function set: the interface width; the geometry is fixed
+            0x20..=0x3f => self.four_bit = value & 0x10 == 0,
+            0x10..=0x1f => {
+                let right = value & 0x04 != 0;
+                if value & 0x08 != 0 {
+                    self.shift_display_by(right);
+                } else {
+                    self.address = self.moved(self.address, right);
+                }
+            }
+            0x08..=0x0f => {
+                self.display_on = value & 0x04 != 0;
+                self.dirty = true;
+            }
+            0x04..=0x07 => {
+                self.increment = value & 0x02 != 0;
+                self.shift_display = value & 0x01 != 0;
+            }
+            0x02 | 0x03 => {
+                self.address = 0;
+                self.in_cgram = false;
+                self.shift = 0;
+                self.busy_cycles = self.clear_cycles;
+                self.dirty = true;
+            }
+            0x01 => {
+                self.ddram = [0x20; DDRAM_SIZE];
+                self.address = 0;
+                self.in_cgram = false;
+                self.increment = true;
+                self.shift = 0;
+                self.busy_cycles = self.clear_cycles;
+                self.dirty = true;
+            }
+            _ => {}
+        }
+    }
+
+    fn write_data(&mut self, value: u8) {
+        self.busy_cycles = self.command_cycles;
+        if self.in_cgram {
+            self.cgram[(self.address & 0x3f) as usize] = value;
+        } else {
+            self.ddram[self.address as usize % DDRAM_SIZE] = value;
+            if self.shift_display {
+                self.shift_display_by(!self.increment);
+            }
+        }
+        self.step_address();
+        self.dirty = true;
+    }
+
+    fn step_address(&mut self) {
+        if self.in_cgram {
+            let step = if self.increment { 1 } else { 0x3f };
+            self.address = (self.address + step) & 0x3f;
+        } else {
+            self.address = self.moved(self.address, self.increment);
+        }
+    }
+
+    // the next or previous display address; the two lines of 40 are
+    // at $00-$27 and $40-$67, and the cursor runs from one to the other
+    fn moved(&self, address: u8, right: bool) -> u8 {
+        match (address, right) {
+            (0x27, true) => 0x40,
+            (0x67, true) => 0x00,
+            (0x00, false) => 0x67,
+            (0x40, false) => 0x27,
+            (_, true) => address + 1,
+            (_, false) => address - 1,
+        }
+    }
+
+    fn shift_display_by(&mut self, right: bool) {
+        self.shift = if right { (self.shift + LINE_LENGTH - 1) % LINE_LENGTH } else { (self.shift + 1) % LINE_LENGTH };
+        self.dirty = true;
+    }
+
+    // displays with four rows show the two lines of ddram as rows 0 and
+    // 1, continuing into rows 2 and 3
+    fn row_text(&self, row: usize) -> String {
+        let (line, start) = match row {
+            0 => (0x00, 0),
+            1 => (0x40, 0),
+            2 => (0x00, self.columns),
+            _ => (0x40, self.columns),
+        };
+        (0..self.columns)
+            .map(|column| {
+                if !self.display_on {
+                    return ' ';
+                }
+                let offset = (start + column + self.shift) % LINE_LENGTH;
+                display_char(self.ddram[line + offset])
+            })
+            .collect()
+    }
+
+    fn draw(&mut self) {
+        {
+            let mut out = self.out.lock();
+            let _result = terminal::queue_display(&mut out, self, !self.drawn).and_then(|_| out.flush());
+        }
+        self.drawn = true;
+        self.dirty = false;
+    }
+
+    pub fn tick(&mut self, cycles: u32) {
+        let cycles = cycles as u64;
+        self.busy_cycles = self.busy_cycles.saturating_sub(cycles);
+        self.wait = self.wait.saturating_sub(cycles);
+        if self.dirty && self.wait == 0 {
+            self.draw();
+            self.wait = self.refresh_cycles;
+        }
+    }
+
+    // cycles until a change waiting to be drawn is drawn, u64::MAX if
+    // there is none; the busy flag is only looked at from a write
+    pub fn next_event(&self) -> u64 {
+        if self.dirty { self.wait } else { u64::MAX }
+    }
+
+    pub fn save_state(&self, out: &mut StateWriter) {
+        out.bytes(&self.ddram);
+        out.bytes(&self.cgram);
+        out.u8(self.address);
+        for nibble in [self.high_nibble, self.low_nibble] {
+            out.bool(nibble.is_some());
+            out.u8(nibble.unwrap_or(0));
+        }
+        for flag in [self.in_cgram, self.four_bit, self.increment, self.shift_display, self.display_on] {
+            out.bool(flag);
+        }
+        out.u8(self.shift as u8);
+        out.u64(self.busy_cycles);
+    }
+
+    pub fn load_state(&mut self, input: &mut StateReader) -> Result<(), String> {
+        input.bytes_into(&mut self.ddram)?;
+        input.bytes_into(&mut self.cgram)?;
+        self.address = input.u8()?;
+        for nibble in [&mut self.high_nibble, &mut self.low_nibble] {
+            let held = input.bool()?;
+            let value = input.u8()?;
+            *nibble = held.then_some(value);
+        }
+        for flag in [&mut self.in_cgram, &mut self.four_bit, &mut self.increment, &mut self.shift_display,
+            &mut self.display_on] {
+            *flag = input.bool()?;
+        }
+        self.shift = input.u8()? as usize % LINE_LENGTH;
+        self.busy_cycles = input.u64()?;
+        self.dirty = true;
+        Ok(())
+    }
+}
+
+fn display_char(code: u8) -> char {
+    match code {
+        0x00..=0x0f => '█',
+        0x5c => '¥',
+        0x7e => '→',
+        0x7f => '←',
+        0x20..=0x7d => code as char,
+        0xff => '█',
+        _ => ' ',
+    }
+}
+
+// which pins the lcd is on
+pub struct Wiring {
+    pub data: Pin,          // D0, or D4 with four_bit
+    pub four_bit: bool,
+    pub e: Pin,
+    pub rw: Option<Pin>,    // None when RW is tied low
+    pub rs: Pin,
+}
+
+impl Wiring {
+    // Ben Eater's: data on port B, E, RW and RS on PA7, PA6 and PA5
+    pub fn eater() -> Wiring {
+        Wiring {
+            data: Pin { port: Port::B, bit: 0 },
+            four_bit: false,
+            e: Pin { port: Port::A, bit: 7 },
+            rw: Some(Pin { port: Port::A, bit: 6 }),
+            rs: Pin { port: Port::A, bit: 5 },
+        }
+    }
+
+    fn data_lines(&self) -> u8 {
+        if self.four_bit { 4 } else { 8 }
+    }
+
+    pub fn check(&self) -> Result<(), String> {
+        if self.data.bit + self.data_lines() > 8 {
+            return Err(format!("{} lcd data lines don't fit on the port from bit {}",
+                self.data_lines(), self.data.bit));
+        }
+        Ok(())
+    }
+}
+
+// an lcd on a VIA's pins
+pub struct LcdPort {
+    lcd: Lcd,
+    wiring: Wiring,
+    enable: bool,           // level on the lcd's E line
+}
+
+impl LcdPort {
+    pub fn new(lcd: Lcd, wiring: Wiring) -> LcdPort {
+        LcdPort { lcd, wiring, enable: false }
+    }
+}
+
+impl PortDevice for LcdPort {
+    fn update(&mut self, via: &mut Via) {
+        let enable = self.wiring.e.level(via);
+        let read = self.wiring.rw.is_some_and(|rw| rw.level(via));
+        let rs = self.wiring.rs.level(via);
+        let data = self.wiring.data;
+        let mask = (((1u16 << self.wiring.data_lines()) - 1) as u8) << data.bit;
+
+        // 4-bit wiring connects D4-D7
+        if enable && !self.enable && read {
+            let bus = self.lcd.bus_read(rs);
+            let lines = if self.wiring.four_bit { bus >> 4 } else { bus };
+            let pins = data.port.pins(via);
+            *pins = (*pins & !mask) | ((lines << data.bit) & mask);
+        } else if !enable && self.enable && !read {
+            let lines = (data.port.levels(via) & mask) >> data.bit;
+            let bus = if self.wiring.four_bit { lines << 4 } else { lines };
+            self.lcd.bus_write(rs, bus);
+        }
+        // the lcd only drives the data lines while E is high for a read
+        if !enable || !read {
+            *data.port.pins(via) |= mask;
+        }
+        self.enable = enable;
+    }
+
+    fn tick(&mut self, cycles: u32) {
+        self.lcd.tick(cycles);
+    }
+
+    fn next_event(&self) -> u64 {
+        self.lcd.next_event()
+    }
+
+    fn save_state(&self, out: &mut StateWriter) {
+        self.lcd.save_state(out);
+        out.bool(self.enable);
+    }
+
+    fn load_state(&mut self, input: &mut StateReader) -> Result<(), String> {
+        self.lcd.load_state(input)?;
+        self.enable = input.bool()?;
+        Ok(())
+    }
+}
+
+// the host terminal an lcd is drawn on
+#[cfg(not(target_arch = "wasm32"))]
+mod terminal {
+    use std::io;
+    use std::io::Write;
+
+    use crossterm::cursor::MoveTo;
+    use crossterm::queue;
+    use crossterm::style::Print;
+    use crossterm::terminal::{Clear, ClearType};
+
+    use super::Lcd;
+
+    // the rows in a border from the top left, with the cursor left below
+    pub fn queue_display(out: &mut impl Write, lcd: &Lcd, clear: bool) -> io::Result<()> {
+        if clear {
+            queue!(out, Clear(ClearType::All))?;
+        }
+        let border = format!("+{}+", "-".repeat(lcd.columns));
+        queue!(out, MoveTo(0, 0), Print(&border))?;
+        for row in 0..lcd.rows {
+            queue!(out, MoveTo(0, row as u16 + 1), Print(format!("|{}|", lcd.row_text(row))))?;
+        }
+        queue!(out, MoveTo(0, lcd.rows as u16 + 1), Print(&border), MoveTo(0, lcd.rows as u16 + 2))
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+mod terminal {
+    use std::io;
+    use std::io::Write;
+
+    use super::Lcd;
+
+    pub fn queue_display(_out: &mut impl Write, _lcd: &Lcd, _clear: bool) -> io::Result<()> {
+        Ok(())
+    }
+}
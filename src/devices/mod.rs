@@ -0,0 +1,519 @@
+
+// memory-mapped devices that can be attached from a machine file or
+// with --device TYPE@ADDR
+//
+//   console     character output: a write to base+1 prints the byte
+//   keyboard    apple ii style keyboard: key at base, strobe clear at
+//               base+$10; uppercase = true sends letters in upper case
+//   via         6522 versatile interface adapter (16 registers); with
+//               lcd = "16x2" (or "20x4", ...) an HD44780 is wired to its
+//               pins: lcd-data = "pb0" names its first data line,
+//               lcd-bits = 4 uses D4-D7 only, and lcd-e, lcd-rw ("none"
+//               when tied low) and lcd-rs its control lines (defaults
+//               as Ben Eater's: pa7, pa6 and pa5); with sd = "card.img"
+//               an sd card backed by that image is wired to sd-sck,
+//               sd-mosi, sd-cs and sd-miso (defaults pa0 to pa3)
+//   framebuffer bitmap display drawn on the terminal in colour: width and
+//               height in pixels (default 64x64), depth = 1, 2, 4 or 8
+//               bits per pixel, and palette = "#000000/#ffffff/..." or an
+//               array of colours (ram of width*height*depth/8 bytes);
+//               window = true shows it in a window instead, scale times
+//               bigger (default 4), with the window feature
+//   text-screen character screen drawn on the terminal: columns and rows
+//               (default 40x25) and charset = "ascii" (bit 7 inverse) or
+//               "pet" screen codes (ram of columns*rows bytes)
+//   speaker     Apple II one-bit speaker: any access flips it; the sound is
+//               played with the audio feature (unless play = false) and
+//               recorded to the wav file named by file (16 registers)
+//   joystick    Atari style stick and fire at base, an Apple II style timed
+//               paddle at base+1 and its position at base+2, worked from
+//               the host keyboard (4 registers)
+//   random      reads give pseudo-random bytes from the seed option, or from
+//               the clock when there is none (1 register)
+//   disk        block storage: 512 byte sectors by number from the image
+//               named by file, or an array of images for several drives
+//               (8 registers)
+//   cassette    tape signal: input level in bit 7 of base, output set by
+//               bit 0 of base or flipped by base+1; load plays a .wav,
+//               .tim timing list or data file (as Kansas City standard
+//               tones) and save records to one (2 registers)
+//   printer     bytes written are added to the file option; translate =
+//               "ascii" or "petscii" turns them into host text (1 register)
+//   pia         6520/6821 peripheral interface adapter (4 registers)
+//   eater-io    Ben Eater's 6522 with a 16x2 lcd and a button (16 registers)
+//   timer       interval timer raising periodic irqs (8 registers)
+//   apple1-io   Apple-1 keyboard and display on a PIA (4 registers)
+//   apple2-text Apple II text page drawn on the terminal ($400 bytes)
+//   pet-screen  PET 40x25 screen drawn on the terminal ($400 bytes)
+//   pet-io      PET keyboard and retrace irq on the first PIA (4 registers)
+//   cia         6526 complex interface adapter (16 registers)
+//   c64-port    C64 processor port and banking at $0000 (2 registers);
+//               the basic, kernal and chargen options name the rom files
+//   c64-io      C64 i/o block at $d000: VIC and SID stubs, colour ram,
+//               CIA 1 with the keyboard and CIA 2 on nmi ($1000 bytes)
+//   c64-screen  C64 40x25 screen drawn on the terminal ($400 bytes)
+//   nes-ram     NES ram mirroring: the 2K at base repeats up to base+$1fff
+//   nes-ppu     NES PPU registers with vblank timing, nothing drawn ($2000)
+//   nes-apu     NES APU frame counter irq and controllers ($18 registers)
+//   nes-cart    NES mapper 0 cartridge at $8000 from the iNES file option
+//   acia        6551 serial port (4 registers); the connect option joins
+//               it to the host, otherwise nothing is on the other end
+//                 connect = "stdio"     the terminal
+//                 connect = "tcp:6551"  a tcp client on localhost port 6551
+//                 connect = "tcp:0.0.0.0:6551"  ...or on any interface
+//               input_newline and output_newline ("cr", "lf" or "crlf")
+//               change the line endings it receives and sends
+//   script      a device written in rhai, from the script named by file,
+//               answering size addresses (default 1); needs the rhai
+//               feature
+//   plugin      a device from the shared library named by file, written
+//               against the e6502r-plugin crate or plugin/e6502r_plugin.h;
+//               other options are passed to it; unix only
+
+mod acia;
+mod apple1;
+#[cfg(feature = "audio")]
+mod audio;
+mod apple2;
+mod c64;
+mod cassette;
+mod cia;
+mod console;
+mod disk;
+mod eater;
+pub mod framebuffer;
+mod joystick;
+pub mod keyboard;
+mod lcd;
+mod nes;
+mod pet;
+mod pia;
+mod plugin;
+mod printer;
+mod random;
+mod screen;
+#[cfg(feature = "rhai")]
+mod script;
+mod sdcard;
+mod serial;
+mod speaker;
+mod timer;
+mod via;
+#[cfg(feature = "window")]
+mod window;
+
+use std::fs;
+use std::io;
+use std::io::Write;
+
+use crate::memory::{Memory, MEMSIZE};
+use crate::replay::Inputs;
+use crate::toml::{get_string, Table, Value};
+
+// a device, its base address and any device specific options
+#[derive(Clone)]
+pub struct DeviceConfig {
+    pub kind: String,
+    pub base: u16,
+    pub options: Table,
+}
+
+// clock rate devices time themselves against when the machine has none
+pub const DEFAULT_CLOCK_HZ: f64 = 1e6;
+
+// a seed from the host clock, as a random device takes without one
+pub fn clock_seed() -> u64 {
+    random::Random::clock_seed()
+}
+
+// bytes from a seed, as the random device gives them
+pub fn random_bytes(seed: u64) -> impl FnMut() -> u8 {
+    let mut random = random::Random::new(seed);
+    move || random.next()
+}
+
+// bytes for the host terminal; while the e6502r command has it in raw
+// mode for a keyboard device, a newline needs a carriage return as well
+pub(crate) fn write_terminal(out: &mut impl Write, bytes: &[u8]) -> io::Result<()> {
+    if !bytes.contains(&b'\n') || !raw_terminal() {
+        return out.write_all(bytes);
+    }
+    for (index, part) in bytes.split(|&byte| byte == b'\n').enumerate() {
+        if index > 0 {
+            out.write_all(b"\r\n")?;
+        }
+        out.write_all(part)?;
+    }
+    Ok(())
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn raw_terminal() -> bool {
+    crossterm::terminal::is_raw_mode_enabled().unwrap_or(false)
+}
+
+#[cfg(target_arch = "wasm32")]
+fn raw_terminal() -> bool {
+    false
+}
+
+// create the device described by config and map it into memory
+// clock_hz is the cpu clock, for devices that run in real time
+pub fn attach_device(mem: &mut Memory, config: &DeviceConfig, clock_hz: f64) -> Result<(), String> {
+    match config.kind.as_str() {
+        "console" => mem.attach(config.base, console::CONSOLE_SIZE, Box::new(console::Console::new())),
+        "keyboard" => {
+            let uppercase = option_bool(config, "uppercase")?.unwrap_or(false);
+            mem.attach(config.base, keyboard::KEYBOARD_SIZE, Box::new(keyboard::Keyboard::new(mem.inputs.clone(), uppercase)))
+        }
+        "via" => {
+            let mut wired: Vec<Box<dyn via::PortDevice>> = Vec::new();
+            if let Some(lcd) = lcd_port(config, clock_hz)? {
+                wired.push(Box::new(lcd));
+            }
+            if let Some(sd) = sd_port(config)? {
+                wired.push(Box::new(sd));
+            }
+            if wired.is_empty() {
+                mem.attach(config.base, via::VIA_SIZE, Box::new(via::Via::new()))
+            } else {
+                mem.attach(config.base, via::VIA_SIZE, Box::new(via::WiredVia::new(wired)))
+            }
+        }
+        "framebuffer" => {
+            let display = framebuffer(config, clock_hz)?;
+            let size = display.size() as u16;
+            mem.attach(config.base, size, Box::new(display))
+        }
+        "text-screen" => {
+            let (display, size) = text_screen(config, clock_hz)?;
+            mem.attach(config.base, size, Box::new(display))
+        }
+        "speaker" => {
+            let path = option_string(config, "file")?;
+            let play = option_bool(config, "play")?.unwrap_or(true);
+            let speaker = speaker::Speaker::new(path.as_deref(), play, clock_hz)
+                .map_err(|message| format!("{} at ${:04x}: {}", config.kind, config.base, message))?;
+            mem.attach(config.base, speaker::SPEAKER_SIZE, Box::new(speaker))
+        }
+        "joystick" => mem.attach(config.base, joystick::JOYSTICK_SIZE, Box::new(joystick::Joystick::new(mem.inputs.clone(), clock_hz))),
+        "random" => {
+            let seed = match option_integer(config, "seed")? {
+                Some(seed) => seed as u64,
+                None => {
+                    let seed = mem.inputs.seed(random::Random::clock_seed());
+                    eprintln!("{} at ${:04x} is seeded with {}", config.kind, config.base, seed);
+                    seed
+                }
+            };
+            mem.attach(config.base, random::RANDOM_SIZE, Box::new(random::Random::new(seed)))
+        }
+        "disk" => {
+            // one image, or an array of them for several drives
+            let paths = match config.options.get("file") {
+                None => return Err(format!("{} at ${:04x} needs a 'file' image", config.kind, config.base)),
+                Some(Value::Array(values)) => values.iter().map(|value| get_string("file", value)).collect::<Result<_, _>>()?,
+                Some(value) => vec![get_string("file", value)?],
+            };
+            let disk = disk::Disk::open(&paths).map_err(|e| format!("{} at ${:04x}: {}", config.kind, config.base, e))?;
+            mem.attach(config.base, disk::DISK_SIZE, Box::new(disk))
+        }
+        "cassette" => {
+            let load = option_string(config, "load")?;
+            let save = option_string(config, "save")?;
+            let cassette = cassette::Cassette::new(load.as_deref(), save.as_deref(), clock_hz)
+                .map_err(|e| format!("{} at ${:04x}: {}", config.kind, config.base, e))?;
+            mem.attach(config.base, cassette::CASSETTE_SIZE, Box::new(cassette))
+        }
+        "printer" => {
+            let context = |message: String| format!("{} at ${:04x}: {}", config.kind, config.base, message);
+            let path = option_string(config, "file")?.ok_or(context(String::from("needs a 'file' to print to")))?;
+            let translation = match option_string(config, "translate")? {
+                Some(text) => printer::Translation::parse(&text).map_err(context)?,
+                None => printer::Translation::None,
+            };
+            let printer = printer::Printer::new(&path, translation).map_err(context)?;
+            mem.attach(config.base, printer::PRINTER_SIZE, Box::new(printer))
+        }
+        "pia" => mem.attach(config.base, pia::PIA_SIZE, Box::new(pia::Pia::new())),
+        "eater-io" => mem.attach(config.base, eater::EATER_IO_SIZE, Box::new(eater::EaterIo::new(mem.inputs.clone(), clock_hz))),
+        "timer" => mem.attach(config.base, timer::TIMER_SIZE, Box::new(timer::Timer::new())),
+        "apple1-io" => mem.attach(config.base, apple1::APPLE1_IO_SIZE, Box::new(apple1::Apple1Io::new(mem.inputs.clone()))),
+        "apple2-text" => {
+            mem.attach(config.base, apple2::TEXT_PAGE_SIZE, Box::new(apple2::text_page(clock_hz)))
+        }
+        "pet-screen" => mem.attach(config.base, pet::SCREEN_SIZE, Box::new(pet::screen(clock_hz))),
+        "pet-io" => mem.attach(config.base, pet::PET_IO_SIZE, Box::new(pet::PetIo::new(mem.inputs.clone(), clock_hz))),
+        "cia" => mem.attach(config.base, cia::CIA_SIZE, Box::new(cia::Cia::new(clock_hz))),
+        "c64-port" => {
+            let roms = c64::Roms {
+                basic: add_rom(mem, config, "basic")?,
+                kernal: add_rom(mem, config, "kernal")?,
+                chargen: add_rom(mem, config, "chargen")?,
+            };
+            mem.attach(config.base, c64::PORT_SIZE, Box::new(c64::ProcessorPort::new(roms)))
+        }
+        "c64-io" => mem.attach(config.base, c64::IO_SIZE, Box::new(c64::C64Io::new(mem.inputs.clone(), clock_hz))),
+        "c64-screen" => mem.attach(config.base, c64::SCREEN_SIZE, Box::new(c64::screen(clock_hz))),
+        "nes-ram" => {
+            nes::mirror_ram(mem, config.base);
+            Ok(())
+        }
+        "nes-ppu" => mem.attach(config.base, nes::PPU_SIZE, Box::new(nes::Ppu::new())),
+        "nes-apu" => mem.attach(config.base, nes::APU_SIZE, Box::new(nes::Apu::new())),
+        "nes-cart" => {
+            let path = option_string(config, "file")?
+                .ok_or(format!("{} at ${:04x} needs the cartridge file", config.kind, config.base))?;
+            nes::load_cart(mem, &path)
+        }
+        "acia" => {
+            let port = serial_port(mem.inputs.clone(), config)?;
+            mem.attach(config.base, acia::ACIA_SIZE, Box::new(acia::Acia::new(port, clock_hz)))
+        }
+        "script" => script_device(mem, config),
+        "plugin" => {
+            let path = option_string(config, "file")?
+                .ok_or(format!("{} at ${:04x} needs the library 'file'", config.kind, config.base))?;
+            let mut options = config.options.clone();
+            options.remove("file");
+            let (device, size) = plugin::PluginDevice::load(&path, &options, clock_hz)?;
+            if size == 0 || config.base as usize + size as usize > MEMSIZE {
+                return Err(format!("{}: bad size {} at ${:04x}", path, size, config.base));
+            }
+            mem.attach(config.base, size, Box::new(device))
+        }
+        other => Err(format!("unknown device type '{}' at ${:04x}", other, config.base)),
+    }
+}
+
+// a device run by the rhai script named by the file option
+#[cfg(feature = "rhai")]
+fn script_device(mem: &mut Memory, config: &DeviceConfig) -> Result<(), String> {
+    let path = option_string(config, "file")?
+        .ok_or(format!("{} at ${:04x} needs the 'file' it runs", config.kind, config.base))?;
+    let size = option_integer(config, "size")?.unwrap_or(1);
+    if !(1..MEMSIZE as i64).contains(&size) {
+        return Err(format!("{} at ${:04x}: bad size {}", config.kind, config.base, size));
+    }
+    let mut options = config.options.clone();
+    options.remove("file");
+    options.remove("size");
+    let device = script::ScriptDevice::load(&path, config.base, &options)?;
+    mem.attach(config.base, size as u16, Box::new(device))
+}
+
+#[cfg(not(feature = "rhai"))]
+fn script_device(_mem: &mut Memory, config: &DeviceConfig) -> Result<(), String> {
+    Err(format!("{} at ${:04x} needs e6502r built with --features rhai", config.kind, config.base))
+}
+
+// a string option, if given
+fn option_string(config: &DeviceConfig, key: &str) -> Result<Option<String>, String> {
+    config.options.get(key).map(|value| get_string(key, value)).transpose()
+}
+
+// read the rom file named by an option, for a bank switching device
+fn add_rom(mem: &mut Memory, config: &DeviceConfig, key: &str) -> Result<usize, String> {
+    let path = option_string(config, key)?
+        .ok_or(format!("{} at ${:04x} needs the '{}' rom file", config.kind, config.base, key))?;
+    let data = fs::read(&path).map_err(|e| format!("{}: {}", path, e))?;
+    Ok(mem.add_rom(data))
+}
+
+// an integer option, if given; from the command line it is a string
+fn option_integer(config: &DeviceConfig, key: &str) -> Result<Option<i64>, String> {
+    match config.options.get(key) {
+        None => Ok(None),
+        Some(Value::Integer(n)) => Ok(Some(*n)),
+        Some(Value::String(text)) if text.parse::<i64>().is_ok() => Ok(text.parse().ok()),
+        Some(_) => Err(format!("{} at ${:04x}: '{}' should be a number", config.kind, config.base, key)),
+    }
+}
+
+// a true/false option, if given; from the command line it is a string
+fn option_bool(config: &DeviceConfig, key: &str) -> Result<Option<bool>, String> {
+    match config.options.get(key) {
+        None => Ok(None),
+        Some(Value::Boolean(flag)) => Ok(Some(*flag)),
+        Some(Value::String(text)) if text == "true" => Ok(Some(true)),
+        Some(Value::String(text)) if text == "false" => Ok(Some(false)),
+        Some(_) => Err(format!("{} at ${:04x}: '{}' should be true or false", config.kind, config.base, key)),
+    }
+}
+
+// the lcd wired to a via by its lcd options, if it has one
+fn lcd_port(config: &DeviceConfig, clock_hz: f64) -> Result<Option<lcd::LcdPort>, String> {
+    let Some(size) = option_string(config, "lcd")? else { return Ok(None) };
+    let context = |message: String| format!("{} at ${:04x}: {}", config.kind, config.base, message);
+    let (columns, rows) = size.split_once('x')
+        .and_then(|(columns, rows)| Some((columns.parse::<usize>().ok()?, rows.parse::<usize>().ok()?)))
+        .filter(|&(columns, rows)| (1..=40).contains(&columns) && matches!(rows, 1 | 2 | 4))
+        .ok_or_else(|| context(format!("bad lcd size '{}', expected one like 16x2", size)))?;
+
+    let mut wiring = lcd::Wiring::eater();
+    let pin = |key: &str| -> Result<Option<via::Pin>, String> {
+        option_string(config, key)?.map(|text| via::Pin::parse(&text).map_err(context)).transpose()
+    };
+    if let Some(data) = pin("lcd-data")? {
+        wiring.data = data;
+    }
+    if let Some(e) = pin("lcd-e")? {
+        wiring.e = e;
+    }
+    if let Some(rs) = pin("lcd-rs")? {
+        wiring.rs = rs;
+    }
+    match option_string(config, "lcd-rw")?.as_deref() {
+        None => {}
+        Some("none") => wiring.rw = None,
+        Some(text) => wiring.rw = Some(via::Pin::parse(text).map_err(context)?),
+    }
+    match option_integer(config, "lcd-bits")? {
+        None | Some(8) => {}
+        Some(4) => wiring.four_bit = true,
+        Some(bits) => return Err(context(format!("lcd-bits should be 4 or 8, not {}", bits))),
+    }
+    wiring.check().map_err(context)?;
+    Ok(Some(lcd::LcdPort::new(lcd::Lcd::new(columns, rows, clock_hz), wiring)))
+}
+
+// a framebuffer sized and coloured by its options
+fn framebuffer(config: &DeviceConfig, clock_hz: f64) -> Result<framebuffer::Framebuffer, String> {
+    let context = |message: String| format!("{} at ${:04x}: {}", config.kind, config.base, message);
+    let width = option_integer(config, "width")?.unwrap_or(64);
+    let height = option_integer(config, "height")?.unwrap_or(64);
+    let depth = option_integer(config, "depth")?.unwrap_or(8);
+    if !matches!(depth, 1 | 2 | 4 | 8) {
+        return Err(context(format!("depth should be 1, 2, 4 or 8, not {}", depth)));
+    }
+    if width < 1 || height < 1 || width * depth % 8 != 0 {
+        return Err(context(format!("can't show {}x{} at {} bits, rows must fill whole bytes", width, height, depth)));
+    }
+    // the sizes are whatever the options say, so far too big to multiply
+    let fits = width.checked_mul(height)
+        .and_then(|pixels| pixels.checked_mul(depth))
+        .is_some_and(|bits| config.base as i64 + bits / 8 <= MEMSIZE as i64);
+    if !fits {
+        return Err(context(format!("{}x{} at {} bits runs past the end of memory", width, height, depth)));
+    }
+    // a palette is an array of colours, or one string of them split by /
+    let colours: Vec<String> = match config.options.get("palette") {
+        None => Vec::new(),
+        Some(Value::Array(values)) => values.iter().map(|value| get_string("palette", value)).collect::<Result<_, _>>()?,
+        Some(value) => get_string("palette", value)?.split('/').map(str::to_string).collect(),
+    };
+    let palette = if colours.is_empty() {
+        framebuffer::default_palette(depth as u32)
+    } else {
+        colours.iter().map(|text| framebuffer::parse_colour(text)).collect::<Result<_, _>>().map_err(context)?
+    };
+    let (width, height, depth) = (width as usize, height as usize, depth as u32);
+    if option_bool(config, "window")?.unwrap_or(false) {
+        return windowed_framebuffer(config, width, height, depth, palette, clock_hz);
+    }
+    Ok(framebuffer::Framebuffer::new(width, height, depth, palette, clock_hz))
+}
+
+// a framebuffer in a window of its own, scaled up by the scale option
+#[cfg(feature = "window")]
+fn windowed_framebuffer(config: &DeviceConfig, width: usize, height: usize, depth: u32, palette: Vec<u32>, clock_hz: f64)
+        -> Result<framebuffer::Framebuffer, String> {
+    let context = |message: String| format!("{} at ${:04x}: {}", config.kind, config.base, message);
+    let factor = option_integer(config, "scale")?.unwrap_or(4);
+    let scale = window::scale_of(factor)
+        .ok_or_else(|| context(format!("scale should be 1, 2, 4, 8, 16 or 32, not {}", factor)))?;
+    let title = format!("e6502r {} at ${:04x}", config.kind, config.base);
+    let window = window::Window::open(&title, width, height, scale).map_err(context)?;
+    Ok(framebuffer::Framebuffer::windowed(width, height, depth, palette, clock_hz, window))
+}
+
+#[cfg(not(feature = "window"))]
+fn windowed_framebuffer(config: &DeviceConfig, _width: usize, _height: usize, _depth: u32, _palette: Vec<u32>, _clock_hz: f64)
+        -> Result<framebuffer::Framebuffer, String> {
+    Err(format!("{} at ${:04x} needs e6502r built with --features window for its window", config.kind, config.base))
+}
+
+// a text screen laid out by its options, and the bytes it takes
+fn text_screen(config: &DeviceConfig, clock_hz: f64) -> Result<(screen::TextScreen, u16), String> {
+    let context = |message: String| format!("{} at ${:04x}: {}", config.kind, config.base, message);
+    let columns = option_integer(config, "columns")?.unwrap_or(40);
+    let rows = option_integer(config, "rows")?.unwrap_or(25);
+    if !(1..=255).contains(&columns) || !(1..=255).contains(&rows) {
+        return Err(context(format!("can't show {} columns by {} rows", columns, rows)));
+    }
+    let size = columns * rows;
+    if config.base as i64 + size > MEMSIZE as i64 {
+        return Err(context(format!("{}x{} takes ${:x} bytes, past the end of memory", columns, rows, size)));
+    }
+    let decode = match option_string(config, "charset")?.as_deref() {
+        None | Some("ascii") => screen::ascii,
+        Some("pet") => pet::decode,
+        Some(other) => return Err(context(format!("unknown charset '{}', expected ascii or pet", other))),
+    };
+    let layout = screen::Layout {
+        columns: columns as usize,
+        rows: rows as usize,
+        row_offset: screen::rows_in_order,
+        decode,
+    };
+    Ok((screen::TextScreen::new(layout, size as u16, 0x20, clock_hz), size as u16))
+}
+
+// an sd card on a via's pins, if the sd option names its image
+fn sd_port(config: &DeviceConfig) -> Result<Option<sdcard::SdPort>, String> {
+    let Some(path) = option_string(config, "sd")? else { return Ok(None) };
+    let context = |message: String| format!("{} at ${:04x}: {}", config.kind, config.base, message);
+    let mut wiring = sdcard::Wiring::standard();
+    for (key, pin) in [
+        ("sd-cs", &mut wiring.cs),
+        ("sd-sck", &mut wiring.sck),
+        ("sd-mosi", &mut wiring.mosi),
+        ("sd-miso", &mut wiring.miso),
+    ] {
+        if let Some(text) = option_string(config, key)? {
+            *pin = via::Pin::parse(&text).map_err(context)?;
+        }
+    }
+    let card = sdcard::SdCard::open(&path).map_err(context)?;
+    Ok(Some(sdcard::SdPort::new(card, wiring)))
+}
+
+// the host end of a serial device's line
+fn serial_port(inputs: Inputs, config: &DeviceConfig) -> Result<Box<dyn acia::SerialPort>, String> {
+    let connect = option_string(config, "connect")?;
+    let port = connected_port(inputs, config, connect.as_deref())?;
+    // enter on the terminal is sent as a carriage return unless told otherwise
+    let mut input = newline_option(config, "input_newline")?;
+    if connect.as_deref() == Some("stdio") {
+        input = input.or(Some(serial::Newline::Cr));
+    }
+    let output = newline_option(config, "output_newline")?;
+    if input.is_none() && output.is_none() {
+        return Ok(port);
+    }
+    Ok(Box::new(serial::Newlines::new(port, input, output)))
+}
+
+// a serial line's line endings one way, if the option is given
+fn newline_option(config: &DeviceConfig, key: &str) -> Result<Option<serial::Newline>, String> {
+    let Some(text) = option_string(config, key)? else { return Ok(None) };
+    serial::Newline::parse(&text).map(Some).ok_or(format!(
+        "{} at ${:04x}: {} should be \"cr\", \"lf\" or \"crlf\", not '{}'", config.kind, config.base, key, text))
+}
+
+// the port at the end of the line the connect option names
+fn connected_port(inputs: Inputs, config: &DeviceConfig, connect: Option<&str>) -> Result<Box<dyn acia::SerialPort>, String> {
+    match connect {
+        None => Ok(Box::new(acia::Unconnected)),
+        Some("stdio") => Ok(Box::new(serial::StdioPort::new(inputs))),
+        Some(tcp) if tcp.starts_with("tcp:") => {
+            let addr = &tcp[4..];
+            // a bare port number listens on localhost only
+            let addr = if addr.contains(':') { addr.to_string() } else { format!("127.0.0.1:{}", addr) };
+            let port = serial::TcpPort::listen(inputs, &addr)?;
+            eprintln!("{} at ${:04x} is listening on {}", config.kind, config.base, addr);
+            Ok(Box::new(port))
+        }
+        Some(other) => Err(format!("{} at ${:04x}: unknown connection '{}'", config.kind, config.base, other)),
+    }
+}
@@ -0,0 +1,266 @@
+
+// skeleton NES: enough of the hardware around the 2a03 for cpu tests
+//
+//   $0000   2K of ram, mirrored up to $1fff (nes-ram)
+//   $2000   PPU registers, mirrored every 8 bytes up to $3fff (nes-ppu):
+//           vblank in PPUSTATUS and its nmi keep ntsc frame timing and
+//           PPUADDR/PPUDATA reach 16K of video memory, but nothing is
+//           drawn and sprite DMA is not emulated
+//   $4000   APU and controller registers (nes-apu): the frame counter
+//           raises its irq every 29830 cycles in 4-step mode unless
+//           inhibited; sound registers are ignored and the controllers
+//           have no buttons down
+//   $8000   a mapper 0 (NROM) cartridge loaded from an iNES file
+//           (nes-cart), with 8K of ram at $6000
+
+use std::fs;
+
+use crate::memory::{Bank, Device, Memory, StateReader, StateWriter};
+
+pub const RAM_SIZE: u16 = 0x800;
+pub const PPU_SIZE: u16 = 0x2000;
+pub const APU_SIZE: u16 = 0x18;
+
+const FRAME_CYCLES: u64 = 29781;        // 262 lines of 341 dots, 3 dots a cycle
+const VBLANK_START: u64 = 27394;        // line 241
+const VBLANK_END: u64 = 29667;          // the pre-render line
+
+const PPUCTRL_NMI: u8 = 0x80;
+const PPUCTRL_INCREMENT_32: u8 = 0x04;
+const PPUSTATUS_VBLANK: u8 = 0x80;
+
+const FRAME_IRQ_CYCLES: u64 = 29830;
+const FRAME_FIVE_STEP: u8 = 0x80;
+const FRAME_IRQ_INHIBIT: u8 = 0x40;
+const STATUS_FRAME_IRQ: u8 = 0x40;
+
+// make the 2K of ram repeat up to $1fff
+pub fn mirror_ram(mem: &mut Memory, base: u16) {
+    mem.mirror(base + RAM_SIZE, 3 * RAM_SIZE, base, RAM_SIZE);
+}
+
+pub struct Ppu {
+    ctrl: u8,
+    mask: u8,
+    status: u8,
+    oam_addr: u8,
+    oam: [u8; 256],
+    vram: Vec<u8>,
+    addr: u16,
+    second_write: bool,     // the next PPUSCROLL/PPUADDR write is the second
+    read_buffer: u8,
+    cycles: u64,            // cycles into the frame
+}
+
+impl Ppu {
+    pub fn new() -> Ppu {
+        Ppu {
+            ctrl: 0,
+            mask: 0,
+            status: 0,
+            oam_addr: 0,
+            oam: [0; 256],
+            vram: vec![0; 0x4000],
+            addr: 0,
+            second_write: false,
+            read_buffer: 0,
+            cycles: 0,
+        }
+    }
+
+    fn increment(&mut self) {
+        let step = if self.ctrl & PPUCTRL_INCREMENT_32 != 0 { 32 } else { 1 };
+        self.addr = self.addr.wrapping_add(step) & 0x3fff;
+    }
+}
+
+impl Device for Ppu {
+    fn read(&mut self, offset: u16) -> u8 {
+        match offset & 0x07 {
+            2 => {
+                let status = self.status;
+                self.status &= !PPUSTATUS_VBLANK;
+                self.second_write = false;
+                status
+            }
+            4 => self.oam[self.oam_addr as usize],
+            7 => {
+                // reads below the palettes come a read late
+                let value = self.vram[self.addr as usize];
+                let result = if self.addr >= 0x3f00 { value } else { self.read_buffer };
+                self.read_buffer = value;
+                self.increment();
+                result
+            }
+            _ => 0,
+        }
+    }
+
+    fn write(&mut self, offset: u16, value: u8) {
+        match offset & 0x07 {
+            0 => self.ctrl = value,
+            1 => self.mask = value,
+            3 => self.oam_addr = value,
+            4 => {
+                self.oam[self.oam_addr as usize] = value;
+                self.oam_addr = self.oam_addr.wrapping_add(1);
+            }
+            5 => self.second_write = !self.second_write,
+            6 => {
+                self.addr = if self.second_write {
+                    (self.addr & 0xff00) | value as u16
+                } else {
+                    ((value as u16 & 0x3f) << 8) | (self.addr & 0x00ff)
+                };
+                self.second_write = !self.second_write;
+            }
+            7 => {
+                self.vram[self.addr as usize] = value;
+                self.increment();
+            }
+            _ => {}
+        }
+    }
+
+    fn tick(&mut self, cycles: u32) {
+        let before = self.cycles;
+        self.cycles += cycles as u64;
+        if before < VBLANK_START && self.cycles >= VBLANK_START {
+            self.status |= PPUSTATUS_VBLANK;
+        }
+        if before < VBLANK_END && self.cycles >= VBLANK_END {
+            self.status &= !PPUSTATUS_VBLANK;
+        }
+        if self.cycles >= FRAME_CYCLES {
+            self.cycles -= FRAME_CYCLES;
+        }
+    }
+
+    fn nmi(&self) -> bool {
+        self.status & PPUSTATUS_VBLANK != 0 && self.ctrl & PPUCTRL_NMI != 0
+    }
+
+    fn raises_irqs(&self) -> bool {
+        true
+    }
+
+    fn save_state(&self, out: &mut StateWriter) {
+        for value in [self.ctrl, self.mask, self.status, self.oam_addr, self.read_buffer] {
+            out.u8(value);
+        }
+        out.bytes(&self.oam);
+        out.bytes(&self.vram);
+        out.u16(self.addr);
+        out.bool(self.second_write);
+        out.u64(self.cycles);
+    }
+
+    fn load_state(&mut self, input: &mut StateReader) -> Result<(), String> {
+        for value in [&mut self.ctrl, &mut self.mask, &mut self.status, &mut self.oam_addr, &mut self.read_buffer] {
+            *value = input.u8()?;
+        }
+        input.bytes_into(&mut self.oam)?;
+        input.bytes_into(&mut self.vram)?;
+        self.addr = input.u16()? & 0x3fff;
+        self.second_write = input.bool()?;
+        self.cycles = input.u64()? % FRAME_CYCLES;
+        Ok(())
+    }
+}
+
+pub struct Apu {
+    frame_control: u8,
+    frame_irq: bool,
+    frame_wait: u64,
+}
+
+impl Apu {
+    pub fn new() -> Apu {
+        Apu { frame_control: 0, frame_irq: false, frame_wait: FRAME_IRQ_CYCLES }
+    }
+}
+
+impl Device for Apu {
+    fn read(&mut self, offset: u16) -> u8 {
+        match offset {
+            0x15 => {
+                let status = if self.frame_irq { STATUS_FRAME_IRQ } else { 0 };
+                self.frame_irq = false;
+                status
+            }
+            // controllers: no buttons down, open bus in the upper bits
+            0x16 | 0x17 => 0x40,
+            _ => 0,
+        }
+    }
+
+    fn write(&mut self, offset: u16, value: u8) {
+        if offset == 0x17 {
+            self.frame_control = value;
+            self.frame_wait = FRAME_IRQ_CYCLES;
+            if value & FRAME_IRQ_INHIBIT != 0 {
+                self.frame_irq = false;
+            }
+        }
+    }
+
+    fn tick(&mut self, cycles: u32) {
+        let cycles = cycles as u64;
+        if self.frame_wait > cycles {
+            self.frame_wait -= cycles;
+            return;
+        }
+        self.frame_wait += FRAME_IRQ_CYCLES - cycles;
+        if self.frame_control & (FRAME_FIVE_STEP | FRAME_IRQ_INHIBIT) == 0 {
+            self.frame_irq = true;
+        }
+    }
+
+    fn irq(&self) -> bool {
+        self.frame_irq
+    }
+
+    fn raises_irqs(&self) -> bool {
+        true
+    }
+
+    fn save_state(&self, out: &mut StateWriter) {
+        out.u8(self.frame_control);
+        out.bool(self.frame_irq);
+        out.u64(self.frame_wait);
+    }
+
+    fn load_state(&mut self, input: &mut StateReader) -> Result<(), String> {
+        self.frame_control = input.u8()?;
+        self.frame_irq = input.bool()?;
+        self.frame_wait = input.u64()?;
+        Ok(())
+    }
+}
+
+// map the program rom of a mapper 0 iNES cartridge at $8000; a 16K rom
+// repeats at $c000. the character rom is not used.
+pub fn load_cart(mem: &mut Memory, path: &str) -> Result<(), String> {
+    let data = fs::read(path).map_err(|e| format!("{}: {}", path, e))?;
+    if data.len() < 16 || &data[..4] != b"NES\x1a" {
+        return Err(format!("{}: not an iNES file", path));
+    }
+    let mapper = (data[6] >> 4) | (data[7] & 0xf0);
+    if mapper != 0 {
+        return Err(format!("{}: mapper {} is not supported, only mapper 0", path, mapper));
+    }
+    let prg_size = data[4] as usize * 0x4000;
+    let start = if data[6] & 0x04 != 0 { 16 + 512 } else { 16 };    // skip a trainer
+    if prg_size == 0 || prg_size > 0x8000 || data.len() < start + prg_size {
+        return Err(format!("{}: bad program rom size", path));
+    }
+    let lower = mem.add_rom(data[start..start + 0x4000].to_vec());
+    let upper = if prg_size == 0x8000 {
+        mem.add_rom(data[start + 0x4000..start + 0x8000].to_vec())
+    } else {
+        lower
+    };
+    mem.set_bank(0x8000, 0x4000, Bank::Rom(lower));
+    mem.set_bank(0xc000, 0x4000, Bank::Rom(upper));
+    Ok(())
+}
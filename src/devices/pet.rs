@@ -0,0 +1,176 @@
+
+// Commodore PET 2001 screen and keyboard
+//
+// the screen is 40x25 screen codes at $8000: $00-$3f are @, A-Z and
+// punctuation, $40-$7f graphics (drawn as the nearest unicode shapes)
+// and $80-$ff the same in reverse.
+//
+// the keyboard and the vertical retrace hang off the first PIA at
+// $e810: port A bits 0-3 select one of the ten rows of the graphics
+// keyboard matrix, port B reads its eight columns (low while pressed),
+// and CB1 sees the 60 Hz retrace that drives the kernal's irq. each
+// key typed on the host is held down for a few frames so the kernal's
+// scan sees it, then released.
+
+use crate::devices::pia::{Pia, PIA_SIZE};
+use crate::devices::screen::{rows_in_order, Layout, TextScreen};
+use crate::replay::Inputs;
+use crate::memory::{Device, StateReader, StateWriter};
+
+pub const SCREEN_SIZE: u16 = 0x400;
+pub const PET_IO_SIZE: u16 = PIA_SIZE;
+
+const RETRACE_HZ: f64 = 60.0;
+const HOLD_FRAMES: u32 = 3;         // frames a key stays down
+const GAP_FRAMES: u32 = 2;          // frames between keys
+
+const PORT_B: u16 = 2;
+
+const NO_KEY: u8 = 0xff;
+const SHIFT: u8 = 0x00;
+const RETURN: u8 = 0x0d;
+const DEL: u8 = 0x14;
+const STOP: u8 = 0x03;
+
+// the graphics keyboard, by row and column, as the petscii each key types
+const KEY_MATRIX: [[u8; 8]; 10] = [
+    [b'!', b'#', b'%', b'&', b'(', 0x5f, 0x13, 0x1d],
+    [b'"', b'$', b'\'', b'\\', b')', NO_KEY, 0x11, DEL],
+    [b'Q', b'E', b'T', b'U', b'O', 0x5e, b'7', b'9'],
+    [b'W', b'R', b'Y', b'I', b'P', NO_KEY, b'8', b'/'],
+    [b'A', b'D', b'G', b'J', b'L', NO_KEY, b'4', b'6'],
+    [b'S', b'F', b'H', b'K', b':', NO_KEY, b'5', b'*'],
+    [b'Z', b'C', b'B', b'M', b';', RETURN, b'1', b'3'],
+    [b'X', b'V', b'N', b',', b'?', NO_KEY, b'2', b'+'],
+    [SHIFT, b'@', b']', NO_KEY, b'>', SHIFT, b'0', b'-'],
+    [0x12, b'[', b' ', b'<', STOP, NO_KEY, b'.', b'='],
+];
+
+// screen codes $40-$7f
+const GRAPHICS: [char; 64] = [
+    '─', '♠', '│', '─', '─', '─', '─', '│', '│', '╮', '╰', '╯', '└', '╲', '╱', '┌',
+    '┐', '●', '─', '♥', '│', '╭', '╳', '○', '♣', '│', '♦', '┼', '▒', '│', 'π', '◥',
+    ' ', '▌', '▄', '▔', '▁', '▏', '▒', '▕', '▒', '◤', '▕', '├', '▗', '└', '┐', '▂',
+    '┌', '┴', '┬', '┤', '▎', '▍', '▐', '▔', '▔', '▃', '◢', '▖', '▝', '┘', '▘', '▚',
+];
+
+pub fn decode(value: u8) -> (char, bool) {
+    let code = value & 0x7f;
+    let c = match code {
+        0x00..=0x1d => (code + 0x40) as char,
+        0x1e => '↑',
+        0x1f => '←',
+        0x20..=0x3f => code as char,
+        _ => GRAPHICS[(code - 0x40) as usize],
+    };
+    (c, value >= 0x80)
+}
+
+// the 40x25 screen, cleared to spaces
+pub fn screen(clock_hz: f64) -> TextScreen {
+    let layout = Layout { columns: 40, rows: 25, row_offset: rows_in_order, decode };
+    TextScreen::new(layout, SCREEN_SIZE, 0x20, clock_hz)
+}
+
+// where a host key is on the matrix
+fn key_position(key: u8) -> Option<(usize, usize)> {
+    let key = match key {
+        b'\n' | b'\r' => RETURN,
+        0x08 | 0x7f => DEL,
+        b'^' => 0x5e,
+        b'_' => 0x5f,
+        SHIFT | NO_KEY => return None,
+        key => key.to_ascii_uppercase(),
+    };
+    (0..KEY_MATRIX.len())
+        .flat_map(|row| (0..8).map(move |col| (row, col)))
+        .find(|&(row, col)| KEY_MATRIX[row][col] == key)
+}
+
+pub struct PetIo {
+    pia: Pia,
+    frame_cycles: u64,
+    frame_wait: u64,
+    pressed: Option<(usize, usize)>,
+    frames_left: u32,           // frames the key stays down, or until the next
+    inputs: Inputs,
+}
+
+impl PetIo {
+    pub fn new(inputs: Inputs, clock_hz: f64) -> PetIo {
+        inputs.claim_keyboard();
+        let frame_cycles = (clock_hz / RETRACE_HZ) as u64;
+        PetIo { pia: Pia::new(), frame_cycles, frame_wait: frame_cycles, pressed: None, frames_left: 0, inputs }
+    }
+
+    fn frame(&mut self) {
+        self.pia.set_cb1(false);
+        self.pia.set_cb1(true);
+
+        if self.frames_left > 0 {
+            self.frames_left -= 1;
+        }
+        if self.frames_left > 0 {
+            return;
+        }
+        if self.pressed.take().is_some() {
+            self.frames_left = GAP_FRAMES;
+        } else if let Some(key) = self.inputs.poll_key() {
+            self.pressed = key_position(key);
+            self.frames_left = HOLD_FRAMES;
+        }
+    }
+
+    // the columns pressed in the selected row
+    fn columns(&self) -> u8 {
+        match self.pressed {
+            Some((row, col)) if row == (self.pia.port_a() & 0x0f) as usize => !(1 << col),
+            _ => 0xff,
+        }
+    }
+}
+
+impl Device for PetIo {
+    fn read(&mut self, offset: u16) -> u8 {
+        if offset == PORT_B {
+            self.pia.pins_b = self.columns();
+        }
+        self.pia.read(offset)
+    }
+
+    fn write(&mut self, offset: u16, value: u8) {
+        self.pia.write(offset, value);
+    }
+
+    fn tick(&mut self, cycles: u32) {
+        let mut cycles = cycles as u64;
+        while cycles >= self.frame_wait {
+            cycles -= self.frame_wait;
+            self.frame_wait = self.frame_cycles;
+            self.frame();
+        }
+        self.frame_wait -= cycles;
+    }
+
+    fn irq(&self) -> bool {
+        self.pia.irq()
+    }
+
+    fn raises_irqs(&self) -> bool {
+        true
+    }
+
+    // a key being typed is not saved
+    fn save_state(&self, out: &mut StateWriter) {
+        self.pia.save_state(out);
+        out.u64(self.frame_wait);
+    }
+
+    fn load_state(&mut self, input: &mut StateReader) -> Result<(), String> {
+        self.pia.load_state(input)?;
+        self.frame_wait = input.u64()?;
+        self.pressed = None;
+        self.frames_left = 0;
+        Ok(())
+    }
+}
@@ -0,0 +1,223 @@
+
+// 6520/6821 peripheral interface adapter
+//
+//   0   ORA, or DDRA when CRA bit 2 is clear
+//   1   CRA
+//   2   ORB, or DDRB when CRB bit 2 is clear
+//   3   CRB
+//
+// control register bits:
+//   0     C1 interrupt enable
+//   1     C1 active edge (set: rising)
+//   2     select the output register rather than the DDR
+//   3-5   C2 mode: bit 5 clear makes C2 an input with bit 3 enabling
+//         its interrupt and bit 4 choosing the edge; bit 5 set makes it
+//         an output, set to bit 3 when bit 4 is set, otherwise a
+//         handshake (pulse when bit 3 is set) on port accesses
+//   6     C2 interrupt flag
+//   7     C1 interrupt flag
+//
+// reading a port clears its interrupt flags. both halves drive the one
+// irq output. machines wire their peripherals up by wrapping a Pia and
+// calling set_ca1 and friends, and by setting pins_a/pins_b.
+
+use crate::memory::{Device, StateReader, StateWriter};
+
+pub const PIA_SIZE: u16 = 4;
+
+const CR_C1_IRQ_ON: u8 = 0x01;
+const CR_C1_RISING: u8 = 0x02;
+const CR_OUTPUT_REGISTER: u8 = 0x04;
+const CR_C2_BIT3: u8 = 0x08;
+const CR_C2_BIT4: u8 = 0x10;
+const CR_C2_OUTPUT: u8 = 0x20;
+const CR_C2_FLAG: u8 = 0x40;
+const CR_C1_FLAG: u8 = 0x80;
+
+// one half of the chip: a port and its two control lines
+struct Side {
+    output: u8,
+    ddr: u8,
+    control: u8,
+    c1: bool,               // input level on C1
+    c2: bool,               // level on C2, as input or output
+}
+
+impl Side {
+    fn new() -> Side {
+        Side { output: 0, ddr: 0, control: 0, c1: true, c2: true }
+    }
+
+    fn irq(&self) -> bool {
+        let c1 = self.control & CR_C1_FLAG != 0 && self.control & CR_C1_IRQ_ON != 0;
+        let c2 = self.control & CR_C2_FLAG != 0 && self.control & CR_C2_BIT3 != 0
+            && self.control & CR_C2_OUTPUT == 0;
+        c1 || c2
+    }
+
+    fn set_c1(&mut self, level: bool) {
+        let rising = self.control & CR_C1_RISING != 0;
+        if level != self.c1 && level == rising {
+            self.control |= CR_C1_FLAG;
+            // a handshake output goes back up on the C1 edge
+            if self.control & (CR_C2_OUTPUT | CR_C2_BIT4 | CR_C2_BIT3) == CR_C2_OUTPUT {
+                self.c2 = true;
+            }
+        }
+        self.c1 = level;
+    }
+
+    fn set_c2(&mut self, level: bool) {
+        if self.control & CR_C2_OUTPUT != 0 {
+            return;
+        }
+        let rising = self.control & CR_C2_BIT4 != 0;
+        if level != self.c2 && level == rising {
+            self.control |= CR_C2_FLAG;
+        }
+        self.c2 = level;
+    }
+
+    // a port access starts a handshake or pulse on C2
+    fn strobe_c2(&mut self) {
+        if self.control & (CR_C2_OUTPUT | CR_C2_BIT4) == CR_C2_OUTPUT {
+            // a pulse is over by the next access, so it ends high
+            self.c2 = self.control & CR_C2_BIT3 != 0;
+        }
+    }
+
+    fn write_control(&mut self, value: u8) {
+        // the flags are read only
+        self.control = (self.control & (CR_C1_FLAG | CR_C2_FLAG)) | (value & 0x3f);
+        if value & (CR_C2_OUTPUT | CR_C2_BIT4) == CR_C2_OUTPUT | CR_C2_BIT4 {
+            self.c2 = value & CR_C2_BIT3 != 0;
+        }
+    }
+
+    fn levels(&self, pins: u8) -> u8 {
+        (self.output & self.ddr) | (pins & !self.ddr)
+    }
+
+    fn save_state(&self, out: &mut StateWriter) {
+        out.u8(self.output);
+        out.u8(self.ddr);
+        out.u8(self.control);
+        out.bool(self.c1);
+        out.bool(self.c2);
+    }
+
+    fn load_state(&mut self, input: &mut StateReader) -> Result<(), String> {
+        self.output = input.u8()?;
+        self.ddr = input.u8()?;
+        self.control = input.u8()?;
+        self.c1 = input.bool()?;
+        self.c2 = input.bool()?;
+        Ok(())
+    }
+}
+
+pub struct Pia {
+    pub pins_a: u8,         // levels on port A pins not driven as outputs
+    pub pins_b: u8,         // levels on port B pins not driven as outputs
+    a: Side,
+    b: Side,
+}
+
+impl Pia {
+    pub fn new() -> Pia {
+        Pia { pins_a: 0xff, pins_b: 0xff, a: Side::new(), b: Side::new() }
+    }
+
+    pub fn port_a(&self) -> u8 {
+        self.a.levels(self.pins_a)
+    }
+
+    pub fn port_b(&self) -> u8 {
+        self.b.levels(self.pins_b)
+    }
+
+    // the control lines are driven by the machines that wrap a Pia
+    pub fn set_ca1(&mut self, level: bool) {
+        self.a.set_c1(level);
+    }
+
+    #[allow(dead_code)]
+    pub fn set_ca2(&mut self, level: bool) {
+        self.a.set_c2(level);
+    }
+
+    pub fn set_cb1(&mut self, level: bool) {
+        self.b.set_c1(level);
+    }
+
+    #[allow(dead_code)]
+    pub fn set_cb2(&mut self, level: bool) {
+        self.b.set_c2(level);
+    }
+
+    #[allow(dead_code)]
+    pub fn ca2(&self) -> bool {
+        self.a.c2
+    }
+
+    #[allow(dead_code)]
+    pub fn cb2(&self) -> bool {
+        self.b.c2
+    }
+}
+
+impl Device for Pia {
+    fn read(&mut self, offset: u16) -> u8 {
+        match offset & 0x03 {
+            0 if self.a.control & CR_OUTPUT_REGISTER != 0 => {
+                self.a.control &= !(CR_C1_FLAG | CR_C2_FLAG);
+                self.a.strobe_c2();
+                self.port_a()
+            }
+            0 => self.a.ddr,
+            1 => self.a.control,
+            2 if self.b.control & CR_OUTPUT_REGISTER != 0 => {
+                self.b.control &= !(CR_C1_FLAG | CR_C2_FLAG);
+                self.port_b()
+            }
+            2 => self.b.ddr,
+            _ => self.b.control,
+        }
+    }
+
+    fn write(&mut self, offset: u16, value: u8) {
+        match offset & 0x03 {
+            0 if self.a.control & CR_OUTPUT_REGISTER != 0 => self.a.output = value,
+            0 => self.a.ddr = value,
+            1 => self.a.write_control(value),
+            2 if self.b.control & CR_OUTPUT_REGISTER != 0 => {
+                self.b.output = value;
+                self.b.strobe_c2();
+            }
+            2 => self.b.ddr = value,
+            _ => self.b.write_control(value),
+        }
+    }
+
+    fn irq(&self) -> bool {
+        self.a.irq() || self.b.irq()
+    }
+
+    fn raises_irqs(&self) -> bool {
+        true
+    }
+
+    fn save_state(&self, out: &mut StateWriter) {
+        out.u8(self.pins_a);
+        out.u8(self.pins_b);
+        self.a.save_state(out);
+        self.b.save_state(out);
+    }
+
+    fn load_state(&mut self, input: &mut StateReader) -> Result<(), String> {
+        self.pins_a = input.u8()?;
+        self.pins_b = input.u8()?;
+        self.a.load_state(input)?;
+        self.b.load_state(input)
+    }
+}
@@ -0,0 +1,173 @@
+// devices loaded from shared libraries
+//
+//   [[device]]
+//   type = "plugin"
+//   base = 0xd000
+//   file = "./librtc.so"
+//   format = "bcd"            # other keys are passed to the plugin
+//
+// a plugin exports e6502r_device_v1, a C function that returns the
+// table of functions below; plugin/e6502r_plugin.h declares it for C
+// and the e6502r-plugin crate writes it for a Rust Device. create takes
+// the options as strings, numbers and booleans written out, and returns
+// the device's state, which every other function is handed, or null
+// with a message in the buffer it was given. the table's abi_version
+// is checked, so a plugin built against another version of the table
+// is refused rather than called wrongly. tick, irq and nmi may be null;
+// raises_irqs says whether irq or nmi can be true.
+//
+// the library stays loaded until e6502r exits. only unix, where dlopen
+// is, can load plugins.
+
+use std::ffi::{c_char, c_void, CStr, CString};
+
+use crate::memory::Device;
+use crate::toml::{Table, Value};
+
+pub const PLUGIN_ABI_VERSION: u32 = 1;
+
+const ERROR_LEN: usize = 256;
+
+#[repr(C)]
+struct DeviceV1 {
+    abi_version: u32,
+    create: unsafe extern "C" fn(keys: *const *const c_char, values: *const *const c_char, count: usize,
+        clock_hz: f64, error: *mut c_char, error_len: usize) -> *mut c_void,
+    size: unsafe extern "C" fn(state: *mut c_void) -> u16,
+    read: unsafe extern "C" fn(state: *mut c_void, offset: u16) -> u8,
+    write: unsafe extern "C" fn(state: *mut c_void, offset: u16, value: u8),
+    tick: Option<unsafe extern "C" fn(state: *mut c_void, cycles: u32)>,
+    irq: Option<unsafe extern "C" fn(state: *mut c_void) -> bool>,
+    nmi: Option<unsafe extern "C" fn(state: *mut c_void) -> bool>,
+    raises_irqs: unsafe extern "C" fn(state: *mut c_void) -> bool,
+    destroy: unsafe extern "C" fn(state: *mut c_void),
+}
+
+pub struct PluginDevice {
+    table: &'static DeviceV1,
+    state: *mut c_void,
+}
+
+impl PluginDevice {
+    // load the library at path and make its device; returns it and the
+    // number of addresses it answers
+    pub fn load(path: &str, options: &Table, clock_hz: f64) -> Result<(PluginDevice, u16), String> {
+        let table = open(path)?;
+        if table.abi_version != PLUGIN_ABI_VERSION {
+            return Err(format!("{}: plugin abi version {}, but e6502r takes {}", path, table.abi_version,
+                PLUGIN_ABI_VERSION));
+        }
+
+        let mut keys = Vec::new();
+        let mut values = Vec::new();
+        for (key, value) in options {
+            let text = match value {
+                Value::String(text) => text.clone(),
+                Value::Integer(n) => n.to_string(),
+                Value::Float(x) => x.to_string(),
+                Value::Boolean(flag) => flag.to_string(),
+                _ => return Err(format!("{}: option '{}' should be a string, number or boolean", path, key)),
+            };
+            let c_string = |text: &str| CString::new(text).map_err(|_| format!("{}: option '{}' has a nul in it", path, key));
+            keys.push(c_string(key)?);
+            values.push(c_string(&text)?);
+        }
+        let key_pointers: Vec<*const c_char> = keys.iter().map(|key| key.as_ptr()).collect();
+        let value_pointers: Vec<*const c_char> = values.iter().map(|value| value.as_ptr()).collect();
+
+        let mut error = [0 as c_char; ERROR_LEN];
+        // SAFETY: the pointers are to count strings that outlive the call,
+        // and error to ERROR_LEN bytes
+        let state = unsafe {
+            (table.create)(key_pointers.as_ptr(), value_pointers.as_ptr(), keys.len(), clock_hz,
+                error.as_mut_ptr(), ERROR_LEN)
+        };
+        if state.is_null() {
+            error[ERROR_LEN - 1] = 0;
+            // SAFETY: the buffer was zeroed and ends in a nul
+            let message = unsafe { CStr::from_ptr(error.as_ptr()) }.to_string_lossy();
+            return Err(format!("{}: {}", path, if message.is_empty() { "the plugin made no device" } else { &message }));
+        }
+        let device = PluginDevice { table, state };
+        // SAFETY: state came from this table's create
+        let size = unsafe { (table.size)(state) };
+        Ok((device, size))
+    }
+}
+
+impl Drop for PluginDevice {
+    fn drop(&mut self) {
+        // SAFETY: state came from this table's create and isn't used again
+        unsafe { (self.table.destroy)(self.state) }
+    }
+}
+
+// SAFETY for the calls below: state came from this table's create and
+// hasn't been destroyed
+impl Device for PluginDevice {
+    fn read(&mut self, offset: u16) -> u8 {
+        unsafe { (self.table.read)(self.state, offset) }
+    }
+
+    fn write(&mut self, offset: u16, value: u8) {
+        unsafe { (self.table.write)(self.state, offset, value) }
+    }
+
+    fn tick(&mut self, cycles: u32) {
+        if let Some(tick) = self.table.tick {
+            unsafe { tick(self.state, cycles) }
+        }
+    }
+
+    fn irq(&self) -> bool {
+        self.table.irq.is_some_and(|irq| unsafe { irq(self.state) })
+    }
+
+    fn nmi(&self) -> bool {
+        self.table.nmi.is_some_and(|nmi| unsafe { nmi(self.state) })
+    }
+
+    fn raises_irqs(&self) -> bool {
+        unsafe { (self.table.raises_irqs)(self.state) }
+    }
+}
+
+#[cfg(unix)]
+fn open(path: &str) -> Result<&'static DeviceV1, String> {
+    extern "C" {
+        fn dlopen(filename: *const c_char, flag: i32) -> *mut c_void;
+        fn dlsym(handle: *mut c_void, symbol: *const c_char) -> *mut c_void;
+        fn dlerror() -> *const c_char;
+    }
+    const RTLD_NOW: i32 = 2;
+
+    let last_error = || {
+        // SAFETY: dlerror returns null or a message that lasts until the next dl call
+        let message = unsafe { dlerror() };
+        if message.is_null() {
+            String::from("unknown error")
+        } else {
+            unsafe { CStr::from_ptr(message) }.to_string_lossy().into_owned()
+        }
+    };
+    let name = CString::new(path).map_err(|_| format!("{}: bad file name", path))?;
+    // SAFETY: the strings are nul terminated; a library's initialisers
+    // run when it is opened, which is what loading a plugin means
+    unsafe {
+        let library = dlopen(name.as_ptr(), RTLD_NOW);
+        if library.is_null() {
+            return Err(last_error());
+        }
+        let symbol = dlsym(library, c"e6502r_device_v1".as_ptr());
+        if symbol.is_null() {
+            return Err(format!("{}: not an e6502r plugin: {}", path, last_error()));
+        }
+        let entry: unsafe extern "C" fn() -> *const DeviceV1 = std::mem::transmute(symbol);
+        entry().as_ref().ok_or(format!("{}: the plugin gave no device table", path))
+    }
+}
+
+#[cfg(not(unix))]
+fn open(path: &str) -> Result<&'static DeviceV1, String> {
+    Err(format!("{}: plugins can only be loaded on unix", path))
+}
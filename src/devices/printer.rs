@@ -0,0 +1,97 @@
+// printer port: bytes written to it are printed to the end of a host file
+//
+// with translate = "ascii" bit 7 is dropped and a carriage return ends
+// the line; with "petscii" the output of Commodore machines is turned
+// into ascii, swapping the case of letters and dropping the control
+// codes. otherwise the bytes are written as they come. the printer is
+// never busy, so reading the port always gives 0.
+
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+
+use crate::memory::{Device, StateReader, StateWriter};
+
+pub const PRINTER_SIZE: u16 = 1;
+
+#[derive(Clone, Copy)]
+pub enum Translation {
+    None,
+    Ascii,
+    Petscii,
+}
+
+impl Translation {
+    pub fn parse(text: &str) -> Result<Translation, String> {
+        match text {
+            "none" => Ok(Translation::None),
+            "ascii" => Ok(Translation::Ascii),
+            "petscii" => Ok(Translation::Petscii),
+            _ => Err(format!("unknown translation '{}', expected none, ascii or petscii", text)),
+        }
+    }
+}
+
+pub struct Printer {
+    file: File,
+    path: String,
+    translation: Translation,
+    last: u8,
+    failed: bool,
+}
+
+impl Printer {
+    pub fn new(path: &str, translation: Translation) -> Result<Printer, String> {
+        let file = OpenOptions::new().create(true).append(true).open(path)
+            .map_err(|e| format!("can't open {}: {}", path, e))?;
+        Ok(Printer { file, path: path.to_string(), translation, last: 0, failed: false })
+    }
+
+    // what a byte prints as, if anything
+    fn translate(&self, value: u8) -> Option<u8> {
+        match self.translation {
+            Translation::None => Some(value),
+            Translation::Ascii => match value & 0x7f {
+                b'\r' => Some(b'\n'),
+                // a line feed after a carriage return is already done
+                b'\n' if self.last & 0x7f == b'\r' => None,
+                c => Some(c),
+            },
+            Translation::Petscii => match value {
+                0x0d => Some(b'\n'),
+                0x41..=0x5a => Some(value + 0x20),
+                0x61..=0x7a => Some(value - 0x20),
+                0xc1..=0xda => Some(value - 0x80),
+                0x20..=0x40 | 0x5b..=0x5f => Some(value),
+                _ => None,
+            },
+        }
+    }
+}
+
+impl Device for Printer {
+    fn read(&mut self, _offset: u16) -> u8 {
+        0
+    }
+
+    fn write(&mut self, _offset: u16, value: u8) {
+        let printed = self.translate(value);
+        self.last = value;
+        let Some(byte) = printed else { return };
+        if self.failed {
+            return;
+        }
+        if let Err(e) = self.file.write_all(&[byte]) {
+            eprintln!("e6502r: can't write {}: {}", self.path, e);
+            self.failed = true;
+        }
+    }
+
+    fn save_state(&self, out: &mut StateWriter) {
+        out.u8(self.last);
+    }
+
+    fn load_state(&mut self, input: &mut StateReader) -> Result<(), String> {
+        self.last = input.u8()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,65 @@
+// random number register
+//
+//   0   read: the next pseudo-random byte
+//       write: restart the sequence from a seed made from the byte
+//
+// the bytes come from xorshift64*, so the same seed always gives the
+// same sequence. without a seed option the device seeds itself from
+// the host clock and reports the seed, so a run can be repeated.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::memory::{Device, StateReader, StateWriter};
+
+pub const RANDOM_SIZE: u16 = 1;
+
+pub struct Random {
+    state: u64,
+}
+
+impl Random {
+    pub fn new(seed: u64) -> Random {
+        Random { state: scramble(seed) }
+    }
+
+    // a seed that differs from run to run, small enough to give back
+    // as the seed option
+    pub fn clock_seed() -> u64 {
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_nanos() as u64).unwrap_or(0);
+        nanos & i64::MAX as u64
+    }
+
+    pub fn next(&mut self) -> u8 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        (self.state.wrapping_mul(0x2545_f491_4f6c_dd1d) >> 56) as u8
+    }
+}
+
+// spread a seed over the state with splitmix64; xorshift can't start at 0
+fn scramble(seed: u64) -> u64 {
+    let mut z = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    (z ^ (z >> 31)).max(1)
+}
+
+impl Device for Random {
+    fn read(&mut self, _offset: u16) -> u8 {
+        self.next()
+    }
+
+    fn write(&mut self, _offset: u16, value: u8) {
+        self.state = scramble(value as u64);
+    }
+
+    fn save_state(&self, out: &mut StateWriter) {
+        out.u64(self.state);
+    }
+
+    fn load_state(&mut self, input: &mut StateReader) -> Result<(), String> {
+        self.state = input.u64()?.max(1);
+        Ok(())
+    }
+}
@@ -0,0 +1,204 @@
+// memory-mapped text screens, drawn on the host terminal
+//
+// a screen stands in for the ram the machine's video reads. the program
+// reads and writes it like ram; whenever it has changed, the screen is
+// redrawn through crossterm at most REFRESH_HZ times a second, of
+// emulated time and of the host's, so a machine in turbo doesn't flood
+// the terminal. each machine supplies where its rows start and how its
+// character codes look. the browser build has no terminal, and its
+// screens are only seen as pictures.
+//
+// for a screenshot the characters are drawn with the font, each cell of
+// it SCALE pixels square, light on dark.
+
+use crate::font;
+use crate::memory::{Device, Picture, StateReader, StateWriter};
+
+const REFRESH_HZ: f64 = 30.0;
+const SCALE: usize = 2;
+const INK: u32 = 0xd8d8d8;
+const PAPER: u32 = 0x101010;
+
+// how a machine lays out its screen memory
+pub struct Layout {
+    pub columns: usize,
+    pub rows: usize,
+    // where a row starts, given the row and the number of columns
+    pub row_offset: fn(usize, usize) -> usize,
+    // the character shown for a byte, and whether it is shown inverse
+    pub decode: fn(u8) -> (char, bool),
+}
+
+// rows one after another, as most machines keep them
+pub fn rows_in_order(row: usize, columns: usize) -> usize {
+    row * columns
+}
+
+// plain ascii, with bit 7 set shown inverse
+pub fn ascii(value: u8) -> (char, bool) {
+    let c = match value & 0x7f {
+        c @ 0x20..=0x7e => c as char,
+        _ => ' ',
+    };
+    (c, value >= 0x80)
+}
+
+pub struct TextScreen {
+    layout: Layout,
+    ram: Vec<u8>,
+    dirty: bool,
+    terminal: terminal::Terminal,
+    refresh_cycles: u64,        // cycles between redraws
+    wait: u64,                  // cycles until the next redraw is allowed
+}
+
+impl TextScreen {
+    // size bytes of screen ram, starting out filled with blank
+    pub fn new(layout: Layout, size: u16, blank: u8, clock_hz: f64) -> TextScreen {
+        TextScreen {
+            layout,
+            ram: vec![blank; size as usize],
+            dirty: false,
+            terminal: terminal::Terminal::new(),
+            refresh_cycles: (clock_hz / REFRESH_HZ) as u64,
+            wait: 0,
+        }
+    }
+}
+
+impl Device for TextScreen {
+    fn read(&mut self, offset: u16) -> u8 {
+        self.ram[offset as usize]
+    }
+
+    fn picture(&self) -> Option<Picture> {
+        let cell = font::CELL * SCALE;
+        let (width, height) = (self.layout.columns * cell, self.layout.rows * cell);
+        let mut pixels = vec![PAPER; width * height];
+        for row in 0..self.layout.rows {
+            let start = (self.layout.row_offset)(row, self.layout.columns);
+            for column in 0..self.layout.columns {
+                let (c, inverse) = (self.layout.decode)(self.ram[start + column]);
+                for y in 0..cell {
+                    for x in 0..cell {
+                        let on = font::lit(c, x / SCALE, y / SCALE) != inverse;
+                        pixels[(row * cell + y) * width + column * cell + x] = if on { INK } else { PAPER };
+                    }
+                }
+            }
+        }
+        Some(Picture { width, height, pixels })
+    }
+
+    fn write(&mut self, offset: u16, value: u8) {
+        if self.ram[offset as usize] != value {
+            self.ram[offset as usize] = value;
+            self.dirty = true;
+        }
+    }
+
+    fn tick(&mut self, cycles: u32) {
+        self.wait = self.wait.saturating_sub(cycles as u64);
+        if self.dirty && self.wait == 0 {
+            if self.terminal.draw(&self.layout, &self.ram) {
+                self.dirty = false;
+            }
+            self.wait = self.refresh_cycles;
+        }
+    }
+
+    fn save_state(&self, out: &mut StateWriter) {
+        out.bytes(&self.ram);
+    }
+
+    fn load_state(&mut self, input: &mut StateReader) -> Result<(), String> {
+        input.bytes_into(&mut self.ram)?;
+        self.dirty = true;
+        Ok(())
+    }
+}
+
+// the host terminal a screen is drawn on
+#[cfg(not(target_arch = "wasm32"))]
+mod terminal {
+    use std::io;
+    use std::io::Write;
+    use std::time::{Duration, Instant};
+
+    use crossterm::cursor::MoveTo;
+    use crossterm::queue;
+    use crossterm::style::{Attribute, Print, SetAttribute};
+    use crossterm::terminal::{Clear, ClearType};
+
+    use super::{Layout, REFRESH_HZ};
+
+    pub struct Terminal {
+        out: io::Stdout,
+        drawn_at: Option<Instant>,      // host time of the last redraw; the first clears the terminal
+    }
+
+    impl Terminal {
+        pub fn new() -> Terminal {
+            Terminal { out: io::stdout(), drawn_at: None }
+        }
+
+        // draw the screen unless the last time was too recent
+        // returns true if it was drawn
+        pub fn draw(&mut self, layout: &Layout, ram: &[u8]) -> bool {
+            let period = Duration::from_secs_f64(1.0 / REFRESH_HZ);
+            if self.drawn_at.is_some_and(|drawn_at| drawn_at.elapsed() < period) {
+                return false;
+            }
+            let first = self.drawn_at.is_none();
+            self.drawn_at = Some(Instant::now());
+            let mut out = self.out.lock();
+            let _result = queue_rows(&mut out, layout, ram, first).and_then(|_| out.flush());
+            true
+        }
+    }
+
+    // every row from the top left, with the cursor left below them
+    fn queue_rows(out: &mut impl Write, layout: &Layout, ram: &[u8], clear: bool) -> io::Result<()> {
+        if clear {
+            queue!(out, Clear(ClearType::All))?;
+        }
+        for row in 0..layout.rows {
+            queue!(out, MoveTo(0, row as u16))?;
+            let start = (layout.row_offset)(row, layout.columns);
+            let mut run = String::new();
+            let mut inverse = false;
+            for &value in &ram[start..start + layout.columns] {
+                let (c, is_inverse) = (layout.decode)(value);
+                if is_inverse != inverse {
+                    let attribute = if is_inverse { Attribute::Reverse } else { Attribute::NoReverse };
+                    queue!(out, Print(&run), SetAttribute(attribute))?;
+                    run.clear();
+                    inverse = is_inverse;
+                }
+                run.push(c);
+            }
+            queue!(out, Print(&run))?;
+            if inverse {
+                queue!(out, SetAttribute(Attribute::NoReverse))?;
+            }
+        }
+        queue!(out, MoveTo(0, layout.rows as u16))
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+mod terminal {
+    use super::Layout;
+
+    pub struct Terminal;
+
+    impl Terminal {
+        pub fn new() -> Terminal {
+            Terminal
+        }
+
+        pub fn draw(&mut self, _layout: &Layout, _ram: &[u8]) -> bool {
+            true
+        }
+    }
+}
@@ -0,0 +1,143 @@
+// a device run by a rhai script, built with the rhai feature
+//
+//   [[device]]
+//   type = "script"
+//   base = 0xd000
+//   size = 4
+//   file = "uart.rhai"
+//   baud = 9600               # other keys are fields of this
+//
+// the script defines any of these functions, leaving out the ones the
+// device doesn't need:
+//
+//   fn init()                 once, when the device is made
+//   fn read(offset)           returns the byte at base+offset
+//   fn write(offset, value)
+//   fn tick(cycles)           the cpu has run this many more cycles
+//   fn irq()                  returns true while the irq line is held
+//
+// the device's state is kept in this, an object map that starts out
+// with the device's other options in it, so handlers say this.count
+// for a count they keep. top level statements run before init. if a
+// handler fails, the error is printed and the device stops calling it.
+// the script's state isn't part of a saved state.
+
+use rhai::{CallFnOptions, Dynamic, Engine, FuncArgs, Map, Scope, AST};
+
+use crate::memory::Device;
+use crate::toml::{Table, Value};
+
+pub struct ScriptDevice {
+    engine: Engine,
+    ast: AST,
+    scope: Scope<'static>,
+    this: Dynamic,
+    base: u16,
+    handlers: [bool; 4],        // read, write, tick and irq are defined
+    irq_line: bool,
+}
+
+const READ: usize = 0;
+const WRITE: usize = 1;
+const TICK: usize = 2;
+const IRQ: usize = 3;
+const HANDLERS: [(&str, usize); 4] = [("read", 1), ("write", 2), ("tick", 1), ("irq", 0)];
+
+impl ScriptDevice {
+    pub fn load(path: &str, base: u16, options: &Table) -> Result<ScriptDevice, String> {
+        let engine = Engine::new();
+        let ast = engine.compile_file(path.into()).map_err(|e| format!("{}: {}", path, e))?;
+        let defined = |name: &str, params: usize| {
+            ast.iter_functions().any(|function| function.name == name && function.params.len() == params)
+        };
+        let handlers = HANDLERS.map(|(name, params)| defined(name, params));
+        let has_init = defined("init", 0);
+
+        let mut fields = Map::new();
+        for (key, value) in options {
+            fields.insert(key.as_str().into(), to_dynamic(value));
+        }
+        let mut device = ScriptDevice {
+            engine,
+            ast,
+            scope: Scope::new(),
+            this: Dynamic::from_map(fields),
+            base,
+            handlers,
+            irq_line: false,
+        };
+        device.engine.run_ast_with_scope(&mut device.scope, &device.ast).map_err(|e| format!("{}: {}", path, e))?;
+        if has_init {
+            device.call::<()>("init", ()).map_err(|e| format!("{}: {}", path, e))?;
+        }
+        Ok(device)
+    }
+
+    fn call<T: Clone + Send + Sync + 'static>(&mut self, name: &str, args: impl FuncArgs) -> Result<T, String> {
+        let options = CallFnOptions::new().eval_ast(false).bind_this_ptr(&mut self.this);
+        self.engine.call_fn_with_options(options, &mut self.scope, &self.ast, name, args).map_err(|e| e.to_string())
+    }
+
+    // call a handler the script defines, giving up on it if it fails
+    fn handle<T: Clone + Send + Sync + 'static>(&mut self, handler: usize, args: impl FuncArgs) -> Option<T> {
+        if !self.handlers[handler] {
+            return None;
+        }
+        let name = HANDLERS[handler].0;
+        match self.call(name, args) {
+            Ok(value) => {
+                if self.handlers[IRQ] && handler != IRQ {
+                    self.irq_line = self.handle(IRQ, ()).unwrap_or(false);
+                }
+                Some(value)
+            }
+            Err(message) => {
+                eprintln!("e6502r: script device at ${:04x}: {}: {}", self.base, name, message);
+                self.handlers[handler] = false;
+                None
+            }
+        }
+    }
+}
+
+impl Device for ScriptDevice {
+    fn read(&mut self, offset: u16) -> u8 {
+        self.handle::<i64>(READ, (offset as i64,)).unwrap_or(0) as u8
+    }
+
+    fn write(&mut self, offset: u16, value: u8) {
+        self.handle::<()>(WRITE, (offset as i64, value as i64));
+    }
+
+    fn tick(&mut self, cycles: u32) {
+        self.handle::<()>(TICK, (cycles as i64,));
+    }
+
+    fn irq(&self) -> bool {
+        self.irq_line
+    }
+
+    fn raises_irqs(&self) -> bool {
+        self.handlers[IRQ]
+    }
+}
+
+// options from --device are all strings, so numbers and true or false
+// in strings are taken as such, as other devices take them
+fn to_dynamic(value: &Value) -> Dynamic {
+    match value {
+        Value::String(text) => match (text.parse::<i64>(), text.as_str()) {
+            (Ok(n), _) => n.into(),
+            (_, "true") => true.into(),
+            (_, "false") => false.into(),
+            _ => text.clone().into(),
+        },
+        Value::Integer(n) => (*n).into(),
+        Value::Float(x) => (*x).into(),
+        Value::Boolean(flag) => (*flag).into(),
+        Value::Array(values) => values.iter().map(to_dynamic).collect::<Vec<Dynamic>>().into(),
+        Value::Table(table) => {
+            Dynamic::from_map(table.iter().map(|(key, value)| (key.as_str().into(), to_dynamic(value))).collect())
+        }
+    }
+}
@@ -0,0 +1,322 @@
+// sd card in spi mode, bit-banged through a VIA's port pins and backed
+// by an image file on the host
+//
+// the card answers the commands a simple driver needs:
+//
+//   CMD0    go idle
+//   CMD8    send interface condition: echoes the check pattern
+//   CMD16   set block length: only 512 is accepted
+//   CMD17   read a block
+//   CMD24   write a block
+//   CMD55   the next command is an application command
+//   ACMD41  start initialization: the card is ready at once
+//   CMD58   read the ocr: a high capacity card at 3.3v
+//
+// it is a high capacity card, so block addresses count 512 byte blocks
+// rather than bytes. crcs are ignored. reads and writes before ACMD41
+// has taken the card out of idle are refused as illegal commands.
+// writes go straight through to the image file.
+//
+// SdPort wires the card to a VIA: the program drives CS, SCK and MOSI
+// on any pins and reads MISO from another, in spi mode 0. the card
+// takes MOSI as SCK rises and moves MISO on to the next bit as SCK
+// falls. while CS is high the card lets go of MISO, which reads high.
+
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+
+use crate::devices::via::{Pin, Port, PortDevice, Via};
+use crate::memory::{StateReader, StateWriter};
+
+const BLOCK_SIZE: usize = 512;
+
+// r1 response bits
+const R1_IDLE: u8 = 0x01;
+const R1_ILLEGAL_COMMAND: u8 = 0x04;
+const R1_ADDRESS_ERROR: u8 = 0x20;
+const R1_PARAMETER_ERROR: u8 = 0x40;
+
+const START_BLOCK_TOKEN: u8 = 0xfe;
+const DATA_ACCEPTED: u8 = 0x05;
+const DATA_WRITE_ERROR: u8 = 0x0d;
+
+// ocr: powered up, high capacity, 3.2-3.4v
+const OCR: [u8; 4] = [0xc0, 0x30, 0x00, 0x00];
+
+// bytes of busy the card signals after taking a block
+const WRITE_BUSY_BYTES: usize = 8;
+
+// what the card does with the bytes it is sent
+enum Receiving {
+    Command,                // waiting for or collecting a command
+    WriteToken(u32),        // waiting for the data token of a write to this block
+    WriteData(u32),         // collecting the data of a write to this block
+}
+
+pub struct SdCard {
+    image: File,
+    blocks: u32,
+    idle: bool,
+    app_command: bool,      // CMD55 came before this command
+    command: Vec<u8>,
+    receiving: Receiving,
+    data: Vec<u8>,
+    sending: VecDeque<u8>,
+}
+
+impl SdCard {
+    pub fn open(path: &str) -> Result<SdCard, String> {
+        let image = OpenOptions::new().read(true).write(true).open(path)
+            .map_err(|e| format!("can't open sd card image {}: {}", path, e))?;
+        let len = image.metadata().map_err(|e| format!("can't read {}: {}", path, e))?.len();
+        Ok(SdCard {
+            image,
+            blocks: (len / BLOCK_SIZE as u64).min(u32::MAX as u64) as u32,
+            idle: true,
+            app_command: false,
+            command: Vec::new(),
+            receiving: Receiving::Command,
+            data: Vec::new(),
+            sending: VecDeque::new(),
+        })
+    }
+
+    // the image itself is not part of a saved state, only where the
+    // card is in a command
+    fn save_state(&self, out: &mut StateWriter) {
+        out.bool(self.idle);
+        out.bool(self.app_command);
+        out.bytes(&self.command);
+        let (kind, block) = match self.receiving {
+            Receiving::Command => (0, 0),
+            Receiving::WriteToken(block) => (1, block),
+            Receiving::WriteData(block) => (2, block),
+        };
+        out.u8(kind);
+        out.u32(block);
+        out.bytes(&self.data);
+        out.bytes(&self.sending.iter().copied().collect::<Vec<u8>>());
+    }
+
+    fn load_state(&mut self, input: &mut StateReader) -> Result<(), String> {
+        self.idle = input.bool()?;
+        self.app_command = input.bool()?;
+        self.command = input.bytes()?.to_vec();
+        let kind = input.u8()?;
+        let block = input.u32()?;
+        self.receiving = match kind {
+            0 => Receiving::Command,
+            1 => Receiving::WriteToken(block),
+            _ => Receiving::WriteData(block),
+        };
+        self.data = input.bytes()?.to_vec();
+        self.sending = input.bytes()?.iter().copied().collect();
+        Ok(())
+    }
+
+    // CS going high ends whatever the card was doing
+    fn deselect(&mut self) {
+        self.command.clear();
+        self.receiving = Receiving::Command;
+        self.sending.clear();
+    }
+
+    // take one byte from the host and return the next byte to send
+    fn exchange(&mut self, byte: u8) -> u8 {
+        match self.receiving {
+            Receiving::Command => self.receive_command(byte),
+            Receiving::WriteToken(block) => {
+                if byte == START_BLOCK_TOKEN {
+                    self.data.clear();
+                    self.receiving = Receiving::WriteData(block);
+                }
+            }
+            Receiving::WriteData(block) => {
+                self.data.push(byte);
+                // the block is followed by two bytes of crc
+                if self.data.len() == BLOCK_SIZE + 2 {
+                    self.receiving = Receiving::Command;
+                    let response = match self.write_block(block) {
+                        Ok(()) => DATA_ACCEPTED,
+                        Err(_) => DATA_WRITE_ERROR,
+                    };
+                    self.sending.push_back(response);
+                    self.sending.extend([0; WRITE_BUSY_BYTES]);
+                }
+            }
+        }
+        self.sending.pop_front().unwrap_or(0xff)
+    }
+
+    fn receive_command(&mut self, byte: u8) {
+        // a command starts with its top bits 01
+        if self.command.is_empty() && byte & 0xc0 != 0x40 {
+            return;
+        }
+        self.command.push(byte);
+        if self.command.len() < 6 {
+            return;
+        }
+        let index = self.command[0] & 0x3f;
+        let arg = u32::from_be_bytes([self.command[1], self.command[2], self.command[3], self.command[4]]);
+        self.command.clear();
+        let app_command = std::mem::take(&mut self.app_command);
+
+        // the response comes after a byte of 0xff
+        self.sending.clear();
+        self.sending.push_back(0xff);
+        match (app_command, index) {
+            (_, 0) => {
+                self.idle = true;
+                self.respond(0);
+            }
+            (_, 8) => {
+                self.respond(0);
+                self.sending.extend([0x00, 0x00, (arg >> 8) as u8 & 0x0f, arg as u8]);
+            }
+            (_, 16) if arg as usize == BLOCK_SIZE => self.respond(0),
+            (_, 16) => self.respond(R1_PARAMETER_ERROR),
+            (_, 17 | 24) if self.idle => self.respond(R1_ILLEGAL_COMMAND),
+            (_, 17 | 24) if arg >= self.blocks => self.respond(R1_ADDRESS_ERROR),
+            (_, 17) => self.read_block(arg),
+            (_, 24) => {
+                self.respond(0);
+                self.receiving = Receiving::WriteToken(arg);
+            }
+            (_, 55) => {
+                self.app_command = true;
+                self.respond(0);
+            }
+            (true, 41) => {
+                self.idle = false;
+                self.respond(0);
+            }
+            (_, 58) => {
+                self.respond(0);
+                self.sending.extend(OCR);
+            }
+            _ => self.respond(R1_ILLEGAL_COMMAND),
+        }
+    }
+
+    // an r1 response, with the idle bit as the card is now
+    fn respond(&mut self, r1: u8) {
+        self.sending.push_back(r1 | if self.idle { R1_IDLE } else { 0 });
+    }
+
+    fn read_block(&mut self, block: u32) {
+        let mut data = [0; BLOCK_SIZE];
+        let read = self.image.seek(SeekFrom::Start(block as u64 * BLOCK_SIZE as u64))
+            .and_then(|_| self.image.read_exact(&mut data));
+        if read.is_err() {
+            self.respond(R1_ADDRESS_ERROR);
+            return;
+        }
+        self.respond(0);
+        // the card takes a moment to find the block
+        self.sending.push_back(0xff);
+        self.sending.push_back(START_BLOCK_TOKEN);
+        self.sending.extend(data);
+        self.sending.extend([0xff, 0xff]);
+    }
+
+    fn write_block(&mut self, block: u32) -> std::io::Result<()> {
+        self.image.seek(SeekFrom::Start(block as u64 * BLOCK_SIZE as u64))?;
+        self.image.write_all(&self.data[..BLOCK_SIZE])?;
+        self.image.flush()
+    }
+}
+
+// which pins the card is on
+pub struct Wiring {
+    pub cs: Pin,
+    pub sck: Pin,
+    pub mosi: Pin,
+    pub miso: Pin,
+}
+
+impl Wiring {
+    // the low bits of port A, clear of the lcd's pins
+    pub fn standard() -> Wiring {
+        Wiring {
+            sck: Pin { port: Port::A, bit: 0 },
+            mosi: Pin { port: Port::A, bit: 1 },
+            cs: Pin { port: Port::A, bit: 2 },
+            miso: Pin { port: Port::A, bit: 3 },
+        }
+    }
+}
+
+// an sd card on a VIA's pins
+pub struct SdPort {
+    card: SdCard,
+    wiring: Wiring,
+    selected: bool,
+    sck: bool,
+    received: u8,
+    bits: u8,               // bits of the current byte taken so far
+    shifting_out: u8,       // its top bit is on MISO
+}
+
+impl SdPort {
+    pub fn new(card: SdCard, wiring: Wiring) -> SdPort {
+        SdPort { card, wiring, selected: false, sck: false, received: 0, bits: 0, shifting_out: 0xff }
+    }
+}
+
+impl PortDevice for SdPort {
+    fn update(&mut self, via: &mut Via) {
+        let selected = !self.wiring.cs.level(via);
+        let sck = self.wiring.sck.level(via);
+        if selected != self.selected {
+            self.selected = selected;
+            self.bits = 0;
+            self.shifting_out = 0xff;
+            if !selected {
+                self.card.deselect();
+            }
+        } else if selected && sck != self.sck {
+            if sck {
+                let mosi = self.wiring.mosi.level(via);
+                self.received = (self.received << 1) | mosi as u8;
+                self.bits += 1;
+            } else if self.bits == 8 {
+                self.bits = 0;
+                self.shifting_out = self.card.exchange(self.received);
+            } else {
+                self.shifting_out = (self.shifting_out << 1) | 1;
+            }
+        }
+        self.sck = sck;
+
+        let miso = self.wiring.miso;
+        let mask = 1 << miso.bit;
+        let pins = miso.port.pins(via);
+        if selected && self.shifting_out & 0x80 == 0 {
+            *pins &= !mask;
+        } else {
+            *pins |= mask;
+        }
+    }
+
+    fn save_state(&self, out: &mut StateWriter) {
+        self.card.save_state(out);
+        for value in [self.selected, self.sck] {
+            out.bool(value);
+        }
+        for value in [self.received, self.bits, self.shifting_out] {
+            out.u8(value);
+        }
+    }
+
+    fn load_state(&mut self, input: &mut StateReader) -> Result<(), String> {
+        self.card.load_state(input)?;
+        self.selected = input.bool()?;
+        self.sck = input.bool()?;
+        self.received = input.u8()?;
+        self.bits = input.u8()?;
+        self.shifting_out = input.u8()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,195 @@
+// the host side of an emulated serial line
+//
+//   stdio   bytes sent go to stdout; keys typed on the terminal are
+//           received, with enter sent as a carriage return
+//   tcp     listens for one client at a time; bytes sent while nobody
+//           is connected are dropped
+//
+// either can have its line endings changed on the way through, with
+// input_newline for those received and output_newline for those sent:
+// a CR, an LF or the two together are one newline, passed on as "cr",
+// "lf" or "crlf".
+
+use std::collections::VecDeque;
+use std::io;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+
+use crate::devices::acia::SerialPort;
+use crate::devices::write_terminal;
+use crate::replay::Inputs;
+
+pub struct StdioPort {
+    out: io::Stdout,
+    inputs: Inputs,
+}
+
+impl StdioPort {
+    pub fn new(inputs: Inputs) -> StdioPort {
+        // typed keys belong to the program while it runs
+        inputs.claim_keyboard();
+        StdioPort { out: io::stdout(), inputs }
+    }
+}
+
+impl SerialPort for StdioPort {
+    fn receive(&mut self) -> Option<u8> {
+        self.inputs.poll_key()
+    }
+
+    fn send(&mut self, byte: u8) {
+        let _result = write_terminal(&mut self.out, &[byte]);
+        let _result = self.out.flush();
+    }
+}
+
+pub struct TcpPort {
+    listener: TcpListener,
+    client: Option<TcpStream>,
+    received: VecDeque<u8>,
+    inputs: Inputs,
+}
+
+impl TcpPort {
+    // listen on addr, like 127.0.0.1:6551
+    pub fn listen(inputs: Inputs, addr: &str) -> Result<TcpPort, String> {
+        let listener = TcpListener::bind(addr).map_err(|e| format!("{}: {}", addr, e))?;
+        listener.set_nonblocking(true).map_err(|e| format!("{}: {}", addr, e))?;
+        Ok(TcpPort { listener, client: None, received: VecDeque::new(), inputs })
+    }
+
+    // pick up a new client and anything it has sent
+    fn poll(&mut self) {
+        if self.client.is_none() {
+            if let Ok((stream, _)) = self.listener.accept() {
+                if stream.set_nonblocking(true).is_ok() {
+                    let _result = stream.set_nodelay(true);
+                    self.client = Some(stream);
+                }
+            }
+        }
+        let Some(stream) = &mut self.client else { return };
+        let mut buf = [0u8; 256];
+        match stream.read(&mut buf) {
+            Ok(0) => self.client = None,
+            Ok(count) => self.received.extend(&buf[..count]),
+            Err(e) if e.kind() == ErrorKind::WouldBlock => {}
+            Err(_) => self.client = None,
+        }
+    }
+}
+
+impl SerialPort for TcpPort {
+    fn receive(&mut self) -> Option<u8> {
+        let inputs = self.inputs.clone();
+        inputs.serial(|| {
+            if self.received.is_empty() {
+                self.poll();
+            }
+            self.received.pop_front()
+        })
+    }
+
+    fn send(&mut self, byte: u8) {
+        self.poll();
+        if let Some(stream) = &mut self.client {
+            if stream.write_all(&[byte]).is_err() {
+                self.client = None;
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum Newline {
+    Cr,
+    Lf,
+    CrLf,
+}
+
+impl Newline {
+    pub fn parse(text: &str) -> Option<Newline> {
+        match text {
+            "cr" => Some(Newline::Cr),
+            "lf" => Some(Newline::Lf),
+            "crlf" => Some(Newline::CrLf),
+            _ => None,
+        }
+    }
+
+    fn bytes(self) -> &'static [u8] {
+        match self {
+            Newline::Cr => b"\r",
+            Newline::Lf => b"\n",
+            Newline::CrLf => b"\r\n",
+        }
+    }
+}
+
+// one direction's line endings, turned into one kind
+struct Translation {
+    newline: Newline,
+    after_cr: bool,         // an LF now finishes a CR LF already passed on
+}
+
+impl Translation {
+    fn new(newline: Newline) -> Translation {
+        Translation { newline, after_cr: false }
+    }
+
+    // add what byte becomes to out
+    fn translate(&mut self, byte: u8, out: &mut VecDeque<u8>) {
+        let after_cr = std::mem::replace(&mut self.after_cr, byte == b'\r');
+        match byte {
+            b'\n' if after_cr => {}
+            b'\r' | b'\n' => out.extend(self.newline.bytes()),
+            _ => out.push_back(byte),
+        }
+    }
+}
+
+// a port with its line endings translated; a direction without a
+// translation passes them on as they are
+pub struct Newlines {
+    port: Box<dyn SerialPort>,
+    input: Option<Translation>,
+    output: Option<Translation>,
+    received: VecDeque<u8>,
+    sent: VecDeque<u8>,
+}
+
+impl Newlines {
+    pub fn new(port: Box<dyn SerialPort>, input: Option<Newline>, output: Option<Newline>) -> Newlines {
+        Newlines {
+            port,
+            input: input.map(Translation::new),
+            output: output.map(Translation::new),
+            received: VecDeque::new(),
+            sent: VecDeque::new(),
+        }
+    }
+}
+
+impl SerialPort for Newlines {
+    fn receive(&mut self) -> Option<u8> {
+        // the LF of a CR LF pair gives nothing, so look past it
+        while self.received.is_empty() {
+            let byte = self.port.receive()?;
+            match &mut self.input {
+                Some(input) => input.translate(byte, &mut self.received),
+                None => self.received.push_back(byte),
+            }
+        }
+        self.received.pop_front()
+    }
+
+    fn send(&mut self, byte: u8) {
+        match &mut self.output {
+            Some(output) => output.translate(byte, &mut self.sent),
+            None => self.sent.push_back(byte),
+        }
+        for byte in self.sent.drain(..) {
+            self.port.send(byte);
+        }
+    }
+}
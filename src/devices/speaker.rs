@@ -0,0 +1,198 @@
+// one-bit speaker, as on the Apple II at $c030: any read or write of
+// it flips the speaker cone between its two positions
+//
+// with the audio feature the sound is played on the host as it's made,
+// and it can be recorded to a wav file (16-bit mono at SAMPLE_RATE)
+// too, or instead when there's no audio. each sample is the average
+// position of the cone over its share of the cpu cycles, so a toggle
+// lands within the instruction that made it. the file is kept valid as
+// it grows, so it can be played while the program is still running.
+
+use std::fs::File;
+use std::io::{Seek, SeekFrom, Write};
+
+use crate::memory::{Device, StateReader, StateWriter};
+#[cfg(feature = "audio")]
+use super::audio::Playback;
+
+// the Apple II answers the whole of $c030-$c03f
+pub const SPEAKER_SIZE: u16 = 16;
+
+pub const SAMPLE_RATE: u32 = 44100;
+const AMPLITUDE: f64 = 8000.0;
+const WAV_HEADER_SIZE: u32 = 44;
+const SAMPLES_PER_WRITE: usize = 4096;
+#[cfg(feature = "audio")]
+const SAMPLES_PER_PLAY: usize = 256;    // about 6ms, so playing keeps up
+
+// the wav file the sound is recorded to
+struct Recording {
+    file: File,
+    path: String,
+    data_size: u32,         // bytes of samples written to the file
+    failed: bool,
+}
+
+impl Recording {
+    fn create(path: &str) -> Result<Recording, String> {
+        let file = File::create(path).map_err(|e| format!("can't create {}: {}", path, e))?;
+        let mut recording = Recording { file, path: path.to_string(), data_size: 0, failed: false };
+        recording.write(&[]);
+        Ok(recording)
+    }
+
+    // append the samples and bring the header up to date
+    fn write(&mut self, samples: &[i16]) {
+        if self.failed {
+            return;
+        }
+        let mut data = Vec::with_capacity(samples.len() * 2);
+        for sample in samples {
+            data.extend(sample.to_le_bytes());
+        }
+        self.data_size += data.len() as u32;
+        let result = self.file.seek(SeekFrom::Start(0))
+            .and_then(|_| self.file.write_all(&wav_header(self.data_size)))
+            .and_then(|_| self.file.seek(SeekFrom::End(0)))
+            .and_then(|_| self.file.write_all(&data));
+        if let Err(e) = result {
+            eprintln!("e6502r: can't write {}: {}", self.path, e);
+            self.failed = true;
+        }
+    }
+}
+
+pub struct Speaker {
+    recording: Option<Recording>,
+    #[cfg(feature = "audio")]
+    playback: Option<Playback>,
+    high: bool,
+    cycles_per_sample: f64,
+    sample_cycles: f64,     // cycles of the current sample so far
+    sample_sum: f64,        // cone position times cycles over them
+    samples: Vec<i16>,
+}
+
+impl Speaker {
+    // a speaker recorded to path, if given, and played if play is set and
+    // there's the audio feature; one or the other must be had
+    #[cfg_attr(not(feature = "audio"), allow(unused_variables))]
+    pub fn new(path: Option<&str>, play: bool, clock_hz: f64) -> Result<Speaker, String> {
+        let recording = path.map(Recording::create).transpose()?;
+        #[cfg(feature = "audio")]
+        let playback = match play.then(|| Playback::open(SAMPLE_RATE)).transpose() {
+            Ok(playback) => playback,
+            // a recording is made all the same
+            Err(message) if recording.is_some() => {
+                eprintln!("e6502r: {}; only recording the speaker", message);
+                None
+            }
+            Err(message) => return Err(message),
+        };
+        #[cfg(feature = "audio")]
+        let heard = recording.is_some() || playback.is_some();
+        #[cfg(not(feature = "audio"))]
+        let heard = recording.is_some();
+        if !heard {
+            return Err(String::from("needs a 'file' to record to, or e6502r built with --features audio to play it"));
+        }
+        Ok(Speaker {
+            recording,
+            #[cfg(feature = "audio")]
+            playback,
+            high: false,
+            cycles_per_sample: clock_hz / SAMPLE_RATE as f64,
+            sample_cycles: 0.0,
+            sample_sum: 0.0,
+            samples: Vec::new(),
+        })
+    }
+
+    fn toggle(&mut self) {
+        self.high = !self.high;
+    }
+
+    // hand the samples so far to the output and the recording
+    fn flush_samples(&mut self) {
+        #[cfg(feature = "audio")]
+        if let Some(playback) = &self.playback {
+            playback.play(self.samples.iter().map(|&sample| sample as f32 / i16::MAX as f32));
+        }
+        if let Some(recording) = &mut self.recording {
+            recording.write(&self.samples);
+        }
+        self.samples.clear();
+    }
+
+    // how many samples are let build up before they're handed over
+    fn samples_per_flush(&self) -> usize {
+        #[cfg(feature = "audio")]
+        if self.playback.is_some() {
+            return SAMPLES_PER_PLAY;
+        }
+        SAMPLES_PER_WRITE
+    }
+}
+
+// the header of a wav file holding data_size bytes of samples
+pub fn wav_header(data_size: u32) -> Vec<u8> {
+    let mut header = Vec::with_capacity(WAV_HEADER_SIZE as usize);
+    header.extend(b"RIFF");
+    header.extend((WAV_HEADER_SIZE - 8 + data_size).to_le_bytes());
+    header.extend(b"WAVEfmt ");
+    header.extend(16u32.to_le_bytes());             // format chunk size
+    header.extend(1u16.to_le_bytes());              // pcm
+    header.extend(1u16.to_le_bytes());              // mono
+    header.extend(SAMPLE_RATE.to_le_bytes());
+    header.extend((SAMPLE_RATE * 2).to_le_bytes()); // bytes per second
+    header.extend(2u16.to_le_bytes());              // bytes per sample
+    header.extend(16u16.to_le_bytes());             // bits per sample
+    header.extend(b"data");
+    header.extend(data_size.to_le_bytes());
+    header
+}
+
+impl Device for Speaker {
+    fn read(&mut self, _offset: u16) -> u8 {
+        self.toggle();
+        0
+    }
+
+    fn write(&mut self, _offset: u16, _value: u8) {
+        self.toggle();
+    }
+
+    fn tick(&mut self, cycles: u32) {
+        let position = if self.high { 1.0 } else { -1.0 };
+        let mut cycles = cycles as f64;
+        while self.sample_cycles + cycles >= self.cycles_per_sample {
+            let part = self.cycles_per_sample - self.sample_cycles;
+            let sum = self.sample_sum + position * part;
+            self.samples.push((sum / self.cycles_per_sample * AMPLITUDE) as i16);
+            cycles -= part;
+            self.sample_cycles = 0.0;
+            self.sample_sum = 0.0;
+        }
+        self.sample_cycles += cycles;
+        self.sample_sum += position * cycles;
+        if self.samples.len() >= self.samples_per_flush() {
+            self.flush_samples();
+        }
+    }
+
+    // the recording carries on in this run's file
+    fn save_state(&self, out: &mut StateWriter) {
+        out.bool(self.high);
+    }
+
+    fn load_state(&mut self, input: &mut StateReader) -> Result<(), String> {
+        self.high = input.bool()?;
+        Ok(())
+    }
+}
+
+impl Drop for Speaker {
+    fn drop(&mut self) {
+        self.flush_samples();
+    }
+}
@@ -0,0 +1,108 @@
+
+// interval timer for periodic interrupts
+//
+//   0-2   divisor, low byte first: cycles between interrupts
+//   3     control: bit 0 runs the timer, bit 1 enables its irq,
+//         bit 2 stops it after one period
+//   4     status: bit 7 is set when a period ends; any write clears it
+//   5-7   read: the cycles left in the current period
+//
+// writing the control register with the run bit set starts a new
+// period from the divisor. the timer is only ticked when a period ends
+// or it's read or written, not after every instruction.
+
+use crate::memory::{Device, StateReader, StateWriter};
+
+pub const TIMER_SIZE: u16 = 8;
+
+const CONTROL_RUN: u8 = 0x01;
+const CONTROL_IRQ_ON: u8 = 0x02;
+const CONTROL_ONE_SHOT: u8 = 0x04;
+const STATUS_EXPIRED: u8 = 0x80;
+
+pub struct Timer {
+    divisor: u32,
+    counter: u32,
+    control: u8,
+    status: u8,
+}
+
+impl Timer {
+    pub fn new() -> Timer {
+        Timer { divisor: 0, counter: 0, control: 0, status: 0 }
+    }
+
+    fn running(&self) -> bool {
+        self.control & CONTROL_RUN != 0 && self.divisor != 0
+    }
+}
+
+impl Device for Timer {
+    fn read(&mut self, offset: u16) -> u8 {
+        match offset & 0x07 {
+            0..=2 => (self.divisor >> (8 * offset)) as u8,
+            3 => self.control,
+            4 => self.status,
+            _ => (self.counter >> (8 * (offset - 5))) as u8,
+        }
+    }
+
+    fn write(&mut self, offset: u16, value: u8) {
+        match offset & 0x07 {
+            0..=2 => {
+                let shift = 8 * offset;
+                self.divisor = (self.divisor & !(0xff << shift)) | ((value as u32) << shift);
+            }
+            3 => {
+                self.control = value;
+                self.counter = self.divisor;
+            }
+            4 => self.status = 0,
+            _ => {}
+        }
+    }
+
+    fn tick(&mut self, cycles: u32) {
+        if !self.running() {
+            return;
+        }
+        let mut cycles = cycles;
+        while cycles >= self.counter {
+            cycles -= self.counter;
+            self.status |= STATUS_EXPIRED;
+            self.counter = self.divisor;
+            if self.control & CONTROL_ONE_SHOT != 0 {
+                self.control &= !CONTROL_RUN;
+                return;
+            }
+        }
+        self.counter -= cycles;
+    }
+
+    fn next_event(&self) -> Option<u64> {
+        Some(if self.running() { self.counter as u64 } else { u64::MAX })
+    }
+
+    fn irq(&self) -> bool {
+        self.status & STATUS_EXPIRED != 0 && self.control & CONTROL_IRQ_ON != 0
+    }
+
+    fn raises_irqs(&self) -> bool {
+        true
+    }
+
+    fn save_state(&self, out: &mut StateWriter) {
+        out.u32(self.divisor);
+        out.u32(self.counter);
+        out.u8(self.control);
+        out.u8(self.status);
+    }
+
+    fn load_state(&mut self, input: &mut StateReader) -> Result<(), String> {
+        self.divisor = input.u32()?;
+        self.counter = input.u32()?;
+        self.control = input.u8()?;
+        self.status = input.u8()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,544 @@
+
+// 6522 versatile interface adapter
+//
+//   0 ORB/IRB   4 T1C-L   8 T2C-L   c PCR
+//   1 ORA/IRA   5 T1C-H   9 T2C-H   d IFR
+//   2 DDRB      6 T1L-L   a SR      e IER
+//   3 DDRA      7 T1L-H   b ACR     f ORA/IRA, no handshake
+//
+// both timers count down once per cycle. timer 1 runs one-shot or
+// free-running (ACR bit 6) and can toggle PB7 (ACR bit 7); timer 2
+// runs one-shot in timed mode (pulse counting is not emulated). the
+// shift register supports the free-running, timer 2 and phi2 modes;
+// with nothing attached to CB2, shifting in reads ones. CA1 is driven
+// with set_ca1 by machines that wire something to it; the other
+// control lines have no inputs, so their flags are only cleared by
+// port accesses.
+//
+// the via is ticked only when a timer is due to fire or a shift to
+// finish, or before it's accessed, and works out the counters for all
+// the cycles since at once.
+//
+// port pins without an output driving them read from pins_a/pins_b,
+// which idle high. a WiredVia has other chips on its pins, each a
+// PortDevice that follows the ports after every write.
+
+use crate::memory::{Device, StateReader, StateWriter};
+
+pub const VIA_SIZE: u16 = 16;
+
+const ORB: u16 = 0x0;
+const ORA: u16 = 0x1;
+const DDRB: u16 = 0x2;
+const DDRA: u16 = 0x3;
+const T1C_L: u16 = 0x4;
+const T1C_H: u16 = 0x5;
+const T1L_L: u16 = 0x6;
+const T1L_H: u16 = 0x7;
+const T2C_L: u16 = 0x8;
+const T2C_H: u16 = 0x9;
+const SR: u16 = 0xa;
+const ACR: u16 = 0xb;
+const PCR: u16 = 0xc;
+const IFR: u16 = 0xd;
+const IER: u16 = 0xe;
+const ORA_NO_HANDSHAKE: u16 = 0xf;
+
+// interrupt flag bits
+const IRQ_CA2: u8 = 0x01;
+const IRQ_CA1: u8 = 0x02;
+const IRQ_SR: u8 = 0x04;
+const IRQ_CB2: u8 = 0x08;
+const IRQ_CB1: u8 = 0x10;
+const IRQ_T2: u8 = 0x20;
+const IRQ_T1: u8 = 0x40;
+const IRQ_ANY: u8 = 0x80;
+
+// auxiliary control bits
+const ACR_T1_PB7: u8 = 0x80;
+const ACR_T1_FREE_RUN: u8 = 0x40;
+const ACR_T2_COUNT_PULSES: u8 = 0x20;
+
+// peripheral control bits
+const PCR_CA1_RISING: u8 = 0x01;
+
+// shift register modes (ACR bits 2-4)
+const SR_DISABLED: u8 = 0;
+const SR_IN_T2: u8 = 1;
+const SR_IN_PHI2: u8 = 2;
+const SR_OUT_FREE_T2: u8 = 4;
+const SR_OUT_T2: u8 = 5;
+const SR_OUT_PHI2: u8 = 6;
+
+pub struct Via {
+    pub pins_a: u8,         // levels on port A pins not driven as outputs
+    pub pins_b: u8,         // levels on port B pins not driven as outputs
+    ca1: bool,              // input level on CA1
+    ora: u8,
+    orb: u8,
+    ddra: u8,
+    ddrb: u8,
+    t1_counter: u16,
+    t1_latch: u16,
+    t1_armed: bool,         // one-shot timer 1 has not fired yet
+    pb7: bool,              // timer 1 output on PB7
+    t2_counter: u16,
+    t2_latch_lo: u8,
+    t2_armed: bool,
+    sr: u8,
+    sr_bits: u8,            // bits left to shift; 0 when idle
+    sr_clock: u16,          // cycles until the next shift
+    acr: u8,
+    pcr: u8,
+    ifr: u8,
+    ier: u8,
+}
+
+impl Via {
+    pub fn new() -> Via {
+        Via {
+            pins_a: 0xff,
+            pins_b: 0xff,
+            ca1: true,
+            ora: 0,
+            orb: 0,
+            ddra: 0,
+            ddrb: 0,
+            t1_counter: 0xffff,
+            t1_latch: 0xffff,
+            t1_armed: false,
+            pb7: true,
+            t2_counter: 0xffff,
+            t2_latch_lo: 0xff,
+            t2_armed: false,
+            sr: 0,
+            sr_bits: 0,
+            sr_clock: 0,
+            acr: 0,
+            pcr: 0,
+            ifr: 0,
+            ier: 0,
+        }
+    }
+
+    // levels on port A, outputs driven from ORA
+    pub fn port_a(&self) -> u8 {
+        (self.ora & self.ddra) | (self.pins_a & !self.ddra)
+    }
+
+    // levels on port B, with PB7 driven by timer 1 when enabled
+    pub fn port_b(&self) -> u8 {
+        let mut value = (self.orb & self.ddrb) | (self.pins_b & !self.ddrb);
+        if self.acr & ACR_T1_PB7 != 0 {
+            value = (value & 0x7f) | if self.pb7 { 0x80 } else { 0 };
+        }
+        value
+    }
+
+    // flag an interrupt on the active edge chosen by PCR bit 0
+    pub fn set_ca1(&mut self, level: bool) {
+        let rising = self.pcr & PCR_CA1_RISING != 0;
+        if level != self.ca1 && level == rising {
+            self.ifr |= IRQ_CA1;
+        }
+        self.ca1 = level;
+    }
+
+    fn sr_mode(&self) -> u8 {
+        (self.acr >> 2) & 0x07
+    }
+
+    // cycles between shifts for the current mode
+    fn sr_period(&self) -> u16 {
+        match self.sr_mode() {
+            SR_IN_PHI2 | SR_OUT_PHI2 => 2,
+            _ => self.t2_latch_lo as u16 + 2,
+        }
+    }
+
+    // a read or write of SR starts eight shifts
+    fn start_shift(&mut self) {
+        self.ifr &= !IRQ_SR;
+        if self.sr_mode() != SR_DISABLED {
+            self.sr_bits = 8;
+            self.sr_clock = self.sr_period();
+        }
+    }
+
+    fn shift(&mut self) {
+        match self.sr_mode() {
+            // nothing drives CB2, so ones shift in
+            SR_IN_T2 | SR_IN_PHI2 => self.sr = (self.sr << 1) | 1,
+            SR_OUT_FREE_T2 | SR_OUT_T2 | SR_OUT_PHI2 => self.sr = self.sr.rotate_left(1),
+            _ => return,
+        }
+        if self.sr_mode() == SR_OUT_FREE_T2 {
+            // free-running output keeps shifting and never interrupts
+            self.sr_bits = 8;
+            return;
+        }
+        self.sr_bits -= 1;
+        if self.sr_bits == 0 {
+            self.ifr |= IRQ_SR;
+        }
+    }
+
+    // timer 1 on by cycles. each cycle the counter counts down, or at
+    // zero reloads, from the latch when free-running, and fires if armed
+    fn advance_t1(&mut self, cycles: u64) {
+        let counter = self.t1_counter as u64;
+        if cycles <= counter {
+            self.t1_counter -= cycles as u16;
+            return;
+        }
+        let free_run = self.acr & ACR_T1_FREE_RUN != 0;
+        let reload = if free_run { self.t1_latch } else { 0xffff };
+        let period = reload as u64 + 1;
+        let after = cycles - counter - 1;      // cycles since the first reload
+        if self.t1_armed {
+            self.ifr |= IRQ_T1;
+            // free-running, PB7 toggles at every reload
+            let reloads = if free_run { 1 + after / period } else { 1 };
+            if reloads % 2 == 1 {
+                self.pb7 = !self.pb7;
+            }
+            self.t1_armed = free_run;
+        }
+        self.t1_counter = reload - (after % period) as u16;
+    }
+
+    // timer 2 on by cycles; timed, it counts down and wraps, firing once
+    // when armed as it leaves zero
+    fn advance_t2(&mut self, cycles: u64) {
+        if self.acr & ACR_T2_COUNT_PULSES != 0 {
+            return;
+        }
+        if self.t2_armed && cycles > self.t2_counter as u64 {
+            self.ifr |= IRQ_T2;
+            self.t2_armed = false;
+        }
+        self.t2_counter = self.t2_counter.wrapping_sub(cycles as u16);
+    }
+
+    // the shift register on by cycles, shifting every sr_period
+    fn advance_sr(&mut self, cycles: u64) {
+        if self.sr_bits == 0 {
+            return;
+        }
+        if cycles < self.sr_clock as u64 {
+            self.sr_clock -= cycles as u16;
+            return;
+        }
+        let period = self.sr_period() as u64;
+        let after = cycles - self.sr_clock as u64;
+        let shifts = 1 + after / period;
+        self.sr_clock = (period - after % period) as u16;
+        // free-running output rotates for ever; the rest stop after eight
+        let shifts = if self.sr_mode() == SR_OUT_FREE_T2 { shifts % 8 } else { shifts.min(self.sr_bits as u64) };
+        for _ in 0..shifts {
+            self.shift();
+        }
+        if self.sr_bits == 0 {
+            self.sr_clock = period as u16;
+        }
+    }
+
+    // cycles until the next change an interrupt could come of: a timer
+    // firing or the last bit of a shift
+    fn next_change(&self) -> u64 {
+        let t1 = if self.t1_armed { self.t1_counter as u64 + 1 } else { u64::MAX };
+        let t2 = if self.t2_armed && self.acr & ACR_T2_COUNT_PULSES == 0 { self.t2_counter as u64 + 1 } else { u64::MAX };
+        let sr = match self.sr_mode() {
+            SR_IN_T2 | SR_IN_PHI2 | SR_OUT_T2 | SR_OUT_PHI2 if self.sr_bits > 0 =>
+                self.sr_clock as u64 + (self.sr_bits as u64 - 1) * self.sr_period() as u64,
+            _ => u64::MAX,
+        };
+        t1.min(t2).min(sr)
+    }
+
+    fn read_ifr(&self) -> u8 {
+        let any = if self.ifr & self.ier & 0x7f != 0 { IRQ_ANY } else { 0 };
+        (self.ifr & 0x7f) | any
+    }
+}
+
+impl Device for Via {
+    fn read(&mut self, offset: u16) -> u8 {
+        match offset & 0x0f {
+            ORB => {
+                self.ifr &= !(IRQ_CB1 | IRQ_CB2);
+                self.port_b()
+            }
+            ORA => {
+                self.ifr &= !(IRQ_CA1 | IRQ_CA2);
+                self.port_a()
+            }
+            DDRB => self.ddrb,
+            DDRA => self.ddra,
+            T1C_L => {
+                self.ifr &= !IRQ_T1;
+                self.t1_counter as u8
+            }
+            T1C_H => (self.t1_counter >> 8) as u8,
+            T1L_L => self.t1_latch as u8,
+            T1L_H => (self.t1_latch >> 8) as u8,
+            T2C_L => {
+                self.ifr &= !IRQ_T2;
+                self.t2_counter as u8
+            }
+            T2C_H => (self.t2_counter >> 8) as u8,
+            SR => {
+                self.start_shift();
+                self.sr
+            }
+            ACR => self.acr,
+            PCR => self.pcr,
+            IFR => self.read_ifr(),
+            IER => self.ier | 0x80,
+            _ => self.port_a(),
+        }
+    }
+
+    fn write(&mut self, offset: u16, value: u8) {
+        match offset & 0x0f {
+            ORB => {
+                self.ifr &= !(IRQ_CB1 | IRQ_CB2);
+                self.orb = value;
+            }
+            ORA => {
+                self.ifr &= !(IRQ_CA1 | IRQ_CA2);
+                self.ora = value;
+            }
+            DDRB => self.ddrb = value,
+            DDRA => self.ddra = value,
+            T1C_L | T1L_L => self.t1_latch = (self.t1_latch & 0xff00) | value as u16,
+            T1C_H => {
+                // loads the counter from the latch and starts timing
+                self.t1_latch = (self.t1_latch & 0x00ff) | ((value as u16) << 8);
+                self.t1_counter = self.t1_latch;
+                self.t1_armed = true;
+                self.ifr &= !IRQ_T1;
+                if self.acr & ACR_T1_PB7 != 0 {
+                    self.pb7 = false;
+                }
+            }
+            T1L_H => {
+                self.t1_latch = (self.t1_latch & 0x00ff) | ((value as u16) << 8);
+                self.ifr &= !IRQ_T1;
+            }
+            T2C_L => self.t2_latch_lo = value,
+            T2C_H => {
+                self.t2_counter = ((value as u16) << 8) | self.t2_latch_lo as u16;
+                self.t2_armed = true;
+                self.ifr &= !IRQ_T2;
+            }
+            SR => {
+                self.sr = value;
+                self.start_shift();
+            }
+            ACR => self.acr = value,
+            PCR => self.pcr = value,
+            IFR => self.ifr &= !(value & 0x7f),     // writing ones clears flags
+            IER => {
+                if value & 0x80 != 0 {
+                    self.ier |= value & 0x7f;
+                } else {
+                    self.ier &= !(value & 0x7f);
+                }
+            }
+            ORA_NO_HANDSHAKE => self.ora = value,
+            _ => {}
+        }
+    }
+
+    fn tick(&mut self, cycles: u32) {
+        let cycles = cycles as u64;
+        self.advance_t1(cycles);
+        self.advance_t2(cycles);
+        self.advance_sr(cycles);
+    }
+
+    // the counters are brought up to date before they're read, so only
+    // what could interrupt needs the via ticked
+    fn next_event(&self) -> Option<u64> {
+        Some(self.next_change())
+    }
+
+    fn irq(&self) -> bool {
+        self.ifr & self.ier & 0x7f != 0
+    }
+
+    fn raises_irqs(&self) -> bool {
+        true
+    }
+
+    fn save_state(&self, out: &mut StateWriter) {
+        for value in [self.pins_a, self.pins_b, self.ora, self.orb, self.ddra, self.ddrb] {
+            out.u8(value);
+        }
+        out.bool(self.ca1);
+        out.u16(self.t1_counter);
+        out.u16(self.t1_latch);
+        out.bool(self.t1_armed);
+        out.bool(self.pb7);
+        out.u16(self.t2_counter);
+        out.u8(self.t2_latch_lo);
+        out.bool(self.t2_armed);
+        out.u8(self.sr);
+        out.u8(self.sr_bits);
+        out.u16(self.sr_clock);
+        for value in [self.acr, self.pcr, self.ifr, self.ier] {
+            out.u8(value);
+        }
+    }
+
+    fn load_state(&mut self, input: &mut StateReader) -> Result<(), String> {
+        for value in [&mut self.pins_a, &mut self.pins_b, &mut self.ora, &mut self.orb, &mut self.ddra, &mut self.ddrb] {
+            *value = input.u8()?;
+        }
+        self.ca1 = input.bool()?;
+        self.t1_counter = input.u16()?;
+        self.t1_latch = input.u16()?;
+        self.t1_armed = input.bool()?;
+        self.pb7 = input.bool()?;
+        self.t2_counter = input.u16()?;
+        self.t2_latch_lo = input.u8()?;
+        self.t2_armed = input.bool()?;
+        self.sr = input.u8()?;
+        self.sr_bits = input.u8()?;
+        self.sr_clock = input.u16()?;
+        for value in [&mut self.acr, &mut self.pcr, &mut self.ifr, &mut self.ier] {
+            *value = input.u8()?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Port {
+    A,
+    B,
+}
+
+impl Port {
+    pub fn levels(&self, via: &Via) -> u8 {
+        match self {
+            Port::A => via.port_a(),
+            Port::B => via.port_b(),
+        }
+    }
+
+    // the levels other chips put on the port's input pins
+    pub fn pins<'a>(&self, via: &'a mut Via) -> &'a mut u8 {
+        match self {
+            Port::A => &mut via.pins_a,
+            Port::B => &mut via.pins_b,
+        }
+    }
+}
+
+// one pin of a VIA port
+#[derive(Clone, Copy)]
+pub struct Pin {
+    pub port: Port,
+    pub bit: u8,
+}
+
+impl Pin {
+    // pins are named like pa7 or pb0
+    pub fn parse(text: &str) -> Result<Pin, String> {
+        let lower = text.to_lowercase();
+        let port = match lower.get(..2) {
+            Some("pa") => Port::A,
+            Some("pb") => Port::B,
+            _ => return Err(format!("bad pin '{}', expected one like pa7", text)),
+        };
+        match lower[2..].parse::<u8>() {
+            Ok(bit) if bit < 8 => Ok(Pin { port, bit }),
+            _ => Err(format!("bad pin '{}', expected one like pa7", text)),
+        }
+    }
+
+    pub fn level(&self, via: &Via) -> bool {
+        self.port.levels(via) & (1 << self.bit) != 0
+    }
+}
+
+// a chip wired to a VIA's port pins, such as an lcd or an sd card
+pub trait PortDevice {
+    // follow the pins after the program changes the ports
+    fn update(&mut self, via: &mut Via);
+
+    fn tick(&mut self, _cycles: u32) {}
+
+    // the cycles until it next needs ticking, u64::MAX if nothing is
+    // coming; it is ticked before every write to the via as well
+    fn next_event(&self) -> u64 {
+        u64::MAX
+    }
+
+    fn save_state(&self, _out: &mut StateWriter) {}
+
+    fn load_state(&mut self, _input: &mut StateReader) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+// a VIA with chips on its ports
+pub struct WiredVia {
+    via: Via,
+    wired: Vec<Box<dyn PortDevice>>,
+}
+
+impl WiredVia {
+    pub fn new(wired: Vec<Box<dyn PortDevice>>) -> WiredVia {
+        WiredVia { via: Via::new(), wired }
+    }
+}
+
+impl Device for WiredVia {
+    fn read(&mut self, offset: u16) -> u8 {
+        self.via.read(offset)
+    }
+
+    fn write(&mut self, offset: u16, value: u8) {
+        self.via.write(offset, value);
+        for device in &mut self.wired {
+            device.update(&mut self.via);
+        }
+    }
+
+    fn tick(&mut self, cycles: u32) {
+        self.via.tick(cycles);
+        for device in &mut self.wired {
+            device.tick(cycles);
+        }
+    }
+
+    fn next_event(&self) -> Option<u64> {
+        Some(self.wired.iter().map(|device| device.next_event()).fold(self.via.next_change(), u64::min))
+    }
+
+    fn irq(&self) -> bool {
+        self.via.irq()
+    }
+
+    fn raises_irqs(&self) -> bool {
+        true
+    }
+
+    fn save_state(&self, out: &mut StateWriter) {
+        self.via.save_state(out);
+        for device in &self.wired {
+            device.save_state(out);
+        }
+    }
+
+    fn load_state(&mut self, input: &mut StateReader) -> Result<(), String> {
+        self.via.load_state(input)?;
+        for device in &mut self.wired {
+            device.load_state(input)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,52 @@
+// a native window on the host for a framebuffer, with the window feature
+//
+// the pixels go to it as they are, 0xrrggbb, and it is scaled up by a
+// whole number so small displays can be seen. the host's events are
+// looked at each time it is shown, so it keeps answering between
+// frames. closing it stops the drawing; the program runs on.
+
+use minifb::{Scale, WindowOptions};
+
+pub struct Window {
+    window: minifb::Window,
+    width: usize,
+    height: usize,
+}
+
+// the window sizes minifb offers
+pub fn scale_of(factor: i64) -> Option<Scale> {
+    match factor {
+        1 => Some(Scale::X1),
+        2 => Some(Scale::X2),
+        4 => Some(Scale::X4),
+        8 => Some(Scale::X8),
+        16 => Some(Scale::X16),
+        32 => Some(Scale::X32),
+        _ => None,
+    }
+}
+
+impl Window {
+    pub fn open(title: &str, width: usize, height: usize, scale: Scale) -> Result<Window, String> {
+        let options = WindowOptions { scale, ..WindowOptions::default() };
+        let mut window = minifb::Window::new(title, width, height, options)
+            .map_err(|e| format!("can't open a window: {}", e))?;
+        // the emulated clock sets the pace, so minifb never sleeps
+        window.set_target_fps(0);
+        Ok(Window { window, width, height })
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.window.is_open()
+    }
+
+    // show the pixels, rows of width from the top left
+    pub fn show(&mut self, pixels: &[u32]) {
+        let _result = self.window.update_with_buffer(pixels, self.width, self.height);
+    }
+
+    // answer the host's events with nothing new to show
+    pub fn update(&mut self) {
+        self.window.update();
+    }
+}
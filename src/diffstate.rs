@@ -0,0 +1,179 @@
+// diff-state: compare two saved states or memory dumps
+//
+// a saved state is compared register by register, then memory, then
+// the banks showing and each device's state; a memory dump, any other
+// file, is taken as memory from address 0, so a state can be checked
+// against a golden dump. memory that differs is shown in ranges, with
+// runs of fewer than MERGE_GAP equal bytes between differences joined
+// into one range.
+
+use std::fs;
+
+use crate::memory::Bank;
+use crate::state::{is_state, read_state, Snapshot};
+
+const MERGE_GAP: usize = 8;
+const BYTES_PER_LINE: usize = 16;
+const LINES_PER_RANGE: usize = 4;
+
+enum Dump {
+    State(Snapshot),
+    Memory(Vec<u8>),
+}
+
+impl Dump {
+    fn load(path: &str) -> Result<Dump, String> {
+        let data = fs::read(path).map_err(|e| format!("can't read {}: {}", path, e))?;
+        if is_state(&data) {
+            Ok(Dump::State(read_state(path)?))
+        } else {
+            Ok(Dump::Memory(data))
+        }
+    }
+
+    fn memory(&self) -> &[u8] {
+        match self {
+            Dump::State(snapshot) => &snapshot.memory.mem,
+            Dump::Memory(data) => data,
+        }
+    }
+}
+
+// print the differences; true when there are none
+pub fn run_diff(path_a: &str, path_b: &str) -> Result<bool, String> {
+    let (a, b) = (Dump::load(path_a)?, Dump::load(path_b)?);
+    let mut same = true;
+    if let (Dump::State(a), Dump::State(b)) = (&a, &b) {
+        same &= diff_registers(a, b);
+    }
+    same &= diff_memory(a.memory(), b.memory());
+    if let (Dump::State(a), Dump::State(b)) = (&a, &b) {
+        same &= diff_banks(a, b);
+        same &= diff_devices(a, b);
+    }
+    if same {
+        println!("{} and {} are the same", path_a, path_b);
+    }
+    Ok(same)
+}
+
+fn diff_registers(a: &Snapshot, b: &Snapshot) -> bool {
+    let (a, b) = (&a.cpu, &b.cpu);
+    let mut lines = Vec::new();
+    if a.variant != b.variant {
+        lines.push(format!("  cpu     {:<8} {}", a.variant, b.variant));
+    }
+    if a.pc != b.pc {
+        lines.push(format!("  PC      ${:04x}    ${:04x}", a.pc, b.pc));
+    }
+    for (name, x, y) in [("A", a.ac, b.ac), ("X", a.xr, b.xr), ("Y", a.yr, b.yr), ("SP", a.sp, b.sp)] {
+        if x != y {
+            lines.push(format!("  {:<7} ${:02x}      ${:02x}", name, x, y));
+        }
+    }
+    let (st_a, st_b) = (a.status(), b.status());
+    if st_a != st_b {
+        lines.push(format!("  P       ${:02x}      ${:02x}      {} -> {}", st_a, st_b, flags(st_a), flags(st_b)));
+    }
+    if a.cycles != b.cycles {
+        lines.push(format!("  cycles  {:<8} {}", a.cycles, b.cycles));
+    }
+    print_section("registers", &lines)
+}
+
+// the status register as NV-BDIZC, lower case for clear flags
+fn flags(st: u8) -> String {
+    "NV-BDIZC".chars().enumerate()
+        .map(|(n, flag)| if st & (0x80 >> n) != 0 { flag } else { flag.to_ascii_lowercase() })
+        .collect()
+}
+
+fn diff_memory(a: &[u8], b: &[u8]) -> bool {
+    let len = a.len().max(b.len());
+    let byte = |data: &[u8], addr: usize| data.get(addr).copied();
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for addr in (0..len).filter(|&addr| byte(a, addr) != byte(b, addr)) {
+        match ranges.last_mut() {
+            Some((_, end)) if addr - *end <= MERGE_GAP => *end = addr + 1,
+            _ => ranges.push((addr, addr + 1)),
+        }
+    }
+    let mut lines = Vec::new();
+    for (start, end) in ranges {
+        let differ = (start..end).filter(|&addr| byte(a, addr) != byte(b, addr)).count();
+        let range = if end - start == 1 { format!("${:04x}", start) } else { format!("${:04x}-${:04x}", start, end - 1) };
+        lines.push(format!("  {}  {} byte{} differ{}", range, differ,
+            if differ == 1 { "" } else { "s" }, if differ == 1 { "s" } else { "" }));
+        for line in (start..end).step_by(BYTES_PER_LINE).take(LINES_PER_RANGE) {
+            let line_end = (line + BYTES_PER_LINE).min(end);
+            lines.push(format!("    a ${:04x}: {}", line, hex(a, line, line_end)));
+            lines.push(format!("    b ${:04x}: {}", line, hex(b, line, line_end)));
+        }
+        if end - start > BYTES_PER_LINE * LINES_PER_RANGE {
+            lines.push(String::from("    ..."));
+        }
+    }
+    print_section("memory", &lines)
+}
+
+// bytes past the end of a dump show as --
+fn hex(data: &[u8], start: usize, end: usize) -> String {
+    (start..end)
+        .map(|addr| data.get(addr).map_or(String::from("--"), |byte| format!("{:02x}", byte)))
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+fn diff_banks(a: &Snapshot, b: &Snapshot) -> bool {
+    let name = |bank: &Bank| match bank {
+        Bank::Ram => String::from("ram"),
+        Bank::Io => String::from("io"),
+        Bank::Rom(rom) => format!("rom {}", rom),
+    };
+    let mut lines = Vec::new();
+    if a.memory.banks.len() != b.memory.banks.len() {
+        lines.push(format!("  {} bank switched regions, {}", a.memory.banks.len(), b.memory.banks.len()));
+    }
+    for ((base, bank_a, ram_a), (_, bank_b, ram_b)) in a.memory.banks.iter().zip(&b.memory.banks) {
+        if bank_a != bank_b {
+            lines.push(format!("  ${:04x} shows {}, {}", base, name(bank_a), name(bank_b)));
+        } else if ram_a != ram_b {
+            lines.push(format!("  ${:04x} has different ram under its rom", base));
+        }
+    }
+    if a.memory.nmi_low != b.memory.nmi_low {
+        lines.push(format!("  nmi line {}, {}", level(a.memory.nmi_low), level(b.memory.nmi_low)));
+    }
+    print_section("banks", &lines)
+}
+
+fn level(low: bool) -> &'static str {
+    if low { "low" } else { "high" }
+}
+
+fn diff_devices(a: &Snapshot, b: &Snapshot) -> bool {
+    let mut lines = Vec::new();
+    if a.memory.devices.len() != b.memory.devices.len() {
+        lines.push(format!("  {} devices, {}", a.memory.devices.len(), b.memory.devices.len()));
+    }
+    for ((base_a, _, data_a), (base_b, _, data_b)) in a.memory.devices.iter().zip(&b.memory.devices) {
+        if base_a != base_b {
+            lines.push(format!("  a device at ${:04x}, one at ${:04x}", base_a, base_b));
+        } else if data_a != data_b {
+            lines.push(format!("  the device at ${:04x} is in a different state", base_a));
+        }
+    }
+    print_section("devices", &lines)
+}
+
+// true when there was nothing to print
+fn print_section(title: &str, lines: &[String]) -> bool {
+    if lines.is_empty() {
+        return true;
+    }
+    println!("{}:", title);
+    for line in lines {
+        println!("{}", line);
+    }
+    false
+}
@@ -0,0 +1,189 @@
+
+// disassembler shared by the execution display and the monitor
+
+use crate::cpu::{instruction_text, CpuVariant};
+use crate::memory::Memory;
+
+// operand addressing modes
+#[derive(Clone, Copy, PartialEq)]
+pub enum AddrMode {
+    Imp,    // implied
+    Acc,    // accumulator
+    Imm,    // immediate         #$nn
+    Zpg,    // zero page         $nn
+    Zpx,    // zero page,x       $nn,X
+    Zpy,    // zero page,y       $nn,Y
+    Abs,    // absolute          $nnnn
+    Abx,    // absolute,x        $nnnn,X
+    Aby,    // absolute,y        $nnnn,Y
+    Ind,    // indirect          ($nnnn)
+    Izx,    // indexed indirect  ($nn,X)
+    Izy,    // indirect indexed  ($nn),Y
+    Rel,    // relative branch   $nnnn
+    Izp,    // zero page indirect ($nn)             65c02
+    Iax,    // absolute indexed indirect ($nnnn,X)  65c02
+    Zpr,    // zero page, relative $nn,$nnnn        65c02
+}
+
+use AddrMode::*;
+
+// addressing mode by opcode
+pub const ADDR_MODES: [AddrMode; 256] = [
+    //0    1    2    3    4    5    6    7    8    9    a    b    c    d    e    f
+    Imp, Izx, Imp, Imp, Imp, Zpg, Zpg, Imp, Imp, Imm, Acc, Imp, Imp, Abs, Abs, Imp,     // 00
+    Rel, Izy, Imp, Imp, Imp, Zpx, Zpx, Imp, Imp, Aby, Imp, Imp, Imp, Abx, Abx, Imp,     // 10
+    Abs, Izx, Imp, Imp, Zpg, Zpg, Zpg, Imp, Imp, Imm, Acc, Imp, Abs, Abs, Abs, Imp,     // 20
+    Rel, Izy, Imp, Imp, Imp, Zpx, Zpx, Imp, Imp, Aby, Imp, Imp, Imp, Abx, Abx, Imp,     // 30
+    Imp, Izx, Imp, Imp, Imp, Zpg, Zpg, Imp, Imp, Imm, Acc, Imp, Abs, Abs, Abs, Imp,     // 40
+    Rel, Izy, Imp, Imp, Imp, Zpx, Zpx, Imp, Imp, Aby, Imp, Imp, Imp, Abx, Abx, Imp,     // 50
+    Imp, Izx, Imp, Imp, Imp, Zpg, Zpg, Imp, Imp, Imm, Acc, Imp, Ind, Abs, Abs, Imp,     // 60
+    Rel, Izy, Imp, Imp, Imp, Zpx, Zpx, Imp, Imp, Aby, Imp, Imp, Imp, Abx, Abx, Imp,     // 70
+    Imp, Izx, Imp, Imp, Zpg, Zpg, Zpg, Imp, Imp, Imp, Imp, Imp, Abs, Abs, Abs, Imp,     // 80
+    Rel, Izy, Imp, Imp, Zpx, Zpx, Zpy, Imp, Imp, Aby, Imp, Imp, Imp, Abx, Imp, Imp,     // 90
+    Imm, Izx, Imm, Imp, Zpg, Zpg, Zpg, Imp, Imp, Imm, Imp, Imp, Abs, Abs, Abs, Imp,     // a0
+    Rel, Izy, Imp, Imp, Zpx, Zpx, Zpy, Imp, Imp, Aby, Imp, Imp, Abx, Abx, Aby, Imp,     // b0
+    Imm, Izx, Imp, Imp, Zpg, Zpg, Zpg, Imp, Imp, Imm, Imp, Imp, Abs, Abs, Abs, Imp,     // c0
+    Rel, Izy, Imp, Imp, Imp, Zpx, Zpx, Imp, Imp, Aby, Imp, Imp, Imp, Abx, Abx, Imp,     // d0
+    Imm, Izx, Imp, Imp, Zpg, Zpg, Zpg, Imp, Imp, Imm, Imp, Imp, Abs, Abs, Abs, Imp,     // e0
+    Rel, Izy, Imp, Imp, Imp, Zpx, Zpx, Imp, Imp, Aby, Imp, Imp, Imp, Abx, Abx, Imp,     // f0
+];
+
+// addressing mode by opcode for the 65c02
+pub const ADDR_MODES_65C02: [AddrMode; 256] = [
+    //0    1    2    3    4    5    6    7    8    9    a    b    c    d    e    f
+    Imp, Izx, Imm, Imp, Zpg, Zpg, Zpg, Zpg, Imp, Imm, Acc, Imp, Abs, Abs, Abs, Zpr,     // 00
+    Rel, Izy, Izp, Imp, Zpg, Zpx, Zpx, Zpg, Imp, Aby, Acc, Imp, Abs, Abx, Abx, Zpr,     // 10
+    Abs, Izx, Imm, Imp, Zpg, Zpg, Zpg, Zpg, Imp, Imm, Acc, Imp, Abs, Abs, Abs, Zpr,     // 20
+    Rel, Izy, Izp, Imp, Zpx, Zpx, Zpx, Zpg, Imp, Aby, Acc, Imp, Abx, Abx, Abx, Zpr,     // 30
+    Imp, Izx, Imm, Imp, Imm, Zpg, Zpg, Zpg, Imp, Imm, Acc, Imp, Abs, Abs, Abs, Zpr,     // 40
+    Rel, Izy, Izp, Imp, Imm, Zpx, Zpx, Zpg, Imp, Aby, Imp, Imp, Abs, Abx, Abx, Zpr,     // 50
+    Imp, Izx, Imm, Imp, Zpg, Zpg, Zpg, Zpg, Imp, Imm, Acc, Imp, Ind, Abs, Abs, Zpr,     // 60
+    Rel, Izy, Izp, Imp, Zpx, Zpx, Zpx, Zpg, Imp, Aby, Imp, Imp, Iax, Abx, Abx, Zpr,     // 70
+    Rel, Izx, Imm, Imp, Zpg, Zpg, Zpg, Zpg, Imp, Imm, Imp, Imp, Abs, Abs, Abs, Zpr,     // 80
+    Rel, Izy, Izp, Imp, Zpx, Zpx, Zpy, Zpg, Imp, Aby, Imp, Imp, Abs, Abx, Abx, Zpr,     // 90
+    Imm, Izx, Imm, Imp, Zpg, Zpg, Zpg, Zpg, Imp, Imm, Imp, Imp, Abs, Abs, Abs, Zpr,     // a0
+    Rel, Izy, Izp, Imp, Zpx, Zpx, Zpy, Zpg, Imp, Aby, Imp, Imp, Abx, Abx, Aby, Zpr,     // b0
+    Imm, Izx, Imm, Imp, Zpg, Zpg, Zpg, Zpg, Imp, Imm, Imp, Imp, Abs, Abs, Abs, Zpr,     // c0
+    Rel, Izy, Izp, Imp, Imm, Zpx, Zpx, Zpg, Imp, Aby, Imp, Imp, Abs, Abx, Abx, Zpr,     // d0
+    Imm, Izx, Imm, Imp, Zpg, Zpg, Zpg, Zpg, Imp, Imm, Imp, Imp, Abs, Abs, Abs, Zpr,     // e0
+    Rel, Izy, Izp, Imp, Imm, Zpx, Zpx, Zpg, Imp, Aby, Imp, Imp, Abs, Abx, Abx, Zpr,     // f0
+];
+
+// number of bytes used by an addressing mode (including the opcode)
+pub fn mode_len(mode: AddrMode) -> u16 {
+    match mode {
+        Imp | Acc => 1,
+        Imm | Zpg | Zpx | Zpy | Izx | Izy | Rel | Izp => 2,
+        Abs | Abx | Aby | Ind | Iax | Zpr => 3,
+    }
+}
+
+// short name of an addressing mode, for listings by opcode
+pub fn mode_name(mode: AddrMode) -> &'static str {
+    match mode {
+        Imp => "",
+        Acc => "a",
+        Imm => "#imm",
+        Zpg => "zp",
+        Zpx => "zp,x",
+        Zpy => "zp,y",
+        Abs => "abs",
+        Abx => "abs,x",
+        Aby => "abs,y",
+        Ind => "(abs)",
+        Izx => "(zp,x)",
+        Izy => "(zp),y",
+        Rel => "rel",
+        Izp => "(zp)",
+        Iax => "(abs,x)",
+        Zpr => "zp,rel",
+    }
+}
+
+// addressing modes for a cpu variant
+pub fn addr_modes(variant: CpuVariant) -> &'static [AddrMode; 256] {
+    match variant {
+        CpuVariant::Nmos6502 | CpuVariant::Ricoh2a03 => &ADDR_MODES,
+        CpuVariant::Cmos65c02 => &ADDR_MODES_65C02,
+    }
+}
+
+// number of bytes used by the instruction with this opcode
+pub fn instruction_len(variant: CpuVariant, opcode: u8) -> u16 {
+    if instruction_text(variant)[opcode as usize].is_empty() {
+        return 1;
+    }
+    mode_len(addr_modes(variant)[opcode as usize])
+}
+
+// disassemble the instruction at addr
+// returns the text and the instruction length
+pub fn disassemble(variant: CpuVariant, mem: &Memory, addr: u16) -> (String, u16) {
+    let opcode = mem.mem[addr as usize];
+    let text = instruction_text(variant)[opcode as usize];
+    if text.is_empty() {
+        return (format!(".byte ${:02x}", opcode), 1);
+    }
+
+    let mode = addr_modes(variant)[opcode as usize];
+    let lo = mem.mem[addr.wrapping_add(1) as usize];
+    let hi = mem.mem[addr.wrapping_add(2) as usize];
+    let word = ((hi as u16) << 8) | lo as u16;
+    let operand = match mode {
+        Imp => String::new(),
+        Acc => String::from(" A"),
+        Imm => format!(" #${:02x}", lo),
+        Zpg => format!(" ${:02x}", lo),
+        Zpx => format!(" ${:02x},X", lo),
+        Zpy => format!(" ${:02x},Y", lo),
+        Abs => format!(" ${:04x}", word),
+        Abx => format!(" ${:04x},X", word),
+        Aby => format!(" ${:04x},Y", word),
+        Ind => format!(" (${:04x})", word),
+        Izx => format!(" (${:02x},X)", lo),
+        Izy => format!(" (${:02x}),Y", lo),
+        Rel => format!(" ${:04x}", addr.wrapping_add(2).wrapping_add(lo as i8 as u16)),
+        Izp => format!(" (${:02x})", lo),
+        Iax => format!(" (${:04x},X)", word),
+        Zpr => format!(" ${:02x},${:04x}", lo, addr.wrapping_add(3).wrapping_add(hi as i8 as u16)),
+    };
+    (format!("{}{}", text, operand), mode_len(mode))
+}
+
+// disassemble up to `before` instructions leading up to addr, the
+// instruction at addr and `after` instructions following it
+// the start point is found by trying earlier addresses until one
+// decodes into an instruction boundary that lands exactly on addr
+pub fn disassemble_around(variant: CpuVariant, mem: &Memory, addr: u16, before: u16, after: u16) -> Vec<(u16, String)> {
+    let mut start = addr;
+    for back in (1..=before * 3).rev() {
+        let candidate = addr.wrapping_sub(back);
+        let mut pos = candidate;
+        let mut count = 0;
+        while pos != addr && count < before + 1 {
+            pos = pos.wrapping_add(instruction_len(variant, mem.mem[pos as usize]));
+            count += 1;
+            if pos.wrapping_sub(candidate) > back {
+                break;
+            }
+        }
+        if pos == addr && count <= before {
+            start = candidate;
+            break;
+        }
+    }
+
+    let mut lines = Vec::new();
+    let mut pos = start;
+    while pos != addr {
+        let (text, len) = disassemble(variant, mem, pos);
+        lines.push((pos, text));
+        pos = pos.wrapping_add(len);
+    }
+    for _ in 0..=after {
+        let (text, len) = disassemble(variant, mem, pos);
+        lines.push((pos, text));
+        pos = pos.wrapping_add(len);
+    }
+    lines
+}
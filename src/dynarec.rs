@@ -0,0 +1,143 @@
+// experimental block translation, which run_for uses with the dynarec
+// feature. each machine keeps its own blocks, in its cpu
+//
+// a basic block is the run of instructions from an address up to and
+// including the first that can send execution elsewhere: a branch,
+// jump, call, return or interrupt. the first time a block is reached it
+// is translated into the list of handlers for its opcodes, so running
+// it again skips fetching, checking and looking up each opcode. BRK and
+// opcodes the cpu doesn't implement are never translated; the
+// interpreter's step deals with them.
+//
+// a block keeps the bytes it was translated from. they are checked
+// against memory before the block runs, and again after any instruction
+// in it that writes memory, so code that modifies itself, or is
+// changed by the monitor, a loaded state or a bank switch, is
+// translated again. after each instruction the caller ticks the
+// devices, takes interrupts and checks the stop conditions, as step
+// does, and the block is left as soon as execution goes anywhere but
+// the next instruction in it.
+
+use crate::cpu::{instruction_text, is_implemented, opcode_table, Cpu, CpuOp, CpuVariant};
+use crate::disasm::instruction_len;
+use crate::memory::{Memory, MEMSIZE};
+use crate::run::StopReason;
+
+// instructions in a block at most
+const MAX_BLOCK_LEN: usize = 64;
+
+// mnemonics that end a block; BBR and BBS cover BBR0..BBS7
+const FLOW: [&str; 18] = [
+    "BPL", "BMI", "BVC", "BVS", "BCC", "BCS", "BNE", "BEQ", "BRA", "BBR", "BBS",
+    "JMP", "JSR", "RTS", "RTI", "BRK", "WAI", "STP",
+];
+
+// mnemonics that may write memory, and so perhaps the block
+const WRITES: [&str; 18] = [
+    "STA", "STX", "STY", "STZ", "INC", "DEC", "ASL", "LSR", "ROL", "ROR",
+    "TSB", "TRB", "RMB", "SMB", "PHA", "PHP", "PHX", "PHY",
+];
+
+#[derive(Clone)]
+struct Op {
+    handler: CpuOp,
+    pc: u16,                // where the instruction is
+    next: u16,              // where the one after it is
+    writes: bool,
+}
+
+#[derive(Clone)]
+struct Block {
+    variant: CpuVariant,
+    bytes: Vec<u8>,         // the code as it was translated
+    ops: Vec<Op>,
+}
+
+impl Block {
+    fn translate(variant: CpuVariant, mem: &Memory, start: u16) -> Option<Block> {
+        let text = instruction_text(variant);
+        let mut ops = Vec::new();
+        let mut pc = start as usize;
+        while ops.len() < MAX_BLOCK_LEN {
+            let opcode = mem.mem[pc];
+            let next = pc + instruction_len(variant, opcode) as usize;
+            if opcode == 0x00 || !is_implemented(variant, opcode) || next > MEMSIZE {
+                break;
+            }
+            let mnemonic = text[opcode as usize];
+            let writes = WRITES.iter().any(|name| mnemonic.starts_with(name));
+            ops.push(Op { handler: opcode_table(variant)[opcode as usize], pc: pc as u16, next: next as u16, writes });
+            pc = next;
+            if FLOW.iter().any(|name| mnemonic.starts_with(name)) {
+                break;
+            }
+        }
+        if ops.is_empty() {
+            return None;
+        }
+        Some(Block { variant, bytes: mem.mem[start as usize..pc].to_vec(), ops })
+    }
+
+    // whether the code from the instruction at index on is as translated
+    fn unchanged(&self, mem: &Memory, index: usize) -> bool {
+        let start = self.ops[0].pc as usize;
+        let from = self.ops[index].pc as usize;
+        mem.mem[from..start + self.bytes.len()] == self.bytes[from - start..]
+    }
+}
+
+#[derive(Clone)]
+pub struct Dynarec {
+    blocks: Vec<Option<Block>>,     // by start address, empty until first used
+}
+
+impl Default for Dynarec {
+    fn default() -> Dynarec {
+        Dynarec::new()
+    }
+}
+
+impl Dynarec {
+    pub fn new() -> Dynarec {
+        Dynarec { blocks: Vec::new() }
+    }
+
+    // run the block at the pc, calling after for each instruction with
+    // the address it was at, and leaving it early once the cycle count
+    // reaches end; returns how many instructions ran and why execution
+    // stopped, if it did, or None when there is no block for the pc and
+    // the instruction has to be stepped
+    pub fn run_block<F>(&mut self, cpu: &mut Cpu, mem: &mut Memory, end: u64, mut after: F) -> Option<(u64, Option<StopReason>)>
+    where
+        F: FnMut(&mut Cpu, &mut Memory, u16) -> Option<StopReason>,
+    {
+        if self.blocks.is_empty() {
+            self.blocks.resize_with(MEMSIZE, || None);
+        }
+        let start = cpu.pc as usize;
+        let stale = match &self.blocks[start] {
+            Some(block) => block.variant != cpu.variant || !block.unchanged(mem, 0),
+            None => true,
+        };
+        if stale {
+            self.blocks[start] = Block::translate(cpu.variant, mem, cpu.pc);
+        }
+        let block = self.blocks[start].as_ref()?;
+
+        let mut executed = 0;
+        for (index, op) in block.ops.iter().enumerate() {
+            (op.handler)(cpu, mem);
+            executed += 1;
+            if let Some(reason) = after(cpu, mem, op.pc) {
+                return Some((executed, Some(reason)));
+            }
+            // the budget spent, a branch taken, an interrupt or code
+            // written over
+            if (end.wrapping_sub(cpu.cycles) as i64) <= 0 || cpu.pc != op.next
+                || (op.writes && index + 1 < block.ops.len() && !block.unchanged(mem, index + 1)) {
+                break;
+            }
+        }
+        Some((executed, None))
+    }
+}
@@ -0,0 +1,178 @@
+// a small bitmap font for drawing text screens into pictures
+//
+// each character takes a cell of CELL by CELL pixels. letters, digits,
+// punctuation and the few symbols the pet screen codes have are 5x7
+// glyphs in the top left of the cell, a column in from the left; line
+// and block drawing characters fill the whole cell, so they join up
+// with their neighbours. anything else is drawn as ?.
+
+pub const CELL: usize = 8;
+
+const GLYPHS: [(char, [&str; 7]); 104] = [
+    (' ', [".....", ".....", ".....", ".....", ".....", ".....", "....."]),
+    ('!', ["..#..", "..#..", "..#..", "..#..", "..#..", ".....", "..#.."]),
+    ('"', [".#.#.", ".#.#.", ".#.#.", ".....", ".....", ".....", "....."]),
+    ('#', [".#.#.", ".#.#.", "#####", ".#.#.", "#####", ".#.#.", ".#.#."]),
+    ('$', ["..#..", ".####", "#.#..", ".###.", "..#.#", "####.", "..#.."]),
+    ('%', ["##...", "##..#", "...#.", "..#..", ".#...", "#..##", "...##"]),
+    ('&', [".##..", "#..#.", "#.#..", ".#...", "#.#.#", "#..#.", ".##.#"]),
+    ('\'', ["..#..", "..#..", ".#...", ".....", ".....", ".....", "....."]),
+    ('(', ["...#.", "..#..", ".#...", ".#...", ".#...", "..#..", "...#."]),
+    (')', [".#...", "..#..", "...#.", "...#.", "...#.", "..#..", ".#..."]),
+    ('*', [".....", "..#..", "#.#.#", ".###.", "#.#.#", "..#..", "....."]),
+    ('+', [".....", "..#..", "..#..", "#####", "..#..", "..#..", "....."]),
+    (',', [".....", ".....", ".....", ".....", ".##..", "..#..", ".#..."]),
+    ('-', [".....", ".....", ".....", "#####", ".....", ".....", "....."]),
+    ('.', [".....", ".....", ".....", ".....", ".....", ".##..", ".##.."]),
+    ('/', [".....", "....#", "...#.", "..#..", ".#...", "#....", "....."]),
+    ('0', [".###.", "#...#", "#..##", "#.#.#", "##..#", "#...#", ".###."]),
+    ('1', ["..#..", ".##..", "..#..", "..#..", "..#..", "..#..", ".###."]),
+    ('2', [".###.", "#...#", "....#", "...#.", "..#..", ".#...", "#####"]),
+    ('3', ["#####", "...#.", "..#..", "...#.", "....#", "#...#", ".###."]),
+    ('4', ["...#.", "..##.", ".#.#.", "#..#.", "#####", "...#.", "...#."]),
+    ('5', ["#####", "#....", "####.", "....#", "....#", "#...#", ".###."]),
+    ('6', ["..##.", ".#...", "#....", "####.", "#...#", "#...#", ".###."]),
+    ('7', ["#####", "....#", "...#.", "..#..", ".#...", ".#...", ".#..."]),
+    ('8', [".###.", "#...#", "#...#", ".###.", "#...#", "#...#", ".###."]),
+    ('9', [".###.", "#...#", "#...#", ".####", "....#", "...#.", ".##.."]),
+    (':', [".....", ".##..", ".##..", ".....", ".##..", ".##..", "....."]),
+    (';', [".....", ".##..", ".##..", ".....", ".##..", "..#..", ".#..."]),
+    ('<', ["...#.", "..#..", ".#...", "#....", ".#...", "..#..", "...#."]),
+    ('=', [".....", ".....", "#####", ".....", "#####", ".....", "....."]),
+    ('>', [".#...", "..#..", "...#.", "....#", "...#.", "..#..", ".#..."]),
+    ('?', [".###.", "#...#", "....#", "...#.", "..#..", ".....", "..#.."]),
+    ('@', [".###.", "#...#", "....#", ".##.#", "#.#.#", "#.#.#", ".###."]),
+    ('A', [".###.", "#...#", "#...#", "#####", "#...#", "#...#", "#...#"]),
+    ('B', ["####.", "#...#", "#...#", "####.", "#...#", "#...#", "####."]),
+    ('C', [".###.", "#...#", "#....", "#....", "#....", "#...#", ".###."]),
+    ('D', ["###..", "#..#.", "#...#", "#...#", "#...#", "#..#.", "###.."]),
+    ('E', ["#####", "#....", "#....", "####.", "#....", "#....", "#####"]),
+    ('F', ["#####", "#....", "#....", "####.", "#....", "#....", "#...."]),
+    ('G', [".###.", "#...#", "#....", "#.###", "#...#", "#...#", ".####"]),
+    ('H', ["#...#", "#...#", "#...#", "#####", "#...#", "#...#", "#...#"]),
+    ('I', [".###.", "..#..", "..#..", "..#..", "..#..", "..#..", ".###."]),
+    ('J', ["..###", "...#.", "...#.", "...#.", "...#.", "#..#.", ".##.."]),
+    ('K', ["#...#", "#..#.", "#.#..", "##...", "#.#..", "#..#.", "#...#"]),
+    ('L', ["#....", "#....", "#....", "#....", "#....", "#....", "#####"]),
+    ('M', ["#...#", "##.##", "#.#.#", "#.#.#", "#...#", "#...#", "#...#"]),
+    ('N', ["#...#", "#...#", "##..#", "#.#.#", "#..##", "#...#", "#...#"]),
+    ('O', [".###.", "#...#", "#...#", "#...#", "#...#", "#...#", ".###."]),
+    ('P', ["####.", "#...#", "#...#", "####.", "#....", "#....", "#...."]),
+    ('Q', [".###.", "#...#", "#...#", "#...#", "#.#.#", "#..#.", ".##.#"]),
+    ('R', ["####.", "#...#", "#...#", "####.", "#.#..", "#..#.", "#...#"]),
+    ('S', [".####", "#....", "#....", ".###.", "....#", "....#", "####."]),
+    ('T', ["#####", "..#..", "..#..", "..#..", "..#..", "..#..", "..#.."]),
+    ('U', ["#...#", "#...#", "#...#", "#...#", "#...#", "#...#", ".###."]),
+    ('V', ["#...#", "#...#", "#...#", "#...#", "#...#", ".#.#.", "..#.."]),
+    ('W', ["#...#", "#...#", "#...#", "#.#.#", "#.#.#", "#.#.#", ".#.#."]),
+    ('X', ["#...#", "#...#", ".#.#.", "..#..", ".#.#.", "#...#", "#...#"]),
+    ('Y', ["#...#", "#...#", ".#.#.", "..#..", "..#..", "..#..", "..#.."]),
+    ('Z', ["#####", "....#", "...#.", "..#..", ".#...", "#....", "#####"]),
+    ('[', [".###.", ".#...", ".#...", ".#...", ".#...", ".#...", ".###."]),
+    ('\\', [".....", "#....", ".#...", "..#..", "...#.", "....#", "....."]),
+    (']', [".###.", "...#.", "...#.", "...#.", "...#.", "...#.", ".###."]),
+    ('^', ["..#..", ".#.#.", "#...#", ".....", ".....", ".....", "....."]),
+    ('_', [".....", ".....", ".....", ".....", ".....", ".....", "#####"]),
+    ('`', [".#...", "..#..", "...#.", ".....", ".....", ".....", "....."]),
+    ('a', [".....", ".....", ".###.", "....#", ".####", "#...#", ".####"]),
+    ('b', ["#....", "#....", "#.##.", "##..#", "#...#", "#...#", "####."]),
+    ('c', [".....", ".....", ".###.", "#....", "#....", "#...#", ".###."]),
+    ('d', ["....#", "....#", ".##.#", "#..##", "#...#", "#...#", ".####"]),
+    ('e', [".....", ".....", ".###.", "#...#", "#####", "#....", ".###."]),
+    ('f', ["..##.", ".#..#", ".#...", "###..", ".#...", ".#...", ".#..."]),
+    ('g', [".....", ".####", "#...#", "#...#", ".####", "....#", ".###."]),
+    ('h', ["#....", "#....", "#.##.", "##..#", "#...#", "#...#", "#...#"]),
+    ('i', ["..#..", ".....", ".##..", "..#..", "..#..", "..#..", ".###."]),
+    ('j', ["...#.", ".....", "..##.", "...#.", "...#.", "#..#.", ".##.."]),
+    ('k', ["#....", "#....", "#..#.", "#.#..", "##...", "#.#..", "#..#."]),
+    ('l', [".##..", "..#..", "..#..", "..#..", "..#..", "..#..", ".###."]),
+    ('m', [".....", ".....", "##.#.", "#.#.#", "#.#.#", "#...#", "#...#"]),
+    ('n', [".....", ".....", "#.##.", "##..#", "#...#", "#...#", "#...#"]),
+    ('o', [".....", ".....", ".###.", "#...#", "#...#", "#...#", ".###."]),
+    ('p', [".....", ".....", "####.", "#...#", "####.", "#....", "#...."]),
+    ('q', [".....", ".....", ".##.#", "#..##", ".####", "....#", "....#"]),
+    ('r', [".....", ".....", "#.##.", "##..#", "#....", "#....", "#...."]),
+    ('s', [".....", ".....", ".###.", "#....", ".###.", "....#", "####."]),
+    ('t', [".#...", ".#...", "###..", ".#...", ".#...", ".#..#", "..##."]),
+    ('u', [".....", ".....", "#...#", "#...#", "#...#", "#..##", ".##.#"]),
+    ('v', [".....", ".....", "#...#", "#...#", "#...#", ".#.#.", "..#.."]),
+    ('w', [".....", ".....", "#...#", "#...#", "#.#.#", "#.#.#", ".#.#."]),
+    ('x', [".....", ".....", "#...#", ".#.#.", "..#..", ".#.#.", "#...#"]),
+    ('y', [".....", ".....", "#...#", "#...#", ".####", "....#", ".###."]),
+    ('z', [".....", ".....", "#####", "...#.", "..#..", ".#...", "#####"]),
+    ('{', ["...#.", "..#..", "..#..", ".#...", "..#..", "..#..", "...#."]),
+    ('|', ["..#..", "..#..", "..#..", "..#..", "..#..", "..#..", "..#.."]),
+    ('}', [".#...", "..#..", "..#..", "...#.", "..#..", "..#..", ".#..."]),
+    ('~', [".....", ".....", ".#...", "#.#.#", "...#.", ".....", "....."]),
+    ('↑', ["..#..", ".###.", "#.#.#", "..#..", "..#..", "..#..", "..#.."]),
+    ('←', [".....", "..#..", ".#...", "#####", ".#...", "..#..", "....."]),
+    ('π', [".....", ".....", "#####", ".#.#.", ".#.#.", ".#.#.", ".#..#"]),
+    ('♠', ["..#..", ".###.", "#####", "#####", "..#..", ".###.", "....."]),
+    ('♥', [".....", ".#.#.", "#####", "#####", ".###.", "..#..", "....."]),
+    ('♣', ["..#..", ".###.", "..#..", "##.##", "##.##", "..#..", ".###."]),
+    ('♦', ["..#..", ".###.", "#####", "#####", ".###.", "..#..", "....."]),
+    ('●', [".....", ".###.", "#####", "#####", "#####", ".###.", "....."]),
+    ('○', [".....", ".###.", "#...#", "#...#", "#...#", ".###.", "....."]),
+];
+
+// a line or block character's pixels across the whole cell, if it is one
+fn cell_drawing(c: char, x: usize, y: usize) -> Option<bool> {
+    const MID: usize = CELL / 2 - 1;
+    const HALF: usize = CELL / 2;
+    let (across, down) = (y == MID, x == MID);
+    let lit = match c {
+        '─' => across,
+        '│' => down,
+        '┼' => across || down,
+        '┌' | '╭' => across && x >= MID || down && y >= MID,
+        '┐' | '╮' => across && x <= MID || down && y >= MID,
+        '└' | '╰' => across && x >= MID || down && y <= MID,
+        '┘' | '╯' => across && x <= MID || down && y <= MID,
+        '├' => down || across && x >= MID,
+        '┤' => down || across && x <= MID,
+        '┬' => across || down && y >= MID,
+        '┴' => across || down && y <= MID,
+        '╱' => x + y == CELL - 1,
+        '╲' => x == y,
+        '╳' => x == y || x + y == CELL - 1,
+        '█' => true,
+        '▌' => x < HALF,
+        '▐' => x >= HALF,
+        '▀' => y < HALF,
+        '▄' => y >= HALF,
+        '▔' => y == 0,
+        '▁' => y == CELL - 1,
+        '▂' => y >= CELL - 2,
+        '▃' => y >= CELL - 3,
+        '▏' => x == 0,
+        '▕' => x == CELL - 1,
+        '▎' => x < 2,
+        '▍' => x < 3,
+        '▘' => x < HALF && y < HALF,
+        '▝' => x >= HALF && y < HALF,
+        '▖' => x < HALF && y >= HALF,
+        '▗' => x >= HALF && y >= HALF,
+        '▚' => (x < HALF) == (y < HALF),
+        '▒' => (x + y).is_multiple_of(2),
+        '◤' => x + y < CELL,
+        '◥' => x >= y,
+        '◣' => x <= y,
+        '◢' => x + y >= CELL - 1,
+        _ => return None,
+    };
+    Some(lit)
+}
+
+// whether the pixel at x, y in the cell for c is lit
+pub fn lit(c: char, x: usize, y: usize) -> bool {
+    if let Some(lit) = cell_drawing(c, x, y) {
+        return lit;
+    }
+    let rows = GLYPHS.iter().find(|(glyph, _)| *glyph == c)
+        .or_else(|| GLYPHS.iter().find(|(glyph, _)| *glyph == '?'))
+        .map(|(_, rows)| rows);
+    match rows {
+        Some(rows) if (1..=5).contains(&x) && y < 7 => rows[y].as_bytes()[x - 1] == b'#',
+        _ => false,
+    }
+}
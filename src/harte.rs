@@ -0,0 +1,288 @@
+
+// Tom Harte's single-instruction tests (ProcessorTests)
+//
+// each NN.json file holds thousands of tests for opcode $NN:
+//
+//   { "name": "a9 3c 5e",
+//     "initial": { "pc": 1234, "s": 253, "a": 0, "x": 0, "y": 0, "p": 36,
+//                  "ram": [[1234, 169], [1235, 60]] },
+//     "final":   { ...same fields... },
+//     "cycles":  [[1234, 169, "read"], [1235, 60, "read"]] }
+//
+// a test sets up the initial state, executes one instruction and checks
+// the final registers and memory, and the cycle count. memory keeps a
+// log of the bus as it runs, which is checked against "cycles" entry by
+// entry; the first cycle that differs is reported.
+
+use std::fs;
+use std::panic;
+use std::path::{Path, PathBuf};
+
+use crate::cpu::{is_implemented, opcode_table, Cpu, CpuVariant, STATUS_FLAGS_BREAK};
+use crate::json;
+use crate::json::{Object, Value};
+use crate::memory::{IoAccess, Memory};
+
+// failures shown in full for each file
+const MAX_REPORTED: usize = 3;
+
+pub struct HarteOptions {
+    pub path: String,           // a .json file or a directory of them
+    pub variant: CpuVariant,
+}
+
+// registers and memory before or after a test
+struct CpuState {
+    pc: u16,
+    s: u8,
+    a: u8,
+    x: u8,
+    y: u8,
+    p: u8,
+    ram: Vec<(u16, u8)>,
+}
+
+struct TestCase {
+    name: String,
+    initial: CpuState,
+    expected: CpuState,
+    cycles: Vec<IoAccess>,
+}
+
+fn get_field<'a>(object: &'a Object, key: &str) -> Result<&'a Value, String> {
+    object.get(key).ok_or(format!("missing '{}'", key))
+}
+
+fn get_number(value: &Value, what: &str, max: f64) -> Result<f64, String> {
+    match value {
+        Value::Number(n) if *n >= 0.0 && *n <= max && n.fract() == 0.0 => Ok(*n),
+        Value::Number(n) => Err(format!("'{}' value {} is out of range", what, n)),
+        other => Err(format!("'{}' should be a number, not {}", what, other.type_name())),
+    }
+}
+
+fn get_byte(object: &Object, key: &str) -> Result<u8, String> {
+    Ok(get_number(get_field(object, key)?, key, 255.0)? as u8)
+}
+
+fn get_array<'a>(value: &'a Value, what: &str) -> Result<&'a Vec<Value>, String> {
+    match value {
+        Value::Array(items) => Ok(items),
+        other => Err(format!("'{}' should be an array, not {}", what, other.type_name())),
+    }
+}
+
+fn read_state(value: &Value) -> Result<CpuState, String> {
+    let object = match value {
+        Value::Object(object) => object,
+        other => return Err(format!("cpu state should be an object, not {}", other.type_name())),
+    };
+    let mut ram = Vec::new();
+    for cell in get_array(get_field(object, "ram")?, "ram")? {
+        match get_array(cell, "ram")?.as_slice() {
+            [addr, byte] => ram.push((get_number(addr, "ram", 65535.0)? as u16, get_number(byte, "ram", 255.0)? as u8)),
+            _ => return Err(String::from("'ram' entries should be [address, value]")),
+        }
+    }
+    Ok(CpuState {
+        pc: get_number(get_field(object, "pc")?, "pc", 65535.0)? as u16,
+        s: get_byte(object, "s")?,
+        a: get_byte(object, "a")?,
+        x: get_byte(object, "x")?,
+        y: get_byte(object, "y")?,
+        p: get_byte(object, "p")?,
+        ram,
+    })
+}
+
+// each cycle is [address, value, "read" or "write"]
+fn read_cycles(value: &Value) -> Result<Vec<IoAccess>, String> {
+    let mut cycles = Vec::new();
+    for cycle in get_array(value, "cycles")? {
+        match get_array(cycle, "cycles")?.as_slice() {
+            [addr, byte, Value::String(kind)] if kind == "read" || kind == "write" => cycles.push(IoAccess {
+                addr: get_number(addr, "cycles", 65535.0)? as u16,
+                value: get_number(byte, "cycles", 255.0)? as u8,
+                write: kind == "write",
+            }),
+            _ => return Err(String::from("'cycles' entries should be [address, value, \"read\" or \"write\"]")),
+        }
+    }
+    Ok(cycles)
+}
+
+// a bus cycle as reported
+fn cycle_text(cycle: Option<&IoAccess>) -> String {
+    match cycle {
+        Some(cycle) => format!("{} ${:04x} = ${:02x}", if cycle.write { "write" } else { "read" }, cycle.addr, cycle.value),
+        None => String::from("nothing"),
+    }
+}
+
+fn read_test(value: &Value) -> Result<TestCase, String> {
+    let object = match value {
+        Value::Object(object) => object,
+        other => return Err(format!("a test should be an object, not {}", other.type_name())),
+    };
+    let name = match get_field(object, "name")? {
+        Value::String(name) => name.clone(),
+        other => return Err(format!("'name' should be a string, not {}", other.type_name())),
+    };
+    Ok(TestCase {
+        initial: read_state(get_field(object, "initial")?).map_err(|e| format!("{}: {}", name, e))?,
+        expected: read_state(get_field(object, "final")?).map_err(|e| format!("{}: {}", name, e))?,
+        cycles: read_cycles(get_field(object, "cycles")?).map_err(|e| format!("{}: {}", name, e))?,
+        name,
+    })
+}
+
+// run one test; Err holds the list of differences
+fn run_test(test: &TestCase, cpu: &mut Cpu, mem: &mut Memory) -> Result<(), Vec<String>> {
+    let initial = &test.initial;
+    cpu.pc = initial.pc;
+    cpu.sp = initial.s;
+    cpu.ac = initial.a;
+    cpu.xr = initial.x;
+    cpu.yr = initial.y;
+    cpu.set_status(initial.p);
+    cpu.cycles = 0;
+    for (addr, byte) in &initial.ram {
+        mem.mem[*addr as usize] = *byte;
+    }
+
+    mem.bus_log = Some(Vec::new());
+    let opcode = mem.fetch(cpu.pc);
+    let handler = opcode_table(cpu.variant)[opcode as usize];
+    let outcome = panic::catch_unwind(panic::AssertUnwindSafe(|| handler(cpu, mem)));
+
+    let mut problems = Vec::new();
+    if outcome.is_err() {
+        problems.push(String::from("the opcode handler panicked"));
+    } else {
+        let expected = &test.expected;
+        let registers = [
+            ("a", cpu.ac, expected.a),
+            ("x", cpu.xr, expected.x),
+            ("y", cpu.yr, expected.y),
+            ("sp", cpu.sp, expected.s),
+            // the cpu's register never holds B, whatever was pulled
+            ("p", cpu.status(), expected.p & !STATUS_FLAGS_BREAK),
+        ];
+        for (name, actual, want) in registers {
+            if actual != want {
+                problems.push(format!("{} = ${:02x}, expected ${:02x}", name, actual, want));
+            }
+        }
+        if cpu.pc != expected.pc {
+            problems.push(format!("pc = ${:04x}, expected ${:04x}", cpu.pc, expected.pc));
+        }
+        for (addr, want) in &expected.ram {
+            let actual = mem.mem[*addr as usize];
+            if actual != *want {
+                problems.push(format!("[${:04x}] = ${:02x}, expected ${:02x}", addr, actual, want));
+            }
+        }
+        if cpu.cycles != test.cycles.len() as u64 {
+            problems.push(format!("{} cycles, expected {}", cpu.cycles, test.cycles.len()));
+        }
+        let bus = mem.bus_log.as_deref().unwrap_or(&[]);
+        let differs = (0..bus.len().max(test.cycles.len())).find(|&n| bus.get(n) != test.cycles.get(n));
+        if let Some(n) = differs {
+            problems.push(format!("cycle {}: {}, expected {}", n + 1, cycle_text(bus.get(n)), cycle_text(test.cycles.get(n))));
+        }
+    }
+    mem.bus_log = None;
+
+    // leave memory clean for the next test
+    for (addr, _) in initial.ram.iter().chain(&test.expected.ram) {
+        mem.mem[*addr as usize] = 0;
+    }
+    if problems.is_empty() { Ok(()) } else { Err(problems) }
+}
+
+// run every test in one file and print its result
+// returns the number of tests passed and failed
+fn run_file(path: &Path, variant: CpuVariant) -> Result<(usize, usize), String> {
+    let name = path.to_string_lossy();
+    let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", name, e))?;
+    let document = json::parse(&text).map_err(|e| format!("{}: {}", name, e))?;
+    let tests = get_array(&document, "tests")
+        .and_then(|items| items.iter().map(read_test).collect::<Result<Vec<TestCase>, String>>())
+        .map_err(|e| format!("{}: {}", name, e))?;
+    let file = path.file_name().unwrap_or_default().to_string_lossy();
+
+    // the file is named after the opcode
+    let opcode = path.file_stem()
+        .and_then(|stem| u8::from_str_radix(&stem.to_string_lossy(), 16).ok());
+    if let Some(opcode) = opcode {
+        if !is_implemented(variant, opcode) {
+            println!("SKIP  {}  (opcode ${:02x} is not implemented)", file, opcode);
+            return Ok((0, 0));
+        }
+    }
+
+    let mut cpu = Cpu::new();
+    cpu.variant = variant;
+    let mut mem = Memory::new();
+    let mut failures = Vec::new();
+    for test in &tests {
+        if let Err(problems) = run_test(test, &mut cpu, &mut mem) {
+            failures.push((&test.name, problems));
+        }
+    }
+
+    if failures.is_empty() {
+        println!("PASS  {}  ({} tests)", file, tests.len());
+    } else {
+        println!("FAIL  {}  ({} of {} tests failed)", file, failures.len(), tests.len());
+        for (test, problems) in failures.iter().take(MAX_REPORTED) {
+            println!("      {}: {}", test, problems.join(", "));
+        }
+    }
+    Ok((tests.len() - failures.len(), failures.len()))
+}
+
+// run a test file or every .json file in a directory and print a summary
+// returns true if no test failed
+pub fn run_harte(options: &HarteOptions) -> bool {
+    let path = Path::new(&options.path);
+    let mut files: Vec<PathBuf> = if path.is_dir() {
+        match fs::read_dir(path) {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok().map(|e| e.path()))
+                .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+                .collect(),
+            Err(e) => {
+                eprintln!("e6502r: {}: {}", options.path, e);
+                return false;
+            }
+        }
+    } else {
+        vec![path.to_path_buf()]
+    };
+    files.sort();
+
+    // a panicking handler is reported as a failure, not on stderr
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+
+    let mut passed = 0;
+    let mut failed = 0;
+    let mut ok = true;
+    for file in &files {
+        match run_file(file, options.variant) {
+            Ok((file_passed, file_failed)) => {
+                passed += file_passed;
+                failed += file_failed;
+            }
+            Err(message) => {
+                eprintln!("e6502r: {}", message);
+                ok = false;
+            }
+        }
+    }
+
+    panic::set_hook(default_hook);
+    println!("{} passed, {} failed", passed, failed);
+    ok && failed == 0
+}
@@ -0,0 +1,86 @@
+// memory as xxd hex dumps, to and from the command line tools
+//
+//   0000e000: a9ff 8d00 d04c 00e0 0000 0000 0000 0000  .....L..........
+//
+// a dump's offsets are the addresses the bytes came from, as with
+// xxd -o, so `xxd -r` of one gives an image of the memory from $0000.
+// loading takes each line's bytes to base plus its offset; a plain
+// `xxd rom.bin` is loaded where the rom goes by giving that as the base.
+// only the offset and hex columns are read, so the text column may be
+// left off, and lines that aren't dump lines are an error.
+
+use std::fs;
+
+use crate::memory::{Memory, MEMSIZE};
+
+const LINE_BYTES: usize = 16;
+
+// the memory from first to last, inclusive, in xxd's layout
+pub fn format(mem: &Memory, first: u16, last: u16) -> String {
+    let mut out = String::new();
+    let bytes = &mem.mem[first as usize..=last as usize];
+    for (index, line) in bytes.chunks(LINE_BYTES).enumerate() {
+        let mut hex = String::new();
+        for pair in line.chunks(2) {
+            for byte in pair {
+                hex.push_str(&format!("{:02x}", byte));
+            }
+            hex.push(' ');
+        }
+        let text: String = line.iter().map(|&b| if (0x20..0x7f).contains(&b) { b as char } else { '.' }).collect();
+        let addr = first as usize + index * LINE_BYTES;
+        out.push_str(&format!("{:08x}: {:<40} {}\n", addr, hex, text));
+    }
+    out
+}
+
+pub fn save(mem: &Memory, path: &str, first: u16, last: u16) -> Result<(), String> {
+    fs::write(path, format(mem, first, last)).map_err(|e| format!("{}: {}", path, e))
+}
+
+// load the dump at path with its offsets taken from base; returns the
+// number of bytes loaded
+pub fn load(mem: &mut Memory, path: &str, base: u16) -> Result<usize, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    let mut loaded = 0;
+    for (index, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let (offset, bytes) = parse_line(line).map_err(|message| format!("{}:{}: {}", path, index + 1, message))?;
+        // the offset is whatever the file says, so it may be far past the
+        // end of memory, and past the end of usize with the base added
+        let end = offset.checked_add(base as usize)
+            .and_then(|start| start.checked_add(bytes.len()))
+            .filter(|&end| end <= MEMSIZE)
+            .ok_or_else(|| format!("{}:{}: offset {:x} from ${:04x} is past the end of memory", path, index + 1, offset, base))?;
+        mem.mem[end - bytes.len()..end].copy_from_slice(&bytes);
+        loaded += bytes.len();
+    }
+    Ok(loaded)
+}
+
+// hex digits and nothing else; from_str_radix would take a sign too
+fn parse_hex(text: &str) -> Option<usize> {
+    text.chars().all(|c| c.is_ascii_hexdigit()).then(|| usize::from_str_radix(text, 16).ok()).flatten()
+}
+
+// the offset and bytes of a dump line; the hex column ends at two
+// spaces, where the text column starts
+fn parse_line(line: &str) -> Result<(usize, Vec<u8>), String> {
+    let (offset, rest) = line.split_once(':').ok_or("not an xxd line")?;
+    let offset = parse_hex(offset.trim()).ok_or_else(|| format!("bad offset '{}'", offset.trim()))?;
+    let rest = rest.strip_prefix(' ').unwrap_or(rest);
+    let hex = rest.split("  ").next().unwrap_or("");
+    let digits: Vec<char> = hex.chars().filter(|c| *c != ' ').collect();
+    if !digits.len().is_multiple_of(2) {
+        return Err(String::from("odd number of hex digits"));
+    }
+    let bytes = digits.chunks(2)
+        .map(|pair| {
+            let text: String = pair.iter().collect();
+            parse_hex(&text).map(|byte| byte as u8).ok_or_else(|| format!("bad byte '{}'", text))
+        })
+        .collect::<Result<Vec<u8>, String>>()?;
+    Ok((offset, bytes))
+}
@@ -0,0 +1,571 @@
+// http control of a headless run, with --http PORT
+//
+//   GET  /registers              the registers as json, and whether running
+//   POST /pause                  stop running
+//   POST /resume                 carry on running
+//   POST /step?count=N           run N instructions (default 1) and stay paused
+//   GET  /memory?addr=A&len=N    N bytes (default 1) from A
+//   PUT  /memory?addr=A          write the request body at A
+//   POST /load?addr=A            write the body at A and set the pc to A
+//   POST /quit                   end the run
+//   GET  /debug                  a websocket for live debugging
+//
+// addresses are hex, as on the command line, and memory goes both ways
+// as raw bytes. everything but memory reads answers with the registers.
+//
+// a debug websocket takes commands as json text messages and sends
+// events back the same way, every one with an "event" field:
+//
+//   {"cmd":"registers"}                 registers (as every command
+//   {"cmd":"pause"}                       below without an event of its
+//   {"cmd":"resume"}                      own answers)
+//   {"cmd":"step","count":N}            registers, once the N are done
+//   {"cmd":"read","addr":A,"len":N}     memory, with data in hex
+//   {"cmd":"write","addr":A,"data":HEX}
+//   {"cmd":"break","addr":A}            breakpoints, the list of them
+//   {"cmd":"clear","addr":A}            breakpoints
+//   {"cmd":"trace","on":BOOL}           watching
+//   {"cmd":"io","on":BOOL}              watching
+//
+// and a bad command is answered with an error event. numbers in json
+// are decimal. every socket is sent a stop event when execution stops
+// for a breakpoint or any other reason; a socket with trace on gets a
+// trace event for each instruction, with its address, disassembly and
+// the registers after it, and one with io on an io event for each read
+// or write that reaches a device.
+//
+// connections are taken one at a time on a background thread, which
+// hands each request to the main loop and waits for the answer, and a
+// websocket gets a thread of its own to read commands. the main loop
+// looks for requests between instructions, so an answer never sees an
+// instruction half done.
+
+use std::collections::BTreeMap;
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread;
+use std::time::Duration;
+
+use crate::args::parse_addr;
+use crate::cpu::Cpu;
+use crate::disasm::disassemble;
+use crate::json;
+use crate::json::{Object, Value};
+use crate::memory::{Memory, MEMSIZE};
+use crate::remote;
+use crate::remote::{RemoteAction, RemoteControl};
+use crate::websocket;
+use crate::websocket::Message;
+use crate::StopReason;
+
+// a request body can fill memory, and no more
+const MAX_BODY: usize = MEMSIZE;
+
+// connections are served one at a time, so one that sends nothing is
+// given up on after this rather than holding up the rest
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+// what the connection threads pass to the main loop
+enum Incoming {
+    Request(Request),
+    Opened(usize, TcpStream),       // a debug socket, by number, and its stream
+    Command(usize, String),
+    Ping(usize, Vec<u8>),
+    Closed(usize),
+}
+
+struct Request {
+    method: String,
+    path: String,
+    query: BTreeMap<String, String>,
+    body: Vec<u8>,
+    reply: Sender<Response>,
+}
+
+struct Response {
+    status: &'static str,
+    content_type: &'static str,
+    body: Vec<u8>,
+}
+
+impl Response {
+    fn text(status: &'static str, text: &str) -> Response {
+        Response { status, content_type: "text/plain", body: format!("{}\n", text).into_bytes() }
+    }
+
+    fn registers(cpu: &Cpu, running: bool) -> Response {
+        let json = format!("{{{}}}\n", register_fields(cpu, running));
+        Response { status: "200 OK", content_type: "application/json", body: json.into_bytes() }
+    }
+}
+
+// a debug websocket
+struct Session {
+    id: usize,
+    out: BufWriter<TcpStream>,
+    trace: bool,
+    io: bool,
+}
+
+// who asked for a step
+enum Stepper {
+    Request(Sender<Response>),
+    Session(usize),
+}
+
+pub struct HttpControl {
+    incoming: Receiver<Incoming>,
+    sessions: Vec<Session>,
+    stepping: Option<(Stepper, u64)>,       // and the instructions left to run
+}
+
+impl HttpControl {
+    // listen on addr, like 127.0.0.1:6502
+    pub fn start(addr: &str) -> Result<HttpControl, String> {
+        let listener = TcpListener::bind(addr).map_err(|e| format!("{}: {}", addr, e))?;
+        let (tx, rx) = channel();
+        thread::spawn(move || {
+            let mut sessions = 0;
+            for stream in listener.incoming().flatten() {
+                // a client that goes away only loses its own answer
+                if let Err(Disconnected) = serve_connection(stream, &tx, &mut sessions) {
+                    return;
+                }
+            }
+        });
+        Ok(HttpControl { incoming: rx, sessions: Vec::new(), stepping: None })
+    }
+
+}
+
+impl RemoteControl for HttpControl {
+    // answer the next request or command, waiting for one if wait is set
+    fn serve(&mut self, cpu: &mut Cpu, mem: &mut Memory, breakpoints: &mut Vec<u16>, running: bool, wait: bool)
+        -> RemoteAction {
+        if wait {
+            self.flush();
+        }
+        let incoming = match remote::receive(&self.incoming, wait) {
+            Ok(incoming) => incoming,
+            Err(action) => return action,
+        };
+        // a step can't be asked for while one is under way
+        let busy = running || self.stepping.is_some();
+        match incoming {
+            Incoming::Request(request) => match handle(&request, cpu, mem, busy) {
+                Ok(Handled::Step(count)) => {
+                    self.stepping = Some((Stepper::Request(request.reply), count));
+                    RemoteAction::None
+                }
+                Ok(Handled::Action(action)) => {
+                    let _result = request.reply.send(Response::registers(cpu, running_after(&action, running)));
+                    action
+                }
+                Err(answer) => {
+                    let _result = request.reply.send(answer);
+                    RemoteAction::None
+                }
+            },
+            Incoming::Opened(id, stream) => {
+                self.sessions.push(Session { id, out: BufWriter::new(stream), trace: false, io: false });
+                self.send(id, &registers_event(cpu, running));
+                self.flush();
+                RemoteAction::None
+            }
+            Incoming::Command(id, text) => {
+                let (event, action) = match self.command(id, &text, mem, breakpoints, busy) {
+                    Ok(Handled::Step(count)) => {
+                        self.stepping = Some((Stepper::Session(id), count));
+                        return RemoteAction::None;
+                    }
+                    Ok(Handled::Action(action)) => (registers_event(cpu, running_after(&action, running)), action),
+                    Err(Answer::Event(event)) => (event, RemoteAction::None),
+                    Err(Answer::Error(message)) => {
+                        (format!("{{\"event\":\"error\",\"message\":{}}}", json::quote(&message)), RemoteAction::None)
+                    }
+                };
+                self.send(id, &event);
+                self.flush();
+                action
+            }
+            Incoming::Ping(id, payload) => {
+                if let Some(session) = self.sessions.iter_mut().find(|session| session.id == id) {
+                    let _result = websocket::write_pong(&mut session.out, &payload);
+                }
+                RemoteAction::None
+            }
+            Incoming::Closed(id) => {
+                self.sessions.retain(|session| session.id != id);
+                if let Some((Stepper::Session(stepper), _)) = &self.stepping {
+                    if *stepper == id {
+                        self.stepping = None;
+                    }
+                }
+                if !self.sessions.iter().any(|session| session.io) {
+                    mem.io_log = None;
+                }
+                RemoteAction::None
+            }
+        }
+    }
+
+    // after the instruction at pc: send the events it made and finish
+    // a step once it has run its count or stopped short
+    fn after_instruction(&mut self, cpu: &Cpu, mem: &mut Memory, pc: u16, stop: Option<&StopReason>) {
+        if self.sessions.iter().any(|session| session.trace) {
+            let (text, _) = disassemble(cpu.variant, mem, pc);
+            let event = format!("{{\"event\":\"trace\",\"addr\":{},\"text\":{},{}}}",
+                pc, json::quote(&text), register_fields(cpu, true));
+            self.broadcast(|session| session.trace, &event);
+        }
+        if let Some(log) = mem.io_log.as_mut() {
+            for access in log.drain(..) {
+                let event = format!("{{\"event\":\"io\",\"addr\":{},\"value\":{},\"write\":{}}}",
+                    access.addr, access.value, access.write);
+                self.broadcast(|session| session.io, &event);
+            }
+        }
+        if let Some(reason) = stop {
+            let event = format!("{{\"event\":\"stop\",\"reason\":{},{}}}", json::quote(&reason.to_string()),
+                register_fields(cpu, false));
+            self.broadcast(|_| true, &event);
+        }
+
+        let Some((_, left)) = &mut self.stepping else { return };
+        *left = if stop.is_some() { 0 } else { *left - 1 };
+        if *left == 0 {
+            match self.stepping.take() {
+                Some((Stepper::Request(reply), _)) => {
+                    let _result = reply.send(Response::registers(cpu, false));
+                }
+                Some((Stepper::Session(id), _)) => {
+                    self.send(id, &registers_event(cpu, false));
+                    self.flush();
+                }
+                None => {}
+            }
+        }
+    }
+
+    fn stepping(&self) -> bool {
+        self.stepping.is_some()
+    }
+}
+
+impl HttpControl {
+    // the answer to a debug socket's command
+    fn command(&mut self, id: usize, text: &str, mem: &mut Memory, breakpoints: &mut Vec<u16>, busy: bool) -> Result<Handled, Answer> {
+        let command = match json::parse(text) {
+            Ok(Value::Object(command)) => command,
+            Ok(_) => return Err(Answer::Error(String::from("a command should be a json object"))),
+            Err(message) => return Err(Answer::Error(message)),
+        };
+        let name = match command.get("cmd") {
+            Some(Value::String(name)) => name.as_str(),
+            _ => return Err(Answer::Error(String::from("a command needs a \"cmd\" string"))),
+        };
+        match name {
+            "registers" => Ok(Handled::Action(RemoteAction::None)),
+            "pause" => Ok(Handled::Action(RemoteAction::Pause)),
+            "resume" => Ok(Handled::Action(RemoteAction::Resume)),
+            "step" => {
+                if busy {
+                    return Err(Answer::Error(String::from("pause before stepping")));
+                }
+                match command.get("count") {
+                    None => Ok(Handled::Step(1)),
+                    Some(_) => Ok(Handled::Step(number(&command, "count", 1, u64::MAX)?)),
+                }
+            }
+            "read" => {
+                let addr = number(&command, "addr", 0, 0xffff)? as usize;
+                let len = match command.get("len") {
+                    None => 1,
+                    Some(_) => number(&command, "len", 0, (MEMSIZE - addr) as u64)? as usize,
+                };
+                let data: String = mem.mem[addr..addr + len].iter().map(|byte| format!("{:02x}", byte)).collect();
+                Err(Answer::Event(format!("{{\"event\":\"memory\",\"addr\":{},\"data\":\"{}\"}}", addr, data)))
+            }
+            "write" => {
+                let addr = number(&command, "addr", 0, 0xffff)? as usize;
+                let data = match command.get("data") {
+                    Some(Value::String(hex)) => parse_hex(hex).ok_or(Answer::Error(format!("bad hex '{}'", hex)))?,
+                    _ => return Err(Answer::Error(String::from("write needs data, a string of hex"))),
+                };
+                if addr + data.len() > MEMSIZE {
+                    return Err(Answer::Error(format!("{} bytes at {} don't fit in memory", data.len(), addr)));
+                }
+                mem.mem[addr..addr + data.len()].copy_from_slice(&data);
+                Ok(Handled::Action(RemoteAction::None))
+            }
+            "break" | "clear" => {
+                let addr = number(&command, "addr", 0, 0xffff)? as u16;
+                breakpoints.retain(|&breakpoint| breakpoint != addr);
+                if name == "break" {
+                    breakpoints.push(addr);
+                }
+                let list: Vec<String> = breakpoints.iter().map(|addr| addr.to_string()).collect();
+                Err(Answer::Event(format!("{{\"event\":\"breakpoints\",\"addrs\":[{}]}}", list.join(","))))
+            }
+            "trace" | "io" => {
+                let on = match command.get("on") {
+                    Some(Value::Boolean(on)) => *on,
+                    _ => return Err(Answer::Error(format!("{} needs on, true or false", name))),
+                };
+                let Some(session) = self.sessions.iter_mut().find(|session| session.id == id) else {
+                    return Ok(Handled::Action(RemoteAction::None));
+                };
+                if name == "trace" {
+                    session.trace = on;
+                } else {
+                    session.io = on;
+                }
+                let event = format!("{{\"event\":\"watching\",\"trace\":{},\"io\":{}}}", session.trace, session.io);
+                let io = self.sessions.iter().any(|session| session.io);
+                match (io, &mem.io_log) {
+                    (true, None) => mem.io_log = Some(Vec::new()),
+                    (false, Some(_)) => mem.io_log = None,
+                    _ => {}
+                }
+                Err(Answer::Event(event))
+            }
+            _ => Err(Answer::Error(format!("unknown command '{}'", name))),
+        }
+    }
+
+    fn send(&mut self, id: usize, event: &str) {
+        self.broadcast(|session| session.id == id, event);
+    }
+
+    fn broadcast(&mut self, to: impl Fn(&Session) -> bool, event: &str) {
+        for session in self.sessions.iter_mut().filter(|session| to(session)) {
+            let _result = websocket::write_message(&mut session.out, event);
+        }
+    }
+
+    fn flush(&mut self) {
+        for session in &mut self.sessions {
+            let _result = session.out.flush();
+        }
+    }
+}
+
+// what a request or command asks the main loop for
+enum Handled {
+    Action(RemoteAction),     // answered with the registers
+    Step(u64),              // answered once the step is done
+}
+
+// a debug socket's answer that isn't the registers
+enum Answer {
+    Event(String),
+    Error(String),
+}
+
+fn running_after(action: &RemoteAction, running: bool) -> bool {
+    match action {
+        RemoteAction::Pause | RemoteAction::Quit => false,
+        RemoteAction::Resume => true,
+        RemoteAction::None => running,
+    }
+}
+
+fn register_fields(cpu: &Cpu, running: bool) -> String {
+    format!("\"pc\":{},\"a\":{},\"x\":{},\"y\":{},\"sp\":{},\"p\":{},\"cycles\":{},\"running\":{}",
+        cpu.pc, cpu.ac, cpu.xr, cpu.yr, cpu.sp, cpu.status(), cpu.cycles, running)
+}
+
+fn registers_event(cpu: &Cpu, running: bool) -> String {
+    format!("{{\"event\":\"registers\",{}}}", register_fields(cpu, running))
+}
+
+// a whole number field from min to max
+fn number(command: &Object, key: &str, min: u64, max: u64) -> Result<u64, Answer> {
+    match command.get(key) {
+        Some(Value::Number(n)) if n.fract() == 0.0 && *n >= min as f64 && *n <= max as f64 => Ok(*n as u64),
+        _ => Err(Answer::Error(format!("{} should be a number from {} to {}", key, min, max))),
+    }
+}
+
+fn parse_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len()).step_by(2).map(|index| u8::from_str_radix(text.get(index..index + 2)?, 16).ok()).collect()
+}
+
+// what the main loop should do, or an answer of its own: memory or an error
+fn handle(request: &Request, cpu: &mut Cpu, mem: &mut Memory, running: bool) -> Result<Handled, Response> {
+    match (request.method.as_str(), request.path.as_str()) {
+        ("GET", "/registers") => Ok(Handled::Action(RemoteAction::None)),
+        ("POST", "/pause") => Ok(Handled::Action(RemoteAction::Pause)),
+        ("POST", "/resume") => Ok(Handled::Action(RemoteAction::Resume)),
+        ("POST", "/step") => {
+            if running {
+                return Err(Response::text("409 Conflict", "pause before stepping"));
+            }
+            match request.query.get("count") {
+                Some(text) => match text.parse::<u64>() {
+                    Ok(count) if count > 0 => Ok(Handled::Step(count)),
+                    _ => Err(Response::text("400 Bad Request", &format!("bad count '{}'", text))),
+                },
+                None => Ok(Handled::Step(1)),
+            }
+        }
+        ("GET", "/memory") => Err(read_memory(request, mem)?),
+        ("PUT", "/memory") => {
+            write_memory(request, mem)?;
+            Ok(Handled::Action(RemoteAction::None))
+        }
+        ("POST", "/load") => {
+            cpu.pc = write_memory(request, mem)?;
+            Ok(Handled::Action(RemoteAction::None))
+        }
+        ("POST", "/quit") => Ok(Handled::Action(RemoteAction::Quit)),
+        ("GET", "/debug") => Err(Response::text("426 Upgrade Required", "/debug is a websocket")),
+        (_, "/registers" | "/pause" | "/resume" | "/step" | "/memory" | "/load" | "/quit" | "/debug") => {
+            Err(Response::text("405 Method Not Allowed", &format!("{} can't be used on {}", request.method, request.path)))
+        }
+        _ => Err(Response::text("404 Not Found", &format!("nothing at {}", request.path))),
+    }
+}
+
+fn address(request: &Request) -> Result<u16, Response> {
+    let text = request.query.get("addr").ok_or_else(|| Response::text("400 Bad Request", "addr is needed"))?;
+    parse_addr(text).map_err(|message| Response::text("400 Bad Request", &message))
+}
+
+// the request body put in memory at addr; returns addr
+fn write_memory(request: &Request, mem: &mut Memory) -> Result<u16, Response> {
+    let addr = address(request)?;
+    let start = addr as usize;
+    if start + request.body.len() > MEMSIZE {
+        return Err(Response::text("400 Bad Request",
+            &format!("{} bytes at ${:04x} don't fit in memory", request.body.len(), addr)));
+    }
+    mem.mem[start..start + request.body.len()].copy_from_slice(&request.body);
+    Ok(addr)
+}
+
+fn read_memory(request: &Request, mem: &Memory) -> Result<Response, Response> {
+    let addr = address(request)? as usize;
+    let len = match request.query.get("len").map(|text| text.parse::<usize>()) {
+        None => 1,
+        Some(Ok(len)) if addr + len <= MEMSIZE => len,
+        Some(_) => return Err(Response::text("400 Bad Request", "len should be a count of bytes that fits in memory")),
+    };
+    Ok(Response { status: "200 OK", content_type: "application/octet-stream", body: mem.mem[addr..addr + len].to_vec() })
+}
+
+// the main loop has finished with requests
+struct Disconnected;
+
+// read a request and pass it on, then write back the answer or, for a
+// websocket, start reading its commands
+fn serve_connection(stream: TcpStream, incoming: &Sender<Incoming>, sessions: &mut usize) -> Result<(), Disconnected> {
+    if stream.set_read_timeout(Some(READ_TIMEOUT)).is_err() {
+        return Ok(());
+    }
+    let mut reader = BufReader::new(match stream.try_clone() {
+        Ok(stream) => stream,
+        Err(_) => return Ok(()),
+    });
+    let response = match read_request(&mut reader) {
+        Ok(Head { method, target, headers, body }) => {
+            let (path, query) = match target.split_once('?') {
+                Some((path, query)) => (path.to_string(), parse_query(query)),
+                None => (target, BTreeMap::new()),
+            };
+            let upgrade = headers.get("upgrade").is_some_and(|value| value.eq_ignore_ascii_case("websocket"));
+            if let (true, "GET", "/debug", Some(key)) = (upgrade, method.as_str(), path.as_str(), headers.get("sec-websocket-key")) {
+                // a debug socket is read on its own thread, and may be
+                // quiet for as long as it likes
+                let mut stream = stream;
+                if stream.set_read_timeout(None).is_err()
+                    || stream.write_all(websocket::handshake_response(key).as_bytes()).is_err() {
+                    return Ok(());
+                }
+                *sessions += 1;
+                return open_session(*sessions, stream, reader, incoming);
+            }
+            let (reply, answer) = channel();
+            incoming.send(Incoming::Request(Request { method, path, query, body, reply })).map_err(|_| Disconnected)?;
+            answer.recv().map_err(|_| Disconnected)?
+        }
+        Err(response) => response,
+    };
+    let mut stream = stream;
+    let head = format!("HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        response.status, response.content_type, response.body.len());
+    let _result = stream.write_all(head.as_bytes()).and_then(|_| stream.write_all(&response.body));
+    Ok(())
+}
+
+// hand a debug socket to the main loop and read its commands on a thread
+fn open_session(id: usize, stream: TcpStream, mut reader: BufReader<TcpStream>, incoming: &Sender<Incoming>)
+    -> Result<(), Disconnected> {
+    incoming.send(Incoming::Opened(id, stream)).map_err(|_| Disconnected)?;
+    let incoming = incoming.clone();
+    thread::spawn(move || loop {
+        let message = match websocket::read_message(&mut reader) {
+            Ok(Some(Message::Text(text))) => Incoming::Command(id, text),
+            Ok(Some(Message::Ping(payload))) => Incoming::Ping(id, payload),
+            Ok(None) | Err(_) => {
+                let _result = incoming.send(Incoming::Closed(id));
+                return;
+            }
+        };
+        if incoming.send(message).is_err() {
+            return;
+        }
+    });
+    Ok(())
+}
+
+// a request as read off the connection
+struct Head {
+    method: String,
+    target: String,
+    headers: BTreeMap<String, String>,      // by lowercase name
+    body: Vec<u8>,
+}
+
+fn read_request(reader: &mut BufReader<TcpStream>) -> Result<Head, Response> {
+    let bad = |text: &str| Response::text("400 Bad Request", text);
+    let mut line = String::new();
+    reader.read_line(&mut line).map_err(|_| bad("unreadable request"))?;
+    let mut words = line.split_whitespace();
+    let (Some(method), Some(target)) = (words.next(), words.next()) else { return Err(bad("bad request line")) };
+    let (method, target) = (method.to_string(), target.to_string());
+
+    let mut headers = BTreeMap::new();
+    loop {
+        let mut header = String::new();
+        reader.read_line(&mut header).map_err(|_| bad("unreadable header"))?;
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            headers.insert(name.trim().to_lowercase(), value.trim().to_string());
+        }
+    }
+    let length = match headers.get("content-length") {
+        Some(value) => value.parse::<usize>().map_err(|_| bad("bad Content-Length"))?,
+        None => 0,
+    };
+    if length > MAX_BODY {
+        return Err(Response::text("413 Content Too Large", "a body can't be bigger than memory"));
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).map_err(|_| bad("body shorter than its Content-Length"))?;
+    Ok(Head { method, target, headers, body })
+}
+
+fn parse_query(query: &str) -> BTreeMap<String, String> {
+    query.split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect()
+}
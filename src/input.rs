@@ -0,0 +1,215 @@
+
+// line input from the host terminal
+//
+// stdin is read on a background thread so the main loop can poll for
+// commands while the program runs; the thread is only started the
+// first time input is needed, so non-interactive runs never touch stdin
+//
+// while a keyboard device is running the program, key mode sends each
+// byte to the emulated keyboard instead, with the terminal in raw mode
+// so keys arrive as they are typed. enter still comes as a newline, and
+// ctrl-c, which raw mode delivers as a byte, does what SIGINT would.
+// ctrl-] asks the main loop to pause, and ctrl-^ pastes the host
+// clipboard. the keys and pasted text wait here until the main loop
+// passes them to the machine's own inputs with pass_keys.
+//
+// with --ctrl-c, ctrl-c is caught while the program runs rather than
+// ending the process, and the main loop picks it up with take_ctrl_c.
+
+use std::collections::VecDeque;
+use std::io;
+use std::io::{IsTerminal, Read};
+use std::process;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver};
+use std::sync::{Mutex, OnceLock};
+use std::thread;
+
+use crossterm::terminal;
+
+use crate::replay::Inputs;
+
+pub const PAUSE_KEY: u8 = 0x1d;         // ctrl-]
+pub const PASTE_KEY: u8 = 0x1e;         // ctrl-^, ctrl-6 on most keyboards
+const CTRL_C_KEY: u8 = 0x03;
+
+// programs that print the host clipboard, tried in turn
+const CLIPBOARD_COMMANDS: [(&str, &[&str]); 5] = [
+    ("pbpaste", &[]),
+    ("wl-paste", &["--no-newline"]),
+    ("xclip", &["-out", "-selection", "clipboard"]),
+    ("xsel", &["--clipboard", "--output"]),
+    ("powershell", &["-noprofile", "-command", "Get-Clipboard"]),
+];
+
+// typed or pasted, for the machine
+enum Typed {
+    Key(u8),
+    Paste(String),
+}
+
+static LINES: OnceLock<Mutex<Receiver<String>>> = OnceLock::new();
+static TYPED: Mutex<VecDeque<Typed>> = Mutex::new(VecDeque::new());
+static TYPED_WAITING: AtomicBool = AtomicBool::new(false);
+static KEY_MODE: AtomicBool = AtomicBool::new(false);
+static PAUSE_REQUESTED: AtomicBool = AtomicBool::new(false);
+static CATCHING_CTRL_C: AtomicBool = AtomicBool::new(false);
+static CTRL_C: AtomicBool = AtomicBool::new(false);
+
+fn lines() -> &'static Mutex<Receiver<String>> {
+    LINES.get_or_init(|| {
+        let (tx, rx) = channel();
+        thread::spawn(move || {
+            let mut stdin = io::stdin();
+            let mut line = Vec::new();
+            let mut buf = [0u8; 256];
+            loop {
+                let count = match stdin.read(&mut buf) {
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                    Ok(0) | Err(_) => return,
+                    Ok(count) => count,
+                };
+                for &byte in &buf[..count] {
+                    if KEY_MODE.load(Ordering::Relaxed) {
+                        if byte == PAUSE_KEY {
+                            PAUSE_REQUESTED.store(true, Ordering::Relaxed);
+                        } else if byte == PASTE_KEY {
+                            match clipboard() {
+                                Ok(text) => type_in(Typed::Paste(text)),
+                                Err(message) => eprint!("e6502r: {}\r\n", message),
+                            }
+                        } else if byte == CTRL_C_KEY {
+                            if CATCHING_CTRL_C.load(Ordering::Relaxed) {
+                                CTRL_C.store(true, Ordering::Relaxed);
+                            } else {
+                                let _result = terminal::disable_raw_mode();
+                                process::exit(130);
+                            }
+                        } else if byte == b'\r' {
+                            type_in(Typed::Key(b'\n'));
+                        } else {
+                            type_in(Typed::Key(byte));
+                        }
+                        continue;
+                    }
+                    line.push(byte);
+                    if byte == b'\n' {
+                        let text = String::from_utf8_lossy(&line).into_owned();
+                        line.clear();
+                        if tx.send(text).is_err() {
+                            return;
+                        }
+                    }
+                }
+            }
+        });
+        Mutex::new(rx)
+    })
+}
+
+// wait for the next line; None at end of input
+pub fn read_line() -> Option<String> {
+    lines().lock().ok()?.recv().ok()
+}
+
+// the next line if one has been typed; None as well at end of input
+pub fn poll_line() -> Option<String> {
+    lines().lock().ok()?.try_recv().ok()
+}
+
+fn type_in(typed: Typed) {
+    if let Ok(mut queue) = TYPED.lock() {
+        queue.push_back(typed);
+        TYPED_WAITING.store(true, Ordering::Relaxed);
+    }
+}
+
+// hand what was typed in key mode, or pasted, since the last call to the
+// machine's inputs; called between instructions, so mostly there is
+// nothing and that costs no lock
+pub fn pass_keys(inputs: &Inputs) {
+    if !TYPED_WAITING.swap(false, Ordering::Relaxed) {
+        return;
+    }
+    let Ok(mut queue) = TYPED.lock() else { return };
+    for typed in queue.drain(..) {
+        match typed {
+            Typed::Key(key) => inputs.push_key(key),
+            Typed::Paste(text) => {
+                inputs.paste(&text);
+            }
+        }
+    }
+}
+
+// the host clipboard's text
+pub fn clipboard() -> Result<String, String> {
+    for (program, args) in CLIPBOARD_COMMANDS {
+        let output = Command::new(program).args(args).stdin(Stdio::null()).stderr(Stdio::null()).output();
+        match output {
+            Ok(output) if output.status.success() => return Ok(String::from_utf8_lossy(&output.stdout).into_owned()),
+            _ => {}
+        }
+    }
+    Err(String::from("can't read the clipboard: none of pbpaste, wl-paste, xclip, xsel or powershell worked"))
+}
+
+// true once after ctrl-] was typed in key mode
+pub fn take_pause_request() -> bool {
+    PAUSE_REQUESTED.swap(false, Ordering::Relaxed)
+}
+
+// catch ctrl-c, or go back to letting it end the process; one caught
+// before is forgotten when catching starts again
+pub fn catch_ctrl_c(on: bool) {
+    if CATCHING_CTRL_C.swap(on, Ordering::Relaxed) == on {
+        return;
+    }
+    CTRL_C.store(false, Ordering::Relaxed);
+    set_sigint_handler(on);
+}
+
+// true once after ctrl-c was caught
+pub fn take_ctrl_c() -> bool {
+    CTRL_C.swap(false, Ordering::Relaxed)
+}
+
+#[cfg(unix)]
+fn set_sigint_handler(on: bool) {
+    extern "C" {
+        fn signal(signum: i32, handler: Option<extern "C" fn(i32)>) -> Option<extern "C" fn(i32)>;
+    }
+    const SIGINT: i32 = 2;
+
+    // only an atomic store, which is safe in a signal handler
+    extern "C" fn caught(_signum: i32) {
+        CTRL_C.store(true, Ordering::Relaxed);
+    }
+    // SAFETY: the handler does nothing a signal handler can't, and None
+    // is SIG_DFL, which ends the process as before
+    unsafe {
+        signal(SIGINT, if on { Some(caught) } else { None });
+    }
+}
+
+// elsewhere ctrl-c ends the run as it always has
+#[cfg(not(unix))]
+fn set_sigint_handler(_on: bool) {}
+
+// switch between key mode and line mode
+// an Err says the terminal could not be switched; keys then only
+// arrive as each line is entered
+pub fn set_key_mode(on: bool) -> Result<(), String> {
+    if KEY_MODE.swap(on, Ordering::Relaxed) == on {
+        return Ok(());
+    }
+    if on {
+        lines();
+    }
+    if !io::stdin().is_terminal() {
+        return Ok(());
+    }
+    let switched = if on { terminal::enable_raw_mode() } else { terminal::disable_raw_mode() };
+    switched.map_err(|e| format!("can't switch the terminal {} raw mode: {}", if on { "to" } else { "out of" }, e))
+}
@@ -0,0 +1,218 @@
+// parser for JSON documents such as the single-instruction test files
+//
+// numbers are kept as f64; strings support the standard escapes
+// including \uXXXX (surrogate pairs are not combined)
+
+use std::collections::BTreeMap;
+
+pub type Object = BTreeMap<String, Value>;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Null,
+    Boolean(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Value>),
+    Object(Object),
+}
+
+impl Value {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Null => "null",
+            Value::Boolean(_) => "boolean",
+            Value::Number(_) => "number",
+            Value::String(_) => "string",
+            Value::Array(_) => "array",
+            Value::Object(_) => "object",
+        }
+    }
+}
+
+// text as a json string, quoted and escaped
+pub fn quote(text: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            c if (c as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+// parse a complete document
+pub fn parse(text: &str) -> Result<Value, String> {
+    let mut parser = Parser { chars: text.chars().collect(), pos: 0, line: 1 };
+    let result = parser.value().and_then(|value| {
+        parser.skip_space();
+        match parser.peek() {
+            None => Ok(value),
+            Some(c) => Err(format!("unexpected '{}' after the document", c)),
+        }
+    });
+    result.map_err(|e| format!("line {}: {}", parser.line, e))
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    line: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek();
+        if c == Some('\n') {
+            self.line += 1;
+        }
+        self.pos += 1;
+        c
+    }
+
+    fn expect(&mut self, want: char) -> Result<(), String> {
+        match self.next() {
+            Some(c) if c == want => Ok(()),
+            Some(c) => Err(format!("expected '{}', found '{}'", want, c)),
+            None => Err(format!("expected '{}' at end of input", want)),
+        }
+    }
+
+    fn skip_space(&mut self) {
+        while let Some(' ') | Some('\t') | Some('\r') | Some('\n') = self.peek() {
+            self.next();
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        self.skip_space();
+        match self.peek() {
+            Some('{') => self.object(),
+            Some('[') => self.array(),
+            Some('"') => Ok(Value::String(self.string()?)),
+            Some('-') | Some('0'..='9') => self.number(),
+            Some(c) if c.is_ascii_alphabetic() => self.keyword(),
+            Some(c) => Err(format!("unexpected '{}'", c)),
+            None => Err(String::from("expected a value at end of input")),
+        }
+    }
+
+    fn object(&mut self) -> Result<Value, String> {
+        self.expect('{')?;
+        let mut object = Object::new();
+        self.skip_space();
+        if self.peek() == Some('}') {
+            self.next();
+            return Ok(Value::Object(object));
+        }
+        loop {
+            self.skip_space();
+            let key = self.string()?;
+            self.skip_space();
+            self.expect(':')?;
+            let value = self.value()?;
+            object.insert(key, value);
+            self.skip_space();
+            match self.next() {
+                Some(',') => {}
+                Some('}') => return Ok(Value::Object(object)),
+                Some(c) => return Err(format!("expected ',' or '}}', found '{}'", c)),
+                None => return Err(String::from("unterminated object")),
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Value, String> {
+        self.expect('[')?;
+        let mut items = Vec::new();
+        self.skip_space();
+        if self.peek() == Some(']') {
+            self.next();
+            return Ok(Value::Array(items));
+        }
+        loop {
+            items.push(self.value()?);
+            self.skip_space();
+            match self.next() {
+                Some(',') => {}
+                Some(']') => return Ok(Value::Array(items)),
+                Some(c) => return Err(format!("expected ',' or ']', found '{}'", c)),
+                None => return Err(String::from("unterminated array")),
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        let mut text = String::new();
+        loop {
+            match self.next() {
+                Some('"') => return Ok(text),
+                Some('\\') => {
+                    let c = match self.next() {
+                        Some('"') => '"',
+                        Some('\\') => '\\',
+                        Some('/') => '/',
+                        Some('b') => '\u{8}',
+                        Some('f') => '\u{c}',
+                        Some('n') => '\n',
+                        Some('r') => '\r',
+                        Some('t') => '\t',
+                        Some('u') => {
+                            let digits: String = (0..4).filter_map(|_| self.next()).collect();
+                            if digits.len() != 4 || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+                                return Err(format!("bad escape '\\u{}'", digits));
+                            }
+                            // half a surrogate pair is no char of its own
+                            u32::from_str_radix(&digits, 16)
+                                .ok()
+                                .and_then(char::from_u32)
+                                .unwrap_or('\u{fffd}')
+                        }
+                        Some(c) => return Err(format!("unknown escape '\\{}'", c)),
+                        None => return Err(String::from("unterminated string")),
+                    };
+                    text.push(c);
+                }
+                Some(c) => text.push(c),
+                None => return Err(String::from("unterminated string")),
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<Value, String> {
+        let start = self.pos;
+        while let Some('-') | Some('+') | Some('.') | Some('e') | Some('E') | Some('0'..='9') = self.peek() {
+            self.next();
+        }
+        let text: String = self.chars[start..self.pos].iter().collect();
+        text.parse::<f64>()
+            .map(Value::Number)
+            .map_err(|_| format!("bad number '{}'", text))
+    }
+
+    fn keyword(&mut self) -> Result<Value, String> {
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            if !c.is_ascii_alphabetic() {
+                break;
+            }
+            self.next();
+        }
+        let word: String = self.chars[start..self.pos].iter().collect();
+        match word.as_str() {
+            "true" => Ok(Value::Boolean(true)),
+            "false" => Ok(Value::Boolean(false)),
+            "null" => Ok(Value::Null),
+            _ => Err(format!("unexpected '{}'", word)),
+        }
+    }
+}
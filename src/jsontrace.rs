@@ -0,0 +1,59 @@
+// the instruction trace as json lines, for scripts rather than people
+//
+// a line for each instruction, written once it has run:
+//
+//   {"pc":1024,"opcode":141,"operands":[0,2],"text":"STA $0200",
+//    "a":65,"x":0,"y":0,"sp":253,"p":36,"cycles":7,"took":4,
+//    "memory":[{"addr":512,"value":65,"write":true}]}
+//
+// the registers and cycle count are from before the instruction, and
+// took is the cycles it ran for. memory is the data it read and wrote
+// in order, the stack's included but not the opcode and operands. an
+// interrupt taken after the instruction is counted in it, pushes and
+// all; a BRK the monitor traps shows as a BRK that took nothing.
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use crate::cpu::Cpu;
+use crate::disasm::disassemble;
+use crate::json;
+use crate::memory::Memory;
+
+pub struct JsonTrace {
+    out: BufWriter<File>,
+    line: String,       // the instruction's fields from before it ran
+    start: u64,         // the cycle count it started at
+}
+
+impl JsonTrace {
+    pub fn create(path: &str) -> Result<JsonTrace, String> {
+        let file = File::create(path).map_err(|e| format!("{}: {}", path, e))?;
+        Ok(JsonTrace { out: BufWriter::new(file), line: String::new(), start: 0 })
+    }
+
+    // note the instruction at the pc and start logging its accesses
+    pub fn before(&mut self, cpu: &Cpu, mem: &mut Memory) {
+        let (text, len) = disassemble(cpu.variant, mem, cpu.pc);
+        let operands: Vec<String> = (1..len).map(|i| mem.mem[cpu.pc.wrapping_add(i) as usize].to_string()).collect();
+        self.line = format!(
+            "{{\"pc\":{},\"opcode\":{},\"operands\":[{}],\"text\":{},\"a\":{},\"x\":{},\"y\":{},\"sp\":{},\"p\":{},\"cycles\":{}",
+            cpu.pc, mem.mem[cpu.pc as usize], operands.join(","), json::quote(&text),
+            cpu.ac, cpu.xr, cpu.yr, cpu.sp, cpu.status(), cpu.cycles);
+        self.start = cpu.cycles;
+        mem.start_access_log();
+    }
+
+    // write the line for the instruction now it has run
+    pub fn after(&mut self, cpu: &Cpu, mem: &Memory) {
+        let accesses: Vec<String> = mem.access_log.iter().flatten()
+            .map(|access| format!("{{\"addr\":{},\"value\":{},\"write\":{}}}", access.addr, access.value, access.write))
+            .collect();
+        let _result = writeln!(self.out, "{},\"took\":{},\"memory\":[{}]}}", self.line, cpu.cycles - self.start,
+            accesses.join(","));
+    }
+
+    pub fn flush(&mut self) {
+        let _result = self.out.flush();
+    }
+}
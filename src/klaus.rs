@@ -0,0 +1,101 @@
+
+// Klaus Dormann's 6502 functional test
+//
+// the published binary (6502_functional_test.bin) is a 64k image that
+// loads at $0000 and starts at $0400. a failed check traps in a
+// self-loop right after it; success is the self-loop at $3469. the
+// number of the test being run is kept at $0200.
+//
+// the 65c02 extended opcodes test (65C02_extended_opcodes_test.bin) is
+// built the same way but succeeds by looping at $24f1, and is run with
+// the cpu set to the 65c02.
+
+use crate::cpu::{reset_cpu, Cpu, CpuVariant};
+use crate::memory::Memory;
+use crate::monitor::print_registers;
+use crate::{load_image, print_code_around, run_for, StopConditions, StopReason};
+
+pub const KLAUS_LOAD_ADDR: u16 = 0x0000;
+pub const KLAUS_START_ADDR: u16 = 0x0400;
+pub const KLAUS_SUCCESS_ADDR: u16 = 0x3469;
+pub const KLAUS_TEST_CASE_ADDR: u16 = 0x0200;
+pub const KLAUS_65C02_SUCCESS_ADDR: u16 = 0x24f1;
+
+// give up if the test hasn't finished after this many instructions
+const MAX_INSTRUCTIONS: u64 = 500_000_000;
+
+// cycles run between checks on the instruction count
+const BLOCK_CYCLES: i64 = 1_000_000;
+
+pub struct KlausOptions {
+    pub path: String,
+    pub variant: CpuVariant,
+    pub start: u16,         // where execution starts
+    pub success: u16,       // address of the success self-loop
+    pub test_case: u16,     // where the current test number is kept
+}
+
+// success address of the published test binary for a cpu variant
+pub fn default_success_addr(variant: CpuVariant) -> u16 {
+    match variant {
+        CpuVariant::Nmos6502 | CpuVariant::Ricoh2a03 => KLAUS_SUCCESS_ADDR,
+        CpuVariant::Cmos65c02 => KLAUS_65C02_SUCCESS_ADDR,
+    }
+}
+
+// run the functional test and report the outcome
+// returns true if it reached the success loop
+pub fn run_klaus(options: &KlausOptions) -> bool {
+    let name = match options.variant {
+        CpuVariant::Nmos6502 | CpuVariant::Ricoh2a03 => "functional test",
+        CpuVariant::Cmos65c02 => "65c02 extended opcodes test",
+    };
+    let mut cpu = Cpu::new();
+    cpu.variant = options.variant;
+    let mut mem = Memory::new();
+    if let Err(message) = load_image(&mut mem, &options.path, KLAUS_LOAD_ADDR) {
+        eprintln!("e6502r: {}", message);
+        return false;
+    }
+    reset_cpu(&mut cpu, &mem);
+    cpu.pc = options.start;
+
+    let stops = StopConditions {
+        trap_on_brk: false,
+        strict_opcodes: true,
+        stop_at: Vec::new(),
+        smc: None,
+    };
+    let mut executed: u64 = 0;
+    let mut leftover = 0;
+    let reason = loop {
+        if executed >= MAX_INSTRUCTIONS {
+            break None;
+        }
+        let block = run_for(&mut cpu, &mut mem, &stops, BLOCK_CYCLES + leftover);
+        executed += block.executed;
+        leftover = block.leftover;
+        if block.stop.is_some() {
+            break block.stop;
+        }
+    };
+
+    let test_case = mem.mem[options.test_case as usize];
+    match reason {
+        Some(StopReason::SelfLoop(addr)) if addr == options.success => {
+            println!("{} passed: {} instructions, {} cycles", name, executed, cpu.cycles);
+            true
+        }
+        Some(reason) => {
+            println!("{} failed in test ${:02x}: {}", name, test_case, reason);
+            print_registers(&cpu);
+            print_code_around(&cpu, &mem);
+            false
+        }
+        None => {
+            println!("{} still running in test ${:02x} after {} instructions at ${:04x}",
+                name, test_case, executed, cpu.pc);
+            false
+        }
+    }
+}
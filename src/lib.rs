@@ -0,0 +1,22 @@
+// the emulator's core: the cpu, memory and devices, and running them
+// with run::step and run::run_for. the e6502r command is built on it, as
+// are the c library, the browser build, the python and lua bindings and
+// the fuzz target. the xxd dumps, the json parser, the websocket
+// protocol the command's debug connection speaks and the threads random
+// runs are shared over are here too, for their tests
+
+pub mod cpu;
+pub mod devices;
+pub mod disasm;
+pub mod dynarec;
+pub mod font;
+pub mod hexdump;
+pub mod json;
+pub mod memory;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod parallel;
+pub mod png;
+pub mod replay;
+pub mod run;
+pub mod toml;
+pub mod websocket;
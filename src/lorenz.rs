@@ -0,0 +1,279 @@
+
+// Wolfgang Lorenz's C64 cpu test suite
+//
+// each test is a .prg file (two byte load address, then the program)
+// started from its BASIC stub at $0816. the tests only need a few
+// KERNAL routines, which are trapped here instead of emulating a C64:
+//
+//   $ffd2  CHROUT   print the PETSCII character in A
+//   $e16f  LOAD     load the test named at ($bb) with length ($b7)
+//                   and run it
+//   $ffe4  GETIN    a test waits for a key after reporting an error
+//   $8000, $a474    the BASIC warm start; the suite has finished
+//
+// BRK and IRQ go through a copy of the KERNAL IRQ handler at $ff48,
+// which jumps through the vectors the tests install at $0314/$0316.
+//
+// a test that stops on an opcode not implemented yet is skipped. it
+// never gets to load the next test, so the suite goes on with the test
+// after it in SUITE_ORDER.
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::cpu::{Cpu, STATUS_BIT_INT_DIS, STATUS_FLAGS_UNUSED};
+use crate::memory::Memory;
+use crate::monitor::print_registers;
+use crate::{print_code_around, step, StopConditions, StopReason};
+
+const LORENZ_RUN_ADDR: u16 = 0x0816;
+const CHROUT_ADDR: u16 = 0xffd2;
+const LOAD_ADDR: u16 = 0xe16f;
+const GETIN_ADDR: u16 = 0xffe4;
+const WARM_START_ADDRS: [u16; 2] = [0x8000, 0xa474];
+const IRQ_HANDLER_ADDR: u16 = 0xff48;
+
+// PHA TXA PHA TYA PHA TSX LDA $0104,X AND #$10 BEQ +3 JMP ($0316) JMP ($0314)
+const IRQ_HANDLER: [u8; 19] = [
+    0x48, 0x8a, 0x48, 0x98, 0x48, 0xba, 0xbd, 0x04, 0x01,
+    0x29, 0x10, 0xf0, 0x03, 0x6c, 0x16, 0x03, 0x6c, 0x14, 0x03,
+];
+
+// the suite's tests in the order each loads the next
+const SUITE_ORDER: &[&str] = &[
+    "start",
+    "ldab", "ldaz", "ldazx", "ldaa", "ldaax", "ldaay", "ldaix", "ldaiy",
+    "staz", "stazx", "staa", "staax", "staay", "staix", "staiy",
+    "ldxb", "ldxz", "ldxzy", "ldxa", "ldxay",
+    "stxz", "stxzy", "stxa",
+    "ldyb", "ldyz", "ldyzx", "ldya", "ldyax",
+    "styz", "styzx", "stya",
+    "taxn", "tayn", "txan", "tyan", "tsxn", "txsn",
+    "phan", "plan", "phpn", "plpn",
+    "inxn", "inyn", "dexn", "deyn",
+    "incz", "inczx", "inca", "incax",
+    "decz", "deczx", "deca", "decax",
+    "asln", "aslz", "aslzx", "asla", "aslax",
+    "lsrn", "lsrz", "lsrzx", "lsra", "lsrax",
+    "roln", "rolz", "rolzx", "rola", "rolax",
+    "rorn", "rorz", "rorzx", "rora", "rorax",
+    "andb", "andz", "andzx", "anda", "andax", "anday", "andix", "andiy",
+    "orab", "oraz", "orazx", "oraa", "oraax", "oraay", "oraix", "oraiy",
+    "eorb", "eorz", "eorzx", "eora", "eorax", "eoray", "eorix", "eoriy",
+    "clcn", "secn", "cldn", "sedn", "clin", "sein", "clvn",
+    "adcb", "adcz", "adczx", "adca", "adcax", "adcay", "adcix", "adciy",
+    "sbcb", "sbcz", "sbczx", "sbca", "sbcax", "sbcay", "sbcix", "sbciy",
+    "cmpb", "cmpz", "cmpzx", "cmpa", "cmpax", "cmpay", "cmpix", "cmpiy",
+    "cpxb", "cpxz", "cpxa",
+    "cpyb", "cpyz", "cpya",
+    "bitz", "bita",
+    "brkn", "rtin", "jsrw", "rtsn", "jmpw", "jmpi",
+    "beqr", "bner", "bmir", "bplr", "bcsr", "bccr", "bvsr", "bvcr",
+    "nopn", "nopb", "nopz", "nopzx", "nopa", "nopax",
+    "asoz", "asozx", "asoa", "asoax", "asoay", "asoix", "asoiy",
+    "rlaz", "rlazx", "rlaa", "rlaax", "rlaay", "rlaix", "rlaiy",
+    "lsez", "lsezx", "lsea", "lseax", "lseay", "lseix", "lseiy",
+    "rraz", "rrazx", "rraa", "rraax", "rraay", "rraix", "rraiy",
+    "axsz", "axszy", "axsa", "axsix",
+    "laxz", "laxzy", "laxa", "laxay", "laxix", "laxiy",
+    "dcmz", "dcmzx", "dcma", "dcmax", "dcmay", "dcmix", "dcmiy",
+    "insz", "inszx", "insa", "insax", "insay", "insix", "insiy",
+    "alrb", "arrb", "aneb", "lxab", "sbxb",
+    "shaay", "shaiy", "shxay", "shyax", "shsay",
+    "ancb", "lasay", "sbcb(eb)",
+    "trap1", "trap2", "trap3", "trap4", "trap5", "trap6", "trap7", "trap8", "trap9",
+    "trap10", "trap11", "trap12", "trap13", "trap14", "trap15", "trap16", "trap17",
+    "branchwrap", "mmufetch", "mmu", "cpuport", "cputiming", "irq", "nmi",
+    "cia1tb123", "cia2tb123", "cia1pb6", "cia1pb7", "cia2pb6", "cia2pb7",
+    "cia1ta", "cia1tb", "cia2ta", "cia2tb", "loadth", "cnto2", "icr01", "imr",
+    "flipos", "oneshot", "finish",
+];
+
+// give up on a single test after this many instructions
+const MAX_INSTRUCTIONS: u64 = 200_000_000;
+
+pub struct LorenzOptions {
+    pub dir: String,
+    pub first: String,      // name of the first test to load
+}
+
+// how a test ended
+enum Outcome {
+    Next(String),           // the test passed and asked for the next one
+    Finished,               // back to BASIC
+    Skipped(String),        // stopped on an opcode not implemented yet
+    Failed(String),
+}
+
+// find the file for a test name, with or without a .prg suffix
+fn find_test(dir: &str, name: &str) -> Option<PathBuf> {
+    let name = name.trim().to_lowercase();
+    [name.clone(), format!("{}.prg", name)].iter()
+        .map(|file| Path::new(dir).join(file))
+        .find(|path| path.is_file())
+}
+
+// the first test after name in the suite's order that is in dir and
+// has not run yet
+fn next_in_order(dir: &str, name: &str, ran: &HashSet<String>) -> Option<String> {
+    let name = name.trim().to_lowercase();
+    let position = SUITE_ORDER.iter().position(|test| *test == name)?;
+    SUITE_ORDER[position + 1..].iter()
+        .find(|test| !ran.contains(**test) && find_test(dir, test).is_some())
+        .map(|test| test.to_string())
+}
+
+// load a .prg at the address in its header
+fn load_prg(mem: &mut Memory, path: &Path) -> Result<(), String> {
+    let name = path.to_string_lossy();
+    let bytes = fs::read(path).map_err(|e| format!("{}: {}", name, e))?;
+    if bytes.len() < 2 {
+        return Err(format!("{}: too short for a .prg", name));
+    }
+    let addr = u16::from_le_bytes([bytes[0], bytes[1]]) as usize;
+    let body = &bytes[2..];
+    if addr + body.len() > mem.mem.len() {
+        return Err(format!("{}: {} bytes do not fit at ${:04x}", name, body.len(), addr));
+    }
+    mem.mem[addr..addr + body.len()].copy_from_slice(body);
+    Ok(())
+}
+
+// the C64 state the tests expect to find
+fn setup_machine(cpu: &mut Cpu, mem: &mut Memory) {
+    mem.mem[0x0002] = 0x00;
+    mem.mem[0xa002] = 0x00;
+    mem.mem[0xa003] = 0x80;
+    mem.mem[0x01fe] = 0xff;
+    mem.mem[0x01ff] = 0x7f;
+    mem.mem[0xfffe] = (IRQ_HANDLER_ADDR & 0xff) as u8;
+    mem.mem[0xffff] = (IRQ_HANDLER_ADDR >> 8) as u8;
+    let handler = IRQ_HANDLER_ADDR as usize;
+    mem.mem[handler..handler + IRQ_HANDLER.len()].copy_from_slice(&IRQ_HANDLER);
+
+    // the trapped routines return with RTS once handled
+    mem.mem[CHROUT_ADDR as usize] = 0x60;
+    mem.mem[GETIN_ADDR as usize] = 0x60;
+
+    cpu.sp = 0xfd;
+    cpu.set_status(STATUS_FLAGS_UNUSED | STATUS_BIT_INT_DIS);
+    cpu.pc = LORENZ_RUN_ADDR;
+}
+
+// convert a PETSCII character for the terminal
+fn petscii_to_char(c: u8) -> Option<char> {
+    match c {
+        0x0d => Some('\n'),
+        0x41..=0x5a => Some((c + 0x20) as char),
+        0x61..=0x7a => Some((c - 0x20) as char),
+        0xc1..=0xda => Some((c - 0x80) as char),
+        0x20..=0x40 | 0x5b..=0x5f => Some(c as char),
+        _ => None,
+    }
+}
+
+// run the loaded test until it asks for the next one or stops
+// output printed by the test is collected in `output`
+fn run_test(cpu: &mut Cpu, mem: &mut Memory, output: &mut String) -> Outcome {
+    let stops = StopConditions {
+        trap_on_brk: false,
+        strict_opcodes: true,
+        stop_at: Vec::new(),
+        smc: None,
+    };
+    for _ in 0..MAX_INSTRUCTIONS {
+        match cpu.pc {
+            CHROUT_ADDR => {
+                if let Some(c) = petscii_to_char(cpu.ac) {
+                    output.push(c);
+                }
+            }
+            LOAD_ADDR => {
+                let len = mem.mem[0xb7] as u16;
+                let start = u16::from_le_bytes([mem.mem[0xbb], mem.mem[0xbc]]);
+                let name: String = (0..len)
+                    .map(|i| mem.mem[start.wrapping_add(i) as usize])
+                    .filter_map(petscii_to_char)
+                    .collect();
+                return Outcome::Next(name);
+            }
+            GETIN_ADDR => return Outcome::Failed(String::from("waiting for a key after an error")),
+            pc if WARM_START_ADDRS.contains(&pc) => return Outcome::Finished,
+            _ => {}
+        }
+        match step(cpu, mem, &stops) {
+            Some(reason @ StopReason::Unimplemented(..)) => return Outcome::Skipped(reason.to_string()),
+            Some(reason) => return Outcome::Failed(reason.to_string()),
+            None => {}
+        }
+    }
+    Outcome::Failed(format!("still running after {} instructions at ${:04x}", MAX_INSTRUCTIONS, cpu.pc))
+}
+
+// run the suite from the first test, following each test's request to
+// load the next, and print a summary
+// returns true if no test failed; skipped tests are not failures
+pub fn run_lorenz(options: &LorenzOptions) -> bool {
+    let mut cpu = Cpu::new();
+    let mut mem = Memory::new();
+    let mut name = options.first.clone();
+    let mut ran = HashSet::new();
+    let mut passed = 0;
+    let mut skipped = 0;
+    loop {
+        ran.insert(name.trim().to_lowercase());
+        let path = match find_test(&options.dir, &name) {
+            Some(path) => path,
+            None if passed + skipped > 0 => {
+                // the last test asks for one that is not part of the suite
+                println!("next test '{}' not found; stopping", name.trim());
+                break;
+            }
+            None => {
+                eprintln!("e6502r: no test '{}' in {}", name.trim(), options.dir);
+                return false;
+            }
+        };
+        if let Err(message) = load_prg(&mut mem, &path) {
+            eprintln!("e6502r: {}", message);
+            return false;
+        }
+        setup_machine(&mut cpu, &mut mem);
+
+        let mut output = String::new();
+        let outcome = run_test(&mut cpu, &mut mem, &mut output);
+        match outcome {
+            Outcome::Next(next) => {
+                println!("PASS  {}", name.trim());
+                passed += 1;
+                name = next;
+            }
+            Outcome::Finished => {
+                println!("PASS  {}", name.trim());
+                passed += 1;
+                break;
+            }
+            Outcome::Skipped(reason) => {
+                println!("SKIP  {}: {}", name.trim(), reason);
+                skipped += 1;
+                match next_in_order(&options.dir, &name, &ran) {
+                    Some(next) => name = next,
+                    None => break,
+                }
+            }
+            Outcome::Failed(reason) => {
+                println!("FAIL  {}: {}", name.trim(), reason);
+                for line in output.lines().filter(|line| !line.trim().is_empty()) {
+                    println!("      {}", line);
+                }
+                print_registers(&cpu);
+                print_code_around(&cpu, &mem);
+                println!("{} passed, {} skipped, 1 failed", passed, skipped);
+                return false;
+            }
+        }
+    }
+    println!("{} passed, {} skipped, 0 failed", passed, skipped);
+    true
+}
@@ -0,0 +1,175 @@
+
+// machine configuration files
+//
+//   name = "my sbc"
+//   cpu = "6502"
+//   clock = "1MHz"
+//   ram = "32K"
+//   reset = 0x8000
+//   power-on = "sp=fd,random"  # registers at power on, as --power-on
+//
+//   [[rom]]
+//   file = "monitor.bin"      # relative to the configuration file
+//   address = 0xe000
+//
+//   [[device]]
+//   type = "console"
+//   base = 0xf000             # other keys are options for the device
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::args::parse_frequency;
+use crate::cpu::CpuVariant;
+use crate::devices::DeviceConfig;
+use crate::poweron;
+use crate::poweron::PowerOn;
+use crate::profiles;
+use crate::toml;
+use crate::toml::{get_string, Table, Value};
+use crate::memory::MEMSIZE;
+
+// a rom image and where it goes in memory
+pub struct RomImage {
+    pub path: PathBuf,
+    pub addr: u16,
+}
+
+pub struct MachineConfig {
+    pub name: String,
+    pub variant: Option<CpuVariant>,
+    pub clock_hz: Option<f64>,
+    pub ram_size: usize,
+    pub reset_vector: Option<u16>,
+    pub power_on: Option<PowerOn>,
+    pub roms: Vec<RomImage>,
+    pub devices: Vec<DeviceConfig>,
+}
+
+// read and check a machine configuration file, or use the built-in
+// profile of that name with its roms from rom_dir
+pub fn load_machine(path: &str, rom_dir: &Path) -> Result<MachineConfig, String> {
+    if let Some(machine) = profiles::builtin(path, rom_dir) {
+        return Ok(machine);
+    }
+    let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    let root = toml::parse(&text).map_err(|e| format!("{}: {}", path, e))?;
+    let dir = Path::new(path).parent().unwrap_or(Path::new("."));
+    machine_from_table(&root, dir).map_err(|e| format!("{}: {}", path, e))
+}
+
+fn machine_from_table(root: &Table, dir: &Path) -> Result<MachineConfig, String> {
+    let mut machine = MachineConfig {
+        name: String::from("custom"),
+        variant: None,
+        clock_hz: None,
+        ram_size: MEMSIZE,
+        reset_vector: None,
+        power_on: None,
+        roms: Vec::new(),
+        devices: Vec::new(),
+    };
+
+    for (key, value) in root {
+        match key.as_str() {
+            "name" => machine.name = get_string(key, value)?,
+            "cpu" => machine.variant = Some(get_string(key, value)?.parse()?),
+            "clock" => machine.clock_hz = Some(match value {
+                Value::String(text) => parse_frequency(text)?,
+                _ => get_integer(key, value)? as f64,
+            }),
+            "ram" => {
+                let size = match value {
+                    Value::String(text) => parse_size(text)?,
+                    _ => get_integer(key, value)? as usize,
+                };
+                if size > MEMSIZE {
+                    return Err(format!("ram size {} is larger than 64K", size));
+                }
+                machine.ram_size = size;
+            }
+            "reset" => machine.reset_vector = Some(get_addr(key, value)?),
+            "power-on" => machine.power_on = Some(poweron::parse(&get_string(key, value)?)?),
+            "rom" => {
+                for table in get_tables(key, value)? {
+                    let file = get_string("file", require(table, "rom", "file")?)?;
+                    machine.roms.push(RomImage {
+                        path: dir.join(file),
+                        addr: get_addr("address", require(table, "rom", "address")?)?,
+                    });
+                }
+            }
+            "device" => {
+                for table in get_tables(key, value)? {
+                    let mut options = table.clone();
+                    options.remove("type");
+                    options.remove("base");
+                    machine.devices.push(DeviceConfig {
+                        kind: get_string("type", require(table, "device", "type")?)?,
+                        base: get_addr("base", require(table, "device", "base")?)?,
+                        options,
+                    });
+                }
+            }
+            _ => return Err(format!("unknown key '{}'", key)),
+        }
+    }
+    Ok(machine)
+}
+
+// one line description for the startup banner
+pub fn describe(machine: &MachineConfig, variant: CpuVariant) -> String {
+    let clock = match machine.clock_hz {
+        Some(hz) => format!(" at {:.3} MHz", hz / 1e6),
+        None => String::new(),
+    };
+    format!("machine '{}': {}{}, {}K RAM, {} rom(s), {} device(s)",
+        machine.name, variant, clock, machine.ram_size / 1024, machine.roms.len(), machine.devices.len())
+}
+
+// sizes like 32768, 32K or 0x8000
+pub fn parse_size(text: &str) -> Result<usize, String> {
+    let trimmed = text.trim();
+    let upper = trimmed.to_uppercase();
+    let size = if let Some(k) = upper.strip_suffix('K') {
+        k.trim().parse::<usize>().ok().map(|k| k * 1024)
+    } else if let Some(hex) = trimmed.strip_prefix("0x") {
+        usize::from_str_radix(hex, 16).ok()
+    } else {
+        trimmed.parse::<usize>().ok()
+    };
+    size.ok_or(format!("bad size '{}'", text))
+}
+
+pub fn require<'a>(table: &'a Table, section: &str, key: &str) -> Result<&'a Value, String> {
+    table.get(key).ok_or(format!("{} is missing '{}'", section, key))
+}
+
+pub fn get_integer(key: &str, value: &Value) -> Result<i64, String> {
+    match value {
+        Value::Integer(n) => Ok(*n),
+        _ => Err(format!("'{}' should be an integer, not {}", key, value.type_name())),
+    }
+}
+
+// addresses are integers, or strings in monitor syntax like "$e000"
+pub fn get_addr(key: &str, value: &Value) -> Result<u16, String> {
+    match value {
+        Value::String(text) => crate::args::parse_addr(text),
+        _ => {
+            let n = get_integer(key, value)?;
+            u16::try_from(n).map_err(|_| format!("'{}' address {} is out of range", key, n))
+        }
+    }
+}
+
+pub fn get_tables<'a>(key: &str, value: &'a Value) -> Result<Vec<&'a Table>, String> {
+    let items = match value {
+        Value::Array(items) => items,
+        _ => return Err(format!("'{}' should be an array of tables", key)),
+    };
+    items.iter().map(|item| match item {
+        Value::Table(table) => Ok(table),
+        _ => Err(format!("'{}' should be an array of tables", key)),
+    }).collect()
+}
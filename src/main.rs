@@ -1,192 +1,686 @@
 
 // ported from c
 
+mod args;
+mod ca65;
+mod color;
+mod coredump;
+mod cosim;
+mod dap;
+mod dbginfo;
+mod diffstate;
+mod harte;
+mod http;
+mod input;
+mod jsontrace;
+mod klaus;
+mod lorenz;
+mod machine;
+mod memtools;
+mod monitor;
+mod nes;
+mod pacer;
+mod poweron;
+mod profiles;
+mod randomtest;
+mod remote;
+mod stackview;
+mod stats;
+mod state;
+mod testrunner;
+mod tui;
+
+use std::env;
+use std::fs;
+use std::fs::File;
 use std::io;
+use std::io::{BufWriter, Write};
+use std::panic;
+use std::panic::AssertUnwindSafe;
+use std::path::Path;
+use std::process;
+use std::time::Instant;
 
-const MEMSIZE: usize = 65536;               // memory size 64k
-const RESET_VECTOR_LOBYTE: usize = 0xfffc;  // reset vector memory location
-const RESET_VECTOR_HIBYTE: usize = 0xfffd;
-const BREAK_VECTOR_LOBYTE: usize = 0xfffe;  // break vector memory location
-const BREAK_VECTOR_HIBYTE: usize = 0xffff;
-const STATUS_BIT_INT_DIS: u8 = 0x04;        // interrup disable status bit
-const STATUS_FLAGS_BREAK: u8 = 0x10;        // break status bit
-const STATUS_FLAGS_UNUSED: u8 = 0x20;       // unused status bit
-
-// instruction text by opcode
-const INSTRUCTION_TEXT: [&str; 256] = [
-	"BRK",  "ORA","",     "","",        "ORA","ASL",     "","PHP","ORA", "ASL", "","",       "ORA", "ASL", "", // 00
-	"BPL",  "ORA","",     "","",        "ORA","ASL",     "","CLC","ORA", "",    "","",       "ORA", "ASL", "", // 10
-	"JSR",  "AND","",     "","BIT",     "AND","ROL",     "","PLP","AND", "ROL", "","BIT",    "AND", "ROL", "", // 20
-	"BMI",  "AND","",     "","",        "AND","ROL",     "","SEC","AND", "",    "","",       "AND", "ROL", "", // 30
-	"RTI",  "EOR","",     "","",        "EOR","LSR",     "","PHA","EOR", "LSR", "","JMP",    "EOR", "LSR", "", // 40
-	"BVC",  "EOR","",     "","",        "EOR","LSR",     "","CLI","EOR", "",    "","",       "EOR", "LSR", "", // 50
-	"RTS",  "ADC","",     "","",        "ADC","ROR",     "","PLA","ADC", "ROR", "","JMP",    "ADC", "ROR", "", // 60
-	"BVS",  "ADC","",     "","",        "ADC","ROR",     "","SEI","ADC", "",    "","",       "ADC", "ROR", "", // 70
-	"",     "STA","",     "","STY",     "STA","STX",     "","DEY","",    "TXA", "","STY",    "STA", "STX", "", // 80
-	"BCC",  "STA","",     "","STY",     "STA","STX",     "","TYA","STA", "TXS", "","",       "STA", "",    "", // 90
-	"LDY",  "LDA","LDX",  "","LDY",     "LDA","LDX",     "","TAY","LDA", "TAX", "","LDY",    "LDA", "LDX", "", // 0a
-	"BCS",  "LDA","",     "","LDY",     "LDA","LDX",     "","CLV","LDA", "TSX", "","LDY",    "LDA", "LDX", "", // 0b
-	"CPY",  "CMP","",     "","CPY",     "CMP","DEC",     "","INY","CMP", "DEX", "","CPY",    "CMP", "DEC", "", // 0c
-	"BNE",  "CMP","",     "","",        "CMP","DEC",     "","CLD","CMP", "",    "","",       "CMP", "DEC", "", // 0d
-	"CPX",  "SBC","",     "","CPX",     "SBC","INC",     "","INX","SBC", "NOP", "","CPX",    "SBC", "INC", "", // 0e
-	"BEQ",  "SBC","",     "","",        "SBX","INC",     "","SED","SBC", "",    "","",       "SBX", "INC", ""  // 0f
-];
-
-// CPU
-struct Cpu {
-    pc: u16,
-    sp: u8,
-    ac: u8,
-    xr: u8,
-    yr: u8,
-    st: u8,
-}
+use args::{CtrlCMode, ParsedArgs};
+use e6502r::{cpu, devices, disasm, hexdump, json, memory, parallel, png, replay, run, toml, websocket};
+use run::{run_for, step, StopConditions, StopReason};
+use coredump::History;
+use cosim::CoSim;
+use dap::DapControl;
+use dbginfo::DebugInfo;
+use devices::DeviceConfig;
+use http::HttpControl;
+use jsontrace::JsonTrace;
+use cpu::{is_implemented, reset_cpu, Cpu, CpuVariant, RESET_VECTOR_HIBYTE, RESET_VECTOR_LOBYTE};
+use memory::{init_memory, IoAccess, Memory, MEMSIZE};
+use monitor::{elapsed_text, Monitor, MonitorAction};
+use pacer::Pacer;
+use remote::{RemoteAction, RemoteControl};
+use tui::Tui;
 
-// MEMORY
-struct Memory {
-    mem: Vec<u8>,
-}
+const POLL_INTERVAL: u64 = 1024;            // instructions between checks for typed commands
+const BENCH_BLOCK: i64 = 10_000;            // cycles --bench runs between looks at the clock
 
-// convert two bytes (hi and lo) to a word
-fn byte_to_word(lobyte: u8, hibyte: u8) -> u16 {
-    ((hibyte as u16) << 8) | lobyte as u16
+// print the code surrounding the pc
+fn print_code_around(cpu: &Cpu, mem: &Memory) {
+    for (addr, text) in disasm::disassemble_around(cpu.variant, mem, cpu.pc, 4, 4) {
+        let marker = if addr == cpu.pc { ">" } else { " " };
+        println!("{} {}\t{}", marker, color::address(addr), color::instruction(&text));
+    }
 }
 
-// initialize memory with zero's
-fn init_memory(mem: &mut Memory) {
-    for i in 0..MEMSIZE {
-        mem.mem[i] = 0x00;
+// the memory an instruction read and wrote, for the end of its trace line
+//   = #$41 -> [$0200], #$07 <- [$0012]
+fn accesses_text(accesses: &[IoAccess], colored: bool) -> String {
+    let accesses: Vec<String> = accesses.iter()
+        .map(|access| {
+            let addr = if colored { color::address(access.addr) } else { format!("${:04x}", access.addr) };
+            let arrow = if access.write { "->" } else { "<-" };
+            format!("#${:02x} {} [{}]", access.value, arrow, addr)
+        })
+        .collect();
+    if accesses.is_empty() {
+        String::new()
+    } else {
+        format!(" = {}", accesses.join(", "))
     }
 }
 
-// reset cpu
-// set stack pointer to 0xff
-// set program counter to reset vector
-// set unused bit on status flag (assuming starts at zero)
-fn reset_cpu(cpu: &mut Cpu, mem: &Memory) {
-    cpu.sp = 0xff;
-    cpu.pc = byte_to_word(mem.mem[RESET_VECTOR_LOBYTE], mem.mem[RESET_VECTOR_HIBYTE]);
-    cpu.st = cpu.st | STATUS_FLAGS_UNUSED;
+// run flat out for the given number of seconds and report the speed,
+// through run_for as the other hosts run, with the devices, interrupts
+// and stop checks each instruction has
+fn run_bench(cpu: &mut Cpu, mem: &mut Memory, seconds: f64) {
+    let stops = StopConditions::default();
+    let start = Instant::now();
+    let start_cycles = cpu.cycles;
+    let mut instructions: u64 = 0;
+    let elapsed = loop {
+        // only look at the clock every block; a stop, as at a loop
+        // jumping to itself, doesn't end the run
+        instructions += run_for(cpu, mem, &stops, BENCH_BLOCK).executed;
+        let elapsed = start.elapsed().as_secs_f64();
+        if elapsed >= seconds {
+            break elapsed;
+        }
+    };
+    let cycles = cpu.cycles - start_cycles;
+    println!("{} instructions, {} cycles in {:.3}s", instructions, cycles, elapsed);
+    println!("{:.2} MIPS, {:.0} cycles/s ({:.2} MHz)",
+        instructions as f64 / elapsed / 1e6, cycles as f64 / elapsed, cycles as f64 / elapsed / 1e6);
 }
 
-// pushes a u8 to the stack
-fn push_to_stack(b:u8, cpu: &mut Cpu, mem: &mut Memory)
-{
-    let stack_base:usize = 0x0100;
-    let memloc:usize = stack_base + cpu.sp as usize;
-	mem.mem[memloc] = b;
-    cpu.sp -= 1;
+// copy a binary image into memory at addr, giving back its length
+fn load_image(mem: &mut Memory, path: &str, addr: u16) -> Result<usize, String> {
+    let bytes = fs::read(path).map_err(|e| format!("{}: {}", path, e))?;
+    if addr as usize + bytes.len() > MEMSIZE {
+        return Err(format!("{}: {} bytes do not fit at ${:04x}", path, bytes.len(), addr));
+    }
+    mem.mem[addr as usize..addr as usize + bytes.len()].copy_from_slice(&bytes);
+    Ok(bytes.len())
 }
 
-// pulls a u8 from the stack
-fn pull_from_stack(cpu: &mut Cpu, mem: &Memory) -> u8
-{
-    cpu.sp += 1;
-    let stack_base:usize = 0x0100;
-    let memloc:usize = stack_base + cpu.sp as usize;
-    mem.mem[memloc]
+// whether an image of len bytes at addr holds the reset vector
+fn covers_reset_vector(addr: u16, len: usize) -> bool {
+    (addr as usize..addr as usize + len).contains(&RESET_VECTOR_LOBYTE)
 }
 
-// prototype for cpu operation (opcode)
-type CpuOp = fn(cpu: &mut Cpu, mem: &mut Memory);
-
-// for unused op codes just do nothing
-fn ixx(_cpu: &mut Cpu, _mem: &mut Memory) {
-    // place holder for op codes not implemented
+// put the terminal back to reading lines, saying so if it can't be
+fn restore_line_mode() {
+    if let Err(message) = input::set_key_mode(false) {
+        eprintln!("e6502r: {}", message);
+    }
 }
 
-// BRK (00)
-fn i00(cpu: &mut Cpu, mem: &mut Memory) {
-    cpu.st |= STATUS_FLAGS_BREAK|STATUS_FLAGS_UNUSED;
-    cpu.pc += 2;
-    push_to_stack((cpu.pc >> 8) as u8, cpu, mem);
-    push_to_stack((cpu.pc & 0xff) as u8, cpu, mem);
-    push_to_stack(cpu.st, cpu, mem);
-    cpu.st |= STATUS_BIT_INT_DIS;
-    cpu.pc = mem.mem[BREAK_VECTOR_LOBYTE] as u16 + ((mem.mem[BREAK_VECTOR_HIBYTE] as u16) << 8);
-	//cpu->pending_cycles += 7;
+// start pacing over, forgetting time spent paused or in turbo
+fn restart_pacers(speed_pacer: &mut Option<Pacer>, clock_pacer: &mut Option<Pacer>, executed: u64, cycles: u64) {
+    if let Some(pacer) = speed_pacer {
+        pacer.restart(executed);
+    }
+    if let Some(pacer) = clock_pacer {
+        pacer.restart(cycles);
+    }
 }
 
-// NOP (EA)
-fn iea(cpu: &mut Cpu, _mem: &mut Memory) {
-    cpu.pc += 1;
-    //cpu->pending_cycles += 2;
-}
+fn main() {
+    let options = match args::parse_args(env::args()) {
+        Ok(ParsedArgs::Run(options)) => options,
+        Ok(ParsedArgs::Test(dir)) => {
+            let passed = testrunner::run_tests(&dir);
+            process::exit(if passed { 0 } else { 1 });
+        }
+        Ok(ParsedArgs::Klaus(options)) => {
+            let passed = klaus::run_klaus(&options);
+            process::exit(if passed { 0 } else { 1 });
+        }
+        Ok(ParsedArgs::Harte(options)) => {
+            let passed = harte::run_harte(&options);
+            process::exit(if passed { 0 } else { 1 });
+        }
+        Ok(ParsedArgs::Lorenz(options)) => {
+            let passed = lorenz::run_lorenz(&options);
+            process::exit(if passed { 0 } else { 1 });
+        }
+        Ok(ParsedArgs::Nes(options)) => {
+            let passed = nes::run_nes(&options);
+            process::exit(if passed { 0 } else { 1 });
+        }
+        Ok(ParsedArgs::Random(options)) => {
+            let passed = randomtest::run_random(&options);
+            process::exit(if passed { 0 } else { 1 });
+        }
+        Ok(ParsedArgs::DiffState(a, b)) => match diffstate::run_diff(&a, &b) {
+            Ok(same) => process::exit(if same { 0 } else { 1 }),
+            Err(message) => {
+                eprintln!("e6502r: {}", message);
+                process::exit(2);
+            }
+        },
+        Ok(ParsedArgs::Disasm(options)) => {
+            if let Err(message) = ca65::run_export(&options) {
+                eprintln!("e6502r: {}", message);
+                process::exit(1);
+            }
+            return;
+        }
+        // help, or a bad command line with the usage
+        Err(error) => error.exit(),
+    };
 
-// op code array
-const CPU_OPS: [CpuOp; 256] = [
-    //0    1    2    3    4    5    6    7    8    9    a    b    c    d    e    f
-    i00, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx,     // 00
-    ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx,     // 10
-    ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx,     // 20
-    ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx,     // 30
-    ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx,     // 40
-    ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx,     // 50
-    ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx,     // 60
-    ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx,     // 70
-    ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx,     // 80
-    ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx,     // 90
-    ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx,     // a0
-    ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx,     // b0
-    ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx,     // c0
-    ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx,     // d0
-    ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, iea, ixx, ixx, ixx, ixx, ixx,     // e0
-    ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx, ixx,     // f0
-];
+    color::init(options.color);
 
-fn main() {
-    let mut cpu = Cpu {
-        pc: 0,
-        sp: 0,
-        ac: 0,
-        xr: 0,
-        yr: 0,
-        st: 0,
+    let mut cpu = Cpu::new();
+    let mut mem = Memory::new();
+
+    // before the devices, which may take a seed
+    let started = match (&options.record, &options.replay) {
+        (Some(path), _) => mem.inputs.start_recording(path),
+        (_, Some(path)) => mem.inputs.start_replay(path),
+        _ => Ok(()),
     };
-    let mut mem: Memory = Memory {
-        mem: vec![0; MEMSIZE],
+    if let Err(message) = started {
+        eprintln!("e6502r: {}", message);
+        process::exit(1);
+    }
+    let mut pause_on_exec_instr = options.pause_on_exec_instr;
+    let mut monitor = Monitor::new();
+    let mut tui = Tui::new();
+
+    let machine = match &options.machine {
+        Some(path) => match machine::load_machine(path, Path::new(&options.rom_dir)) {
+            Ok(machine) => Some(machine),
+            Err(message) => {
+                eprintln!("e6502r: {}", message);
+                process::exit(1);
+            }
+        },
+        None => None,
     };
-    let pause_on_exec_instr: u8 = 1;
-    let print_output: u8 = 1;
+
+    // command line settings override the machine configuration
+    let variant = options.variant
+        .or(machine.as_ref().and_then(|m| m.variant))
+        .unwrap_or(CpuVariant::Nmos6502);
+    let reset_vector = options.reset_vector.or(machine.as_ref().and_then(|m| m.reset_vector));
+    let power_on = options.power_on.clone().or(machine.as_ref().and_then(|m| m.power_on.clone()));
+
+    if let Some(machine) = &machine {
+        eprintln!("{}", machine::describe(machine, variant));
+    }
+    cpu.variant = variant;
+
+    // instruction trace goes to stdout unless a file is given
+    let mut trace: Box<dyn Write> = match &options.trace_file {
+        Some(path) => match File::create(path) {
+            Ok(file) => Box::new(BufWriter::new(file)),
+            Err(e) => {
+                eprintln!("e6502r: {}: {}", path, e);
+                process::exit(1);
+            }
+        },
+        None => Box::new(io::stdout()),
+    };
+    let mut json_trace = options.trace_json.as_deref().map(JsonTrace::create).transpose().unwrap_or_else(|e| {
+        eprintln!("e6502r: {}", e);
+        process::exit(1);
+    });
 
     // initialize memory
     init_memory(&mut mem);
 
-    // for debugging; start at 0x400
-    mem.mem[0xfffc] = 0x00;
-    mem.mem[0xfffd] = 0x04;
-    mem.mem[0x0400] = 0xea;
-    
+    // devices run against the emulated clock, even when it isn't paced
+    let clock_hz = options.clock_hz.or(machine.as_ref().and_then(|m| m.clock_hz));
+    let device_clock_hz = clock_hz.unwrap_or(devices::DEFAULT_CLOCK_HZ);
+    monitor.clock_hz = device_clock_hz;
+
+    // the monitor takes labels from --dbg as addresses
+    if let Some(path) = &options.debug_info {
+        match DebugInfo::load(path) {
+            Ok(debug) => monitor.debug = Some(debug),
+            Err(message) => {
+                eprintln!("e6502r: {}", message);
+                process::exit(1);
+            }
+        }
+    }
+
+    // whether a rom or image brings its own reset vector
+    let mut vector_loaded = false;
+    if let Some(machine) = &machine {
+        for rom in &machine.roms {
+            let path = rom.path.to_string_lossy();
+            match load_image(&mut mem, &path, rom.addr) {
+                Ok(len) => vector_loaded |= covers_reset_vector(rom.addr, len),
+                Err(message) => {
+                    eprintln!("e6502r: {}", message);
+                    process::exit(1);
+                }
+            }
+        }
+    }
+    // the machine's devices, then those from the command line
+    let mut device_configs: Vec<DeviceConfig> = machine.iter()
+        .flat_map(|machine| machine.devices.iter().cloned())
+        .chain(options.devices.iter().cloned())
+        .collect();
+    if let Some(connect) = &options.serial {
+        match device_configs.iter_mut().find(|device| device.kind == "acia") {
+            Some(acia) => {
+                acia.options.insert(String::from("connect"), toml::Value::String(connect.clone()));
+            }
+            None => {
+                eprintln!("e6502r: --serial needs an acia, from --device or the machine");
+                process::exit(1);
+            }
+        }
+    }
+    for device in &device_configs {
+        if let Err(message) = devices::attach_device(&mut mem, device, device_clock_hz) {
+            eprintln!("e6502r: {}", message);
+            process::exit(1);
+        }
+    }
+
+    if options.images.is_empty() && machine.is_none() {
+        // for debugging; start at 0x400
+        mem.mem[0xfffc] = 0x00;
+        mem.mem[0xfffd] = 0x04;
+        mem.mem[0x0400] = 0xea;
+    }
+    for image in &options.images {
+        match load_image(&mut mem, &image.path, image.addr) {
+            Ok(len) => vector_loaded |= covers_reset_vector(image.addr, len),
+            Err(message) => {
+                eprintln!("e6502r: {}", message);
+                process::exit(1);
+            }
+        }
+    }
+    for dump in &options.xxd_loads {
+        if let Err(message) = hexdump::load(&mut mem, &dump.path, dump.addr) {
+            eprintln!("e6502r: {}", message);
+            process::exit(1);
+        }
+    }
+    // an image without a vector starts where the first one was loaded
+    let reset_vector = reset_vector.or(options.images.first()
+        .filter(|_| !vector_loaded)
+        .map(|image| image.addr));
+    if let Some(addr) = reset_vector {
+        mem.mem[RESET_VECTOR_LOBYTE] = (addr & 0xff) as u8;
+        mem.mem[RESET_VECTOR_HIBYTE] = (addr >> 8) as u8;
+    }
+
     // initialize cpu
     reset_cpu(&mut cpu, &mem);
+    if let Some(power_on) = &power_on {
+        poweron::apply(&mut cpu, &mem.inputs, power_on);
+    }
+    if let Some(path) = &options.load_state {
+        if let Err(message) = state::load_state(path, &mut cpu, &mut mem) {
+            eprintln!("e6502r: {}", message);
+            process::exit(1);
+        }
+    }
+
+    if let Some(seconds) = options.bench {
+        run_bench(&mut cpu, &mut mem, seconds);
+        return;
+    }
+
+    let mut stops = StopConditions {
+        trap_on_brk: options.trap_on_brk,
+        strict_opcodes: options.strict_opcodes,
+        stop_at: options.stop_at.clone(),
+        smc: options.smc,
+    };
+    if options.smc.is_some() {
+        mem.executed = Some(Box::new([false; MEMSIZE]));
+    }
 
-    let stdin = io::stdin();
+    // pacing for --speed and --clock
+    let mut executed: u64 = 0;
+    let mut speed_pacer = options.speed.map(|speed| Pacer::new(speed as f64, executed));
+    let mut clock_pacer = clock_hz.map(|hz| Pacer::new(hz, cpu.cycles));
 
-    // main loop
-    loop {
-        // get keys for 0xC000 (keyboard)
+    // reference emulator run in lock-step for --cosim
+    let mut cosim = match &options.cosim {
+        Some(command) => match CoSim::start(command, &cpu, &mem) {
+            Ok(cosim) => Some(cosim),
+            Err(message) => {
+                eprintln!("e6502r: {}", message);
+                process::exit(1);
+            }
+        },
+        None => None,
+    };
 
-        if print_output == 1 {
-            // TODO implement g_instruction_text
-            let memloc:usize = cpu.pc as usize;
-            let instrloc:usize = mem.mem[memloc] as usize;
-            print!("\t${:04x}\t{}", cpu.pc, INSTRUCTION_TEXT[instrloc]);
+    // requests and debug sockets for --http, or a debugger's messages
+    // for --dap; the two can't be given together
+    let started: Option<Result<Box<dyn RemoteControl>, String>> = match (&options.http, &options.dap) {
+        (Some(addr), _) => Some(HttpControl::start(addr).map(|control| {
+            eprintln!("listening for http requests on {}", addr);
+            Box::new(control) as Box<dyn RemoteControl>
+        })),
+        (_, Some(addr)) => {
+            let debug = match &options.debug_info {
+                Some(path) => DebugInfo::load(path).map(Some),
+                None => Ok(None),
+            };
+            Some(debug.and_then(|debug| DapControl::start(addr, debug)).map(|control| {
+                eprintln!("waiting for a debugger on {}", addr);
+                Box::new(control) as Box<dyn RemoteControl>
+            }))
+        }
+        _ => None,
+    };
+    let mut remote = match started.transpose() {
+        Ok(remote) => remote,
+        Err(message) => {
+            eprintln!("e6502r: {}", message);
+            process::exit(1);
         }
+    };
+
+    // keys typed while running go to an attached keyboard device
+    let keyboard = mem.inputs.keyboard_claimed();
+    if keyboard && !pause_on_exec_instr {
+        if let Err(message) = input::set_key_mode(true) {
+            eprintln!("e6502r: {}", message);
+            process::exit(1);
+        }
+    }
+    let catch_ctrl_c = options.ctrl_c.is_some();
+    input::catch_ctrl_c(catch_ctrl_c && !pause_on_exec_instr);
+
+    // the last instructions, for core dumps
+    let mut history = History::new();
+    let mut jammed = false;
+    let mut traced = None;          // the registers on the last trace line
+
+    // main loop; a panic in it leaves a core dump
+    let outcome = panic::catch_unwind(AssertUnwindSafe(|| {
+        loop {
+            // under http or a debugger's control a paused machine waits
+            // for requests, and a running one looks for them now and then
+            if let Some(control) = &mut remote {
+                let waiting = pause_on_exec_instr && !control.stepping();
+                if waiting || executed.is_multiple_of(POLL_INTERVAL) {
+                    match control.serve(&mut cpu, &mut mem, &mut stops.stop_at, !pause_on_exec_instr, waiting) {
+                        RemoteAction::None => {}
+                        RemoteAction::Pause => {
+                            pause_on_exec_instr = true;
+                            input::catch_ctrl_c(false);
+                        }
+                        RemoteAction::Resume => {
+                            pause_on_exec_instr = false;
+                            input::catch_ctrl_c(catch_ctrl_c);
+                            restart_pacers(&mut speed_pacer, &mut clock_pacer, executed, cpu.cycles);
+                        }
+                        RemoteAction::Quit => break,
+                    }
+                    if pause_on_exec_instr && !control.stepping() {
+                        continue;
+                    }
+                }
+            }
+
+            // the registers, then the instruction's bytes and its
+            // disassembly; the memory it touches is added once it has run
+            let colored = options.trace_file.is_none();
+            let trace_line = options.print_output.then(|| {
+                let (text, len) = disasm::disassemble(cpu.variant, &mem, cpu.pc);
+                let bytes: Vec<String> = (0..len).map(|i| format!("{:02x}", mem.mem[cpu.pc.wrapping_add(i) as usize])).collect();
+                if colored {
+                    format!("{}\t{:<8} {}", color::registers(&cpu, traced.as_ref()), bytes.join(" "), color::instruction(&text))
+                } else {
+                    format!("{}\t{:<8} {}", cpu, bytes.join(" "), text)
+                }
+            });
+            if options.print_output {
+                traced = Some(color::registers_of(&cpu));
+            }
 
-        // execute the opcode
-        let opcode = mem.mem[cpu.pc as usize];
-        let opcode_handler = CPU_OPS[opcode as usize];
-        opcode_handler(&mut cpu, &mut mem);
+            // the trace and watches look at the memory the instruction uses
+            if options.print_output || !monitor.watches.is_empty() {
+                mem.start_access_log();
+            } else {
+                mem.access_log = None;
+            }
+
+            // execute the opcode
+            let pc = cpu.pc;
+            history.record(&cpu);
+            if let Some(json_trace) = &mut json_trace {
+                json_trace.before(&cpu, &mut mem);
+            }
+            let opcode = mem.mem[pc as usize];
+            let mut stop = step(&mut cpu, &mut mem, &stops);
+            for (addr, write) in mem.smc_warnings.drain(..) {
+                println!("self-modifying code: ${:04x} wrote #${:02x} to ${:04x}", addr, write.value, write.addr);
+            }
+            if !matches!(stop, Some(StopReason::Break(..)) | Some(StopReason::Unimplemented(..))) {
+                monitor.stats.record(opcode);
+            }
+            if let Some(json_trace) = &mut json_trace {
+                json_trace.after(&cpu, &mem);
+            }
+            if let Some(StopReason::StopAddress(addr)) = stop {
+                if !monitor.breakpoint_reached(addr, !pause_on_exec_instr) {
+                    stop = None;
+                }
+            }
+            if stop.is_none() {
+                stop = monitor.check_stop(&cpu, &mem, pc, opcode);
+            }
+            if let Some(line) = trace_line {
+                let (accesses, entry) = mem.logged_accesses();
+                let _result = writeln!(trace, "{}{}", line, accesses_text(accesses, colored));
+                // an interrupt taken after it, on a line of its own with
+                // the registers it was taken with and the pushes it made
+                if let Some(taken) = mem.interrupt_entry.as_ref().filter(|_| !entry.is_empty()) {
+                    let registers = if colored { color::registers(&taken.cpu, traced.as_ref()) } else { taken.cpu.to_string() };
+                    let name = if colored { color::instruction(taken.name) } else { taken.name.to_string() };
+                    let _result = writeln!(trace, "{}\t{:<8} {}{}", registers, "", name, accesses_text(entry, colored));
+                    traced = Some(color::registers_of(&taken.cpu));
+                }
+            }
+
+            if let Some(reference) = &mut cosim {
+                if let Err(message) = reference.check(&cpu, &mem) {
+                    println!("{}", message);
+                    stop = Some(StopReason::Diverged(pc));
+                }
+            }
+
+            if let Some(reason) = &stop {
+                let _result = trace.flush();
+                if let Some(json_trace) = &mut json_trace {
+                    json_trace.flush();
+                }
+                println!("{}", reason);
+                if let StopReason::Unimplemented(..) = reason {
+                    print_code_around(&cpu, &mem);
+                }
+                pause_on_exec_instr = true;
+            }
+
+            // an opcode the cpu can't execute jams it; dump the first time
+            let jam = match &stop {
+                Some(StopReason::Unimplemented(..)) => true,
+                Some(StopReason::SelfLoop(addr)) => !is_implemented(cpu.variant, mem.mem[*addr as usize]),
+                _ => false,
+            };
+            if let Some(reason) = stop.as_ref().filter(|_| jam && !jammed) {
+                coredump::write_dump(&format!("cpu jammed: {}", reason), &cpu, &mem, &history);
+            }
+            jammed = jam;
+
+            if let Some(control) = &mut remote {
+                control.after_instruction(&cpu, &mut mem, pc, stop.as_ref());
+            }
+
+            executed += 1;
+            if !monitor.turbo {
+                if let Some(pacer) = &mut speed_pacer {
+                    pacer.pace(executed);
+                }
+                if let Some(pacer) = &mut clock_pacer {
+                    pacer.pace(cpu.cycles);
+                }
+            }
+
+            // while running, a line typed on the terminal pauses
+            // execution, or toggles turbo if it is 't'
+            // with a keyboard device the keys belong to the program instead
+            if keyboard && !pause_on_exec_instr {
+                input::pass_keys(&mem.inputs);
+            }
+            // with a keyboard device the terminal is still in raw mode here
+            if keyboard && options.interactive && !pause_on_exec_instr && input::take_pause_request() {
+                print!("paused at ${:04x}\r\n", cpu.pc);
+                pause_on_exec_instr = true;
+            }
+            // a caught ctrl-c pulls nmi, or pauses
+            if !pause_on_exec_instr && input::take_ctrl_c() {
+                match options.ctrl_c {
+                    Some(CtrlCMode::Nmi) => mem.inputs.pulse_nmi(),
+                    Some(CtrlCMode::Monitor) => {
+                        print!("paused at ${:04x}\r\n", cpu.pc);
+                        pause_on_exec_instr = true;
+                    }
+                    None => {}
+                }
+            }
+            if options.interactive && !keyboard && !pause_on_exec_instr && executed.is_multiple_of(POLL_INTERVAL) {
+                match input::poll_line() {
+                    Some(line) if line.trim() == "t" => {
+                        monitor.toggle_turbo();
+                        if !monitor.turbo {
+                            restart_pacers(&mut speed_pacer, &mut clock_pacer, executed, cpu.cycles);
+                        }
+                    }
+                    Some(_) => {
+                        println!("paused at ${:04x}", cpu.pc);
+                        pause_on_exec_instr = true;
+                    }
+                    None => {}
+                }
+            }
+
+            if pause_on_exec_instr {
+                let _result = trace.flush();
+                if let Some(json_trace) = &mut json_trace {
+                    json_trace.flush();
+                }
+                restore_line_mode();
+                input::catch_ctrl_c(false);
+
+                // nobody to hand control to in a non-interactive run,
+                // unless it's under http or a debugger's control
+                if !options.interactive {
+                    if remote.is_some() {
+                        continue;
+                    }
+                    break;
+                }
+
+                // get user input
+                let action = if options.use_tui {
+                    tui.prompt(&mut monitor, &mut cpu, &mut mem, &mut stops.stop_at)
+                } else {
+                    monitor.prompt(&mut cpu, &mut mem, &mut stops.stop_at)
+                };
+                match action {
+                    MonitorAction::Step => {}
+                    MonitorAction::Run => {
+                        pause_on_exec_instr = false;
+                        if let Err(message) = input::set_key_mode(keyboard) {
+                            eprintln!("e6502r: {}", message);
+                        }
+                        input::catch_ctrl_c(catch_ctrl_c);
+                    }
+                    MonitorAction::Quit => break,
+                    MonitorAction::Abort => {
+                        coredump::write_dump("the monitor's input ended", &cpu, &mem, &history);
+                        break;
+                    }
+                }
+
+                // the monitor may have changed registers or memory
+                if let Some(reference) = &mut cosim {
+                    if let Err(message) = reference.sync(&cpu, &mem) {
+                        println!("{}; co-simulation stopped", message);
+                        cosim = None;
+                    }
+                }
+
+                // don't try to catch up on time spent in the monitor
+                restart_pacers(&mut speed_pacer, &mut clock_pacer, executed, cpu.cycles);
+            }
 
-        if print_output == 1 {
-            println!();
         }
+    }));
+    if let Err(payload) = outcome {
+        restore_line_mode();
+        let message = payload.downcast_ref::<&str>().map(|text| text.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| String::from("unknown"));
+        coredump::write_dump(&format!("panic: {}", message), &cpu, &mem, &history);
+        process::exit(101);
+    }
+    let _result = trace.flush();
+    if let Some(json_trace) = &mut json_trace {
+        json_trace.flush();
+    }
+    restore_line_mode();
+    eprintln!("{} at {:.3} MHz", elapsed_text(cpu.cycles, device_clock_hz), device_clock_hz / 1e6);
 
-        if pause_on_exec_instr == 1 {
-            // get user input
-            let mut user_input = String::new();
-            let _result = stdin.read_line(&mut user_input);
+    if let Some(path) = &options.save_state {
+        if let Err(message) = state::save_state(path, &cpu, &mut mem) {
+            eprintln!("e6502r: {}", message);
+        }
+    }
+    if let Some((path, first, last)) = &options.save_xxd {
+        if let Err(message) = hexdump::save(&mem, path, *first, *last) {
+            eprintln!("e6502r: {}", message);
         }
+    }
+    if let Some(path) = &options.stats {
+        if let Err(message) = monitor.stats.save_csv(cpu.variant, path) {
+            eprintln!("e6502r: {}", message);
+        }
+    }
+    if let Some(path) = &options.screenshot {
+        if let Err(message) = png::screenshot(&mem, path) {
+            eprintln!("e6502r: {}", message);
+        }
+    }
 
+    // let scripts check the result left in emulated memory
+    if let Some(addr) = options.exit_code_at {
+        let code = mem.mem[addr as usize] as i32;
+        // devices finish writing their files before the exit
+        drop(mem);
+        process::exit(code);
     }
 }
@@ -0,0 +1,710 @@
+// 64k of memory, with devices mapped over parts of it
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use crate::cpu::Cpu;
+use crate::replay::Inputs;
+
+pub const MEMSIZE: usize = 65536;               // memory size 64k
+
+const PAGE_COUNT: usize = MEMSIZE / 256;
+
+// a memory-mapped device
+// addresses passed in are offsets from the device's base address
+pub trait Device {
+    fn read(&mut self, offset: u16) -> u8;
+    fn write(&mut self, offset: u16, value: u8);
+
+    // advance by this many cpu cycles
+    fn tick(&mut self, _cycles: u32) {}
+
+    // a device that only needs ticking now and then returns the cycles
+    // until it next does, u64::MAX if nothing is coming, and is then
+    // ticked when that comes round or before it's read or written.
+    // None, the default, ticks it after every instruction.
+    fn next_event(&self) -> Option<u64> {
+        None
+    }
+
+    // true while the device holds the irq line low
+    fn irq(&self) -> bool {
+        false
+    }
+
+    // true while the device holds the nmi line low
+    fn nmi(&self) -> bool {
+        false
+    }
+
+    // true if the device can ever raise an irq or nmi
+    fn raises_irqs(&self) -> bool {
+        false
+    }
+
+    // a display returns what it shows, for screenshots
+    fn picture(&self) -> Option<Picture> {
+        None
+    }
+
+    // a bank switching device returns the regions to change after a
+    // write to it, and its starting map the first time it is asked
+    // as (base, size, bank)
+    fn bank_switch(&mut self) -> Option<Vec<(u16, u16, Bank)>> {
+        None
+    }
+
+    // add whatever the device needs to carry on from where it is to a
+    // saved state; things on the host, like files, are not saved
+    fn save_state(&self, _out: &mut StateWriter) {}
+
+    // pick up from what save_state wrote
+    fn load_state(&mut self, _input: &mut StateReader) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+// what a display shows: rows of width pixels as 0xrrggbb, top first
+pub struct Picture {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u32>,
+}
+
+// builds the bytes of a saved state, numbers little endian
+pub struct StateWriter {
+    pub data: Vec<u8>,
+}
+
+impl Default for StateWriter {
+    fn default() -> StateWriter {
+        StateWriter::new()
+    }
+}
+
+impl StateWriter {
+    pub fn new() -> StateWriter {
+        StateWriter { data: Vec::new() }
+    }
+
+    pub fn u8(&mut self, value: u8) {
+        self.data.push(value);
+    }
+
+    pub fn bool(&mut self, value: bool) {
+        self.data.push(value as u8);
+    }
+
+    pub fn u16(&mut self, value: u16) {
+        self.data.extend(value.to_le_bytes());
+    }
+
+    pub fn u32(&mut self, value: u32) {
+        self.data.extend(value.to_le_bytes());
+    }
+
+    pub fn u64(&mut self, value: u64) {
+        self.data.extend(value.to_le_bytes());
+    }
+
+    // a block of bytes, with its length
+    pub fn bytes(&mut self, bytes: &[u8]) {
+        self.u32(bytes.len() as u32);
+        self.data.extend(bytes);
+    }
+}
+
+// reads back what a StateWriter built
+pub struct StateReader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> StateReader<'a> {
+    pub fn new(data: &'a [u8]) -> StateReader<'a> {
+        StateReader { data }
+    }
+
+    fn take(&mut self, count: usize) -> Result<&'a [u8], String> {
+        if self.data.len() < count {
+            return Err(String::from("the saved state ends early"));
+        }
+        let (taken, rest) = self.data.split_at(count);
+        self.data = rest;
+        Ok(taken)
+    }
+
+    pub fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn bool(&mut self) -> Result<bool, String> {
+        Ok(self.u8()? != 0)
+    }
+
+    pub fn u16(&mut self) -> Result<u16, String> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap_or_default()))
+    }
+
+    pub fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap_or_default()))
+    }
+
+    pub fn u64(&mut self) -> Result<u64, String> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap_or_default()))
+    }
+
+    pub fn bytes(&mut self) -> Result<&'a [u8], String> {
+        let count = self.u32()? as usize;
+        self.take(count)
+    }
+
+    // a block of bytes that must fill buffer exactly
+    pub fn bytes_into(&mut self, buffer: &mut [u8]) -> Result<(), String> {
+        let bytes = self.bytes()?;
+        if bytes.len() != buffer.len() {
+            return Err(format!("the saved state has {} bytes where {} were expected", bytes.len(), buffer.len()));
+        }
+        buffer.copy_from_slice(bytes);
+        Ok(())
+    }
+}
+
+// memory as Memory::save_state wrote it, read without a machine to
+// put it in
+pub struct SavedMemory {
+    pub mem: Vec<u8>,
+    pub banks: Vec<(u16, Bank, Vec<u8>)>,      // region base, bank showing, ram under a rom
+    pub nmi_low: bool,
+    pub ticked_to: u64,
+    pub devices: Vec<(u16, u16, Vec<u8>)>,     // base, size and the device's own state
+}
+
+impl SavedMemory {
+    pub fn read(input: &mut StateReader) -> Result<SavedMemory, String> {
+        let mem = input.bytes()?.to_vec();
+        if mem.len() != MEMSIZE {
+            return Err(String::from("the saved state's memory is the wrong size"));
+        }
+        let mut banks = Vec::new();
+        for _ in 0..input.u32()? {
+            let base = input.u16()?;
+            let kind = input.u8()?;
+            let rom = input.u32()? as usize;
+            let bank = match kind {
+                0 => Bank::Ram,
+                1 => Bank::Io,
+                _ => Bank::Rom(rom),
+            };
+            banks.push((base, bank, input.bytes()?.to_vec()));
+        }
+        let nmi_low = input.bool()?;
+        let ticked_to = input.u64()?;
+        let mut devices = Vec::new();
+        for _ in 0..input.u32()? {
+            let (base, size) = (input.u16()?, input.u16()?);
+            devices.push((base, size, input.bytes()?.to_vec()));
+        }
+        Ok(SavedMemory { mem, banks, nmi_low, ticked_to, devices })
+    }
+}
+
+// what the cpu sees in a bank switched region
+#[derive(Clone, Copy, PartialEq)]
+pub enum Bank {
+    Ram,
+    Rom(usize),             // an image added with add_rom
+    Io,                     // the devices attached there
+}
+
+// a region of memory that can be switched between ram, a rom and io
+// while a rom is showing, the ram under it is kept in hidden_ram
+struct Region {
+    base: u16,
+    size: u16,
+    showing: Bank,
+    hidden_ram: Vec<u8>,
+}
+
+// a device and the addresses it answers
+struct MappedDevice {
+    base: u16,
+    size: u16,
+    device: Box<dyn Device>,
+    scheduled: bool,        // ticked when its events come round
+    ticked_to: u64,         // the cycle count a scheduled device has reached
+    due: u64,               // and when it next needs to be ticked
+}
+
+impl MappedDevice {
+    // bring a scheduled device up to a cycle count
+    fn catch_up(&mut self, cycles: u64) {
+        while self.ticked_to < cycles {
+            let step = (cycles - self.ticked_to).min(u32::MAX as u64);
+            self.device.tick(step as u32);
+            self.ticked_to += step;
+        }
+    }
+}
+
+// a data access that reached a device, or any the cpu made
+#[derive(Clone, Copy, PartialEq)]
+pub struct IoAccess {
+    pub addr: u16,
+    pub value: u8,
+    pub write: bool,
+}
+
+// an interrupt the cpu took as an instruction ended, noted while
+// accesses are logged: which, the cpu as it was taken, and where in
+// access_log its own accesses start
+pub struct InterruptEntry {
+    pub name: &'static str,
+    pub cpu: Cpu,
+    pub from: usize,
+}
+
+// MEMORY
+pub struct Memory {
+    pub mem: Box<[u8; MEMSIZE]>,    // indexed by a u16 address, never out of bounds
+    devices: Vec<MappedDevice>,
+    io_pages: [bool; PAGE_COUNT],    // pages with a device in them
+    rom_pages: [bool; PAGE_COUNT],   // pages showing a banked rom
+    page_map: [u8; PAGE_COUNT],     // the page each page's data is kept in
+    irq_sources: bool,              // some device can raise an irq
+    nmi_low: bool,                  // the nmi line was low after the last check
+    ticked_to: u64,                 // cpu cycle count devices have reached
+    events: BinaryHeap<Reverse<(u64, usize)>>,     // when scheduled devices are due, by index
+    roms: Vec<Vec<u8>>,
+    regions: Vec<Region>,
+    pub io_log: Option<Vec<IoAccess>>,  // device accesses are kept here while it's Some
+    pub access_log: Option<Vec<IoAccess>>,  // and all data accesses, the stack's too, here
+    pub interrupt_entry: Option<InterruptEntry>,    // an interrupt taken since the log started
+    pub bus_log: Option<Vec<IoAccess>>,     // every cycle's access, fetches and ignored ones too, while it's Some
+    pub executed: Option<Box<[bool; MEMSIZE]>>,    // bytes run as code since last written, while it's Some
+    pub code_writes: Vec<IoAccess>,     // writes to those bytes, which then count as unrun
+    pub smc_warnings: Vec<(u16, IoAccess)>,     // with --smc warn, those writes and the instruction that made each, for the host to report and clear
+    pub irq_pulse: bool,            // irq held low from outside until the next check
+    pub nmi_pulse: bool,            // and nmi
+    pub inputs: Inputs,             // from outside, recorded or replayed, shared with the devices taking them
+}
+
+impl Default for Memory {
+    fn default() -> Memory {
+        Memory::new()
+    }
+}
+
+impl Memory {
+    pub fn new() -> Memory {
+        Memory {
+            mem: Box::new([0; MEMSIZE]),
+            devices: Vec::new(),
+            io_pages: [false; PAGE_COUNT],
+            rom_pages: [false; PAGE_COUNT],
+            page_map: std::array::from_fn(|page| page as u8),
+            irq_sources: false,
+            nmi_low: false,
+            ticked_to: 0,
+            events: BinaryHeap::new(),
+            roms: Vec::new(),
+            regions: Vec::new(),
+            io_log: None,
+            access_log: None,
+            interrupt_entry: None,
+            bus_log: None,
+            executed: None,
+            code_writes: Vec::new(),
+            smc_warnings: Vec::new(),
+            irq_pulse: false,
+            nmi_pulse: false,
+            inputs: Inputs::new(),
+        }
+    }
+
+    // map a device at base; it answers size addresses from there
+    pub fn attach(&mut self, base: u16, size: u16, device: Box<dyn Device>) -> Result<(), String> {
+        let end = base as usize + size as usize;
+        if size == 0 || end > MEMSIZE {
+            return Err(format!("device at ${:04x} does not fit in memory", base));
+        }
+        for mapped in &self.devices {
+            let mapped_end = mapped.base as usize + mapped.size as usize;
+            if (base as usize) < mapped_end && (mapped.base as usize) < end {
+                return Err(format!("device at ${:04x} overlaps the device at ${:04x}", base, mapped.base));
+            }
+        }
+        // devices in a region switched away from io stay hidden
+        for page in base as usize >> 8..=(end - 1) >> 8 {
+            let hidden = self.regions.iter().any(|region| {
+                region.showing != Bank::Io && page >= region.base as usize >> 8
+                    && page < (region.base as usize + region.size as usize) >> 8
+            });
+            self.io_pages[page] = !hidden;
+        }
+        self.irq_sources |= device.raises_irqs();
+        let scheduled = device.next_event().is_some();
+        self.devices.push(MappedDevice { base, size, device, scheduled, ticked_to: self.ticked_to, due: u64::MAX });
+        if scheduled {
+            self.schedule(self.devices.len() - 1);
+        }
+        let banks = self.devices.last_mut().and_then(|mapped| mapped.device.bank_switch());
+        self.switch_banks(banks);
+        Ok(())
+    }
+
+    // make the pages from base up to base+size repeat the target_size
+    // bytes at target, as partly decoded addresses do. data accesses see
+    // the mirror; opcodes, pointers and the stack read the page as it is.
+    pub fn mirror(&mut self, base: u16, size: u16, target: u16, target_size: u16) {
+        let (base, target) = (base as usize >> 8, target as usize >> 8);
+        let pages = (size as usize + 0xff) >> 8;
+        let target_pages = (target_size as usize >> 8).max(1);
+        for page in 0..pages {
+            self.page_map[base + page] = (target + page % target_pages) as u8;
+        }
+    }
+
+    // keep a rom image for bank switching devices; returns its number
+    pub fn add_rom(&mut self, data: Vec<u8>) -> usize {
+        self.roms.push(data);
+        self.roms.len() - 1
+    }
+
+    fn switch_banks(&mut self, banks: Option<Vec<(u16, u16, Bank)>>) {
+        for (base, size, bank) in banks.unwrap_or_default() {
+            self.set_bank(base, size, bank);
+        }
+    }
+
+    // regions start out as ram the first time they are switched
+    pub fn set_bank(&mut self, base: u16, size: u16, bank: Bank) {
+        let index = match self.regions.iter().position(|region| region.base == base) {
+            Some(index) => index,
+            None => {
+                let start = base as usize;
+                self.io_pages[start >> 8..=(start + size as usize - 1) >> 8].fill(false);
+                self.regions.push(Region { base, size, showing: Bank::Ram, hidden_ram: Vec::new() });
+                self.regions.len() - 1
+            }
+        };
+        let region = &mut self.regions[index];
+        if region.showing == bank {
+            return;
+        }
+        let start = region.base as usize;
+        let end = start + region.size as usize;
+        let pages = start >> 8..=(end - 1) >> 8;
+
+        // put the ram back, then show the new bank over it
+        match region.showing {
+            Bank::Rom(_) => self.mem[start..end].copy_from_slice(&region.hidden_ram),
+            Bank::Io => self.io_pages[pages.clone()].fill(false),
+            Bank::Ram => {}
+        }
+        self.rom_pages[pages.clone()].fill(false);
+        match bank {
+            Bank::Rom(rom) => {
+                region.hidden_ram = self.mem[start..end].to_vec();
+                let image = &self.roms[rom];
+                let len = image.len().min(end - start);
+                self.mem[start..start + len].copy_from_slice(&image[..len]);
+                self.rom_pages[pages].fill(true);
+            }
+            Bank::Io => self.io_pages[pages].fill(true),
+            Bank::Ram => {}
+        }
+        region.showing = bank;
+    }
+
+    // the device answering addr, caught up if it's scheduled
+    fn device_at(&mut self, addr: u16) -> Option<usize> {
+        let index = self.devices.iter().position(|d| addr >= d.base && addr - d.base < d.size)?;
+        let ticked_to = self.ticked_to;
+        let mapped = &mut self.devices[index];
+        if mapped.scheduled {
+            mapped.catch_up(ticked_to);
+        }
+        Some(index)
+    }
+
+    // ask a scheduled device when it's next due; an earlier event of its
+    // still in the queue is passed over when it comes out
+    fn schedule(&mut self, index: usize) {
+        let mapped = &mut self.devices[index];
+        let wait = mapped.device.next_event().unwrap_or(u64::MAX).max(1);
+        mapped.due = mapped.ticked_to.saturating_add(wait);
+        if mapped.due != u64::MAX {
+            self.events.push(Reverse((mapped.due, index)));
+        }
+    }
+
+    // bring the devices up to the cpu's cycle count: those ticked every
+    // instruction now, and scheduled ones whose events have come round
+    pub fn tick(&mut self, cycles: u64) {
+        let elapsed = cycles.saturating_sub(self.ticked_to);
+        self.ticked_to = cycles;
+        if elapsed == 0 {
+            return;
+        }
+        for mapped in self.devices.iter_mut().filter(|mapped| !mapped.scheduled) {
+            mapped.device.tick(elapsed as u32);
+        }
+        while let Some(&Reverse((due, index))) = self.events.peek() {
+            if due > cycles {
+                break;
+            }
+            self.events.pop();
+            if self.devices[index].due != due {
+                continue;
+            }
+            self.devices[index].catch_up(cycles);
+            self.schedule(index);
+        }
+    }
+
+    // bring every scheduled device up to the cpu's cycle count, so the
+    // state of each is as of now
+    pub fn catch_up(&mut self) {
+        for index in 0..self.devices.len() {
+            if self.devices[index].scheduled {
+                self.devices[index].catch_up(self.ticked_to);
+                self.schedule(index);
+            }
+        }
+    }
+
+    // true if any device is asserting irq, or it's pulsed
+    pub fn irq(&mut self) -> bool {
+        let pulsed = std::mem::take(&mut self.irq_pulse);
+        pulsed || self.irq_sources && self.devices.iter().any(|mapped| mapped.device.irq())
+    }
+
+    // true when the nmi line has just gone low
+    pub fn nmi(&mut self) -> bool {
+        let pulsed = std::mem::take(&mut self.nmi_pulse);
+        let low = pulsed || self.irq_sources && self.devices.iter().any(|mapped| mapped.device.nmi());
+        let edge = low && !self.nmi_low;
+        self.nmi_low = low;
+        edge
+    }
+
+    // what the first display attached shows
+    pub fn picture(&self) -> Option<Picture> {
+        self.devices.iter().find_map(|mapped| mapped.device.picture())
+    }
+
+    // true if an irq could arrive to break the cpu out of a loop
+    pub fn has_irq_sources(&self) -> bool {
+        self.irq_sources
+    }
+
+    // where the data for addr is kept
+    fn mapped(&self, addr: u16) -> u16 {
+        ((self.page_map[addr as usize >> 8] as u16) << 8) | (addr & 0xff)
+    }
+
+    // memory, banking and every device's state, for a saved state
+    pub fn save_state(&mut self, out: &mut StateWriter) {
+        self.catch_up();
+        out.bytes(&self.mem[..]);
+        out.u32(self.regions.len() as u32);
+        for region in &self.regions {
+            out.u16(region.base);
+            out.u8(match region.showing {
+                Bank::Ram => 0,
+                Bank::Io => 1,
+                Bank::Rom(_) => 2,
+            });
+            out.u32(match region.showing {
+                Bank::Rom(rom) => rom as u32,
+                _ => 0,
+            });
+            out.bytes(&region.hidden_ram);
+        }
+        out.bool(self.nmi_low);
+        out.u64(self.ticked_to);
+        out.u32(self.devices.len() as u32);
+        for mapped in &self.devices {
+            out.u16(mapped.base);
+            out.u16(mapped.size);
+            let mut device = StateWriter::new();
+            mapped.device.save_state(&mut device);
+            out.bytes(&device.data);
+        }
+    }
+
+    // restore a saved state into memory set up as it was when it was
+    // saved; a state that doesn't match is refused before anything changes
+    pub fn restore(&mut self, saved: SavedMemory) -> Result<(), String> {
+        if saved.banks.len() != self.regions.len()
+            || saved.banks.iter().zip(&self.regions).any(|(bank, region)| bank.0 != region.base) {
+            return Err(String::from("the saved state has different bank switching"));
+        }
+        if saved.banks.iter().any(|bank| matches!(bank.1, Bank::Rom(rom) if rom >= self.roms.len())) {
+            return Err(String::from("the saved state shows a rom that isn't loaded"));
+        }
+        if saved.devices.len() != self.devices.len() {
+            return Err(format!("the saved state has {} devices, this machine {}", saved.devices.len(), self.devices.len()));
+        }
+        for (device, mapped) in saved.devices.iter().zip(&self.devices) {
+            if (device.0, device.1) != (mapped.base, mapped.size) {
+                return Err(format!("the saved state has a device at ${:04x} where this machine has one at ${:04x}",
+                    device.0, mapped.base));
+            }
+        }
+
+        for (index, (_, bank, hidden_ram)) in saved.banks.into_iter().enumerate() {
+            let (base, size) = (self.regions[index].base, self.regions[index].size);
+            self.set_bank(base, size, bank);
+            self.regions[index].hidden_ram = hidden_ram;
+        }
+        self.mem.copy_from_slice(&saved.mem);
+        self.nmi_low = saved.nmi_low;
+        self.ticked_to = saved.ticked_to;
+        for ((_, _, data), mapped) in saved.devices.iter().zip(&mut self.devices) {
+            mapped.device.load_state(&mut StateReader::new(data))
+                .map_err(|message| format!("device at ${:04x}: {}", mapped.base, message))?;
+            mapped.ticked_to = saved.ticked_to;
+        }
+        self.events.clear();
+        for index in 0..self.devices.len() {
+            if self.devices[index].scheduled {
+                self.schedule(index);
+            }
+        }
+        Ok(())
+    }
+
+    // read a byte as the cpu sees it
+    pub fn read(&mut self, addr: u16) -> u8 {
+        if self.io_pages[addr as usize >> 8] {
+            if let Some(index) = self.device_at(addr) {
+                let mapped = &mut self.devices[index];
+                let value = mapped.device.read(addr - mapped.base);
+                if mapped.scheduled {
+                    self.schedule(index);
+                }
+                if let Some(log) = &mut self.io_log {
+                    log.push(IoAccess { addr, value, write: false });
+                }
+                self.log_access(addr, value, false);
+                return value;
+            }
+        }
+        let value = self.mem[self.mapped(addr) as usize];
+        self.log_access(addr, value, false);
+        value
+    }
+
+    // write a byte as the cpu does; writes to a rom go to the ram under it
+    pub fn write(&mut self, addr: u16, value: u8) {
+        let page = addr as usize >> 8;
+        if self.io_pages[page] {
+            if let Some(index) = self.device_at(addr) {
+                let mapped = &mut self.devices[index];
+                mapped.device.write(addr - mapped.base, value);
+                let banks = mapped.device.bank_switch();
+                if mapped.scheduled {
+                    self.schedule(index);
+                }
+                self.switch_banks(banks);
+                if let Some(log) = &mut self.io_log {
+                    log.push(IoAccess { addr, value, write: true });
+                }
+                self.log_access(addr, value, true);
+                return;
+            }
+        }
+        self.log_access(addr, value, true);
+        let addr = self.mapped(addr);
+        if self.rom_pages[addr as usize >> 8] {
+            if let Some(region) = self.regions.iter_mut().find(|r| addr >= r.base && addr - r.base < r.size) {
+                region.hidden_ram[(addr - region.base) as usize] = value;
+                return;
+            }
+        }
+        self.mem[addr as usize] = value;
+    }
+
+    // log the data accesses of the next instruction
+    pub fn start_access_log(&mut self) {
+        match &mut self.access_log {
+            Some(log) => log.clear(),
+            None => self.access_log = Some(Vec::new()),
+        }
+        self.interrupt_entry = None;
+    }
+
+    // note an interrupt about to be taken, while accesses are logged, so
+    // its pushes are not put down to the instruction before
+    pub fn note_interrupt(&mut self, name: &'static str, cpu: &Cpu) {
+        if let Some(log) = &self.access_log {
+            self.interrupt_entry = Some(InterruptEntry { name, cpu: cpu.clone(), from: log.len() });
+        }
+    }
+
+    // the logged accesses the instruction made, and those of an
+    // interrupt taken after it
+    pub fn logged_accesses(&self) -> (&[IoAccess], &[IoAccess]) {
+        let log = self.access_log.as_deref().unwrap_or(&[]);
+        let from = self.interrupt_entry.as_ref().map_or(log.len(), |entry| entry.from);
+        log.split_at(from)
+    }
+
+    // note a data access, for the cpu's stack accesses, which go
+    // straight to ram
+    pub fn log_access(&mut self, addr: u16, value: u8, write: bool) {
+        if let Some(log) = &mut self.access_log {
+            log.push(IoAccess { addr, value, write });
+        }
+        if let Some(log) = &mut self.bus_log {
+            log.push(IoAccess { addr, value, write });
+        }
+        if let Some(executed) = self.executed.as_mut().filter(|executed| write && executed[addr as usize]) {
+            executed[addr as usize] = false;
+            self.code_writes.push(IoAccess { addr, value, write });
+        }
+    }
+
+    // the cycles the cpu spends on itself: fetching instructions,
+    // pointers and vectors, and reading or writing what it then ignores.
+    // none of them reach a device, and only the bus log sees them
+
+    // read a byte of an instruction, a pointer or a vector
+    pub fn fetch(&mut self, addr: u16) -> u8 {
+        let value = self.mem[addr as usize];
+        if let Some(log) = &mut self.bus_log {
+            log.push(IoAccess { addr, value, write: false });
+        }
+        value
+    }
+
+    // a read whose value is thrown away
+    pub fn dummy_read(&mut self, addr: u16) {
+        if let Some(log) = &mut self.bus_log {
+            log.push(IoAccess { addr, value: self.mem[addr as usize], write: false });
+        }
+    }
+
+    // the 6502's write of a value it is about to change, back where it was
+    pub fn dummy_write(&mut self, addr: u16, value: u8) {
+        if let Some(log) = &mut self.bus_log {
+            log.push(IoAccess { addr, value, write: true });
+        }
+    }
+
+    // a cycle spent with the last address still on the bus
+    pub fn repeat_cycle(&mut self) {
+        if let Some(last) = self.bus_log.as_ref().and_then(|log| log.last()) {
+            let addr = last.addr;
+            self.dummy_read(addr);
+        }
+    }
+}
+
+// initialize memory with zero's
+pub fn init_memory(mem: &mut Memory) {
+    for i in 0..MEMSIZE {
+        mem.mem[i] = 0x00;
+    }
+}
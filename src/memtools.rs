@@ -0,0 +1,178 @@
+// monitor commands that look through memory, and set it up
+//
+//   find a9ff??8d [A B]     bytes in hex, ?? matching any byte
+//   find "HELLO?" [A B]     ascii text, ? matching any character
+//
+// the search covers all of memory unless it's given from A to B, and a
+// match may not run past B.
+//
+//   cmp A1 A2 LEN           LEN bytes at A1 against those at A2
+//   cmp A1 FILE [LEN]       memory at A1 against the file's bytes
+//
+//   fill A LEN BYTE         LEN bytes from A set to BYTE
+//   copy SRC DST LEN        LEN bytes from SRC to DST, which may overlap
+//
+// lengths and bytes are hex, and addresses hex or labels from --dbg; a
+// second argument to cmp that isn't one is the file's path. memory is written directly, as loading
+// an image does, without going through the devices.
+
+use std::fs;
+
+use crate::args::parse_addr;
+use crate::dbginfo::{parse_location, DebugInfo};
+use crate::memory::{Memory, MEMSIZE};
+
+// matches or differences listed at most
+const MAX_MATCHES: usize = 32;
+
+// a byte to match, or None for any byte
+pub type Pattern = Vec<Option<u8>>;
+
+// parse hex like a9ff??8d or quoted text like "HEL?O"
+pub fn parse_pattern(text: &str) -> Result<Pattern, String> {
+    if let Some(quoted) = text.strip_prefix('"') {
+        let inner = quoted.strip_suffix('"').ok_or(format!("unterminated text {}", text))?;
+        if inner.is_empty() || !inner.is_ascii() {
+            return Err(format!("bad text {}, should be ascii", text));
+        }
+        return Ok(inner.bytes().map(|b| if b == b'?' { None } else { Some(b) }).collect());
+    }
+    let digits = text.strip_prefix('$').unwrap_or(text);
+    if digits.is_empty() || !digits.is_ascii() || !digits.len().is_multiple_of(2) {
+        return Err(format!("bad pattern '{}', should be pairs of hex digits", text));
+    }
+    (0..digits.len()).step_by(2).map(|i| match &digits[i..i + 2] {
+        "??" => Ok(None),
+        pair => u8::from_str_radix(pair, 16).map(Some).map_err(|_| format!("bad byte '{}' in '{}'", pair, text)),
+    }).collect()
+}
+
+// the addresses from first to last where the pattern starts
+pub fn find(mem: &Memory, pattern: &[Option<u8>], first: u16, last: u16) -> Vec<u16> {
+    let region = &mem.mem[first as usize..=last as usize];
+    region.windows(pattern.len())
+        .enumerate()
+        .filter(|(_, window)| window.iter().zip(pattern).all(|(byte, want)| want.is_none_or(|want| *byte == want)))
+        .map(|(offset, _)| first + offset as u16)
+        .collect()
+}
+
+// the find command: its arguments are the pattern, then an optional
+// start and end
+pub fn find_command(mem: &Memory, args: &str, debug: Option<&DebugInfo>) -> Result<String, String> {
+    let args = args.trim();
+    // quoted text may have spaces in it
+    let (pattern, rest) = match args.strip_prefix('"').and_then(|quoted| quoted.find('"')) {
+        Some(end) => args.split_at(end + 2),
+        None => args.split_once(' ').unwrap_or((args, "")),
+    };
+    let pattern = parse_pattern(pattern)?;
+    let bounds: Vec<&str> = rest.split_whitespace().collect();
+    let (first, last) = match bounds.as_slice() {
+        [] => (0x0000, 0xffff),
+        [first, last] => (parse_location(first, debug)?, parse_location(last, debug)?),
+        _ => return Err(String::from("find needs a pattern, then a start and end or neither")),
+    };
+    if first > last {
+        return Err(format!("bad range ${:04x}-${:04x}, it ends before it starts", first, last));
+    }
+
+    let found = find(mem, &pattern, first, last);
+    if found.is_empty() {
+        return Ok(String::from("not found\n"));
+    }
+    let mut out = String::new();
+    for &addr in found.iter().take(MAX_MATCHES) {
+        let bytes = &mem.mem[addr as usize..addr as usize + pattern.len()];
+        let hex: Vec<String> = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+        let text: String = bytes.iter().map(|&b| if (0x20..0x7f).contains(&b) { b as char } else { '.' }).collect();
+        out.push_str(&format!("${:04x}: {}  {}\n", addr, hex.join(" "), text));
+    }
+    if found.len() > MAX_MATCHES {
+        out.push_str(&format!("... and {} more\n", found.len() - MAX_MATCHES));
+    }
+    Ok(out)
+}
+
+// the cmp command: the differences between two regions of memory, or
+// memory and a file
+pub fn compare_command(mem: &Memory, args: &str, debug: Option<&DebugInfo>) -> Result<String, String> {
+    let words: Vec<&str> = args.split_whitespace().collect();
+    let usage = || String::from("cmp needs A1 A2 LEN, or A1 FILE [LEN]");
+    let (first, second, len) = match words.as_slice() {
+        [first, second] => (*first, *second, None),
+        [first, second, len] => (*first, *second, Some(parse_addr(len)? as usize)),
+        _ => return Err(usage()),
+    };
+    let first = parse_location(first, debug)? as usize;
+
+    // the other bytes, from memory or the file, and where they came from
+    let (other, from): (Vec<u8>, Option<usize>) = match parse_location(second, debug) {
+        Ok(addr) => {
+            let len = len.ok_or_else(usage)?;
+            let end = (addr as usize + len).min(MEMSIZE);
+            (mem.mem[addr as usize..end].to_vec(), Some(addr as usize))
+        }
+        Err(_) => {
+            let bytes = fs::read(second).map_err(|e| format!("{}: {}", second, e))?;
+            let len = len.unwrap_or(bytes.len()).min(bytes.len());
+            (bytes[..len].to_vec(), None)
+        }
+    };
+    let len = len.unwrap_or(other.len());
+    if first + len > MEMSIZE || other.len() < len {
+        return Err(format!("cmp of {} bytes runs past the end of memory or the file", len));
+    }
+
+    let differ: Vec<usize> = (0..len).filter(|&offset| mem.mem[first + offset] != other[offset]).collect();
+    if differ.is_empty() {
+        return Ok(format!("the {} bytes are the same\n", len));
+    }
+    let mut out = String::new();
+    for &offset in differ.iter().take(MAX_MATCHES) {
+        let theirs = match from {
+            Some(addr) => format!("${:04x}: {:02x}", addr + offset, other[offset]),
+            None => format!("file: {:02x}", other[offset]),
+        };
+        out.push_str(&format!("+{:04x}  ${:04x}: {:02x}  {}\n", offset, first + offset, mem.mem[first + offset], theirs));
+    }
+    if differ.len() > MAX_MATCHES {
+        out.push_str(&format!("... and {} more\n", differ.len() - MAX_MATCHES));
+    }
+    out.push_str(&format!("{} of {} bytes differ\n", differ.len(), len));
+    Ok(out)
+}
+
+// the range of len bytes from addr, if it fits in memory
+fn region(addr: u16, len: usize) -> Result<std::ops::Range<usize>, String> {
+    let start = addr as usize;
+    if start + len > MEMSIZE {
+        return Err(format!("{} bytes from ${:04x} run past the end of memory", len, addr));
+    }
+    Ok(start..start + len)
+}
+
+// the fill command
+pub fn fill_command(mem: &mut Memory, args: &str, debug: Option<&DebugInfo>) -> Result<String, String> {
+    let words: Vec<&str> = args.split_whitespace().collect();
+    let [addr, len, byte] = words.as_slice() else {
+        return Err(String::from("fill needs A LEN BYTE"));
+    };
+    let (addr, len) = (parse_location(addr, debug)?, parse_addr(len)? as usize);
+    let byte = u8::try_from(parse_addr(byte)?).map_err(|_| format!("bad byte '{}'", byte))?;
+    mem.mem[region(addr, len)?].fill(byte);
+    Ok(format!("filled {} bytes from ${:04x} with ${:02x}\n", len, addr, byte))
+}
+
+// the copy command
+pub fn copy_command(mem: &mut Memory, args: &str, debug: Option<&DebugInfo>) -> Result<String, String> {
+    let words: Vec<&str> = args.split_whitespace().collect();
+    let [src, dst, len] = words.as_slice() else {
+        return Err(String::from("copy needs SRC DST LEN"));
+    };
+    let (src, dst, len) = (parse_location(src, debug)?, parse_location(dst, debug)?, parse_addr(len)? as usize);
+    let from = region(src, len)?;
+    region(dst, len)?;
+    mem.mem.copy_within(from, dst as usize);
+    Ok(format!("copied {} bytes from ${:04x} to ${:04x}\n", len, src, dst))
+}
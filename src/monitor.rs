@@ -0,0 +1,733 @@
+
+// interactive monitor used while stepping through a program
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::io::Write;
+
+use crate::color;
+use crate::devices::DEFAULT_CLOCK_HZ;
+use crate::cpu::{instruction_text, Cpu, STATUS_BIT_INT_DIS};
+use crate::dbginfo::{parse_location, parse_location_range, DebugInfo};
+use crate::disasm;
+use crate::disasm::AddrMode;
+use crate::hexdump;
+use crate::input;
+use crate::memory::{IoAccess, Memory};
+use crate::memtools;
+use crate::png;
+use crate::replay::Inputs;
+use crate::stackview;
+use crate::state::{load_state, save_state};
+use crate::stats::OpcodeStats;
+use crate::StopReason;
+
+// what the main loop should do after the monitor returns
+pub enum MonitorAction {
+    Step,
+    Run,
+    Quit,
+    Abort,                  // the monitor's input ended
+}
+
+// print the cpu registers
+pub fn print_registers(cpu: &Cpu) {
+    println!("{}", color::registers(cpu, None));
+}
+
+// zero page as a grid of 16 rows, each byte that differs from before
+// highlighted, or with a * before it when there's no colour
+//
+//       0  1  2  3  4  5  6  7  8  9  a  b  c  d  e  f
+//   00: 00*41 00 00 00 00 00 00 00 00 00 00 00 00 00 00
+pub fn zero_page_text(mem: &Memory, before: Option<&[u8; 256]>) -> String {
+    let mut out = String::from("   ");
+    for column in 0..16 {
+        out.push_str(&format!("  {:x}", column));
+    }
+    out.push('\n');
+    for row in 0..16 {
+        out.push_str(&format!("{:02x}:", row * 16));
+        for column in 0..16 {
+            let addr = row * 16 + column;
+            let byte = mem.mem[addr];
+            let text = format!("{:02x}", byte);
+            match before.filter(|before| before[addr] != byte) {
+                None => out.push_str(&format!(" {}", text)),
+                Some(_) if color::enabled() => out.push_str(&format!(" {}", color::changed(&text))),
+                Some(_) => out.push_str(&format!("*{}", text)),
+            }
+        }
+        out.push('\n');
+    }
+    out
+}
+
+// the cycles of a tick, one to a line, with those of an interrupt taken
+// as the instruction ended after them
+//
+//   cycle 1  $0400 r $ad
+//   cycle 4  $c000 r $00
+fn cycles_text(cycles: &[IoAccess]) -> String {
+    cycles.iter()
+        .enumerate()
+        .map(|(index, cycle)| {
+            let addr = color::address(cycle.addr);
+            let direction = if cycle.write { "w" } else { "r" };
+            format!("cycle {:<2} {} {} ${:02x}\n", index + 1, addr, direction, cycle.value)
+        })
+        .collect()
+}
+
+// a range of memory to stop on reads or writes of
+pub struct Watch {
+    first: u16,
+    last: u16,          // inclusive
+    read: bool,
+    write: bool,
+}
+
+impl Watch {
+    fn covers(&self, access: &IoAccess) -> bool {
+        (self.first..=self.last).contains(&access.addr) && if access.write { self.write } else { self.read }
+    }
+}
+
+impl fmt::Display for Watch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let kind = match (self.read, self.write) {
+            (true, true) => "rw",
+            (true, false) => "r",
+            _ => "w",
+        };
+        if self.first == self.last {
+            write!(f, "${:04x} {}", self.first, kind)
+        } else {
+            write!(f, "${:04x}..${:04x} {}", self.first, self.last, kind)
+        }
+    }
+}
+
+// how often a breakpoint has been reached, and how many more times
+// it's to be passed by
+#[derive(Default)]
+struct Counts {
+    hits: u64,
+    ignore: u64,
+}
+
+// a value display shows each time execution stops: a register, or the
+// bytes in a range of memory
+enum Display {
+    Register(&'static str),
+    Memory(u16, u16),
+}
+
+impl Display {
+    fn parse(text: &str, debug: Option<&DebugInfo>) -> Result<Display, String> {
+        let text = text.trim();
+        if let Some(inner) = text.strip_prefix('[').and_then(|rest| rest.strip_suffix(']')) {
+            let (first, last) = if inner.contains("..") || inner.contains('-') {
+                parse_location_range(inner, debug)?
+            } else {
+                let addr = parse_location(inner, debug)?;
+                (addr, addr)
+            };
+            return Ok(Display::Memory(first, last));
+        }
+        let register = ["a", "x", "y", "sp", "p", "pc"].into_iter().find(|name| text.eq_ignore_ascii_case(name))
+            .ok_or(format!("bad expression '{}', should be a register or [ADDR]", text))?;
+        Ok(Display::Register(register))
+    }
+
+    fn text(&self, cpu: &Cpu, mem: &Memory) -> String {
+        match *self {
+            Display::Register("pc") => format!("pc = ${:04x}", cpu.pc),
+            Display::Register(name) => {
+                let value = match name {
+                    "a" => cpu.ac,
+                    "x" => cpu.xr,
+                    "y" => cpu.yr,
+                    "sp" => cpu.sp,
+                    _ => cpu.status(),
+                };
+                format!("{} = ${:02x}", name, value)
+            }
+            Display::Memory(addr, last) if addr == last => format!("[${:04x}] = ${:02x}", addr, mem.mem[addr as usize]),
+            Display::Memory(first, last) => {
+                let bytes: Vec<String> = (first..=last).map(|addr| format!("{:02x}", mem.mem[addr as usize])).collect();
+                format!("[${:04x}..${:04x}] = {}", first, last, bytes.join(" "))
+            }
+        }
+    }
+}
+
+// instructions d lists at a time
+const DISASSEMBLY_LINES: usize = 16;
+
+const RTS: u8 = 0x60;
+const RTI: u8 = 0x40;
+
+// state kept between visits to the monitor
+pub struct Monitor {
+    pub turbo: bool,    // ignore --clock/--speed pacing
+    pub clock_hz: f64,  // the machine's clock, for the time the cycles take
+    pub stats: OpcodeStats, // how often each opcode has run
+    zero_page: Option<[u8; 256]>,   // zero page as zp last showed it
+    pub debug: Option<DebugInfo>,   // labels from --dbg, taken as addresses
+    listed: Option<u16>,            // where d carries on from
+    temporary: Vec<u16>,            // breakpoints that go once they're reached
+    counts: BTreeMap<u16, Counts>,  // by breakpoint
+    pub watches: Vec<Watch>,        // memory to stop on accesses to
+    displays: Vec<Display>,         // shown each time execution stops
+    return_sp: Option<u8>,          // the stack pointer rts runs to return from
+    run_to_branch: bool,            // stop at the next jump or taken branch
+}
+
+impl Monitor {
+    pub fn new() -> Monitor {
+        Monitor { turbo: false, clock_hz: DEFAULT_CLOCK_HZ, stats: OpcodeStats::new(), zero_page: None,
+            debug: None, listed: None, temporary: Vec::new(), counts: BTreeMap::new(), watches: Vec::new(),
+            displays: Vec::new(), return_sp: None,
+            run_to_branch: false }
+    }
+
+    // run a single monitor command
+    // returns the action to take if the command resumes execution
+    //   <enter> or s  step one instruction
+    //   g             run without pausing
+    //   r             show registers
+    //   turbo         toggle running without speed limiting
+    //   save-state F  save the whole machine to the file F
+    //   load-state F  carry on from the machine saved in F
+    //   break A       stop on reaching A
+    //   tbreak A      stop on reaching A, once
+    //   break list    list the addresses stopped at
+    //   delete A      stop stopping at A
+    //   until A       run until reaching A, as tbreak A then g
+    //   rts           run until an RTS or RTI returns from the subroutine
+    //                 or handler running now
+    //   irq, nmi      pull the irq or nmi line low as the next instruction ends
+    //   tick          step one instruction, showing the address, data and
+    //                 read or write of each of its cycles
+    //   branch        run until a jump, or a branch that's taken
+    //   ignore A N    pass the breakpoint at A by the next N times
+    //   watch A..B [r|w|rw]  stop on writes (or reads) from A to B
+    //   watch list    list the watches
+    //   unwatch A..B  remove the watch from A to B
+    //   display E, .. show the registers or memory ([A] or [A..B]) given
+    //                 each time execution stops, or those already given
+    //   undisplay     stop showing them
+    //   d [A]         disassemble from A, or on from the last d
+    //   xxd A-B [F]   show memory from A to B as xxd does, or write it to F
+    //   load-xxd F [A]  load the xxd dump F, its offsets taken from A
+    //   find P [A B]  search memory for hex bytes like a9??8d or text like "HI?"
+    //   cmp A1 A2 LEN show where LEN bytes at A1 and A2 differ
+    //   cmp A1 F [LEN]  show where memory at A1 differs from the file F
+    //   fill A LEN B  set LEN bytes from A to B
+    //   copy S D LEN  copy LEN bytes from S to D
+    //   stack         show the stack, with guesses at what it holds
+    //   zp            show zero page, marking bytes changed since the last zp
+    //   stats         show how often each opcode has run
+    //   stats F       write the counts to the file F as csv
+    //   stats clear   start counting again
+    //   screenshot F  save what the framebuffer or text screen shows to F as a png
+    //   paste [F]     type the host clipboard, or the file F, into the
+    //                 keyboard or serial device as the program runs
+    //   paste delay N put N cycles between pasted characters
+    //   paste stop    drop what's left to paste
+    //   q             quit
+    // addresses are hex or, with --dbg, labels. breakpoints share the
+    // list of --stop-at addresses
+    pub fn command(&mut self, line: &str, cpu: &mut Cpu, mem: &mut Memory, breakpoints: &mut Vec<u16>)
+        -> Option<MonitorAction> {
+        let line = line.trim();
+        let debug = self.debug.as_ref();
+        if let Some(arg) = line.strip_prefix("break ").map(str::trim).filter(|arg| *arg != "list") {
+            match parse_location(arg, debug) {
+                Ok(addr) => {
+                    self.add_breakpoint(breakpoints, addr, false);
+                    println!("breakpoint at ${:04x}{}", addr, self.label_text(addr));
+                }
+                Err(message) => println!("{}", message),
+            }
+            return None;
+        }
+        if let Some(arg) = line.strip_prefix("tbreak ").map(str::trim) {
+            match parse_location(arg, debug) {
+                Ok(addr) if breakpoints.contains(&addr) && !self.temporary.contains(&addr) => {
+                    println!("there's already a breakpoint at ${:04x}", addr);
+                }
+                Ok(addr) => {
+                    self.add_breakpoint(breakpoints, addr, true);
+                    println!("temporary breakpoint at ${:04x}{}", addr, self.label_text(addr));
+                }
+                Err(message) => println!("{}", message),
+            }
+            return None;
+        }
+        if let Some(arg) = line.strip_prefix("delete ").map(str::trim) {
+            match parse_location(arg, debug) {
+                Ok(addr) if breakpoints.contains(&addr) => {
+                    breakpoints.retain(|&at| at != addr);
+                    self.temporary.retain(|&at| at != addr);
+                    self.counts.remove(&addr);
+                    println!("deleted the breakpoint at ${:04x}", addr);
+                }
+                Ok(addr) => println!("no breakpoint at ${:04x}", addr),
+                Err(message) => println!("{}", message),
+            }
+            return None;
+        }
+        if let Some(args) = line.strip_prefix("watch ").filter(|args| args.trim() != "list") {
+            match self.parse_watch(args) {
+                Ok(watch) => {
+                    println!("watching {}", watch);
+                    self.watches.retain(|other| (other.first, other.last) != (watch.first, watch.last));
+                    self.watches.push(watch);
+                }
+                Err(message) => println!("{}", message),
+            }
+            return None;
+        }
+        if let Some(arg) = line.strip_prefix("unwatch ") {
+            match parse_location_range(arg.trim(), debug).or_else(|_| parse_location(arg, debug).map(|addr| (addr, addr))) {
+                Ok(range) => {
+                    let before = self.watches.len();
+                    self.watches.retain(|watch| (watch.first, watch.last) != range);
+                    if self.watches.len() < before {
+                        println!("removed the watch on ${:04x}..${:04x}", range.0, range.1);
+                    } else {
+                        println!("no watch on ${:04x}..${:04x}", range.0, range.1);
+                    }
+                }
+                Err(message) => println!("{}", message),
+            }
+            return None;
+        }
+        if let Some(args) = line.strip_prefix("ignore ") {
+            let mut words = args.split_whitespace();
+            let addr = words.next().map(|addr| parse_location(addr, debug)).unwrap_or(Err(String::from("ignore needs A N")));
+            let count = words.next().map(|count| count.parse::<u64>().map_err(|_| format!("bad count '{}'", count)))
+                .unwrap_or(Err(String::from("ignore needs A N")));
+            match (addr, count) {
+                (Ok(addr), Ok(count)) if breakpoints.contains(&addr) => {
+                    self.counts.entry(addr).or_default().ignore = count;
+                    println!("passing the breakpoint at ${:04x} by {} times", addr, count);
+                }
+                (Ok(addr), Ok(_)) => println!("no breakpoint at ${:04x}", addr),
+                (Err(message), _) | (_, Err(message)) => println!("{}", message),
+            }
+            return None;
+        }
+        if let Some(args) = line.strip_prefix("display ") {
+            match args.split(',').map(|text| Display::parse(text, debug)).collect::<Result<Vec<Display>, String>>() {
+                Ok(displays) => {
+                    self.displays.extend(displays);
+                    self.show_displays(cpu, mem);
+                }
+                Err(message) => println!("{}", message),
+            }
+            return None;
+        }
+        if let Some(arg) = line.strip_prefix("until ").map(str::trim) {
+            match parse_location(arg, debug) {
+                Ok(addr) => {
+                    self.add_breakpoint(breakpoints, addr, true);
+                    return Some(MonitorAction::Run);
+                }
+                Err(message) => println!("{}", message),
+            }
+            return None;
+        }
+        if line == "d" || line.starts_with("d ") {
+            let start = match line[1..].trim() {
+                "" => Ok(self.listed.unwrap_or(cpu.pc)),
+                arg => parse_location(arg, debug),
+            };
+            match start {
+                Ok(start) => self.listed = Some(self.disassemble(cpu, mem, start)),
+                Err(message) => println!("{}", message),
+            }
+            return None;
+        }
+        if let Some(path) = line.strip_prefix("save-state ") {
+            match save_state(path.trim(), cpu, mem) {
+                Ok(()) => println!("saved state to {}", path.trim()),
+                Err(message) => println!("{}", message),
+            }
+            return None;
+        }
+        if let Some(path) = line.strip_prefix("load-state ") {
+            match load_state(path.trim(), cpu, mem) {
+                Ok(()) => print_registers(cpu),
+                Err(message) => println!("{}", message),
+            }
+            return None;
+        }
+        if let Some(rest) = line.strip_prefix("xxd ") {
+            let mut words = rest.split_whitespace();
+            let range = words.next().map(|range| parse_location_range(range, debug)).unwrap_or(Err(String::from("xxd needs A-B")));
+            match (range, words.next()) {
+                (Ok((first, last)), None) => print!("{}", hexdump::format(mem, first, last)),
+                (Ok((first, last)), Some(path)) => match hexdump::save(mem, path, first, last) {
+                    Ok(()) => println!("wrote ${:04x}-${:04x} to {}", first, last, path),
+                    Err(message) => println!("{}", message),
+                },
+                (Err(message), _) => println!("{}", message),
+            }
+            return None;
+        }
+        if let Some(rest) = line.strip_prefix("load-xxd ") {
+            let mut words = rest.split_whitespace();
+            let path = words.next().unwrap_or("");
+            let base = words.next().map(|base| parse_location(base, debug)).unwrap_or(Ok(0));
+            match base.and_then(|base| hexdump::load(mem, path, base)) {
+                Ok(count) => println!("loaded {} bytes from {}", count, path),
+                Err(message) => println!("{}", message),
+            }
+            return None;
+        }
+        if let Some(rest) = line.strip_prefix("find ") {
+            match memtools::find_command(mem, rest, debug) {
+                Ok(text) => print!("{}", text),
+                Err(message) => println!("{}", message),
+            }
+            return None;
+        }
+        if let Some(rest) = line.strip_prefix("cmp ") {
+            match memtools::compare_command(mem, rest, debug) {
+                Ok(text) => print!("{}", text),
+                Err(message) => println!("{}", message),
+            }
+            return None;
+        }
+        if let Some(rest) = line.strip_prefix("fill ") {
+            match memtools::fill_command(mem, rest, debug) {
+                Ok(text) => print!("{}", text),
+                Err(message) => println!("{}", message),
+            }
+            return None;
+        }
+        if let Some(rest) = line.strip_prefix("copy ") {
+            match memtools::copy_command(mem, rest, debug) {
+                Ok(text) => print!("{}", text),
+                Err(message) => println!("{}", message),
+            }
+            return None;
+        }
+        if let Some(path) = line.strip_prefix("screenshot ").map(str::trim) {
+            match png::screenshot(mem, path) {
+                Ok((width, height)) => println!("wrote the {}x{} screen to {}", width, height, path),
+                Err(message) => println!("{}", message),
+            }
+            return None;
+        }
+        if line == "paste" || line.starts_with("paste ") {
+            match paste_command(&mem.inputs, line["paste".len()..].trim()) {
+                Ok(text) => println!("{}", text),
+                Err(message) => println!("{}", message),
+            }
+            return None;
+        }
+        if let Some(path) = line.strip_prefix("stats ").map(str::trim).filter(|rest| *rest != "clear") {
+            match self.stats.save_csv(cpu.variant, path) {
+                Ok(()) => println!("wrote opcode counts to {}", path),
+                Err(message) => println!("{}", message),
+            }
+            return None;
+        }
+        match line {
+            "" | "s" => return Some(MonitorAction::Step),
+            "g" => return Some(MonitorAction::Run),
+            "irq" => {
+                mem.inputs.pulse_irq();
+                let masked = if cpu.status() & STATUS_BIT_INT_DIS != 0 { ", but I is set" } else { "" };
+                println!("irq is low as the next instruction ends{}", masked);
+            }
+            "nmi" => {
+                mem.inputs.pulse_nmi();
+                println!("nmi is low as the next instruction ends");
+            }
+            "branch" => {
+                self.run_to_branch = true;
+                return Some(MonitorAction::Run);
+            }
+            "rts" => {
+                self.return_sp = Some(cpu.sp);
+                return Some(MonitorAction::Run);
+            }
+            "q" => return Some(MonitorAction::Quit),
+            "r" => print_registers(cpu),
+            "turbo" => self.toggle_turbo(),
+            "stack" => print!("{}", stackview::format(cpu, mem)),
+            "zp" => {
+                print!("{}", zero_page_text(mem, self.zero_page.as_ref()));
+                let mut zero_page = [0; 256];
+                zero_page.copy_from_slice(&mem.mem[..256]);
+                self.zero_page = Some(zero_page);
+            }
+            "break" | "break list" => self.list_breakpoints(breakpoints),
+            "display" if self.displays.is_empty() => println!("nothing is displayed"),
+            "display" => self.show_displays(cpu, mem),
+            "undisplay" => self.displays.clear(),
+            "watch" | "watch list" => {
+                if self.watches.is_empty() {
+                    println!("no watches");
+                }
+                for watch in &self.watches {
+                    println!("{}", watch);
+                }
+            }
+            "stats" => print!("{}", self.stats.report(cpu.variant)),
+            "stats clear" => {
+                self.stats.clear();
+                println!("opcode counts cleared");
+            }
+            // the cpu runs an instruction's cycles all at once, so there's
+            // no stopping between them; the step logs each one instead,
+            // and stopped shows them
+            "tick" => {
+                mem.bus_log = Some(Vec::new());
+                return Some(MonitorAction::Step);
+            }
+            other => println!("unknown command '{}'", other),
+        }
+        None
+    }
+
+    // the label an address is at or after, like " <main+3>"
+    fn label_text(&self, addr: u16) -> String {
+        match self.debug.as_ref().and_then(|debug| debug.label_before(addr)) {
+            Some((label, 0)) => format!(" <{}>", label),
+            Some((label, offset)) => format!(" <{}+{}>", label, offset),
+            None => String::new(),
+        }
+    }
+
+    // a temporary breakpoint doesn't make one already there go once it's
+    // reached, and a lasting one makes a temporary one stay
+    fn add_breakpoint(&mut self, breakpoints: &mut Vec<u16>, addr: u16, temporary: bool) {
+        if !breakpoints.contains(&addr) {
+            breakpoints.push(addr);
+            if temporary {
+                self.temporary.push(addr);
+            }
+        } else if !temporary {
+            self.temporary.retain(|&at| at != addr);
+        }
+    }
+
+    fn list_breakpoints(&self, breakpoints: &[u16]) {
+        if breakpoints.is_empty() {
+            println!("no breakpoints");
+        }
+        for &addr in breakpoints {
+            let once = if self.temporary.contains(&addr) { " (once)" } else { "" };
+            let counts = self.counts.get(&addr).map_or(String::new(), |counts| match counts.ignore {
+                0 => format!(", hit {} times", counts.hits),
+                ignore => format!(", hit {} times, passing by {} more", counts.hits, ignore),
+            });
+            println!("${:04x}{}{}{}", addr, self.label_text(addr), once, counts);
+        }
+    }
+
+    // count a breakpoint being reached; returns whether to stop there.
+    // only running free passes it by: stepping onto it leaves the
+    // ignore count as it was
+    pub fn breakpoint_reached(&mut self, addr: u16, running: bool) -> bool {
+        let counts = self.counts.entry(addr).or_default();
+        counts.hits += 1;
+        if running && counts.ignore > 0 {
+            counts.ignore -= 1;
+            return false;
+        }
+        true
+    }
+
+    // list the instructions from start, with the labels at them, and
+    // return the address after the last
+    fn disassemble(&self, cpu: &Cpu, mem: &Memory, start: u16) -> u16 {
+        let mut addr = start;
+        for _ in 0..DISASSEMBLY_LINES {
+            if let Some((label, 0)) = self.debug.as_ref().and_then(|debug| debug.label_before(addr)) {
+                println!("{}:", label);
+            }
+            let (text, len) = disasm::disassemble(cpu.variant, mem, addr);
+            let marker = if addr == cpu.pc { ">" } else { " " };
+            println!("{} {}\t{}", marker, color::address(addr), color::instruction(&text));
+            addr = addr.wrapping_add(len);
+        }
+        addr
+    }
+
+    // a watch command's range, a single address or A..B, then what to
+    // watch for, writes by default
+    fn parse_watch(&self, args: &str) -> Result<Watch, String> {
+        let debug = self.debug.as_ref();
+        let mut words = args.split_whitespace();
+        let range = words.next().ok_or("watch needs an address or A..B")?;
+        let (first, last) = if range.contains("..") || range.contains('-') {
+            parse_location_range(range, debug)?
+        } else {
+            let addr = parse_location(range, debug)?;
+            (addr, addr)
+        };
+        let (read, write) = match words.next() {
+            None | Some("w") => (false, true),
+            Some("r") => (true, false),
+            Some("rw") => (true, true),
+            Some(other) => return Err(format!("bad watch kind '{}', should be r, w or rw", other)),
+        };
+        Ok(Watch { first, last, read, write })
+    }
+
+    // after the instruction at pc, whether a watch, rts or branch stops
+    // execution
+    pub fn check_stop(&self, cpu: &Cpu, mem: &Memory, pc: u16, opcode: u8) -> Option<StopReason> {
+        self.check_watches(mem, pc)
+            .or_else(|| self.check_return(cpu, pc, opcode))
+            .or_else(|| self.check_branch(cpu, mem, pc, opcode))
+    }
+
+    // the first access the instruction at pc made that a watch covers,
+    // or failing that the first an interrupt taken after it made
+    fn check_watches(&self, mem: &Memory, pc: u16) -> Option<StopReason> {
+        let covered = |access: &&IoAccess| self.watches.iter().any(|watch| watch.covers(access));
+        let (accesses, entry) = mem.logged_accesses();
+        accesses.iter().find(covered).map(|access| StopReason::Watched(pc, *access))
+            .or_else(|| {
+                let name = mem.interrupt_entry.as_ref()?.name;
+                entry.iter().find(covered).map(|access| StopReason::WatchedEntry(name, *access))
+            })
+    }
+
+    // after the instruction at pc, whether it was the RTS or RTI that
+    // returned from where rts was given: one that takes the stack pointer
+    // above where it was then, the calls made since having returned
+    fn check_return(&self, cpu: &Cpu, pc: u16, opcode: u8) -> Option<StopReason> {
+        let sp = self.return_sp?;
+        let returned = (opcode == RTS || opcode == RTI) && (cpu.sp.wrapping_sub(sp) as i8) > 0;
+        returned.then_some(StopReason::Returned(pc, opcode))
+    }
+
+    // after the instruction at pc, whether it was a jump, or a branch
+    // that was taken, for branch
+    fn check_branch(&self, cpu: &Cpu, mem: &Memory, pc: u16, opcode: u8) -> Option<StopReason> {
+        if !self.run_to_branch {
+            return None;
+        }
+        let mnemonic = instruction_text(cpu.variant)[opcode as usize];
+        if matches!(mnemonic, "JMP" | "JSR" | "RTS" | "RTI" | "BRK") {
+            return Some(StopReason::Branched(pc, cpu.pc));
+        }
+        if !matches!(disasm::addr_modes(cpu.variant)[opcode as usize], AddrMode::Rel | AddrMode::Zpr) {
+            return None;
+        }
+        // the offset is the branch's last byte. an interrupt taken after
+        // it leaves the pc at neither the target nor the fall-through,
+        // and where the branch went is the address it pushed
+        let len = disasm::instruction_len(cpu.variant, opcode);
+        let fall_through = pc.wrapping_add(len);
+        let offset = mem.mem[pc.wrapping_add(len - 1) as usize] as i8;
+        let target = fall_through.wrapping_add_signed(offset as i16);
+        let went = if cpu.pc == target || cpu.pc == fall_through {
+            cpu.pc
+        } else {
+            let pushed = |i: u8| mem.mem[0x0100 + cpu.sp.wrapping_add(i) as usize] as u16;
+            pushed(2) | pushed(3) << 8
+        };
+        (went == target && target != fall_through).then_some(StopReason::Branched(pc, target))
+    }
+
+    fn show_displays(&self, cpu: &Cpu, mem: &Memory) {
+        if !self.displays.is_empty() {
+            let texts: Vec<String> = self.displays.iter().map(|display| display.text(cpu, mem)).collect();
+            println!("{}", texts.join("  "));
+        }
+    }
+
+    // each time execution stops: the cycles a tick logged are shown, the
+    // displays are shown, a temporary breakpoint at the pc has been
+    // reached and goes, and d starts again from the pc
+    pub fn stopped(&mut self, cpu: &Cpu, mem: &mut Memory, breakpoints: &mut Vec<u16>) {
+        let pc = cpu.pc;
+        if let Some(cycles) = mem.bus_log.take() {
+            print!("{}", cycles_text(&cycles));
+        }
+        self.show_displays(cpu, mem);
+        self.listed = None;
+        self.return_sp = None;
+        self.run_to_branch = false;
+        if self.temporary.contains(&pc) {
+            self.temporary.retain(|&at| at != pc);
+            self.counts.remove(&pc);
+            breakpoints.retain(|&at| at != pc);
+        }
+    }
+
+    pub fn toggle_turbo(&mut self) {
+        self.turbo = !self.turbo;
+        println!("turbo {}", if self.turbo { "on" } else { "off" });
+    }
+
+    // read commands from stdin until one of them resumes execution
+    pub fn prompt(&mut self, cpu: &mut Cpu, mem: &mut Memory, breakpoints: &mut Vec<u16>) -> MonitorAction {
+        self.stopped(cpu, mem, breakpoints);
+        loop {
+            let user_input = match read_command(&format!("[{}] > ", elapsed_text(cpu.cycles, self.clock_hz))) {
+                Some(line) => line,
+                None => return MonitorAction::Abort,
+            };
+            if let Some(action) = self.command(&user_input, cpu, mem, breakpoints) {
+                return action;
+            }
+        }
+    }
+}
+
+// the paste command: the clipboard, or a file, typed in as keys
+fn paste_command(inputs: &Inputs, args: &str) -> Result<String, String> {
+    let words: Vec<&str> = args.split_whitespace().collect();
+    let text = match words.as_slice() {
+        ["delay", cycles] => {
+            let cycles = cycles.parse::<u64>().map_err(|_| format!("bad delay '{}', should be cycles", cycles))?;
+            inputs.set_paste_delay(cycles);
+            return Ok(format!("pasted characters go {} cycles apart", cycles));
+        }
+        ["delay"] => return Ok(format!("pasted characters go {} cycles apart", inputs.paste_delay())),
+        ["stop"] => return Ok(format!("stopped pasting with {} characters left", inputs.cancel_paste())),
+        [] => input::clipboard()?,
+        _ => fs::read_to_string(args).map_err(|e| format!("{}: {}", args, e))?,
+    };
+    let mut out = format!("pasting {} characters, {} cycles apart, as the program runs",
+        inputs.paste(&text), inputs.paste_delay());
+    if !inputs.keyboard_claimed() {
+        out.push_str("; there's no keyboard or serial device to take them");
+    }
+    Ok(out)
+}
+
+// the cycles run and the time they take at clock_hz
+//   1023000 cycles, 1.000 s
+pub fn elapsed_text(cycles: u64, clock_hz: f64) -> String {
+    let seconds = cycles as f64 / clock_hz;
+    let time = if seconds >= 1.0 {
+        format!("{:.3} s", seconds)
+    } else if seconds >= 1e-3 {
+        format!("{:.3} ms", seconds * 1e3)
+    } else {
+        format!("{:.3} us", seconds * 1e6)
+    };
+    format!("{} cycles, {}", cycles, time)
+}
+
+// read a line from stdin after printing the prompt
+// returns None at end of input
+pub fn read_command(prompt: &str) -> Option<String> {
+    print!("{}", prompt);
+    let _result = io::stdout().flush();
+    input::read_line()
+}
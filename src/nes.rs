@@ -0,0 +1,166 @@
+// NES cpu test roms, run headless on the skeleton NES
+//
+// blargg's test roms report through the cartridge ram: once $6001-$6003
+// hold the signature $de $b0 $61, $6000 is $80 while the test runs, $81
+// when it wants the reset button pressed, and otherwise the result code
+// (0 for passed), with a message as text from $6004. a result only
+// counts once the rom has reported that it is running.
+//
+// nestest runs in its automation mode with --nestest: it starts at
+// $c000 and ends with the RTS at $c66e, leaving the number of the first
+// failed official opcode test in $02 and unofficial one in $03.
+
+use std::path::Path;
+
+use crate::cpu::{reset_cpu, Cpu, CpuVariant};
+use crate::devices;
+use crate::devices::DeviceConfig;
+use crate::memory::Memory;
+use crate::monitor::print_registers;
+use crate::profiles;
+use crate::toml::{Table, Value};
+use crate::{print_code_around, step, StopConditions, StopReason};
+
+const NES_CLOCK_HZ: f64 = 1.789773e6;
+
+const NESTEST_START: u16 = 0xc000;
+const NESTEST_END: u16 = 0xc66e;
+
+const STATUS_ADDR: usize = 0x6000;
+const SIGNATURE_ADDR: usize = 0x6001;
+const TEXT_ADDR: usize = 0x6004;
+const SIGNATURE: [u8; 3] = [0xde, 0xb0, 0x61];
+const STATUS_RUNNING: u8 = 0x80;
+const STATUS_RESET: u8 = 0x81;
+
+// the reset button is pressed this long after a test asks for it
+const RESET_DELAY_CYCLES: u64 = 200_000;
+
+// give up if the test hasn't finished after this many instructions
+const MAX_INSTRUCTIONS: u64 = 500_000_000;
+
+pub struct NesOptions {
+    pub path: String,
+    pub nestest: bool,      // run nestest in automation mode
+}
+
+// the skeleton NES with the cartridge loaded
+fn build_machine(path: &str) -> Result<Memory, String> {
+    let mut mem = Memory::new();
+    let machine = profiles::builtin("nes", Path::new(".")).ok_or("no nes profile")?;
+    let mut cart = Table::new();
+    cart.insert(String::from("file"), Value::String(path.to_string()));
+    let cart = DeviceConfig { kind: String::from("nes-cart"), base: 0x8000, options: cart };
+    for device in machine.devices.iter().chain([&cart]) {
+        devices::attach_device(&mut mem, device, NES_CLOCK_HZ)?;
+    }
+    Ok(mem)
+}
+
+// the status blargg's roms report, once they have signed the ram
+fn blargg_status(mem: &Memory) -> Option<u8> {
+    let signed = mem.mem[SIGNATURE_ADDR..SIGNATURE_ADDR + 3] == SIGNATURE;
+    signed.then_some(mem.mem[STATUS_ADDR])
+}
+
+fn blargg_text(mem: &Memory) -> String {
+    let text = &mem.mem[TEXT_ADDR..0x8000];
+    let end = text.iter().position(|&b| b == 0).unwrap_or(text.len());
+    String::from_utf8_lossy(&text[..end]).trim_end().to_string()
+}
+
+// run a test rom and report the outcome
+// returns true if it passed
+pub fn run_nes(options: &NesOptions) -> bool {
+    let mut cpu = Cpu::new();
+    cpu.variant = CpuVariant::Ricoh2a03;
+    let mut mem = match build_machine(&options.path) {
+        Ok(mem) => mem,
+        Err(message) => {
+            eprintln!("e6502r: {}", message);
+            return false;
+        }
+    };
+    reset_cpu(&mut cpu, &mem);
+    if options.nestest {
+        // the state nestest.log starts from
+        cpu.pc = NESTEST_START;
+        cpu.sp = 0xfd;
+        cpu.set_status(0x24);
+        cpu.cycles = 7;
+    }
+
+    let stops = StopConditions {
+        trap_on_brk: true,
+        strict_opcodes: true,
+        stop_at: if options.nestest { vec![NESTEST_END] } else { Vec::new() },
+        smc: None,
+    };
+    let mut executed: u64 = 0;
+    let mut reset_at = None;
+    let mut running = false;        // the rom has reported it is running
+    let reason = loop {
+        if executed == MAX_INSTRUCTIONS {
+            break None;
+        }
+        executed += 1;
+        if let Some(reason) = step(&mut cpu, &mut mem, &stops) {
+            break Some(reason);
+        }
+        match blargg_status(&mem) {
+            Some(STATUS_RUNNING) => running = true,
+            Some(STATUS_RESET) => match reset_at {
+                None => reset_at = Some(cpu.cycles + RESET_DELAY_CYCLES),
+                Some(at) if cpu.cycles >= at => {
+                    reset_at = None;
+                    mem.mem[STATUS_ADDR] = STATUS_RUNNING;
+                    reset_cpu(&mut cpu, &mem);
+                }
+                Some(_) => {}
+            },
+            Some(_) if running => break None,
+            _ => {}
+        }
+    };
+
+    if options.nestest {
+        let (official, unofficial) = (mem.mem[0x02], mem.mem[0x03]);
+        return match reason {
+            Some(StopReason::StopAddress(_)) if official == 0 && unofficial == 0 => {
+                println!("nestest passed: {} instructions, {} cycles", executed, cpu.cycles);
+                true
+            }
+            Some(StopReason::StopAddress(_)) => {
+                println!("nestest failed: official ${:02x}, unofficial ${:02x}", official, unofficial);
+                false
+            }
+            _ => report_stuck("nestest", reason, executed, &cpu, &mem),
+        };
+    }
+    match blargg_status(&mem) {
+        Some(status) if status < STATUS_RUNNING => {
+            let text = blargg_text(&mem);
+            if !text.is_empty() {
+                println!("{}", text);
+            }
+            if status == 0 {
+                println!("{} passed: {} instructions, {} cycles", options.path, executed, cpu.cycles);
+            } else {
+                println!("{} failed with code {}", options.path, status);
+            }
+            status == 0
+        }
+        _ => report_stuck(&options.path, reason, executed, &cpu, &mem),
+    }
+}
+
+// the test stopped, or never finished, without reporting a result
+fn report_stuck(name: &str, reason: Option<StopReason>, executed: u64, cpu: &Cpu, mem: &Memory) -> bool {
+    match reason {
+        Some(reason) => println!("{} stopped without a result: {}", name, reason),
+        None => println!("{} still running after {} instructions at ${:04x}", name, executed, cpu.pc),
+    }
+    print_registers(cpu);
+    print_code_around(cpu, mem);
+    false
+}
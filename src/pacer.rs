@@ -0,0 +1,35 @@
+
+// keeps emulation in step with wall-clock time
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+// don't bother sleeping for less than this
+const MIN_SLEEP: f64 = 0.001;
+
+pub struct Pacer {
+    rate: f64,          // instructions or cycles per second
+    start: Instant,     // when pacing (re)started
+    base: u64,          // count when pacing (re)started
+}
+
+impl Pacer {
+    pub fn new(rate: f64, count: u64) -> Pacer {
+        Pacer { rate, start: Instant::now(), base: count }
+    }
+
+    // start over from the current count, forgetting time spent elsewhere
+    pub fn restart(&mut self, count: u64) {
+        self.start = Instant::now();
+        self.base = count;
+    }
+
+    // sleep if emulation has got ahead of real time
+    pub fn pace(&mut self, count: u64) {
+        let due = count.wrapping_sub(self.base) as f64 / self.rate;
+        let ahead = due - self.start.elapsed().as_secs_f64();
+        if ahead >= MIN_SLEEP {
+            thread::sleep(Duration::from_secs_f64(ahead));
+        }
+    }
+}
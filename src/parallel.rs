@@ -0,0 +1,40 @@
+// running many independent machines at once
+//
+// run_machines gives each of a number of jobs a fresh cpu and memory of
+// its own, with its own inputs and interrupt pulses, and hands the jobs to a rayon pool of the given size. idle
+// threads steal work from busy ones, so jobs of uneven length keep
+// every thread busy. the results come back in job order, whatever order
+// the jobs finished in.
+
+use std::thread;
+
+use rayon::prelude::*;
+use rayon::ThreadPoolBuilder;
+
+use crate::cpu::Cpu;
+use crate::memory::Memory;
+
+// one thread per core the host has
+pub fn default_threads() -> usize {
+    thread::available_parallelism().map(|threads| threads.get()).unwrap_or(1)
+}
+
+pub fn run_machines<T, F>(count: usize, threads: usize, job: F) -> Vec<T>
+where
+    T: Send,
+    F: Fn(usize, &mut Cpu, &mut Memory) -> T + Sync,
+{
+    let run = || (0..count).into_par_iter()
+        .map(|index| {
+            let mut cpu = Cpu::new();
+            let mut mem = Memory::new();
+            job(index, &mut cpu, &mut mem)
+        })
+        .collect();
+    // a pool of its own, so the thread count is the one asked for; a
+    // panicking job is passed on to the caller
+    match ThreadPoolBuilder::new().num_threads(threads.clamp(1, count.max(1))).build() {
+        Ok(pool) => pool.install(run),
+        Err(_) => run(),
+    }
+}
@@ -0,0 +1,95 @@
+// png files of what a display shows, for the monitor's screenshot
+// command and --screenshot
+//
+// the image data is 8-bit rgb, each row with filter type 0, in a zlib
+// stream of stored deflate blocks: nothing is compressed, so nothing is
+// needed beyond a crc and an adler checksum. screens are small enough
+// that the files are too.
+
+use std::fs;
+
+use crate::memory::{Memory, Picture};
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a];
+const MAX_STORED: usize = 0xffff;       // bytes a stored deflate block holds at most
+
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = 0xffff_ffffu32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xedb8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+fn adler32(bytes: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in bytes {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+// a chunk: its length, type, data and the crc of the type and data
+fn chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    out.extend((data.len() as u32).to_be_bytes());
+    let start = out.len();
+    out.extend(kind);
+    out.extend(data);
+    let crc = crc32(&out[start..]);
+    out.extend(crc.to_be_bytes());
+}
+
+// raw bytes in a zlib stream, stored rather than compressed
+fn zlib_stored(raw: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x78, 0x01];
+    let blocks: Vec<&[u8]> = raw.chunks(MAX_STORED).collect();
+    if blocks.is_empty() {
+        out.extend([0x01, 0x00, 0x00, 0xff, 0xff]);
+    }
+    for (index, block) in blocks.iter().enumerate() {
+        out.push((index + 1 == blocks.len()) as u8);
+        let len = block.len() as u16;
+        out.extend(len.to_le_bytes());
+        out.extend((!len).to_le_bytes());
+        out.extend(*block);
+    }
+    out.extend(adler32(raw).to_be_bytes());
+    out
+}
+
+// the png file's bytes for a picture
+pub fn encode(picture: &Picture) -> Vec<u8> {
+    let mut header = Vec::new();
+    header.extend((picture.width as u32).to_be_bytes());
+    header.extend((picture.height as u32).to_be_bytes());
+    header.extend([8, 2, 0, 0, 0]);     // 8 bits a sample, rgb, no interlace
+
+    let mut raw = Vec::with_capacity((picture.width * 3 + 1) * picture.height);
+    for row in picture.pixels.chunks(picture.width.max(1)) {
+        raw.push(0);
+        for &colour in row {
+            raw.extend([(colour >> 16) as u8, (colour >> 8) as u8, colour as u8]);
+        }
+    }
+
+    let mut out = SIGNATURE.to_vec();
+    chunk(&mut out, b"IHDR", &header);
+    chunk(&mut out, b"IDAT", &zlib_stored(&raw));
+    chunk(&mut out, b"IEND", &[]);
+    out
+}
+
+pub fn save(picture: &Picture, path: &str) -> Result<(), String> {
+    fs::write(path, encode(picture)).map_err(|e| format!("can't write {}: {}", path, e))
+}
+
+// save what the first display attached shows; returns its size
+pub fn screenshot(mem: &Memory, path: &str) -> Result<(usize, usize), String> {
+    let picture = mem.picture().ok_or(String::from("there's no framebuffer or text screen to take a screenshot of"))?;
+    save(&picture, path)?;
+    Ok((picture.width, picture.height))
+}
@@ -0,0 +1,78 @@
+// the registers at power on, for software that depends on them, or
+// that should be tried against values it can't count on
+//
+//   --power-on a=00,x=00,y=00,sp=fd,p=34
+//   --power-on random               # any not given, from the clock
+//   --power-on random=SEED,sp=ff
+//
+// values are hex. the registers otherwise start at zero, with the stack
+// pointer at $ff from the reset; the pc always comes from the reset
+// vector. a random start reports its seed, which --record keeps for
+// --replay, as the random device does.
+
+use crate::args::parse_addr;
+use crate::cpu::Cpu;
+use crate::devices;
+use crate::replay::Inputs;
+
+#[derive(Clone, Default)]
+pub struct PowerOn {
+    pub a: Option<u8>,
+    pub x: Option<u8>,
+    pub y: Option<u8>,
+    pub sp: Option<u8>,
+    pub p: Option<u8>,
+    pub random: Option<Option<u64>>,    // randomize the rest, from a seed or the clock
+}
+
+// parse a list like a=00,sp=fd,random
+pub fn parse(text: &str) -> Result<PowerOn, String> {
+    let mut power_on = PowerOn::default();
+    for part in text.split(',') {
+        let (key, value) = match part.split_once('=') {
+            Some((key, value)) => (key.trim(), Some(value.trim())),
+            None => (part.trim(), None),
+        };
+        if key == "random" {
+            let seed = value.map(|seed| seed.parse::<u64>().map_err(|_| format!("bad power-on seed '{}'", seed)))
+                .transpose()?;
+            power_on.random = Some(seed);
+            continue;
+        }
+        let value = value.ok_or(format!("power-on register '{}' needs a value", key))?;
+        let byte = parse_addr(value).ok()
+            .and_then(|value| u8::try_from(value).ok())
+            .ok_or(format!("bad power-on value '{}'", value))?;
+        let register = match key.to_lowercase().as_str() {
+            "a" => &mut power_on.a,
+            "x" => &mut power_on.x,
+            "y" => &mut power_on.y,
+            "sp" | "s" => &mut power_on.sp,
+            "p" => &mut power_on.p,
+            _ => return Err(format!("unknown power-on register '{}'", key)),
+        };
+        *register = Some(byte);
+    }
+    Ok(power_on)
+}
+
+// set the registers after a reset
+pub fn apply(cpu: &mut Cpu, inputs: &Inputs, power_on: &PowerOn) {
+    let mut random = power_on.random.map(|seed| {
+        let seed = seed.unwrap_or_else(|| {
+            let seed = inputs.seed(devices::clock_seed());
+            eprintln!("power-on registers are seeded with {}", seed);
+            seed
+        });
+        devices::random_bytes(seed)
+    });
+    let mut pick = |given: Option<u8>, otherwise: u8| {
+        given.unwrap_or_else(|| random.as_mut().map_or(otherwise, |next| next()))
+    };
+    cpu.ac = pick(power_on.a, cpu.ac);
+    cpu.xr = pick(power_on.x, cpu.xr);
+    cpu.yr = pick(power_on.y, cpu.yr);
+    cpu.sp = pick(power_on.sp, cpu.sp);
+    let status = pick(power_on.p, cpu.status());
+    cpu.set_status(status);
+}
@@ -0,0 +1,127 @@
+
+// built-in machine profiles, used with --machine NAME
+//
+// rom images are not included; each profile names the files it loads
+// from the rom directory (--rom-dir, default the current directory)
+//
+//   apple1    8K RAM, PIA keyboard and display at $d010, Woz Monitor
+//             (wozmon.bin, 256 bytes) at $ff00
+//   apple2    48K RAM, keyboard at $c000, text page 1 drawn on the
+//             terminal, 12K of rom (apple2.rom: Integer BASIC or
+//             Applesoft with the monitor) at $d000
+//   pet       PET 2001: 32K RAM, screen at $8000, keyboard and retrace
+//             PIA at $e810, second PIA at $e820, VIA at $e840, BASIC
+//             (pet-basic.rom, 8K) at $c000, screen editor (pet-edit.rom,
+//             2K) at $e000 and kernal (pet-kernal.rom, 4K) at $f000
+//   c64       Commodore 64 without video: 64K RAM banked under BASIC
+//             (c64-basic.rom, 8K), the kernal (c64-kernal.rom, 8K) and
+//             the character rom (c64-chargen.rom, 4K) or i/o, with the
+//             screen at $0400 drawn on the terminal
+//   eater     Ben Eater's breadboard computer: 16K RAM, a 6522 at $6000
+//             with a 16x2 lcd and a button, 32K of rom (eater.bin) at
+//             $8000
+//   nes       skeleton NES with a 2a03: 2K RAM mirrored to $1fff, PPU
+//             and APU registers; add the cartridge with
+//             --device nes-cart@8000,file=GAME.nes (mapper 0 only)
+
+use std::path::Path;
+
+use crate::cpu::CpuVariant;
+use crate::devices::DeviceConfig;
+use crate::machine::{MachineConfig, RomImage};
+use crate::toml::{Table, Value};
+
+fn device(kind: &str, base: u16) -> DeviceConfig {
+    DeviceConfig { kind: kind.to_string(), base, options: Table::new() }
+}
+
+fn rom(rom_dir: &Path, file: &str, addr: u16) -> RomImage {
+    RomImage { path: rom_dir.join(file), addr }
+}
+
+// the profile with this name, if there is one
+pub fn builtin(name: &str, rom_dir: &Path) -> Option<MachineConfig> {
+    match name {
+        "apple1" => Some(MachineConfig {
+            name: String::from("apple1"),
+            variant: Some(CpuVariant::Nmos6502),
+            clock_hz: Some(1.023e6),
+            ram_size: 8 * 1024,
+            reset_vector: None,
+            power_on: None,
+            roms: vec![rom(rom_dir, "wozmon.bin", 0xff00)],
+            devices: vec![device("apple1-io", 0xd010)],
+        }),
+        "apple2" => {
+            let mut keyboard = device("keyboard", 0xc000);
+            keyboard.options.insert(String::from("uppercase"), Value::Boolean(true));
+            Some(MachineConfig {
+                name: String::from("apple2"),
+                variant: Some(CpuVariant::Nmos6502),
+                clock_hz: Some(1.023e6),
+                ram_size: 48 * 1024,
+                reset_vector: None,
+                power_on: None,
+                roms: vec![rom(rom_dir, "apple2.rom", 0xd000)],
+                devices: vec![keyboard, device("apple2-text", 0x0400)],
+            })
+        }
+        "pet" => Some(MachineConfig {
+            name: String::from("pet"),
+            variant: Some(CpuVariant::Nmos6502),
+            clock_hz: Some(1e6),
+            ram_size: 32 * 1024,
+            reset_vector: None,
+            power_on: None,
+            roms: vec![
+                rom(rom_dir, "pet-basic.rom", 0xc000),
+                rom(rom_dir, "pet-edit.rom", 0xe000),
+                rom(rom_dir, "pet-kernal.rom", 0xf000),
+            ],
+            devices: vec![
+                device("pet-screen", 0x8000),
+                device("pet-io", 0xe810),
+                device("pia", 0xe820),
+                device("via", 0xe840),
+            ],
+        }),
+        "c64" => {
+            let mut port = device("c64-port", 0x0000);
+            for (key, file) in [("basic", "c64-basic.rom"), ("kernal", "c64-kernal.rom"), ("chargen", "c64-chargen.rom")] {
+                let path = rom_dir.join(file).to_string_lossy().into_owned();
+                port.options.insert(key.to_string(), Value::String(path));
+            }
+            Some(MachineConfig {
+                name: String::from("c64"),
+                variant: Some(CpuVariant::Nmos6502),
+                clock_hz: Some(0.985e6),
+                ram_size: 64 * 1024,
+                reset_vector: None,
+                power_on: None,
+                roms: Vec::new(),
+                devices: vec![device("c64-io", 0xd000), device("c64-screen", 0x0400), port],
+            })
+        }
+        "eater" => Some(MachineConfig {
+            name: String::from("eater"),
+            variant: Some(CpuVariant::Nmos6502),
+            clock_hz: Some(1e6),
+            ram_size: 16 * 1024,
+            reset_vector: None,
+            power_on: None,
+            roms: vec![rom(rom_dir, "eater.bin", 0x8000)],
+            devices: vec![device("eater-io", 0x6000)],
+        }),
+        "nes" => Some(MachineConfig {
+            name: String::from("nes"),
+            variant: Some(CpuVariant::Ricoh2a03),
+            clock_hz: Some(1.789773e6),
+            ram_size: 2 * 1024,
+            reset_vector: None,
+            power_on: None,
+            roms: Vec::new(),
+            devices: vec![device("nes-ram", 0x0000), device("nes-ppu", 0x2000), device("nes-apu", 0x4000)],
+        }),
+        _ => None,
+    }
+}
@@ -0,0 +1,86 @@
+// random instruction sequences run through the cpu
+//
+// each run fills all of memory from its own seed, resets the cpu and
+// executes a number of instructions, checking what has to hold whatever
+// the program does: no handler panics and the unused status bit stays
+// set. runs are shared out over threads with parallel::run_machines.
+// run N uses seed SEED+N, so a failure can be repeated on its own with
+// --seed and --count 1.
+
+use std::panic;
+use std::panic::AssertUnwindSafe;
+
+use crate::cpu::{opcode_table, reset_cpu, Cpu, CpuVariant, STATUS_FLAGS_UNUSED};
+use crate::devices::clock_seed;
+use crate::memory::Memory;
+use crate::parallel::{default_threads, run_machines};
+
+// failures shown in full
+const MAX_REPORTED: usize = 10;
+
+pub struct RandomOptions {
+    pub count: usize,           // number of runs
+    pub steps: usize,           // instructions in each run
+    pub variant: CpuVariant,
+    pub seed: Option<u64>,      // seed of the first run; from the clock if not given
+    pub threads: Option<usize>, // one per core if not given
+}
+
+// run them all and print a summary
+// returns true if every run held up
+pub fn run_random(options: &RandomOptions) -> bool {
+    let seed = options.seed.unwrap_or_else(clock_seed);
+    let threads = options.threads.unwrap_or_else(default_threads);
+
+    // a panicking handler is reported as a failure, not on stderr
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+    let results = run_machines(options.count, threads, |index, cpu, mem| {
+        let run_seed = seed.wrapping_add(index as u64);
+        run_one(run_seed, options, cpu, mem).map_err(|problem| (run_seed, problem))
+    });
+    panic::set_hook(default_hook);
+
+    let failures: Vec<&(u64, String)> = results.iter().filter_map(|result| result.as_ref().err()).collect();
+    for (run_seed, problem) in failures.iter().take(MAX_REPORTED) {
+        println!("FAIL  seed {}: {}", run_seed, problem);
+    }
+    let threads = threads.clamp(1, options.count.max(1));
+    let plural = |n: usize| if n == 1 { "" } else { "s" };
+    println!("{} run{} of {} instructions from seed {} on {} thread{}: {} passed, {} failed",
+        options.count, plural(options.count), options.steps, seed, threads, plural(threads),
+        options.count - failures.len(), failures.len());
+    failures.is_empty()
+}
+
+fn run_one(seed: u64, options: &RandomOptions, cpu: &mut Cpu, mem: &mut Memory) -> Result<(), String> {
+    let mut state = seed;
+    for chunk in mem.mem.chunks_mut(8) {
+        chunk.copy_from_slice(&splitmix64(&mut state).to_le_bytes());
+    }
+    cpu.variant = options.variant;
+    reset_cpu(cpu, mem);
+
+    let ops = opcode_table(cpu.variant);
+    for executed in 0..options.steps {
+        let (pc, opcode) = (cpu.pc, mem.mem[cpu.pc as usize]);
+        let outcome = panic::catch_unwind(AssertUnwindSafe(|| ops[opcode as usize](cpu, mem)));
+        if outcome.is_err() {
+            return Err(format!("opcode ${:02x} at ${:04x} panicked after {} instructions", opcode, pc, executed));
+        }
+        if cpu.status() & STATUS_FLAGS_UNUSED == 0 {
+            return Err(format!("opcode ${:02x} at ${:04x} cleared the unused status bit after {} instructions",
+                opcode, pc, executed));
+        }
+    }
+    Ok(())
+}
+
+// the next number from a splitmix64 sequence
+fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
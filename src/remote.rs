@@ -0,0 +1,48 @@
+// a headless run under the control of http requests (--http) or a
+// debugger (--dap)
+//
+// both take requests on background threads and hand them to the main
+// loop through a channel. the main loop looks for them between
+// instructions, or waits for one while paused, through RemoteControl,
+// so it runs either the same way
+
+use std::sync::mpsc::{Receiver, TryRecvError};
+
+use crate::cpu::Cpu;
+use crate::memory::Memory;
+use crate::StopReason;
+
+// what the main loop should do after a request
+pub enum RemoteAction {
+    None,
+    Pause,
+    Resume,
+    Quit,
+}
+
+pub trait RemoteControl {
+    // answer the next request, waiting for one if wait is set
+    fn serve(&mut self, cpu: &mut Cpu, mem: &mut Memory, breakpoints: &mut Vec<u16>, running: bool, wait: bool)
+        -> RemoteAction;
+
+    // after the instruction at pc: report a stop, and see to a step
+    // under way
+    fn after_instruction(&mut self, cpu: &Cpu, mem: &mut Memory, pc: u16, stop: Option<&StopReason>);
+
+    // whether a step asked for is still running
+    fn stepping(&self) -> bool;
+}
+
+// the next thing the connection threads passed on, waiting for it if
+// wait is set; with nothing there, or nobody left to send anything, the
+// action to take instead
+pub fn receive<T>(incoming: &Receiver<T>, wait: bool) -> Result<T, RemoteAction> {
+    if wait {
+        incoming.recv().map_err(|_| RemoteAction::Quit)
+    } else {
+        incoming.try_recv().map_err(|e| match e {
+            TryRecvError::Empty => RemoteAction::None,
+            TryRecvError::Disconnected => RemoteAction::Quit,
+        })
+    }
+}
@@ -0,0 +1,307 @@
+// recording the inputs a run takes from outside, and replaying them
+//
+// everything that reaches the emulated machine from the host goes
+// through here: keys typed for keyboard devices and the stdio serial
+// port, bytes a tcp client sends to a serial port, the seed a random
+// device takes from the clock, and irq and nmi pulled from outside, by
+// the monitor or ctrl-c. each is recorded with the cpu cycle it arrived
+// at. the devices, and the interrupts they raise, are deterministic
+// given their inputs, so replaying the same inputs at the same cycles
+// with the same options reproduces the run exactly.
+//
+// a recording is text, one input per line:
+//
+//   CYCLE key HEX
+//   CYCLE serial HEX
+//   CYCLE seed N
+//   CYCLE irq
+//   CYCLE nmi
+//
+// while replaying, live input is ignored; once the recording runs out
+// the machine gets no more input.
+//
+// each machine has inputs of its own, which hold the keys the host
+// pushes for its keyboard devices too, and text being pasted: typed a
+// character at a time, paste_delay cycles apart so the program has time
+// to take each one, after any keys pushed.
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::fs;
+use std::fs::File;
+use std::io::Write;
+use std::rc::Rc;
+
+const HEADER: &str = "# e6502r input recording: cycle, source, value";
+pub const DEFAULT_PASTE_DELAY: u64 = 20000;     // cycles between pasted characters
+
+#[derive(Clone, Copy, PartialEq)]
+enum Source {
+    Key,
+    Serial,
+    Seed,
+    Irq,
+    Nmi,
+}
+
+impl Source {
+    fn name(&self) -> &'static str {
+        match self {
+            Source::Key => "key",
+            Source::Serial => "serial",
+            Source::Seed => "seed",
+            Source::Irq => "irq",
+            Source::Nmi => "nmi",
+        }
+    }
+}
+
+struct Input {
+    cycle: u64,
+    source: Source,
+    value: u64,
+}
+
+enum Mode {
+    Live,
+    Recording(File, String),   // written as inputs come, so a crash keeps them
+    Replaying(VecDeque<Input>),
+}
+
+struct State {
+    mode: Mode,
+    cycle: u64,
+    irq: bool,                  // pulled since the last instruction
+    nmi: bool,
+    next_interrupt: u64,        // the cycle the replay pulls one at
+    keys: VecDeque<u8>,         // pushed by the host, for the keyboard devices
+    claimed: bool,              // some device takes them
+    paste: VecDeque<u8>,
+    paste_delay: u64,
+    paste_next: u64,            // the cycle the next pasted character is due at
+}
+
+// one machine's inputs. memory holds them and the devices that take
+// input share them, so machines run side by side, on threads or not,
+// each record and replay their own
+#[derive(Clone)]
+pub struct Inputs(Rc<RefCell<State>>);
+
+impl Default for Inputs {
+    fn default() -> Inputs {
+        Inputs::new()
+    }
+}
+
+impl Inputs {
+    pub fn new() -> Inputs {
+        Inputs(Rc::new(RefCell::new(State {
+            mode: Mode::Live,
+            cycle: 0,
+            irq: false,
+            nmi: false,
+            next_interrupt: u64::MAX,
+            keys: VecDeque::new(),
+            claimed: false,
+            paste: VecDeque::new(),
+            paste_delay: DEFAULT_PASTE_DELAY,
+            paste_next: 0,
+        })))
+    }
+
+    // step keeps this at the cpu's cycle count
+    pub fn set_cycle(&self, cycles: u64) {
+        self.0.borrow_mut().cycle = cycles;
+    }
+
+    pub fn cycle(&self) -> u64 {
+        self.0.borrow().cycle
+    }
+
+    pub fn start_recording(&self, path: &str) -> Result<(), String> {
+        let mut file = File::create(path).map_err(|e| format!("can't create {}: {}", path, e))?;
+        writeln!(file, "{}", HEADER).map_err(|e| format!("can't write {}: {}", path, e))?;
+        self.0.borrow_mut().mode = Mode::Recording(file, path.to_string());
+        Ok(())
+    }
+
+    pub fn start_replay(&self, path: &str) -> Result<(), String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("can't read {}: {}", path, e))?;
+        let inputs = parse(&text).map_err(|message| format!("{}: {}", path, message))?;
+        let mut state = self.0.borrow_mut();
+        state.next_interrupt = next_interrupt(&inputs);
+        state.mode = Mode::Replaying(inputs);
+        Ok(())
+    }
+
+    // called by a keyboard device so the host knows to send it keys
+    pub fn claim_keyboard(&self) {
+        self.0.borrow_mut().claimed = true;
+    }
+
+    pub fn keyboard_claimed(&self) -> bool {
+        self.0.borrow().claimed
+    }
+
+    // queue a key for the keyboard devices
+    pub fn push_key(&self, key: u8) {
+        self.0.borrow_mut().keys.push_back(key);
+    }
+
+    // the next key pushed, or pasted, for a keyboard device, without
+    // waiting
+    pub fn poll_key(&self) -> Option<u8> {
+        self.take(Source::Key, || self.0.borrow_mut().live_key().map(u64::from)).map(|value| value as u8)
+    }
+
+    // type text in as keys, line endings as enter; returns the characters
+    // left to type
+    pub fn paste(&self, text: &str) -> usize {
+        let mut state = self.0.borrow_mut();
+        let text = text.replace("\r\n", "\n").replace('\r', "\n");
+        state.paste.extend(text.bytes().filter(|byte| byte.is_ascii()));
+        state.paste.len()
+    }
+
+    // stop typing what's left of a paste; returns how much that was
+    pub fn cancel_paste(&self) -> usize {
+        std::mem::take(&mut self.0.borrow_mut().paste).len()
+    }
+
+    pub fn paste_delay(&self) -> u64 {
+        self.0.borrow().paste_delay
+    }
+
+    pub fn set_paste_delay(&self, cycles: u64) {
+        self.0.borrow_mut().paste_delay = cycles;
+    }
+
+    // a byte from a serial client
+    pub fn serial(&self, live: impl FnOnce() -> Option<u8>) -> Option<u8> {
+        self.take(Source::Serial, || live().map(u64::from)).map(|value| value as u8)
+    }
+
+    // a seed taken from the clock; a replay gets the recorded one
+    pub fn seed(&self, live: u64) -> u64 {
+        self.take(Source::Seed, || Some(live)).unwrap_or(live)
+    }
+
+    // pull irq or nmi from outside the machine, as the monitor's commands
+    // and ctrl-c do, for the end of the next instruction
+    pub fn pulse_irq(&self) {
+        self.0.borrow_mut().irq = true;
+    }
+
+    pub fn pulse_nmi(&self) {
+        self.0.borrow_mut().nmi = true;
+    }
+
+    // whether irq or nmi is pulled from outside as this instruction ends
+    pub fn irq(&self) -> bool {
+        self.interrupt(Source::Irq)
+    }
+
+    pub fn nmi(&self) -> bool {
+        self.interrupt(Source::Nmi)
+    }
+
+    // asked after every instruction, which mostly has no pulse and
+    // nothing recorded due
+    fn interrupt(&self, source: Source) -> bool {
+        let pulsed = {
+            let mut state = self.0.borrow_mut();
+            let live = if source == Source::Irq { &mut state.irq } else { &mut state.nmi };
+            let pulsed = std::mem::take(live);
+            if !pulsed && state.cycle < state.next_interrupt {
+                return false;
+            }
+            pulsed
+        };
+        self.take(source, || pulsed.then_some(1)).is_some()
+    }
+
+    // live is called with the inputs unborrowed, so it may take from them
+    fn take(&self, source: Source, live: impl FnOnce() -> Option<u64>) -> Option<u64> {
+        if let Mode::Replaying(_) = self.0.borrow().mode {
+            return self.replayed(source);
+        }
+        let value = live()?;
+        let state = &mut *self.0.borrow_mut();
+        if let Mode::Recording(out, path) = &mut state.mode {
+            let line = match source {
+                Source::Seed => format!("{} {} {}", state.cycle, source.name(), value),
+                Source::Irq | Source::Nmi => format!("{} {}", state.cycle, source.name()),
+                _ => format!("{} {} {:02x}", state.cycle, source.name(), value),
+            };
+            if let Err(e) = writeln!(out, "{}", line) {
+                eprintln!("e6502r: can't write {}: {}", path, e);
+            }
+        }
+        Some(value)
+    }
+
+    // inputs of each source come in the order they were recorded
+    fn replayed(&self, source: Source) -> Option<u64> {
+        let state = &mut *self.0.borrow_mut();
+        let Mode::Replaying(inputs) = &mut state.mode else { return None };
+        let index = inputs.iter().position(|input| input.source == source)?;
+        if inputs[index].cycle > state.cycle {
+            return None;
+        }
+        let value = inputs.remove(index).map(|input| input.value);
+        state.next_interrupt = next_interrupt(inputs);
+        value
+    }
+}
+
+impl State {
+    // the next key pushed, or the next pasted character if it's due
+    fn live_key(&mut self) -> Option<u8> {
+        if let Some(key) = self.keys.pop_front() {
+            return Some(key);
+        }
+        if self.cycle < self.paste_next {
+            return None;
+        }
+        let key = self.paste.pop_front()?;
+        self.paste_next = self.cycle + self.paste_delay;
+        Some(key)
+    }
+}
+
+// the cycle of the first interrupt still to be replayed
+fn next_interrupt(inputs: &VecDeque<Input>) -> u64 {
+    inputs.iter()
+        .filter(|input| matches!(input.source, Source::Irq | Source::Nmi))
+        .map(|input| input.cycle)
+        .min()
+        .unwrap_or(u64::MAX)
+}
+
+fn parse(text: &str) -> Result<VecDeque<Input>, String> {
+    let mut inputs = VecDeque::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or("").trim();
+        if line.is_empty() {
+            continue;
+        }
+        let bad = || format!("line {}: '{}' should be a cycle, a source and a value", number + 1, line);
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let (cycle, source, value) = match fields[..] {
+            [cycle, source @ ("irq" | "nmi")] => (cycle, source, ""),
+            [cycle, source, value] => (cycle, source, value),
+            _ => return Err(bad()),
+        };
+        let cycle = cycle.parse::<u64>().map_err(|_| bad())?;
+        let (source, value) = match source {
+            "key" => (Source::Key, u8::from_str_radix(value, 16).map_err(|_| bad())? as u64),
+            "serial" => (Source::Serial, u8::from_str_radix(value, 16).map_err(|_| bad())? as u64),
+            "seed" => (Source::Seed, value.parse::<u64>().map_err(|_| bad())?),
+            "irq" => (Source::Irq, 1),
+            "nmi" => (Source::Nmi, 1),
+            _ => return Err(format!("line {}: unknown source '{}'", number + 1, source)),
+        };
+        inputs.push_back(Input { cycle, source, value });
+    }
+    Ok(inputs)
+}
@@ -0,0 +1,271 @@
+// running instructions: one at a time with step, or a cycle budget at
+// a time with run_for, each followed by the devices catching up, any
+// interrupt being taken and the checks for whether to stop. the
+// e6502r command and the crates built on the core all run through here
+
+use std::fmt;
+
+use crate::cpu;
+use crate::cpu::{is_implemented, Cpu, STATUS_BIT_INT_DIS};
+use crate::disasm;
+use crate::memory::{IoAccess, Memory};
+
+// what to do about writes to code that has run
+#[derive(Clone, Copy, PartialEq)]
+pub enum SmcMode {
+    Warn,
+    Break,
+}
+
+// why execution stopped
+pub enum StopReason {
+    Break(u16, u8),             // BRK trapped at address, with signature byte
+    Unimplemented(u16, u8),     // unimplemented opcode at address
+    StopAddress(u16),           // reached a --stop-at address
+    SelfLoop(u16),              // instruction at address jumped to itself
+    Diverged(u16),              // instruction at address differed from the reference
+    SelfModified(u16, u16),     // instruction at address wrote to code that had run
+    Watched(u16, IoAccess),     // instruction at address made an access a watch covers
+    WatchedEntry(&'static str, IoAccess),   // entering the interrupt made an access a watch covers
+    Returned(u16, u8),          // RTS or RTI at address left the monitor's rts frame
+    Branched(u16, u16),         // branch or jump at address went to the other
+}
+
+impl fmt::Display for StopReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StopReason::Break(addr, signature) => write!(f, "BRK #${:02x} at ${:04x}", signature, addr),
+            StopReason::Unimplemented(addr, opcode) => write!(f, "unimplemented opcode ${:02x} at ${:04x}", opcode, addr),
+            StopReason::StopAddress(addr) => write!(f, "reached stop address ${:04x}", addr),
+            StopReason::SelfLoop(addr) => write!(f, "self-loop at ${:04x}", addr),
+            StopReason::Diverged(addr) => write!(f, "diverged from the reference at ${:04x}", addr),
+            StopReason::SelfModified(addr, code) => write!(f, "self-modifying code: ${:04x} wrote to ${:04x}", addr, code),
+            StopReason::Watched(addr, access) if access.write =>
+                write!(f, "watchpoint: ${:04x} wrote #${:02x} to ${:04x}", addr, access.value, access.addr),
+            StopReason::Watched(addr, access) =>
+                write!(f, "watchpoint: ${:04x} read #${:02x} from ${:04x}", addr, access.value, access.addr),
+            StopReason::WatchedEntry(name, access) =>
+                write!(f, "watchpoint: {} entry wrote #${:02x} to ${:04x}", name, access.value, access.addr),
+            StopReason::Returned(addr, opcode) =>
+                write!(f, "returned by {} at ${:04x}", if *opcode == 0x40 { "RTI" } else { "RTS" }, addr),
+            StopReason::Branched(addr, to) => write!(f, "branch or jump at ${:04x} to ${:04x}", addr, to),
+        }
+    }
+}
+
+// settings that decide when execution stops
+#[derive(Default)]
+pub struct StopConditions {
+    pub trap_on_brk: bool,      // BRK stops instead of vectoring
+    pub strict_opcodes: bool,   // unimplemented opcodes stop
+    pub stop_at: Vec<u16>,      // stop on reaching these addresses
+    pub smc: Option<SmcMode>,   // report or stop at writes to code that has run
+}
+
+// run the handler for an opcode; calling through the table measures a
+// little faster with --bench than decoding and matching, which the
+// match-dispatch feature switches to
+#[cfg(not(feature = "match-dispatch"))]
+#[inline(always)]
+pub fn dispatch(cpu: &mut Cpu, mem: &mut Memory, opcode: u8) {
+    cpu::opcode_table(cpu.variant)[opcode as usize](cpu, mem);
+}
+
+#[cfg(all(feature = "match-dispatch", not(feature = "decode-cache")))]
+#[inline(always)]
+pub fn dispatch(cpu: &mut Cpu, mem: &mut Memory, opcode: u8) {
+    cpu::execute(cpu, mem, cpu::decode(cpu.variant, opcode));
+}
+
+#[cfg(feature = "decode-cache")]
+#[inline(always)]
+pub fn dispatch(cpu: &mut Cpu, mem: &mut Memory, opcode: u8) {
+    let instruction = cpu.decoded.fetch(cpu.variant, cpu.pc, opcode);
+    cpu::execute(cpu, mem, instruction);
+}
+
+// execute one instruction
+// returns the reason if execution should stop
+pub fn step(cpu: &mut Cpu, mem: &mut Memory, stops: &StopConditions) -> Option<StopReason> {
+    let pc = cpu.pc;
+    let opcode = mem.mem[pc as usize];
+    if opcode == 0x00 && stops.trap_on_brk {
+        // skip over the signature byte so execution
+        // can be resumed from the monitor
+        let signature = mem.mem[pc.wrapping_add(1) as usize];
+        cpu.pc = pc.wrapping_add(2);
+        return Some(StopReason::Break(pc, signature));
+    }
+    if stops.strict_opcodes && !is_implemented(cpu.variant, opcode) {
+        // stop instead of spinning on an unchanged pc
+        return Some(StopReason::Unimplemented(pc, opcode));
+    }
+
+    mem.inputs.set_cycle(cpu.cycles);
+    // the opcode's own cycle, for the bus log
+    mem.fetch(pc);
+    dispatch(cpu, mem, opcode);
+    finish_instruction(cpu, mem, stops, pc)
+}
+
+// after the instruction at pc: the devices catch up, interrupts are
+// taken and the stop conditions checked
+pub fn finish_instruction(cpu: &mut Cpu, mem: &mut Memory, stops: &StopConditions, pc: u16) -> Option<StopReason> {
+    // devices catch up with the cpu and may interrupt it, as may a pulse
+    // from outside, which goes through the machine's inputs to be recorded
+    mem.tick(cpu.cycles);
+    mem.irq_pulse |= mem.inputs.irq();
+    mem.nmi_pulse |= mem.inputs.nmi();
+    if mem.nmi() {
+        mem.note_interrupt("NMI", cpu);
+        cpu::nmi(cpu, mem);
+    } else if mem.irq() {
+        mem.note_interrupt("IRQ", cpu);
+        cpu::irq(cpu, mem);
+    }
+
+    // writes to code that had run, then this instruction's bytes have
+    let modified = stops.smc.and_then(|mode| check_code_writes(cpu, mem, pc, mode));
+
+    // a loop waiting for an interrupt is not stuck, nor is a WAI, which
+    // an irq ends even while they are disabled
+    let waiting = mem.has_irq_sources() && (cpu.waiting || cpu.status() & STATUS_BIT_INT_DIS == 0);
+    if modified.is_some() {
+        modified
+    } else if cpu.pc == pc && !waiting {
+        Some(StopReason::SelfLoop(pc))
+    } else if stops.stop_at.contains(&cpu.pc) {
+        Some(StopReason::StopAddress(cpu.pc))
+    } else {
+        None
+    }
+}
+
+// keep the writes the instruction at pc made to code that had run for
+// the host to report, or stop at the first with --smc break, then mark
+// the instruction as run
+fn check_code_writes(cpu: &Cpu, mem: &mut Memory, pc: u16, mode: SmcMode) -> Option<StopReason> {
+    let writes = std::mem::take(&mut mem.code_writes);
+    if mode == SmcMode::Warn {
+        mem.smc_warnings.extend(writes.iter().map(|write| (pc, *write)));
+    }
+    let len = disasm::instruction_len(cpu.variant, mem.mem[pc as usize]);
+    if let Some(executed) = &mut mem.executed {
+        for i in 0..len {
+            executed[pc.wrapping_add(i) as usize] = true;
+        }
+    }
+    writes.first().filter(|_| mode == SmcMode::Break).map(|write| StopReason::SelfModified(pc, write.addr))
+}
+
+// how a run_for call ended
+pub struct BlockRun {
+    pub leftover: i64,              // cycles of the budget not spent
+    pub executed: u64,              // instructions executed
+    pub stop: Option<StopReason>,
+}
+
+// run instructions until the cycle budget is spent or execution stops,
+// with none of the tracing, monitor or pacing the main loop does around
+// each one, for hosts that run a frame or a block at a time. when the
+// last instruction runs past the budget the leftover is negative, and
+// adding it to the next budget keeps the blocks to their length. with
+// the dynarec feature, translated blocks run here
+pub fn run_for(cpu: &mut Cpu, mem: &mut Memory, stops: &StopConditions, cycles: i64) -> BlockRun {
+    let end = cpu.cycles.wrapping_add_signed(cycles);
+    let mut executed: u64 = 0;
+    let mut stop = None;
+    while (end.wrapping_sub(cpu.cycles) as i64) > 0 {
+        let before = cpu.cycles;
+        #[cfg(feature = "dynarec")]
+        if let Some((block_executed, block_stop)) = run_block(cpu, mem, stops, end) {
+            executed += block_executed;
+            stop = block_stop;
+            if stop.is_some() {
+                break;
+            }
+            continue;
+        }
+        stop = step(cpu, mem, stops);
+        // a trapped BRK or a strict unimplemented opcode stops before it
+        // runs, taking no cycles, and isn't counted. an instruction that
+        // takes no cycles, as an unimplemented opcode does without
+        // strict_opcodes, would be run again forever: while time stands
+        // still no device can interrupt it
+        if cpu.cycles != before {
+            executed += 1;
+        } else if stop.is_none() {
+            stop = Some(StopReason::SelfLoop(cpu.pc));
+        }
+        if stop.is_some() {
+            break;
+        }
+    }
+    BlockRun { leftover: end.wrapping_sub(cpu.cycles) as i64, executed, stop }
+}
+
+// run the translated block at the pc, from the machine's own cache, up
+// to the cycle count end, or None when there is none and the instruction
+// has to be stepped
+#[cfg(feature = "dynarec")]
+fn run_block(cpu: &mut Cpu, mem: &mut Memory, stops: &StopConditions, end: u64) -> Option<(u64, Option<StopReason>)> {
+    let mut blocks = std::mem::take(&mut cpu.blocks);
+    let run = blocks.run_block(cpu, mem, end, |cpu, mem, pc| {
+        let stop = finish_instruction(cpu, mem, stops, pc);
+        mem.inputs.set_cycle(cpu.cycles);
+        stop
+    });
+    cpu.blocks = blocks;
+    run
+}
+
+// where frames at a clock end: each a frame's cycles after the last, the
+// fraction carried, so frames take the clock's cycles exactly on average,
+// e.g. 29780 and 29781 in turn for an ntsc nes
+pub struct FrameClock {
+    clock_hz: f64,
+    frame_rate: f64,
+    frames: u64,            // frames ended since from
+    from: u64,              // the cycle count frames are counted from
+}
+
+impl Default for FrameClock {
+    // 1 MHz at 60 frames a second
+    fn default() -> FrameClock {
+        FrameClock { clock_hz: 1_000_000.0, frame_rate: 60.0, frames: 0, from: 0 }
+    }
+}
+
+impl FrameClock {
+    pub fn new(clock_hz: f64, frame_rate: f64, from: u64) -> Result<FrameClock, String> {
+        if !(clock_hz >= 1.0 && frame_rate > 0.0 && clock_hz.is_finite() && frame_rate.is_finite()) {
+            return Err(format!("bad clock of {} Hz at {} frames a second", clock_hz, frame_rate));
+        }
+        Ok(FrameClock { clock_hz, frame_rate, frames: 0, from })
+    }
+
+    // count frames from the cycle count from, as after a reset
+    pub fn restart(&mut self, from: u64) {
+        self.frames = 0;
+        self.from = from;
+    }
+
+    // the cycles a frame takes, on average
+    pub fn frame_cycles(&self) -> f64 {
+        self.clock_hz / self.frame_rate
+    }
+
+    // the cycle count the next frame ends at
+    pub fn next_end(&mut self) -> u64 {
+        self.frames += 1;
+        self.from.wrapping_add((self.frames as f64 * self.frame_cycles()).round() as u64)
+    }
+}
+
+// run_for to the end of the next frame of the clock: the frame's cycles
+// and whatever the last frame left over, so an instruction that runs
+// past the end of one frame comes off the next
+pub fn run_frame(cpu: &mut Cpu, mem: &mut Memory, stops: &StopConditions, clock: &mut FrameClock) -> BlockRun {
+    let end = clock.next_end();
+    run_for(cpu, mem, stops, end.wrapping_sub(cpu.cycles) as i64)
+}
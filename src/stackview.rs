@@ -0,0 +1,82 @@
+// the stack page from the top of the stack up, with guesses at what the
+// bytes are, for the monitor's stack command
+//
+//   $01f9: 30        status [nv-Bdizc], by BRK at $0500
+//   $01fa: 02 05     return to $0502
+//   $01fc: 06 04     return to $0407, from JSR $0500 at $0404
+//   $01fe: 30        status [nv-Bdizc], by PHP?
+//   $01ff: 12
+//
+// two bytes that point at the last byte of a JSR are taken as the return
+// address it pushed. a byte with bit 5 set just below two more is taken
+// as the status BRK or an interrupt pushed with its return address, by
+// BRK if B is set and a BRK is where it points; other bytes with bit 5
+// set might be a status pushed by PHP. they are only guesses: data
+// pushed with PHA can look like any of them.
+
+use crate::color;
+use crate::cpu::{flags_text, Cpu};
+use crate::memory::Memory;
+
+const JSR: u8 = 0x20;
+const BRK: u8 = 0x00;
+const B: u8 = 0x10;
+const U: u8 = 0x20;
+
+// the jsr that pushed a return address, if there is one: it pushes the
+// address of its last byte
+fn jsr_before(mem: &Memory, pushed: u16) -> Option<u16> {
+    let at = pushed.wrapping_sub(2);
+    (mem.mem[at as usize] == JSR).then_some(at)
+}
+
+fn word_at(mem: &Memory, addr: u16) -> u16 {
+    mem.mem[addr as usize] as u16 | (mem.mem[addr as usize + 1] as u16) << 8
+}
+
+fn line(out: &mut String, addr: u16, bytes: &[u8], note: &str) {
+    let hex: Vec<String> = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+    let text = format!("{}: {:<9} {}", color::address(addr), hex.join(" "), note);
+    out.push_str(text.trim_end());
+    out.push('\n');
+}
+
+// the bytes from sp+1 to $01ff, annotated
+pub fn format(cpu: &Cpu, mem: &Memory) -> String {
+    if cpu.sp == 0xff {
+        return String::from("the stack is empty\n");
+    }
+    let mut out = String::new();
+    let mut addr = 0x0100 + cpu.sp as u16 + 1;
+    while addr <= 0x01ff {
+        let byte = mem.mem[addr as usize];
+        let pair = (addr < 0x01ff).then(|| word_at(mem, addr));
+
+        // a return address pushed by JSR, less one
+        if let Some(jsr) = pair.and_then(|pushed| jsr_before(mem, pushed)) {
+            let target = word_at(mem, jsr.wrapping_add(1));
+            let note = format!("return to ${:04x}, from JSR ${:04x} at ${:04x}", pair.unwrap().wrapping_add(1), target, jsr);
+            line(&mut out, addr, &mem.mem[addr as usize..addr as usize + 2], &note);
+            addr += 2;
+            continue;
+        }
+
+        // the status, then the return address, pushed by BRK or an interrupt
+        if byte & U != 0 && addr < 0x01fe {
+            let ret = word_at(mem, addr + 1);
+            let by_brk = byte & B != 0 && mem.mem[ret.wrapping_sub(2) as usize] == BRK;
+            if by_brk || byte & B == 0 {
+                let from = if by_brk { format!("by BRK at ${:04x}", ret.wrapping_sub(2)) } else { String::from("by an interrupt?") };
+                line(&mut out, addr, &[byte], &format!("status [{}], {}", flags_text(byte), from));
+                line(&mut out, addr + 1, &mem.mem[addr as usize + 1..addr as usize + 3], &format!("return to ${:04x}", ret));
+                addr += 3;
+                continue;
+            }
+        }
+
+        let note = if byte & U != 0 { format!("status [{}], by PHP?", flags_text(byte)) } else { String::new() };
+        line(&mut out, addr, &[byte], &note);
+        addr += 1;
+    }
+    out
+}
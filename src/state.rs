@@ -0,0 +1,88 @@
+// saving the whole machine to a file and picking it up again
+//
+// the file is the magic bytes, a version number, the cpu registers and
+// cycle count, then memory: all 64K, the banks showing, and each device
+// in the order it was attached. a state can only be loaded into a
+// machine set up the same way, with the same devices at the same
+// addresses; the files and connections devices use on the host are not
+// part of it.
+
+use std::fs;
+
+use crate::cpu::{Cpu, CpuVariant};
+use crate::memory::{Memory, SavedMemory, StateReader, StateWriter};
+
+const MAGIC: &[u8; 8] = b"E6502RST";
+const VERSION: u16 = 1;
+
+pub fn save_state(path: &str, cpu: &Cpu, mem: &mut Memory) -> Result<(), String> {
+    let mut out = StateWriter::new();
+    out.data.extend(MAGIC);
+    out.u16(VERSION);
+    out.u16(cpu.pc);
+    out.u8(cpu.sp);
+    out.u8(cpu.ac);
+    out.u8(cpu.xr);
+    out.u8(cpu.yr);
+    out.u8(cpu.status());
+    out.u64(cpu.cycles);
+    out.u8(match cpu.variant {
+        CpuVariant::Nmos6502 => 0,
+        CpuVariant::Cmos65c02 => 1,
+        CpuVariant::Ricoh2a03 => 2,
+    });
+    mem.save_state(&mut out);
+    fs::write(path, &out.data).map_err(|e| format!("can't write {}: {}", path, e))
+}
+
+// a saved state as it is in the file
+pub struct Snapshot {
+    pub cpu: Cpu,
+    pub memory: SavedMemory,
+}
+
+pub fn read_state(path: &str) -> Result<Snapshot, String> {
+    let data = fs::read(path).map_err(|e| format!("can't read {}: {}", path, e))?;
+    if !is_state(&data) {
+        return Err(format!("{} is not a saved state", path));
+    }
+    let mut input = StateReader::new(&data[MAGIC.len()..]);
+    let version = input.u16().map_err(|message| format!("{}: {}", path, message))?;
+    if version != VERSION {
+        return Err(format!("{} is a version {} saved state, this is version {}", path, version, VERSION));
+    }
+    let mut read = || Ok((read_cpu(&mut input)?, SavedMemory::read(&mut input)?));
+    let (cpu, memory) = read().map_err(|message: String| format!("{}: {}", path, message))?;
+    Ok(Snapshot { cpu, memory })
+}
+
+fn read_cpu(input: &mut StateReader) -> Result<Cpu, String> {
+    let mut cpu = Cpu::new();
+    cpu.pc = input.u16()?;
+    cpu.sp = input.u8()?;
+    cpu.ac = input.u8()?;
+    cpu.xr = input.u8()?;
+    cpu.yr = input.u8()?;
+    cpu.set_status(input.u8()?);
+    cpu.cycles = input.u64()?;
+    cpu.variant = match input.u8()? {
+        0 => CpuVariant::Nmos6502,
+        1 => CpuVariant::Cmos65c02,
+        2 => CpuVariant::Ricoh2a03,
+        other => return Err(format!("unknown cpu variant {}", other)),
+    };
+    Ok(cpu)
+}
+
+// whether file data starts as a saved state does
+pub fn is_state(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+// the cpu and memory are only changed if the state matches the machine
+pub fn load_state(path: &str, cpu: &mut Cpu, mem: &mut Memory) -> Result<(), String> {
+    let snapshot = read_state(path)?;
+    mem.restore(snapshot.memory).map_err(|message| format!("{}: {}", path, message))?;
+    *cpu = snapshot.cpu;
+    Ok(())
+}
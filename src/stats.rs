@@ -0,0 +1,107 @@
+// how often each opcode has run, for the monitor's stats command and
+// --stats, to see where emulated code spends its time and which of the
+// cpu's handlers matter most
+//
+//   opcode  instruction            count   share
+//   $b1     LDA (zp),y             40960   31.2%
+//
+// the counts are of instructions the main loop steps, whether run,
+// stepped or traced; the csv has a line for each opcode that ran, most
+// run first, the mode quoted when it has a comma:
+//
+//   opcode,mnemonic,mode,count
+//   b1,LDA,"(zp),y",40960
+
+use std::fs;
+
+use crate::cpu::{instruction_text, CpuVariant};
+use crate::disasm::{addr_modes, mode_name};
+
+// opcodes shown by the report at most
+const MAX_SHOWN: usize = 20;
+
+pub struct OpcodeStats {
+    counts: [u64; 256],
+}
+
+impl OpcodeStats {
+    pub fn new() -> OpcodeStats {
+        OpcodeStats { counts: [0; 256] }
+    }
+
+    pub fn record(&mut self, opcode: u8) {
+        self.counts[opcode as usize] += 1;
+    }
+
+    pub fn clear(&mut self) {
+        self.counts = [0; 256];
+    }
+
+    // the opcodes that ran, most run first
+    fn ranked(&self) -> Vec<(u8, u64)> {
+        let mut ranked: Vec<(u8, u64)> = (0..=255u8).map(|opcode| (opcode, self.counts[opcode as usize]))
+            .filter(|&(_, count)| count > 0)
+            .collect();
+        ranked.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        ranked
+    }
+
+    // the most run opcodes, then the count for each mnemonic
+    pub fn report(&self, variant: CpuVariant) -> String {
+        let total: u64 = self.counts.iter().sum();
+        if total == 0 {
+            return String::from("no instructions counted\n");
+        }
+        let share = |count: u64| 100.0 * count as f64 / total as f64;
+        let text = instruction_text(variant);
+        let modes = addr_modes(variant);
+        let ranked = self.ranked();
+
+        let mut out = format!("{} instructions, {} different opcodes\n", total, ranked.len());
+        out.push_str("opcode  instruction            count   share\n");
+        for &(opcode, count) in ranked.iter().take(MAX_SHOWN) {
+            let instruction = format!("{} {}", name(text[opcode as usize]), mode_name(modes[opcode as usize])).trim_end().to_string();
+            out.push_str(&format!("${:02x}     {:<16} {:>10}  {:>5.1}%\n", opcode, instruction, count, share(count)));
+        }
+        if ranked.len() > MAX_SHOWN {
+            out.push_str(&format!("... and {} more\n", ranked.len() - MAX_SHOWN));
+        }
+
+        let mut mnemonics: Vec<(&str, u64)> = Vec::new();
+        for &(opcode, count) in &ranked {
+            let mnemonic = name(text[opcode as usize]);
+            match mnemonics.iter_mut().find(|(seen, _)| *seen == mnemonic) {
+                Some((_, total)) => *total += count,
+                None => mnemonics.push((mnemonic, count)),
+            }
+        }
+        mnemonics.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        out.push_str("by mnemonic:");
+        for (mnemonic, count) in mnemonics {
+            out.push_str(&format!(" {} {:.1}%", mnemonic, share(count)));
+        }
+        out.push('\n');
+        out
+    }
+
+    pub fn csv(&self, variant: CpuVariant) -> String {
+        let text = instruction_text(variant);
+        let modes = addr_modes(variant);
+        let mut out = String::from("opcode,mnemonic,mode,count\n");
+        for (opcode, count) in self.ranked() {
+            let mode = mode_name(modes[opcode as usize]);
+            let mode = if mode.contains(',') { format!("\"{}\"", mode) } else { mode.to_string() };
+            out.push_str(&format!("{:02x},{},{},{}\n", opcode, name(text[opcode as usize]), mode, count));
+        }
+        out
+    }
+
+    pub fn save_csv(&self, variant: CpuVariant, path: &str) -> Result<(), String> {
+        fs::write(path, self.csv(variant)).map_err(|e| format!("{}: {}", path, e))
+    }
+}
+
+// an opcode with no instruction counts under ???
+fn name(mnemonic: &'static str) -> &'static str {
+    if mnemonic.is_empty() { "???" } else { mnemonic }
+}
@@ -0,0 +1,228 @@
+
+// runs a directory of test programs
+//
+// each DIR/NAME.bin is loaded and run; an optional DIR/NAME.toml
+// sidecar says how to run it and what the result should be
+//
+//   load = 0x0400              # load address (default 0x0400)
+//   start = 0x0400             # initial pc (default: load address)
+//   stop-at = [0x0420]         # also stop on reaching these addresses
+//   max-instructions = 100000  # fail if still running after this many
+//   trap-brk = true            # BRK ends the run (default true)
+//   cpu = "65c02"              # cpu variant (default "6502")
+//
+//   [expect]
+//   a = 0x42                   # also x, y, sp, p and pc
+//
+//   [[expect.memory]]
+//   address = 0x0200
+//   bytes = [0x41, 0x42]
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::machine::{get_addr, get_integer, get_tables};
+use crate::toml;
+use crate::toml::{get_string, Table, Value};
+use crate::cpu::{reset_cpu, Cpu, CpuVariant, RESET_VECTOR_HIBYTE, RESET_VECTOR_LOBYTE};
+use crate::memory::Memory;
+use crate::{load_image, step, StopConditions, StopReason};
+
+const DEFAULT_LOAD_ADDR: u16 = 0x0400;
+const DEFAULT_MAX_INSTRUCTIONS: u64 = 1_000_000;
+
+// how to run one test and what to expect afterwards
+struct TestSpec {
+    load: u16,
+    start: u16,
+    variant: CpuVariant,
+    stops: StopConditions,
+    max_instructions: u64,
+    registers: Vec<(String, u16)>,      // register name and expected value
+    memory: Vec<(u16, Vec<u8>)>,        // address and expected bytes
+}
+
+// run every test in dir and print a summary
+// returns true if they all passed
+pub fn run_tests(dir: &str) -> bool {
+    let mut binaries: Vec<PathBuf> = match fs::read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "bin"))
+            .collect(),
+        Err(e) => {
+            eprintln!("e6502r: {}: {}", dir, e);
+            return false;
+        }
+    };
+    binaries.sort();
+
+    let mut passed = 0;
+    let mut failed = 0;
+    for binary in &binaries {
+        let name = binary.file_name().unwrap_or_default().to_string_lossy();
+        match run_test(binary) {
+            Ok(summary) => {
+                println!("PASS  {}  ({})", name, summary);
+                passed += 1;
+            }
+            Err(problems) => {
+                println!("FAIL  {}", name);
+                for problem in problems {
+                    println!("      {}", problem);
+                }
+                failed += 1;
+            }
+        }
+    }
+    println!("{} passed, {} failed", passed, failed);
+    failed == 0
+}
+
+// run one test; Ok holds a short summary, Err the list of problems
+fn run_test(binary: &Path) -> Result<String, Vec<String>> {
+    let spec = load_spec(&binary.with_extension("toml")).map_err(|e| vec![e])?;
+
+    let mut cpu = Cpu::new();
+    cpu.variant = spec.variant;
+    let mut mem = Memory::new();
+    load_image(&mut mem, &binary.to_string_lossy(), spec.load).map_err(|e| vec![e])?;
+    mem.mem[RESET_VECTOR_LOBYTE] = (spec.start & 0xff) as u8;
+    mem.mem[RESET_VECTOR_HIBYTE] = (spec.start >> 8) as u8;
+    reset_cpu(&mut cpu, &mem);
+
+    let mut executed: u64 = 0;
+    let reason = loop {
+        if executed == spec.max_instructions {
+            return Err(vec![format!("still running after {} instructions at ${:04x}", executed, cpu.pc)]);
+        }
+        executed += 1;
+        if let Some(reason) = step(&mut cpu, &mut mem, &spec.stops) {
+            break reason;
+        }
+    };
+    if let StopReason::Unimplemented(..) = reason {
+        return Err(vec![reason.to_string()]);
+    }
+
+    let mut problems = Vec::new();
+    for (name, expected) in &spec.registers {
+        let actual = register_value(&cpu, name);
+        if actual != *expected {
+            let width = if name == "pc" { 4 } else { 2 };
+            problems.push(format!("{} = ${:0w$x}, expected ${:0w$x}", name, actual, expected, w = width));
+        }
+    }
+    for (addr, bytes) in &spec.memory {
+        for (i, expected) in bytes.iter().enumerate() {
+            let loc = addr.wrapping_add(i as u16);
+            let actual = mem.mem[loc as usize];
+            if actual != *expected {
+                problems.push(format!("[${:04x}] = ${:02x}, expected ${:02x}", loc, actual, expected));
+            }
+        }
+    }
+    if !problems.is_empty() {
+        problems.push(format!("stopped: {}", reason));
+        return Err(problems);
+    }
+    Ok(format!("{} instructions, {}", executed, reason))
+}
+
+fn register_value(cpu: &Cpu, name: &str) -> u16 {
+    match name {
+        "a" => cpu.ac as u16,
+        "x" => cpu.xr as u16,
+        "y" => cpu.yr as u16,
+        "sp" => cpu.sp as u16,
+        "p" => cpu.status() as u16,
+        _ => cpu.pc,
+    }
+}
+
+// read the sidecar; a missing sidecar means all defaults
+fn load_spec(path: &Path) -> Result<TestSpec, String> {
+    let mut spec = TestSpec {
+        load: DEFAULT_LOAD_ADDR,
+        start: DEFAULT_LOAD_ADDR,
+        variant: CpuVariant::Nmos6502,
+        stops: StopConditions {
+            trap_on_brk: true,
+            strict_opcodes: true,
+            stop_at: Vec::new(),
+            smc: None,
+        },
+        max_instructions: DEFAULT_MAX_INSTRUCTIONS,
+        registers: Vec::new(),
+        memory: Vec::new(),
+    };
+    if !path.exists() {
+        return Ok(spec);
+    }
+
+    let name = path.to_string_lossy();
+    let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", name, e))?;
+    let root = toml::parse(&text).map_err(|e| format!("{}: {}", name, e))?;
+    read_spec(&root, &mut spec).map_err(|e| format!("{}: {}", name, e))?;
+    Ok(spec)
+}
+
+fn read_spec(root: &Table, spec: &mut TestSpec) -> Result<(), String> {
+    let mut start = None;
+    for (key, value) in root {
+        match key.as_str() {
+            "load" => spec.load = get_addr(key, value)?,
+            "start" => start = Some(get_addr(key, value)?),
+            "stop-at" => match value {
+                Value::Array(items) => {
+                    for item in items {
+                        spec.stops.stop_at.push(get_addr(key, item)?);
+                    }
+                }
+                _ => spec.stops.stop_at.push(get_addr(key, value)?),
+            },
+            "cpu" => spec.variant = get_string(key, value)?.parse()?,
+            "max-instructions" => spec.max_instructions = get_integer(key, value)?.max(1) as u64,
+            "trap-brk" => match value {
+                Value::Boolean(b) => spec.stops.trap_on_brk = *b,
+                _ => return Err(String::from("'trap-brk' should be a boolean")),
+            },
+            "expect" => match value {
+                Value::Table(expect) => read_expect(expect, spec)?,
+                _ => return Err(String::from("'expect' should be a table")),
+            },
+            _ => return Err(format!("unknown key '{}'", key)),
+        }
+    }
+    spec.start = start.unwrap_or(spec.load);
+    Ok(())
+}
+
+fn read_expect(expect: &Table, spec: &mut TestSpec) -> Result<(), String> {
+    for (key, value) in expect {
+        match key.as_str() {
+            "a" | "x" | "y" | "sp" | "p" => {
+                let n = get_integer(key, value)?;
+                let byte = u8::try_from(n).map_err(|_| format!("'{}' value {} is out of range", key, n))?;
+                spec.registers.push((key.clone(), byte as u16));
+            }
+            "pc" => spec.registers.push((key.clone(), get_addr(key, value)?)),
+            "memory" => {
+                for table in get_tables(key, value)? {
+                    let addr = get_addr("address", table.get("address").ok_or("memory check is missing 'address'")?)?;
+                    let bytes = match table.get("bytes") {
+                        Some(Value::Array(items)) => items.iter()
+                            .map(|item| get_integer("bytes", item).and_then(|n| {
+                                u8::try_from(n).map_err(|_| format!("byte value {} is out of range", n))
+                            }))
+                            .collect::<Result<Vec<u8>, String>>()?,
+                        _ => return Err(String::from("memory check needs a 'bytes' array")),
+                    };
+                    spec.memory.push((addr, bytes));
+                }
+            }
+            _ => return Err(format!("unknown expectation '{}'", key)),
+        }
+    }
+    Ok(())
+}
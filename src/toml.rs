@@ -0,0 +1,372 @@
+
+// parser for the subset of TOML used by machine and test files
+//
+// supports comments, [tables], [[arrays of tables]], bare and quoted
+// keys, basic and literal strings, integers (decimal, hex, octal,
+// binary), floats, booleans, arrays and inline tables
+
+use std::collections::BTreeMap;
+
+pub type Table = BTreeMap<String, Value>;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    String(String),
+    Integer(i64),
+    Float(f64),
+    Boolean(bool),
+    Array(Vec<Value>),
+    Table(Table),
+}
+
+impl Value {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::String(_) => "string",
+            Value::Integer(_) => "integer",
+            Value::Float(_) => "float",
+            Value::Boolean(_) => "boolean",
+            Value::Array(_) => "array",
+            Value::Table(_) => "table",
+        }
+    }
+}
+
+// the string a key is set to, or an error naming the key
+pub fn get_string(key: &str, value: &Value) -> Result<String, String> {
+    match value {
+        Value::String(text) => Ok(text.clone()),
+        _ => Err(format!("'{}' should be a string, not {}", key, value.type_name())),
+    }
+}
+
+// parse a document into its root table
+pub fn parse(text: &str) -> Result<Table, String> {
+    let mut parser = Parser { chars: text.chars().collect(), pos: 0, line: 1 };
+    parser.document().map_err(|e| format!("line {}: {}", parser.line, e))
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    line: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek();
+        if c == Some('\n') {
+            self.line += 1;
+        }
+        self.pos += 1;
+        c
+    }
+
+    fn expect(&mut self, want: char) -> Result<(), String> {
+        match self.next() {
+            Some(c) if c == want => Ok(()),
+            Some(c) => Err(format!("expected '{}', found '{}'", want, c)),
+            None => Err(format!("expected '{}' at end of input", want)),
+        }
+    }
+
+    // skip spaces and tabs on the current line
+    fn skip_blank(&mut self) {
+        while let Some(' ') | Some('\t') = self.peek() {
+            self.pos += 1;
+        }
+    }
+
+    // skip whitespace, newlines and comments
+    fn skip_all(&mut self) {
+        loop {
+            match self.peek() {
+                Some(' ') | Some('\t') | Some('\r') | Some('\n') => {
+                    self.next();
+                }
+                Some('#') => self.skip_comment(),
+                _ => return,
+            }
+        }
+    }
+
+    fn skip_comment(&mut self) {
+        while let Some(c) = self.peek() {
+            if c == '\n' {
+                return;
+            }
+            self.pos += 1;
+        }
+    }
+
+    // only a comment may follow a value on its line
+    fn end_of_line(&mut self) -> Result<(), String> {
+        self.skip_blank();
+        match self.peek() {
+            Some('#') => {
+                self.skip_comment();
+                Ok(())
+            }
+            Some('\r') | Some('\n') | None => Ok(()),
+            Some(c) => Err(format!("unexpected '{}' after value", c)),
+        }
+    }
+
+    fn document(&mut self) -> Result<Table, String> {
+        let mut root = Table::new();
+        // path of the table currently receiving keys
+        let mut current: Vec<String> = Vec::new();
+
+        loop {
+            self.skip_all();
+            match self.peek() {
+                None => return Ok(root),
+                Some('[') => {
+                    self.next();
+                    let is_array = self.peek() == Some('[');
+                    if is_array {
+                        self.next();
+                    }
+                    self.skip_blank();
+                    let path = self.key_path()?;
+                    self.skip_blank();
+                    self.expect(']')?;
+                    if is_array {
+                        self.expect(']')?;
+                    }
+                    self.end_of_line()?;
+
+                    let (parent_path, last) = path.split_at(path.len() - 1);
+                    let parent = table_at(&mut root, parent_path)?;
+                    if is_array {
+                        let entry = parent.entry(last[0].clone()).or_insert_with(|| Value::Array(Vec::new()));
+                        match entry {
+                            Value::Array(items) => items.push(Value::Table(Table::new())),
+                            _ => return Err(format!("'{}' is not an array of tables", last[0])),
+                        }
+                    } else {
+                        if parent.contains_key(&last[0]) {
+                            return Err(format!("table '{}' defined twice", path.join(".")));
+                        }
+                        parent.insert(last[0].clone(), Value::Table(Table::new()));
+                    }
+                    current = path;
+                }
+                Some(_) => {
+                    let path = self.key_path()?;
+                    self.skip_blank();
+                    self.expect('=')?;
+                    self.skip_blank();
+                    let value = self.value()?;
+                    self.end_of_line()?;
+
+                    let (parents, last) = path.split_at(path.len() - 1);
+                    let target = table_at(table_at(&mut root, &current)?, parents)?;
+                    if target.insert(last[0].clone(), value).is_some() {
+                        return Err(format!("key '{}' defined twice", path.join(".")));
+                    }
+                }
+            }
+        }
+    }
+
+    // dotted key: a.b."c d"
+    fn key_path(&mut self) -> Result<Vec<String>, String> {
+        let mut path = vec![self.key()?];
+        loop {
+            self.skip_blank();
+            if self.peek() != Some('.') {
+                return Ok(path);
+            }
+            self.next();
+            self.skip_blank();
+            path.push(self.key()?);
+        }
+    }
+
+    fn key(&mut self) -> Result<String, String> {
+        match self.peek() {
+            Some('"') => self.basic_string(),
+            Some('\'') => self.literal_string(),
+            _ => {
+                let mut key = String::new();
+                while let Some(c) = self.peek() {
+                    if c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+                        key.push(c);
+                        self.pos += 1;
+                    } else {
+                        break;
+                    }
+                }
+                if key.is_empty() {
+                    return Err(String::from("expected a key"));
+                }
+                Ok(key)
+            }
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        match self.peek() {
+            Some('"') => Ok(Value::String(self.basic_string()?)),
+            Some('\'') => Ok(Value::String(self.literal_string()?)),
+            Some('[') => self.array(),
+            Some('{') => self.inline_table(),
+            Some(_) => self.scalar(),
+            None => Err(String::from("expected a value")),
+        }
+    }
+
+    // the next character of a string, which has to end on its line;
+    // the newline is left unread, so the error gives the string's line
+    fn string_char(&mut self) -> Result<char, String> {
+        match self.peek() {
+            Some('\n') | None => Err(String::from("unterminated string")),
+            Some(c) => {
+                self.pos += 1;
+                Ok(c)
+            }
+        }
+    }
+
+    fn basic_string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        let mut text = String::new();
+        loop {
+            match self.string_char()? {
+                '"' => return Ok(text),
+                '\\' => match self.string_char()? {
+                    'n' => text.push('\n'),
+                    't' => text.push('\t'),
+                    'r' => text.push('\r'),
+                    '"' => text.push('"'),
+                    '\\' => text.push('\\'),
+                    c => return Err(format!("unknown escape '\\{}'", c)),
+                },
+                c => text.push(c),
+            }
+        }
+    }
+
+    fn literal_string(&mut self) -> Result<String, String> {
+        self.expect('\'')?;
+        let mut text = String::new();
+        loop {
+            match self.string_char()? {
+                '\'' => return Ok(text),
+                c => text.push(c),
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Value, String> {
+        self.expect('[')?;
+        let mut items = Vec::new();
+        loop {
+            self.skip_all();
+            if self.peek() == Some(']') {
+                self.next();
+                return Ok(Value::Array(items));
+            }
+            items.push(self.value()?);
+            self.skip_all();
+            match self.next() {
+                Some(',') => {}
+                Some(']') => return Ok(Value::Array(items)),
+                _ => return Err(String::from("expected ',' or ']' in array")),
+            }
+        }
+    }
+
+    fn inline_table(&mut self) -> Result<Value, String> {
+        self.expect('{')?;
+        let mut table = Table::new();
+        self.skip_blank();
+        if self.peek() == Some('}') {
+            self.next();
+            return Ok(Value::Table(table));
+        }
+        loop {
+            self.skip_blank();
+            let key = self.key()?;
+            self.skip_blank();
+            self.expect('=')?;
+            self.skip_blank();
+            let value = self.value()?;
+            if table.insert(key.clone(), value).is_some() {
+                return Err(format!("key '{}' defined twice", key));
+            }
+            self.skip_blank();
+            match self.next() {
+                Some(',') => {}
+                Some('}') => return Ok(Value::Table(table)),
+                _ => return Err(String::from("expected ',' or '}' in inline table")),
+            }
+        }
+    }
+
+    // numbers and booleans
+    fn scalar(&mut self) -> Result<Value, String> {
+        let mut word = String::new();
+        while let Some(c) = self.peek() {
+            if c.is_ascii_alphanumeric() || "+-_.".contains(c) {
+                word.push(c);
+                self.pos += 1;
+            } else {
+                break;
+            }
+        }
+        match word.as_str() {
+            "true" => return Ok(Value::Boolean(true)),
+            "false" => return Ok(Value::Boolean(false)),
+            _ => {}
+        }
+
+        let digits = word.replace('_', "");
+        let (negative, unsigned) = match digits.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, digits.strip_prefix('+').unwrap_or(&digits)),
+        };
+        let radix = match unsigned.get(..2) {
+            Some("0x") => Some(16),
+            Some("0o") => Some(8),
+            Some("0b") => Some(2),
+            _ => None,
+        };
+        let integer = match radix {
+            Some(radix) => i64::from_str_radix(&unsigned[2..], radix).ok(),
+            None => unsigned.parse::<i64>().ok(),
+        };
+        if let Some(n) = integer {
+            return Ok(Value::Integer(if negative { -n } else { n }));
+        }
+        if radix.is_none() {
+            if let Ok(f) = digits.parse::<f64>() {
+                return Ok(Value::Float(f));
+            }
+        }
+        Err(format!("bad value '{}'", word))
+    }
+}
+
+// find the table at path below root, descending into the last
+// element of arrays of tables
+fn table_at<'a>(root: &'a mut Table, path: &[String]) -> Result<&'a mut Table, String> {
+    let mut table = root;
+    for key in path {
+        let entry = table.entry(key.clone()).or_insert_with(|| Value::Table(Table::new()));
+        table = match entry {
+            Value::Table(t) => t,
+            Value::Array(items) => match items.last_mut() {
+                Some(Value::Table(t)) => t,
+                _ => return Err(format!("'{}' is not a table", key)),
+            },
+            _ => return Err(format!("'{}' is not a table", key)),
+        };
+    }
+    Ok(table)
+}
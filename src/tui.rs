@@ -0,0 +1,189 @@
+// full-screen debugger drawn with ratatui on a crossterm backend
+//
+// the panes are laid out over the top of the screen and redrawn each
+// time execution stops; commands typed on the line below them are
+// handled by the monitor, so their output appears under the panes until
+// the next redraw
+//
+// the panes are drawn on the terminal's alternate screen, which is left
+// for the program's own output whenever execution resumes, and when
+// e6502r ends or panics, so the terminal is as it was
+
+use std::io;
+use std::panic;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Once;
+
+use crossterm::cursor::{MoveTo, Show};
+use crossterm::execute;
+use crossterm::terminal::{Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+use ratatui::backend::CrosstermBackend;
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Paragraph, Wrap};
+use ratatui::{Frame, Terminal, TerminalOptions, Viewport};
+
+use crate::disasm;
+use crate::monitor;
+use crate::monitor::{Monitor, MonitorAction};
+use crate::cpu::Cpu;
+use crate::memory::Memory;
+
+const LEFT_WIDTH: u16 = 42;
+const PANE_ROWS: u16 = 12;
+const DUMP_ROWS: u16 = 8;
+// the panes with their borders, and the line of commands under them
+const PANES_HEIGHT: u16 = (PANE_ROWS + 2) + (DUMP_ROWS + 2) + 1;
+
+static ON_ALTERNATE_SCREEN: AtomicBool = AtomicBool::new(false);
+static LEAVE_ON_PANIC: Once = Once::new();
+
+fn enter_alternate_screen() {
+    // a panic's message is printed on the screen it leaves behind
+    LEAVE_ON_PANIC.call_once(|| {
+        let default_hook = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            leave_alternate_screen();
+            default_hook(info);
+        }));
+    });
+    if !ON_ALTERNATE_SCREEN.swap(true, Ordering::SeqCst) {
+        let _result = execute!(io::stdout(), EnterAlternateScreen);
+    }
+}
+
+fn leave_alternate_screen() {
+    if ON_ALTERNATE_SCREEN.swap(false, Ordering::SeqCst) {
+        let _result = execute!(io::stdout(), LeaveAlternateScreen);
+    }
+}
+
+pub struct Tui {
+    dump_addr: u16,     // first address shown in the memory pane
+}
+
+impl Tui {
+    pub fn new() -> Tui {
+        Tui { dump_addr: 0x0000 }
+    }
+
+    // draw the panes and read commands until execution resumes
+    pub fn prompt(&mut self, monitor: &mut Monitor, cpu: &mut Cpu, mem: &mut Memory, breakpoints: &mut Vec<u16>)
+        -> MonitorAction {
+        enter_alternate_screen();
+        let action = self.read_commands(monitor, cpu, mem, breakpoints);
+        leave_alternate_screen();
+        action
+    }
+
+    fn read_commands(&mut self, monitor: &mut Monitor, cpu: &mut Cpu, mem: &mut Memory, breakpoints: &mut Vec<u16>)
+        -> MonitorAction {
+        self.redraw(cpu, mem);
+        monitor.stopped(cpu, mem, breakpoints);
+        loop {
+            let prompt = format!("[{}] > ", monitor::elapsed_text(cpu.cycles, monitor.clock_hz));
+            let user_input = match monitor::read_command(&prompt) {
+                Some(line) => line,
+                None => return MonitorAction::Abort,
+            };
+
+            // m ADDR moves the memory pane
+            let line = user_input.trim();
+            if let Some(arg) = line.strip_prefix("m ") {
+                match u16::from_str_radix(arg.trim().trim_start_matches('$'), 16) {
+                    Ok(addr) => {
+                        self.dump_addr = addr & 0xfff0;
+                        self.redraw(cpu, mem);
+                    }
+                    Err(_) => println!("bad address '{}'", arg.trim()),
+                }
+                continue;
+            }
+            if line == "redraw" {
+                self.redraw(cpu, mem);
+                continue;
+            }
+
+            if let Some(action) = monitor.command(line, cpu, mem, breakpoints) {
+                return action;
+            }
+        }
+    }
+
+    fn redraw(&self, cpu: &Cpu, mem: &Memory) {
+        if let Err(e) = self.draw(cpu, mem) {
+            println!("can't draw the panes: {}", e);
+        }
+    }
+
+    // clear the screen and draw the panes across its top, leaving the
+    // cursor on the line under them for the monitor
+    fn draw(&self, cpu: &Cpu, mem: &Memory) -> io::Result<()> {
+        let (width, _) = crossterm::terminal::size()?;
+        execute!(io::stdout(), Clear(ClearType::All))?;
+        // the screen was cleared, so a new terminal with nothing drawn
+        // yet is right about what is on it
+        let viewport = Viewport::Fixed(Rect::new(0, 0, width, PANES_HEIGHT));
+        let mut terminal = Terminal::with_options(CrosstermBackend::new(io::stdout()), TerminalOptions { viewport })?;
+        terminal.draw(|frame| self.render(frame, cpu, mem))?;
+        drop(terminal);
+        execute!(io::stdout(), MoveTo(0, PANES_HEIGHT), Show)
+    }
+
+    fn render(&self, frame: &mut Frame, cpu: &Cpu, mem: &Memory) {
+        let [top, memory, help] = Layout::vertical([
+            Constraint::Length(PANE_ROWS + 2),
+            Constraint::Length(DUMP_ROWS + 2),
+            Constraint::Length(1),
+        ]).areas(frame.area());
+        let [code, right] = Layout::horizontal([Constraint::Length(LEFT_WIDTH), Constraint::Min(0)]).areas(top);
+        let [registers, stack] = Layout::vertical([Constraint::Length(4), Constraint::Min(0)]).areas(right);
+
+        let current = Style::default().add_modifier(Modifier::REVERSED);
+        let lines: Vec<Line> = disasm::disassemble_around(cpu.variant, mem, cpu.pc, 4, PANE_ROWS - 5)
+            .into_iter()
+            .map(|(addr, text)| {
+                let line = Line::from(format!("${:04x}  {}", addr, text));
+                if addr == cpu.pc { line.style(current) } else { line }
+            })
+            .collect();
+        frame.render_widget(Paragraph::new(lines).block(Block::bordered().title(" disassembly ")), code);
+
+        // as the trace and the monitor's r show them, wrapped onto a
+        // second line in a narrow terminal
+        let text = Paragraph::new(cpu.to_string()).wrap(Wrap { trim: true });
+        frame.render_widget(text.block(Block::bordered().title(" registers ")), registers);
+
+        let mut lines = Vec::new();
+        let mut sp = cpu.sp;
+        while sp != 0xff && lines.len() < stack.height.saturating_sub(2) as usize {
+            sp = sp.wrapping_add(1);
+            lines.push(Line::from(format!("$01{:02x}  ${:02x}", sp, mem.mem[0x0100 + sp as usize])));
+        }
+        frame.render_widget(Paragraph::new(lines).block(Block::bordered().title(" stack ")), stack);
+
+        let lines: Vec<Line> = (0..DUMP_ROWS)
+            .map(|row| {
+                let base = self.dump_addr.wrapping_add(row * 16);
+                let mut text = format!("${:04x} ", base);
+                let mut ascii = String::new();
+                for i in 0..16u16 {
+                    let b = mem.mem[base.wrapping_add(i) as usize];
+                    text.push_str(&format!(" {:02x}", b));
+                    ascii.push(if (0x20..0x7f).contains(&b) { b as char } else { '.' });
+                }
+                Line::from(format!("{}  {}", text, ascii))
+            })
+            .collect();
+        frame.render_widget(Paragraph::new(lines).block(Block::bordered().title(" memory ")), memory);
+
+        frame.render_widget(Paragraph::new(" s step, g go, r regs, m ADDR memory, turbo, redraw, q quit"), help);
+    }
+}
+
+impl Drop for Tui {
+    fn drop(&mut self) {
+        leave_alternate_screen();
+    }
+}
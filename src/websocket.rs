@@ -0,0 +1,198 @@
+// the parts of the websocket protocol (RFC 6455) the debug connection
+// needs: the opening handshake and text frames each way
+//
+// frames from the client are masked and may be split into fragments;
+// pings are handed back to be answered by whoever writes to the client,
+// and a close frame or a dropped connection ends the stream. frames to
+// the client are sent whole and unmasked. the handshake's SHA-1 and
+// base64 are done here too.
+
+use std::io;
+use std::io::{ErrorKind, Read, Write};
+
+const ACCEPT_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+// a message may not be bigger than this
+const MAX_MESSAGE: usize = 1 << 20;
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+const OP_CONTINUATION: u8 = 0x0;
+const OP_TEXT: u8 = 0x1;
+const OP_BINARY: u8 = 0x2;
+const OP_CLOSE: u8 = 0x8;
+const OP_PING: u8 = 0x9;
+const OP_PONG: u8 = 0xa;
+
+// the response that accepts an upgrade asked for with key
+pub fn handshake_response(key: &str) -> String {
+    let accept = base64(&sha1(format!("{}{}", key.trim(), ACCEPT_GUID).as_bytes()));
+    format!("HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+        Sec-WebSocket-Accept: {}\r\n\r\n", accept)
+}
+
+pub enum Message {
+    Text(String),
+    Ping(Vec<u8>),          // to be answered with write_pong
+}
+
+// the next message; None once the client closes the connection
+pub fn read_message(input: &mut impl Read) -> io::Result<Option<Message>> {
+    let mut message = Vec::new();
+    loop {
+        let mut head = [0u8; 2];
+        match input.read_exact(&mut head) {
+            Ok(()) => {}
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e),
+        }
+        let last = head[0] & 0x80 != 0;
+        let opcode = head[0] & 0x0f;
+        let masked = head[1] & 0x80 != 0;
+        let length = match head[1] & 0x7f {
+            126 => {
+                let mut bytes = [0u8; 2];
+                input.read_exact(&mut bytes)?;
+                u16::from_be_bytes(bytes) as usize
+            }
+            127 => {
+                let mut bytes = [0u8; 8];
+                input.read_exact(&mut bytes)?;
+                usize::try_from(u64::from_be_bytes(bytes)).unwrap_or(usize::MAX)
+            }
+            length => length as usize,
+        };
+        if message.len().saturating_add(length) > MAX_MESSAGE {
+            return Err(io::Error::new(ErrorKind::InvalidData, "message too big"));
+        }
+        let mut mask = [0u8; 4];
+        if masked {
+            input.read_exact(&mut mask)?;
+        }
+        let mut payload = vec![0; length];
+        input.read_exact(&mut payload)?;
+        if masked {
+            for (index, byte) in payload.iter_mut().enumerate() {
+                *byte ^= mask[index % 4];
+            }
+        }
+        match opcode {
+            OP_CLOSE => return Ok(None),
+            OP_PING => return Ok(Some(Message::Ping(payload))),
+            OP_PONG => {}
+            OP_TEXT | OP_BINARY | OP_CONTINUATION => {
+                message.extend_from_slice(&payload);
+                if last {
+                    return Ok(Some(Message::Text(String::from_utf8_lossy(&message).into_owned())));
+                }
+            }
+            _ => return Err(io::Error::new(ErrorKind::InvalidData, "unknown frame")),
+        }
+    }
+}
+
+// send a text message
+pub fn write_message(out: &mut impl Write, text: &str) -> io::Result<()> {
+    write_frame(out, OP_TEXT, text.as_bytes())
+}
+
+pub fn write_pong(out: &mut impl Write, payload: &[u8]) -> io::Result<()> {
+    write_frame(out, OP_PONG, payload)
+}
+
+fn write_frame(out: &mut impl Write, opcode: u8, payload: &[u8]) -> io::Result<()> {
+    let mut head = vec![0x80 | opcode];
+    match payload.len() {
+        length if length < 126 => head.push(length as u8),
+        length if length <= 0xffff => {
+            head.push(126);
+            head.extend_from_slice(&(length as u16).to_be_bytes());
+        }
+        length => {
+            head.push(127);
+            head.extend_from_slice(&(length as u64).to_be_bytes());
+        }
+    }
+    out.write_all(&head)?;
+    out.write_all(payload)
+}
+
+pub fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476, 0xc3d2e1f0];
+    let mut padded = data.to_vec();
+    padded.push(0x80);
+    while padded.len() % 64 != 56 {
+        padded.push(0);
+    }
+    padded.extend_from_slice(&((data.len() as u64) * 8).to_be_bytes());
+
+    for block in padded.chunks(64) {
+        let mut w = [0u32; 80];
+        for (i, word) in block.chunks(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e] = h;
+        for (i, &word) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5a827999),
+                20..=39 => (b ^ c ^ d, 0x6ed9eba1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8f1bbcdc),
+                _ => (b ^ c ^ d, 0xca62c1d6),
+            };
+            let temp = a.rotate_left(5).wrapping_add(f).wrapping_add(e).wrapping_add(k).wrapping_add(word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+        for (state, value) in h.iter_mut().zip([a, b, c, d, e]) {
+            *state = state.wrapping_add(value);
+        }
+    }
+
+    let mut digest = [0u8; 20];
+    for (bytes, word) in digest.chunks_mut(4).zip(h) {
+        bytes.copy_from_slice(&word.to_be_bytes());
+    }
+    digest
+}
+
+// base64 is also how the debug adapter passes memory
+pub fn base64(data: &[u8]) -> String {
+    let mut text = String::new();
+    for chunk in data.chunks(3) {
+        let bits = (chunk[0] as u32) << 16
+            | (*chunk.get(1).unwrap_or(&0) as u32) << 8
+            | *chunk.get(2).unwrap_or(&0) as u32;
+        for index in 0..4 {
+            if index <= chunk.len() {
+                text.push(BASE64[(bits >> (18 - 6 * index)) as usize & 0x3f] as char);
+            } else {
+                text.push('=');
+            }
+        }
+    }
+    text
+}
+
+// None if text isn't base64
+pub fn base64_decode(text: &str) -> Option<Vec<u8>> {
+    let text = text.trim_end_matches('=');
+    let mut data = Vec::new();
+    let mut bits: u32 = 0;
+    let mut count = 0;
+    for c in text.bytes() {
+        let value = BASE64.iter().position(|&b| b == c)? as u32;
+        bits = bits << 6 | value;
+        count += 6;
+        if count >= 8 {
+            count -= 8;
+            data.push((bits >> count) as u8);
+        }
+    }
+    Some(data)
+}
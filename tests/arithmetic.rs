@@ -0,0 +1,218 @@
+// ADC, SBC and the compares against a model of the hardware written
+// apart from the cpu, following Bruce Clark's "Decimal Mode" notes on
+// 6502.org, for every operand and carry in binary and decimal mode
+//
+//   cargo test --test arithmetic
+//
+// the sweeps try every accumulator, operand and carry on each cpu;
+// the proptests start from random status registers as well, checking
+// that I and D come through untouched. in decimal mode the 6502's ADC
+// takes N and V from the sum before its high digit is adjusted and Z
+// from the binary addition, and its SBC every flag from the binary
+// subtraction.
+
+mod common;
+
+use common::{Machine, CODE, VARIANTS};
+use e6502r::cpu::{opcode_table, CpuVariant};
+use proptest::prelude::*;
+
+const C: u8 = 0x01;
+const Z: u8 = 0x02;
+const I: u8 = 0x04;
+const D: u8 = 0x08;
+const V: u8 = 0x40;
+const N: u8 = 0x80;
+
+// the flags compared: those the instructions set, and two they mustn't
+const CHECKED: u8 = N | V | Z | C | D | I;
+
+const ADC_IMM: u8 = 0x69;
+const SBC_IMM: u8 = 0xe9;
+const CMP_IMM: u8 = 0xc9;
+const CPX_IMM: u8 = 0xe0;
+const CPY_IMM: u8 = 0xc0;
+
+// a register and the flags after an instruction
+#[derive(Debug, PartialEq)]
+struct Outcome {
+    register: u8,
+    status: u8,
+}
+
+// ------------------------------------------------------------------
+// the model
+
+fn nz(value: u8) -> u8 {
+    (value & N) | if value == 0 { Z } else { 0 }
+}
+
+fn with_flags(status: u8, flags: u8) -> u8 {
+    (status & !(N | V | Z | C)) | flags
+}
+
+fn decimal(variant: CpuVariant, status: u8) -> bool {
+    status & D != 0 && variant != CpuVariant::Ricoh2a03
+}
+
+fn adc_model(variant: CpuVariant, a: u8, m: u8, status: u8) -> Outcome {
+    let carry = (status & C) as u16;
+    let binary = a as u16 + m as u16 + carry;
+    let overflow = if (!(a ^ m) & (a ^ binary as u8) & 0x80) != 0 { V } else { 0 };
+    if !decimal(variant, status) {
+        let carry_out = if binary > 0xff { C } else { 0 };
+        return Outcome { register: binary as u8, status: with_flags(status, nz(binary as u8) | overflow | carry_out) };
+    }
+
+    // sequence 1: the accumulator and carry
+    let mut low = (a & 0x0f) as u16 + (m & 0x0f) as u16 + carry;
+    if low >= 0x0a {
+        low = ((low + 0x06) & 0x0f) + 0x10;
+    }
+    let mut sum = (a & 0xf0) as u16 + (m & 0xf0) as u16 + low;
+    if sum >= 0xa0 {
+        sum += 0x60;
+    }
+    let result = sum as u8;
+    let carry_out = if sum >= 0x100 { C } else { 0 };
+
+    // sequence 2, signed, for V on both; N from it on the 6502
+    let signed = (a & 0xf0) as i8 as i16 + (m & 0xf0) as i8 as i16 + low as i16;
+    let overflow = if !(-128..=127).contains(&signed) { V } else { 0 };
+    let flags = if variant == CpuVariant::Cmos65c02 {
+        // N and Z from the result
+        nz(result) | overflow | carry_out
+    } else {
+        // Z from the binary addition
+        (signed as u8 & N) | (nz(binary as u8) & Z) | overflow | carry_out
+    };
+    Outcome { register: result, status: with_flags(status, flags) }
+}
+
+fn sbc_model(variant: CpuVariant, a: u8, m: u8, status: u8) -> Outcome {
+    let carry = (status & C) as i16;
+    let binary = a as i16 - m as i16 + carry - 1;
+    let overflow = if ((a ^ m) & (a ^ binary as u8) & 0x80) != 0 { V } else { 0 };
+    let carry_out = if binary >= 0 { C } else { 0 };
+    let binary_flags = nz(binary as u8) | overflow | carry_out;
+    if !decimal(variant, status) {
+        return Outcome { register: binary as u8, status: with_flags(status, binary_flags) };
+    }
+
+    let low = (a & 0x0f) as i16 - (m & 0x0f) as i16 + carry - 1;
+    if variant == CpuVariant::Cmos65c02 {
+        // sequence 4; N and Z from the result
+        let mut difference = binary;
+        if difference < 0 {
+            difference -= 0x60;
+        }
+        if low < 0 {
+            difference -= 0x06;
+        }
+        let result = difference as u8;
+        Outcome { register: result, status: with_flags(status, nz(result) | overflow | carry_out) }
+    } else {
+        // sequence 3; every flag from the binary subtraction
+        let low = if low < 0 { ((low - 0x06) & 0x0f) - 0x10 } else { low };
+        let mut difference = (a & 0xf0) as i16 - (m & 0xf0) as i16 + low;
+        if difference < 0 {
+            difference -= 0x60;
+        }
+        Outcome { register: difference as u8, status: with_flags(status, binary_flags) }
+    }
+}
+
+// V is left alone
+fn compare_model(register: u8, m: u8, status: u8) -> Outcome {
+    let carry_out = if register >= m { C } else { 0 };
+    Outcome { register, status: with_flags(status, nz(register.wrapping_sub(m)) | (status & V) | carry_out) }
+}
+
+// ------------------------------------------------------------------
+// the cpu
+
+// run opcode #m with register (a, x or y for the opcode) and status
+fn run(machine: &mut Machine, variant: CpuVariant, opcode: u8, register: u8, m: u8, status: u8) -> Outcome {
+    let cpu = &mut machine.cpu;
+    cpu.variant = variant;
+    cpu.pc = CODE;
+    cpu.set_status(status);
+    machine.mem.mem[CODE as usize] = opcode;
+    machine.mem.mem[CODE as usize + 1] = m;
+    match opcode {
+        CPX_IMM => cpu.xr = register,
+        CPY_IMM => cpu.yr = register,
+        _ => cpu.ac = register,
+    }
+    opcode_table(variant)[opcode as usize](cpu, &mut machine.mem);
+    assert_eq!(cpu.pc, CODE + 2, "{:?} ${:02x} left the pc at ${:04x}", variant, opcode, cpu.pc);
+    let register = match opcode {
+        CPX_IMM => cpu.xr,
+        CPY_IMM => cpu.yr,
+        _ => cpu.ac,
+    };
+    Outcome { register, status: cpu.status() & CHECKED }
+}
+
+fn model(variant: CpuVariant, opcode: u8, register: u8, m: u8, status: u8) -> Outcome {
+    let outcome = match opcode {
+        ADC_IMM => adc_model(variant, register, m, status),
+        SBC_IMM => sbc_model(variant, register, m, status),
+        _ => compare_model(register, m, status),
+    };
+    Outcome { status: outcome.status & CHECKED, ..outcome }
+}
+
+fn check(machine: &mut Machine, variant: CpuVariant, opcode: u8, register: u8, m: u8, status: u8) {
+    let got = run(machine, variant, opcode, register, m, status);
+    let expected = model(variant, opcode, register, m, status);
+    assert_eq!(got, expected, "{:?} ${:02x} with register ${:02x}, operand ${:02x}, status ${:02x}",
+        variant, opcode, register, m, status);
+}
+
+// every register, operand and carry, in binary and decimal mode
+fn sweep(opcode: u8) {
+    let mut machine = Machine::new(CpuVariant::Nmos6502, &[]);
+    for variant in VARIANTS {
+        for status in [0, C, D, D | C] {
+            for register in 0..=255 {
+                for m in 0..=255 {
+                    check(&mut machine, variant, opcode, register, m, status);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn adc_every_operand() {
+    sweep(ADC_IMM);
+}
+
+#[test]
+fn sbc_every_operand() {
+    sweep(SBC_IMM);
+}
+
+#[test]
+fn compares_every_operand() {
+    sweep(CMP_IMM);
+    sweep(CPX_IMM);
+    sweep(CPY_IMM);
+}
+
+fn variant() -> impl Strategy<Value = CpuVariant> {
+    prop::sample::select(VARIANTS.to_vec())
+}
+
+fn opcode() -> impl Strategy<Value = u8> {
+    prop::sample::select(vec![ADC_IMM, SBC_IMM, CMP_IMM, CPX_IMM, CPY_IMM])
+}
+
+proptest! {
+    #[test]
+    fn arithmetic_from_any_status(variant in variant(), opcode in opcode(), register: u8, m: u8, status: u8) {
+        let mut machine = Machine::new(CpuVariant::Nmos6502, &[]);
+        check(&mut machine, variant, opcode, register, m, status);
+    }
+}
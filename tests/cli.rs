@@ -0,0 +1,328 @@
+// the e6502r command run as a script would run it, on images written to
+// the temporary directory
+//
+//   cargo test --test cli
+
+use std::env;
+use std::fs;
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::path::PathBuf;
+use std::process;
+use std::process::{Command, Output, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// LDA #42, STA $10, then a JMP to itself, which stops a --run
+const EXIT_42: [u8; 7] = [0xa9, 0x2a, 0x85, 0x10, 0x4c, 0x04, 0x04];
+
+// bytes written to a file of their own for this test
+fn image(name: &str, bytes: &[u8]) -> PathBuf {
+    let path = env::temp_dir().join(format!("e6502r-cli-{}-{}.bin", process::id(), name));
+    fs::write(&path, bytes).unwrap();
+    path
+}
+
+fn e6502r(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_e6502r"))
+        .args(args)
+        .stdin(Stdio::null())
+        .output()
+        .unwrap()
+}
+
+// the same, with lines typed at it
+fn e6502r_typing(args: &[&str], typed: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_e6502r"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(typed.as_bytes()).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn image_without_a_vector_starts_where_it_was_loaded() {
+    let path = image("default", &EXIT_42);
+    let output = e6502r(&[path.to_str().unwrap(), "--run", "--exit-code-at", "10"]);
+    assert_eq!(output.status.code(), Some(42));
+}
+
+#[test]
+fn image_without_a_vector_at_an_address_starts_there() {
+    // the same program moved to $2000, its jump with it
+    let path = image("at", &[0xa9, 0x2a, 0x85, 0x10, 0x4c, 0x04, 0x20]);
+    let arg = format!("{}@2000", path.to_str().unwrap());
+    let output = e6502r(&[&arg, "--run", "--exit-code-at", "10"]);
+    assert_eq!(output.status.code(), Some(42));
+}
+
+#[test]
+fn image_with_a_vector_keeps_it() {
+    // a full 64K image with its code at $8000 and the vector pointing there
+    let mut memory = vec![0u8; 0x10000];
+    memory[0x8000..0x8007].copy_from_slice(&[0xa9, 0x07, 0x85, 0x10, 0x4c, 0x04, 0x80]);
+    memory[0xfffc] = 0x00;
+    memory[0xfffd] = 0x80;
+    let path = image("vector", &memory);
+    let arg = format!("{}@0", path.to_str().unwrap());
+    let output = e6502r(&[&arg, "--run", "--exit-code-at", "10"]);
+    assert_eq!(output.status.code(), Some(7));
+}
+
+#[test]
+fn reset_overrides_the_load_address() {
+    // two NOPs ahead of the program, which --reset skips
+    let mut bytes = vec![0xea, 0xea];
+    bytes.extend_from_slice(&[0xa9, 0x2a, 0x85, 0x10, 0x4c, 0x06, 0x04]);
+    let path = image("reset", &bytes);
+    let output = e6502r(&[path.to_str().unwrap(), "--reset", "0402", "--run", "--exit-code-at", "10"]);
+    assert_eq!(output.status.code(), Some(42));
+}
+
+#[test]
+fn help_exits_with_success() {
+    let output = e6502r(&["--help"]);
+    assert_eq!(output.status.code(), Some(0));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("--exit-code-at"));
+}
+
+#[test]
+fn bad_command_lines_exit_with_2() {
+    let path = image("bad", &EXIT_42);
+    let path = path.to_str().unwrap();
+    let bad: [&[&str]; 8] = [
+        &["--no-such-option"],
+        &[path, "--reset", "zzzz"],
+        &[path, "--speed", "0"],
+        &[path, "--smc", "sometimes"],
+        &[path, "--record", "a", "--replay", "b"],
+        &[path, "--http", "8080", "--dap", "8081"],
+        &[path, "--run", "--ctrl-c", "monitor"],
+        &["klaus"],
+    ];
+    for args in bad {
+        let output = e6502r(args);
+        assert_eq!(output.status.code(), Some(2), "{:?}", args);
+        assert!(!output.stderr.is_empty(), "{:?}", args);
+    }
+}
+
+#[test]
+fn disasm_writes_source() {
+    let path = image("disasm", &EXIT_42);
+    let out = env::temp_dir().join(format!("e6502r-cli-{}-disasm.s", process::id()));
+    let output = e6502r(&["disasm", path.to_str().unwrap(), "--entry", "0400", "--out", out.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(0));
+    let source = fs::read_to_string(&out).unwrap();
+    assert!(source.contains("lda     #$2A") && source.contains("jmp     L0404"), "{}", source);
+}
+
+// 65c02 code using every addressing mode, then a --data range the code
+// runs into and data it refers to
+//   $0400: nop / asl a / lda #$12 / lda $34 / lda $34,x / ldx $34,y
+//          lda $0034 / lda $1200 / lda $0034,x / lda $0034,y
+//          lda ($34,x) / lda ($34),y / lda ($34) / lda $0440 / lda $0440,x
+//          bbr0 $34,+2 / beq +0 / jsr $0430 / jmp ($0438,x)
+//   $042d: jmp ($043c)
+//   $0430: jsr $00f0
+//   $0433: five NOPs, as data
+//   $0438: a table of words, $0440: "DATA"
+const ADDRESSING_MODES: [u8; 0x44] = [
+    0xea, 0x0a, 0xa9, 0x12, 0xa5, 0x34, 0xb5, 0x34, 0xb6, 0x34,
+    0xad, 0x34, 0x00, 0xad, 0x00, 0x12, 0xbd, 0x34, 0x00, 0xb9, 0x34, 0x00,
+    0xa1, 0x34, 0xb1, 0x34, 0xb2, 0x34, 0xad, 0x40, 0x04, 0xbd, 0x40, 0x04,
+    0x0f, 0x34, 0x02, 0xf0, 0x00, 0x20, 0x30, 0x04, 0x7c, 0x38, 0x04,
+    0x6c, 0x3c, 0x04,
+    0x20, 0xf0, 0x00,
+    0xea, 0xea, 0xea, 0xea, 0xea,
+    0x30, 0x04, 0x30, 0x04, 0x30, 0x04, 0x00, 0x00,
+    b'D', b'A', b'T', b'A',
+];
+
+// ca65 would assemble an absolute operand below $100 as zero page, so
+// those keep their a: while JSR, which has no zero page form, doesn't
+#[test]
+fn disasm_writes_every_addressing_mode_as_ca65_takes_it() {
+    let path = image("modes", &ADDRESSING_MODES);
+    let output = e6502r(&["disasm", path.to_str().unwrap(), "--cpu", "65c02",
+                          "--entry", "0400", "--entry", "042d", "--data", "0433-0437"]);
+    assert_eq!(output.status.code(), Some(0), "{:?}", output);
+    let source = String::from_utf8_lossy(&output.stdout);
+    let expected = [
+        "        .setcpu \"65C02\"",
+        "        .org    $0400",
+        "",
+        "L0400:",
+        "        nop",
+        "        asl     a",
+        "        lda     #$12",
+        "        lda     $34",
+        "        lda     $34,x",
+        "        ldx     $34,y",
+        "        lda     a:$0034",
+        "        lda     $1200",
+        "        lda     a:$0034,x",
+        "        lda     a:$0034,y",
+        "        lda     ($34,x)",
+        "        lda     ($34),y",
+        "        lda     ($34)",
+        "        lda     L0440",
+        "        lda     L0440,x",
+        "        bbr0    $34,L0427",
+        "        beq     L0427",
+        "L0427:",
+        "        jsr     L0430",
+        "        jmp     (L0438,x)",
+        "L042D:",
+        "        jmp     (L043C)",
+        "L0430:",
+        "        jsr     $00F0",
+        "        .byte   $EA,$EA,$EA,$EA,$EA",
+        "L0438:",
+        "        .byte   $30,$04,$30,$04",
+        "L043C:",
+        "        .byte   $30,$04,$00,$00",
+        "L0440:",
+        "        .byte   $44,$41,$54,$41",
+    ];
+    let lines: Vec<&str> = source.lines().skip_while(|line| !line.contains(".setcpu")).collect();
+    assert_eq!(lines, expected, "{}", source);
+}
+
+#[test]
+fn framebuffer_too_big_to_multiply_is_an_error() {
+    let path = image("framebuffer", &EXIT_42);
+    let device = "framebuffer@2000,width=4000000000,height=4000000000,depth=8";
+    let output = e6502r(&[path.to_str().unwrap(), "--device", device, "--run"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("past the end of memory"));
+}
+
+#[test]
+fn tui_leaves_the_alternate_screen() {
+    let path = image("tui", &EXIT_42);
+    let output = e6502r_typing(&[path.to_str().unwrap(), "--tui"], "s\nq\n");
+    assert_eq!(output.status.code(), Some(0));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    // entered at each stop, and left as the step runs and on quitting
+    assert_eq!(stdout.matches("\x1b[?1049h").count(), 2, "{}", stdout);
+    assert_eq!(stdout.matches("\x1b[?1049l").count(), 2, "{}", stdout);
+    assert!(stdout.trim_end().ends_with("\x1b[?1049l"), "{}", stdout);
+}
+
+// the nmos 6502 writes a read-modify-write's old value back before the
+// new one, and tick shows that cycle as well
+#[test]
+fn tick_shows_each_cycle_of_the_next_instruction() {
+    // INX, which runs before the first prompt, INC $10, then a JMP to itself
+    let path = image("tick", &[0xe8, 0xe6, 0x10, 0x4c, 0x03, 0x04]);
+    let output = e6502r_typing(&[path.to_str().unwrap(), "--no-color"], "tick\nq\n");
+    assert_eq!(output.status.code(), Some(0));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let cycles: Vec<&str> = stdout.lines().filter(|line| line.starts_with("cycle ")).collect();
+    assert_eq!(cycles, [
+        "cycle 1  $0401 r $e6",
+        "cycle 2  $0402 r $10",
+        "cycle 3  $0010 r $00",
+        "cycle 4  $0010 w $00",
+        "cycle 5  $0010 w $01",
+    ], "{}", stdout);
+}
+
+#[test]
+fn stepping_onto_a_breakpoint_does_not_pass_it_by() {
+    // INX, then a JMP back to it
+    let path = image("ignore", &[0xe8, 0x4c, 0x00, 0x04]);
+    let output = e6502r_typing(&[path.to_str().unwrap()], "break 400\nignore 400 2\ns\ns\nbreak list\ng\nr\nq\n");
+    assert_eq!(output.status.code(), Some(0));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("$0400, hit 1 times, passing by 2 more"), "{}", stdout);
+    // passed by twice running, so stopped on the third time round
+    assert!(stdout.contains("reached stop address $0400"), "{}", stdout);
+    assert!(stdout.contains("PC=$0400 A=$00 X=$04"), "{}", stdout);
+}
+
+#[test]
+fn breakpoint_arguments_are_trimmed() {
+    // INX, then a JMP back to it
+    let path = image("trimmed", &[0xe8, 0x4c, 0x00, 0x04]);
+    let output = e6502r_typing(&[path.to_str().unwrap()], "tbreak  401\ndelete  401\nuntil  401\nq\n");
+    assert_eq!(output.status.code(), Some(0));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("temporary breakpoint at $0401"), "{}", stdout);
+    assert!(stdout.contains("deleted the breakpoint at $0401"), "{}", stdout);
+    assert!(stdout.contains("reached stop address $0401"), "{}", stdout);
+}
+
+// LDA #A, then BEQ $0414 with an nmi taken as it ends
+fn branch_then_nmi(name: &str, a: u8) -> String {
+    let path = image(name, &[0xa9, a, 0xf0, 0x10]);
+    let output = e6502r_typing(&[path.to_str().unwrap()], "nmi\nbranch\nq\n");
+    assert_eq!(output.status.code(), Some(0));
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+#[test]
+fn branch_is_not_taken_by_an_interrupt() {
+    let stdout = branch_then_nmi("branch-not-taken", 1);
+    assert!(!stdout.contains("branch or jump at $0402"), "{}", stdout);
+}
+
+#[test]
+fn branch_taken_before_an_interrupt_stops() {
+    let stdout = branch_then_nmi("branch-taken", 0);
+    assert!(stdout.contains("branch or jump at $0402 to $0414"), "{}", stdout);
+}
+
+#[test]
+fn serial_connects_the_first_acia() {
+    // SEI, enable the acia at $d000, wait for a byte and echo it, then a
+    // JMP to itself
+    let path = image("serial", &[0x78, 0xa9, 0x0b, 0x8d, 0x02, 0xd0, 0xad, 0x01, 0xd0, 0x29, 0x08, 0xf0, 0xf9,
+                                 0xad, 0x00, 0xd0, 0x8d, 0x00, 0xd0, 0x4c, 0x13, 0x04]);
+    let port = 20000 + (process::id() % 20000) as u16;
+    let serial = format!("tcp:{}", port);
+    let child = Command::new(env!("CARGO_BIN_EXE_e6502r"))
+        .args([path.to_str().unwrap(), "--device", "acia@d000", "--serial", &serial, "--run"])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    let give_up = Instant::now() + Duration::from_secs(5);
+    let mut client = loop {
+        match TcpStream::connect(("127.0.0.1", port)) {
+            Ok(client) => break client,
+            Err(e) if Instant::now() > give_up => panic!("can't connect: {}", e),
+            Err(_) => thread::sleep(Duration::from_millis(10)),
+        }
+    };
+    client.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    client.write_all(b"x").unwrap();
+    let mut echoed = [0; 1];
+    client.read_exact(&mut echoed).unwrap();
+    assert_eq!(&echoed, b"x");
+    assert_eq!(child.wait_with_output().unwrap().status.code(), Some(0));
+}
+
+#[test]
+fn serial_needs_an_acia() {
+    let path = image("serial-none", &EXIT_42);
+    let output = e6502r(&[path.to_str().unwrap(), "--serial", "tcp:6502", "--run"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--serial needs an acia"));
+}
+
+#[test]
+fn text_screen_is_drawn_through_crossterm() {
+    // an inverse H at the top left, then a JMP to itself
+    let path = image("screen", &[0xa9, 0xc8, 0x8d, 0x00, 0x80, 0x4c, 0x05, 0x04]);
+    let output = e6502r(&[path.to_str().unwrap(), "--device", "text-screen@8000,columns=2,rows=1", "--run"]);
+    assert_eq!(output.status.code(), Some(0));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.starts_with("\x1b[2J\x1b[1;1H\x1b[7mH\x1b[27m \x1b[2;1H"), "{:?}", stdout);
+}
@@ -0,0 +1,60 @@
+// the machine the cpu tests run instructions on: a cpu and memory with
+// nothing attached, run an instruction at a time through the handler
+// table. each test file takes what it needs of this, so the rest is
+// dead code to it
+
+#![allow(dead_code)]
+
+use e6502r::cpu::{opcode_table, Cpu, CpuVariant};
+use e6502r::memory::Memory;
+
+pub const VARIANTS: [CpuVariant; 3] = [CpuVariant::Nmos6502, CpuVariant::Cmos65c02, CpuVariant::Ricoh2a03];
+
+pub const CODE: u16 = 0x0200;       // where Machine::new puts the code
+pub const HANDLER: u16 = 0x0300;    // where the nmi, irq and brk vectors point
+
+pub struct Machine {
+    pub cpu: Cpu,
+    pub mem: Memory,
+}
+
+impl Machine {
+    // the bytes at CODE, the pc there and SP at $ff
+    pub fn new(variant: CpuVariant, bytes: &[u8]) -> Machine {
+        Machine::at(variant, CODE, bytes)
+    }
+
+    // the bytes written from pc, wrapping round memory
+    pub fn at(variant: CpuVariant, pc: u16, bytes: &[u8]) -> Machine {
+        let mut machine = Machine { cpu: Cpu::new(), mem: Memory::new() };
+        machine.cpu.variant = variant;
+        machine.cpu.sp = 0xff;
+        machine.cpu.pc = pc;
+        for vector in [0xfffa, 0xfffe] {
+            machine.mem.mem[vector] = HANDLER as u8;
+            machine.mem.mem[vector + 1] = (HANDLER >> 8) as u8;
+        }
+        for (i, &byte) in bytes.iter().enumerate() {
+            machine.mem.mem[pc.wrapping_add(i as u16) as usize] = byte;
+        }
+        machine
+    }
+
+    // run one instruction at the pc
+    pub fn step(&mut self) {
+        let opcode = self.mem.mem[self.cpu.pc as usize];
+        opcode_table(self.cpu.variant)[opcode as usize](&mut self.cpu, &mut self.mem);
+    }
+
+    // put the bytes at the pc and run the instruction they start with
+    pub fn run(&mut self, bytes: &[u8]) {
+        let pc = self.cpu.pc as usize;
+        self.mem.mem[pc..pc + bytes.len()].copy_from_slice(bytes);
+        self.step();
+    }
+
+    // the last byte pushed
+    pub fn pushed(&self) -> u8 {
+        self.mem.mem[0x0100 + self.cpu.sp.wrapping_add(1) as usize]
+    }
+}
@@ -0,0 +1,70 @@
+// the two ways of running an opcode, calling its handler through the
+// table and matching on the instruction it decodes to, leave the cpu and
+// memory the same for every opcode on every cpu
+//
+//   cargo test --test dispatch
+
+mod common;
+
+use common::{CODE, VARIANTS};
+use e6502r::cpu::{decode, execute, opcode_table, Cpu, CpuVariant};
+use e6502r::memory::{Memory, MEMSIZE};
+
+const SEEDS: u64 = 8;
+
+// a random generator for the machine's state, one sequence per seed
+fn generator(seed: u64) -> impl FnMut() -> u8 {
+    let mut state = seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1;
+    move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state as u8
+    }
+}
+
+// a machine with memory from the image and registers from the seed,
+// the opcode at the pc
+fn machine(variant: CpuVariant, opcode: u8, seed: u64, image: &[u8]) -> (Cpu, Memory) {
+    let mut next = generator(seed);
+    let mut mem = Memory::new();
+    mem.mem.copy_from_slice(image);
+    mem.mem[CODE as usize] = opcode;
+    let mut cpu = Cpu::new();
+    cpu.variant = variant;
+    cpu.pc = CODE;
+    cpu.ac = next();
+    cpu.xr = next();
+    cpu.yr = next();
+    cpu.sp = next();
+    cpu.set_status(next());
+    (cpu, mem)
+}
+
+fn registers(cpu: &Cpu) -> (u16, u8, u8, u8, u8, u8, u64) {
+    (cpu.pc, cpu.ac, cpu.xr, cpu.yr, cpu.sp, cpu.status(), cpu.cycles)
+}
+
+#[test]
+fn match_runs_as_the_table_does() {
+    let images: Vec<Vec<u8>> = (0..SEEDS).map(|seed| {
+        let mut next = generator(!seed);
+        (0..MEMSIZE).map(|_| next()).collect()
+    }).collect();
+    for variant in VARIANTS {
+        for opcode in 0..=255 {
+            for seed in 0..SEEDS {
+                let image = &images[seed as usize];
+                let (mut table_cpu, mut table_mem) = machine(variant, opcode, seed, image);
+                opcode_table(variant)[opcode as usize](&mut table_cpu, &mut table_mem);
+                let (mut match_cpu, mut match_mem) = machine(variant, opcode, seed, image);
+                execute(&mut match_cpu, &mut match_mem, decode(variant, opcode));
+
+                assert_eq!(registers(&match_cpu), registers(&table_cpu), "{:?} opcode ${:02x} seed {}", variant, opcode, seed);
+                let differs = (match_mem.mem[..] != table_mem.mem[..])
+                    .then(|| (0..MEMSIZE).find(|&addr| match_mem.mem[addr] != table_mem.mem[addr]));
+                assert_eq!(differs, None, "{:?} opcode ${:02x} seed {} memory", variant, opcode, seed);
+            }
+        }
+    }
+}
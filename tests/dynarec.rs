@@ -0,0 +1,129 @@
+// the dynarec's translated blocks: each is translated again when the
+// code it came from is written over, whether by the block itself, by
+// code elsewhere or from outside between runs. run_for only uses them
+// with the dynarec feature, and the run_for tests here hold either way
+//
+//   cargo test --test dynarec
+//   cargo test --features dynarec --test dynarec
+
+mod common;
+
+use common::{Machine, CODE};
+use e6502r::cpu::CpuVariant;
+use e6502r::dynarec::Dynarec;
+use e6502r::run::{finish_instruction, run_for, step, StopConditions, StopReason};
+
+const BRK: u8 = 0x00;
+const DEY: u8 = 0x88;
+const INX: u8 = 0xe8;
+const INY: u8 = 0xc8;
+const JMP_ABS: u8 = 0x4c;
+const LDA_IMM: u8 = 0xa9;
+const NOP: u8 = 0xea;
+const PHX: u8 = 0xda;
+const STA_ABS: u8 = 0x8d;
+
+// run the block at the pc, as run_for does, with cycles to spare
+fn run_block(blocks: &mut Dynarec, machine: &mut Machine) -> Option<(u64, Option<StopReason>)> {
+    let stops = StopConditions::default();
+    let end = machine.cpu.cycles + 1000;
+    blocks.run_block(&mut machine.cpu, &mut machine.mem, end, |cpu, mem, pc| finish_instruction(cpu, mem, &stops, pc))
+}
+
+fn jmp(addr: u16) -> [u8; 3] {
+    [JMP_ABS, addr as u8, (addr >> 8) as u8]
+}
+
+#[test]
+fn blocks_run_to_the_jump_that_ends_them() {
+    let mut machine = Machine::new(CpuVariant::Nmos6502, &[INX, INX, INY, JMP_ABS, CODE as u8, (CODE >> 8) as u8]);
+    let mut blocks = Dynarec::new();
+    for pass in 1..=3 {
+        let (executed, stop) = run_block(&mut blocks, &mut machine).unwrap();
+        assert_eq!(executed, 4);
+        assert!(stop.is_none());
+        assert_eq!((machine.cpu.pc, machine.cpu.xr, machine.cpu.yr), (CODE, 2 * pass, pass));
+    }
+}
+
+#[test]
+fn code_changed_between_runs_is_translated_again() {
+    let mut machine = Machine::new(CpuVariant::Nmos6502, &[INX, INX]);
+    machine.mem.mem[CODE as usize + 2..CODE as usize + 5].copy_from_slice(&jmp(CODE));
+    let mut blocks = Dynarec::new();
+    run_block(&mut blocks, &mut machine).unwrap();
+    assert_eq!(machine.cpu.xr, 2);
+    // as the monitor, a loaded state or a bank switch would
+    machine.mem.mem[CODE as usize + 1] = DEY;
+    run_block(&mut blocks, &mut machine).unwrap();
+    assert_eq!((machine.cpu.xr, machine.cpu.yr), (3, 0xff));
+}
+
+// the block writes INX over the NOP ahead of it in the same block
+#[test]
+fn a_block_writing_over_itself_runs_what_it_wrote() {
+    let mut machine = Machine::new(CpuVariant::Nmos6502, &[LDA_IMM, INX, STA_ABS, (CODE + 5) as u8, ((CODE + 5) >> 8) as u8, NOP]);
+    machine.mem.mem[CODE as usize + 6..CODE as usize + 9].copy_from_slice(&jmp(CODE));
+    let mut blocks = Dynarec::new();
+    // the block is left after the write, and the rest of it translated
+    // as a block of its own
+    assert_eq!(run_block(&mut blocks, &mut machine).unwrap().0, 2);
+    assert_eq!(machine.cpu.pc, CODE + 5);
+    assert_eq!(machine.mem.mem[CODE as usize + 5], INX);
+    assert_eq!(run_block(&mut blocks, &mut machine).unwrap().0, 2);
+    assert_eq!((machine.cpu.pc, machine.cpu.xr), (CODE, 1));
+    // and the block at CODE, translated with the NOP, again
+    assert_eq!(run_block(&mut blocks, &mut machine).unwrap().0, 4);
+    assert_eq!(machine.cpu.xr, 2);
+}
+
+// PHX is a 65c02 instruction, which the nmos 6502 doesn't implement
+#[test]
+fn blocks_are_translated_again_for_another_variant() {
+    let mut machine = Machine::new(CpuVariant::Cmos65c02, &[PHX]);
+    machine.mem.mem[CODE as usize + 1..CODE as usize + 4].copy_from_slice(&jmp(CODE));
+    machine.cpu.sp = 0xff;
+    let mut blocks = Dynarec::new();
+    run_block(&mut blocks, &mut machine).unwrap();
+    assert_eq!(machine.cpu.sp, 0xfe);
+    machine.cpu.variant = CpuVariant::Nmos6502;
+    assert!(run_block(&mut blocks, &mut machine).is_none());
+    assert_eq!((machine.cpu.pc, machine.cpu.sp), (CODE, 0xfe));
+}
+
+#[test]
+fn brk_is_left_to_step() {
+    let mut machine = Machine::new(CpuVariant::Nmos6502, &[BRK]);
+    assert!(run_block(&mut Dynarec::new(), &mut machine).is_none());
+    assert_eq!(machine.cpu.pc, CODE);
+}
+
+// a loop rewriting its own first instruction, which alternates between
+// INX and DEY, gives the same machine through run_for as a step at a time
+//   CODE: inx / lda CODE / eor #$e8^$88 / sta CODE / jmp CODE
+#[test]
+fn run_for_matches_stepping_code_that_rewrites_itself() {
+    let program = [INX, 0xad, CODE as u8, (CODE >> 8) as u8, 0x49, INX ^ DEY,
+                   STA_ABS, CODE as u8, (CODE >> 8) as u8, JMP_ABS, CODE as u8, (CODE >> 8) as u8];
+    let stops = StopConditions::default();
+    let mut blocks = Machine::new(CpuVariant::Nmos6502, &program);
+    let run = run_for(&mut blocks.cpu, &mut blocks.mem, &stops, 10_000);
+    assert!(run.stop.is_none());
+    let mut stepped = Machine::new(CpuVariant::Nmos6502, &program);
+    while stepped.cpu.cycles < blocks.cpu.cycles {
+        assert!(step(&mut stepped.cpu, &mut stepped.mem, &stops).is_none());
+    }
+    assert_eq!(stepped.cpu.to_string(), blocks.cpu.to_string());
+    assert_eq!(stepped.cpu.cycles, blocks.cpu.cycles);
+    assert_eq!(stepped.mem.mem[..], blocks.mem.mem[..]);
+}
+
+#[test]
+fn blocks_are_left_when_the_budget_is_spent() {
+    let mut machine = Machine::new(CpuVariant::Nmos6502, &[INX, INX, INX, INX]);
+    machine.mem.mem[CODE as usize + 4..CODE as usize + 7].copy_from_slice(&jmp(CODE));
+    let stops = StopConditions::default();
+    let run = Dynarec::new().run_block(&mut machine.cpu, &mut machine.mem, 3, |cpu, mem, pc| finish_instruction(cpu, mem, &stops, pc));
+    assert_eq!(run.unwrap().0, 2);
+    assert_eq!((machine.cpu.pc, machine.cpu.cycles), (CODE + 2, 4));
+}
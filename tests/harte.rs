@@ -0,0 +1,78 @@
+// the harte sub-command on test files written to the temporary
+// directory, checking instructions cycle by cycle against the bus
+//
+//   cargo test --test harte
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::process::{Command, Output, Stdio};
+
+// INC $10FF,X with X = 1: the 6502 reads the wrong page before the
+// right one, and writes the old value back before the new one
+const INC_ABX: &str = r#"[{ "name": "fe ff 10",
+    "initial": { "pc": 512, "s": 253, "a": 0, "x": 1, "y": 0, "p": 36,
+                 "ram": [[512, 254], [513, 255], [514, 16], [4096, 0], [4352, 127]] },
+    "final":   { "pc": 515, "s": 253, "a": 0, "x": 1, "y": 0, "p": 164,
+                 "ram": [[512, 254], [513, 255], [514, 16], [4096, 0], [4352, 128]] },
+    "cycles":  [[512, 254, "read"], [513, 255, "read"], [514, 16, "read"], [4096, 0, "read"],
+                [4352, 127, "read"], [4352, 127, "write"], [4352, 128, "write"]] }]"#;
+
+// JSR $3000: the target's high byte is read after the pushes
+const JSR: &str = r#"[{ "name": "20 00 30",
+    "initial": { "pc": 512, "s": 253, "a": 0, "x": 0, "y": 0, "p": 36,
+                 "ram": [[512, 32], [513, 0], [514, 48], [509, 0], [508, 0]] },
+    "final":   { "pc": 12288, "s": 251, "a": 0, "x": 0, "y": 0, "p": 36,
+                 "ram": [[512, 32], [513, 0], [514, 48], [509, 2], [508, 2]] },
+    "cycles":  [[512, 32, "read"], [513, 0, "read"], [509, 0, "read"], [509, 2, "write"],
+                [508, 2, "write"], [514, 48, "read"]] }]"#;
+
+// LDA $10, expecting the read from the wrong address
+const LDA_WRONG_ADDRESS: &str = r#"[{ "name": "a5 10",
+    "initial": { "pc": 512, "s": 253, "a": 0, "x": 0, "y": 0, "p": 36,
+                 "ram": [[512, 165], [513, 16], [16, 5]] },
+    "final":   { "pc": 514, "s": 253, "a": 5, "x": 0, "y": 0, "p": 36,
+                 "ram": [[512, 165], [513, 16], [16, 5]] },
+    "cycles":  [[512, 165, "read"], [513, 16, "read"], [17, 5, "read"]] }]"#;
+
+// a test file of its own, named after its opcode
+fn test_file(name: &str, opcode: &str, json: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("e6502r-harte-{}-{}", process::id(), name));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(format!("{}.json", opcode));
+    fs::write(&path, json).unwrap();
+    path
+}
+
+fn harte(path: &Path) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_e6502r"))
+        .args(["harte", path.to_str().unwrap()])
+        .stdin(Stdio::null())
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn read_modify_write_matches_the_bus() {
+    let output = harte(&test_file("inc", "fe", INC_ABX));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}", stdout);
+    assert!(stdout.contains("PASS  fe.json"), "{}", stdout);
+}
+
+#[test]
+fn jsr_matches_the_bus() {
+    let output = harte(&test_file("jsr", "20", JSR));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}", stdout);
+    assert!(stdout.contains("PASS  20.json"), "{}", stdout);
+}
+
+#[test]
+fn first_mismatching_cycle_is_reported() {
+    let output = harte(&test_file("lda", "a5", LDA_WRONG_ADDRESS));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(!output.status.success());
+    assert!(stdout.contains("a5 10: cycle 3: read $0010 = $05, expected read $0011 = $05"), "{}", stdout);
+}
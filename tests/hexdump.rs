@@ -0,0 +1,134 @@
+// memory saved as an xxd dump and loaded back, offsets near the top of
+// memory, and the errors malformed dumps give
+//
+//   cargo test --test hexdump
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process;
+
+use e6502r::hexdump::{format, load, save};
+use e6502r::memory::{Memory, MEMSIZE};
+
+// a file of its own for this test
+fn dump_file(name: &str, text: &str) -> PathBuf {
+    let path = env::temp_dir().join(format!("e6502r-hexdump-{}-{}.txt", process::id(), name));
+    fs::write(&path, text).unwrap();
+    path
+}
+
+fn load_text(name: &str, text: &str, base: u16) -> Result<(usize, Memory), String> {
+    let path = dump_file(name, text);
+    let mut mem = Memory::new();
+    load(&mut mem, path.to_str().unwrap(), base).map(|loaded| (loaded, mem))
+}
+
+// every byte value, somewhere other than where each would be on its own
+fn pattern(mem: &mut Memory) {
+    for (addr, byte) in mem.mem.iter_mut().enumerate() {
+        *byte = (addr * 7 + addr / 256) as u8;
+    }
+}
+
+#[test]
+fn lines_are_in_xxds_layout() {
+    let mut mem = Memory::new();
+    mem.mem[0xe000..0xe008].copy_from_slice(&[0xa9, 0xff, 0x8d, 0x00, 0xd0, 0x4c, 0x00, 0xe0]);
+    mem.mem[0xe010] = b'A';
+    assert_eq!(format(&mem, 0xe000, 0xe010),
+        "0000e000: a9ff 8d00 d04c 00e0 0000 0000 0000 0000  .....L..........\n\
+         0000e010: 41                                       A\n");
+}
+
+#[test]
+fn saved_memory_loads_back() {
+    let mut mem = Memory::new();
+    pattern(&mut mem);
+    let path = dump_file("round-trip", "");
+    // ranges that end mid-line, and all of memory
+    for (first, last) in [(0x0000, 0xffff), (0x0200, 0x02ff), (0x1234, 0x1300), (0xfff0, 0xffff), (0x0400, 0x0400)] {
+        save(&mem, path.to_str().unwrap(), first, last).unwrap();
+        let mut loaded = Memory::new();
+        let count = load(&mut loaded, path.to_str().unwrap(), 0).unwrap();
+        assert_eq!(count, last as usize - first as usize + 1);
+        let range = first as usize..=last as usize;
+        assert_eq!(loaded.mem[range.clone()], mem.mem[range], "${:04x}-${:04x}", first, last);
+        assert!(loaded.mem[..first as usize].iter().all(|&b| b == 0));
+        assert!(loaded.mem[last as usize + 1..].iter().all(|&b| b == 0));
+    }
+}
+
+#[test]
+fn text_column_may_be_left_off() {
+    let (loaded, mem) = load_text("no-text", "00000010: 0102 0304\n\n00000020: ff\n", 0).unwrap();
+    assert_eq!(loaded, 5);
+    assert_eq!(mem.mem[0x10..0x14], [1, 2, 3, 4]);
+    assert_eq!(mem.mem[0x20], 0xff);
+}
+
+#[test]
+fn offsets_are_taken_from_the_base() {
+    // a plain xxd of a rom, loaded where the rom goes
+    let (_, mem) = load_text("base", "00000000: a9ff  ..\n00000010: 60  `\n", 0xe000).unwrap();
+    assert_eq!(mem.mem[0xe000..0xe002], [0xa9, 0xff]);
+    assert_eq!(mem.mem[0xe010], 0x60);
+}
+
+#[test]
+fn dump_can_reach_the_end_of_memory() {
+    let mut mem = Memory::new();
+    pattern(&mut mem);
+    assert!(format(&mem, 0xffff, 0xffff).starts_with("0000ffff: "));
+
+    // the last line ends on $ffff from its offset, or from a base
+    let (_, loaded) = load_text("top", &format(&mem, 0xfff0, 0xffff), 0).unwrap();
+    assert_eq!(loaded.mem[0xfff0..], mem.mem[0xfff0..]);
+    let (_, loaded) = load_text("top-base", "00000000: 0102 0304 0506 0708 090a 0b0c 0d0e 0f10\n", 0xfff0).unwrap();
+    assert_eq!(loaded.mem[0xfff0..], (1..=16).collect::<Vec<u8>>()[..]);
+    let (_, loaded) = load_text("top-byte", "0000000f: 42\n", 0xfff0).unwrap();
+    assert_eq!(loaded.mem[MEMSIZE - 1], 0x42);
+}
+
+#[test]
+fn lines_past_the_end_of_memory_are_errors() {
+    let cases = [
+        // a byte past $ffff, from the offset, the base or both
+        ("0000fff0: 0102 0304 0506 0708 090a 0b0c 0d0e 0f10 11\n", 0x0000, "offset fff0 from $0000"),
+        ("00000010: 00\n", 0xfff0, "offset 10 from $fff0"),
+        ("0000000f: 0102\n", 0xfff1, "offset f from $fff1"),
+        ("00010000: 00\n", 0x0000, "offset 10000 from $0000"),
+        // past the end of usize with the base added
+        ("ffffffffffffffff: 00\n", 0x0001, "offset ffffffffffffffff from $0001"),
+    ];
+    for (text, base, message) in cases {
+        let error = load_text("past-end", text, base).err().unwrap();
+        assert!(error.ends_with(&format!(":1: {} is past the end of memory", message)), "{:?}: {}", text, error);
+    }
+}
+
+#[test]
+fn malformed_lines_are_errors() {
+    let cases = [
+        ("hello\n", "not an xxd line"),
+        ("zz: 00\n", "bad offset 'zz'"),
+        (": 00\n", "bad offset ''"),
+        ("+10: 00\n", "bad offset '+10'"),
+        ("00000000: abc\n", "odd number of hex digits"),
+        ("00000000: 0g\n", "bad byte '0g'"),
+        ("00000000: +f\n", "bad byte '+f'"),
+        ("00000000: 00\n00000010: 01 xx\n", "bad byte 'xx'"),
+    ];
+    for (text, message) in cases {
+        let error = load_text("malformed", text, 0).err().unwrap();
+        let line = text.lines().count();
+        assert!(error.ends_with(&format!(":{}: {}", line, message)), "{:?}: {}", text, error);
+    }
+}
+
+#[test]
+fn missing_file_is_an_error() {
+    let mut mem = Memory::new();
+    let path = env::temp_dir().join(format!("e6502r-hexdump-{}-missing.txt", process::id()));
+    assert!(load(&mut mem, path.to_str().unwrap(), 0).is_err());
+}
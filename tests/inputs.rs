@@ -0,0 +1,75 @@
+// each machine's inputs: keys pushed for one machine reach only its
+// keyboard, pasted text is typed paste_delay cycles apart after any keys
+// pushed, and an interrupt pulled on one machine stays with it
+//
+//   cargo test --test inputs
+
+mod common;
+
+use common::Machine;
+use e6502r::cpu::CpuVariant;
+use e6502r::devices::keyboard::{Keyboard, KEYBOARD_SIZE};
+
+const KEYBOARD: u16 = 0xc000;
+const STROBE: u16 = KEYBOARD + 0x10;
+
+// a machine with a keyboard at KEYBOARD
+fn machine() -> Machine {
+    let mut machine = Machine::new(CpuVariant::Nmos6502, &[]);
+    let keyboard = Keyboard::new(machine.mem.inputs.clone(), false);
+    machine.mem.attach(KEYBOARD, KEYBOARD_SIZE, Box::new(keyboard)).unwrap();
+    machine
+}
+
+// the key latched, with the strobe cleared after it
+fn read_key(machine: &mut Machine) -> u8 {
+    let key = machine.mem.read(KEYBOARD);
+    machine.mem.read(STROBE);
+    key
+}
+
+#[test]
+fn keys_go_to_their_own_machine() {
+    let mut first = machine();
+    let mut second = machine();
+    first.mem.inputs.push_key(b'a');
+    second.mem.inputs.push_key(b'b');
+    second.mem.inputs.push_key(b'c');
+    assert_eq!(read_key(&mut first), b'a' | 0x80);
+    assert_eq!(read_key(&mut first), b'a');
+    assert_eq!(read_key(&mut second), b'b' | 0x80);
+    assert_eq!(read_key(&mut second), b'c' | 0x80);
+    assert!(first.mem.inputs.keyboard_claimed() && second.mem.inputs.keyboard_claimed());
+    assert!(!Machine::new(CpuVariant::Nmos6502, &[]).mem.inputs.keyboard_claimed());
+}
+
+#[test]
+fn pasted_text_comes_a_delay_apart_after_the_keys() {
+    let mut machine = machine();
+    let inputs = machine.mem.inputs.clone();
+    inputs.set_paste_delay(100);
+    assert_eq!(inputs.paste("hi\r\n"), 3);
+    inputs.push_key(b'x');
+    assert_eq!(read_key(&mut machine), b'x' | 0x80);
+    assert_eq!(read_key(&mut machine), b'h' | 0x80);
+    // nothing new until the delay is up
+    inputs.set_cycle(99);
+    assert_eq!(read_key(&mut machine), b'h');
+    inputs.set_cycle(100);
+    assert_eq!(read_key(&mut machine), b'i' | 0x80);
+    assert_eq!(inputs.cancel_paste(), 1);
+    inputs.set_cycle(1000);
+    assert_eq!(read_key(&mut machine), b'i');
+}
+
+#[test]
+fn interrupts_pulled_stay_with_their_machine() {
+    let first = machine();
+    let second = machine();
+    first.mem.inputs.pulse_irq();
+    second.mem.inputs.pulse_nmi();
+    assert!(first.mem.inputs.irq() && !first.mem.inputs.nmi());
+    assert!(second.mem.inputs.nmi() && !second.mem.inputs.irq());
+    // each is taken once
+    assert!(!first.mem.inputs.irq() && !second.mem.inputs.nmi());
+}
@@ -0,0 +1,146 @@
+// the json parser the single-instruction tests and debugger requests
+// are read with: strings and their escapes, numbers, nesting, quote
+// giving back what parse reads, and the errors malformed documents
+// give, which come back without panicking
+//
+//   cargo test --test json
+
+use e6502r::json::{parse, quote, Object, Value};
+
+fn string(text: &str) -> Value {
+    Value::String(String::from(text))
+}
+
+#[test]
+fn strings_and_escapes() {
+    let cases = [
+        (r#""""#, ""),
+        (r#""plain""#, "plain"),
+        (r#""\"\\\/""#, "\"\\/"),
+        (r#""\b\f\n\r\t""#, "\u{8}\u{c}\n\r\t"),
+        (r#""Aé☺""#, "A\u{e9}\u{263a}"),
+        (r#""\ud83d""#, "\u{fffd}"),
+        ("\"caf\u{e9}\"", "caf\u{e9}"),
+    ];
+    for (text, expected) in cases {
+        assert_eq!(parse(text), Ok(string(expected)), "{}", text);
+    }
+}
+
+#[test]
+fn quote_round_trips() {
+    for text in ["", "plain", "\"quoted\"", "back\\slash", "tab\tnew\nline\r\u{1}", "caf\u{e9}"] {
+        assert_eq!(parse(&quote(text)), Ok(string(text)), "{:?}", text);
+    }
+}
+
+#[test]
+fn numbers() {
+    let cases = [
+        ("0", 0.0),
+        ("42", 42.0),
+        ("-42", -42.0),
+        ("-0", -0.0),
+        ("3.25", 3.25),
+        ("-0.5", -0.5),
+        ("1e3", 1e3),
+        ("1E+3", 1e3),
+        ("2.5e-3", 2.5e-3),
+        ("65535", 65535.0),
+        ("18446744073709551616", 18446744073709551616.0),
+    ];
+    for (text, expected) in cases {
+        assert_eq!(parse(text), Ok(Value::Number(expected)), "{}", text);
+    }
+}
+
+#[test]
+fn nested_objects_and_arrays() {
+    let document = parse(r#"
+        [{ "name": "a9 01",
+           "initial": { "pc": 512, "ram": [[512, 169], [513, 1]] },
+           "cycles": [[512, 169, "read"]],
+           "empty": {}, "none": [], "flags": [true, false, null] }]
+    "#).unwrap();
+    let tests = match document {
+        Value::Array(tests) => tests,
+        other => panic!("{}", other.type_name()),
+    };
+    let test = match &tests[0] {
+        Value::Object(test) => test,
+        other => panic!("{}", other.type_name()),
+    };
+    assert_eq!(test["name"], string("a9 01"));
+    let initial = match &test["initial"] {
+        Value::Object(initial) => initial,
+        other => panic!("{}", other.type_name()),
+    };
+    assert_eq!(initial["ram"], Value::Array(vec![
+        Value::Array(vec![Value::Number(512.0), Value::Number(169.0)]),
+        Value::Array(vec![Value::Number(513.0), Value::Number(1.0)]),
+    ]));
+    assert_eq!(test["cycles"], Value::Array(vec![
+        Value::Array(vec![Value::Number(512.0), Value::Number(169.0), string("read")]),
+    ]));
+    assert_eq!(test["empty"], Value::Object(Object::new()));
+    assert_eq!(test["none"], Value::Array(Vec::new()));
+    assert_eq!(test["flags"], Value::Array(vec![Value::Boolean(true), Value::Boolean(false), Value::Null]));
+}
+
+#[test]
+fn deep_nesting() {
+    let depth = 200;
+    let text = format!("{}{}", "[".repeat(depth), "]".repeat(depth));
+    let mut value = parse(&text).unwrap();
+    for _ in 1..depth {
+        value = match value {
+            Value::Array(mut items) => items.remove(0),
+            other => panic!("{}", other.type_name()),
+        };
+    }
+    assert_eq!(value, Value::Array(Vec::new()));
+}
+
+#[test]
+fn malformed_documents_are_errors() {
+    let cases = [
+        ("", "expected a value at end of input"),
+        ("   ", "expected a value at end of input"),
+        ("\"open", "unterminated string"),
+        (r#""\q""#, r"unknown escape '\q'"),
+        (r#""\"#, "unterminated string"),
+        (r#""\u12"#, r"bad escape '\u12'"),
+        (r#""\u00""#, r#"bad escape '\u00"'"#),
+        (r#""\u+123""#, r"bad escape '\u+123'"),
+        ("-", "bad number '-'"),
+        ("1.2.3", "bad number '1.2.3'"),
+        ("1e", "bad number '1e'"),
+        ("--1", "bad number '--1'"),
+        ("0x10", "unexpected 'x' after the document"),
+        ("+1", "unexpected '+'"),
+        (".5", "unexpected '.'"),
+        ("nul", "unexpected 'nul'"),
+        ("True", "unexpected 'True'"),
+        ("[1, 2", "unterminated array"),
+        ("[1 2]", "expected ',' or ']', found '2'"),
+        ("[1,]", "unexpected ']'"),
+        ("{\"a\": 1", "unterminated object"),
+        ("{\"a\" 1}", "expected ':', found '1'"),
+        ("{a: 1}", "expected '\"', found 'a'"),
+        ("{\"a\": 1,}", "expected '\"', found '}'"),
+        ("{\"a\": 1 \"b\": 2}", "expected ',' or '}', found '\"'"),
+        ("[] []", "unexpected '[' after the document"),
+    ];
+    for (text, message) in cases {
+        match parse(text) {
+            Ok(value) => panic!("{:?} parsed as {:?}", text, value),
+            Err(error) => assert!(error.contains(message), "{:?}: {}", text, error),
+        }
+    }
+}
+
+#[test]
+fn errors_give_the_line() {
+    let error = parse("{\n  \"a\": 1,\n  \"b\": ?\n}").unwrap_err();
+    assert_eq!(error, "line 3: unexpected '?'");
+}
@@ -0,0 +1,287 @@
+// N and Z are kept as the last result and only worked out when looked
+// at. these check everything that looks at them, after each kind of
+// instruction that sets them: the status PHP, BRK, IRQ and NMI push,
+// the branches on them, BIT setting them apart from a result, and PLP
+// and RTI replacing a result still pending. each runs through the
+// handler table and through decode and match, which must agree
+//
+//   cargo test --test lazy_flags
+
+mod common;
+
+use common::{Machine, HANDLER, VARIANTS};
+use e6502r::cpu::{decode, execute, irq, nmi, CpuVariant};
+
+const Z: u8 = 0x02;
+const V: u8 = 0x40;
+const N: u8 = 0x80;
+const U: u8 = 0x20;
+
+const BRK: u8 = 0x00;
+const PHP: u8 = 0x08;
+const BPL: u8 = 0x10;
+const PLP: u8 = 0x28;
+const BMI: u8 = 0x30;
+const RTI: u8 = 0x40;
+const LDX_IMM: u8 = 0xa2;
+const LDA_IMM: u8 = 0xa9;
+const BNE: u8 = 0xd0;
+const BEQ: u8 = 0xf0;
+const BIT_ZPG: u8 = 0x24;
+const BIT_IMM: u8 = 0x89;
+
+const ZPG: u16 = 0x0010;
+const VALUES: [u8; 6] = [0x00, 0x01, 0x40, 0x7f, 0x80, 0xff];
+
+// the two ways of running an instruction
+#[derive(Clone, Copy, Debug)]
+enum Dispatch {
+    Table,
+    Match,
+}
+
+const DISPATCHES: [Dispatch; 2] = [Dispatch::Table, Dispatch::Match];
+
+fn step(machine: &mut Machine, dispatch: Dispatch) {
+    match dispatch {
+        Dispatch::Table => machine.step(),
+        Dispatch::Match => {
+            let instruction = decode(machine.cpu.variant, machine.mem.mem[machine.cpu.pc as usize]);
+            execute(&mut machine.cpu, &mut machine.mem, instruction);
+        }
+    }
+}
+
+fn nz(value: u8) -> u8 {
+    (value & N) | if value == 0 { Z } else { 0 }
+}
+
+// an instruction setting N and Z, run with v in A, X, Y, $10 and on top
+// of the stack; result gives the byte N and Z should come from
+struct Case {
+    name: &'static str,
+    bytes: Vec<u8>,
+    result: fn(&Machine, u8) -> u8,
+}
+
+fn case(name: &'static str, bytes: &[u8], result: fn(&Machine, u8) -> u8) -> Case {
+    Case { name, bytes: bytes.to_vec(), result }
+}
+
+fn cases() -> Vec<Case> {
+    vec![
+        case("LDA #", &[0xa9, 0x00], |m, _| m.cpu.ac),
+        case("LDX $10", &[0xa6, 0x10], |m, _| m.cpu.xr),
+        case("LDY $10", &[0xa4, 0x10], |m, _| m.cpu.yr),
+        case("TAX", &[0xaa], |m, _| m.cpu.xr),
+        case("TYA", &[0x98], |m, _| m.cpu.ac),
+        case("INX", &[0xe8], |m, _| m.cpu.xr),
+        case("DEY", &[0x88], |m, _| m.cpu.yr),
+        case("INC $10", &[0xe6, 0x10], |m, _| m.mem.mem[ZPG as usize]),
+        case("DEC $10", &[0xc6, 0x10], |m, _| m.mem.mem[ZPG as usize]),
+        case("AND #", &[0x29, 0xc1], |m, _| m.cpu.ac),
+        case("ORA #", &[0x09, 0x00], |m, _| m.cpu.ac),
+        case("EOR #", &[0x49, 0x80], |m, _| m.cpu.ac),
+        case("ADC #", &[0x69, 0x01], |m, _| m.cpu.ac),
+        case("SBC #", &[0xe9, 0x01], |m, _| m.cpu.ac),
+        case("CMP #", &[0xc9, 0x40], |_, v| v.wrapping_sub(0x40)),
+        case("CPX #", &[0xe0, 0x80], |_, v| v.wrapping_sub(0x80)),
+        case("CPY #", &[0xc0, 0x01], |_, v| v.wrapping_sub(0x01)),
+        case("ASL A", &[0x0a], |m, _| m.cpu.ac),
+        case("LSR A", &[0x4a], |m, _| m.cpu.ac),
+        case("ROL $10", &[0x26, 0x10], |m, _| m.mem.mem[ZPG as usize]),
+        case("ROR A", &[0x6a], |m, _| m.cpu.ac),
+        case("PLA", &[0x68], |m, _| m.cpu.ac),
+    ]
+}
+
+// a machine with v everywhere the case might take it from, the case's
+// instruction at CODE and then the next bytes, with I clear
+fn machine(variant: CpuVariant, case: &Case, v: u8, next: &[u8]) -> Machine {
+    let mut bytes = case.bytes.clone();
+    if bytes[0] == LDA_IMM {
+        bytes[1] = v;
+    }
+    bytes.extend(next);
+    let mut machine = Machine::new(variant, &bytes);
+    machine.cpu.ac = v;
+    machine.cpu.xr = v;
+    machine.cpu.yr = v;
+    machine.cpu.sp = 0xfd;
+    machine.mem.mem[0x01fe] = v;
+    machine.mem.mem[ZPG as usize] = v;
+    machine.cpu.set_status(U);
+    machine
+}
+
+// every case, value, cpu and dispatch, with the case's instruction run
+// and the next bytes after it; check is given the machine, what N and Z
+// should be, and a name for messages
+fn each_case(next: &[u8], check: impl Fn(&mut Machine, Dispatch, u8, &str)) {
+    for case in cases() {
+        for v in VALUES {
+            for variant in VARIANTS {
+                for dispatch in DISPATCHES {
+                    let mut machine = machine(variant, &case, v, next);
+                    step(&mut machine, dispatch);
+                    let expected = nz((case.result)(&machine, v));
+                    let name = format!("{} with ${:02x} on {:?} by {:?}", case.name, v, variant, dispatch);
+                    check(&mut machine, dispatch, expected, &name);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn php_pushes_flags_from_the_result() {
+    each_case(&[PHP], |machine, dispatch, expected, name| {
+        step(machine, dispatch);
+        assert_eq!(machine.pushed() & (N | Z), expected, "{}", name);
+    });
+}
+
+#[test]
+fn brk_pushes_flags_from_the_result() {
+    each_case(&[BRK, 0x00], |machine, dispatch, expected, name| {
+        step(machine, dispatch);
+        assert_eq!(machine.cpu.pc, HANDLER, "{}", name);
+        assert_eq!(machine.pushed() & (N | Z), expected, "{}", name);
+    });
+}
+
+#[test]
+fn irq_and_nmi_push_flags_from_the_result() {
+    each_case(&[], |machine, _, expected, name| {
+        assert!(irq(&mut machine.cpu, &mut machine.mem), "{}", name);
+        assert_eq!(machine.pushed() & (N | Z), expected, "{} irq", name);
+    });
+    each_case(&[], |machine, _, expected, name| {
+        nmi(&mut machine.cpu, &mut machine.mem);
+        assert_eq!(machine.pushed() & (N | Z), expected, "{} nmi", name);
+    });
+}
+
+// whether a branch is taken with the flags
+fn taken(branch: u8, flags: u8) -> bool {
+    match branch {
+        BEQ => flags & Z != 0,
+        BNE => flags & Z == 0,
+        BMI => flags & N != 0,
+        _ => flags & N == 0,
+    }
+}
+
+#[test]
+fn branches_are_taken_on_the_result() {
+    for branch in [BEQ, BNE, BMI, BPL] {
+        each_case(&[branch, 0x10], |machine, dispatch, expected, name| {
+            let after = machine.cpu.pc + 2;
+            step(machine, dispatch);
+            let target = if taken(branch, expected) { after + 0x10 } else { after };
+            assert_eq!(machine.cpu.pc, target, "${:02x} after {}", branch, name);
+        });
+    }
+}
+
+// N and V from the byte, Z from it anded with A, whatever result was
+// pending from the LDX before
+#[test]
+fn bit_sets_flags_apart_from_the_result() {
+    for variant in VARIANTS {
+        for dispatch in DISPATCHES {
+            for pending in VALUES {
+                for a in VALUES {
+                    for m in VALUES {
+                        let mut machine = Machine::new(variant, &[LDX_IMM, pending, BIT_ZPG, ZPG as u8, PHP]);
+                        machine.cpu.ac = a;
+                        machine.mem.mem[ZPG as usize] = m;
+                        for _ in 0..3 {
+                            step(&mut machine, dispatch);
+                        }
+                        let expected = (m & (N | V)) | if a & m == 0 { Z } else { 0 };
+                        let name = format!("A=${:02x} M=${:02x} after ${:02x} on {:?} by {:?}", a, m, pending, variant, dispatch);
+                        assert_eq!(machine.cpu.status() & (N | V | Z), expected, "{}", name);
+                        assert_eq!(machine.pushed() & (N | V | Z), expected, "{} pushed", name);
+                    }
+                }
+            }
+        }
+    }
+}
+
+// the 65c02's BIT # sets only Z, so N stays as the pending result had it
+#[test]
+fn bit_immediate_leaves_n_from_the_result() {
+    for dispatch in DISPATCHES {
+        for pending in VALUES {
+            for a in VALUES {
+                for m in VALUES {
+                    let mut machine = Machine::new(CpuVariant::Cmos65c02, &[LDX_IMM, pending, BIT_IMM, m, PHP]);
+                    machine.cpu.ac = a;
+                    for _ in 0..3 {
+                        step(&mut machine, dispatch);
+                    }
+                    let expected = (pending & N) | if a & m == 0 { Z } else { 0 };
+                    let name = format!("A=${:02x} #${:02x} after ${:02x} by {:?}", a, m, pending, dispatch);
+                    assert_eq!(machine.cpu.status() & (N | Z), expected, "{}", name);
+                    assert_eq!(machine.pushed() & (N | Z), expected, "{} pushed", name);
+                }
+            }
+        }
+    }
+}
+
+// a result pending from LDA is dropped for the status pulled
+#[test]
+fn plp_and_rti_replace_the_result() {
+    for variant in VARIANTS {
+        for dispatch in DISPATCHES {
+            for v in VALUES {
+                for pulled in [0x00, Z, N, N | Z] {
+                    let mut machine = Machine::new(variant, &[LDA_IMM, v, PLP, PHP]);
+                    machine.cpu.sp = 0xfe;
+                    machine.mem.mem[0x01ff] = pulled;
+                    for _ in 0..3 {
+                        step(&mut machine, dispatch);
+                    }
+                    let name = format!("${:02x} pulled over ${:02x} on {:?} by {:?}", pulled, v, variant, dispatch);
+                    assert_eq!(machine.cpu.status() & (N | Z), pulled, "{} by PLP", name);
+                    assert_eq!(machine.pushed() & (N | Z), pulled, "{} by PLP, pushed", name);
+
+                    let mut machine = Machine::new(variant, &[LDA_IMM, v, RTI]);
+                    machine.cpu.sp = 0xfc;
+                    machine.mem.mem[0x01fd] = pulled;
+                    machine.mem.mem[0x01fe] = HANDLER as u8;
+                    machine.mem.mem[0x01ff] = (HANDLER >> 8) as u8;
+                    machine.mem.mem[HANDLER as usize] = PHP;
+                    for _ in 0..3 {
+                        step(&mut machine, dispatch);
+                    }
+                    assert_eq!(machine.cpu.pc, HANDLER + 1, "{} by RTI", name);
+                    assert_eq!(machine.cpu.status() & (N | Z), pulled, "{} by RTI", name);
+                    assert_eq!(machine.pushed() & (N | Z), pulled, "{} by RTI, pushed", name);
+                }
+            }
+        }
+    }
+}
+
+// the two dispatches leave the same registers and status after each case
+#[test]
+fn table_and_match_agree() {
+    for case in cases() {
+        for v in VALUES {
+            for variant in VARIANTS {
+                let [table, matched] = DISPATCHES.map(|dispatch| {
+                    let mut machine = machine(variant, &case, v, &[PHP]);
+                    step(&mut machine, dispatch);
+                    step(&mut machine, dispatch);
+                    let cpu = &machine.cpu;
+                    (cpu.pc, cpu.ac, cpu.xr, cpu.yr, cpu.sp, cpu.status(), machine.pushed(), machine.mem.mem[ZPG as usize])
+                });
+                assert_eq!(table, matched, "{} with ${:02x} on {:?}", case.name, v, variant);
+            }
+        }
+    }
+}
@@ -0,0 +1,96 @@
+// machines run across threads by parallel::run_machines, against the
+// same machines run one after another, with each machine's interrupts
+// pulled from outside at times of its own
+//
+//   cargo test --test parallel
+
+use std::thread;
+use std::time::Duration;
+
+use e6502r::cpu::{reset_cpu, Cpu, CpuVariant};
+use e6502r::memory::Memory;
+use e6502r::parallel::run_machines;
+use e6502r::run::{run_for, StopConditions};
+
+const MACHINES: usize = 24;
+
+// a loop adding the machine's number into a counter at $10, for a
+// number of passes that grows with it, so the jobs are of uneven length
+//   ldx #passes / loop: clc / lda $10 / adc #index / sta $10 / dex / bne loop / stp
+fn job(index: usize, cpu: &mut Cpu, mem: &mut Memory) -> (u16, u8, u64) {
+    let program = [0xa2, (index * 10) as u8 + 1, 0x18, 0xa5, 0x10, 0x69, index as u8,
+                   0x85, 0x10, 0xca, 0xd0, 0xf6, 0xdb];
+    mem.mem[0x0200..0x0200 + program.len()].copy_from_slice(&program);
+    mem.mem[0xfffc] = 0x00;
+    mem.mem[0xfffd] = 0x02;
+    cpu.variant = CpuVariant::Cmos65c02;
+    reset_cpu(cpu, mem);
+    run_for(cpu, mem, &StopConditions::default(), 100_000);
+    (cpu.pc, mem.mem[0x10], cpu.cycles)
+}
+
+fn sequential() -> Vec<(u16, u8, u64)> {
+    (0..MACHINES)
+        .map(|index| {
+            let mut cpu = Cpu::new();
+            let mut mem = Memory::new();
+            job(index, &mut cpu, &mut mem)
+        })
+        .collect()
+}
+
+#[test]
+fn threads_give_the_sequential_results() {
+    let expected = sequential();
+    for threads in [1, 2, 4, 7] {
+        assert_eq!(run_machines(MACHINES, threads, job), expected, "{} threads", threads);
+    }
+}
+
+// a loop counting in x, interrupted from outside every so many cycles,
+// a different number for each machine, with the handler counting the
+// interrupts at $11
+//   $0200: cli / loop: inx / jmp loop
+//   $0300: inc $11 / rti
+fn interrupted_job(index: usize, cpu: &mut Cpu, mem: &mut Memory) -> (u16, u8, u8, u64) {
+    mem.mem[0x0200..0x0205].copy_from_slice(&[0x58, 0xe8, 0x4c, 0x01, 0x02]);
+    mem.mem[0x0300..0x0303].copy_from_slice(&[0xe6, 0x11, 0x40]);
+    mem.mem[0xfffc..=0xffff].copy_from_slice(&[0x00, 0x02, 0x00, 0x03]);
+    reset_cpu(cpu, mem);
+    let stops = StopConditions::default();
+    let every = 50 + 13 * index as i64;
+    let mut leftover = 0;
+    for _ in 0..200 {
+        mem.inputs.pulse_irq();
+        leftover = run_for(cpu, mem, &stops, every + leftover).leftover;
+    }
+    (cpu.pc, cpu.xr, mem.mem[0x11], cpu.cycles)
+}
+
+#[test]
+fn machines_take_only_their_own_interrupts() {
+    let expected: Vec<_> = (0..MACHINES)
+        .map(|index| interrupted_job(index, &mut Cpu::new(), &mut Memory::new()))
+        .collect();
+    // every machine took all its interrupts, each at its own pace
+    assert!(expected.iter().all(|&(_, _, taken, _)| taken == 200));
+    assert!(expected.windows(2).all(|pair| pair[0].3 < pair[1].3));
+    for threads in [2, 4, 7] {
+        assert_eq!(run_machines(MACHINES, threads, interrupted_job), expected, "{} threads", threads);
+    }
+}
+
+#[test]
+fn results_come_back_in_job_order() {
+    // the first jobs finish last
+    let results = run_machines(8, 4, |index, _, _| {
+        thread::sleep(Duration::from_millis(5 * (8 - index as u64)));
+        index
+    });
+    assert_eq!(results, (0..8).collect::<Vec<usize>>());
+}
+
+#[test]
+fn no_machines_is_no_results() {
+    assert!(run_machines(0, 4, job).is_empty());
+}
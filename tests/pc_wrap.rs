@@ -0,0 +1,102 @@
+// code straddling the top of memory: the pc and operand fetches wrap
+// from $ffff to $0000, as the hardware's do
+//
+//   cargo test --test pc_wrap
+
+mod common;
+
+use common::{Machine, VARIANTS};
+use e6502r::cpu::CpuVariant;
+
+const JSR: u8 = 0x20;
+const JMP: u8 = 0x4c;
+const RTS: u8 = 0x60;
+const BNE: u8 = 0xd0;
+const LDA_IMM: u8 = 0xa9;
+const LDA_ABS: u8 = 0xad;
+const NOP: u8 = 0xea;
+const BBR0: u8 = 0x0f;
+
+#[test]
+fn one_byte_instruction_at_ffff() {
+    for variant in VARIANTS {
+        let mut machine = Machine::at(variant, 0xffff, &[NOP]);
+        machine.step();
+        assert_eq!(machine.cpu.pc, 0x0000, "{:?}", variant);
+    }
+}
+
+#[test]
+fn immediate_operand_at_0000() {
+    for variant in VARIANTS {
+        let mut machine = Machine::at(variant, 0xffff, &[LDA_IMM, 0x42]);
+        machine.step();
+        assert_eq!(machine.cpu.ac, 0x42, "{:?}", variant);
+        assert_eq!(machine.cpu.pc, 0x0001, "{:?}", variant);
+    }
+}
+
+#[test]
+fn absolute_operand_across_the_top() {
+    for variant in VARIANTS {
+        // the low byte of the address at $ffff, the high at $0000
+        let mut machine = Machine::at(variant, 0xfffe, &[LDA_ABS, 0x34, 0x12]);
+        machine.mem.mem[0x1234] = 0x99;
+        machine.step();
+        assert_eq!(machine.cpu.ac, 0x99, "{:?}", variant);
+        assert_eq!(machine.cpu.pc, 0x0001, "{:?}", variant);
+    }
+}
+
+#[test]
+fn jump_across_the_top() {
+    for variant in VARIANTS {
+        let mut machine = Machine::at(variant, 0xffff, &[JMP, 0x00, 0x30]);
+        machine.step();
+        assert_eq!(machine.cpu.pc, 0x3000, "{:?}", variant);
+    }
+}
+
+#[test]
+fn branch_across_the_top() {
+    for variant in VARIANTS {
+        // forwards from the instruction after, at $0000, and back again
+        let mut machine = Machine::at(variant, 0xfffe, &[BNE, 0x10]);
+        machine.cpu.set_status(0x00);
+        machine.step();
+        assert_eq!(machine.cpu.pc, 0x0010, "{:?}", variant);
+        let mut machine = Machine::at(variant, 0x0002, &[BNE, 0xf0]);
+        machine.cpu.set_status(0x00);
+        machine.step();
+        assert_eq!(machine.cpu.pc, 0xfff4, "{:?}", variant);
+    }
+}
+
+#[test]
+fn bbr_across_the_top() {
+    let mut machine = Machine::at(CpuVariant::Cmos65c02, 0xfffe, &[BBR0, 0x10, 0x04]);
+    machine.step();
+    assert_eq!(machine.cpu.pc, 0x0005);
+}
+
+#[test]
+fn call_and_return_across_the_top() {
+    for variant in VARIANTS {
+        // the return address pushed is that of the call's last byte, $0000
+        let mut machine = Machine::at(variant, 0xfffe, &[JSR, 0x00, 0x30]);
+        machine.mem.mem[0x3000] = RTS;
+        machine.step();
+        assert_eq!(machine.cpu.pc, 0x3000, "{:?}", variant);
+        assert_eq!(machine.mem.mem[0x01ff], 0x00, "{:?}", variant);
+        assert_eq!(machine.mem.mem[0x01fe], 0x00, "{:?}", variant);
+        machine.step();
+        assert_eq!(machine.cpu.pc, 0x0001, "{:?}", variant);
+
+        // and one pushed as $ffff returns to $0000
+        let mut machine = Machine::at(variant, 0xfffd, &[JSR, 0x00, 0x30]);
+        machine.mem.mem[0x3000] = RTS;
+        machine.step();
+        machine.step();
+        assert_eq!(machine.cpu.pc, 0x0000, "{:?}", variant);
+    }
+}
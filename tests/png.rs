@@ -0,0 +1,73 @@
+// screenshots are written as pngs with stored, uncompressed image data;
+// reading the chunks back checks the lengths, crcs and the pixels
+//
+//   cargo test --test png
+
+use e6502r::memory::Picture;
+use e6502r::png;
+
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xedb8_8320 & (crc & 1).wrapping_neg());
+        }
+    }
+    !crc
+}
+
+// the chunks of a png file as (type, data), checking each crc
+fn chunks(file: &[u8]) -> Vec<(String, Vec<u8>)> {
+    assert_eq!(&file[..8], b"\x89PNG\r\n\x1a\n");
+    let mut chunks = Vec::new();
+    let mut at = 8;
+    while at < file.len() {
+        let len = u32::from_be_bytes(file[at..at + 4].try_into().unwrap()) as usize;
+        let body = &file[at + 4..at + 8 + len];
+        let crc = u32::from_be_bytes(file[at + 8 + len..at + 12 + len].try_into().unwrap());
+        assert_eq!(crc, crc32(body));
+        chunks.push((String::from_utf8_lossy(&body[..4]).into_owned(), body[4..].to_vec()));
+        at += 12 + len;
+    }
+    chunks
+}
+
+// the bytes in a zlib stream of stored blocks
+fn unstore(stream: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut at = 2;
+    loop {
+        let last = stream[at] & 1 != 0;
+        let len = u16::from_le_bytes([stream[at + 1], stream[at + 2]]) as usize;
+        assert_eq!(!len as u16, u16::from_le_bytes([stream[at + 3], stream[at + 4]]));
+        out.extend(&stream[at + 5..at + 5 + len]);
+        at += 5 + len;
+        if last {
+            return out;
+        }
+    }
+}
+
+#[test]
+fn small_picture() {
+    let picture = Picture { width: 2, height: 2, pixels: vec![0xff0000, 0x00ff00, 0x0000ff, 0xffffff] };
+    let chunks = chunks(&png::encode(&picture));
+    let kinds: Vec<&str> = chunks.iter().map(|(kind, _)| kind.as_str()).collect();
+    assert_eq!(kinds, ["IHDR", "IDAT", "IEND"]);
+    assert_eq!(chunks[0].1, [0, 0, 0, 2, 0, 0, 0, 2, 8, 2, 0, 0, 0]);
+    assert_eq!(unstore(&chunks[1].1), [0, 255, 0, 0, 0, 255, 0, 0, 0, 0, 255, 255, 255, 255]);
+}
+
+#[test]
+fn picture_over_one_block() {
+    // 320x240 rgb takes several stored blocks
+    let picture = Picture { width: 320, height: 240, pixels: (0..320 * 240).collect() };
+    let chunks = chunks(&png::encode(&picture));
+    let raw = unstore(&chunks[1].1);
+    assert_eq!(raw.len(), (320 * 3 + 1) * 240);
+    let row = 100 * (320 * 3 + 1);
+    let pixel = 100 * 320 + 7;
+    assert_eq!(raw[row], 0);
+    assert_eq!(raw[row + 1 + 7 * 3..row + 1 + 8 * 3], [(pixel >> 16) as u8, (pixel >> 8) as u8, pixel as u8]);
+}
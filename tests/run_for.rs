@@ -0,0 +1,113 @@
+// run_for runs a budget of cycles and hands back what's left, so blocks
+// keep to their length, counts only the instructions that ran, and gives
+// up on an instruction that takes no time rather than running it
+// forever. run_frame runs frames at a clock on it
+//
+//   cargo test --test run_for
+
+mod common;
+
+use common::{Machine, CODE};
+use e6502r::cpu::CpuVariant;
+use e6502r::memory::Device;
+use e6502r::run::{run_for, run_frame, FrameClock, StopConditions, StopReason};
+
+const BRK: u8 = 0x00;
+const INX: u8 = 0xe8;
+const JMP_ABS: u8 = 0x4c;
+const NMOS_JAM: u8 = 0x02;
+
+// a device that could interrupt, but never does
+struct Quiet;
+
+impl Device for Quiet {
+    fn read(&mut self, _offset: u16) -> u8 {
+        0
+    }
+
+    fn write(&mut self, _offset: u16, _value: u8) {}
+
+    fn raises_irqs(&self) -> bool {
+        true
+    }
+}
+
+#[test]
+fn leftover_keeps_blocks_to_their_length() {
+    // INX and JMP take 2 and 3 cycles, so blocks of 1000 end mid-loop
+    let mut machine = Machine::new(CpuVariant::Nmos6502, &[INX, JMP_ABS, CODE as u8, (CODE >> 8) as u8]);
+    let stops = StopConditions::default();
+    let mut leftover = 0;
+    for block in 1..=10 {
+        let run = run_for(&mut machine.cpu, &mut machine.mem, &stops, 1001 + leftover);
+        assert!(run.stop.is_none());
+        assert!((-2..=0).contains(&run.leftover), "{} left", run.leftover);
+        leftover = run.leftover;
+        assert_eq!(machine.cpu.cycles as i64, block * 1001 - leftover);
+    }
+}
+
+// an unimplemented opcode runs as nothing without strict_opcodes, and
+// with interrupts enabled and something to raise one that isn't a loop
+// to itself, but no device can move on while no cycles pass
+#[test]
+fn an_instruction_taking_no_cycles_stops_the_block() {
+    let mut machine = Machine::new(CpuVariant::Nmos6502, &[NMOS_JAM]);
+    machine.mem.attach(0xd000, 1, Box::new(Quiet)).unwrap();
+    machine.cpu.set_status(0x20);
+    let run = run_for(&mut machine.cpu, &mut machine.mem, &StopConditions::default(), 1000);
+    assert!(matches!(run.stop, Some(StopReason::SelfLoop(CODE))));
+    assert_eq!(run.leftover, 1000);
+    assert_eq!(run.executed, 0);
+}
+
+// instructions that stop the block before running aren't counted; the
+// one that reaches a stop address ran, and is
+#[test]
+fn only_instructions_that_ran_are_counted() {
+    let mut machine = Machine::new(CpuVariant::Nmos6502, &[INX, INX, BRK, 0x42]);
+    let stops = StopConditions { trap_on_brk: true, ..StopConditions::default() };
+    let run = run_for(&mut machine.cpu, &mut machine.mem, &stops, 1000);
+    assert!(matches!(run.stop, Some(StopReason::Break(0x0202, 0x42))));
+    assert_eq!(run.executed, 2);
+
+    let mut machine = Machine::new(CpuVariant::Nmos6502, &[INX, NMOS_JAM]);
+    let stops = StopConditions { strict_opcodes: true, ..StopConditions::default() };
+    let run = run_for(&mut machine.cpu, &mut machine.mem, &stops, 1000);
+    assert!(matches!(run.stop, Some(StopReason::Unimplemented(0x0201, NMOS_JAM))));
+    assert_eq!(run.executed, 1);
+
+    let mut machine = Machine::new(CpuVariant::Nmos6502, &[INX, INX, INX]);
+    let stops = StopConditions { stop_at: vec![0x0202], ..StopConditions::default() };
+    let run = run_for(&mut machine.cpu, &mut machine.mem, &stops, 1000);
+    assert!(matches!(run.stop, Some(StopReason::StopAddress(0x0202))));
+    assert_eq!(run.executed, 2);
+}
+
+// an ntsc nes's 29780.5 cycles a frame: each frame ends 29780 or 29781
+// cycles after the last, and the leftover is carried, so n frames end
+// within an instruction of n times that
+#[test]
+fn frames_end_a_frame_after_the_last() {
+    let mut machine = Machine::new(CpuVariant::Ricoh2a03, &[INX, JMP_ABS, CODE as u8, (CODE >> 8) as u8]);
+    let mut clock = FrameClock::new(1789773.0, 60.0988, 0).unwrap();
+    let mut end = 0;
+    for frame in 1..=100 {
+        let next = clock.next_end();
+        assert!([29780, 29781].contains(&(next - end)), "frame {} takes {}", frame, next - end);
+        end = next;
+    }
+    clock.restart(0);
+    for frame in 1..=100u64 {
+        run_frame(&mut machine.cpu, &mut machine.mem, &StopConditions::default(), &mut clock);
+        let expected = (frame as f64 * clock.frame_cycles()).round() as u64;
+        assert!((expected..expected + 3).contains(&machine.cpu.cycles), "frame {} at {}", frame, machine.cpu.cycles);
+    }
+}
+
+#[test]
+fn bad_clocks_are_refused() {
+    for (clock_hz, frame_rate) in [(0.0, 60.0), (1e6, 0.0), (f64::NAN, 60.0), (1e6, f64::INFINITY)] {
+        assert!(FrameClock::new(clock_hz, frame_rate, 0).is_err());
+    }
+}
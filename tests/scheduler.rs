@@ -0,0 +1,174 @@
+// devices that say when they next need ticking are only ticked then, or
+// when they're read or written, and come out at the same count as a
+// device ticked after every instruction. the via and acia are scheduled
+// so, and interrupt on the same cycle as if ticked every cycle
+//
+//   cargo test --test scheduler
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use e6502r::devices::{attach_device, DeviceConfig};
+use e6502r::memory::{Device, Memory};
+use e6502r::toml::Table;
+
+// counts the cycles it's given, and the ticks they came in; when
+// scheduled it asks to run every period cycles
+struct Counter {
+    period: Option<u64>,
+    seen: Rc<RefCell<(u64, u32)>>,
+}
+
+impl Device for Counter {
+    fn read(&mut self, _offset: u16) -> u8 {
+        self.seen.borrow().0 as u8
+    }
+
+    fn write(&mut self, _offset: u16, _value: u8) {}
+
+    fn tick(&mut self, cycles: u32) {
+        let mut seen = self.seen.borrow_mut();
+        seen.0 += cycles as u64;
+        seen.1 += 1;
+    }
+
+    fn next_event(&self) -> Option<u64> {
+        self.period
+    }
+}
+
+fn attach(mem: &mut Memory, base: u16, period: Option<u64>) -> Rc<RefCell<(u64, u32)>> {
+    let seen = Rc::new(RefCell::new((0, 0)));
+    mem.attach(base, 1, Box::new(Counter { period, seen: seen.clone() })).unwrap();
+    seen
+}
+
+#[test]
+fn ticked_only_when_due() {
+    let mut mem = Memory::new();
+    let every = attach(&mut mem, 0xd000, None);
+    let scheduled = attach(&mut mem, 0xd100, Some(100));
+    for cycles in (4..100).step_by(4) {
+        mem.tick(cycles);
+    }
+    assert_eq!(*every.borrow(), (96, 24));
+    assert_eq!(*scheduled.borrow(), (0, 0));
+
+    mem.tick(102);
+    assert_eq!(*scheduled.borrow(), (102, 1));
+    mem.tick(150);
+    assert_eq!(*scheduled.borrow(), (102, 1));
+    mem.tick(202);
+    assert_eq!(*scheduled.borrow(), (202, 2));
+}
+
+#[test]
+fn caught_up_before_access() {
+    let mut mem = Memory::new();
+    let scheduled = attach(&mut mem, 0xd100, Some(1000));
+    mem.tick(37);
+    assert_eq!(mem.read(0xd100), 37);
+    mem.tick(50);
+    mem.write(0xd100, 0);
+    assert_eq!(*scheduled.borrow(), (50, 2));
+
+    // the next event is counted from the access
+    mem.tick(1040);
+    assert_eq!(scheduled.borrow().0, 50);
+    mem.tick(1050);
+    assert_eq!(scheduled.borrow().0, 1050);
+}
+
+#[test]
+fn idle_device_waits() {
+    let mut mem = Memory::new();
+    let idle = attach(&mut mem, 0xd100, Some(u64::MAX));
+    mem.tick(1 << 40);
+    assert_eq!(*idle.borrow(), (0, 0));
+    mem.catch_up();
+    assert_eq!(idle.borrow().0, 1 << 40);
+}
+
+const IO: u16 = 0xd000;
+
+fn memory_with(kind: &str) -> Memory {
+    let mut mem = Memory::new();
+    let config = DeviceConfig { kind: String::from(kind), base: IO, options: Table::new() };
+    attach_device(&mut mem, &config, 1e6).unwrap();
+    mem
+}
+
+// via registers
+const ORB: u16 = IO;
+const T1C_L: u16 = IO + 0x4;
+const T1C_H: u16 = IO + 0x5;
+const T1L_L: u16 = IO + 0x6;
+const T1L_H: u16 = IO + 0x7;
+const T2C_L: u16 = IO + 0x8;
+const T2C_H: u16 = IO + 0x9;
+const SR: u16 = IO + 0xa;
+const ACR: u16 = IO + 0xb;
+const IFR: u16 = IO + 0xd;
+const IER: u16 = IO + 0xe;
+
+// the registers reading leaves alone, bar the port's handshake flags
+fn via_registers(mem: &mut Memory) -> Vec<u8> {
+    [ORB, T1C_H, T1L_L, T1L_H, T2C_H, ACR, IFR, IER].iter().map(|&addr| mem.read(addr)).collect()
+}
+
+// random writes to the timers and shift register with random gaps
+// between, run on a via read every cycle and on one left to its events
+#[test]
+fn via_fires_as_when_ticked_every_cycle() {
+    let mut seed: u32 = 0x6522;
+    let mut next = move |limit: u32| {
+        seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+        (seed >> 8) % limit
+    };
+    let mut every = memory_with("via");
+    let mut scheduled = memory_with("via");
+    let mut cycles = 0;
+    for _ in 0..2000 {
+        let (addr, value) = match next(8) {
+            0 => (ACR, [0x00, 0x40, 0xc0, 0x04, 0x08, 0x10, 0x14, 0x18, 0x80][next(9) as usize]),
+            1 => (T1C_L, next(0x40) as u8),
+            2 => (T1C_H, next(2) as u8),
+            3 => (T2C_L, next(0x40) as u8),
+            4 => (T2C_H, next(2) as u8),
+            5 => (SR, next(0x100) as u8),
+            6 => (IER, 0xff),
+            _ => (IFR, 0x7f),
+        };
+        every.write(addr, value);
+        scheduled.write(addr, value);
+        for _ in 0..next(700) {
+            cycles += 1;
+            every.tick(cycles);
+            every.read(IFR);
+            scheduled.tick(cycles);
+            assert_eq!(scheduled.irq(), every.irq(), "irq at cycle {}", cycles);
+        }
+        assert_eq!(via_registers(&mut scheduled), via_registers(&mut every), "registers at cycle {}", cycles);
+    }
+}
+
+// acia registers
+const ACIA_DATA: u16 = IO;
+const ACIA_STATUS: u16 = IO + 1;
+const ACIA_COMMAND: u16 = IO + 2;
+const ACIA_CONTROL: u16 = IO + 3;
+
+// a character at 9600 baud, 8 bits and a stop bit, takes 10 bit times:
+// 1041 cycles at 1 MHz
+#[test]
+fn acia_interrupts_when_a_character_has_gone() {
+    let mut mem = memory_with("acia");
+    mem.write(ACIA_CONTROL, 0x0e);
+    mem.write(ACIA_COMMAND, 0x07);
+    mem.write(ACIA_DATA, b'A');
+    mem.tick(1040);
+    assert!(!mem.irq());
+    mem.tick(1041);
+    assert!(mem.irq());
+    assert_eq!(mem.read(ACIA_STATUS) & 0x90, 0x90);
+}
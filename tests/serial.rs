@@ -0,0 +1,103 @@
+// an acia on a tcp connection, its line endings changed by the
+// input_newline and output_newline options, read and written through
+// its registers
+//
+//   cargo test --test serial
+
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::process;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use e6502r::devices::{attach_device, DeviceConfig};
+use e6502r::memory::Memory;
+use e6502r::toml;
+
+const ACIA: u16 = 0xd000;
+const DATA: u16 = ACIA;
+const STATUS: u16 = ACIA + 1;
+const COMMAND: u16 = ACIA + 2;
+
+const STATUS_RDRF: u8 = 0x08;
+
+// the command enabling the chip with its receive interrupt off
+const ENABLED: u8 = 0x0b;
+
+fn acia(options: &str) -> Result<Memory, String> {
+    let mut mem = Memory::new();
+    let config = DeviceConfig { kind: String::from("acia"), base: ACIA, options: toml::parse(options)? };
+    attach_device(&mut mem, &config, 1e6)?;
+    Ok(mem)
+}
+
+// an acia listening on a port of its own, and a client connected to it
+fn connected(name: u16, newlines: &str) -> (Memory, TcpStream) {
+    let port = 20000 + (process::id() % 20000) as u16 + name;
+    let mut mem = acia(&format!("connect = \"tcp:{}\"\n{}", port, newlines)).unwrap();
+    mem.write(COMMAND, ENABLED);
+    let client = TcpStream::connect(("127.0.0.1", port)).unwrap();
+    client.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    (mem, client)
+}
+
+// the bytes the 6502 receives, until count have come
+fn received(mem: &mut Memory, count: usize) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    let mut cycles = 0;
+    let give_up = Instant::now() + Duration::from_secs(5);
+    while bytes.len() < count && Instant::now() < give_up {
+        cycles += 100;
+        mem.tick(cycles);
+        if mem.read(STATUS) & STATUS_RDRF != 0 {
+            bytes.push(mem.read(DATA));
+        } else {
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+    bytes
+}
+
+// the bytes the client is sent, until count have come
+fn sent(client: &mut TcpStream, count: usize) -> Vec<u8> {
+    let mut bytes = vec![0; count];
+    client.read_exact(&mut bytes).unwrap();
+    bytes
+}
+
+#[test]
+fn input_newlines_become_one_kind() {
+    let (mut mem, mut client) = connected(0, "input_newline = \"crlf\"");
+    client.write_all(b"a\nb\r\nc\rd").unwrap();
+    assert_eq!(received(&mut mem, 10), b"a\r\nb\r\nc\r\nd");
+}
+
+#[test]
+fn output_newlines_become_one_kind() {
+    let (mut mem, mut client) = connected(1, "output_newline = \"lf\"");
+    // the acia picks up the client as it looks for input
+    client.write_all(b"!").unwrap();
+    assert_eq!(received(&mut mem, 1), b"!");
+    for byte in b"x\ry\r\nz\n" {
+        mem.write(DATA, *byte);
+    }
+    assert_eq!(sent(&mut client, 6), b"x\ny\nz\n");
+}
+
+#[test]
+fn newlines_pass_through_without_the_options() {
+    let (mut mem, mut client) = connected(2, "");
+    client.write_all(b"a\r\nb\n").unwrap();
+    assert_eq!(received(&mut mem, 5), b"a\r\nb\n");
+    for byte in b"x\r\n" {
+        mem.write(DATA, *byte);
+    }
+    assert_eq!(sent(&mut client, 3), b"x\r\n");
+}
+
+#[test]
+fn unknown_newline_is_an_error() {
+    let error = acia("input_newline = \"crcr\"").err().unwrap();
+    assert!(error.contains("input_newline should be \"cr\", \"lf\" or \"crlf\", not 'crcr'"), "{}", error);
+    assert!(acia("output_newline = \"cr\"").is_ok());
+}
@@ -0,0 +1,149 @@
+// writes to code that has run, which --smc reports: memory notes them
+// while it has a map of the bytes run as code, and keeps them for the
+// host to print. and whichever dispatch is built, an opcode overwritten
+// after it ran runs as the new one, which with decode-cache means the
+// cached decode of it is replaced
+//
+//   cargo test --test smc
+//   cargo test --features decode-cache --test smc
+
+mod common;
+
+use common::{Machine, CODE};
+use e6502r::cpu::{decode, CpuVariant, DecodeCache};
+use e6502r::memory::MEMSIZE;
+use e6502r::run::{step, SmcMode, StopConditions};
+
+const STA_ABS: u8 = 0x8d;
+const PHA: u8 = 0x48;
+const JMP_ABS: u8 = 0x4c;
+const DEY: u8 = 0x88;
+const INX: u8 = 0xe8;
+
+// a machine noting writes to code, with the bytes at CODE
+fn machine(bytes: &[u8]) -> Machine {
+    let mut machine = Machine::new(CpuVariant::Nmos6502, bytes);
+    machine.mem.executed = Some(Box::new([false; MEMSIZE]));
+    machine
+}
+
+#[test]
+fn writes_to_code_that_ran_are_noted() {
+    let mut machine = machine(&[STA_ABS, 0x00, 0x03, STA_ABS, 0x10, 0x03]);
+    machine.mem.executed.as_mut().unwrap()[0x0300] = true;
+    machine.cpu.ac = 0x41;
+    machine.step();
+    machine.step();
+    let writes: Vec<(u16, u8)> = machine.mem.code_writes.iter().map(|write| (write.addr, write.value)).collect();
+    assert_eq!(writes, vec![(0x0300, 0x41)]);
+    // and the byte counts as unrun until it runs again
+    assert!(!machine.mem.executed.as_ref().unwrap()[0x0300]);
+}
+
+#[test]
+fn stack_writes_over_code_are_noted() {
+    let mut machine = machine(&[PHA]);
+    machine.mem.executed.as_mut().unwrap()[0x01ff] = true;
+    machine.step();
+    assert_eq!(machine.mem.code_writes.len(), 1);
+    assert_eq!(machine.mem.code_writes[0].addr, 0x01ff);
+}
+
+#[test]
+fn nothing_is_noted_without_the_map() {
+    let mut machine = machine(&[STA_ABS, 0x00, 0x02]);
+    machine.mem.executed = None;
+    machine.step();
+    assert!(machine.mem.code_writes.is_empty());
+}
+
+// --smc warn leaves the writes for the host to report, each with the
+// instruction that made it
+#[test]
+fn warnings_are_kept_for_the_host() {
+    let mut machine = machine(&[INX, STA_ABS, CODE as u8, (CODE >> 8) as u8]);
+    let stops = StopConditions { smc: Some(SmcMode::Warn), ..StopConditions::default() };
+    for _ in 0..2 {
+        assert!(step(&mut machine.cpu, &mut machine.mem, &stops).is_none());
+    }
+    let warnings: Vec<(u16, u16)> = machine.mem.smc_warnings.iter().map(|(pc, write)| (*pc, write.addr)).collect();
+    assert_eq!(warnings, vec![(CODE + 1, CODE)]);
+}
+
+// INX runs, is overwritten with DEY and jumped back to
+#[test]
+fn overwritten_opcodes_run_as_written() {
+    let mut machine = Machine::new(CpuVariant::Nmos6502, &[INX, STA_ABS, CODE as u8, (CODE >> 8) as u8, JMP_ABS, CODE as u8, (CODE >> 8) as u8]);
+    machine.cpu.ac = DEY;
+    let stops = StopConditions::default();
+    for _ in 0..4 {
+        assert!(step(&mut machine.cpu, &mut machine.mem, &stops).is_none());
+    }
+    assert_eq!(machine.mem.mem[CODE as usize], DEY);
+    assert_eq!((machine.cpu.xr, machine.cpu.yr), (0x01, 0xff));
+}
+
+// the decode cache is keyed by opcode, so a changed byte is decoded again
+#[test]
+fn the_decode_cache_misses_on_a_changed_opcode() {
+    let mut cache = DecodeCache::new();
+    assert_eq!(cache.fetch(CpuVariant::Nmos6502, CODE, INX), decode(CpuVariant::Nmos6502, INX));
+    assert_eq!(cache.fetch(CpuVariant::Nmos6502, CODE, INX), decode(CpuVariant::Nmos6502, INX));
+    assert_eq!(cache.fetch(CpuVariant::Nmos6502, CODE, DEY), decode(CpuVariant::Nmos6502, DEY));
+    // and other addresses keep their own entries
+    assert_eq!(cache.fetch(CpuVariant::Nmos6502, CODE + 1, INX), decode(CpuVariant::Nmos6502, INX));
+    assert_eq!(cache.fetch(CpuVariant::Nmos6502, CODE, DEY), decode(CpuVariant::Nmos6502, DEY));
+}
+
+// an opcode that decodes differently on another variant
+#[test]
+fn the_decode_cache_starts_again_for_another_variant() {
+    const PHX: u8 = 0xda;
+    let mut cache = DecodeCache::new();
+    assert_eq!(cache.fetch(CpuVariant::Nmos6502, CODE, PHX), decode(CpuVariant::Nmos6502, PHX));
+    assert_eq!(cache.fetch(CpuVariant::Cmos65c02, CODE, PHX), decode(CpuVariant::Cmos65c02, PHX));
+    assert_ne!(decode(CpuVariant::Nmos6502, PHX), decode(CpuVariant::Cmos65c02, PHX));
+}
+
+// a loop that writes ac and xr over the opcode and operand of its own
+// first instruction and jumps back to it, so with decode-cache the cached
+// entry is written over after it has run
+//   CODE: lda #$00 / sta CODE / stx CODE+1 / jmp CODE
+#[test]
+fn rewritten_code_runs_as_written() {
+    const LDA_IMM: u8 = 0xa9;
+    const LDX_IMM: u8 = 0xa2;
+    const LDY_IMM: u8 = 0xa0;
+    let mut machine = Machine::new(CpuVariant::Nmos6502, &[LDA_IMM, 0x00,
+        STA_ABS, CODE as u8, (CODE >> 8) as u8,
+        0x8e, (CODE + 1) as u8, ((CODE + 1) >> 8) as u8,   // stx
+        JMP_ABS, CODE as u8, (CODE >> 8) as u8]);
+    let stops = StopConditions::default();
+    let run = |machine: &mut Machine, count: usize| {
+        for _ in 0..count {
+            assert!(step(&mut machine.cpu, &mut machine.mem, &stops).is_none());
+        }
+    };
+    run(&mut machine, 1);
+    assert_eq!(machine.cpu.ac, 0x00);
+    // ldx #$05 over lda #$00
+    machine.cpu.ac = LDX_IMM;
+    machine.cpu.xr = 0x05;
+    run(&mut machine, 3);
+    assert_eq!(machine.cpu.pc, CODE);
+    run(&mut machine, 1);
+    assert_eq!((machine.cpu.ac, machine.cpu.xr), (LDX_IMM, 0x05));
+    // ldy #$07 over ldx #$05
+    machine.cpu.ac = LDY_IMM;
+    machine.cpu.xr = 0x07;
+    run(&mut machine, 3);
+    run(&mut machine, 1);
+    assert_eq!((machine.cpu.xr, machine.cpu.yr), (0x07, 0x07));
+    // and back to lda, with a new operand
+    machine.cpu.ac = LDA_IMM;
+    machine.cpu.xr = 0x42;
+    run(&mut machine, 3);
+    run(&mut machine, 1);
+    assert_eq!(machine.cpu.ac, 0x42);
+    assert_eq!(&machine.mem.mem[CODE as usize..CODE as usize + 2], &[LDA_IMM, 0x42]);
+}
@@ -0,0 +1,116 @@
+// the stack pointer wrapping round page one, as the hardware's does:
+// a push with SP at $00 writes $0100 and leaves SP at $ff, and a pull
+// with SP at $ff reads $0100 and leaves it at $00
+//
+//   cargo test --test stack
+
+mod common;
+
+use common::{Machine, CODE, HANDLER, VARIANTS};
+use e6502r::cpu::CpuVariant;
+
+const BRK: u8 = 0x00;
+const PHP: u8 = 0x08;
+const JSR: u8 = 0x20;
+const PLP: u8 = 0x28;
+const RTI: u8 = 0x40;
+const PHA: u8 = 0x48;
+const RTS: u8 = 0x60;
+const PLA: u8 = 0x68;
+
+// a machine with SP at sp
+fn stack_at(variant: CpuVariant, sp: u8) -> Machine {
+    let mut machine = Machine::new(variant, &[]);
+    machine.cpu.sp = sp;
+    machine
+}
+
+#[test]
+fn pushes_wrap_from_00_to_ff() {
+    for variant in VARIANTS {
+        let mut machine = stack_at(variant, 0xff);
+        // round the page once and part way again
+        for n in 0..300u16 {
+            machine.cpu.pc = CODE;
+            machine.cpu.ac = n as u8;
+            machine.run(&[PHA]);
+            assert_eq!(machine.cpu.sp, 0xffu8.wrapping_sub((n + 1) as u8), "{:?} after {} pushes", variant, n + 1);
+        }
+        // the last 256 pushes are what page one holds
+        for n in 44..300u16 {
+            let addr = 0x0100 + 0xffu8.wrapping_sub(n as u8) as usize;
+            assert_eq!(machine.mem.mem[addr], n as u8, "{:?} ${:04x}", variant, addr);
+        }
+        assert_eq!(machine.mem.mem[0x00ff], 0, "{:?} pushed below page one", variant);
+        assert_eq!(machine.mem.mem[0x0200], PHA, "{:?} pushed above page one", variant);
+    }
+}
+
+#[test]
+fn pulls_wrap_from_ff_to_00() {
+    for variant in VARIANTS {
+        let mut machine = stack_at(variant, 0xfd);
+        for n in 0..256usize {
+            machine.mem.mem[0x0100 + n] = n as u8 ^ 0x5a;
+        }
+        for n in 0..300usize {
+            machine.cpu.pc = CODE;
+            machine.run(&[PLA]);
+            let sp = (0xfd + n + 1) as u8;
+            assert_eq!(machine.cpu.sp, sp, "{:?} after {} pulls", variant, n + 1);
+            assert_eq!(machine.cpu.ac, sp ^ 0x5a, "{:?} pull {}", variant, n + 1);
+        }
+    }
+}
+
+#[test]
+fn push_and_pull_across_the_boundary() {
+    for variant in VARIANTS {
+        let mut machine = stack_at(variant, 0x00);
+        machine.cpu.set_status(0xc3);
+        machine.run(&[PHP]);
+        assert_eq!(machine.cpu.sp, 0xff);
+        assert_eq!(machine.mem.mem[0x0100] & 0xc3, 0xc3, "{:?}", variant);
+        machine.cpu.set_status(0x00);
+        machine.cpu.pc = CODE;
+        machine.run(&[PLP]);
+        assert_eq!(machine.cpu.sp, 0x00);
+        assert_eq!(machine.cpu.status() & 0xc3, 0xc3, "{:?}", variant);
+    }
+}
+
+#[test]
+fn jsr_and_rts_across_the_boundary() {
+    for variant in VARIANTS {
+        // the return address's high byte goes to $0100, its low to $01ff
+        let mut machine = stack_at(variant, 0x00);
+        machine.mem.mem[HANDLER as usize] = RTS;
+        machine.run(&[JSR, HANDLER as u8, (HANDLER >> 8) as u8]);
+        assert_eq!(machine.cpu.pc, HANDLER, "{:?}", variant);
+        assert_eq!(machine.cpu.sp, 0xfe, "{:?}", variant);
+        assert_eq!(machine.mem.mem[0x0100], ((CODE + 2) >> 8) as u8, "{:?}", variant);
+        assert_eq!(machine.mem.mem[0x01ff], (CODE + 2) as u8, "{:?}", variant);
+        machine.step();
+        assert_eq!(machine.cpu.pc, CODE + 3, "{:?}", variant);
+        assert_eq!(machine.cpu.sp, 0x00, "{:?}", variant);
+    }
+}
+
+#[test]
+fn brk_and_rti_across_the_boundary() {
+    for variant in VARIANTS {
+        let mut machine = stack_at(variant, 0x01);
+        machine.mem.mem[HANDLER as usize] = RTI;
+        machine.cpu.set_status(0x01);
+        machine.run(&[BRK, 0x00]);
+        assert_eq!(machine.cpu.pc, HANDLER, "{:?}", variant);
+        assert_eq!(machine.cpu.sp, 0xfe, "{:?}", variant);
+        assert_eq!(machine.mem.mem[0x0101], ((CODE + 2) >> 8) as u8, "{:?}", variant);
+        assert_eq!(machine.mem.mem[0x0100], (CODE + 2) as u8, "{:?}", variant);
+        assert_eq!(machine.mem.mem[0x01ff] & 0x01, 0x01, "{:?}", variant);
+        machine.step();
+        assert_eq!(machine.cpu.pc, CODE + 2, "{:?}", variant);
+        assert_eq!(machine.cpu.sp, 0x01, "{:?}", variant);
+        assert_eq!(machine.cpu.status() & 0x01, 0x01, "{:?}", variant);
+    }
+}
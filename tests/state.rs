@@ -0,0 +1,141 @@
+// --save-state and --load-state: a machine with a VIA and an ACIA saved
+// partway through, with the VIA's timer interrupting and a character on
+// its way out of the ACIA, carries on from the file exactly as it would
+// have without stopping; and files cut short or damaged are refused
+//
+//   cargo test --test state
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process;
+use std::process::{Command, Output, Stdio};
+
+const VIA: &str = "via@6000";
+const ACIA: &str = "acia@5000";
+
+// the VIA's timer 1 free-running every 311 cycles, the ACIA at 19200
+// baud, and a loop sending a byte every 256 passes until it has sent 6.
+// MIDDLE is only passed once, right after the third is sent, and the
+// interrupt handler counts at $12 and keeps the ACIA's status at $13
+//   $0400: lda #$40 / sta $600b / lda #$c0 / sta $600e
+//          lda #$37 / sta $6004 / lda #$01 / sta $6005
+//          lda #$1f / sta $5003 / lda #$0b / sta $5002 / cli
+//   loop:  inc $10 / bne loop / inc $11 / lda $11 / sta $5000
+//          cmp #3 / bne next / nop
+//   next:  cmp #6 / bne loop
+//   end:   jmp end
+//   $0440: pha / lda $6004 / inc $12 / lda $5001 / sta $13 / pla / rti
+const PROGRAM: [u8; 54] = [
+    0xa9, 0x40, 0x8d, 0x0b, 0x60, 0xa9, 0xc0, 0x8d, 0x0e, 0x60,
+    0xa9, 0x37, 0x8d, 0x04, 0x60, 0xa9, 0x01, 0x8d, 0x05, 0x60,
+    0xa9, 0x1f, 0x8d, 0x03, 0x50, 0xa9, 0x0b, 0x8d, 0x02, 0x50, 0x58,
+    0xe6, 0x10, 0xd0, 0xfc, 0xe6, 0x11, 0xa5, 0x11, 0x8d, 0x00, 0x50,
+    0xc9, 0x03, 0xd0, 0x01, 0xea,
+    0xc9, 0x06, 0xd0, 0xec,
+    0x4c, 0x33, 0x04,
+];
+const HANDLER: [u8; 13] = [0x48, 0xad, 0x04, 0x60, 0xe6, 0x12, 0xad, 0x01, 0x50, 0x85, 0x13, 0x68, 0x40];
+const MIDDLE: &str = "042e";
+const END: &str = "0433";
+
+// the offset of the cpu variant in a saved state, after the magic
+// bytes, version, registers and cycle count
+const VARIANT_OFFSET: usize = 8 + 2 + 2 + 5 + 8;
+
+fn temp_file(name: &str) -> PathBuf {
+    env::temp_dir().join(format!("e6502r-state-{}-{}", process::id(), name))
+}
+
+// the program and its vectors as a whole 64K image, loaded at 0
+fn machine_image(name: &str) -> String {
+    let mut memory = vec![0u8; 0x10000];
+    memory[0x0400..0x0400 + PROGRAM.len()].copy_from_slice(&PROGRAM);
+    memory[0x0440..0x0440 + HANDLER.len()].copy_from_slice(&HANDLER);
+    memory[0xfffc..].copy_from_slice(&[0x00, 0x04, 0x40, 0x04]);
+    let path = temp_file(&format!("{}.bin", name));
+    fs::write(&path, memory).unwrap();
+    format!("{}@0", path.to_str().unwrap())
+}
+
+// the machine run until it reaches stop_at, with the devices given
+fn e6502r(image: &str, devices: &[&str], stop_at: &str, args: &[&str]) -> Output {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_e6502r"));
+    command.arg(image);
+    for device in devices {
+        command.args(["--device", device]);
+    }
+    command.args(["--run", "--stop-at", stop_at, "--exit-code-at", "12"])
+        .args(args)
+        .stdin(Stdio::null())
+        .output()
+        .unwrap()
+}
+
+// the state saved at MIDDLE
+fn middle_state(image: &str, name: &str) -> PathBuf {
+    let path = temp_file(name);
+    let output = e6502r(image, &[VIA, ACIA], MIDDLE, &["--save-state", path.to_str().unwrap()]);
+    assert!(output.status.code().is_some_and(|code| code > 0), "{:?}", output);
+    path
+}
+
+#[test]
+fn a_restored_machine_runs_on_as_the_original_does() {
+    let image = machine_image("restored");
+    let middle = middle_state(&image, "middle.state");
+    let restored = temp_file("restored.state");
+    let original = temp_file("original.state");
+    let from_file = e6502r(&image, &[VIA, ACIA], END, &["--load-state", middle.to_str().unwrap(),
+                                                        "--save-state", restored.to_str().unwrap()]);
+    let straight = e6502r(&image, &[VIA, ACIA], END, &["--save-state", original.to_str().unwrap()]);
+    // interrupts were taken both before and after the save
+    let before = e6502r(&image, &[VIA, ACIA], MIDDLE, &[]).status.code().unwrap();
+    let after = straight.status.code().unwrap();
+    assert!(before > 0 && after > before, "{} then {}", before, after);
+    assert_eq!(from_file.status.code(), Some(after), "{:?}", from_file);
+    let restored = fs::read(restored).unwrap();
+    assert!(restored == fs::read(original).unwrap(), "the restored machine ended up elsewhere");
+    assert!(restored != fs::read(middle).unwrap());
+}
+
+#[test]
+fn truncated_states_are_refused() {
+    let image = machine_image("truncated");
+    let middle = fs::read(middle_state(&image, "whole.state")).unwrap();
+    let cut = temp_file("cut.state");
+    for length in [0, 9, VARIANT_OFFSET, 0x8000, middle.len() - 1] {
+        fs::write(&cut, &middle[..length]).unwrap();
+        let output = e6502r(&image, &[VIA, ACIA], END, &["--load-state", cut.to_str().unwrap()]);
+        assert_eq!(output.status.code(), Some(1), "{} bytes", length);
+        let message = String::from_utf8_lossy(&output.stderr);
+        assert!(message.contains(cut.to_str().unwrap()), "{} bytes: {}", length, message);
+    }
+}
+
+#[test]
+fn damaged_states_are_refused() {
+    let image = machine_image("damaged");
+    let middle = fs::read(middle_state(&image, "good.state")).unwrap();
+    let damaged = temp_file("damaged.state");
+    let refused = |state: &[u8], devices: &[&str], expected: &str| {
+        fs::write(&damaged, state).unwrap();
+        let output = e6502r(&image, devices, END, &["--load-state", damaged.to_str().unwrap()]);
+        assert_eq!(output.status.code(), Some(1), "{}", expected);
+        let message = String::from_utf8_lossy(&output.stderr);
+        assert!(message.contains(expected), "{}", message);
+    };
+
+    let mut state = middle.clone();
+    state[0] ^= 0xff;
+    refused(&state, &[VIA, ACIA], "is not a saved state");
+    let mut state = middle.clone();
+    state[8] = 0xff;
+    refused(&state, &[VIA, ACIA], "is a version");
+    let mut state = middle.clone();
+    state[VARIANT_OFFSET] = 9;
+    refused(&state, &[VIA, ACIA], "unknown cpu variant 9");
+    // a good state for a machine set up otherwise
+    refused(&middle, &[VIA], "the saved state has 2 devices");
+    refused(&middle, &[ACIA, VIA], "where this machine has one at");
+}
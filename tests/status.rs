@@ -0,0 +1,147 @@
+// B and bit 5 of the status register: B is only in the copies BRK and
+// PHP push, never in the register, and bit 5 always reads as set. and D
+// on entering a handler: the 65c02 clears it, the 6502 leaves it
+//
+//   cargo test --test status
+
+mod common;
+
+use common::{Machine, CODE, HANDLER, VARIANTS};
+use e6502r::cpu::{irq, nmi, Cpu, CpuVariant};
+
+const I: u8 = 0x04;
+const D: u8 = 0x08;
+const B: u8 = 0x10;
+const U: u8 = 0x20;
+
+const BRK: u8 = 0x00;
+const PHP: u8 = 0x08;
+const PLP: u8 = 0x28;
+const RTI: u8 = 0x40;
+
+#[test]
+fn bit_5_is_always_set() {
+    let cpu = Cpu::new();
+    assert_eq!(cpu.status() & (B | U), U);
+    for status in 0..=255 {
+        let mut cpu = Cpu::new();
+        cpu.set_status(status);
+        assert_eq!(cpu.status(), (status & !B) | U, "set to ${:02x}", status);
+    }
+}
+
+#[test]
+fn brk_pushes_b_and_leaves_it_out_of_the_register() {
+    for variant in VARIANTS {
+        for status in [0x00, !B] {
+            let mut machine = Machine::new(variant, &[BRK, 0x00]);
+            machine.cpu.set_status(status);
+            machine.step();
+            assert_eq!(machine.cpu.pc, HANDLER, "{:?}", variant);
+            assert_eq!(machine.pushed() & (B | U), B | U, "{:?} from ${:02x}", variant, status);
+            assert_eq!(machine.cpu.status() & B, 0, "{:?} from ${:02x}", variant, status);
+        }
+    }
+}
+
+#[test]
+fn php_pushes_b() {
+    for variant in VARIANTS {
+        let mut machine = Machine::new(variant, &[PHP]);
+        machine.cpu.set_status(0x00);
+        machine.step();
+        assert_eq!(machine.pushed(), B | U, "{:?}", variant);
+        assert_eq!(machine.cpu.status() & B, 0, "{:?}", variant);
+    }
+}
+
+#[test]
+fn interrupts_push_b_clear() {
+    for variant in VARIANTS {
+        let mut machine = Machine::new(variant, &[]);
+        machine.cpu.set_status(!0x04);
+        assert!(irq(&mut machine.cpu, &mut machine.mem));
+        assert_eq!(machine.pushed() & (B | U), U, "{:?} irq", variant);
+
+        let mut machine = Machine::new(variant, &[]);
+        machine.cpu.set_status(0xff);
+        nmi(&mut machine.cpu, &mut machine.mem);
+        assert_eq!(machine.pushed() & (B | U), U, "{:?} nmi", variant);
+    }
+}
+
+#[test]
+fn plp_and_rti_ignore_b_and_bit_5() {
+    for variant in VARIANTS {
+        for pulled in [0x00, B, U, B | U, 0xff] {
+            let mut machine = Machine::new(variant, &[PLP]);
+            machine.cpu.sp = 0xfe;
+            machine.mem.mem[0x01ff] = pulled;
+            machine.step();
+            assert_eq!(machine.cpu.status(), (pulled & !B) | U, "{:?} PLP of ${:02x}", variant, pulled);
+
+            let mut machine = Machine::new(variant, &[RTI]);
+            machine.cpu.sp = 0xfc;
+            machine.mem.mem[0x01fd] = pulled;
+            machine.mem.mem[0x01fe] = CODE as u8;
+            machine.mem.mem[0x01ff] = (CODE >> 8) as u8;
+            machine.step();
+            assert_eq!(machine.cpu.status(), (pulled & !B) | U, "{:?} RTI of ${:02x}", variant, pulled);
+        }
+    }
+}
+
+#[test]
+fn php_then_plp_after_brk() {
+    // a handler that saves and restores the status doesn't leave B behind
+    for variant in VARIANTS {
+        let mut machine = Machine::new(variant, &[BRK, 0x00]);
+        machine.mem.mem[HANDLER as usize] = PHP;
+        machine.mem.mem[HANDLER as usize + 1] = PLP;
+        machine.cpu.set_status(0x00);
+        machine.step();
+        machine.step();
+        machine.step();
+        assert_eq!(machine.cpu.status() & B, 0, "{:?}", variant);
+    }
+}
+
+// the way into a handler, by BRK, IRQ or NMI
+fn enter(machine: &mut Machine, how: &str) {
+    match how {
+        "brk" => machine.step(),
+        "irq" => assert!(irq(&mut machine.cpu, &mut machine.mem)),
+        _ => nmi(&mut machine.cpu, &mut machine.mem),
+    }
+}
+
+#[test]
+fn decimal_mode_on_entering_handlers() {
+    for variant in VARIANTS {
+        for how in ["brk", "irq", "nmi"] {
+            let mut machine = Machine::new(variant, &[BRK, 0x00]);
+            machine.mem.mem[HANDLER as usize] = RTI;
+            machine.cpu.set_status(D);
+            enter(&mut machine, how);
+            assert_eq!(machine.cpu.pc, HANDLER, "{:?} {}", variant, how);
+            assert_eq!(machine.pushed() & D, D, "{:?} {} pushed", variant, how);
+            assert_eq!(machine.cpu.status() & I, I, "{:?} {}", variant, how);
+            let expected = if variant == CpuVariant::Cmos65c02 { 0 } else { D };
+            assert_eq!(machine.cpu.status() & D, expected, "{:?} {}", variant, how);
+            // RTI brings it back
+            machine.step();
+            assert_eq!(machine.cpu.status() & D, D, "{:?} {} after RTI", variant, how);
+        }
+    }
+}
+
+#[test]
+fn registers_display_with_their_flags() {
+    let mut cpu = Cpu::new();
+    cpu.pc = 0x0400;
+    cpu.sp = 0xff;
+    cpu.set_status(0x85);
+    assert_eq!(cpu.to_string(), "PC=$0400 A=$00 X=$00 Y=$00 SP=$ff P=$a5 [Nv-bdIzC]");
+    cpu.set_status(0xff);
+    assert!(cpu.to_string().ends_with("P=$ef [NV-bDIZC]"));
+}
@@ -0,0 +1,151 @@
+// the toml parser machine and test files are read with: strings and
+// their escapes, numbers, nested tables and arrays, and the errors
+// malformed documents give, which come back without panicking
+//
+//   cargo test --test toml
+
+use e6502r::toml::{parse, Table, Value};
+
+fn string(text: &str) -> Value {
+    Value::String(String::from(text))
+}
+
+// the value of key in a one-line document
+fn value(line: &str) -> Value {
+    parse(line).unwrap().remove("key").unwrap()
+}
+
+fn table(value: &Value) -> &Table {
+    match value {
+        Value::Table(table) => table,
+        other => panic!("{} is not a table", other.type_name()),
+    }
+}
+
+#[test]
+fn strings_and_escapes() {
+    assert_eq!(value(r#"key = "a\tb\nc\r\"d\"\\""#), string("a\tb\nc\r\"d\"\\"));
+    // literal strings take backslashes as they are
+    assert_eq!(value(r"key = 'c:\roms\basic.bin'"), string(r"c:\roms\basic.bin"));
+    assert_eq!(value(r##"key = "# not a comment""##), string("# not a comment"));
+    assert_eq!(value("key = \"\""), string(""));
+    assert_eq!(value("key = \"caf\u{e9}\""), string("caf\u{e9}"));
+}
+
+#[test]
+fn numbers() {
+    let cases = [
+        ("42", Value::Integer(42)),
+        ("-42", Value::Integer(-42)),
+        ("+7", Value::Integer(7)),
+        ("1_000_000", Value::Integer(1_000_000)),
+        ("0xfffc", Value::Integer(0xfffc)),
+        ("0xFF", Value::Integer(0xff)),
+        ("-0x10", Value::Integer(-0x10)),
+        ("0o755", Value::Integer(0o755)),
+        ("0b1010_0101", Value::Integer(0xa5)),
+        ("9223372036854775807", Value::Integer(i64::MAX)),
+        ("1.5", Value::Float(1.5)),
+        ("-0.25", Value::Float(-0.25)),
+        ("1e6", Value::Float(1e6)),
+        ("1.79e6", Value::Float(1.79e6)),
+        ("6.02E-3", Value::Float(6.02e-3)),
+        ("true", Value::Boolean(true)),
+        ("false", Value::Boolean(false)),
+    ];
+    for (text, expected) in cases {
+        assert_eq!(value(&format!("key = {}", text)), expected, "{}", text);
+    }
+}
+
+#[test]
+fn nested_tables_and_arrays() {
+    let document = parse(r#"
+        # a machine
+        name = "test"   # trailing comment
+
+        [cpu]
+        variant = "65c02"
+
+        [cpu.clock]
+        hz = 1_000_000
+
+        [[devices]]
+        type = "acia"
+        base = 0xd000
+        options = { connect = "tcp:6551", input_newline = "crlf" }
+
+        [[devices]]
+        type = "via"
+        base = 0x6000
+        ports = [[1, 2], [], ["a", 'b'],]
+        "quoted key".inner = 1
+    "#).unwrap();
+
+    assert_eq!(document["name"], string("test"));
+    let cpu = table(&document["cpu"]);
+    assert_eq!(cpu["variant"], string("65c02"));
+    assert_eq!(table(&cpu["clock"])["hz"], Value::Integer(1_000_000));
+
+    let devices = match &document["devices"] {
+        Value::Array(devices) => devices,
+        other => panic!("devices is {}", other.type_name()),
+    };
+    assert_eq!(devices.len(), 2);
+    let acia = table(&devices[0]);
+    assert_eq!(acia["base"], Value::Integer(0xd000));
+    assert_eq!(table(&acia["options"])["input_newline"], string("crlf"));
+    let via = table(&devices[1]);
+    assert_eq!(via["ports"], Value::Array(vec![
+        Value::Array(vec![Value::Integer(1), Value::Integer(2)]),
+        Value::Array(vec![]),
+        Value::Array(vec![string("a"), string("b")]),
+    ]));
+    assert_eq!(table(&via["quoted key"])["inner"], Value::Integer(1));
+}
+
+#[test]
+fn arrays_may_span_lines() {
+    assert_eq!(value("key = [\n  1, # one\n  2,\n]"), Value::Array(vec![Value::Integer(1), Value::Integer(2)]));
+}
+
+#[test]
+fn malformed_documents_are_errors() {
+    let cases = [
+        ("key = \"open", "line 1: unterminated string"),
+        ("key = \"split\nline\"", "unterminated string"),
+        ("key = 'open", "unterminated string"),
+        (r#"key = "\q""#, r"unknown escape '\q'"),
+        ("key = 12abc", "bad value '12abc'"),
+        ("key = 0xfg", "bad value '0xfg'"),
+        ("key = 0x1.5", "bad value '0x1.5'"),
+        ("key = 99999999999999999999999e", "bad value"),
+        ("key = 1 2", "unexpected '2' after value"),
+        ("key = ", "expected a value"),
+        ("key = @", "bad value ''"),
+        ("key", "expected '='"),
+        ("= 1", "expected a key"),
+        ("key = [1, 2", "expected ',' or ']' in array"),
+        ("key = [1 2]", "expected ',' or ']' in array"),
+        ("key = { a = 1", "expected ',' or '}' in inline table"),
+        ("key = { a = 1, a = 2 }", "key 'a' defined twice"),
+        ("a = 1\na = 2", "line 2: key 'a' defined twice"),
+        ("[t]\n[t]", "table 't' defined twice"),
+        ("t = 1\n[t.u]", "'t' is not a table"),
+        ("t = 1\n[[t]]", "'t' is not an array of tables"),
+        ("[t", "expected ']'"),
+        ("[[t]", "expected ']' at end of input"),
+    ];
+    for (text, message) in cases {
+        match parse(text) {
+            Ok(document) => panic!("{:?} parsed as {:?}", text, document),
+            Err(error) => assert!(error.contains(message), "{:?}: {}", text, error),
+        }
+    }
+}
+
+#[test]
+fn errors_give_the_line() {
+    let error = parse("a = 1\n\n# comment\nb = \"open\n").unwrap_err();
+    assert!(error.starts_with("line 4: "), "{}", error);
+}
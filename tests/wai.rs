@@ -0,0 +1,59 @@
+// the 65c02's WAI: an interrupt that arrives while it waits returns
+// past it, one that arrives before it has run returns to it, and an irq
+// while interrupts are disabled ends the wait without the vector
+//
+//   cargo test --test wai
+
+mod common;
+
+use common::{Machine, CODE, HANDLER};
+use e6502r::cpu::{irq, nmi, CpuVariant};
+
+const I: u8 = 0x04;
+
+const WAI: u8 = 0xcb;
+
+// the return address an interrupt pushed
+fn returns_to(machine: &Machine) -> u16 {
+    let lo = machine.mem.mem[0x01fe] as u16;
+    let hi = machine.mem.mem[0x01ff] as u16;
+    (hi << 8) | lo
+}
+
+#[test]
+fn an_interrupt_while_waiting_returns_past_the_wai() {
+    let mut machine = Machine::new(CpuVariant::Cmos65c02, &[WAI]);
+    machine.cpu.set_status(0x00);
+    machine.step();
+    assert_eq!(machine.cpu.pc, CODE);
+    assert!(irq(&mut machine.cpu, &mut machine.mem));
+    assert_eq!(machine.cpu.pc, HANDLER);
+    assert_eq!(returns_to(&machine), CODE + 1);
+
+    let mut machine = Machine::new(CpuVariant::Cmos65c02, &[WAI]);
+    machine.step();
+    nmi(&mut machine.cpu, &mut machine.mem);
+    assert_eq!(returns_to(&machine), CODE + 1);
+}
+
+#[test]
+fn an_interrupt_before_the_wai_returns_to_it() {
+    let mut machine = Machine::new(CpuVariant::Cmos65c02, &[WAI]);
+    machine.cpu.set_status(0x00);
+    assert!(irq(&mut machine.cpu, &mut machine.mem));
+    assert_eq!(returns_to(&machine), CODE);
+
+    let mut machine = Machine::new(CpuVariant::Cmos65c02, &[WAI]);
+    nmi(&mut machine.cpu, &mut machine.mem);
+    assert_eq!(returns_to(&machine), CODE);
+}
+
+#[test]
+fn a_masked_irq_ends_the_wait_without_the_vector() {
+    let mut machine = Machine::new(CpuVariant::Cmos65c02, &[WAI]);
+    machine.cpu.set_status(I);
+    machine.step();
+    assert!(!irq(&mut machine.cpu, &mut machine.mem));
+    assert_eq!(machine.cpu.pc, CODE + 1);
+    assert_eq!(machine.cpu.sp, 0xff);
+}
@@ -0,0 +1,48 @@
+// the websocket handshake: its SHA-1 and base64 against the standard
+// test vectors, and the accept key against rfc 6455's example
+//
+//   cargo test --test websocket
+
+use e6502r::websocket::{base64, base64_decode, handshake_response, sha1};
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[test]
+fn handshake_accepts_the_rfc_6455_key() {
+    let response = handshake_response("dGhlIHNhbXBsZSBub25jZQ==");
+    assert!(response.starts_with("HTTP/1.1 101"), "{}", response);
+    assert!(response.contains("Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n"), "{}", response);
+}
+
+#[test]
+fn sha1_matches_the_standard_vectors() {
+    assert_eq!(hex(&sha1(b"")), "da39a3ee5e6b4b0d3255bfef95601890afd80709");
+    assert_eq!(hex(&sha1(b"abc")), "a9993e364706816aba3e25717850c26c9cd0d89d");
+    // 56 bytes, so the length needs a block of its own
+    assert_eq!(hex(&sha1(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq")),
+        "84983e441c3bd26ebaae4aa1f95129e5e54670f1");
+    assert_eq!(hex(&sha1(&[b'a'; 1000])), "291e9a6c66994949b57ba5e650361e98fc36b1ba");
+}
+
+#[test]
+fn base64_matches_the_rfc_4648_vectors() {
+    let vectors = [("", ""), ("f", "Zg=="), ("fo", "Zm8="), ("foo", "Zm9v"),
+                   ("foob", "Zm9vYg=="), ("fooba", "Zm9vYmE="), ("foobar", "Zm9vYmFy")];
+    for (text, encoded) in vectors {
+        assert_eq!(base64(text.as_bytes()), encoded);
+        assert_eq!(base64_decode(encoded).as_deref(), Some(text.as_bytes()), "{}", encoded);
+    }
+}
+
+#[test]
+fn base64_round_trips_every_byte() {
+    let bytes: Vec<u8> = (0..=255).collect();
+    assert_eq!(base64_decode(&base64(&bytes)), Some(bytes));
+}
+
+#[test]
+fn bad_base64_is_refused() {
+    assert_eq!(base64_decode("Zm9v!"), None);
+}
@@ -0,0 +1,221 @@
+// the emulator for the browser, with javascript bindings
+//
+// build with: wasm-pack build --target web wasm
+//
+// javascript makes a Machine, loads a program into it and calls
+// run_frame from an animation frame, which runs the cycles one frame
+// takes at the clock set with set_clock, or run_for with a count of its
+// own. keys go to an attached keyboard device with key, and an attached
+// framebuffer gives its picture as rgba bytes for a canvas with frame.
+// index.html is a small playground that does all of that.
+//
+// nothing here waits for input or writes to a terminal: there is no
+// monitor, the keyboard is fed by key instead of the stdin thread,
+// and the framebuffer is kept offscreen.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use wasm_bindgen::prelude::*;
+
+use e6502r::cpu::{reset_cpu, Cpu, CpuVariant};
+use e6502r::devices::framebuffer::{default_palette, Framebuffer};
+use e6502r::devices::keyboard::{Keyboard, KEYBOARD_SIZE};
+use e6502r::memory::{Device, Memory, StateReader, StateWriter, MEMSIZE};
+use e6502r::run::{run_for, run_frame, step, FrameClock, StopConditions};
+
+#[wasm_bindgen]
+pub struct Machine {
+    cpu: Cpu,
+    mem: Memory,
+    framebuffer: Option<Rc<RefCell<Framebuffer>>>,
+    stopped: Option<String>,
+    clock: FrameClock,
+}
+
+// unimplemented opcodes and loops to themselves stop the machine
+const STOPS: StopConditions = StopConditions { trap_on_brk: false, strict_opcodes: true, stop_at: Vec::new(), smc: None };
+
+#[wasm_bindgen]
+impl Machine {
+    // cpu is "6502", "65c02" or "2a03"
+    #[wasm_bindgen(constructor)]
+    pub fn new(cpu: &str) -> Result<Machine, JsError> {
+        let mut machine = Machine {
+            cpu: Cpu::new(),
+            mem: Memory::new(),
+            framebuffer: None,
+            stopped: None,
+            clock: FrameClock::default(),
+        };
+        machine.cpu.variant = cpu.parse::<CpuVariant>().map_err(|e| JsError::new(&e))?;
+        Ok(machine)
+    }
+
+    // copy a program or rom image into memory at addr
+    pub fn load(&mut self, addr: u16, bytes: &[u8]) -> Result<(), JsError> {
+        let start = addr as usize;
+        if start + bytes.len() > MEMSIZE {
+            return Err(JsError::new(&format!("{} bytes at ${:04x} don't fit in memory", bytes.len(), addr)));
+        }
+        self.mem.mem[start..start + bytes.len()].copy_from_slice(bytes);
+        Ok(())
+    }
+
+    // start from the reset vector
+    pub fn reset(&mut self) {
+        reset_cpu(&mut self.cpu, &self.mem);
+        self.stopped = None;
+        self.clock.restart(self.cpu.cycles);
+    }
+
+    // the cpu's clock and the frames a second run_frame runs, e.g.
+    // 1789773 and 60.0988 for an ntsc nes, 1 MHz at 60 until set;
+    // frames are counted from here
+    #[wasm_bindgen(js_name = setClock)]
+    pub fn set_clock(&mut self, clock_hz: f64, frame_rate: f64) -> Result<(), JsError> {
+        self.clock = FrameClock::new(clock_hz, frame_rate, self.cpu.cycles).map_err(|e| JsError::new(&e))?;
+        Ok(())
+    }
+
+    // an apple ii style keyboard at addr, as --device keyboard@ADDR
+    #[wasm_bindgen(js_name = attachKeyboard)]
+    pub fn attach_keyboard(&mut self, addr: u16, uppercase: bool) -> Result<(), JsError> {
+        self.mem.attach(addr, KEYBOARD_SIZE, Box::new(Keyboard::new(self.mem.inputs.clone(), uppercase))).map_err(|e| JsError::new(&e))
+    }
+
+    // a width by height framebuffer at addr of depth bits a pixel, in the
+    // default palette for the depth
+    #[wasm_bindgen(js_name = attachFramebuffer)]
+    pub fn attach_framebuffer(&mut self, addr: u16, width: usize, height: usize, depth: u32) -> Result<(), JsError> {
+        if ![1, 2, 4, 8].contains(&depth) {
+            return Err(JsError::new(&format!("depth {} should be 1, 2, 4 or 8", depth)));
+        }
+        let framebuffer = Framebuffer::offscreen(width, height, depth, default_palette(depth));
+        let size = u16::try_from(framebuffer.size())
+            .map_err(|_| JsError::new(&format!("a {}x{} framebuffer is too big", width, height)))?;
+        let framebuffer = Rc::new(RefCell::new(framebuffer));
+        self.mem.attach(addr, size, Box::new(Shared(framebuffer.clone()))).map_err(|e| JsError::new(&e))?;
+        self.framebuffer = Some(framebuffer);
+        Ok(())
+    }
+
+    // a key for the keyboard device, as an ascii code
+    pub fn key(&mut self, key: u8) {
+        self.mem.inputs.push_key(key);
+    }
+
+    // the framebuffer as width*height*4 rgba bytes if it changed since
+    // last asked, for putImageData
+    pub fn frame(&mut self) -> Option<Vec<u8>> {
+        self.framebuffer.as_ref()?.borrow_mut().take_frame()
+    }
+
+    // run one instruction; false once the machine has stopped
+    pub fn step(&mut self) -> bool {
+        if self.stopped.is_some() {
+            return false;
+        }
+        self.stopped = step(&mut self.cpu, &mut self.mem, &STOPS).map(|stop| stop.to_string());
+        self.stopped.is_none()
+    }
+
+    // run for a budget of cpu cycles or until the machine stops; returns
+    // the cycles left over, negative when the last instruction ran past
+    // the budget, for adding to the next one
+    #[wasm_bindgen(js_name = runFor)]
+    pub fn run_for(&mut self, cycles: i32) -> i32 {
+        if self.stopped.is_some() {
+            return cycles;
+        }
+        let block = run_for(&mut self.cpu, &mut self.mem, &STOPS, cycles as i64);
+        self.stopped = block.stop.map(|stop| stop.to_string());
+        block.leftover as i32
+    }
+
+    // run one frame's cycles at the clock, or until the machine stops;
+    // returns the instructions run. an instruction that runs past the
+    // end of a frame comes off the next
+    #[wasm_bindgen(js_name = runFrame)]
+    pub fn run_frame(&mut self) -> u32 {
+        if self.stopped.is_some() {
+            return 0;
+        }
+        let block = run_frame(&mut self.cpu, &mut self.mem, &STOPS, &mut self.clock);
+        self.stopped = block.stop.map(|stop| stop.to_string());
+        block.executed as u32
+    }
+
+    // the cycles a frame takes at the clock, on average
+    #[wasm_bindgen(js_name = frameCycles)]
+    pub fn frame_cycles(&self) -> f64 {
+        self.clock.frame_cycles()
+    }
+
+    // why the machine stopped, if it has
+    pub fn stopped(&self) -> Option<String> {
+        self.stopped.clone()
+    }
+
+    pub fn peek(&self, addr: u16) -> u8 {
+        self.mem.mem[addr as usize]
+    }
+
+    pub fn poke(&mut self, addr: u16, value: u8) {
+        self.mem.write(addr, value);
+    }
+
+    pub fn pc(&self) -> u16 {
+        self.cpu.pc
+    }
+
+    pub fn a(&self) -> u8 {
+        self.cpu.ac
+    }
+
+    pub fn x(&self) -> u8 {
+        self.cpu.xr
+    }
+
+    pub fn y(&self) -> u8 {
+        self.cpu.yr
+    }
+
+    pub fn sp(&self) -> u8 {
+        self.cpu.sp
+    }
+
+    pub fn p(&self) -> u8 {
+        self.cpu.status()
+    }
+
+    // a javascript number holds a cycle count exactly up to 2^53
+    pub fn cycles(&self) -> f64 {
+        self.cpu.cycles as f64
+    }
+}
+
+// a device the machine keeps hold of as well as attaching it
+struct Shared<D: Device>(Rc<RefCell<D>>);
+
+impl<D: Device> Device for Shared<D> {
+    fn read(&mut self, offset: u16) -> u8 {
+        self.0.borrow_mut().read(offset)
+    }
+
+    fn write(&mut self, offset: u16, value: u8) {
+        self.0.borrow_mut().write(offset, value)
+    }
+
+    fn tick(&mut self, cycles: u32) {
+        self.0.borrow_mut().tick(cycles)
+    }
+
+    fn save_state(&self, out: &mut StateWriter) {
+        self.0.borrow().save_state(out)
+    }
+
+    fn load_state(&mut self, input: &mut StateReader) -> Result<(), String> {
+        self.0.borrow_mut().load_state(input)
+    }
+}